@@ -0,0 +1,15 @@
+use std::process::Command;
+
+fn main() {
+    // Record the compiler version for `otter export-logs`
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    
+    println!("cargo:rustc-env=OTTER_RUSTC_VERSION={}", version);
+}
@@ -0,0 +1,189 @@
+//! # Crypto Benchmark
+//!
+//! Measures end-to-end encryption throughput and latency on the local machine
+//! by encrypting on one session and decrypting on its peer in a tight loop.
+
+use anyhow::Result;
+use otter_crypto::{CryptoSession, KdfAlgorithm, PFSSession};
+use otter_identity::{Identity, PublicIdentity};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+use x25519_dalek::PublicKey as X25519PublicKey;
+
+/// Warm-up time before measurements start
+pub const WARMUP: Duration = Duration::from_secs(1);
+
+/// Benchmark results
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkResult {
+    /// Session type that was measured
+    pub session: &'static str,
+    
+    /// Payload size in bytes
+    pub message_size: usize,
+    
+    /// Messages encrypted and decrypted during the measurement
+    pub messages: u64,
+    
+    /// Measured wall-clock time in seconds
+    pub elapsed_secs: f64,
+    
+    /// Plaintext throughput in MB/s
+    pub throughput_mb_s: f64,
+    
+    /// Messages per second
+    pub messages_per_sec: f64,
+    
+    /// Median encrypt+decrypt latency in microseconds
+    pub latency_p50_us: u64,
+    
+    /// 99th percentile encrypt+decrypt latency in microseconds
+    pub latency_p99_us: u64,
+}
+
+/// A sender/receiver session pair
+enum SessionPair {
+    Static(Box<(CryptoSession, CryptoSession)>),
+    Pfs(Box<(PFSSession, PFSSession)>),
+}
+
+impl SessionPair {
+    fn new(pfs: bool) -> Result<Self> {
+        let alice = Identity::generate()?;
+        let bob = Identity::generate()?;
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        
+        if !pfs {
+            return Ok(SessionPair::Static(Box::new((
+                CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default())?,
+                CryptoSession::new(&bob, &alice_public, KdfAlgorithm::default())?,
+            ))));
+        }
+        
+        let alice_ephemeral = PFSSession::generate_ephemeral();
+        let bob_ephemeral = PFSSession::generate_ephemeral();
+        let alice_attestation = alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral))?;
+        let bob_attestation = bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral))?;
+        
+        Ok(SessionPair::Pfs(Box::new((
+            PFSSession::new(&alice, &bob_public, alice_ephemeral, &bob_attestation, true)?,
+            PFSSession::new(&bob, &alice_public, bob_ephemeral, &alice_attestation, false)?,
+        ))))
+    }
+    
+    fn roundtrip(&mut self, payload: &[u8]) -> Result<()> {
+        let decrypted = match self {
+            SessionPair::Static(pair) => {
+                let (sender, receiver) = &mut **pair;
+                let encrypted = sender.encrypt(payload, None)?;
+                receiver.decrypt(&encrypted)?
+            }
+            SessionPair::Pfs(pair) => {
+                let (sender, receiver) = &mut **pair;
+                let encrypted = sender.encrypt(payload, None)?;
+                receiver.decrypt(&encrypted)?
+            }
+        };
+        
+        if decrypted.len() != payload.len() {
+            anyhow::bail!("Decrypted payload has wrong length");
+        }
+        
+        Ok(())
+    }
+}
+
+/// Run the benchmark
+pub fn run_benchmark(
+    duration: Duration,
+    warmup: Duration,
+    message_size: usize,
+    pfs: bool,
+) -> Result<BenchmarkResult> {
+    let mut sessions = SessionPair::new(pfs)?;
+    let payload = vec![0x5Au8; message_size];
+    
+    let warmup_start = Instant::now();
+    while warmup_start.elapsed() < warmup {
+        sessions.roundtrip(&payload)?;
+    }
+    
+    let mut latencies_us: Vec<u64> = Vec::new();
+    let start = Instant::now();
+    
+    while start.elapsed() < duration || latencies_us.is_empty() {
+        let message_start = Instant::now();
+        sessions.roundtrip(&payload)?;
+        latencies_us.push(message_start.elapsed().as_micros() as u64);
+    }
+    
+    let elapsed_secs = start.elapsed().as_secs_f64();
+    let messages = latencies_us.len() as u64;
+    latencies_us.sort_unstable();
+    
+    Ok(BenchmarkResult {
+        session: if pfs { "PFSSession" } else { "CryptoSession" },
+        message_size,
+        messages,
+        elapsed_secs,
+        throughput_mb_s: (messages as f64 * message_size as f64) / elapsed_secs / 1_000_000.0,
+        messages_per_sec: messages as f64 / elapsed_secs,
+        latency_p50_us: percentile(&latencies_us, 50),
+        latency_p99_us: percentile(&latencies_us, 99),
+    })
+}
+
+/// Percentile of an ascending-sorted slice
+fn percentile(sorted: &[u64], pct: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let index = (sorted.len() * pct).div_ceil(100).saturating_sub(1);
+    sorted[index.min(sorted.len() - 1)]
+}
+
+/// Print results as a human-readable table
+pub fn print_table(result: &BenchmarkResult) {
+    println!("Otter crypto benchmark");
+    println!("======================");
+    println!("{:<18} {}", "Session", result.session);
+    println!("{:<18} {} KB", "Message size", result.message_size / 1024);
+    println!("{:<18} {}", "Messages", result.messages);
+    println!("{:<18} {:.2} s", "Duration", result.elapsed_secs);
+    println!("{:<18} {:.2} MB/s", "Throughput", result.throughput_mb_s);
+    println!("{:<18} {:.0}", "Messages/sec", result.messages_per_sec);
+    println!("{:<18} {} µs", "Latency p50", result.latency_p50_us);
+    println!("{:<18} {} µs", "Latency p99", result.latency_p99_us);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_benchmark_reports_throughput() {
+        for pfs in [false, true] {
+            let result = run_benchmark(
+                Duration::from_millis(100),
+                Duration::from_millis(10),
+                1024,
+                pfs,
+            )
+            .unwrap();
+            
+            assert!(result.messages > 0);
+            assert!(result.throughput_mb_s > 0.0);
+            assert!(result.messages_per_sec > 0.0);
+            assert!(result.latency_p50_us <= result.latency_p99_us);
+        }
+    }
+    
+    #[test]
+    fn test_percentile() {
+        let values: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&values, 50), 50);
+        assert_eq!(percentile(&values, 99), 99);
+        assert_eq!(percentile(&[7], 99), 7);
+    }
+}
@@ -0,0 +1,379 @@
+//! # Setup Diagnostics
+//!
+//! Runs a checklist of common setup problems and explains each failure with a
+//! suggested fix. With `--fix`, problems that can be repaired locally (a
+//! missing data directory, a missing or corrupt identity) are repaired.
+
+use otter_identity::Identity;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// Public STUN server used to check outbound UDP
+pub const DEFAULT_STUN_SERVER: &str = "stun.l.google.com:19302";
+
+/// How long to wait for the STUN server to answer
+pub const STUN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// mDNS multicast group used for LAN discovery
+const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+
+/// STUN Binding Request message type
+const STUN_BINDING_REQUEST: u16 = 0x0001;
+
+/// STUN Binding Success Response message type
+const STUN_BINDING_SUCCESS: u16 = 0x0101;
+
+/// STUN magic cookie (RFC 5389)
+const STUN_MAGIC_COOKIE: u32 = 0x2112_A442;
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckStatus::Ok => write!(f, "[ OK ]"),
+            CheckStatus::Warn => write!(f, "[WARN]"),
+            CheckStatus::Fail => write!(f, "[FAIL]"),
+        }
+    }
+}
+
+/// Result of a check, with an explanation and a suggested fix when it did not pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+impl CheckResult {
+    fn ok(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Ok,
+            message: message.into(),
+            fix: None,
+        }
+    }
+    
+    fn warn(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+    
+    fn fail(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// Check that the data directory exists and is writable, creating it with `fix`
+pub fn check_data_dir(data_dir: &Path, fix: bool) -> CheckResult {
+    const NAME: &str = "Data directory";
+    
+    if !data_dir.exists() {
+        if !fix {
+            return CheckResult::warn(
+                NAME,
+                format!("{} does not exist yet", data_dir.display()),
+                "Run `otter diagnose --fix` or start otter once to create it",
+            );
+        }
+        if let Err(e) = fs::create_dir_all(data_dir) {
+            return CheckResult::fail(
+                NAME,
+                format!("Cannot create {}: {}", data_dir.display(), e),
+                "Check the permissions of the parent directory or pass --data-dir",
+            );
+        }
+        return CheckResult::ok(NAME, format!("Created {}", data_dir.display()));
+    }
+    
+    if !data_dir.is_dir() {
+        return CheckResult::fail(
+            NAME,
+            format!("{} is a file, not a directory", data_dir.display()),
+            "Move the file out of the way or pass --data-dir",
+        );
+    }
+    
+    let probe = data_dir.join(".diagnose-write-test");
+    match fs::write(&probe, b"") {
+        Ok(()) => {
+            let _ = fs::remove_file(&probe);
+            CheckResult::ok(NAME, format!("{} is writable", data_dir.display()))
+        }
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("Cannot write to {}: {}", data_dir.display(), e),
+            format!("Make {} writable by your user", data_dir.display()),
+        ),
+    }
+}
+
+/// Check that `identity.json` exists and parses, generating a new one with `fix`
+///
+/// An identity in the OS credential store is reported without being loaded.
+///
+/// A corrupt identity is kept as `identity.json.bak` before it is replaced.
+pub fn check_identity(data_dir: &Path, fix: bool) -> CheckResult {
+    const NAME: &str = "Identity";
+    let identity_path = data_dir.join("identity.json");
+    
+    // Secure storage entries need no file checks; loading them may prompt the user
+    if let Ok(label) = fs::read_to_string(data_dir.join(crate::KEYCHAIN_LABEL_FILE)) {
+        return CheckResult::ok(NAME, format!("Identity kept in the OS credential store as {}", label.trim()));
+    }
+    
+    let problem = match fs::read_to_string(&identity_path) {
+        Ok(json) => match Identity::from_json(&json) {
+            Ok(identity) => return CheckResult::ok(NAME, format!("Loaded identity {}", identity.peer_id())),
+            Err(e) => CheckResult::fail(
+                NAME,
+                format!("{} is not a valid identity: {}", identity_path.display(), e),
+                "Restore it from a backup, or run `otter diagnose --fix` to generate a new one (your peer ID will change)",
+            ),
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => CheckResult::warn(
+            NAME,
+            format!("{} does not exist yet", identity_path.display()),
+            "Run `otter diagnose --fix` or start otter once to generate one",
+        ),
+        Err(e) => {
+            return CheckResult::fail(
+                NAME,
+                format!("Cannot read {}: {}", identity_path.display(), e),
+                format!("Make {} readable by your user", identity_path.display()),
+            )
+        }
+    };
+    
+    if !fix || !data_dir.is_dir() {
+        return problem;
+    }
+    
+    let regenerate = || -> Result<Identity, Box<dyn std::error::Error>> {
+        if identity_path.exists() {
+            fs::rename(&identity_path, data_dir.join("identity.json.bak"))?;
+        }
+        let identity = Identity::generate()?;
+        fs::write(&identity_path, identity.to_json()?)?;
+        Ok(identity)
+    };
+    match regenerate() {
+        Ok(identity) => CheckResult::ok(NAME, format!("Generated new identity {}", identity.peer_id())),
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("Cannot write a new identity: {}", e),
+            format!("Make {} writable by your user", data_dir.display()),
+        ),
+    }
+}
+
+/// Check that a STUN server answers a Binding Request over UDP
+pub async fn check_stun(server: &str, timeout: Duration) -> CheckResult {
+    const NAME: &str = "STUN server";
+    let fix = "Allow outbound UDP in your firewall; without it voice calls need a TURN relay";
+    
+    let address = match tokio::net::lookup_host(server).await.map(|mut a| a.find(SocketAddr::is_ipv4)) {
+        Ok(Some(address)) => address,
+        Ok(None) | Err(_) => {
+            return CheckResult::warn(NAME, format!("Cannot resolve {}", server), "Check your internet connection and DNS settings");
+        }
+    };
+    
+    match tokio::time::timeout(timeout, stun_ping(address)).await {
+        Ok(Ok(())) => CheckResult::ok(NAME, format!("{} answered over UDP", server)),
+        Ok(Err(e)) => CheckResult::warn(NAME, format!("UDP to {} failed: {}", server, e), fix),
+        Err(_) => CheckResult::warn(NAME, format!("{} did not answer within {:?}", server, timeout), fix),
+    }
+}
+
+/// Send one Binding Request to `server` and wait for the matching success response
+async fn stun_ping(server: SocketAddr) -> std::io::Result<()> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let transaction_id = &nanos.to_le_bytes()[..12];
+    
+    let mut request = Vec::with_capacity(20);
+    request.extend_from_slice(&STUN_BINDING_REQUEST.to_be_bytes());
+    request.extend_from_slice(&0u16.to_be_bytes());
+    request.extend_from_slice(&STUN_MAGIC_COOKIE.to_be_bytes());
+    request.extend_from_slice(transaction_id);
+    socket.send_to(&request, server).await?;
+    
+    let mut buf = [0u8; 1500];
+    loop {
+        let (len, from) = socket.recv_from(&mut buf).await?;
+        if from == server
+            && len >= 20
+            && buf[..2] == STUN_BINDING_SUCCESS.to_be_bytes()
+            && &buf[8..20] == transaction_id
+        {
+            return Ok(());
+        }
+    }
+}
+
+/// Check that the listening port can be bound (`0` picks a random port)
+pub async fn check_port(port: u16) -> CheckResult {
+    const NAME: &str = "Listening port";
+    
+    match TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)).await {
+        Ok(listener) => {
+            let bound = listener.local_addr().map(|a| a.port()).unwrap_or(port);
+            CheckResult::ok(NAME, format!("TCP port {} is available", bound))
+        }
+        Err(e) => CheckResult::fail(
+            NAME,
+            format!("Cannot listen on TCP port {}: {}", port, e),
+            "Stop the program using the port or pass a different --port",
+        ),
+    }
+}
+
+/// Check that a UDP socket can join the mDNS multicast group
+pub async fn check_mdns() -> CheckResult {
+    const NAME: &str = "mDNS multicast";
+    let fix = "Peers on the local network will not be found automatically; connect by address, or allow multicast";
+    
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(e) => return CheckResult::warn(NAME, format!("Cannot open a UDP socket: {}", e), fix),
+    };
+    match socket.join_multicast_v4(MDNS_GROUP, Ipv4Addr::UNSPECIFIED) {
+        Ok(()) => CheckResult::ok(NAME, format!("Joined multicast group {}", MDNS_GROUP)),
+        Err(e) => CheckResult::warn(NAME, format!("Cannot join multicast group {}: {}", MDNS_GROUP, e), fix),
+    }
+}
+
+/// Run every check in order
+pub async fn run_checks(data_dir: &Path, port: u16, stun_server: &str, fix: bool) -> Vec<CheckResult> {
+    vec![
+        check_data_dir(data_dir, fix),
+        check_identity(data_dir, fix),
+        check_stun(stun_server, STUN_TIMEOUT).await,
+        check_port(port).await,
+        check_mdns().await,
+    ]
+}
+
+/// Print one line per check, with the suggested fix indented below problems
+pub fn print_report<W: Write>(out: &mut W, results: &[CheckResult]) -> std::io::Result<()> {
+    for result in results {
+        writeln!(out, "{} {}: {}", result.status, result.name, result.message)?;
+        if let Some(fix) = &result.fix {
+            writeln!(out, "       Fix: {}", fix)?;
+        }
+    }
+    out.flush()
+}
+
+/// Process exit code: 0 if every check passed, 1 if any warned, 2 if any failed
+pub fn exit_code(results: &[CheckResult]) -> i32 {
+    match results.iter().map(|r| r.status).max() {
+        Some(CheckStatus::Fail) => 2,
+        Some(CheckStatus::Warn) => 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    #[test]
+    fn test_data_dir_and_identity_checks() {
+        let temp = TempDir::new().unwrap();
+        let data_dir = temp.path().join("otter");
+        
+        assert_eq!(check_data_dir(&data_dir, false).status, CheckStatus::Warn);
+        assert_eq!(check_identity(&data_dir, false).status, CheckStatus::Warn);
+        assert_eq!(check_data_dir(&data_dir, true).status, CheckStatus::Ok);
+        assert!(data_dir.is_dir());
+        
+        fs::write(data_dir.join("identity.json"), "{ not json").unwrap();
+        let corrupt = check_identity(&data_dir, false);
+        assert_eq!(corrupt.status, CheckStatus::Fail);
+        assert!(corrupt.fix.unwrap().contains("--fix"));
+        
+        assert_eq!(check_identity(&data_dir, true).status, CheckStatus::Ok);
+        assert_eq!(fs::read_to_string(data_dir.join("identity.json.bak")).unwrap(), "{ not json");
+        assert_eq!(check_identity(&data_dir, false).status, CheckStatus::Ok);
+        
+        let file = temp.path().join("file");
+        fs::write(&file, "").unwrap();
+        assert_eq!(check_data_dir(&file, true).status, CheckStatus::Fail);
+    }
+    
+    #[tokio::test]
+    async fn test_network_checks() {
+        // A local server that answers every Binding Request with an empty success response
+        let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1500];
+            while let Ok((len, from)) = server.recv_from(&mut buf).await {
+                if len >= 20 {
+                    let mut response = buf[..20].to_vec();
+                    response[..2].copy_from_slice(&STUN_BINDING_SUCCESS.to_be_bytes());
+                    let _ = server.send_to(&response, from).await;
+                }
+            }
+        });
+        assert_eq!(check_stun(&server_addr.to_string(), STUN_TIMEOUT).await.status, CheckStatus::Ok);
+        
+        // Nobody answers on a port that was just released
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap().local_addr().unwrap();
+        let result = check_stun(&silent.to_string(), Duration::from_millis(200)).await;
+        assert_eq!(result.status, CheckStatus::Warn);
+        assert!(result.fix.is_some());
+        
+        let taken = TcpListener::bind("0.0.0.0:0").await.unwrap();
+        let port = taken.local_addr().unwrap().port();
+        assert_eq!(check_port(port).await.status, CheckStatus::Fail);
+        drop(taken);
+        assert_eq!(check_port(0).await.status, CheckStatus::Ok);
+    }
+    
+    #[test]
+    fn test_report_format_and_exit_code() {
+        let results = vec![
+            CheckResult::ok("Data directory", "/tmp/otter is writable"),
+            CheckResult::warn("mDNS multicast", "Cannot join multicast group", "Connect by address"),
+        ];
+        let mut out = Vec::new();
+        print_report(&mut out, &results).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "[ OK ] Data directory: /tmp/otter is writable\n\
+             [WARN] mDNS multicast: Cannot join multicast group\n       Fix: Connect by address\n"
+        );
+        assert_eq!(exit_code(&results), 1);
+        assert_eq!(exit_code(&results[..1]), 0);
+        
+        let failed = [CheckResult::fail("Listening port", "In use", "Pick another port"), results[1].clone()];
+        assert_eq!(exit_code(&failed), 2);
+    }
+}
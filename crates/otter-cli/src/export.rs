@@ -0,0 +1,228 @@
+//! # Log Export
+//!
+//! Bundles recent logs and system information into a zip file for bug reports.
+//!
+//! Hex strings longer than 16 characters are redacted from log lines since they
+//! may be key material. Private keys are never included.
+
+use chrono::{DateTime, Duration, Utc};
+use otter_identity::Identity;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use thiserror::Error;
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+/// Longest hex run that is kept verbatim in exported logs
+const MAX_HEX_RUN: usize = 16;
+
+#[derive(Error, Debug)]
+pub enum ExportError {
+    #[error("Log directory not found")]
+    LogDirectoryNotFound,
+    #[error("Failed to write zip archive: {0}")]
+    ZipFailed(String),
+    #[error("Invalid identity: {0}")]
+    InvalidIdentity(String),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+}
+
+impl From<zip::result::ZipError> for ExportError {
+    fn from(e: zip::result::ZipError) -> Self {
+        ExportError::ZipFailed(e.to_string())
+    }
+}
+
+/// Export a bug report bundle to `output`
+///
+/// Returns the names of the files written to the archive.
+pub fn export_logs(
+    data_dir: &Path,
+    output: &Path,
+    last_hours: u32,
+    include_identity: bool,
+) -> Result<Vec<String>, ExportError> {
+    let log_dir = data_dir.join("logs");
+    if !log_dir.is_dir() {
+        return Err(ExportError::LogDirectoryNotFound);
+    }
+    
+    let cutoff = Utc::now() - Duration::hours(last_hours as i64);
+    
+    let mut entries: Vec<(String, String)> = Vec::new();
+    
+    let mut log_files: Vec<_> = fs::read_dir(&log_dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().and_then(|s| s.to_str()) == Some("log"))
+        .collect();
+    log_files.sort();
+    
+    for path in log_files {
+        let contents = fs::read_to_string(&path)?;
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        entries.push((format!("logs/{}", name), filter_log(&contents, cutoff)));
+    }
+    
+    // Status snapshot written by a running peer, if any
+    let status_path = data_dir.join("status.json");
+    if status_path.exists() {
+        entries.push(("status.json".to_string(), fs::read_to_string(&status_path)?));
+    }
+    
+    entries.push(("system_info.txt".to_string(), system_info()));
+    
+    if include_identity {
+        let json = fs::read_to_string(data_dir.join("identity.json"))?;
+        let identity = Identity::from_json(&json)
+            .map_err(|e| ExportError::InvalidIdentity(e.to_string()))?;
+        entries.push(("identity.txt".to_string(), format!("Peer ID: {}\n", identity.peer_id())));
+    }
+    
+    let file = fs::File::create(output)?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default();
+    
+    for (name, contents) in &entries {
+        zip.start_file(name.as_str(), options)?;
+        zip.write_all(contents.as_bytes())
+            .map_err(|e| ExportError::ZipFailed(e.to_string()))?;
+    }
+    
+    zip.finish()?;
+    
+    Ok(entries.into_iter().map(|(name, _)| name).collect())
+}
+
+/// Keep lines logged after `cutoff` and redact potential key material
+///
+/// Lines without a leading timestamp (e.g. wrapped output) follow the line before them.
+fn filter_log(contents: &str, cutoff: DateTime<Utc>) -> String {
+    let mut output = String::new();
+    let mut keep = false;
+    
+    for line in contents.lines() {
+        if let Some(timestamp) = line
+            .split_whitespace()
+            .next()
+            .and_then(|token| DateTime::parse_from_rfc3339(token).ok())
+        {
+            keep = timestamp.with_timezone(&Utc) >= cutoff;
+        }
+        
+        if keep {
+            output.push_str(&redact_hex(line));
+            output.push('\n');
+        }
+    }
+    
+    output
+}
+
+/// Replace every hex run longer than 16 characters with `[REDACTED]`
+pub fn redact_hex(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut run = String::new();
+    
+    for c in line.chars() {
+        if c.is_ascii_hexdigit() {
+            run.push(c);
+            continue;
+        }
+        flush_run(&mut output, &mut run);
+        output.push(c);
+    }
+    flush_run(&mut output, &mut run);
+    
+    output
+}
+
+fn flush_run(output: &mut String, run: &mut String) {
+    if run.len() > MAX_HEX_RUN {
+        output.push_str("[REDACTED]");
+    } else {
+        output.push_str(run);
+    }
+    run.clear();
+}
+
+/// Describe the platform and build
+fn system_info() -> String {
+    format!(
+        "Otter version: {}\nOS: {}\nArch: {}\nRust: {}\nExported at: {}\n",
+        env!("CARGO_PKG_VERSION"),
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        env!("OTTER_RUSTC_VERSION"),
+        Utc::now().to_rfc3339(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use tempfile::TempDir;
+    
+    fn has_long_hex_run(text: &str) -> bool {
+        text.split(|c: char| !c.is_ascii_hexdigit())
+            .any(|run| run.len() > MAX_HEX_RUN)
+    }
+    
+    #[test]
+    fn test_redact_hex() {
+        let key = "a".repeat(64);
+        assert_eq!(redact_hex(&format!("key={} ok", key)), "key=[REDACTED] ok");
+        assert_eq!(redact_hex("short deadbeef"), "short deadbeef");
+    }
+    
+    #[test]
+    fn test_export_bundle_contents() {
+        let temp = TempDir::new().unwrap();
+        let data_dir = temp.path();
+        fs::create_dir_all(data_dir.join("logs")).unwrap();
+        
+        let identity = Identity::generate().unwrap();
+        fs::write(data_dir.join("identity.json"), identity.to_json().unwrap()).unwrap();
+        
+        let old = (Utc::now() - Duration::hours(48)).to_rfc3339();
+        let recent = Utc::now().to_rfc3339();
+        let log = format!(
+            "{} INFO old line\n{} INFO session key {}\n  continuation {}\n",
+            old,
+            recent,
+            "0123456789abcdef".repeat(4),
+            "f".repeat(32),
+        );
+        fs::write(data_dir.join("logs").join("otter.log"), log).unwrap();
+        fs::write(data_dir.join("logs").join("notes.txt"), "ignored").unwrap();
+        
+        let output = data_dir.join("bundle.zip");
+        export_logs(data_dir, &output, 24, true).unwrap();
+        
+        let mut archive = zip::ZipArchive::new(fs::File::open(&output).unwrap()).unwrap();
+        let mut names: Vec<String> = archive.file_names().map(String::from).collect();
+        names.sort();
+        assert_eq!(names, vec!["identity.txt", "logs/otter.log", "system_info.txt"]);
+        
+        for i in 0..archive.len() {
+            let mut contents = String::new();
+            archive.by_index(i).unwrap().read_to_string(&mut contents).unwrap();
+            assert!(!has_long_hex_run(&contents), "unredacted hex in {}", contents);
+        }
+        
+        let mut log = String::new();
+        archive.by_name("logs/otter.log").unwrap().read_to_string(&mut log).unwrap();
+        assert!(!log.contains("old line"));
+        assert!(log.contains("session key [REDACTED]"));
+        assert!(log.contains("continuation [REDACTED]"));
+    }
+    
+    #[test]
+    fn test_missing_log_directory() {
+        let temp = TempDir::new().unwrap();
+        let result = export_logs(temp.path(), &temp.path().join("out.zip"), 24, false);
+        assert!(matches!(result, Err(ExportError::LogDirectoryNotFound)));
+    }
+}
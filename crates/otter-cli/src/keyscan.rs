@@ -0,0 +1,345 @@
+//! # Key Scan
+//!
+//! Connects to a peer, exchanges identity announcements and shows both emoji
+//! fingerprints side by side so the user can compare them out-of-band before
+//! the peer is pinned in the trust store.
+//!
+//! `verify` uses the same exchange to show the session's safety words, which
+//! both users read aloud to each other.
+
+use anyhow::{Context, Result};
+use otter_crypto::{CryptoSession, KdfAlgorithm};
+use otter_identity::{
+    trust::{emoji_fingerprint, TrustStore},
+    Identity, Mnemonic, PeerId, PublicIdentity,
+};
+use otter_messaging::Message;
+use otter_network::{create_network_channels, AcceptAll, MessagePriority, Network, NetworkCommand, NetworkEvent};
+use std::{io::Write, time::Duration};
+use tracing::{debug, warn};
+
+/// Question asked after the fingerprints are shown
+pub const MATCH_PROMPT: &str = "Do these fingerprints match? [y/N]";
+
+/// Question asked after the safety words are shown
+pub const WORDS_PROMPT: &str = "Did your peer read out the same words? [y/N]";
+
+/// How long to wait for an answer before treating it as "no"
+pub const PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Exit code when the identity's peer ID is not derived from its key
+pub const EXIT_NOT_DERIVED: i32 = 1;
+
+/// Exit code when the user does not confirm the fingerprints
+pub const EXIT_REJECTED: i32 = 2;
+
+/// Obtains a peer's public identity in exchange for ours
+pub trait IdentityExchange {
+    /// Send `local` to `peer_id` and return the identity it announces
+    async fn exchange(&mut self, local: &PublicIdentity, peer_id: &PeerId) -> Result<PublicIdentity>;
+}
+
+/// Exchange identities over a fresh libp2p network, discovering the peer via mDNS or the DHT
+pub struct NetworkExchange {
+    /// Give up if the peer has not announced itself by then
+    pub timeout: Duration,
+}
+
+impl IdentityExchange for NetworkExchange {
+    async fn exchange(&mut self, local: &PublicIdentity, peer_id: &PeerId) -> Result<PublicIdentity> {
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll))?;
+        network.listen("/ip4/0.0.0.0/tcp/0")?;
+        let network_handle = tokio::spawn(network.run());
+        
+        let announcement = Message::identity(local.clone()).to_bytes()?;
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        
+        let result = loop {
+            let event = match tokio::time::timeout_at(deadline, event_rx.recv()).await {
+                Ok(Some(event)) => event,
+                Ok(None) => break Err(anyhow::anyhow!("Network stopped before {} answered", peer_id)),
+                Err(_) => break Err(anyhow::anyhow!("Timed out waiting for {} to announce itself", peer_id)),
+            };
+            
+            match event {
+                NetworkEvent::PeerDiscovered { peer_id: discovered, .. } => {
+                    command_tx.send(NetworkCommand::FindPeer { peer_id: discovered, response: None }).await?;
+                }
+                NetworkEvent::PeerReadyForMessages { peer_id: ready } => {
+                    command_tx.send(NetworkCommand::SendMessage {
+                        to: ready,
+                        data: announcement.clone(),
+                        priority: MessagePriority::Interactive,
+                    }).await?;
+                }
+                NetworkEvent::MessageReceived { data, .. } => {
+                    match Message::from_bytes(&data) {
+                        Ok(Message::Identity { public_identity, .. }) if public_identity.peer_id() == peer_id => {
+                            break Ok(public_identity);
+                        }
+                        Ok(_) => {}
+                        Err(e) => debug!("Ignoring undecodable message: {}", e),
+                    }
+                }
+                _ => {}
+            }
+        };
+        
+        if let Err(e) = Network::shutdown(&command_tx, 200).await {
+            warn!("Failed to request network shutdown: {}", e);
+        }
+        drop(command_tx);
+        let _ = tokio::time::timeout(Duration::from_secs(2), network_handle).await;
+        
+        result
+    }
+}
+
+/// Check that `remote` claims `expected` and that this peer ID is derived from its signing key
+pub fn peer_id_matches_key(remote: &PublicIdentity, expected: &PeerId) -> bool {
+    match remote.verifying_key() {
+        Ok(key) => remote.peer_id() == expected && PeerId::from_public_key(&key) == *expected,
+        Err(_) => false,
+    }
+}
+
+/// Print both fingerprints side by side, followed by the confirmation prompt
+pub fn print_comparison<W: Write>(
+    out: &mut W,
+    local: &PublicIdentity,
+    remote: &PublicIdentity,
+) -> std::io::Result<()> {
+    writeln!(out, "{:<10} {}", "Local:", emoji_fingerprint(local))?;
+    writeln!(out, "{:<10} {}", "Remote:", emoji_fingerprint(remote))?;
+    writeln!(out, "{:<10} {}", "Peer ID:", remote.peer_id())?;
+    write!(out, "{} ", MATCH_PROMPT)?;
+    out.flush()
+}
+
+/// Read one line from stdin, giving up after `timeout`
+pub fn read_answer(timeout: Duration) -> Option<String> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        let mut line = String::new();
+        if std::io::stdin().read_line(&mut line).is_ok() {
+            let _ = tx.send(line);
+        }
+    });
+    
+    rx.recv_timeout(timeout).ok()
+}
+
+/// Scan a peer's key and return the process exit code
+///
+/// `answer` is called once the prompt has been printed and returns `None` on
+/// timeout. With `non_interactive`, no prompt is shown and the result only
+/// reflects whether the peer ID is derived from the announced key.
+pub async fn keyscan<E: IdentityExchange, W: Write>(
+    exchange: &mut E,
+    local: &PublicIdentity,
+    peer_id: &PeerId,
+    trust_store: &mut TrustStore,
+    non_interactive: bool,
+    out: &mut W,
+    answer: impl FnOnce() -> Option<String>,
+) -> Result<i32> {
+    let remote = exchange
+        .exchange(local, peer_id)
+        .await
+        .context("Identity exchange failed")?;
+    
+    if !peer_id_matches_key(&remote, peer_id) {
+        writeln!(out, "⚠ Peer ID {} is not derived from the key it announced", peer_id)?;
+        return Ok(EXIT_NOT_DERIVED);
+    }
+    
+    if non_interactive {
+        return Ok(0);
+    }
+    
+    print_comparison(out, local, &remote)?;
+    
+    match answer() {
+        Some(reply) if reply.trim().eq_ignore_ascii_case("y") => {
+            trust_store.pin(remote)?;
+            writeln!(out, "✓ Pinned {}", peer_id)?;
+            Ok(0)
+        }
+        Some(_) => {
+            writeln!(out, "⚠ Fingerprints not confirmed; {} was not pinned", peer_id)?;
+            Ok(EXIT_REJECTED)
+        }
+        None => {
+            writeln!(out)?;
+            writeln!(out, "⚠ No answer received; {} was not pinned", peer_id)?;
+            Ok(EXIT_REJECTED)
+        }
+    }
+}
+
+/// Show the safety words of a session with `peer_id` and return the process exit code
+///
+/// Both peers derive the words from their shared secret, so they only match
+/// if nobody substituted a key in between.
+pub async fn verify<E: IdentityExchange, W: Write>(
+    exchange: &mut E,
+    identity: &Identity,
+    peer_id: &PeerId,
+    out: &mut W,
+    answer: impl FnOnce() -> Option<String>,
+) -> Result<i32> {
+    let local = PublicIdentity::from_identity(identity);
+    let remote = exchange
+        .exchange(&local, peer_id)
+        .await
+        .context("Identity exchange failed")?;
+    
+    if !peer_id_matches_key(&remote, peer_id) {
+        writeln!(out, "⚠ Peer ID {} is not derived from the key it announced", peer_id)?;
+        return Ok(EXIT_NOT_DERIVED);
+    }
+    
+    let session = CryptoSession::new(identity, &remote, KdfAlgorithm::default())?;
+    let words = Mnemonic::from_fingerprint(&session.safety_number());
+    
+    writeln!(out, "Safety words for your session with {}:", peer_id)?;
+    for line in words.split(' ').collect::<Vec<_>>().chunks(12) {
+        writeln!(out, "  {}", line.join(" "))?;
+    }
+    writeln!(out, "Read them aloud to each other.")?;
+    write!(out, "{} ", WORDS_PROMPT)?;
+    out.flush()?;
+    
+    match answer() {
+        Some(reply) if reply.trim().eq_ignore_ascii_case("y") => {
+            writeln!(out, "✓ Session with {} verified", peer_id)?;
+            Ok(0)
+        }
+        Some(_) => {
+            writeln!(out, "⚠ Safety words not confirmed; the connection to {} may be intercepted", peer_id)?;
+            Ok(EXIT_REJECTED)
+        }
+        None => {
+            writeln!(out)?;
+            writeln!(out, "⚠ No answer received; {} was not verified", peer_id)?;
+            Ok(EXIT_REJECTED)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use otter_identity::trust::TrustLevel;
+    
+    /// Returns a fixed identity instead of talking to the network
+    struct MockExchange {
+        remote: PublicIdentity,
+        received: Option<PublicIdentity>,
+    }
+    
+    impl IdentityExchange for MockExchange {
+        async fn exchange(&mut self, local: &PublicIdentity, _peer_id: &PeerId) -> Result<PublicIdentity> {
+            self.received = Some(local.clone());
+            Ok(self.remote.clone())
+        }
+    }
+    
+    fn setup() -> (PublicIdentity, PublicIdentity, MockExchange) {
+        let local = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let remote = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let exchange = MockExchange { remote: remote.clone(), received: None };
+        (local, remote, exchange)
+    }
+    
+    #[tokio::test]
+    async fn test_confirmed_fingerprint_pins_peer() {
+        let (local, remote, mut exchange) = setup();
+        let mut store = TrustStore::new();
+        let mut out = Vec::new();
+        
+        let code = keyscan(&mut exchange, &local, remote.peer_id(), &mut store, false, &mut out, || {
+            Some("y\n".to_string())
+        }).await.unwrap();
+        
+        assert_eq!(code, 0);
+        assert_eq!(exchange.received.unwrap().peer_id(), local.peer_id());
+        
+        let output = String::from_utf8(out).unwrap();
+        assert!(output.contains(MATCH_PROMPT));
+        assert!(output.contains(&emoji_fingerprint(&local)));
+        assert!(output.contains(&emoji_fingerprint(&remote)));
+        assert_eq!(store.get(remote.peer_id()).unwrap().trust_level, TrustLevel::Verified);
+    }
+    
+    #[tokio::test]
+    async fn test_rejected_or_timed_out_prompt_does_not_pin() {
+        for answer in [Some("N\n".to_string()), Some("\n".to_string()), None] {
+            let (local, remote, mut exchange) = setup();
+            let mut store = TrustStore::new();
+            let mut out = Vec::new();
+            
+            let code = keyscan(&mut exchange, &local, remote.peer_id(), &mut store, false, &mut out, || {
+                answer
+            }).await.unwrap();
+            
+            assert_eq!(code, EXIT_REJECTED);
+            assert!(store.get(remote.peer_id()).is_none());
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_non_interactive_checks_peer_id_derivation() {
+        let (local, remote, mut exchange) = setup();
+        let mut store = TrustStore::new();
+        let mut out = Vec::new();
+        
+        let code = keyscan(&mut exchange, &local, remote.peer_id(), &mut store, true, &mut out, || {
+            panic!("non-interactive mode must not prompt")
+        }).await.unwrap();
+        assert_eq!(code, 0);
+        assert!(!String::from_utf8(out).unwrap().contains(MATCH_PROMPT));
+        
+        // An identity claiming a peer ID that isn't derived from its key
+        let mut json: serde_json::Value = serde_json::to_value(&remote).unwrap();
+        json["peer_id"] = serde_json::Value::String(local.peer_id().to_string());
+        exchange.remote = serde_json::from_value(json).unwrap();
+        
+        let code = keyscan(&mut exchange, &local, local.peer_id(), &mut store, true, &mut Vec::new(), || None)
+            .await
+            .unwrap();
+        assert_eq!(code, EXIT_NOT_DERIVED);
+        assert!(store.get(local.peer_id()).is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_verify_shows_same_words_on_both_sides() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        
+        let mut alice_out = Vec::new();
+        let mut exchange = MockExchange { remote: bob_public.clone(), received: None };
+        let code = verify(&mut exchange, &alice, bob_public.peer_id(), &mut alice_out, || Some("y\n".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(code, 0);
+        
+        let mut bob_out = Vec::new();
+        let mut exchange = MockExchange { remote: alice_public.clone(), received: None };
+        let code = verify(&mut exchange, &bob, alice_public.peer_id(), &mut bob_out, || Some("n\n".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(code, EXIT_REJECTED);
+        
+        // The word lines are identical, only the peer IDs around them differ
+        let words = |out: Vec<u8>| -> Vec<String> {
+            String::from_utf8(out).unwrap().lines().filter(|l| l.starts_with("  ")).map(str::to_string).collect()
+        };
+        let alice_words = words(alice_out);
+        assert_eq!(alice_words.len(), 2);
+        assert_eq!(alice_words, words(bob_out));
+    }
+}
@@ -4,23 +4,37 @@
 //!
 //! A minimal CLI peer client for interacting with the Otter network.
 
+mod benchmark;
+mod diagnose;
+mod export;
+mod keyscan;
+mod metrics;
+mod relay;
+
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
-use dialoguer::{theme::ColorfulTheme, Input, Select};
-use otter_identity::{Identity, PublicIdentity};
+use dialoguer::{theme::ColorfulTheme, Input, Password, Select};
+use otter_identity::{trust::TrustStore, Identity, MfaGate, PeerId, PublicIdentity, SecureIdentityStorage};
 use otter_messaging::{Message, MessageHandler};
-use otter_network::{create_network_channels, Network, NetworkCommand, NetworkEvent};
-use otter_protocol::SignalingMessage;
-use otter_voice::{CallState, VoiceManager};
+use otter_network::{create_network_channels, AcceptAll, AddressBookEntry, ConnectionPriority, MessagePriority, MetricsExporter, Network, NetworkCommand, NetworkEvent, PeerAddressBook};
+use otter_protocol::{ChangelogEntry, SignalingMessage, PROTOCOL_VERSION};
+use otter_storage::{AddressBookData, AddressBookRecord, FileStorage, Storage};
+use otter_voice::{CallState, NetworkInterfaceMonitor, VoiceError, VoiceManager};
 use std::{
     fs,
-    path::PathBuf,
+    net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
 use tokio::sync::{mpsc, Mutex};
 use tracing::{debug, error, info, warn};
-use tracing_subscriber;
+
+/// Minimum width in pixels of generated identity QR codes
+const QR_CODE_WIDTH: u32 = 400;
+
+/// File in the data directory naming the identity's entry in the OS credential store
+const KEYCHAIN_LABEL_FILE: &str = "identity.keychain";
 
 #[derive(Parser)]
 #[command(name = "otter")]
@@ -69,6 +83,164 @@ enum Commands {
         #[arg(short, long, default_value = "identity.json")]
         identity: PathBuf,
     },
+    
+    /// Bundle recent logs and system info into a zip for bug reports
+    ExportLogs {
+        /// Path of the zip file to create
+        #[arg(short, long, default_value = "otter-logs.zip")]
+        output: PathBuf,
+        
+        /// Only include log lines from the last N hours
+        #[arg(long, default_value = "24")]
+        last_hours: u32,
+        
+        /// Include this peer's ID (never private keys)
+        #[arg(long)]
+        include_identity: bool,
+    },
+    
+    /// Measure end-to-end encryption throughput and latency
+    Benchmark {
+        /// Measurement duration in seconds (after a 1 second warm-up)
+        #[arg(long, default_value = "5")]
+        duration_secs: u32,
+        
+        /// Payload size in KB
+        #[arg(long, default_value = "1")]
+        message_size_kb: u32,
+        
+        /// Benchmark PFSSession instead of CryptoSession
+        #[arg(long)]
+        pfs: bool,
+        
+        /// Print results as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    
+    /// Show protocol changes and migration steps
+    Changelog {
+        /// Only show changes after this protocol version
+        #[arg(long)]
+        from_version: Option<u32>,
+    },
+    
+    /// Verify a peer's key fingerprint and pin it in the trust store
+    Keyscan {
+        /// Otter peer ID to verify
+        peer_id: String,
+        
+        /// Skip the prompt; exit 0 if the peer ID is derived from the announced key, 1 otherwise
+        #[arg(long)]
+        non_interactive: bool,
+        
+        /// Seconds to wait for the peer to announce its identity
+        #[arg(long, default_value = "30")]
+        timeout_secs: u64,
+    },
+    
+    /// Show safety words for the session with a peer, to compare by reading them aloud
+    Verify {
+        /// Otter peer ID to verify
+        peer_id: String,
+        
+        /// Seconds to wait for the peer to announce its identity
+        #[arg(long, default_value = "30")]
+        timeout_secs: u64,
+    },
+    
+    /// Write this identity as a QR code PNG for others to scan
+    QrCode {
+        /// Path to identity file
+        #[arg(short, long, default_value = "identity.json")]
+        identity: PathBuf,
+        
+        /// Path of the PNG file to create
+        #[arg(short, long, default_value = "identity.png")]
+        output: PathBuf,
+    },
+    
+    /// Require a code from an authenticator app before this identity signs
+    EnableMfa {
+        /// Path to identity file
+        #[arg(short, long, default_value = "identity.json")]
+        identity: PathBuf,
+    },
+    
+    /// Write a passphrase-encrypted backup of the trust store
+    BackupTrust {
+        /// Path of the zip file to create
+        #[arg(short, long, default_value = "trust-backup.zip")]
+        output: PathBuf,
+    },
+    
+    /// Replace the trust store with a backup made by backup-trust
+    RestoreTrust {
+        /// Path of the backup zip file
+        #[arg(short, long)]
+        input: PathBuf,
+    },
+    
+    /// Join the network briefly and print this node's gossipsub mesh
+    Topology {
+        /// Seconds to spend discovering peers before printing
+        #[arg(long, default_value = "10")]
+        discovery_secs: u64,
+    },
+    
+    /// Set the label or notes of a network peer in the address book
+    Annotate {
+        /// libp2p peer ID of the peer
+        peer_id: String,
+        
+        /// Short name shown instead of the peer ID (empty to clear)
+        #[arg(long)]
+        label: Option<String>,
+        
+        /// Free-form notes (empty to clear)
+        #[arg(long)]
+        notes: Option<String>,
+    },
+    
+    /// List the peers in the address book
+    AddressBook,
+    
+    /// Check for common setup problems and suggest fixes
+    Diagnose {
+        /// Repair what can be repaired locally (data directory, identity)
+        #[arg(long)]
+        fix: bool,
+    },
+    
+    /// Run a network node and serve its counters for Prometheus at /metrics
+    MetricsServer {
+        /// HTTP port of the metrics endpoint
+        #[arg(long, default_value = "9464")]
+        port: u16,
+        
+        /// Address to bind the metrics endpoint to
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: IpAddr,
+    },
+    
+    /// Act as a TURN relay for peers behind restrictive NATs
+    Relay {
+        /// UDP port of the TURN listener
+        #[arg(long, default_value = "3478")]
+        port: u16,
+        
+        /// Maximum number of simultaneous allocations
+        #[arg(long, default_value = "100")]
+        max_allocations: usize,
+        
+        /// Secret shared with the peers' credential issuer
+        #[arg(long)]
+        shared_secret: String,
+        
+        /// Address announced for relay ports (default: the outbound interface)
+        #[arg(long)]
+        public_ip: Option<IpAddr>,
+    },
 }
 
 #[tokio::main]
@@ -94,6 +266,84 @@ async fn main() -> Result<()> {
         Some(Commands::Info { identity }) => {
             show_info(identity)?;
         }
+        Some(Commands::ExportLogs { output, last_hours, include_identity }) => {
+            let data_dir = resolve_data_dir(cli.data_dir)?;
+            let files = export::export_logs(&data_dir, &output, last_hours, include_identity)?;
+            println!("✓ Exported {} files to {}", files.len(), output.display());
+            for file in files {
+                println!("  {}", file);
+            }
+        }
+        Some(Commands::Benchmark { duration_secs, message_size_kb, pfs, json }) => {
+            let result = benchmark::run_benchmark(
+                Duration::from_secs(duration_secs as u64),
+                benchmark::WARMUP,
+                message_size_kb as usize * 1024,
+                pfs,
+            )?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&result)?);
+            } else {
+                benchmark::print_table(&result);
+            }
+        }
+        Some(Commands::Changelog { from_version }) => {
+            show_changelog(from_version);
+        }
+        Some(Commands::Keyscan { peer_id, non_interactive, timeout_secs }) => {
+            let data_dir = resolve_data_dir(cli.data_dir)?;
+            let code = run_keyscan(&data_dir, PeerId::from_string(peer_id), non_interactive, timeout_secs).await?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Some(Commands::Verify { peer_id, timeout_secs }) => {
+            let data_dir = resolve_data_dir(cli.data_dir)?;
+            let code = run_verify(&data_dir, PeerId::from_string(peer_id), timeout_secs).await?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
+        Some(Commands::QrCode { identity, output }) => {
+            write_qr_code(identity, output)?;
+        }
+        Some(Commands::EnableMfa { identity }) => {
+            enable_mfa(identity)?;
+        }
+        Some(Commands::BackupTrust { output }) => {
+            let data_dir = resolve_data_dir(cli.data_dir)?;
+            backup_trust(&data_dir, &output).await?;
+        }
+        Some(Commands::RestoreTrust { input }) => {
+            let data_dir = resolve_data_dir(cli.data_dir)?;
+            restore_trust(&data_dir, &input).await?;
+        }
+        Some(Commands::Topology { discovery_secs }) => {
+            show_topology(Duration::from_secs(discovery_secs)).await?;
+        }
+        Some(Commands::MetricsServer { port, bind }) => {
+            run_metrics_server(cli.port.unwrap_or(0), SocketAddr::new(bind, port)).await?;
+        }
+        Some(Commands::Annotate { peer_id, label, notes }) => {
+            let data_dir = resolve_data_dir(cli.data_dir)?;
+            annotate_peer(&data_dir, &peer_id, label.as_deref(), notes.as_deref()).await?;
+        }
+        Some(Commands::AddressBook) => {
+            let data_dir = resolve_data_dir(cli.data_dir)?;
+            show_address_book(&data_dir).await?;
+        }
+        Some(Commands::Relay { port, max_allocations, shared_secret, public_ip }) => {
+            run_relay(port, max_allocations, shared_secret, public_ip).await?;
+        }
+        Some(Commands::Diagnose { fix }) => {
+            let data_dir = resolve_data_dir(cli.data_dir)?;
+            let results = diagnose::run_checks(&data_dir, cli.port.unwrap_or(0), diagnose::DEFAULT_STUN_SERVER, fix).await;
+            diagnose::print_report(&mut std::io::stdout(), &results)?;
+            let code = diagnose::exit_code(&results);
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
         None => {
             // Default mode: Auto-setup and start
             run_simple_mode(cli.nickname, cli.port, cli.data_dir).await?;
@@ -141,17 +391,399 @@ fn show_info(path: PathBuf) -> Result<()> {
     Ok(())
 }
 
-/// Run in simple mode with auto-setup
-async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir: Option<PathBuf>) -> Result<()> {
-    // Determine data directory
-    let data_dir = match data_dir {
-        Some(dir) => dir,
+/// Save the public part of an identity as a QR code PNG
+fn write_qr_code(identity_path: PathBuf, output: PathBuf) -> Result<()> {
+    let json = fs::read_to_string(&identity_path)
+        .context("Failed to read identity file")?;
+    let identity = Identity::from_json(&json)?;
+    
+    let png = PublicIdentity::from_identity(&identity).to_qr_code(QR_CODE_WIDTH)?;
+    fs::write(&output, png).context("Failed to write QR code")?;
+    
+    println!("✓ QR code for {} saved to {}", identity.peer_id(), output.display());
+    Ok(())
+}
+
+/// Set up a TOTP gate on an identity file
+///
+/// The QR code is shown for the authenticator app. Once a code from the app
+/// has been accepted, the identity moves into the OS credential store, the
+/// only place its TOTP secret is kept, and the plain file is replaced by the
+/// credential store label.
+fn enable_mfa(identity_path: PathBuf) -> Result<()> {
+    let json = fs::read_to_string(&identity_path)
+        .context("Failed to read identity file")?;
+    let mut identity = Identity::from_json(&json)?;
+    if identity.mfa_gate().is_some() {
+        anyhow::bail!("MFA is already enabled for {}", identity.peer_id());
+    }
+    
+    identity.enable_mfa(MfaGate::generate_secret())?;
+    let gate = identity.mfa_gate().context("MFA gate missing after enabling")?;
+    let account = identity.peer_id().to_string();
+    println!("Scan this code with your authenticator app:\n");
+    println!("{}", gate.provisioning_qr_code(&account)?);
+    println!("Or enter this URI manually:\n  {}\n", gate.provisioning_uri(&account));
+    
+    let code: String = Input::with_theme(&ColorfulTheme::default())
+        .with_prompt("Code shown by the app")
+        .interact_text()?;
+    if !gate.verify_code(&code, chrono::Utc::now().timestamp().max(0) as u64)? {
+        anyhow::bail!("Code not accepted; MFA was not enabled");
+    }
+    
+    let label = SecureIdentityStorage::label(&identity);
+    SecureIdentityStorage::save(&identity, &label)
+        .context("MFA needs the OS credential store to keep its secret")?;
+    fs::write(identity_path.with_file_name(KEYCHAIN_LABEL_FILE), &label)?;
+    fs::remove_file(&identity_path).context("Failed to remove the plain identity file")?;
+    println!("✓ MFA enabled for {}", identity.peer_id());
+    println!("  The identity moved to the OS credential store as: {}", label);
+    println!("  Signing with this identity now requires a code from the app.");
+    Ok(())
+}
+
+/// Encrypt the trust store into a backup file
+async fn backup_trust(data_dir: &Path, output: &Path) -> Result<()> {
+    let storage = FileStorage::new(data_dir)?;
+    let trust_store = storage.load_trust_store().await?.unwrap_or_default();
+    
+    let passphrase = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt("Backup passphrase")
+        .with_confirmation("Repeat passphrase", "Passphrases do not match")
+        .interact()?;
+    trust_store.backup(output, &passphrase)?;
+    
+    println!(
+        "✓ Trust store with {} verified peers backed up to {}",
+        trust_store.verified_peers().len(),
+        output.display()
+    );
+    Ok(())
+}
+
+/// Decrypt a backup file and save it as the trust store
+async fn restore_trust(data_dir: &Path, input: &Path) -> Result<()> {
+    let passphrase = Password::with_theme(&ColorfulTheme::default())
+        .with_prompt("Backup passphrase")
+        .interact()?;
+    let trust_store = TrustStore::restore(input, &passphrase)?;
+    
+    FileStorage::new(data_dir)?.save_trust_store(&trust_store).await?;
+    println!(
+        "✓ Restored trust store with {} verified peers from {}",
+        trust_store.verified_peers().len(),
+        input.display()
+    );
+    Ok(())
+}
+
+/// Discover peers for `discovery` and print the mesh of each topic as an adjacency list
+async fn show_topology(discovery: Duration) -> Result<()> {
+    let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+    let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll))?;
+    let local_peer_id = network.local_peer_id();
+    network.listen("/ip4/0.0.0.0/tcp/0")?;
+    let network_handle = tokio::spawn(network.run());
+    
+    println!("🔍 Discovering peers for {} seconds...", discovery.as_secs());
+    let deadline = tokio::time::Instant::now() + discovery;
+    while let Ok(Some(event)) = tokio::time::timeout_at(deadline, event_rx.recv()).await {
+        if let NetworkEvent::PeerDiscovered { peer_id, .. } = event {
+            command_tx.send(NetworkCommand::FindPeer { peer_id, response: None }).await?;
+        }
+    }
+    
+    let (response, topology) = tokio::sync::oneshot::channel();
+    command_tx.send(NetworkCommand::GetMeshTopology { response }).await?;
+    let mut topology: Vec<_> = topology.await?.into_iter().collect();
+    topology.sort();
+    
+    for (topic, peers) in topology {
+        println!("\n{}", topic);
+        if peers.is_empty() {
+            println!("  {} -> (no mesh peers)", local_peer_id);
+        } else {
+            let peers: Vec<String> = peers.iter().map(|peer| peer.to_string()).collect();
+            println!("  {} -> {}", local_peer_id, peers.join(", "));
+        }
+    }
+    
+    Network::shutdown(&command_tx, 200).await?;
+    drop(command_tx);
+    let _ = tokio::time::timeout(Duration::from_secs(2), network_handle).await;
+    Ok(())
+}
+
+/// Run a network node on `p2p_port` and serve its metrics on `metrics_addr` until Ctrl+C
+async fn run_metrics_server(p2p_port: u16, metrics_addr: SocketAddr) -> Result<()> {
+    let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+    let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll))?;
+    let exporter = Arc::new(MetricsExporter::new());
+    network.set_metrics_exporter(exporter.clone());
+    network.listen(&format!("/ip4/0.0.0.0/tcp/{}", p2p_port))?;
+    let network_handle = tokio::spawn(network.run());
+    
+    // Nobody reads the events; drain them so the network never blocks on a full channel
+    tokio::spawn(async move {
+        while let Some(event) = event_rx.recv().await {
+            debug!("Network event: {:?}", event);
+        }
+    });
+    
+    let shutdown = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    metrics::serve(exporter, metrics_addr, shutdown).await?;
+    
+    Network::shutdown(&command_tx, 200).await?;
+    drop(command_tx);
+    let _ = tokio::time::timeout(Duration::from_secs(2), network_handle).await;
+    Ok(())
+}
+
+/// Serve as a TURN relay until Ctrl+C
+async fn run_relay(port: u16, max_allocations: usize, shared_secret: String, public_ip: Option<IpAddr>) -> Result<()> {
+    let public_ip = match public_ip {
+        Some(ip) => ip,
+        None => outbound_ip().context("Could not determine the public address; pass --public-ip")?,
+    };
+    
+    let config = relay::RelayConfig::new(port, public_ip, max_allocations, shared_secret);
+    let server = relay::RelayServer::new(config).await?;
+    println!("📡 TURN relay on {} (relay ports announced at {})", server.local_addr(), public_ip);
+    println!("   Press Ctrl+C to stop");
+    
+    tokio::select! {
+        _ = server.run() => {}
+        _ = tokio::signal::ctrl_c() => {}
+    }
+    
+    let stats = server.stats();
+    server.close().await?;
+    println!("✓ Relay stopped after {}s, {} bytes relayed", stats.uptime_secs, stats.bytes_relayed);
+    Ok(())
+}
+
+/// Address of the interface that routes to the internet
+///
+/// Connecting a UDP socket only picks a route; no packet is sent.
+fn outbound_ip() -> std::io::Result<IpAddr> {
+    let socket = std::net::UdpSocket::bind("0.0.0.0:0")?;
+    socket.connect("8.8.8.8:53")?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Load the stored address book, skipping entries whose peer ID does not parse
+async fn load_address_book(storage: &FileStorage) -> Result<PeerAddressBook> {
+    let data = storage.load_address_book().await?;
+    let mut address_book = PeerAddressBook::default();
+    for record in data.entries.into_values() {
+        let Ok(peer_id) = record.peer_id.parse::<libp2p::PeerId>() else {
+            warn!("Skipping address book entry with invalid peer ID {}", record.peer_id);
+            continue;
+        };
+        address_book.entries.insert(peer_id, AddressBookEntry {
+            peer_id,
+            label: record.label,
+            notes: record.notes,
+            pinned: record.pinned,
+            tags: record.tags,
+        });
+    }
+    Ok(address_book)
+}
+
+/// Save `address_book`, replacing the stored one
+async fn save_address_book(storage: &FileStorage, address_book: &PeerAddressBook) -> Result<()> {
+    let entries = address_book
+        .entries
+        .values()
+        .map(|entry| {
+            let record = AddressBookRecord {
+                peer_id: entry.peer_id.to_base58(),
+                label: entry.label.clone(),
+                notes: entry.notes.clone(),
+                pinned: entry.pinned,
+                tags: entry.tags.clone(),
+            };
+            (record.peer_id.clone(), record)
+        })
+        .collect();
+    storage.save_address_book(&AddressBookData { entries }).await?;
+    Ok(())
+}
+
+/// Update the address book entry of `peer_id`
+async fn annotate_peer(data_dir: &Path, peer_id: &str, label: Option<&str>, notes: Option<&str>) -> Result<()> {
+    let peer_id: libp2p::PeerId = peer_id.parse().context("Invalid libp2p peer ID")?;
+    if label.is_none() && notes.is_none() {
+        anyhow::bail!("Nothing to change: pass --label and/or --notes");
+    }
+    
+    let storage = FileStorage::new(data_dir)?;
+    let mut address_book = load_address_book(&storage).await?;
+    address_book.annotate(&peer_id, label, notes);
+    save_address_book(&storage, &address_book).await?;
+    
+    println!("✓ Updated {} in the address book", peer_id);
+    Ok(())
+}
+
+/// Print every address book entry, pinned peers first
+async fn show_address_book(data_dir: &Path) -> Result<()> {
+    let address_book = load_address_book(&FileStorage::new(data_dir)?).await?;
+    if address_book.is_empty() {
+        println!("The address book is empty. Add a peer with 'otter annotate <peer-id> --label <name>'.");
+        return Ok(());
+    }
+    
+    for entry in address_book.sorted_entries() {
+        let pin = if entry.pinned { "📌 " } else { "" };
+        println!("{}{}", pin, entry.label.as_deref().unwrap_or("(no label)"));
+        println!("  Peer ID: {}", entry.peer_id);
+        if !entry.tags.is_empty() {
+            println!("  Tags: {}", entry.tags.join(", "));
+        }
+        if let Some(ref notes) = entry.notes {
+            println!("  Notes: {}", notes);
+        }
+    }
+    Ok(())
+}
+
+/// Print the protocol changelog, optionally with migration steps from an older version
+fn show_changelog(from_version: Option<u32>) {
+    println!("Otter protocol changelog (current version: {})", PROTOCOL_VERSION);
+    println!("================================================");
+    
+    for entry in ChangelogEntry::since(from_version.unwrap_or(0)) {
+        println!();
+        println!("Version {} ({})", entry.version, entry.date);
+        for change in entry.changes {
+            println!("  • {}", change);
+        }
+    }
+    
+    if let Some(from) = from_version {
+        let steps = ChangelogEntry::migration_steps(from);
+        println!();
+        if steps.is_empty() {
+            println!("Already on protocol version {} - nothing to migrate.", PROTOCOL_VERSION);
+        } else {
+            println!("Migrating from version {}:", from);
+            for (i, step) in steps.iter().enumerate() {
+                println!("  {}. {}", i + 1, step);
+            }
+        }
+    }
+}
+
+/// Scan a peer's key and persist the pin if the user confirms it
+async fn run_keyscan(data_dir: &Path, peer_id: PeerId, non_interactive: bool, timeout_secs: u64) -> Result<i32> {
+    let identity = load_stored_identity(data_dir)
+        .await?
+        .context("No identity found. Run 'otter' once to create one.")?;
+    let local = PublicIdentity::from_identity(&identity);
+    
+    let storage = FileStorage::new(data_dir)?;
+    let mut trust_store = storage.load_trust_store().await?.unwrap_or_default();
+    
+    println!("🔍 Looking for {}...", peer_id);
+    let mut exchange = keyscan::NetworkExchange {
+        timeout: Duration::from_secs(timeout_secs),
+    };
+    let code = keyscan::keyscan(
+        &mut exchange,
+        &local,
+        &peer_id,
+        &mut trust_store,
+        non_interactive,
+        &mut std::io::stdout(),
+        || keyscan::read_answer(keyscan::PROMPT_TIMEOUT),
+    )
+    .await?;
+    
+    if code == 0 && !non_interactive {
+        storage.save_trust_store(&trust_store).await?;
+    }
+    
+    Ok(code)
+}
+
+/// Exchange identities with a peer and show the session's safety words
+async fn run_verify(data_dir: &Path, peer_id: PeerId, timeout_secs: u64) -> Result<i32> {
+    let identity = load_stored_identity(data_dir)
+        .await?
+        .context("No identity found. Run 'otter' once to create one.")?;
+    
+    println!("🔍 Looking for {}...", peer_id);
+    let mut exchange = keyscan::NetworkExchange {
+        timeout: Duration::from_secs(timeout_secs),
+    };
+    keyscan::verify(
+        &mut exchange,
+        &identity,
+        &peer_id,
+        &mut std::io::stdout(),
+        || keyscan::read_answer(keyscan::PROMPT_TIMEOUT),
+    )
+    .await
+}
+
+/// Resolve the data directory, defaulting to ~/.otter
+fn resolve_data_dir(data_dir: Option<PathBuf>) -> Result<PathBuf> {
+    match data_dir {
+        Some(dir) => Ok(dir),
         None => {
-            // Use ~/.otter as default
             let home = dirs::home_dir().context("Unable to determine home directory")?;
-            home.join(".otter")
+            Ok(home.join(".otter"))
         }
-    };
+    }
+}
+
+/// Load the identity from the OS credential store or from `identity.json`
+///
+/// The credential store is used when `identity.keychain` names an entry in it.
+async fn load_stored_identity(data_dir: &Path) -> Result<Option<Identity>> {
+    let keychain_path = data_dir.join(KEYCHAIN_LABEL_FILE);
+    if keychain_path.exists() {
+        let label = fs::read_to_string(&keychain_path)?.trim().to_string();
+        let identity = tokio::task::spawn_blocking(move || -> Result<Identity> {
+            let mut identity = SecureIdentityStorage::load(&label)?
+                .context("Identity is missing from the OS credential store")?;
+            if identity.add_kyber_keys() {
+                SecureIdentityStorage::save(&identity, &label)?;
+                info!("Added a Kyber768 key pair to the stored identity");
+            }
+            Ok(identity)
+        })
+        .await??;
+        return Ok(Some(identity));
+    }
+    
+    let identity_path = data_dir.join("identity.json");
+    if !identity_path.exists() {
+        return Ok(None);
+    }
+    Ok(Some(load_identity_file(&identity_path)?))
+}
+
+/// Load an identity file, saving it back with a Kyber768 key pair if it predates Kyber support
+fn load_identity_file(path: &Path) -> Result<Identity> {
+    let json = fs::read_to_string(path)?;
+    let mut identity = Identity::from_json(&json)?;
+    if identity.add_kyber_keys() {
+        fs::write(path, identity.to_json()?).context("Failed to save migrated identity")?;
+        info!("Added a Kyber768 key pair to {}", path.display());
+    }
+    Ok(identity)
+}
+
+/// Run in simple mode with auto-setup
+async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir: Option<PathBuf>) -> Result<()> {
+    // Determine data directory
+    let data_dir = resolve_data_dir(data_dir)?;
     
     // Create data directory if it doesn't exist
     if !data_dir.exists() {
@@ -160,19 +792,31 @@ async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir:
         println!("✓ Created data directory: {}", data_dir.display());
     }
     
-    // Path to identity file
+    // Path to identity file, or to the label of the identity in the OS credential store
     let identity_path = data_dir.join("identity.json");
+    let keychain_path = data_dir.join(KEYCHAIN_LABEL_FILE);
     
     // Load or create identity
-    let identity = if identity_path.exists() {
-        let json = fs::read_to_string(&identity_path)?;
-        Identity::from_json(&json)?
+    let identity = if let Some(identity) = load_stored_identity(&data_dir).await? {
+        identity
     } else {
         println!("🦦 First run detected - generating new identity...");
         let identity = Identity::generate()?;
-        let json = identity.to_json()?;
-        fs::write(&identity_path, json)?;
-        println!("✓ Identity generated and saved to: {}", identity_path.display());
+        let label = SecureIdentityStorage::label(&identity);
+        let stored = identity.clone();
+        let stored_label = label.clone();
+        match tokio::task::spawn_blocking(move || SecureIdentityStorage::save(&stored, &stored_label)).await? {
+            Ok(()) => {
+                fs::write(&keychain_path, &label)?;
+                println!("✓ Identity generated and saved to the OS credential store as: {}", label);
+            }
+            Err(e) => {
+                debug!("Secure identity storage failed: {}", e);
+                let json = identity.to_json()?;
+                fs::write(&identity_path, json)?;
+                println!("✓ Identity generated and saved to: {}", identity_path.display());
+            }
+        }
         identity
     };
     
@@ -205,8 +849,9 @@ async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir:
     // Create network channels
     let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
     
-    // Create network
-    let mut network = Network::new(event_tx, command_rx)?;
+    // Create network; chat messages are JSON rather than ProtocolMessage, so
+    // the default validator would drop them
+    let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll))?;
     
     // Start listening
     let listen_addr = format!("/ip4/0.0.0.0/tcp/{}", port);
@@ -216,7 +861,7 @@ async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir:
     let message_handler = Arc::new(Mutex::new(MessageHandler::new(identity)));
     
     // Create voice manager
-    let voice_manager = Arc::new(Mutex::new(VoiceManager::new()?));
+    let voice_manager = Arc::new(Mutex::new(VoiceManager::new_async().await?));
     
     // Create signaling channel
     let (signaling_tx, mut signaling_rx) = mpsc::unbounded_channel();
@@ -224,6 +869,7 @@ async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir:
         let mut vm = voice_manager.lock().await;
         vm.set_signaling_channel(signaling_tx);
     }
+    spawn_interface_monitor(voice_manager.clone());
     
     // Spawn network task
     let network_handle = tokio::spawn(async move {
@@ -251,10 +897,13 @@ async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir:
         }
     });
     
+    // Pinned peers get reserved mesh slots once they announce themselves
+    let trust_store = Arc::new(FileStorage::new(&data_dir)?.load_trust_store().await?.unwrap_or_default());
+    
     // Spawn event handler
     let event_handle = tokio::spawn(async move {
         while let Some(event) = event_rx.recv().await {
-            if let Err(e) = handle_network_event(event, msg_handler.clone(), voice_mgr.clone(), cmd_tx_events.clone()).await {
+            if let Err(e) = handle_network_event(event, msg_handler.clone(), voice_mgr.clone(), cmd_tx_events.clone(), trust_store.clone()).await {
                 error!("Error handling event: {}", e);
             }
         }
@@ -318,7 +967,10 @@ async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir:
         }
     }
     
-    // Cleanup
+    // Cleanup: let in-flight messages reach the mesh before closing
+    if let Err(e) = Network::shutdown(&command_tx, 500).await {
+        warn!("Failed to request network shutdown: {}", e);
+    }
     drop(command_tx);
     let _ = tokio::time::timeout(Duration::from_secs(2), network_handle).await;
     let _ = tokio::time::timeout(Duration::from_secs(2), event_handle).await;
@@ -330,11 +982,9 @@ async fn run_simple_mode(nickname: Option<String>, port: Option<u16>, data_dir:
 /// Start the chat peer
 async fn start_peer(identity_path: PathBuf, port: u16) -> Result<()> {
     // Load identity
-    let json = fs::read_to_string(&identity_path)
+    let identity = load_identity_file(&identity_path)
         .context("Failed to read identity file. Run 'otter init' first.")?;
     
-    let identity = Identity::from_json(&json)?;
-    
     println!("🦦 Otter Chat - Decentralized & Private");
     println!("========================================");
     println!("Peer ID: {}", identity.peer_id());
@@ -343,8 +993,9 @@ async fn start_peer(identity_path: PathBuf, port: u16) -> Result<()> {
     // Create network channels
     let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
     
-    // Create network
-    let mut network = Network::new(event_tx, command_rx)?;
+    // Create network; chat messages are JSON rather than ProtocolMessage, so
+    // the default validator would drop them
+    let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll))?;
     
     // Start listening
     let listen_addr = format!("/ip4/0.0.0.0/tcp/{}", port);
@@ -354,7 +1005,7 @@ async fn start_peer(identity_path: PathBuf, port: u16) -> Result<()> {
     let message_handler = Arc::new(Mutex::new(MessageHandler::new(identity)));
     
     // Create voice manager
-    let voice_manager = Arc::new(Mutex::new(VoiceManager::new()?));
+    let voice_manager = Arc::new(Mutex::new(VoiceManager::new_async().await?));
     
     // Create signaling channel
     let (signaling_tx, mut signaling_rx) = mpsc::unbounded_channel();
@@ -362,6 +1013,7 @@ async fn start_peer(identity_path: PathBuf, port: u16) -> Result<()> {
         let mut vm = voice_manager.lock().await;
         vm.set_signaling_channel(signaling_tx);
     }
+    spawn_interface_monitor(voice_manager.clone());
     
     // Spawn network task
     let network_handle = tokio::spawn(async move {
@@ -389,10 +1041,12 @@ async fn start_peer(identity_path: PathBuf, port: u16) -> Result<()> {
         }
     });
     
+    let trust_store = Arc::new(TrustStore::default());
+    
     // Spawn event handler
     let event_handle = tokio::spawn(async move {
         while let Some(event) = event_rx.recv().await {
-            if let Err(e) = handle_network_event(event, msg_handler.clone(), voice_mgr.clone(), cmd_tx_events.clone()).await {
+            if let Err(e) = handle_network_event(event, msg_handler.clone(), voice_mgr.clone(), cmd_tx_events.clone(), trust_store.clone()).await {
                 error!("Error handling event: {}", e);
             }
         }
@@ -452,7 +1106,10 @@ async fn start_peer(identity_path: PathBuf, port: u16) -> Result<()> {
         }
     }
     
-    // Cleanup
+    // Cleanup: let in-flight messages reach the mesh before closing
+    if let Err(e) = Network::shutdown(&command_tx, 500).await {
+        warn!("Failed to request network shutdown: {}", e);
+    }
     drop(command_tx);
     let _ = tokio::time::timeout(Duration::from_secs(2), network_handle).await;
     let _ = tokio::time::timeout(Duration::from_secs(2), event_handle).await;
@@ -465,6 +1122,7 @@ async fn handle_network_event(
     message_handler: Arc<Mutex<MessageHandler>>,
     voice_manager: Arc<Mutex<VoiceManager>>,
     command_tx: mpsc::Sender<NetworkCommand>,
+    trust_store: Arc<TrustStore>,
 ) -> Result<()> {
     match event {
         NetworkEvent::PeerDiscovered { peer_id, addresses } => {
@@ -476,7 +1134,7 @@ async fn handle_network_event(
                 info!("Auto-dialing peer {} at {}", peer_id, address);
                 if let Err(e) = command_tx
                     .send(NetworkCommand::DialPeer {
-                        peer_id: peer_id.clone(),
+                        peer_id,
                         address: address.clone(),
                     })
                     .await
@@ -496,7 +1154,7 @@ async fn handle_network_event(
             // (in case PeerReadyForMessages doesn't fire)
             let cmd_tx = command_tx.clone();
             let msg_handler = message_handler.clone();
-            let peer = peer_id.clone();
+            let peer = peer_id;
             tokio::spawn(async move {
                 // Wait for gossipsub to potentially be ready
                 tokio::time::sleep(Duration::from_secs(2)).await;
@@ -509,6 +1167,7 @@ async fn handle_network_event(
                     let _ = cmd_tx.send(NetworkCommand::SendMessage {
                         to: peer,
                         data,
+                        priority: MessagePriority::Interactive,
                     }).await;
                     info!("Sent identity via fallback mechanism");
                 }
@@ -529,8 +1188,9 @@ async fn handle_network_event(
                     // Send identity message via network
                     if let Err(e) = command_tx
                         .send(NetworkCommand::SendMessage {
-                            to: peer_id.clone(),
+                            to: peer_id,
                             data,
+                            priority: MessagePriority::Interactive,
                         })
                         .await
                     {
@@ -565,24 +1225,34 @@ async fn handle_network_event(
                         Message::Identity { public_identity, .. } => {
                             let peer_id = public_identity.peer_id().to_string();
                             info!("Received identity from peer: {}", peer_id);
+                            
+                            let priority = ConnectionPriority::for_peer(&trust_store, public_identity.peer_id());
+                            if priority != ConnectionPriority::Low {
+                                let _ = command_tx.send(NetworkCommand::SetPeerPriority { peer_id: from, priority }).await;
+                            }
+                            
                             let mut handler = message_handler.lock().await;
                             
                             if let Err(e) = handler.register_peer(public_identity) {
                                 warn!("Failed to register peer: {}", e);
                             } else {
+                                handler.set_peer_online(&peer_id, true);
                                 println!("\n✓ Identity verified for peer: {}", peer_id);
                             }
                         }
                     
                     Message::Text { content, .. } => {
                         // Check if it's a signaling message
-                        if content.starts_with("SIGNALING:") {
-                            let json_str = &content[10..]; // Remove "SIGNALING:" prefix
+                        if let Some(json_str) = content.strip_prefix("SIGNALING:") {
                             if let Ok(signaling_msg) = serde_json::from_str::<SignalingMessage>(json_str) {
                                 let peer_id_str = from.to_string();
                                 let mut vm = voice_manager.lock().await;
                                 if let Err(e) = vm.handle_signaling(&peer_id_str, signaling_msg).await {
-                                    warn!("Failed to handle signaling: {}", e);
+                                    if let Some(VoiceError::CallRejected(reason)) = e.downcast_ref::<VoiceError>() {
+                                        println!("\n📵 {} rejected the call ({:?})", peer_id_str, reason);
+                                    } else {
+                                        warn!("Failed to handle signaling: {}", e);
+                                    }
                                 } else {
                                     // Check call state and notify user
                                     let state = vm.get_call_state().await;
@@ -602,11 +1272,38 @@ async fn handle_network_event(
                         }
                     }
                     
-                    Message::Encrypted { ref from_peer_id, .. } => {
+                    Message::Encrypted { ref from_peer_id, .. }
+                    | Message::EncryptedWithIdempotency { ref from_peer_id, .. }
+                    | Message::SignedEncrypted { ref from_peer_id, .. } => {
                         let mut handler = message_handler.lock().await;
                         match handler.decrypt_message(&message) {
                             Ok(content) => {
                                 println!("\n🔐 Encrypted message from {}: {}", from_peer_id, content);
+                                
+                                // The message is on screen, so it is delivered and read at once
+                                let mut receipts = Vec::new();
+                                if let Some(m) = handler.conversation(from_peer_id).and_then(|c| c.messages().last()) {
+                                    for receipt in [handler.acknowledge(from_peer_id, &m.id), handler.read_receipt(from_peer_id, &m.id)] {
+                                        match receipt {
+                                            Ok(receipt) => receipts.push(receipt),
+                                            Err(e) => warn!("Failed to sign receipt for {}: {}", m.id, e),
+                                        }
+                                    }
+                                }
+                                drop(handler);
+                                for receipt in receipts {
+                                    let Ok(data) = receipt.to_bytes() else { continue };
+                                    if let Err(e) = command_tx
+                                        .send(NetworkCommand::SendMessage {
+                                            to: from,
+                                            data,
+                                            priority: MessagePriority::Interactive,
+                                        })
+                                        .await
+                                    {
+                                        warn!("Failed to send delivery receipt to {}: {}", from, e);
+                                    }
+                                }
                             }
                             Err(e) => {
                                 warn!("Failed to decrypt message: {}", e);
@@ -614,6 +1311,46 @@ async fn handle_network_event(
                         }
                     }
                     
+                    Message::DeliveryReceipt { ref from_peer_id, ref message_id, .. }
+                    | Message::ReadReceipt { ref from_peer_id, ref message_id, .. } => {
+                        let mut handler = message_handler.lock().await;
+                        match handler.receive_receipt(&message) {
+                            Ok(Some(_)) => {
+                                if let Some(status) = handler.delivery_status(message_id) {
+                                    println!("\n{} {:?}: message {}", status.icon(), status, message_id);
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                debug!("Ignoring receipt from {}: {}", from_peer_id, e);
+                            }
+                        }
+                    }
+                    
+                    Message::EphemeralInvite(ref invite) => {
+                        let mut handler = message_handler.lock().await;
+                        match handler.accept_ephemeral_invite(invite) {
+                            Ok(channel) => {
+                                println!("\n🔥 {} opened an ephemeral channel ({} messages)", channel.peer_id, invite.max_messages);
+                            }
+                            Err(e) => {
+                                warn!("Rejected ephemeral invite from {}: {}", invite.from_peer_id, e);
+                            }
+                        }
+                    }
+                    
+                    Message::Ephemeral { channel_id, .. } => {
+                        let mut handler = message_handler.lock().await;
+                        match handler.decrypt_message(&message) {
+                            Ok(content) => {
+                                println!("\n🔥 Ephemeral message from {}: {}", from, content);
+                            }
+                            Err(e) => {
+                                warn!("Failed to decrypt ephemeral message on {}: {}", channel_id, e);
+                            }
+                        }
+                    }
+                    
                     _ => {}
                     }
                 }
@@ -635,6 +1372,46 @@ async fn handle_network_event(
         NetworkEvent::ListeningOn { address } => {
             println!("Listening on: {}", address);
         }
+        
+        NetworkEvent::StatsSnapshot(stats) => {
+            for (peer_id, s) in stats {
+                debug!(
+                    "Peer {}: sent {} msgs/{} bytes, received {} msgs/{} bytes",
+                    peer_id, s.messages_sent, s.bytes_sent, s.messages_received, s.bytes_received
+                );
+            }
+        }
+        NetworkEvent::PeerUnresponsive { peer_id } => {
+            warn!("Peer {} stopped responding", peer_id);
+            println!("\n⚠️  Peer {} is not responding", peer_id);
+        }
+        NetworkEvent::ShuttingDown => {
+            info!("Network shut down");
+        }
+        NetworkEvent::QueueDepth { real_time, interactive, bulk } => {
+            if real_time + interactive + bulk > 0 {
+                debug!("Send queue: {} real-time, {} interactive, {} bulk", real_time, interactive, bulk);
+            }
+        }
+        NetworkEvent::MessageRejected { from, reason } => {
+            warn!("Dropped message from {}: {}", from, reason);
+        }
+        NetworkEvent::BootstrapCompleted { known_peers } => {
+            info!("Joined the DHT with {} known peers", known_peers);
+        }
+        
+        NetworkEvent::ListenerModeChanged(enabled) => {
+            info!("Listener mode {}", if enabled { "enabled" } else { "disabled" });
+        }
+        NetworkEvent::PriorityChanged { peer_id, priority } => {
+            debug!("Peer {} now has {:?} connection priority", peer_id, priority);
+        }
+        NetworkEvent::PeerFound { peer_id, addresses } => {
+            debug!("Found peer {} at {:?}", peer_id, addresses);
+        }
+        NetworkEvent::PeerBanned { peer_id, duration } => {
+            warn!("Banned peer {} for {} seconds", peer_id, duration.as_secs());
+        }
     }
     
     Ok(())
@@ -702,6 +1479,7 @@ async fn send_message(
         let mut handler = message_handler.lock().await;
         
         let encrypted_msg = handler.prepare_encrypted_message(peer_id_str, &message)?;
+        let announcements = handler.take_session_announcements();
         debug!("Prepared encrypted message: {:?}", encrypted_msg);
         let data = encrypted_msg.to_bytes()?;
         debug!("Serialized to {} bytes", data.len());
@@ -728,10 +1506,26 @@ async fn send_message(
             // The encryption ensures only the intended recipient can decrypt it
             // NOTE: The 'to' parameter is currently ignored by gossipsub broadcast.
             // All connected peers receive the message, but only the intended recipient can decrypt it.
-            let to = connected_peers[0].clone();
+            let to = connected_peers[0];
+            
+            // A renewed session must reach the peer before messages encrypted with it
+            for (renewed_peer, announcement) in announcements {
+                debug!("Announcing identity to {} after a session renewal", renewed_peer);
+                command_tx
+                    .send(NetworkCommand::SendMessage {
+                        to,
+                        data: announcement.to_bytes()?,
+                        priority: MessagePriority::Interactive,
+                    })
+                    .await?;
+            }
             
             if let Err(e) = command_tx
-                .send(NetworkCommand::SendMessage { to, data })
+                .send(NetworkCommand::SendMessage {
+                    to,
+                    data,
+                    priority: MessagePriority::Interactive,
+                })
                 .await
             {
                 error!("Failed to send message: {}", e);
@@ -745,6 +1539,26 @@ async fn send_message(
     Ok(())
 }
 
+/// Restart ICE on the active call whenever the local IP addresses change
+fn spawn_interface_monitor(voice_manager: Arc<Mutex<VoiceManager>>) {
+    let (change_tx, mut change_rx) = mpsc::unbounded_channel();
+    NetworkInterfaceMonitor::new().spawn(change_tx);
+    
+    tokio::spawn(async move {
+        while let Some(change) = change_rx.recv().await {
+            let mut vm = voice_manager.lock().await;
+            if !vm.has_active_call().await {
+                continue;
+            }
+            
+            info!("Network interfaces changed ({:?}), restarting ICE", change);
+            if let Err(e) = vm.restart_ice().await {
+                warn!("ICE restart failed: {}", e);
+            }
+        }
+    });
+}
+
 /// Start a voice call
 async fn start_call(voice_manager: &Arc<Mutex<VoiceManager>>) -> Result<()> {
     let mut vm = voice_manager.lock().await;
@@ -795,7 +1609,7 @@ async fn start_call(voice_manager: &Arc<Mutex<VoiceManager>>) -> Result<()> {
         CallState::Connecting => {
             println!("Call is connecting...");
         }
-        CallState::Connected => {
+        CallState::Connected | CallState::Renegotiating | CallState::IceRestarting => {
             if let Some(peer_id) = vm.get_current_peer().await {
                 println!("Already in a call with {}. Use /hangup to end the call first.", peer_id);
             }
@@ -0,0 +1,80 @@
+//! # Metrics Endpoint
+//!
+//! Minimal HTTP server exposing the network counters at `GET /metrics` for
+//! Prometheus to scrape. Every other path answers 404 and every other method
+//! on `/metrics` answers 405.
+
+use anyhow::Result;
+use hyper::header::{ALLOW, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use otter_network::MetricsExporter;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Content type of the Prometheus text exposition format
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+/// Serve `exporter` on `addr` until `shutdown` resolves
+pub async fn serve(
+    exporter: Arc<MetricsExporter>,
+    addr: SocketAddr,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let make_service = make_service_fn(move |_| {
+        let exporter = exporter.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let response = respond(&exporter, &request);
+                async move { Ok::<_, Infallible>(response) }
+            }))
+        }
+    });
+    
+    let server = Server::try_bind(&addr)?.serve(make_service);
+    println!("📈 Serving metrics at http://{}/metrics", server.local_addr());
+    server.with_graceful_shutdown(shutdown).await?;
+    Ok(())
+}
+
+/// Response to one request
+fn respond(exporter: &MetricsExporter, request: &Request<Body>) -> Response<Body> {
+    let response = match (request.method(), request.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header(CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)
+            .body(Body::from(exporter.render_prometheus())),
+        (_, "/metrics") => Response::builder()
+            .status(StatusCode::METHOD_NOT_ALLOWED)
+            .header(ALLOW, "GET")
+            .body(Body::empty()),
+        _ => Response::builder().status(StatusCode::NOT_FOUND).body(Body::empty()),
+    };
+    response.expect("static headers are valid")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn request(method: Method, path: &str) -> Request<Body> {
+        Request::builder().method(method).uri(path).body(Body::empty()).unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_metrics_route() {
+        let exporter = MetricsExporter::new();
+        exporter.message_sent(42);
+        
+        let response = respond(&exporter, &request(Method::GET, "/metrics"));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[CONTENT_TYPE], PROMETHEUS_CONTENT_TYPE);
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("# TYPE bytes_sent_total counter\nbytes_sent_total 42\n"));
+        
+        assert_eq!(respond(&exporter, &request(Method::POST, "/metrics")).status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(respond(&exporter, &request(Method::GET, "/")).status(), StatusCode::NOT_FOUND);
+    }
+}
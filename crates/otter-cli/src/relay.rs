@@ -0,0 +1,356 @@
+//! # TURN Relay
+//!
+//! A UDP TURN server for peers that cannot reach each other directly. Clients
+//! authenticate with credentials from `TurnTokenIssuer`: the password of a
+//! username is recomputed from the shared secret, so the relay keeps no user
+//! database. Each allocation gets its own relay port, and at most
+//! `max_allocations` exist at once.
+
+use async_trait::async_trait;
+use otter_voice::TurnTokenIssuer;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::net::UdpSocket;
+use tokio::sync::mpsc;
+use tracing::{debug, info, warn};
+use webrtc::turn::allocation::AllocationInfo;
+use webrtc::turn::auth::{generate_auth_key, AuthHandler};
+use webrtc::turn::relay::relay_static::RelayAddressGeneratorStatic;
+use webrtc::turn::relay::RelayAddressGenerator;
+use webrtc::turn::server::config::{ConnConfig, ServerConfig};
+use webrtc::turn::server::Server;
+use webrtc::util::vnet::net::Net;
+use webrtc::util::Conn;
+
+/// Realm the relay authenticates in
+pub const RELAY_REALM: &str = "otter";
+
+/// How often `RelayServer::run` prints statistics
+pub const STATS_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Lifetime of a channel binding
+const CHANNEL_BIND_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Error, Debug)]
+pub enum RelayError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("TURN error: {0}")]
+    Turn(#[from] webrtc::turn::Error),
+}
+
+/// Settings of a relay
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    /// Address the TURN listener binds to
+    pub listen: SocketAddr,
+    /// Address announced to clients for their relay ports
+    pub public_ip: IpAddr,
+    pub max_allocations: usize,
+    /// Secret shared with the `TurnTokenIssuer` of the clients
+    pub shared_secret: String,
+}
+
+impl RelayConfig {
+    /// Listen on every interface at `port`, announcing `public_ip`
+    pub fn new(port: u16, public_ip: IpAddr, max_allocations: usize, shared_secret: String) -> Self {
+        Self {
+            listen: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+            public_ip,
+            max_allocations,
+            shared_secret,
+        }
+    }
+}
+
+/// Snapshot of a relay's activity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RelayStats {
+    pub active_allocations: usize,
+    /// Bytes forwarded in either direction through relay ports
+    pub bytes_relayed: u64,
+    pub uptime_secs: u64,
+}
+
+/// Counters shared with the allocations
+#[derive(Debug, Default)]
+struct RelayCounters {
+    active_allocations: AtomicUsize,
+    bytes_relayed: AtomicU64,
+}
+
+/// Accepts usernames signed with the shared secret until they expire
+struct TokenAuthHandler {
+    shared_secret: String,
+}
+
+impl AuthHandler for TokenAuthHandler {
+    fn auth_handle(&self, username: &str, realm: &str, src_addr: SocketAddr) -> Result<Vec<u8>, webrtc::turn::Error> {
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        match TurnTokenIssuer::expected_password_at(username, self.shared_secret.as_bytes(), now) {
+            Some(password) => Ok(generate_auth_key(username, realm, &password)),
+            None => {
+                debug!("Rejected TURN credential {} from {}", username, src_addr);
+                Err(webrtc::turn::Error::Other(format!("invalid or expired username {}", username)))
+            }
+        }
+    }
+}
+
+/// Relay ports that count allocations and forwarded bytes
+struct LimitedRelayGenerator {
+    inner: RelayAddressGeneratorStatic,
+    max_allocations: usize,
+    counters: Arc<RelayCounters>,
+}
+
+#[async_trait]
+impl RelayAddressGenerator for LimitedRelayGenerator {
+    fn validate(&self) -> Result<(), webrtc::turn::Error> {
+        self.inner.validate()
+    }
+    
+    async fn allocate_conn(
+        &self,
+        use_ipv4: bool,
+        requested_port: u16,
+    ) -> Result<(Arc<dyn Conn + Send + Sync>, SocketAddr), webrtc::turn::Error> {
+        let reserved = self.counters.active_allocations.fetch_update(Ordering::AcqRel, Ordering::Acquire, |active| {
+            (active < self.max_allocations).then_some(active + 1)
+        });
+        if reserved.is_err() {
+            warn!("Refusing allocation: {} allocations are active", self.max_allocations);
+            return Err(webrtc::turn::Error::Other("allocation quota reached".to_string()));
+        }
+        
+        match self.inner.allocate_conn(use_ipv4, requested_port).await {
+            Ok((conn, relay_addr)) => {
+                let conn = CountingConn {
+                    inner: conn,
+                    counters: self.counters.clone(),
+                };
+                Ok((Arc::new(conn), relay_addr))
+            }
+            Err(e) => {
+                self.counters.active_allocations.fetch_sub(1, Ordering::AcqRel);
+                Err(e)
+            }
+        }
+    }
+}
+
+/// Relay port that adds the bytes it forwards to the counters
+struct CountingConn {
+    inner: Arc<dyn Conn + Send + Sync>,
+    counters: Arc<RelayCounters>,
+}
+
+impl CountingConn {
+    fn count(&self, bytes: usize) {
+        self.counters.bytes_relayed.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl Conn for CountingConn {
+    async fn connect(&self, addr: SocketAddr) -> webrtc::util::Result<()> {
+        self.inner.connect(addr).await
+    }
+    
+    async fn recv(&self, buf: &mut [u8]) -> webrtc::util::Result<usize> {
+        let n = self.inner.recv(buf).await?;
+        self.count(n);
+        Ok(n)
+    }
+    
+    async fn recv_from(&self, buf: &mut [u8]) -> webrtc::util::Result<(usize, SocketAddr)> {
+        let (n, from) = self.inner.recv_from(buf).await?;
+        self.count(n);
+        Ok((n, from))
+    }
+    
+    async fn send(&self, buf: &[u8]) -> webrtc::util::Result<usize> {
+        let n = self.inner.send(buf).await?;
+        self.count(n);
+        Ok(n)
+    }
+    
+    async fn send_to(&self, buf: &[u8], target: SocketAddr) -> webrtc::util::Result<usize> {
+        let n = self.inner.send_to(buf, target).await?;
+        self.count(n);
+        Ok(n)
+    }
+    
+    fn local_addr(&self) -> webrtc::util::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+    
+    fn remote_addr(&self) -> Option<SocketAddr> {
+        self.inner.remote_addr()
+    }
+    
+    async fn close(&self) -> webrtc::util::Result<()> {
+        self.inner.close().await
+    }
+}
+
+/// A running TURN relay
+pub struct RelayServer {
+    server: Server,
+    local_addr: SocketAddr,
+    counters: Arc<RelayCounters>,
+    started: Instant,
+}
+
+impl RelayServer {
+    /// Bind the listener and start serving
+    pub async fn new(config: RelayConfig) -> Result<Self, RelayError> {
+        let socket = UdpSocket::bind(config.listen).await?;
+        let local_addr = socket.local_addr()?;
+        let counters = Arc::new(RelayCounters::default());
+        
+        let relay_addr_generator = LimitedRelayGenerator {
+            inner: RelayAddressGeneratorStatic {
+                relay_address: config.public_ip,
+                address: config.listen.ip().to_string(),
+                net: Arc::new(Net::new(None)),
+            },
+            max_allocations: config.max_allocations,
+            counters: counters.clone(),
+        };
+        
+        let (close_tx, mut close_rx) = mpsc::channel::<AllocationInfo>(64);
+        let server = Server::new(ServerConfig {
+            conn_configs: vec![ConnConfig {
+                conn: Arc::new(socket),
+                relay_addr_generator: Box::new(relay_addr_generator),
+            }],
+            realm: RELAY_REALM.to_string(),
+            auth_handler: Arc::new(TokenAuthHandler {
+                shared_secret: config.shared_secret,
+            }),
+            channel_bind_timeout: CHANNEL_BIND_TIMEOUT,
+            alloc_close_notify: Some(close_tx),
+        })
+        .await?;
+        
+        let closed_counters = counters.clone();
+        tokio::spawn(async move {
+            while let Some(info) = close_rx.recv().await {
+                debug!("Allocation of {} closed", info.username);
+                closed_counters.active_allocations.fetch_sub(1, Ordering::AcqRel);
+            }
+        });
+        
+        info!("TURN relay listening on {}", local_addr);
+        Ok(Self {
+            server,
+            local_addr,
+            counters,
+            started: Instant::now(),
+        })
+    }
+    
+    /// Address of the TURN listener
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+    
+    /// Current activity
+    pub fn stats(&self) -> RelayStats {
+        RelayStats {
+            active_allocations: self.counters.active_allocations.load(Ordering::Acquire),
+            bytes_relayed: self.counters.bytes_relayed.load(Ordering::Relaxed),
+            uptime_secs: self.started.elapsed().as_secs(),
+        }
+    }
+    
+    /// Print statistics every `STATS_INTERVAL`; never returns
+    ///
+    /// The relay serves from background tasks, so dropping this future only
+    /// stops the statistics.
+    pub async fn run(&self) {
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + STATS_INTERVAL, STATS_INTERVAL);
+        loop {
+            interval.tick().await;
+            let stats = self.stats();
+            println!(
+                "📡 {} active allocations, {} bytes relayed, up {}s",
+                stats.active_allocations, stats.bytes_relayed, stats.uptime_secs
+            );
+        }
+    }
+    
+    /// Close every allocation and the listener
+    pub async fn close(&self) -> Result<(), RelayError> {
+        self.server.close().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use otter_identity::PeerId;
+    use webrtc::turn::client::{Client, ClientConfig};
+    
+    const SECRET: &str = "relay-test-secret";
+    
+    async fn client(relay: SocketAddr, secret: &str) -> Client {
+        let cred = TurnTokenIssuer::generate_credential(&PeerId::from_string("12D3KooWRelayClient".to_string()), 600, secret.as_bytes());
+        let client = Client::new(ClientConfig {
+            stun_serv_addr: relay.to_string(),
+            turn_serv_addr: relay.to_string(),
+            username: cred.username,
+            password: cred.password,
+            realm: RELAY_REALM.to_string(),
+            software: String::new(),
+            rto_in_ms: 0,
+            conn: Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap()),
+            vnet: None,
+        })
+        .await
+        .unwrap();
+        client.listen().await.unwrap();
+        client
+    }
+    
+    #[tokio::test]
+    async fn test_relay_forwards_udp() {
+        let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+        let config = RelayConfig {
+            listen: SocketAddr::new(localhost, 0),
+            ..RelayConfig::new(0, localhost, 1, SECRET.to_string())
+        };
+        let relay = RelayServer::new(config).await.unwrap();
+        
+        let rejected = client(relay.local_addr(), "wrong-secret").await;
+        assert!(rejected.allocate().await.is_err());
+        assert_eq!(relay.stats().active_allocations, 0);
+        
+        let owner = client(relay.local_addr(), SECRET).await;
+        let relay_conn = owner.allocate().await.unwrap();
+        assert_eq!(relay.stats().active_allocations, 1);
+        
+        let peer = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        relay_conn.send_to(b"hello through the relay", peer.local_addr().unwrap()).await.unwrap();
+        
+        let mut buf = [0u8; 64];
+        let (n, from) = tokio::time::timeout(Duration::from_secs(5), peer.recv_from(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(&buf[..n], b"hello through the relay");
+        assert_eq!(from, relay_conn.local_addr().unwrap());
+        assert_eq!(relay.stats().bytes_relayed, n as u64);
+        
+        // The only allocation slot is taken
+        let second = client(relay.local_addr(), SECRET).await;
+        assert!(second.allocate().await.is_err());
+        
+        owner.close().await.unwrap();
+        second.close().await.unwrap();
+        rejected.close().await.unwrap();
+        relay.close().await.unwrap();
+    }
+}
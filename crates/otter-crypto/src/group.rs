@@ -0,0 +1,550 @@
+//! # Group Sessions
+//!
+//! Groups use Sender Keys (see [`crate::sender_key`]): every member encrypts
+//! with its own ratcheting chain and hands that chain to the other members
+//! in a [`SenderKeyMessage`], wrapped individually with a pairwise X25519 key.
+//! A group message is encrypted once, whatever the group size.
+//!
+//! Whenever a member is removed, the initiator rotates its sender key and
+//! sends it to the remaining members in a signed [`GroupRekeyBundle`]. Every
+//! member that applies the bundle rotates its own sender key too, so the
+//! removed peer cannot read anything sent afterwards. Rekey bundles carry a
+//! counter that must increase by exactly one.
+
+use crate::sender_key::{ReceiverChain, SenderChain, SENDER_KEY_LEN, SIGNATURE_LEN};
+use crate::{CryptoError, EncryptedMessage, SecretBuffer, SenderKeyMessage};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use ed25519_dalek::Signature;
+use otter_identity::{Identity, IdentityError, PublicIdentity};
+use rand::{rngs::OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use x25519_dalek::StaticSecret;
+
+/// BLAKE3 context for the key that wraps a sender key for one member
+const KEY_WRAP_CONTEXT: &str = "otter group key wrap v1";
+
+/// The initiator's rotated sender key, signed together with the new key generation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupRekeyBundle {
+    /// Initiator's sender key, wrapped for each remaining member
+    pub sender_key: SenderKeyMessage,
+    /// Key generation this bundle moves the group to
+    pub rekey_counter: u64,
+    /// Initiator's Ed25519 signature over the group ID, counter and wrapped keys
+    pub initiator_signature: Vec<u8>,
+}
+
+impl GroupRekeyBundle {
+    fn digest(group_id: &str, rekey_counter: u64, sender_key: &SenderKeyMessage) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(group_id.as_bytes());
+        hasher.update(&rekey_counter.to_le_bytes());
+        hasher.update(&(sender_key.sender_peer_id.len() as u64).to_le_bytes());
+        hasher.update(sender_key.sender_peer_id.as_bytes());
+        hasher.update(&sender_key.generation.to_le_bytes());
+        for (peer_id, wrapped) in sender_key.encrypted.iter().collect::<BTreeMap<_, _>>() {
+            hasher.update(&(peer_id.len() as u64).to_le_bytes());
+            hasher.update(peer_id.as_bytes());
+            hasher.update(&(wrapped.len() as u64).to_le_bytes());
+            hasher.update(wrapped);
+        }
+        *hasher.finalize().as_bytes()
+    }
+}
+
+/// Encryption state of the local member of a group
+pub struct GroupSession {
+    group_id: String,
+    local_peer_id: String,
+    /// Local X25519 secret, for the pairwise keys that wrap sender keys
+    local_secret: StaticSecret,
+    members: HashMap<String, PublicIdentity>,
+    sender_chain: SenderChain,
+    /// Other members' chains, keyed by peer ID
+    sender_keys: HashMap<String, ReceiverChain>,
+    rekey_counter: u64,
+}
+
+impl GroupSession {
+    /// Create a group with `local` as its only member
+    pub fn new(group_id: impl Into<String>, local: &Identity) -> Self {
+        let local_public = PublicIdentity::from_identity(local);
+        let local_peer_id = local_public.peer_id().to_string();
+        
+        Self {
+            group_id: group_id.into(),
+            members: HashMap::from([(local_peer_id.clone(), local_public)]),
+            local_peer_id,
+            local_secret: local.encryption_secret_key().clone(),
+            sender_chain: SenderChain::new(0),
+            sender_keys: HashMap::new(),
+            rekey_counter: 0,
+        }
+    }
+    
+    /// Join a group using the key bundle sent by `initiator_public`
+    ///
+    /// Installs the initiator's sender key. The other members' sender keys
+    /// arrive separately; send them [`GroupSession::sender_key_message`].
+    pub fn join(
+        group_id: impl Into<String>,
+        local: &Identity,
+        members: Vec<PublicIdentity>,
+        bundle: &GroupRekeyBundle,
+        initiator_public: &PublicIdentity,
+    ) -> Result<Self, CryptoError> {
+        let mut session = Self::new(group_id, local);
+        for member in members {
+            session.add_member(member);
+        }
+        session.add_member(initiator_public.clone());
+        
+        session.verify_bundle(bundle, initiator_public)?;
+        session.install_sender_key(initiator_public.peer_id().as_str(), &bundle.sender_key)?;
+        session.rekey_counter = bundle.rekey_counter;
+        Ok(session)
+    }
+    
+    /// Get the group ID
+    pub fn group_id(&self) -> &str {
+        &self.group_id
+    }
+    
+    /// Get the current key generation
+    pub fn rekey_counter(&self) -> u64 {
+        self.rekey_counter
+    }
+    
+    /// Check whether a peer is a member
+    pub fn is_member(&self, peer_id: &str) -> bool {
+        self.members.contains_key(peer_id)
+    }
+    
+    /// Get the peer IDs of all members, including the local peer
+    pub fn members(&self) -> Vec<&str> {
+        self.members.keys().map(String::as_str).collect()
+    }
+    
+    /// Check whether the sender key of `peer_id` is installed
+    pub fn has_sender_key(&self, peer_id: &str) -> bool {
+        self.sender_keys.contains_key(peer_id)
+    }
+    
+    /// Add a member; share sender keys with it through [`GroupSession::key_bundle`]
+    /// or [`GroupSession::sender_key_message`]
+    pub fn add_member(&mut self, member: PublicIdentity) {
+        self.members.insert(member.peer_id().to_string(), member);
+    }
+    
+    /// Wrap the local sender key for every other member
+    ///
+    /// Members install it with [`GroupSession::install_sender_key`]. It lets
+    /// them read messages from the current iteration on, not earlier ones.
+    pub fn sender_key_message(&self) -> Result<SenderKeyMessage, CryptoError> {
+        let exported = self.sender_chain.export();
+        let generation = self.sender_chain.generation();
+        
+        let mut encrypted = HashMap::new();
+        for (peer_id, member) in &self.members {
+            if *peer_id == self.local_peer_id {
+                continue;
+            }
+            let kek = wrapping_key(&self.local_secret, member)?;
+            let aad = self.wrap_aad(&self.local_peer_id, generation, peer_id);
+            encrypted.insert(peer_id.clone(), seal(&kek, &aad, exported.as_bytes())?);
+        }
+        
+        Ok(SenderKeyMessage {
+            sender_peer_id: self.local_peer_id.clone(),
+            generation,
+            encrypted,
+        })
+    }
+    
+    /// Unwrap and install the sender key of member `peer_id`
+    ///
+    /// A key of an older generation than the installed one is a replay; the
+    /// installed generation is left as it is so its chain keeps its position.
+    pub fn install_sender_key(&mut self, peer_id: &str, message: &SenderKeyMessage) -> Result<(), CryptoError> {
+        if message.sender_peer_id != peer_id {
+            return Err(CryptoError::InvalidKey);
+        }
+        let member = self
+            .members
+            .get(peer_id)
+            .ok_or_else(|| CryptoError::NotGroupMember(peer_id.to_string()))?;
+        match self.sender_keys.get(peer_id).map(ReceiverChain::generation) {
+            Some(installed) if message.generation < installed => return Err(CryptoError::ReplayAttack),
+            Some(installed) if message.generation == installed => return Ok(()),
+            _ => {}
+        }
+        
+        let wrapped = message
+            .encrypted
+            .get(&self.local_peer_id)
+            .ok_or_else(|| CryptoError::NotGroupMember(self.local_peer_id.clone()))?;
+        let kek = wrapping_key(&self.local_secret, member)?;
+        let aad = self.wrap_aad(peer_id, message.generation, &self.local_peer_id);
+        let exported = SecretBuffer::<SENDER_KEY_LEN>::new(
+            open(&kek, &aad, wrapped)?
+                .as_slice()
+                .try_into()
+                .map_err(|_| CryptoError::InvalidKey)?,
+        );
+        
+        let chain = ReceiverChain::import(message.generation, exported.as_bytes())?;
+        self.sender_keys.insert(peer_id.to_string(), chain);
+        Ok(())
+    }
+    
+    /// Wrap the local sender key for every other member, signed as a welcome bundle
+    pub fn key_bundle(&self, initiator: &Identity) -> Result<GroupRekeyBundle, CryptoError> {
+        self.build_bundle(initiator, self.rekey_counter)
+    }
+    
+    /// Remove a member and rotate the local sender key
+    ///
+    /// The returned bundle must be delivered to the remaining members, who
+    /// pass it to [`GroupSession::apply_rekey`].
+    pub fn remove_member(
+        &mut self,
+        member_peer_id: &str,
+        initiator: &Identity,
+    ) -> Result<GroupRekeyBundle, CryptoError> {
+        if member_peer_id == self.local_peer_id || self.members.remove(member_peer_id).is_none() {
+            return Err(CryptoError::NotGroupMember(member_peer_id.to_string()));
+        }
+        self.sender_keys.remove(member_peer_id);
+        
+        let rekey_counter = self
+            .rekey_counter
+            .checked_add(1)
+            .ok_or(CryptoError::CounterOverflow)?;
+        self.rotate_sender_key()?;
+        let bundle = self.build_bundle(initiator, rekey_counter)?;
+        
+        self.rekey_counter = rekey_counter;
+        Ok(bundle)
+    }
+    
+    /// Verify a rekey bundle from `initiator_public`, install its sender key and rotate ours
+    ///
+    /// Members that received no wrapped key are dropped from the group. The
+    /// returned sender key message carries the local rotated key and must be
+    /// delivered to the remaining members.
+    pub fn apply_rekey(
+        &mut self,
+        bundle: &GroupRekeyBundle,
+        initiator_public: &PublicIdentity,
+    ) -> Result<SenderKeyMessage, CryptoError> {
+        if bundle.rekey_counter != self.rekey_counter.wrapping_add(1) {
+            return Err(CryptoError::ReplayAttack);
+        }
+        
+        let initiator_peer_id = initiator_public.peer_id().to_string();
+        if !self.is_member(&initiator_peer_id) {
+            return Err(CryptoError::NotGroupMember(initiator_peer_id));
+        }
+        
+        self.verify_bundle(bundle, initiator_public)?;
+        self.install_sender_key(&initiator_peer_id, &bundle.sender_key)?;
+        self.rekey_counter = bundle.rekey_counter;
+        
+        let local_peer_id = self.local_peer_id.clone();
+        let recipients = &bundle.sender_key.encrypted;
+        self.members.retain(|peer_id, _| {
+            *peer_id == local_peer_id || *peer_id == initiator_peer_id || recipients.contains_key(peer_id)
+        });
+        let members = &self.members;
+        self.sender_keys.retain(|peer_id, _| members.contains_key(peer_id));
+        
+        self.rotate_sender_key()?;
+        self.sender_key_message()
+    }
+    
+    /// Encrypt a message once for the whole group with the local sender key
+    ///
+    /// The ciphertext ends with the sender's signature over the associated
+    /// data, nonce and AEAD output.
+    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<EncryptedMessage, CryptoError> {
+        let generation = self.sender_chain.generation();
+        let (message_counter, message_key) = self.sender_chain.next_message_key()?;
+        
+        let cipher = ChaCha20Poly1305::new(message_key.as_bytes().into());
+        let mut nonce_bytes = [0u8; 12];
+        OsRng.fill_bytes(&mut nonce_bytes);
+        
+        let aad = self.message_aad(&self.local_peer_id, generation, message_counter);
+        let mut ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: plaintext, aad: &aad })
+            .map_err(|_| CryptoError::EncryptionFailed)?;
+        
+        let signature = self.sender_chain.sign(&signed_data(&aad, &nonce_bytes, &ciphertext));
+        ciphertext.extend_from_slice(&signature);
+        
+        Ok(EncryptedMessage {
+            nonce: nonce_bytes.to_vec(),
+            ciphertext,
+            associated_data: None,
+            message_counter,
+            timestamp: Some(chrono::Utc::now().timestamp()),
+            ratchet_public: None,
+            previous_chain_length: None,
+        })
+    }
+    
+    /// Decrypt a group message from `sender_peer_id` with its installed sender key
+    pub fn decrypt(&mut self, sender_peer_id: &str, encrypted: &EncryptedMessage) -> Result<Vec<u8>, CryptoError> {
+        let group_id = &self.group_id;
+        let chain = self
+            .sender_keys
+            .get_mut(sender_peer_id)
+            .ok_or_else(|| CryptoError::NotGroupMember(sender_peer_id.to_string()))?;
+        let nonce_bytes: [u8; 12] = encrypted
+            .nonce
+            .as_slice()
+            .try_into()
+            .map_err(|_| CryptoError::DecryptionFailed)?;
+        if encrypted.ciphertext.len() < SIGNATURE_LEN {
+            return Err(CryptoError::DecryptionFailed);
+        }
+        let (ciphertext, signature) = encrypted.ciphertext.split_at(encrypted.ciphertext.len() - SIGNATURE_LEN);
+        
+        let aad = message_aad(group_id, sender_peer_id, chain.generation(), encrypted.message_counter);
+        chain.verify(&signed_data(&aad, &nonce_bytes, ciphertext), signature)?;
+        let message_key = chain.take_message_key(encrypted.message_counter)?;
+        
+        ChaCha20Poly1305::new(message_key.as_bytes().into())
+            .decrypt(Nonce::from_slice(&nonce_bytes), Payload { msg: ciphertext, aad: &aad })
+            .map_err(|_| CryptoError::DecryptionFailed)
+    }
+    
+    /// Group ID, sender, sender key generation and message counter
+    fn message_aad(&self, sender_peer_id: &str, generation: u64, message_counter: u64) -> Vec<u8> {
+        message_aad(&self.group_id, sender_peer_id, generation, message_counter)
+    }
+    
+    fn rotate_sender_key(&mut self) -> Result<(), CryptoError> {
+        let generation = self
+            .sender_chain
+            .generation()
+            .checked_add(1)
+            .ok_or(CryptoError::CounterOverflow)?;
+        self.sender_chain = SenderChain::new(generation);
+        Ok(())
+    }
+    
+    fn build_bundle(&self, initiator: &Identity, rekey_counter: u64) -> Result<GroupRekeyBundle, CryptoError> {
+        let sender_key = self.sender_key_message()?;
+        let digest = GroupRekeyBundle::digest(&self.group_id, rekey_counter, &sender_key);
+        Ok(GroupRekeyBundle {
+            sender_key,
+            rekey_counter,
+            initiator_signature: initiator.sign(&digest)?.to_bytes().to_vec(),
+        })
+    }
+    
+    fn verify_bundle(&self, bundle: &GroupRekeyBundle, initiator_public: &PublicIdentity) -> Result<(), CryptoError> {
+        let signature: [u8; 64] = bundle
+            .initiator_signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| IdentityError::InvalidSignature)?;
+        let digest = GroupRekeyBundle::digest(&self.group_id, bundle.rekey_counter, &bundle.sender_key);
+        initiator_public.verify(&digest, &Signature::from_bytes(&signature))?;
+        Ok(())
+    }
+    
+    /// Binds a wrapped sender key to its group, sender, generation and recipient
+    fn wrap_aad(&self, sender_peer_id: &str, generation: u64, recipient_peer_id: &str) -> Vec<u8> {
+        let mut aad = self.group_id.as_bytes().to_vec();
+        aad.extend_from_slice(&(sender_peer_id.len() as u64).to_le_bytes());
+        aad.extend_from_slice(sender_peer_id.as_bytes());
+        aad.extend_from_slice(&generation.to_le_bytes());
+        aad.extend_from_slice(recipient_peer_id.as_bytes());
+        aad
+    }
+}
+
+fn message_aad(group_id: &str, sender_peer_id: &str, generation: u64, message_counter: u64) -> Vec<u8> {
+    let mut aad = group_id.as_bytes().to_vec();
+    aad.extend_from_slice(&(sender_peer_id.len() as u64).to_le_bytes());
+    aad.extend_from_slice(sender_peer_id.as_bytes());
+    aad.extend_from_slice(&generation.to_le_bytes());
+    aad.extend_from_slice(&message_counter.to_le_bytes());
+    aad
+}
+
+/// What the sender signs: associated data, nonce and AEAD output
+fn signed_data(aad: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    [aad, nonce, ciphertext].concat()
+}
+
+pub(crate) fn random_key() -> [u8; 32] {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    key
+}
+
+/// Pairwise key that wraps sender keys between two peers; both sides derive the same key
+fn wrapping_key(local_secret: &StaticSecret, remote: &PublicIdentity) -> Result<SecretBuffer<32>, CryptoError> {
+    let shared = local_secret.diffie_hellman(&remote.encryption_public_key()?);
+    Ok(SecretBuffer::new(blake3::derive_key(KEY_WRAP_CONTEXT, shared.as_bytes())))
+}
+
+/// Encrypt as nonce || ciphertext
+fn seal(key: &SecretBuffer<32>, aad: &[u8], plaintext: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    let cipher = ChaCha20Poly1305::new(key.as_bytes().into());
+    let mut nonce = [0u8; 12];
+    OsRng.fill_bytes(&mut nonce);
+    
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad })
+        .map_err(|_| CryptoError::EncryptionFailed)?;
+    
+    let mut sealed = nonce.to_vec();
+    sealed.extend_from_slice(&ciphertext);
+    Ok(sealed)
+}
+
+fn open(key: &SecretBuffer<32>, aad: &[u8], sealed: &[u8]) -> Result<Vec<u8>, CryptoError> {
+    if sealed.len() < 12 {
+        return Err(CryptoError::DecryptionFailed);
+    }
+    let (nonce, ciphertext) = sealed.split_at(12);
+    
+    ChaCha20Poly1305::new(key.as_bytes().into())
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+        .map_err(|_| CryptoError::DecryptionFailed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_sender_keys_encrypt_once_for_all_members() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let carol = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let carol_public = PublicIdentity::from_identity(&carol);
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut alice_group = GroupSession::new("otters", &alice);
+        alice_group.add_member(bob_public.clone());
+        alice_group.add_member(carol_public.clone());
+        let welcome = alice_group.key_bundle(&alice).unwrap();
+        
+        let mut bob_group = GroupSession::join("otters", &bob, vec![carol_public.clone()], &welcome, &alice_public).unwrap();
+        let mut carol_group = GroupSession::join("otters", &carol, vec![bob_public.clone()], &welcome, &alice_public).unwrap();
+        
+        // Bob distributes his sender key; Carol installs it, Alice gets it later
+        let bob_key = bob_group.sender_key_message().unwrap();
+        assert_eq!(bob_key.encrypted.len(), 2);
+        carol_group.install_sender_key(&bob_id, &bob_key).unwrap();
+        
+        let first = bob_group.encrypt(b"one").unwrap();
+        let second = bob_group.encrypt(b"two").unwrap();
+        assert!(matches!(alice_group.decrypt(&bob_id, &first), Err(CryptoError::NotGroupMember(_))));
+        
+        // Reordered messages decrypt once; the same ciphertext serves every member
+        assert_eq!(carol_group.decrypt(&bob_id, &second).unwrap(), b"two");
+        assert_eq!(carol_group.decrypt(&bob_id, &first).unwrap(), b"one");
+        assert!(matches!(carol_group.decrypt(&bob_id, &first), Err(CryptoError::ReplayAttack)));
+        
+        // A late joiner of Bob's chain cannot read what came before it
+        alice_group.install_sender_key(&bob_id, &bob_group.sender_key_message().unwrap()).unwrap();
+        let third = bob_group.encrypt(b"three").unwrap();
+        assert!(matches!(alice_group.decrypt(&bob_id, &first), Err(CryptoError::ReplayAttack)));
+        assert_eq!(alice_group.decrypt(&bob_id, &third).unwrap(), b"three");
+        
+        // Carol holds Bob's chain key but cannot forge a message from him
+        let mut forged = carol_group.encrypt(b"from bob, honest").unwrap();
+        forged.message_counter = 3;
+        assert!(matches!(alice_group.decrypt(&bob_id, &forged), Err(CryptoError::DecryptionFailed)));
+        
+        // Sender keys are bound to their group
+        let mut other_group = GroupSession::new("beavers", &carol);
+        other_group.add_member(bob_public.clone());
+        assert!(other_group.install_sender_key(&bob_id, &bob_group.sender_key_message().unwrap()).is_err());
+        assert!(!other_group.has_sender_key(&bob_id));
+    }
+    
+    #[test]
+    fn test_removed_member_cannot_decrypt_after_rekey() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let carol = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let carol_public = PublicIdentity::from_identity(&carol);
+        let alice_id = alice_public.peer_id().to_string();
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut alice_group = GroupSession::new("otters", &alice);
+        alice_group.add_member(bob_public.clone());
+        alice_group.add_member(carol_public.clone());
+        let welcome = alice_group.key_bundle(&alice).unwrap();
+        
+        let mut bob_group = GroupSession::join("otters", &bob, vec![carol_public.clone()], &welcome, &alice_public).unwrap();
+        let mut carol_group = GroupSession::join("otters", &carol, vec![bob_public.clone()], &welcome, &alice_public).unwrap();
+        let bob_key = bob_group.sender_key_message().unwrap();
+        alice_group.install_sender_key(&bob_id, &bob_key).unwrap();
+        carol_group.install_sender_key(&bob_id, &bob_key).unwrap();
+        
+        let before = alice_group.encrypt(b"hello everyone").unwrap();
+        assert_eq!(carol_group.decrypt(&alice_id, &before).unwrap(), b"hello everyone");
+        
+        // Alice removes Carol; Bob applies the rekey and answers with his rotated key
+        let bundle = alice_group.remove_member(carol_public.peer_id().as_str(), &alice).unwrap();
+        assert_eq!(bundle.rekey_counter, 1);
+        assert!(!bundle.sender_key.encrypted.contains_key(carol_public.peer_id().as_str()));
+        let bob_rotated = bob_group.apply_rekey(&bundle, &alice_public).unwrap();
+        assert!(!bob_group.is_member(carol_public.peer_id().as_str()));
+        assert_eq!(bob_rotated.generation, 1);
+        assert!(!bob_rotated.encrypted.contains_key(carol_public.peer_id().as_str()));
+        alice_group.install_sender_key(&bob_id, &bob_rotated).unwrap();
+        
+        let after = alice_group.encrypt(b"carol is gone").unwrap();
+        assert_eq!(bob_group.decrypt(&alice_id, &after).unwrap(), b"carol is gone");
+        assert!(carol_group.decrypt(&alice_id, &after).is_err());
+        let from_bob = bob_group.encrypt(b"bye carol").unwrap();
+        assert_eq!(alice_group.decrypt(&bob_id, &from_bob).unwrap(), b"bye carol");
+        assert!(carol_group.decrypt(&bob_id, &from_bob).is_err());
+        
+        // Replaying the same bundle or Bob's old sender key is rejected
+        assert!(matches!(bob_group.apply_rekey(&bundle, &alice_public), Err(CryptoError::ReplayAttack)));
+        assert!(matches!(alice_group.install_sender_key(&bob_id, &bob_key), Err(CryptoError::ReplayAttack)));
+    }
+    
+    #[test]
+    fn test_tampered_rekey_bundle_rejected() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let carol = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let carol_public = PublicIdentity::from_identity(&carol);
+        
+        let mut alice_group = GroupSession::new("otters", &alice);
+        alice_group.add_member(bob_public.clone());
+        alice_group.add_member(carol_public.clone());
+        let welcome = alice_group.key_bundle(&alice).unwrap();
+        let mut bob_group = GroupSession::join("otters", &bob, vec![carol_public.clone()], &welcome, &alice_public)
+            .unwrap();
+        
+        let mut bundle = alice_group.remove_member(carol_public.peer_id().as_str(), &alice).unwrap();
+        bundle.initiator_signature[0] ^= 0xFF;
+        assert!(matches!(
+            bob_group.apply_rekey(&bundle, &alice_public),
+            Err(CryptoError::IdentityError(IdentityError::InvalidSignature))
+        ));
+        assert_eq!(bob_group.rekey_counter(), 0);
+    }
+}
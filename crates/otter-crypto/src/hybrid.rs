@@ -0,0 +1,138 @@
+//! # Hybrid Key Exchange
+//!
+//! Combines static X25519 Diffie-Hellman with a Kyber768 encapsulation to the
+//! responder's identity, so a session key stays secret unless both X25519 and
+//! Kyber768 are broken. The shared secret is `BLAKE3(x25519 || kyber)`.
+//!
+//! Kyber decapsulation uses implicit rejection: a corrupted ciphertext of the
+//! right length yields an unrelated secret rather than an error, so tampering
+//! shows up as `DecryptionFailed` on the first message of the session.
+
+use crate::{CryptoError, SecretBuffer};
+use otter_identity::{Identity, PublicIdentity};
+use pqcrypto_kyber::kyber768;
+use pqcrypto_traits::kem::{Ciphertext as _, PublicKey as _, SecretKey as _, SharedSecret as _};
+use serde::{Deserialize, Serialize};
+use x25519_dalek::PublicKey as X25519PublicKey;
+
+/// Kyber768 ciphertext sent from the initiator to the responder
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KyberCiphertext(pub Vec<u8>);
+
+impl KyberCiphertext {
+    /// Borrow the ciphertext bytes
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// Shared secret of a hybrid key exchange, zeroized on drop
+#[derive(Debug)]
+pub struct HybridSharedSecret(SecretBuffer<32>);
+
+impl HybridSharedSecret {
+    fn combine(x25519: &[u8; 32], kyber: &[u8]) -> Self {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(x25519);
+        hasher.update(kyber);
+        Self(SecretBuffer::new(*hasher.finalize().as_bytes()))
+    }
+    
+    /// Borrow the secret bytes
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        self.0.as_bytes()
+    }
+    
+    pub(crate) fn into_inner(self) -> SecretBuffer<32> {
+        self.0
+    }
+}
+
+/// X25519 + Kyber768 key exchange between two identities
+pub struct HybridKeyExchange;
+
+impl HybridKeyExchange {
+    /// Derive a shared secret with `remote_public` and the ciphertext it needs to derive the same one
+    ///
+    /// Fails with `InvalidKey` if the remote identity carries no Kyber768 key.
+    pub fn initiator_send(
+        local_identity: &Identity,
+        remote_public: &PublicIdentity,
+    ) -> Result<(HybridSharedSecret, KyberCiphertext), CryptoError> {
+        let remote_x25519 = remote_public.encryption_public_key()?;
+        let remote_kyber = remote_public
+            .kyber_public_key()
+            .ok()
+            .and_then(|key| kyber768::PublicKey::from_bytes(key).ok())
+            .ok_or(CryptoError::InvalidKey)?;
+        
+        let x25519 = local_identity.encryption_secret_key().diffie_hellman(&remote_x25519);
+        let (kyber, ciphertext) = kyber768::encapsulate(&remote_kyber);
+        
+        Ok((
+            HybridSharedSecret::combine(x25519.as_bytes(), kyber.as_bytes()),
+            KyberCiphertext(ciphertext.as_bytes().to_vec()),
+        ))
+    }
+    
+    /// Derive the initiator's shared secret from its ciphertext and X25519 public key
+    ///
+    /// Fails with `InvalidKey` if the local identity has no Kyber768 key pair
+    /// yet, see [`Identity::add_kyber_keys`].
+    pub fn responder_receive(
+        local_identity: &Identity,
+        kyber_ciphertext: &KyberCiphertext,
+        x25519_public: &X25519PublicKey,
+    ) -> Result<HybridSharedSecret, CryptoError> {
+        let ciphertext = kyber768::Ciphertext::from_bytes(kyber_ciphertext.as_bytes())
+            .map_err(|_| CryptoError::DecryptionFailed)?;
+        if !local_identity.has_kyber_keys() {
+            return Err(CryptoError::InvalidKey);
+        }
+        let secret_key = kyber768::SecretKey::from_bytes(local_identity.kyber_secret_key())
+            .map_err(|_| CryptoError::InvalidKey)?;
+        
+        let x25519 = local_identity.encryption_secret_key().diffie_hellman(x25519_public);
+        let kyber = kyber768::decapsulate(&ciphertext, &secret_key);
+        
+        Ok(HybridSharedSecret::combine(x25519.as_bytes(), kyber.as_bytes()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CryptoSession;
+    
+    #[test]
+    fn test_hybrid_key_exchange() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let bob_public = PublicIdentity::from_identity(&bob);
+        
+        let (alice_secret, ciphertext) = HybridKeyExchange::initiator_send(&alice, &bob_public).unwrap();
+        let bob_secret = HybridKeyExchange::responder_receive(&bob, &ciphertext, alice.encryption_public_key()).unwrap();
+        assert_eq!(alice_secret.as_bytes(), bob_secret.as_bytes());
+        
+        let mut alice_session = CryptoSession::from_hybrid(alice_secret);
+        let mut bob_session = CryptoSession::from_hybrid(bob_secret);
+        assert_eq!(alice_session.fingerprint(), bob_session.fingerprint());
+        let encrypted = alice_session.encrypt(b"post-quantum hello", None).unwrap();
+        assert_eq!(bob_session.decrypt(&encrypted).unwrap(), b"post-quantum hello");
+        
+        // A flipped bit decapsulates to a different secret, so the first message fails to decrypt
+        let mut corrupted = ciphertext.clone();
+        corrupted.0[0] ^= 0x01;
+        let wrong_secret = HybridKeyExchange::responder_receive(&bob, &corrupted, alice.encryption_public_key()).unwrap();
+        assert!(matches!(
+            CryptoSession::from_hybrid(wrong_secret).decrypt(&encrypted),
+            Err(CryptoError::DecryptionFailed)
+        ));
+        
+        let truncated = KyberCiphertext(ciphertext.0[1..].to_vec());
+        assert!(matches!(
+            HybridKeyExchange::responder_receive(&bob, &truncated, alice.encryption_public_key()),
+            Err(CryptoError::DecryptionFailed)
+        ));
+    }
+}
@@ -0,0 +1,114 @@
+//! # Key Derivation
+//!
+//! Session keys are derived with BLAKE3 by default. HKDF-SHA256 (RFC 5869)
+//! is available for clients that need a standard KDF to interoperate. A
+//! session uses its KDF for its whole key schedule: the initial key, key
+//! rotations, and the root, chain and message keys of PFS sessions.
+
+use crate::CryptoError;
+use hkdf::Hkdf;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::str::FromStr;
+
+/// Key derivation function used by a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum KdfAlgorithm {
+    /// BLAKE3, keyed with the hash of the salt if one is given
+    #[default]
+    Blake3,
+    /// HKDF with SHA-256
+    HkdfSha256,
+}
+
+impl fmt::Display for KdfAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KdfAlgorithm::Blake3 => write!(f, "blake3"),
+            KdfAlgorithm::HkdfSha256 => write!(f, "hkdf-sha256"),
+        }
+    }
+}
+
+impl FromStr for KdfAlgorithm {
+    type Err = CryptoError;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "blake3" => Ok(KdfAlgorithm::Blake3),
+            "hkdf-sha256" => Ok(KdfAlgorithm::HkdfSha256),
+            _ => Err(CryptoError::UnsupportedKdf(s.to_string())),
+        }
+    }
+}
+
+/// Derive a 32-byte key from input keying material
+///
+/// With an empty salt and info, BLAKE3 derivation is a plain hash of `ikm`,
+/// which keeps keys compatible with sessions created before KDF selection.
+pub fn derive_key(algorithm: KdfAlgorithm, ikm: &[u8], salt: &[u8], info: &[u8]) -> [u8; 32] {
+    match algorithm {
+        KdfAlgorithm::Blake3 => {
+            let mut hasher = if salt.is_empty() {
+                blake3::Hasher::new()
+            } else {
+                blake3::Hasher::new_keyed(blake3::hash(salt).as_bytes())
+            };
+            hasher.update(ikm);
+            hasher.update(info);
+            *hasher.finalize().as_bytes()
+        }
+        KdfAlgorithm::HkdfSha256 => {
+            let salt = (!salt.is_empty()).then_some(salt);
+            let mut okm = [0u8; 32];
+            Hkdf::<Sha256>::new(salt, ikm)
+                .expand(info, &mut okm)
+                .expect("32 bytes is a valid HKDF-SHA256 output length");
+            okm
+        }
+    }
+}
+
+/// Derive a 32-byte key for one step of a session's key schedule
+///
+/// BLAKE3 sessions keep the derivation `blake3` computes, which they have
+/// always used, so they stay compatible with existing peers. Any other KDF
+/// derives from `ikm` with `info` as the label of the step.
+pub(crate) fn derive_step(
+    algorithm: KdfAlgorithm,
+    ikm: &[u8],
+    info: &[u8],
+    blake3: impl FnOnce(&[u8]) -> [u8; 32],
+) -> [u8; 32] {
+    match algorithm {
+        KdfAlgorithm::Blake3 => blake3(ikm),
+        _ => derive_key(algorithm, ikm, &[], info),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_derive_key() {
+        // RFC 5869 test case 1, first 32 bytes of OKM
+        let ikm = [0x0b; 22];
+        let salt: Vec<u8> = (0x00..=0x0c).collect();
+        let info: Vec<u8> = (0xf0..=0xf9).collect();
+        assert_eq!(
+            hex::encode(derive_key(KdfAlgorithm::HkdfSha256, &ikm, &salt, &info)),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf"
+        );
+        
+        assert_eq!(&derive_key(KdfAlgorithm::Blake3, b"secret", &[], &[]), blake3::hash(b"secret").as_bytes());
+        assert_ne!(
+            derive_key(KdfAlgorithm::Blake3, b"secret", b"salt", &[]),
+            derive_key(KdfAlgorithm::Blake3, b"secret", &[], &[])
+        );
+        
+        assert_eq!("HKDF-SHA256".parse::<KdfAlgorithm>().unwrap(), KdfAlgorithm::HkdfSha256);
+        assert!(matches!("scrypt".parse::<KdfAlgorithm>(), Err(CryptoError::UnsupportedKdf(_))));
+    }
+}
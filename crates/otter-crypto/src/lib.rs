@@ -8,17 +8,49 @@
 //! - Secure message encryption and decryption
 //! - Key derivation and management
 //! - Perfect Forward Secrecy with ephemeral keys
-//! - Simple key ratcheting for session security
+//! - Double Ratchet (DH and symmetric-key ratchets) for PFS sessions
+//! - Zeroization of key material on drop
+//! - Group sessions using Sender Keys, rotated on member removal
+//! - Selectable key derivation (BLAKE3 or HKDF-SHA256)
+//! - Hybrid X25519 + Kyber768 key exchange against quantum adversaries
+//! - Session key logging for Wireshark (`key_export` feature, debug builds only)
+//! - Plaintext padding that hides message lengths
+//! - A sliding replay window that tolerates reordered and dropped messages
 
 use chacha20poly1305::{
     aead::{Aead, KeyInit, Payload},
     ChaCha20Poly1305, Nonce,
 };
-use otter_identity::{Identity, IdentityError, PublicIdentity};
+use otter_identity::{EphemeralKeyAttestation, Identity, IdentityError, PublicIdentity};
 use rand::{rngs::OsRng, RngCore};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
 use thiserror::Error;
-use x25519_dalek::{EphemeralSecret, PublicKey as X25519PublicKey, SharedSecret};
+use tokio::time::Instant;
+use x25519_dalek::{PublicKey as X25519PublicKey, SharedSecret, StaticSecret};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+#[cfg(all(feature = "key_export", not(debug_assertions)))]
+compile_error!("the key_export feature leaks session keys and must not be enabled in release builds");
+
+pub mod group;
+pub mod hybrid;
+pub mod kdf;
+pub mod padding;
+pub mod replay;
+pub mod secret;
+pub mod sender_key;
+pub use group::{GroupRekeyBundle, GroupSession};
+pub use hybrid::{HybridKeyExchange, HybridSharedSecret, KyberCiphertext};
+pub use kdf::KdfAlgorithm;
+pub use padding::PaddingStrategy;
+pub use replay::{ReplayWindow, DEFAULT_REPLAY_WINDOW};
+pub use secret::SecretBuffer;
+pub use sender_key::SenderKeyMessage;
 
 #[derive(Error, Debug)]
 pub enum CryptoError {
@@ -36,6 +68,12 @@ pub enum CryptoError {
     ReplayAttack,
     #[error("Message counter overflow")]
     CounterOverflow,
+    #[error("Not a group member: {0}")]
+    NotGroupMember(String),
+    #[error("Unsupported KDF: {0}")]
+    UnsupportedKdf(String),
+    #[error("Session expired")]
+    SessionExpired,
 }
 
 /// Encrypted message envelope with replay protection
@@ -56,40 +94,231 @@ pub struct EncryptedMessage {
     /// Optional timestamp (signed as part of AAD)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub timestamp: Option<i64>,
+    /// Sender's current ratchet public key (PFS sessions only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ratchet_public: Option<Vec<u8>>,
+    /// Length of the sender's previous sending chain (PFS sessions only)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_chain_length: Option<u64>,
+}
+
+/// Short session fingerprint for out-of-band verification
+///
+/// Equality is constant-time so comparing against an expected value does not
+/// leak how many leading bytes matched.
+#[derive(Clone, Copy)]
+pub struct Fingerprint(pub [u8; 8]);
+
+impl Fingerprint {
+    /// Derive a fingerprint from a shared secret
+    fn from_secret(secret: &[u8; 32]) -> Self {
+        let hash = blake3::hash(secret);
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&hash.as_bytes()[..8]);
+        Self(bytes)
+    }
+    
+    /// Check against an expected fingerprint in constant time
+    pub fn verify(&self, expected: &Fingerprint) -> bool {
+        constant_time_eq::constant_time_eq_n(&self.0, &expected.0)
+    }
+    
+    /// Hex encoding for display
+    pub fn as_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+}
+
+impl PartialEq for Fingerprint {
+    fn eq(&self, other: &Self) -> bool {
+        self.verify(other)
+    }
+}
+
+impl Eq for Fingerprint {}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_hex())
+    }
+}
+
+impl fmt::Debug for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Fingerprint({})", self.as_hex())
+    }
 }
 
 /// Manages encryption sessions between peers
 ///
 /// Uses X25519 ECDH for key exchange and ChaCha20-Poly1305 for encryption.
 /// Note: For Perfect Forward Secrecy, use PFSSession instead.
+///
+/// The shared secret and cipher key are zeroized when the session is dropped.
+#[derive(ZeroizeOnDrop)]
 pub struct CryptoSession {
-    shared_secret: SharedSecret,
-    cipher_key: [u8; 32],
+    shared_secret: SecretBuffer<32>,
+    cipher_key: SecretBuffer<32>,
+    #[zeroize(skip)]
     send_counter: u64,
-    receive_counter: u64,
+    #[zeroize(skip)]
+    receive_window: ReplayWindow,
+    #[zeroize(skip)]
+    kdf: KdfAlgorithm,
+    #[zeroize(skip)]
+    padding: PaddingStrategy,
+    #[zeroize(skip)]
+    created_at: Instant,
+    /// Age after which `encrypt` refuses, if limited
+    #[zeroize(skip)]
+    max_lifetime: Option<Duration>,
+    /// Unix time of session creation or the last `rotate_key`
+    #[zeroize(skip)]
+    rotated_at: i64,
+    /// SSLKEYLOGFILE-style log that every encrypt and decrypt appends to
+    #[cfg(feature = "key_export")]
+    #[zeroize(skip)]
+    key_log: Option<std::fs::File>,
 }
 
 impl CryptoSession {
     /// Create a new crypto session between local and remote peer
     ///
-    /// Performs X25519 Diffie-Hellman key exchange and derives a shared secret.
+    /// Performs X25519 Diffie-Hellman key exchange and derives the cipher key
+    /// with `kdf`. Both peers must use the same KDF.
     pub fn new(
         local_identity: &Identity,
         remote_public: &PublicIdentity,
+        kdf: KdfAlgorithm,
+    ) -> Result<Self, CryptoError> {
+        Self::with_replay_window(local_identity, remote_public, kdf, DEFAULT_REPLAY_WINDOW)
+    }
+    
+    /// Create a session that accepts messages up to `replay_window` counters out of order
+    pub fn with_replay_window(
+        local_identity: &Identity,
+        remote_public: &PublicIdentity,
+        kdf: KdfAlgorithm,
+        replay_window: u64,
     ) -> Result<Self, CryptoError> {
         let remote_key = remote_public.encryption_public_key()?;
         let shared_secret = local_identity.encryption_secret_key().diffie_hellman(&remote_key);
         
-        // Derive cipher key from shared secret using BLAKE3
-        let hash = blake3::hash(shared_secret.as_bytes());
-        let cipher_key: [u8; 32] = *hash.as_bytes();
+        Ok(Self::from_shared_secret(SecretBuffer::new(shared_secret.to_bytes()), kdf, replay_window))
+    }
+    
+    /// Create a session keyed with the result of a hybrid key exchange
+    ///
+    /// Uses the default KDF; both peers must run the same exchange.
+    pub fn from_hybrid(secret: HybridSharedSecret) -> Self {
+        Self::from_shared_secret(secret.into_inner(), KdfAlgorithm::default(), DEFAULT_REPLAY_WINDOW)
+    }
+    
+    fn from_shared_secret(shared_secret: SecretBuffer<32>, kdf: KdfAlgorithm, replay_window: u64) -> Self {
+        let cipher_key = SecretBuffer::new(kdf::derive_key(kdf, shared_secret.as_bytes(), &[], &[]));
         
-        Ok(Self {
+        Self {
             shared_secret,
             cipher_key,
             send_counter: 0,
-            receive_counter: 0,
-        })
+            receive_window: ReplayWindow::new(replay_window),
+            kdf,
+            padding: PaddingStrategy::None,
+            created_at: Instant::now(),
+            max_lifetime: None,
+            rotated_at: chrono::Utc::now().timestamp(),
+            #[cfg(feature = "key_export")]
+            key_log: None,
+        }
+    }
+    
+    /// Append the cipher key to `path` after every encrypt and decrypt
+    ///
+    /// Each line reads `CLIENT_RANDOM <nonce_hex> <cipher_key_hex>`. Write
+    /// errors are ignored so that logging never breaks a session.
+    #[cfg(feature = "key_export")]
+    pub fn enable_key_export(&mut self, path: &Path) -> Result<(), io::Error> {
+        let file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        self.key_log = Some(file);
+        Ok(())
+    }
+    
+    /// Key export is compiled out; always fails with `Unsupported`
+    #[cfg(not(feature = "key_export"))]
+    pub fn enable_key_export(&mut self, _path: &Path) -> Result<(), io::Error> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "key export disabled"))
+    }
+    
+    #[cfg(feature = "key_export")]
+    fn log_key(&mut self, nonce: &[u8]) {
+        use std::io::Write;
+        
+        if let Some(log) = self.key_log.as_mut() {
+            let _ = writeln!(log, "CLIENT_RANDOM {} {}", hex::encode(nonce), hex::encode(self.cipher_key.as_bytes()));
+        }
+    }
+    
+    #[cfg(not(feature = "key_export"))]
+    fn log_key(&mut self, _nonce: &[u8]) {}
+    
+    /// Key derivation function this session was created with
+    pub fn kdf(&self) -> KdfAlgorithm {
+        self.kdf
+    }
+    
+    /// Pad plaintexts with `strategy` before encryption and strip it after decryption
+    ///
+    /// Both peers must use padding (any strategy other than `None`) or neither.
+    pub fn set_padding_strategy(&mut self, strategy: PaddingStrategy) {
+        self.padding = strategy;
+    }
+    
+    /// Padding applied to plaintexts
+    pub fn padding_strategy(&self) -> PaddingStrategy {
+        self.padding
+    }
+    
+    /// Refuse to encrypt once the session is `duration` old
+    ///
+    /// Decryption keeps working, so messages already in flight can be read.
+    pub fn set_expiry(&mut self, duration: Duration) {
+        self.max_lifetime = Some(duration);
+    }
+    
+    /// Whether the session outlived the limit set with `set_expiry`
+    pub fn is_expired(&self) -> bool {
+        self.max_lifetime.is_some_and(|lifetime| self.created_at.elapsed() >= lifetime)
+    }
+    
+    /// Replace the cipher key with one derived from it and `context`, without a new DH
+    ///
+    /// Both counters restart at zero, so messages encrypted before the
+    /// rotation no longer decrypt. Both sides must call `rotate_key` with an
+    /// identical `context` at the same point in the conversation, or the
+    /// session falls out of sync. Fails with `InvalidKey` if `context` is empty.
+    pub fn rotate_key(&mut self, context: &[u8]) -> Result<(), CryptoError> {
+        if context.is_empty() {
+            return Err(CryptoError::InvalidKey);
+        }
+        
+        let mut key_material = Vec::with_capacity(32 + context.len());
+        key_material.extend_from_slice(self.cipher_key.as_bytes());
+        key_material.extend_from_slice(context);
+        self.cipher_key = SecretBuffer::new(kdf::derive_step(self.kdf, &key_material, b"otter-session-rotation-v1", |ikm| {
+            blake3::derive_key("otter-session-rotation-v1", ikm)
+        }));
+        key_material.zeroize();
+        
+        self.send_counter = 0;
+        self.receive_window = ReplayWindow::new(self.receive_window.size());
+        self.rotated_at = chrono::Utc::now().timestamp();
+        Ok(())
+    }
+    
+    /// Whether `max_messages` were sent or `max_age_secs` passed since the last rotation
+    pub fn rotation_due(&self, max_messages: u64, max_age_secs: i64) -> bool {
+        self.send_counter >= max_messages
+            || chrono::Utc::now().timestamp() - self.rotated_at >= max_age_secs
     }
     
     /// Encrypt a message with optional associated data
@@ -100,11 +329,14 @@ impl CryptoSession {
         plaintext: &[u8],
         associated_data: Option<&[u8]>,
     ) -> Result<EncryptedMessage, CryptoError> {
+        if self.is_expired() {
+            return Err(CryptoError::SessionExpired);
+        }
         if self.send_counter == u64::MAX {
             return Err(CryptoError::CounterOverflow);
         }
         
-        let cipher = ChaCha20Poly1305::new(&self.cipher_key.into());
+        let cipher = ChaCha20Poly1305::new(self.cipher_key.as_bytes().into());
         
         // Generate random nonce
         let mut nonce_bytes = [0u8; 12];
@@ -118,8 +350,9 @@ impl CryptoSession {
             aad.extend_from_slice(ad);
         }
         
+        let padded = self.padding.pad(plaintext);
         let payload = Payload {
-            msg: plaintext,
+            msg: &padded,
             aad: &aad,
         };
         
@@ -130,6 +363,7 @@ impl CryptoSession {
         
         let message_counter = self.send_counter;
         self.send_counter += 1;
+        self.log_key(&nonce_bytes);
         
         Ok(EncryptedMessage {
             nonce: nonce_bytes.to_vec(),
@@ -137,19 +371,19 @@ impl CryptoSession {
             associated_data: associated_data.map(|ad| ad.to_vec()),
             message_counter,
             timestamp: Some(chrono::Utc::now().timestamp()),
+            ratchet_public: None,
+            previous_chain_length: None,
         })
     }
     
     /// Decrypt an encrypted message
+    ///
+    /// Messages may arrive out of order within the replay window; each counter
+    /// is accepted once.
     pub fn decrypt(&mut self, encrypted: &EncryptedMessage) -> Result<Vec<u8>, CryptoError> {
-        // Replay protection: ensure counter is strictly increasing
-        // But allow same counter if it's 0 (first message from fresh session)
-        if encrypted.message_counter < self.receive_counter || 
-           (encrypted.message_counter == self.receive_counter && self.receive_counter > 0) {
-            return Err(CryptoError::ReplayAttack);
-        }
+        self.receive_window.check(encrypted.message_counter)?;
         
-        let cipher = ChaCha20Poly1305::new(&self.cipher_key.into());
+        let cipher = ChaCha20Poly1305::new(self.cipher_key.as_bytes().into());
         
         // Reconstruct nonce
         let nonce_bytes: [u8; 12] = encrypted
@@ -171,110 +405,261 @@ impl CryptoSession {
             aad: &aad,
         };
         
-        // Decrypt
+        // Decrypt, then check and strip the padding
         let plaintext = cipher
             .decrypt(nonce, payload)
             .map_err(|_| CryptoError::DecryptionFailed)?;
+        let plaintext = self.padding.unpad(plaintext)?;
         
-        self.receive_counter = encrypted.message_counter;
+        self.receive_window.accept(encrypted.message_counter);
+        self.log_key(&nonce_bytes);
         
         Ok(plaintext)
     }
     
     /// Get the shared secret fingerprint (for verification)
-    pub fn fingerprint(&self) -> String {
-        let hash = blake3::hash(self.shared_secret.as_bytes());
-        hex::encode(&hash.as_bytes()[..8])
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::from_secret(self.shared_secret.as_bytes())
+    }
+    
+    /// Full 32-byte BLAKE3 hash of the shared secret, of which `fingerprint` is a prefix
+    ///
+    /// Both peers derive the same value, so it can be compared out-of-band
+    /// (for example as a word mnemonic) to rule out a man in the middle.
+    pub fn safety_number(&self) -> [u8; 32] {
+        *blake3::hash(self.shared_secret.as_bytes()).as_bytes()
     }
 }
 
-/// Perfect Forward Secrecy session with ephemeral keys and ratcheting
+/// Most message keys a PFS message may skip in one receiving chain
+///
+/// Bounds the ratcheting a single (possibly forged) message can trigger.
+pub const MAX_SKIPPED_MESSAGES: u64 = 1000;
+
+/// Receiving chains are identified by the remote ratchet public key that opened them
+type ChainIndex = [u8; 32];
+
+/// Position of a message within its chain
+type MessageIndex = u64;
+
+/// Message keys derived while ratcheting past messages that have not arrived
+type SkippedKeys = Vec<((ChainIndex, MessageIndex), SecretBuffer<32>)>;
+
+/// Perfect Forward Secrecy session using the Double Ratchet
+///
+/// Provides PFS and post-compromise security through:
+/// - Ephemeral X25519 key pairs for the handshake
+/// - A DH ratchet: every time the remote sends a new ratchet public key,
+///   a fresh key pair is generated and the root key and both chain keys move on
+/// - A symmetric KDF ratchet deriving one key per message within a chain
+/// - A replay window over the current receiving chain
+///
+/// Each message carries the sender's ratchet public key. The initiator opens
+/// the first DH step; the responder can send on its initial chain until it
+/// hears from the initiator. Keys of skipped messages are cached, including
+/// those of the previous receiving chain, so messages reordered across a
+/// ratchet step still decrypt.
 ///
-/// Provides session-level PFS through:
-/// - Ephemeral X25519 key pairs for each session
-/// - Key ratcheting on message exchange
-/// - Message counter for replay protection
+/// Ratchet keys are `StaticSecret`s because each takes part in two DH
+/// computations: one for the sending chain it opens and one for the receiving
+/// chain opened by the remote's reply. The static secret, root key, ratchet
+/// secret and chain keys are zeroized when the session is dropped, and each
+/// ratchet step wipes the keys it replaces.
+#[derive(ZeroizeOnDrop)]
 pub struct PFSSession {
     /// Static identity-based shared secret (for authentication)
     static_secret: SharedSecret,
     
-    /// Current ephemeral shared secret (ratcheted)
-    ephemeral_secret: SharedSecret,
+    /// Root key, advanced by every DH ratchet step
+    root_key: SecretBuffer<32>,
+    
+    /// Our current ratchet key pair
+    ratchet_secret: StaticSecret,
+    #[zeroize(skip)]
+    ratchet_public: X25519PublicKey,
+    
+    /// Remote ratchet public key of the current receiving chain
+    #[zeroize(skip)]
+    remote_ratchet_public: ChainIndex,
+    
+    /// Remote ratchet public key of the receiving chain before it
+    #[zeroize(skip)]
+    closed_chain: Option<ChainIndex>,
     
     /// Current sending chain key
-    sending_chain_key: [u8; 32],
+    sending_chain_key: SecretBuffer<32>,
     
     /// Current receiving chain key
-    receiving_chain_key: [u8; 32],
+    receiving_chain_key: SecretBuffer<32>,
     
-    /// Message counter for sending (monotonically increasing)
-    send_counter: u64,
+    /// Index of the next message in the sending chain
+    #[zeroize(skip)]
+    send_counter: MessageIndex,
+    
+    /// Length of the previous sending chain, sent so the remote can finish it
+    #[zeroize(skip)]
+    previous_sending_length: MessageIndex,
     
-    /// Last received message counter (for replay protection)
-    receive_counter: u64,
+    /// Indices already received in the current receiving chain (for replay protection)
+    #[zeroize(skip)]
+    receive_window: ReplayWindow,
+    
+    /// Index whose message key `receiving_chain_key` derives
+    #[zeroize(skip)]
+    receiving_chain_index: MessageIndex,
+    
+    /// Message keys of skipped messages in the current and the closed receiving chain
+    #[zeroize(skip)]
+    skipped_keys: HashMap<(ChainIndex, MessageIndex), SecretBuffer<32>>,
     
     /// Ephemeral public key to share with peer
+    #[zeroize(skip)]
     pub ephemeral_public: X25519PublicKey,
+    
+    /// KDF of the root, chain and message key derivations
+    #[zeroize(skip)]
+    kdf: KdfAlgorithm,
 }
 
 impl PFSSession {
     /// Create a new PFS session with ephemeral handshake
     ///
     /// This performs:
-    /// 1. Static DH (identity keys) for authentication
-    /// 2. Ephemeral DH for PFS
-    /// 3. Derives independent sending/receiving chain keys
+    /// 1. Verification of the remote ephemeral key attestation
+    /// 2. Static DH (identity keys) for authentication
+    /// 3. Ephemeral DH for PFS
+    /// 4. Derives the root key and the initial chain keys
+    /// 5. For the initiator, the first DH ratchet step
     ///
     /// IMPORTANT: The role (initiator vs responder) determines which chain key is used for sending/receiving
     pub fn new(
         local_identity: &Identity,
         remote_public: &PublicIdentity,
-        local_ephemeral: EphemeralSecret,
-        remote_ephemeral: &X25519PublicKey,
+        local_ephemeral: StaticSecret,
+        remote_attestation: &EphemeralKeyAttestation,
         is_initiator: bool,
     ) -> Result<Self, CryptoError> {
+        Self::with_kdf(
+            local_identity,
+            remote_public,
+            local_ephemeral,
+            remote_attestation,
+            is_initiator,
+            KdfAlgorithm::default(),
+        )
+    }
+    
+    /// Create a PFS session whose key schedule uses `kdf`; both peers must use the same KDF
+    pub fn with_kdf(
+        local_identity: &Identity,
+        remote_public: &PublicIdentity,
+        local_ephemeral: StaticSecret,
+        remote_attestation: &EphemeralKeyAttestation,
+        is_initiator: bool,
+        kdf: KdfAlgorithm,
+    ) -> Result<Self, CryptoError> {
+        Self::with_replay_window(
+            local_identity,
+            remote_public,
+            local_ephemeral,
+            remote_attestation,
+            is_initiator,
+            kdf,
+            DEFAULT_REPLAY_WINDOW,
+        )
+    }
+    
+    /// Create a PFS session that accepts messages up to `replay_window` indices out of order
+    pub fn with_replay_window(
+        local_identity: &Identity,
+        remote_public: &PublicIdentity,
+        local_ephemeral: StaticSecret,
+        remote_attestation: &EphemeralKeyAttestation,
+        is_initiator: bool,
+        kdf: KdfAlgorithm,
+        replay_window: u64,
+    ) -> Result<Self, CryptoError> {
+        // Reject ephemeral keys the remote identity did not sign
+        remote_public.verify_attestation(remote_attestation)?;
+        let remote_ephemeral = remote_attestation.ephemeral_public_key();
+        
         // Static DH for authentication
         let remote_key = remote_public.encryption_public_key()?;
         let static_secret = local_identity.encryption_secret_key().diffie_hellman(&remote_key);
         
         // Ephemeral DH for PFS
         let ephemeral_public = X25519PublicKey::from(&local_ephemeral);
-        let ephemeral_secret = local_ephemeral.diffie_hellman(remote_ephemeral);
+        let ephemeral_secret = local_ephemeral.diffie_hellman(&remote_ephemeral);
         
         // Derive root key from both secrets (KDF chain)
         let mut root_key_material = Vec::new();
         root_key_material.extend_from_slice(static_secret.as_bytes());
         root_key_material.extend_from_slice(ephemeral_secret.as_bytes());
-        root_key_material.extend_from_slice(b"otter-pfs-v1");
         
-        let root_key = blake3::hash(&root_key_material);
+        let root_key = SecretBuffer::new(kdf::derive_step(kdf, &root_key_material, b"otter-pfs-v1 root", |ikm| {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(ikm);
+            hasher.update(b"otter-pfs-v1");
+            *hasher.finalize().as_bytes()
+        }));
+        root_key_material.zeroize();
         
         // Derive chain keys for both directions
-        let chain_key_0 = blake3::derive_key("chain-0", root_key.as_bytes());
-        let chain_key_1 = blake3::derive_key("chain-1", root_key.as_bytes());
-        
-        // Initiator sends on chain-0, receives on chain-1
-        // Responder sends on chain-1, receives on chain-0
+        let chain_key_0 = SecretBuffer::new(kdf::derive_step(kdf, root_key.as_bytes(), b"otter-pfs-v1 chain-0", |ikm| {
+            blake3::derive_key("chain-0", ikm)
+        }));
+        let chain_key_1 = SecretBuffer::new(kdf::derive_step(kdf, root_key.as_bytes(), b"otter-pfs-v1 chain-1", |ikm| {
+            blake3::derive_key("chain-1", ikm)
+        }));
+        
+        // Initial chains belong to the ephemeral keys: the initiator's chain-0
+        // is replaced by the first DH step below, the responder sends on
+        // chain-1 until the initiator's first ratchet key arrives
         let (sending_chain_key, receiving_chain_key) = if is_initiator {
             (chain_key_0, chain_key_1)
         } else {
             (chain_key_1, chain_key_0)
         };
         
-        Ok(Self {
+        let mut session = Self {
             static_secret,
-            ephemeral_secret,
+            root_key,
+            ratchet_secret: local_ephemeral,
+            ratchet_public: ephemeral_public,
+            remote_ratchet_public: remote_ephemeral.to_bytes(),
+            closed_chain: None,
             sending_chain_key,
             receiving_chain_key,
             send_counter: 0,
-            receive_counter: 0,
+            previous_sending_length: 0,
+            receive_window: ReplayWindow::new(replay_window),
+            receiving_chain_index: 0,
+            skipped_keys: HashMap::new(),
             ephemeral_public,
-        })
+            kdf,
+        };
+        if is_initiator {
+            session.ratchet_sending_chain();
+        }
+        Ok(session)
     }
     
     /// Generate a new ephemeral secret for initiating a session
-    pub fn generate_ephemeral() -> EphemeralSecret {
-        EphemeralSecret::random_from_rng(OsRng)
+    ///
+    /// The responder's ephemeral key doubles as its first ratchet key, so it
+    /// must survive a second DH computation.
+    pub fn generate_ephemeral() -> StaticSecret {
+        StaticSecret::random_from_rng(OsRng)
+    }
+    
+    /// Our current ratchet public key, sent with every message
+    pub fn ratchet_public(&self) -> X25519PublicKey {
+        self.ratchet_public
+    }
+    
+    /// Key derivation function of this session
+    pub fn kdf(&self) -> KdfAlgorithm {
+        self.kdf
     }
     
     /// Encrypt a message with PFS and replay protection
@@ -288,12 +673,7 @@ impl PFSSession {
             return Err(CryptoError::CounterOverflow);
         }
         
-        // Derive message key from chain key and counter
-        let mut key_material = Vec::new();
-        key_material.extend_from_slice(&self.sending_chain_key);
-        key_material.extend_from_slice(&self.send_counter.to_le_bytes());
-        let message_key = blake3::hash(&key_material);
-        
+        let message_key = Self::message_key(self.kdf, &self.sending_chain_key, self.send_counter);
         let cipher = ChaCha20Poly1305::new(message_key.as_bytes().into());
         
         // Generate random nonce
@@ -301,12 +681,14 @@ impl PFSSession {
         OsRng.fill_bytes(&mut nonce_bytes);
         let nonce = Nonce::from_slice(&nonce_bytes);
         
-        // Include counter in AAD for authentication
-        let mut aad = Vec::new();
-        aad.extend_from_slice(&self.send_counter.to_le_bytes());
-        if let Some(ad) = associated_data {
-            aad.extend_from_slice(ad);
-        }
+        // Authenticate the ratchet header along with the message
+        let ratchet_public = self.ratchet_public.to_bytes();
+        let aad = Self::header_aad(
+            self.send_counter,
+            &ratchet_public,
+            self.previous_sending_length,
+            associated_data,
+        );
         
         let payload = Payload {
             msg: plaintext,
@@ -322,7 +704,7 @@ impl PFSSession {
         
         // Increment counter and ratchet chain key
         self.send_counter += 1;
-        self.ratchet_sending_chain();
+        self.sending_chain_key = Self::next_chain_key(self.kdf, &self.sending_chain_key);
         
         Ok(EncryptedMessage {
             nonce: nonce_bytes.to_vec(),
@@ -330,23 +712,70 @@ impl PFSSession {
             associated_data: associated_data.map(|ad| ad.to_vec()),
             message_counter,
             timestamp: Some(chrono::Utc::now().timestamp()),
+            ratchet_public: Some(ratchet_public.to_vec()),
+            previous_chain_length: Some(self.previous_sending_length),
         })
     }
     
     /// Decrypt a message with replay protection
+    ///
+    /// A message under a new remote ratchet key closes the current receiving
+    /// chain (caching the keys of its messages that have not arrived yet),
+    /// performs a DH ratchet step and starts a fresh sending chain. Within a
+    /// chain, messages may arrive out of order inside the replay window.
     pub fn decrypt(&mut self, encrypted: &EncryptedMessage) -> Result<Vec<u8>, CryptoError> {
-        // Replay protection: ensure counter is strictly increasing
-        // But allow same counter if it's 0 (first message from fresh session)
-        if encrypted.message_counter < self.receive_counter || 
-           (encrypted.message_counter == self.receive_counter && self.receive_counter > 0) {
+        let remote: ChainIndex = encrypted
+            .ratchet_public
+            .as_deref()
+            .and_then(|key| key.try_into().ok())
+            .ok_or(CryptoError::DecryptionFailed)?;
+        let counter = encrypted.message_counter;
+        let previous_chain_length = encrypted.previous_chain_length.unwrap_or(0);
+        
+        // Work on copies so a forged message cannot move any chain
+        let mut root_step = None;
+        let mut advanced = None;
+        let message_key = if let Some(message_key) = self.skipped_keys.get(&(remote, counter)) {
+            if remote == self.remote_ratchet_public {
+                self.receive_window.check(counter)?;
+            }
+            message_key.clone()
+        } else if remote == self.remote_ratchet_public {
+            self.receive_window.check(counter)?;
+            if counter < self.receiving_chain_index {
+                // Behind the chain and not skipped: already received, or the key was pruned
+                return Err(CryptoError::ReplayAttack);
+            }
+            let (chain_key, skipped) = Self::skip_chain(
+                self.kdf,
+                &self.receiving_chain_key,
+                self.receiving_chain_index,
+                counter,
+                remote,
+            )?;
+            let message_key = Self::message_key(self.kdf, &chain_key, counter);
+            advanced = Some((chain_key, skipped));
+            message_key
+        } else if self.closed_chain == Some(remote) {
             return Err(CryptoError::ReplayAttack);
-        }
-        
-        // Derive message key from chain key and counter
-        let mut key_material = Vec::new();
-        key_material.extend_from_slice(&self.receiving_chain_key);
-        key_material.extend_from_slice(&encrypted.message_counter.to_le_bytes());
-        let message_key = blake3::hash(&key_material);
+        } else {
+            // New remote ratchet key: finish the current chain, then step the root
+            let (_, mut skipped) = Self::skip_chain(
+                self.kdf,
+                &self.receiving_chain_key,
+                self.receiving_chain_index,
+                previous_chain_length.max(self.receiving_chain_index),
+                self.remote_ratchet_public,
+            )?;
+            let dh = self.ratchet_secret.diffie_hellman(&X25519PublicKey::from(remote));
+            let (root_key, chain_key) = Self::ratchet_root(self.kdf, &self.root_key, dh.as_bytes());
+            let (chain_key, new_skipped) = Self::skip_chain(self.kdf, &chain_key, 0, counter, remote)?;
+            skipped.extend(new_skipped);
+            let message_key = Self::message_key(self.kdf, &chain_key, counter);
+            root_step = Some(root_key);
+            advanced = Some((chain_key, skipped));
+            message_key
+        };
         
         let cipher = ChaCha20Poly1305::new(message_key.as_bytes().into());
         
@@ -358,12 +787,13 @@ impl PFSSession {
             .map_err(|_| CryptoError::DecryptionFailed)?;
         let nonce = Nonce::from_slice(&nonce_bytes);
         
-        // Reconstruct AAD with counter
-        let mut aad = Vec::new();
-        aad.extend_from_slice(&encrypted.message_counter.to_le_bytes());
-        if let Some(ref ad) = encrypted.associated_data {
-            aad.extend_from_slice(ad);
-        }
+        // Reconstruct AAD from the ratchet header
+        let aad = Self::header_aad(
+            counter,
+            &remote,
+            previous_chain_length,
+            encrypted.associated_data.as_deref(),
+        );
         
         let payload = Payload {
             msg: &encrypted.ciphertext,
@@ -375,35 +805,140 @@ impl PFSSession {
             .decrypt(nonce, payload)
             .map_err(|_| CryptoError::DecryptionFailed)?;
         
-        // Update receive counter
-        self.receive_counter = encrypted.message_counter;
-        
-        // Ratchet receiving chain key
-        self.ratchet_receiving_chain();
+        if let Some(root_key) = root_step {
+            // Only the chain being closed keeps its skipped keys
+            let closed = self.remote_ratchet_public;
+            self.skipped_keys.retain(|(chain, _), _| *chain == closed);
+            self.closed_chain = Some(closed);
+            self.root_key = root_key;
+            self.remote_ratchet_public = remote;
+            self.receive_window = ReplayWindow::new(self.receive_window.size());
+            self.ratchet_sending_chain();
+        }
+        match advanced {
+            Some((chain_key, skipped)) => {
+                self.skipped_keys.extend(skipped);
+                self.receiving_chain_key = Self::next_chain_key(self.kdf, &chain_key);
+                self.receiving_chain_index = counter + 1;
+                self.receive_window.accept(counter);
+            }
+            None => {
+                self.skipped_keys.remove(&(remote, counter));
+                if remote == self.remote_ratchet_public {
+                    self.receive_window.accept(counter);
+                }
+            }
+        }
+        let current = self.remote_ratchet_public;
+        let lowest = self.receive_window.lowest_acceptable();
+        self.skipped_keys.retain(|(chain, index), _| *chain != current || *index >= lowest);
         
         Ok(plaintext)
     }
     
-    /// Ratchet the sending chain key forward (simple KDF ratchet)
+    /// Open a new sending chain under a fresh ratchet key (DH ratchet step)
     fn ratchet_sending_chain(&mut self) {
-        let mut ratchet_material = Vec::new();
-        ratchet_material.extend_from_slice(&self.sending_chain_key);
-        ratchet_material.extend_from_slice(b"ratchet-forward");
-        self.sending_chain_key = *blake3::hash(&ratchet_material).as_bytes();
+        self.ratchet_secret = StaticSecret::random_from_rng(OsRng);
+        self.ratchet_public = X25519PublicKey::from(&self.ratchet_secret);
+        let dh = self
+            .ratchet_secret
+            .diffie_hellman(&X25519PublicKey::from(self.remote_ratchet_public));
+        let (root_key, chain_key) = Self::ratchet_root(self.kdf, &self.root_key, dh.as_bytes());
+        self.root_key = root_key;
+        self.sending_chain_key = chain_key;
+        self.previous_sending_length = self.send_counter;
+        self.send_counter = 0;
+    }
+    
+    /// Derive the next root key and a new chain key from a ratchet DH output
+    fn ratchet_root(
+        kdf: KdfAlgorithm,
+        root_key: &SecretBuffer<32>,
+        dh_output: &[u8; 32],
+    ) -> (SecretBuffer<32>, SecretBuffer<32>) {
+        if kdf != KdfAlgorithm::Blake3 {
+            // The root key salts the DH output, as in the Double Ratchet's KDF_RK
+            let next_root = kdf::derive_key(kdf, dh_output, root_key.as_bytes(), b"otter-pfs-v1 root ratchet root");
+            let chain_key = kdf::derive_key(kdf, dh_output, root_key.as_bytes(), b"otter-pfs-v1 root ratchet chain");
+            return (SecretBuffer::new(next_root), SecretBuffer::new(chain_key));
+        }
+        
+        let mut hasher = blake3::Hasher::new_derive_key("otter-pfs-v1 root ratchet");
+        hasher.update(root_key.as_bytes());
+        hasher.update(dh_output);
+        let mut output = [0u8; 64];
+        hasher.finalize_xof().fill(&mut output);
+        
+        let mut next_root = [0u8; 32];
+        let mut chain_key = [0u8; 32];
+        next_root.copy_from_slice(&output[..32]);
+        chain_key.copy_from_slice(&output[32..]);
+        output.zeroize();
+        (SecretBuffer::new(next_root), SecretBuffer::new(chain_key))
+    }
+    
+    /// Ratchet `chain_key` (at index `from`) to index `to`, returning the keys it skips
+    fn skip_chain(
+        kdf: KdfAlgorithm,
+        chain_key: &SecretBuffer<32>,
+        from: MessageIndex,
+        to: MessageIndex,
+        chain: ChainIndex,
+    ) -> Result<(SecretBuffer<32>, SkippedKeys), CryptoError> {
+        if to.saturating_sub(from) > MAX_SKIPPED_MESSAGES {
+            return Err(CryptoError::DecryptionFailed);
+        }
+        let mut chain_key = chain_key.clone();
+        let mut skipped = Vec::new();
+        for index in from..to {
+            skipped.push(((chain, index), Self::message_key(kdf, &chain_key, index)));
+            chain_key = Self::next_chain_key(kdf, &chain_key);
+        }
+        Ok((chain_key, skipped))
+    }
+    
+    /// Associated data binding the ratchet header to the ciphertext
+    fn header_aad(
+        counter: MessageIndex,
+        ratchet_public: &[u8; 32],
+        previous_chain_length: MessageIndex,
+        associated_data: Option<&[u8]>,
+    ) -> Vec<u8> {
+        let mut aad = Vec::new();
+        aad.extend_from_slice(&counter.to_le_bytes());
+        aad.extend_from_slice(ratchet_public);
+        aad.extend_from_slice(&previous_chain_length.to_le_bytes());
+        if let Some(ad) = associated_data {
+            aad.extend_from_slice(ad);
+        }
+        aad
+    }
+    
+    /// Key of message `counter` from the chain key at that counter
+    fn message_key(kdf: KdfAlgorithm, chain_key: &SecretBuffer<32>, counter: u64) -> SecretBuffer<32> {
+        let mut key_material = Vec::new();
+        key_material.extend_from_slice(chain_key.as_bytes());
+        key_material.extend_from_slice(&counter.to_le_bytes());
+        let message_key = SecretBuffer::new(kdf::derive_step(kdf, &key_material, b"otter-pfs-v1 message key", |ikm| {
+            *blake3::hash(ikm).as_bytes()
+        }));
+        key_material.zeroize();
+        message_key
     }
     
-    /// Ratchet the receiving chain key forward
-    fn ratchet_receiving_chain(&mut self) {
-        let mut ratchet_material = Vec::new();
-        ratchet_material.extend_from_slice(&self.receiving_chain_key);
-        ratchet_material.extend_from_slice(b"ratchet-forward");
-        self.receiving_chain_key = *blake3::hash(&ratchet_material).as_bytes();
+    /// Ratchet a chain key forward (symmetric KDF ratchet)
+    fn next_chain_key(kdf: KdfAlgorithm, chain_key: &SecretBuffer<32>) -> SecretBuffer<32> {
+        SecretBuffer::new(kdf::derive_step(kdf, chain_key.as_bytes(), b"otter-pfs-v1 chain ratchet", |ikm| {
+            let mut hasher = blake3::Hasher::new();
+            hasher.update(ikm);
+            hasher.update(b"ratchet-forward");
+            *hasher.finalize().as_bytes()
+        }))
     }
     
     /// Get fingerprint for verification
-    pub fn fingerprint(&self) -> String {
-        let hash = blake3::hash(self.static_secret.as_bytes());
-        hex::encode(&hash.as_bytes()[..8])
+    pub fn fingerprint(&self) -> Fingerprint {
+        Fingerprint::from_secret(self.static_secret.as_bytes())
     }
 }
 
@@ -470,11 +1005,205 @@ mod tests {
         let bob_public = PublicIdentity::from_identity(&bob);
         
         // Create sessions on both sides
-        let alice_session = CryptoSession::new(&alice, &bob_public).unwrap();
-        let bob_session = CryptoSession::new(&bob, &alice_public).unwrap();
+        let alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
+        let bob_session = CryptoSession::new(&bob, &alice_public, KdfAlgorithm::default()).unwrap();
         
         // Verify fingerprints match
+        let fingerprint = alice_session.fingerprint();
+        assert!(
+            fingerprint.verify(&bob_session.fingerprint()),
+            "fingerprint mismatch: {} vs {}",
+            fingerprint.as_hex(),
+            bob_session.fingerprint().as_hex()
+        );
+        assert_eq!(fingerprint.as_hex().len(), 16);
+        assert_eq!(alice_session.safety_number(), bob_session.safety_number());
+        assert_eq!(&alice_session.safety_number()[..8], &fingerprint.0);
+    }
+    
+    #[cfg(feature = "key_export")]
+    #[test]
+    fn test_key_export_logs_each_message() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let mut alice_session =
+            CryptoSession::new(&alice, &PublicIdentity::from_identity(&bob), KdfAlgorithm::default()).unwrap();
+        let mut bob_session =
+            CryptoSession::new(&bob, &PublicIdentity::from_identity(&alice), KdfAlgorithm::default()).unwrap();
+        
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("keylog.txt");
+        alice_session.enable_key_export(&path).unwrap();
+        let line_count = || std::fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(line_count(), 0);
+        
+        for i in 1..=3 {
+            let encrypted = alice_session.encrypt(b"logged", None).unwrap();
+            assert_eq!(line_count(), 2 * i - 1);
+            bob_session.decrypt(&encrypted).unwrap();
+            
+            let reply = bob_session.encrypt(b"reply", None).unwrap();
+            alice_session.decrypt(&reply).unwrap();
+            assert_eq!(line_count(), 2 * i);
+        }
+        
+        let log = std::fs::read_to_string(&path).unwrap();
+        let fields: Vec<&str> = log.lines().next().unwrap().split(' ').collect();
+        assert_eq!(fields[0], "CLIENT_RANDOM");
+        assert_eq!(fields[1].len(), 24);
+        assert_eq!(fields[2], hex::encode(alice_session.cipher_key.as_bytes()));
+    }
+    
+    #[cfg(not(feature = "key_export"))]
+    #[test]
+    fn test_key_export_disabled() {
+        let alice = Identity::generate().unwrap();
+        let bob_public = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let mut session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
+        
+        let error = session.enable_key_export(Path::new("keylog.txt")).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::Unsupported);
+    }
+    
+    #[test]
+    fn test_hkdf_sessions() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::HkdfSha256).unwrap();
+        let mut bob_session = CryptoSession::new(&bob, &alice_public, KdfAlgorithm::HkdfSha256).unwrap();
         assert_eq!(alice_session.fingerprint(), bob_session.fingerprint());
+        assert_eq!(bob_session.kdf(), KdfAlgorithm::HkdfSha256);
+        
+        let encrypted = alice_session.encrypt(b"hello", None).unwrap();
+        assert_eq!(bob_session.decrypt(&encrypted).unwrap(), b"hello");
+        
+        // A peer deriving keys with BLAKE3 cannot read it
+        let mut blake3_session = CryptoSession::new(&bob, &alice_public, KdfAlgorithm::Blake3).unwrap();
+        assert!(matches!(blake3_session.decrypt(&encrypted), Err(CryptoError::DecryptionFailed)));
+    }
+    
+    #[test]
+    fn test_rotation_uses_the_session_kdf() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::HkdfSha256).unwrap();
+        let mut bob_session = CryptoSession::new(&bob, &alice_public, KdfAlgorithm::HkdfSha256).unwrap();
+        let mut blake3_session = CryptoSession::new(&bob, &alice_public, KdfAlgorithm::Blake3).unwrap();
+        
+        // The rotated key is plain HKDF-SHA256, so other implementations can follow it
+        let mut key_material = alice_session.cipher_key.as_bytes().to_vec();
+        key_material.extend_from_slice(b"epoch-1");
+        let expected = kdf::derive_key(KdfAlgorithm::HkdfSha256, &key_material, &[], b"otter-session-rotation-v1");
+        
+        for session in [&mut alice_session, &mut bob_session, &mut blake3_session] {
+            session.rotate_key(b"epoch-1").unwrap();
+        }
+        assert_eq!(alice_session.cipher_key.as_bytes(), &expected);
+        
+        let encrypted = alice_session.encrypt(b"after rotation", None).unwrap();
+        assert_eq!(bob_session.decrypt(&encrypted).unwrap(), b"after rotation");
+        assert!(matches!(blake3_session.decrypt(&encrypted), Err(CryptoError::DecryptionFailed)));
+    }
+    
+    #[test]
+    fn test_pfs_ratchet_uses_the_session_kdf() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let pair = |kdf: KdfAlgorithm| {
+            let alice_ephemeral = PFSSession::generate_ephemeral();
+            let bob_ephemeral = PFSSession::generate_ephemeral();
+            let alice_attestation = alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral)).unwrap();
+            let bob_attestation = bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral)).unwrap();
+            let alice_session = PFSSession::with_kdf(
+                &alice,
+                &PublicIdentity::from_identity(&bob),
+                alice_ephemeral.clone(),
+                &bob_attestation,
+                true,
+                kdf,
+            ).unwrap();
+            let bob_session = PFSSession::with_kdf(
+                &bob,
+                &PublicIdentity::from_identity(&alice),
+                bob_ephemeral.clone(),
+                &alice_attestation,
+                false,
+                kdf,
+            ).unwrap();
+            let blake3_bob = PFSSession::new(
+                &bob,
+                &PublicIdentity::from_identity(&alice),
+                bob_ephemeral,
+                &alice_attestation,
+                false,
+            ).unwrap();
+            (alice_session, bob_session, blake3_bob)
+        };
+        
+        let (mut alice_session, mut bob_session, mut blake3_bob) = pair(KdfAlgorithm::HkdfSha256);
+        assert_eq!(alice_session.kdf(), KdfAlgorithm::HkdfSha256);
+        
+        // Several DH ratchet steps in both directions
+        for round in 0..3u8 {
+            let to_bob = alice_session.encrypt(&[round], None).unwrap();
+            assert_eq!(bob_session.decrypt(&to_bob).unwrap(), [round]);
+            if round == 0 {
+                assert!(blake3_bob.decrypt(&to_bob).is_err());
+            }
+            let to_alice = bob_session.encrypt(&[round, round], None).unwrap();
+            assert_eq!(alice_session.decrypt(&to_alice).unwrap(), [round, round]);
+        }
+    }
+    
+    #[test]
+    fn test_padding_hides_message_length() {
+        /// Poly1305 tag appended to every ciphertext
+        const TAG_LEN: usize = 16;
+        
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let mut alice_session = CryptoSession::new(&alice, &PublicIdentity::from_identity(&bob), KdfAlgorithm::default()).unwrap();
+        let mut bob_session = CryptoSession::new(&bob, &PublicIdentity::from_identity(&alice), KdfAlgorithm::default()).unwrap();
+        alice_session.set_padding_strategy(PaddingStrategy::Fixed(1024));
+        bob_session.set_padding_strategy(PaddingStrategy::Fixed(1024));
+        
+        for len in 1..=1023 {
+            let plaintext = vec![b'x'; len];
+            let encrypted = alice_session.encrypt(&plaintext, None).unwrap();
+            assert_eq!(encrypted.ciphertext.len(), 1024 + TAG_LEN, "{} byte message", len);
+            assert_eq!(bob_session.decrypt(&encrypted).unwrap(), plaintext);
+        }
+        
+        alice_session.set_padding_strategy(PaddingStrategy::PowerOfTwo);
+        bob_session.set_padding_strategy(PaddingStrategy::PowerOfTwo);
+        let plaintext = vec![b'y'; 1025];
+        let encrypted = alice_session.encrypt(&plaintext, None).unwrap();
+        assert_eq!(encrypted.ciphertext.len(), 2048 + TAG_LEN);
+        assert_eq!(bob_session.decrypt(&encrypted).unwrap(), plaintext);
+        
+        // A receiver that expects padding rejects a message without it
+        alice_session.set_padding_strategy(PaddingStrategy::None);
+        let encrypted = alice_session.encrypt(b"unpadded\x00", None).unwrap();
+        assert!(matches!(bob_session.decrypt(&encrypted), Err(CryptoError::DecryptionFailed)));
+    }
+    
+    #[test]
+    fn test_fingerprint_verify_single_byte_difference() {
+        let fingerprint = Fingerprint([1, 2, 3, 4, 5, 6, 7, 8]);
+        assert!(fingerprint.verify(&Fingerprint([1, 2, 3, 4, 5, 6, 7, 8])));
+        
+        for i in 0..8 {
+            let mut bytes = fingerprint.0;
+            bytes[i] ^= 0x01;
+            assert!(!fingerprint.verify(&Fingerprint(bytes)));
+            assert_ne!(fingerprint, Fingerprint(bytes));
+        }
     }
     
     #[test]
@@ -483,7 +1212,7 @@ mod tests {
         let bob = Identity::generate().unwrap();
         
         let bob_public = PublicIdentity::from_identity(&bob);
-        let mut alice_session = CryptoSession::new(&alice, &bob_public).unwrap();
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
         
         let plaintext = b"Hello, Bob!";
         let encrypted = alice_session.encrypt(plaintext, None).unwrap();
@@ -498,7 +1227,7 @@ mod tests {
         let bob = Identity::generate().unwrap();
         
         let bob_public = PublicIdentity::from_identity(&bob);
-        let mut alice_session = CryptoSession::new(&alice, &bob_public).unwrap();
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
         
         let plaintext = b"Secret message";
         let associated_data = b"public metadata";
@@ -517,7 +1246,7 @@ mod tests {
         let bob = Identity::generate().unwrap();
         
         let bob_public = PublicIdentity::from_identity(&bob);
-        let mut alice_session = CryptoSession::new(&alice, &bob_public).unwrap();
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
         
         let text = "Hello, Bob! This is a secret message.";
         let encrypted = MessageCrypto::encrypt_text(&mut alice_session, text).unwrap();
@@ -532,7 +1261,7 @@ mod tests {
         let bob = Identity::generate().unwrap();
         
         let bob_public = PublicIdentity::from_identity(&bob);
-        let mut alice_session = CryptoSession::new(&alice, &bob_public).unwrap();
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
         
         let text = "Test message";
         let encrypted = MessageCrypto::encrypt_text(&mut alice_session, text).unwrap();
@@ -550,7 +1279,7 @@ mod tests {
         let bob = Identity::generate().unwrap();
         
         let bob_public = PublicIdentity::from_identity(&bob);
-        let mut alice_session = CryptoSession::new(&alice, &bob_public).unwrap();
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
         
         let plaintext1 = b"Message 1";
         let encrypted1 = alice_session.encrypt(plaintext1, None).unwrap();
@@ -565,6 +1294,58 @@ mod tests {
         assert!(matches!(result, Err(CryptoError::ReplayAttack)));
     }
     
+    #[test]
+    fn test_session_expiry() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
+        let encrypted = alice_session.encrypt(b"before", None).unwrap();
+        
+        alice_session.set_expiry(Duration::from_secs(3600));
+        assert!(!alice_session.is_expired());
+        
+        alice_session.set_expiry(Duration::ZERO);
+        assert!(alice_session.is_expired());
+        assert!(matches!(alice_session.encrypt(b"after", None), Err(CryptoError::SessionExpired)));
+        assert_eq!(alice_session.decrypt(&encrypted).unwrap(), b"before");
+    }
+    
+    #[test]
+    fn test_rotate_key() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        
+        let mut alice_session = CryptoSession::new(&alice, &PublicIdentity::from_identity(&bob), KdfAlgorithm::default()).unwrap();
+        let mut bob_session = CryptoSession::new(&bob, &PublicIdentity::from_identity(&alice), KdfAlgorithm::default()).unwrap();
+        
+        for _ in 0..3 {
+            let encrypted = alice_session.encrypt(b"before", None).unwrap();
+            bob_session.decrypt(&encrypted).unwrap();
+        }
+        let stale = alice_session.encrypt(b"stale", None).unwrap();
+        assert!(alice_session.rotation_due(4, 3600));
+        assert!(!alice_session.rotation_due(5, 3600));
+        assert!(alice_session.rotation_due(5, 0));
+        
+        assert!(matches!(alice_session.rotate_key(b""), Err(CryptoError::InvalidKey)));
+        alice_session.rotate_key(b"epoch-1").unwrap();
+        bob_session.rotate_key(b"epoch-1").unwrap();
+        assert!(!alice_session.rotation_due(4, 3600));
+        
+        let encrypted = alice_session.encrypt(b"after", None).unwrap();
+        assert_eq!(encrypted.message_counter, 0);
+        assert_eq!(bob_session.decrypt(&encrypted).unwrap(), b"after");
+        assert!(matches!(bob_session.decrypt(&stale), Err(CryptoError::DecryptionFailed)));
+        
+        // Different contexts leave the sides with different keys
+        alice_session.rotate_key(b"epoch-2").unwrap();
+        bob_session.rotate_key(b"epoch-two").unwrap();
+        let encrypted = alice_session.encrypt(b"lost", None).unwrap();
+        assert!(bob_session.decrypt(&encrypted).is_err());
+    }
+    
     #[test]
     fn test_pfs_session() {
         let alice = Identity::generate().unwrap();
@@ -577,15 +1358,15 @@ mod tests {
         let alice_ephemeral = PFSSession::generate_ephemeral();
         let bob_ephemeral = PFSSession::generate_ephemeral();
         
-        let alice_ephemeral_pub = X25519PublicKey::from(&alice_ephemeral);
-        let bob_ephemeral_pub = X25519PublicKey::from(&bob_ephemeral);
+        let alice_attestation = alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral)).unwrap();
+        let bob_attestation = bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral)).unwrap();
         
         // Create PFS sessions (Alice is initiator, Bob is responder)
         let mut alice_session = PFSSession::new(
             &alice,
             &bob_public,
             alice_ephemeral,
-            &bob_ephemeral_pub,
+            &bob_attestation,
             true, // Alice is initiator
         ).unwrap();
         
@@ -593,7 +1374,7 @@ mod tests {
             &bob,
             &alice_public,
             bob_ephemeral,
-            &alice_ephemeral_pub,
+            &alice_attestation,
             false, // Bob is responder
         ).unwrap();
         
@@ -616,14 +1397,14 @@ mod tests {
         let alice_ephemeral = PFSSession::generate_ephemeral();
         let bob_ephemeral = PFSSession::generate_ephemeral();
         
-        let alice_ephemeral_pub = X25519PublicKey::from(&alice_ephemeral);
-        let bob_ephemeral_pub = X25519PublicKey::from(&bob_ephemeral);
+        let alice_attestation = alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral)).unwrap();
+        let bob_attestation = bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral)).unwrap();
         
         let mut alice_session = PFSSession::new(
             &alice,
             &bob_public,
             alice_ephemeral,
-            &bob_ephemeral_pub,
+            &bob_attestation,
             true,
         ).unwrap();
         
@@ -631,7 +1412,7 @@ mod tests {
             &bob,
             &alice_public,
             bob_ephemeral,
-            &alice_ephemeral_pub,
+            &alice_attestation,
             false,
         ).unwrap();
         
@@ -655,14 +1436,14 @@ mod tests {
         let alice_ephemeral = PFSSession::generate_ephemeral();
         let bob_ephemeral = PFSSession::generate_ephemeral();
         
-        let alice_ephemeral_pub = X25519PublicKey::from(&alice_ephemeral);
-        let bob_ephemeral_pub = X25519PublicKey::from(&bob_ephemeral);
+        let alice_attestation = alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral)).unwrap();
+        let bob_attestation = bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral)).unwrap();
         
         let mut alice_session = PFSSession::new(
             &alice,
             &bob_public,
             alice_ephemeral,
-            &bob_ephemeral_pub,
+            &bob_attestation,
             true,
         ).unwrap();
         
@@ -670,7 +1451,7 @@ mod tests {
             &bob,
             &alice_public,
             bob_ephemeral,
-            &alice_ephemeral_pub,
+            &alice_attestation,
             false,
         ).unwrap();
         
@@ -686,4 +1467,192 @@ mod tests {
         let result = bob_session.decrypt(&encrypted1);
         assert!(matches!(result, Err(CryptoError::ReplayAttack)));
     }
+    
+    #[test]
+    fn test_reordered_messages_within_window() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
+        let mut bob_session = CryptoSession::with_replay_window(&bob, &alice_public, KdfAlgorithm::default(), 4).unwrap();
+        
+        let messages: Vec<_> = (0..8).map(|i| alice_session.encrypt(&[i], None).unwrap()).collect();
+        assert_eq!(bob_session.decrypt(&messages[2]).unwrap(), [2]);
+        assert_eq!(bob_session.decrypt(&messages[0]).unwrap(), [0]);
+        assert!(matches!(bob_session.decrypt(&messages[0]), Err(CryptoError::ReplayAttack)));
+        
+        // 1 is lost; once 7 arrives everything before 4 is out of the window
+        assert_eq!(bob_session.decrypt(&messages[7]).unwrap(), [7]);
+        assert_eq!(bob_session.decrypt(&messages[5]).unwrap(), [5]);
+        assert!(matches!(bob_session.decrypt(&messages[3]), Err(CryptoError::ReplayAttack)));
+    }
+    
+    #[test]
+    fn test_pfs_out_of_order_and_dropped_messages() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        
+        let alice_ephemeral = PFSSession::generate_ephemeral();
+        let bob_ephemeral = PFSSession::generate_ephemeral();
+        let alice_attestation = alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral)).unwrap();
+        let bob_attestation = bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral)).unwrap();
+        
+        let mut alice_session = PFSSession::new(
+            &alice,
+            &PublicIdentity::from_identity(&bob),
+            alice_ephemeral,
+            &bob_attestation,
+            true,
+        ).unwrap();
+        let mut bob_session = PFSSession::with_replay_window(
+            &bob,
+            &PublicIdentity::from_identity(&alice),
+            bob_ephemeral,
+            &alice_attestation,
+            false,
+            KdfAlgorithm::default(),
+            8,
+        ).unwrap();
+        
+        let messages: Vec<_> = (0..20).map(|i| alice_session.encrypt(&[i], None).unwrap()).collect();
+        
+        // 0 and 1 are dropped, 3 overtakes 2
+        assert_eq!(bob_session.decrypt(&messages[3]).unwrap(), [3]);
+        assert_eq!(bob_session.decrypt(&messages[2]).unwrap(), [2]);
+        assert_eq!(bob_session.decrypt(&messages[4]).unwrap(), [4]);
+        assert!(matches!(bob_session.decrypt(&messages[2]), Err(CryptoError::ReplayAttack)));
+        
+        // A tampered message ahead of the chain leaves it where it was
+        let mut forged = messages[6].clone();
+        forged.ciphertext[0] ^= 0xff;
+        assert!(matches!(bob_session.decrypt(&forged), Err(CryptoError::DecryptionFailed)));
+        assert_eq!(bob_session.decrypt(&messages[5]).unwrap(), [5]);
+        assert_eq!(bob_session.decrypt(&messages[6]).unwrap(), [6]);
+        
+        // Skipped keys that leave the window are gone
+        assert_eq!(bob_session.decrypt(&messages[19]).unwrap(), [19]);
+        assert!(matches!(bob_session.decrypt(&messages[10]), Err(CryptoError::ReplayAttack)));
+        assert_eq!(bob_session.decrypt(&messages[12]).unwrap(), [12]);
+        assert_eq!(bob_session.skipped_keys.len(), 6);
+    }
+    
+    fn pfs_pair() -> (PFSSession, PFSSession) {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        
+        let alice_ephemeral = PFSSession::generate_ephemeral();
+        let bob_ephemeral = PFSSession::generate_ephemeral();
+        let alice_attestation = alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral)).unwrap();
+        let bob_attestation = bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral)).unwrap();
+        
+        let alice_session = PFSSession::new(
+            &alice,
+            &PublicIdentity::from_identity(&bob),
+            alice_ephemeral,
+            &bob_attestation,
+            true,
+        ).unwrap();
+        let bob_session = PFSSession::new(
+            &bob,
+            &PublicIdentity::from_identity(&alice),
+            bob_ephemeral,
+            &alice_attestation,
+            false,
+        ).unwrap();
+        (alice_session, bob_session)
+    }
+    
+    #[test]
+    fn test_double_ratchet_steps_on_new_remote_key() {
+        let (mut alice_session, mut bob_session) = pfs_pair();
+        
+        // Bob can send on his initial chain before hearing from Alice
+        let early = bob_session.encrypt(b"early", None).unwrap();
+        assert_eq!(early.ratchet_public.as_deref(), Some(bob_session.ephemeral_public.as_bytes().as_slice()));
+        assert_eq!(alice_session.decrypt(&early).unwrap(), b"early");
+        
+        let mut last_alice_key = alice_session.ratchet_public();
+        let mut last_bob_key = bob_session.ratchet_public();
+        for round in 0..3u8 {
+            // Consecutive messages from one side share a ratchet key
+            let first = alice_session.encrypt(&[round, 0], None).unwrap();
+            let second = alice_session.encrypt(&[round, 1], None).unwrap();
+            assert_eq!(first.ratchet_public, second.ratchet_public);
+            assert_eq!(bob_session.decrypt(&first).unwrap(), [round, 0]);
+            assert_eq!(bob_session.decrypt(&second).unwrap(), [round, 1]);
+            
+            // Hearing a new key makes the receiver ratchet before replying
+            assert_ne!(bob_session.ratchet_public(), last_bob_key);
+            last_bob_key = bob_session.ratchet_public();
+            let reply = bob_session.encrypt(&[round, 2], None).unwrap();
+            assert_eq!(reply.message_counter, 0);
+            assert_eq!(alice_session.decrypt(&reply).unwrap(), [round, 2]);
+            assert_ne!(alice_session.ratchet_public(), last_alice_key);
+            last_alice_key = alice_session.ratchet_public();
+        }
+        
+        // A message without a ratchet key or with a tampered one is rejected
+        let mut headerless = alice_session.encrypt(b"x", None).unwrap();
+        let mut tampered = headerless.clone();
+        headerless.ratchet_public = None;
+        assert!(matches!(bob_session.decrypt(&headerless), Err(CryptoError::DecryptionFailed)));
+        tampered.previous_chain_length = Some(7);
+        assert!(matches!(bob_session.decrypt(&tampered), Err(CryptoError::DecryptionFailed)));
+    }
+    
+    #[test]
+    fn test_double_ratchet_reordering_across_steps() {
+        let (mut alice_session, mut bob_session) = pfs_pair();
+        
+        let a0 = alice_session.encrypt(b"a0", None).unwrap();
+        let a1 = alice_session.encrypt(b"a1", None).unwrap();
+        let a2 = alice_session.encrypt(b"a2", None).unwrap();
+        assert_eq!(bob_session.decrypt(&a0).unwrap(), b"a0");
+        
+        let b0 = bob_session.encrypt(b"b0", None).unwrap();
+        assert_eq!(alice_session.decrypt(&b0).unwrap(), b"b0");
+        
+        // Alice's next message is on a new chain and overtakes a1 and a2
+        let a3 = alice_session.encrypt(b"a3", None).unwrap();
+        assert_ne!(a3.ratchet_public, a2.ratchet_public);
+        assert_eq!(a3.previous_chain_length, Some(3));
+        assert_eq!(bob_session.decrypt(&a3).unwrap(), b"a3");
+        
+        // The closed chain's messages still decrypt, once
+        assert_eq!(bob_session.decrypt(&a2).unwrap(), b"a2");
+        assert_eq!(bob_session.decrypt(&a1).unwrap(), b"a1");
+        assert!(matches!(bob_session.decrypt(&a1), Err(CryptoError::ReplayAttack)));
+        assert!(matches!(bob_session.decrypt(&a0), Err(CryptoError::ReplayAttack)));
+        assert!(matches!(bob_session.decrypt(&a3), Err(CryptoError::ReplayAttack)));
+        assert!(bob_session.skipped_keys.is_empty());
+    }
+    
+    #[test]
+    fn test_pfs_rejects_substituted_ephemeral() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let mallory = Identity::generate().unwrap();
+        
+        let bob_public = PublicIdentity::from_identity(&bob);
+        
+        let bob_ephemeral = PFSSession::generate_ephemeral();
+        let mallory_ephemeral = PFSSession::generate_ephemeral();
+        let mallory_ephemeral_pub = X25519PublicKey::from(&mallory_ephemeral);
+        
+        // Mallory swaps the key inside Bob's attestation
+        let mut substituted = bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral)).unwrap();
+        substituted.ephemeral_public = mallory_ephemeral_pub.to_bytes();
+        let result = PFSSession::new(&alice, &bob_public, PFSSession::generate_ephemeral(), &substituted, true);
+        assert!(matches!(
+            result,
+            Err(CryptoError::IdentityError(IdentityError::InvalidSignature))
+        ));
+        
+        // Mallory attests her own key, but it is not signed by Bob
+        let forged = mallory.attest_ephemeral(&mallory_ephemeral_pub).unwrap();
+        let result = PFSSession::new(&alice, &bob_public, PFSSession::generate_ephemeral(), &forged, true);
+        assert!(result.is_err());
+    }
 }
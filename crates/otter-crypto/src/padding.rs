@@ -0,0 +1,119 @@
+//! # Message Padding
+//!
+//! ChaCha20-Poly1305 ciphertexts are exactly as long as their plaintext
+//! plus the tag, so an observer learns the length of every message. Padding
+//! the plaintext to a coarser size before encryption hides it.
+//!
+//! Padding is PKCS#7-style: `n` bytes of value `n`. PKCS#7 stops at 255
+//! bytes, so longer padding ends in a zero byte instead, preceded by `n` as
+//! a little-endian u32 and zero filler. At least one byte of padding is
+//! always added, so the last byte can always be read.
+
+use crate::CryptoError;
+
+/// Length of the `n` field and the terminating zero of long padding
+const LONG_TRAILER_LEN: usize = 5;
+
+/// How plaintexts are padded before encryption
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PaddingStrategy {
+    /// No padding; ciphertext length reveals plaintext length
+    #[default]
+    None,
+    /// Pad to a multiple of this many bytes, so shorter messages all look alike
+    Fixed(usize),
+    /// Pad to the next power of two above the plaintext length
+    PowerOfTwo,
+}
+
+impl PaddingStrategy {
+    /// Length of `len` plaintext bytes once padded
+    pub fn padded_len(&self, len: usize) -> usize {
+        match *self {
+            PaddingStrategy::None => len,
+            PaddingStrategy::Fixed(block) => {
+                let block = block.max(1);
+                (len / block + 1) * block
+            }
+            PaddingStrategy::PowerOfTwo => (len + 1).next_power_of_two(),
+        }
+    }
+    
+    /// Append padding to `plaintext`
+    pub fn pad(&self, plaintext: &[u8]) -> Vec<u8> {
+        if *self == PaddingStrategy::None {
+            return plaintext.to_vec();
+        }
+        
+        let pad_len = self.padded_len(plaintext.len()) - plaintext.len();
+        let mut padded = Vec::with_capacity(plaintext.len() + pad_len);
+        padded.extend_from_slice(plaintext);
+        if pad_len <= u8::MAX as usize {
+            padded.resize(plaintext.len() + pad_len, pad_len as u8);
+        } else {
+            padded.resize(plaintext.len() + pad_len - LONG_TRAILER_LEN, 0);
+            padded.extend_from_slice(&(pad_len as u32).to_le_bytes());
+            padded.push(0);
+        }
+        padded
+    }
+    
+    /// Remove the padding added by `pad`, checking every padding byte
+    pub fn unpad(&self, mut padded: Vec<u8>) -> Result<Vec<u8>, CryptoError> {
+        if *self == PaddingStrategy::None {
+            return Ok(padded);
+        }
+        
+        let len = padded.len();
+        let &last = padded.last().ok_or(CryptoError::DecryptionFailed)?;
+        let (pad_len, filler) = if last != 0 {
+            (last as usize, &padded[len.saturating_sub(last as usize)..])
+        } else {
+            let start = len.checked_sub(LONG_TRAILER_LEN).ok_or(CryptoError::DecryptionFailed)?;
+            let pad_len = u32::from_le_bytes(padded[start..len - 1].try_into().expect("4 bytes")) as usize;
+            if pad_len <= u8::MAX as usize {
+                return Err(CryptoError::DecryptionFailed);
+            }
+            (pad_len, &padded[len.saturating_sub(pad_len).min(start)..start])
+        };
+        if pad_len > len || filler.iter().any(|&byte| byte != last) {
+            return Err(CryptoError::DecryptionFailed);
+        }
+        
+        padded.truncate(len - pad_len);
+        Ok(padded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_pad_roundtrip_and_rejects_bad_padding() {
+        for strategy in [PaddingStrategy::Fixed(1024), PaddingStrategy::Fixed(16), PaddingStrategy::PowerOfTwo] {
+            for len in [0usize, 1, 15, 16, 250, 255, 256, 700, 1023, 1024, 3000] {
+                let plaintext: Vec<u8> = (0..len).map(|i| (i % 251) as u8).collect();
+                let padded = strategy.pad(&plaintext);
+                assert_eq!(padded.len(), strategy.padded_len(len));
+                assert!(padded.len() > len);
+                assert_eq!(strategy.unpad(padded).unwrap(), plaintext, "{:?} {}", strategy, len);
+            }
+        }
+        
+        let strategy = PaddingStrategy::Fixed(1024);
+        let mut short = strategy.pad(&[7u8; 1000]);
+        short[1000] ^= 1;
+        assert!(matches!(strategy.unpad(short), Err(CryptoError::DecryptionFailed)));
+        
+        let mut long = strategy.pad(b"hi");
+        long[10] = 1;
+        assert!(strategy.unpad(long).is_err());
+        
+        // A length field claiming more bytes than there are
+        let mut oversized = strategy.pad(b"hi");
+        oversized[1018..1022].copy_from_slice(&5000u32.to_le_bytes());
+        assert!(strategy.unpad(oversized).is_err());
+        assert!(strategy.unpad(Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,154 @@
+//! # Replay Window
+//!
+//! Networks reorder and drop packets, so a receiver cannot insist on
+//! strictly increasing message counters. Instead it accepts any counter
+//! within `size` of the highest one seen, once: a bitfield records which
+//! counters in the window were already accepted. Counters that fall behind
+//! the window are rejected as replays.
+
+use crate::CryptoError;
+
+/// Window width used by `CryptoSession::new` and `PFSSession::new`
+pub const DEFAULT_REPLAY_WINDOW: u64 = 64;
+
+/// Sliding window of accepted message counters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReplayWindow {
+    size: u64,
+    /// Highest accepted counter
+    highest: Option<u64>,
+    /// Bit `counter % size` is set once `counter` was accepted
+    seen: Vec<u64>,
+}
+
+impl Default for ReplayWindow {
+    fn default() -> Self {
+        Self::new(DEFAULT_REPLAY_WINDOW)
+    }
+}
+
+impl ReplayWindow {
+    /// Create a window `size` counters wide (at least one)
+    pub fn new(size: u64) -> Self {
+        let size = size.max(1);
+        Self {
+            size,
+            highest: None,
+            seen: vec![0; size.div_ceil(64) as usize],
+        }
+    }
+    
+    /// Width of the window
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+    
+    /// Highest accepted counter
+    pub fn highest(&self) -> Option<u64> {
+        self.highest
+    }
+    
+    /// Lowest counter that can still be accepted
+    pub fn lowest_acceptable(&self) -> u64 {
+        self.highest.map_or(0, |highest| (highest + 1).saturating_sub(self.size))
+    }
+    
+    /// Fail with `ReplayAttack` if `counter` is behind the window or was accepted
+    pub fn check(&self, counter: u64) -> Result<(), CryptoError> {
+        match self.highest {
+            Some(highest) if counter <= highest => {
+                if counter < self.lowest_acceptable() || self.is_set(counter) {
+                    Err(CryptoError::ReplayAttack)
+                } else {
+                    Ok(())
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    /// Record `counter` as accepted, sliding the window forward if it is new
+    ///
+    /// Call after [`ReplayWindow::check`] passed and the message authenticated.
+    pub fn accept(&mut self, counter: u64) {
+        match self.highest {
+            Some(highest) if counter <= highest => {}
+            Some(highest) if counter - highest < self.size => {
+                for skipped in highest + 1..counter {
+                    self.set(skipped, false);
+                }
+                self.highest = Some(counter);
+            }
+            _ => {
+                self.seen.fill(0);
+                self.highest = Some(counter);
+            }
+        }
+        self.set(counter, true);
+    }
+    
+    fn bit(&self, counter: u64) -> (usize, u64) {
+        let index = counter % self.size;
+        ((index / 64) as usize, 1 << (index % 64))
+    }
+    
+    fn is_set(&self, counter: u64) -> bool {
+        let (word, mask) = self.bit(counter);
+        self.seen[word] & mask != 0
+    }
+    
+    fn set(&mut self, counter: u64, value: bool) {
+        let (word, mask) = self.bit(counter);
+        if value {
+            self.seen[word] |= mask;
+        } else {
+            self.seen[word] &= !mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn accept(window: &mut ReplayWindow, counter: u64) -> Result<(), CryptoError> {
+        window.check(counter)?;
+        window.accept(counter);
+        Ok(())
+    }
+    
+    #[test]
+    fn test_reordered_counters_accepted_once() {
+        let mut window = ReplayWindow::new(4);
+        assert!(accept(&mut window, 0).is_ok());
+        assert!(accept(&mut window, 2).is_ok());
+        assert!(accept(&mut window, 1).is_ok());
+        assert!(matches!(accept(&mut window, 1), Err(CryptoError::ReplayAttack)));
+        
+        // 3 was never seen but slid out of the window
+        assert!(accept(&mut window, 7).is_ok());
+        assert_eq!(window.lowest_acceptable(), 4);
+        assert!(matches!(accept(&mut window, 3), Err(CryptoError::ReplayAttack)));
+        assert!(accept(&mut window, 5).is_ok());
+        assert!(matches!(accept(&mut window, 7), Err(CryptoError::ReplayAttack)));
+        
+        // A jump past the whole window forgets everything before it
+        assert!(accept(&mut window, 100).is_ok());
+        assert!(accept(&mut window, 98).is_ok());
+        assert!(matches!(accept(&mut window, 96), Err(CryptoError::ReplayAttack)));
+    }
+    
+    #[test]
+    fn test_window_wider_than_one_word() {
+        let mut window = ReplayWindow::new(200);
+        assert!(accept(&mut window, 199).is_ok());
+        for counter in (0..199).rev() {
+            assert!(accept(&mut window, counter).is_ok(), "counter {}", counter);
+        }
+        assert!(matches!(accept(&mut window, 64), Err(CryptoError::ReplayAttack)));
+        assert!(accept(&mut window, 300).is_ok());
+        assert!(matches!(accept(&mut window, 100), Err(CryptoError::ReplayAttack)));
+        assert!(matches!(accept(&mut window, 101), Err(CryptoError::ReplayAttack)));
+        assert!(accept(&mut window, 250).is_ok());
+    }
+}
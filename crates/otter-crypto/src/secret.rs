@@ -0,0 +1,65 @@
+//! # Secret Buffers
+//!
+//! Fixed-size key material that is wiped when it goes out of scope.
+//!
+//! Session keys live on the heap for as long as a conversation is open. Without
+//! explicit wiping, the bytes of a dropped key stay in freed memory until the
+//! allocator reuses it, where they can end up in swap, core dumps, or be read by
+//! another process with access to our address space. `SecretBuffer` overwrites
+//! its contents with zeros (using volatile writes the optimizer cannot elide)
+//! on drop, and on every reassignment since the old value is dropped in place.
+
+use std::fmt;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Fixed-size secret that is zeroized on drop
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+#[repr(transparent)]
+pub struct SecretBuffer<const N: usize>([u8; N]);
+
+impl<const N: usize> SecretBuffer<N> {
+    /// Wrap secret bytes
+    pub fn new(bytes: [u8; N]) -> Self {
+        Self(bytes)
+    }
+    
+    /// Borrow the secret bytes
+    pub fn as_bytes(&self) -> &[u8; N] {
+        &self.0
+    }
+}
+
+impl<const N: usize> From<[u8; N]> for SecretBuffer<N> {
+    fn from(bytes: [u8; N]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl<const N: usize> fmt::Debug for SecretBuffer<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBuffer<{}>([REDACTED])", N)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::ManuallyDrop;
+    
+    #[test]
+    fn test_secret_buffer_zeroized_on_drop() {
+        let mut slot = ManuallyDrop::new(SecretBuffer::new([0xAB; 32]));
+        assert_eq!(slot.as_bytes(), &[0xAB; 32]);
+        
+        // Run the destructor but keep the memory around to inspect it
+        unsafe { ManuallyDrop::drop(&mut slot) };
+        let remaining = unsafe { std::ptr::read(&*slot as *const SecretBuffer<32> as *const [u8; 32]) };
+        assert_eq!(remaining, [0u8; 32]);
+    }
+    
+    #[test]
+    fn test_secret_buffer_debug_redacted() {
+        let secret = SecretBuffer::new([0x42; 16]);
+        assert!(!format!("{:?}", secret).contains("42"));
+    }
+}
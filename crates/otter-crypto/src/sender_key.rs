@@ -0,0 +1,162 @@
+//! # Sender Keys
+//!
+//! In a group every member encrypts with its own sender key: a chain key that
+//! is ratcheted once per message, plus an Ed25519 signing key. A member hands
+//! its sender key to every other member in a [`SenderKeyMessage`], wrapped
+//! individually with a pairwise X25519 key, and then encrypts each group
+//! message once. Receivers ratchet their copy of the chain forward and check
+//! the signature, so members holding the chain key still cannot forge
+//! messages in the sender's name.
+
+use crate::{CryptoError, SecretBuffer, DEFAULT_REPLAY_WINDOW, MAX_SKIPPED_MESSAGES};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const CHAIN_KEY_CONTEXT: &str = "otter sender key chain v1";
+const MESSAGE_KEY_CONTEXT: &str = "otter sender key message v1";
+
+/// Length of an exported sender key: chain key, iteration, signing public key
+pub(crate) const SENDER_KEY_LEN: usize = 32 + 8 + 32;
+
+/// Length of the signature appended to every group ciphertext
+pub(crate) const SIGNATURE_LEN: usize = 64;
+
+/// A member's sender key, wrapped for each recipient
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SenderKeyMessage {
+    /// Peer ID of the member the sender key belongs to
+    pub sender_peer_id: String,
+    /// Rotation count of the sender key; older generations are refused
+    pub generation: u64,
+    /// Recipient peer ID -> nonce || wrapped sender key
+    pub encrypted: HashMap<String, Vec<u8>>,
+}
+
+/// The local member's sending chain
+pub(crate) struct SenderChain {
+    generation: u64,
+    chain_key: SecretBuffer<32>,
+    iteration: u64,
+    signing_key: SigningKey,
+}
+
+impl SenderChain {
+    /// A fresh random chain and signing key
+    pub(crate) fn new(generation: u64) -> Self {
+        Self {
+            generation,
+            chain_key: SecretBuffer::new(crate::group::random_key()),
+            iteration: 0,
+            signing_key: SigningKey::generate(&mut OsRng),
+        }
+    }
+    
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+    
+    /// Chain key at the current iteration, the iteration and the signing public key
+    ///
+    /// Receivers can decrypt messages from this iteration on, but none before.
+    pub(crate) fn export(&self) -> SecretBuffer<SENDER_KEY_LEN> {
+        let mut exported = [0u8; SENDER_KEY_LEN];
+        exported[..32].copy_from_slice(self.chain_key.as_bytes());
+        exported[32..40].copy_from_slice(&self.iteration.to_le_bytes());
+        exported[40..].copy_from_slice(self.signing_key.verifying_key().as_bytes());
+        SecretBuffer::new(exported)
+    }
+    
+    /// Iteration and key of the next message, ratcheting the chain past it
+    pub(crate) fn next_message_key(&mut self) -> Result<(u64, SecretBuffer<32>), CryptoError> {
+        if self.iteration == u64::MAX {
+            return Err(CryptoError::CounterOverflow);
+        }
+        let message_key = message_key(&self.chain_key);
+        let iteration = self.iteration;
+        self.chain_key = next_chain_key(&self.chain_key);
+        self.iteration += 1;
+        Ok((iteration, message_key))
+    }
+    
+    pub(crate) fn sign(&self, data: &[u8]) -> [u8; SIGNATURE_LEN] {
+        self.signing_key.sign(data).to_bytes()
+    }
+}
+
+/// Another member's chain, as installed from its sender key message
+pub(crate) struct ReceiverChain {
+    generation: u64,
+    chain_key: SecretBuffer<32>,
+    iteration: u64,
+    verifying_key: VerifyingKey,
+    /// Keys of skipped iterations still inside the replay window
+    skipped_keys: HashMap<u64, SecretBuffer<32>>,
+}
+
+impl ReceiverChain {
+    /// Rebuild a chain from [`SenderChain::export`]
+    pub(crate) fn import(generation: u64, exported: &[u8]) -> Result<Self, CryptoError> {
+        let exported: &[u8; SENDER_KEY_LEN] = exported.try_into().map_err(|_| CryptoError::InvalidKey)?;
+        let mut chain_key = [0u8; 32];
+        chain_key.copy_from_slice(&exported[..32]);
+        let mut iteration = [0u8; 8];
+        iteration.copy_from_slice(&exported[32..40]);
+        let mut verifying_key = [0u8; 32];
+        verifying_key.copy_from_slice(&exported[40..]);
+        
+        Ok(Self {
+            generation,
+            chain_key: SecretBuffer::new(chain_key),
+            iteration: u64::from_le_bytes(iteration),
+            verifying_key: VerifyingKey::from_bytes(&verifying_key).map_err(|_| CryptoError::InvalidKey)?,
+            skipped_keys: HashMap::new(),
+        })
+    }
+    
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+    
+    pub(crate) fn verify(&self, data: &[u8], signature: &[u8]) -> Result<(), CryptoError> {
+        let signature: [u8; SIGNATURE_LEN] = signature.try_into().map_err(|_| CryptoError::DecryptionFailed)?;
+        self.verifying_key
+            .verify(data, &Signature::from_bytes(&signature))
+            .map_err(|_| CryptoError::DecryptionFailed)
+    }
+    
+    /// Key of message `iteration`, ratcheting the chain forward if it is ahead
+    ///
+    /// Call only after the signature verified, so forged messages cannot move the chain.
+    pub(crate) fn take_message_key(&mut self, iteration: u64) -> Result<SecretBuffer<32>, CryptoError> {
+        if iteration < self.iteration {
+            // Behind the chain and not skipped: already received, or the key was pruned
+            return self.skipped_keys.remove(&iteration).ok_or(CryptoError::ReplayAttack);
+        }
+        if iteration - self.iteration > MAX_SKIPPED_MESSAGES {
+            return Err(CryptoError::DecryptionFailed);
+        }
+        
+        while self.iteration < iteration {
+            self.skipped_keys.insert(self.iteration, message_key(&self.chain_key));
+            self.chain_key = next_chain_key(&self.chain_key);
+            self.iteration += 1;
+        }
+        let key = message_key(&self.chain_key);
+        self.chain_key = next_chain_key(&self.chain_key);
+        self.iteration += 1;
+        
+        let lowest = self.iteration.saturating_sub(DEFAULT_REPLAY_WINDOW);
+        self.skipped_keys.retain(|skipped, _| *skipped >= lowest);
+        Ok(key)
+    }
+}
+
+fn message_key(chain_key: &SecretBuffer<32>) -> SecretBuffer<32> {
+    SecretBuffer::new(blake3::derive_key(MESSAGE_KEY_CONTEXT, chain_key.as_bytes()))
+}
+
+fn next_chain_key(chain_key: &SecretBuffer<32>) -> SecretBuffer<32> {
+    SecretBuffer::new(blake3::derive_key(CHAIN_KEY_CONTEXT, chain_key.as_bytes()))
+}
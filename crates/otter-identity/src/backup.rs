@@ -0,0 +1,195 @@
+//! # Trust Store Backups
+//!
+//! Passphrase-protected ZIP backups of the trust store. The archive holds two
+//! entries:
+//!
+//! - `metadata.json`: the Argon2id parameters and salt
+//! - `trust_store.enc`: `nonce (12 bytes) || ciphertext`, the trust store JSON
+//!   encrypted with AES-256-GCM under the Argon2id-derived key
+//!
+//! The metadata is authenticated as associated data, so tampering with the
+//! parameters makes decryption fail.
+
+use crate::trust::TrustStore;
+use crate::IdentityError;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+const METADATA_ENTRY: &str = "metadata.json";
+const CIPHERTEXT_ENTRY: &str = "trust_store.enc";
+const BACKUP_VERSION: u32 = 1;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// Largest Argon2 memory cost accepted on restore, in KiB (1 GiB)
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Key derivation settings stored next to the ciphertext
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BackupMetadata {
+    version: u32,
+    kdf: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    /// Hex-encoded salt
+    salt: String,
+}
+
+impl BackupMetadata {
+    fn new() -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            version: BACKUP_VERSION,
+            kdf: "argon2id".to_string(),
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            salt: hex::encode(salt),
+        }
+    }
+    
+    /// Derive the AES-256 key for `passphrase`
+    fn derive_key(&self, passphrase: &str) -> Result<Zeroizing<[u8; 32]>, IdentityError> {
+        if self.version != BACKUP_VERSION || self.kdf != "argon2id" {
+            return Err(IdentityError::BackupError(format!(
+                "Unsupported backup format {} with {}",
+                self.version, self.kdf
+            )));
+        }
+        if self.memory_kib > MAX_MEMORY_KIB {
+            return Err(IdentityError::BackupError(format!("Argon2 memory cost too high: {} KiB", self.memory_kib)));
+        }
+        
+        let salt = hex::decode(&self.salt).map_err(|e| IdentityError::BackupError(e.to_string()))?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| IdentityError::BackupError(e.to_string()))?;
+        
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| IdentityError::BackupError(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+fn zip_error(e: zip::result::ZipError) -> IdentityError {
+    IdentityError::BackupError(e.to_string())
+}
+
+impl TrustStore {
+    /// Write an encrypted backup of the trust store to `dest`
+    pub fn backup(&self, dest: &Path, passphrase: &str) -> Result<(), IdentityError> {
+        let json = Zeroizing::new(
+            self.to_json()
+                .map_err(|e| IdentityError::SerializationError(e.to_string()))?,
+        );
+        
+        let metadata = BackupMetadata::new();
+        let metadata_json = serde_json::to_vec_pretty(&metadata)
+            .map_err(|e| IdentityError::SerializationError(e.to_string()))?;
+        let key = metadata.derive_key(passphrase)?;
+        
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = Aes256Gcm::new(key.as_ref().into())
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: json.as_bytes(),
+                    aad: &metadata_json,
+                },
+            )
+            .map_err(|_| IdentityError::BackupError("Encryption failed".to_string()))?;
+        
+        let mut zip = ZipWriter::new(File::create(dest)?);
+        let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+        zip.start_file(METADATA_ENTRY, options).map_err(zip_error)?;
+        zip.write_all(&metadata_json)?;
+        zip.start_file(CIPHERTEXT_ENTRY, options).map_err(zip_error)?;
+        zip.write_all(&nonce)?;
+        zip.write_all(&ciphertext)?;
+        zip.finish().map_err(zip_error)?;
+        
+        Ok(())
+    }
+    
+    /// Read a backup written by [`TrustStore::backup`]
+    ///
+    /// A wrong passphrase and a modified archive both fail with
+    /// `IdentityError::InvalidPassphrase`, as AES-GCM cannot tell them apart.
+    pub fn restore(src: &Path, passphrase: &str) -> Result<Self, IdentityError> {
+        let mut archive = ZipArchive::new(File::open(src)?).map_err(zip_error)?;
+        let read_entry = |archive: &mut ZipArchive<File>, name: &str| -> Result<Vec<u8>, IdentityError> {
+            let mut data = Vec::new();
+            archive.by_name(name).map_err(zip_error)?.read_to_end(&mut data)?;
+            Ok(data)
+        };
+        let metadata_json = read_entry(&mut archive, METADATA_ENTRY)?;
+        let encrypted = read_entry(&mut archive, CIPHERTEXT_ENTRY)?;
+        
+        let metadata: BackupMetadata = serde_json::from_slice(&metadata_json)
+            .map_err(|e| IdentityError::BackupError(format!("Invalid metadata: {}", e)))?;
+        if encrypted.len() < NONCE_LEN {
+            return Err(IdentityError::BackupError("Ciphertext too short".to_string()));
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+        
+        let key = metadata.derive_key(passphrase)?;
+        let json = Zeroizing::new(
+            Aes256Gcm::new(key.as_ref().into())
+                .decrypt(
+                    Nonce::from_slice(nonce),
+                    Payload {
+                        msg: ciphertext,
+                        aad: &metadata_json,
+                    },
+                )
+                .map_err(|_| IdentityError::InvalidPassphrase)?,
+        );
+        
+        let json = std::str::from_utf8(&json).map_err(|e| IdentityError::SerializationError(e.to_string()))?;
+        TrustStore::from_json(json).map_err(|e| IdentityError::SerializationError(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Identity, PublicIdentity};
+    
+    #[test]
+    fn test_backup_round_trip() {
+        let temp = tempfile::TempDir::new().unwrap();
+        let path = temp.path().join("trust.zip");
+        
+        let public = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let mut store = TrustStore::new();
+        store.pin(public.clone()).unwrap();
+        store.backup(&path, "correct horse").unwrap();
+        
+        // The trust store must not be readable from the archive
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!raw.windows(public.peer_id().as_str().len()).any(|w| w == public.peer_id().as_str().as_bytes()));
+        
+        let restored = TrustStore::restore(&path, "correct horse").unwrap();
+        assert_eq!(restored.verified_peers().len(), 1);
+        assert_eq!(restored.get(public.peer_id()).unwrap().fingerprint, store.get(public.peer_id()).unwrap().fingerprint);
+        
+        assert!(matches!(
+            TrustStore::restore(&path, "battery staple"),
+            Err(IdentityError::InvalidPassphrase)
+        ));
+    }
+}
@@ -5,21 +5,48 @@
 //! This crate provides:
 //! - Ed25519 keypair generation for signing and identity
 //! - X25519 keypair generation for encryption key exchange
+//! - Kyber768 keypair generation for post-quantum key encapsulation
 //! - Peer identity representation and verification
 //! - Key serialization and deserialization
 //! - Multi-device support with device subkeys
 //! - Trust chain and device revocation
 //! - Trust management and fingerprint verification (TOFU model)
+//! - Web of trust for transitively trusted peers
+//! - QR codes for sharing public identities
+//! - Signed peer profiles (display name, avatar hash, bio)
+//! - Word mnemonics for reading session fingerprints aloud
+//! - Passphrase-encrypted trust store backups
+//! - Signing key rotation with cross-signed proofs
+//! - Identity storage in the OS credential store
+//! - TOTP codes required before signing (multi-factor authentication)
 
+pub mod backup;
+pub mod mfa;
+pub mod mnemonic;
+pub mod profile;
+pub mod qr;
+pub mod rotation;
+pub mod secure_storage;
 pub mod trust;
+pub mod web_of_trust;
+
+pub use mfa::MfaGate;
+pub use mnemonic::Mnemonic;
+pub use profile::PeerProfile;
+pub use rotation::RotationProof;
+pub use secure_storage::SecureIdentityStorage;
+pub use web_of_trust::{TrustSignature, WebOfTrust};
 
 use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use pqcrypto_kyber::kyber768;
+use pqcrypto_traits::kem::{PublicKey as _, SecretKey as _};
 use rand::rngs::OsRng;
 use serde::{Deserialize, Serialize};
 use std::fmt;
 use thiserror::Error;
 use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret as X25519StaticSecret};
 use chrono::{DateTime, Utc};
+use zeroize::ZeroizeOnDrop;
 
 #[derive(Error, Debug)]
 pub enum IdentityError {
@@ -37,24 +64,59 @@ pub enum IdentityError {
     DeviceRevoked(String),
     #[error("Invalid device signature")]
     InvalidDeviceSignature,
+    #[error("QR code error: {0}")]
+    QrCodeError(String),
+    #[error("Invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
+    #[error("Backup error: {0}")]
+    BackupError(String),
+    #[error("Wrong passphrase or corrupted backup")]
+    InvalidPassphrase,
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("No OS credential store is available")]
+    SecureStorageUnavailable,
+    #[error("Signing requires a TOTP code")]
+    MfaRequired,
+    #[error("Invalid TOTP code")]
+    InvalidMfaCode,
+    #[error("MFA error: {0}")]
+    MfaError(String),
+    #[error("An identity with MFA enabled cannot be exported as plain JSON")]
+    MfaExportRefused,
+    #[error("Ephemeral key attestation is stale or from the future")]
+    StaleAttestation,
 }
 
 /// A peer's identity in the network
 ///
-/// Contains both Ed25519 signing keys and X25519 encryption keys.
+/// Contains Ed25519 signing keys, X25519 encryption keys and a Kyber768
+/// key encapsulation key pair.
 /// The PeerId is derived from the Ed25519 public key.
-#[derive(Clone)]
+/// Secret keys are zeroized when the identity is dropped.
+#[derive(Clone, ZeroizeOnDrop)]
 pub struct Identity {
     /// Ed25519 signing key pair
     signing_key: SigningKey,
+    #[zeroize(skip)]
     verifying_key: VerifyingKey,
     
     /// X25519 encryption key pair for key exchange
     encryption_secret: X25519StaticSecret,
+    #[zeroize(skip)]
     encryption_public: X25519PublicKey,
     
+    /// Kyber768 key pair for hybrid post-quantum key exchange
+    kyber_secret: Vec<u8>,
+    #[zeroize(skip)]
+    kyber_public: Vec<u8>,
+    
     /// Unique peer identifier derived from public key
+    #[zeroize(skip)]
     peer_id: PeerId,
+    
+    /// TOTP gate in front of the signing key, if enabled
+    mfa: Option<MfaGate>,
 }
 
 impl Identity {
@@ -67,9 +129,12 @@ impl Identity {
         let verifying_key = signing_key.verifying_key();
         
         // Generate X25519 encryption keypair
-        let encryption_secret = X25519StaticSecret::random_from_rng(&mut rng);
+        let encryption_secret = X25519StaticSecret::random_from_rng(rng);
         let encryption_public = X25519PublicKey::from(&encryption_secret);
         
+        // Generate Kyber768 encapsulation keypair
+        let (kyber_public, kyber_secret) = kyber768::keypair();
+        
         // Derive peer ID from Ed25519 public key
         let peer_id = PeerId::from_public_key(&verifying_key);
         
@@ -78,7 +143,10 @@ impl Identity {
             verifying_key,
             encryption_secret,
             encryption_public,
+            kyber_secret: kyber_secret.as_bytes().to_vec(),
+            kyber_public: kyber_public.as_bytes().to_vec(),
             peer_id,
+            mfa: None,
         })
     }
     
@@ -102,16 +170,88 @@ impl Identity {
         &self.encryption_secret
     }
     
+    /// Get the Kyber768 public encapsulation key
+    ///
+    /// Empty until [`Identity::add_kyber_keys`] has been called on an
+    /// identity imported from an export that predates Kyber support.
+    pub fn kyber_public_key(&self) -> &[u8] {
+        &self.kyber_public
+    }
+    
+    /// Get the Kyber768 secret decapsulation key
+    pub fn kyber_secret_key(&self) -> &[u8] {
+        &self.kyber_secret
+    }
+    
+    /// Whether this identity has a Kyber768 key pair for hybrid key exchange
+    pub fn has_kyber_keys(&self) -> bool {
+        !self.kyber_secret.is_empty()
+    }
+    
+    /// Generate the Kyber768 key pair of an identity that predates Kyber support
+    ///
+    /// Returns `false` if the identity already has one. The identity has to be
+    /// saved again afterwards, or the next load will be without Kyber keys.
+    pub fn add_kyber_keys(&mut self) -> bool {
+        if self.has_kyber_keys() {
+            return false;
+        }
+        let (public, secret) = kyber768::keypair();
+        self.kyber_secret = secret.as_bytes().to_vec();
+        self.kyber_public = public.as_bytes().to_vec();
+        true
+    }
+    
     /// Sign a message with this identity
-    pub fn sign(&self, message: &[u8]) -> Signature {
-        self.signing_key.sign(message)
+    ///
+    /// Fails with `MfaRequired` while an MFA gate is active; see
+    /// `sign_with_mfa`.
+    pub fn sign(&self, message: &[u8]) -> Result<Signature, IdentityError> {
+        if self.mfa.as_ref().is_some_and(MfaGate::required) {
+            return Err(IdentityError::MfaRequired);
+        }
+        Ok(self.signing_key.sign(message))
+    }
+    
+    /// Attest that an ephemeral X25519 key belongs to this identity
+    pub fn attest_ephemeral(&self, ephemeral_public: &X25519PublicKey) -> Result<EphemeralKeyAttestation, IdentityError> {
+        let ephemeral_public = ephemeral_public.to_bytes();
+        let timestamp = Utc::now().timestamp();
+        let digest = EphemeralKeyAttestation::digest(&ephemeral_public, timestamp);
+        
+        Ok(EphemeralKeyAttestation {
+            ephemeral_public,
+            static_signature: self.sign(digest.as_bytes())?.to_bytes().to_vec(),
+            timestamp,
+        })
     }
     
     /// Export identity to JSON format
+    ///
+    /// Refused with `MfaExportRefused` while an MFA gate is enabled: the TOTP
+    /// secret does not go into plain files, and a file without it would load
+    /// as an identity that signs without a code.
     pub fn to_json(&self) -> Result<String, IdentityError> {
+        if self.mfa.is_some() {
+            return Err(IdentityError::MfaExportRefused);
+        }
+        self.export(false)
+    }
+    
+    /// Export identity to JSON format, including the TOTP secret
+    ///
+    /// Only for the OS credential store.
+    pub(crate) fn to_json_with_mfa(&self) -> Result<String, IdentityError> {
+        self.export(true)
+    }
+    
+    fn export(&self, with_mfa: bool) -> Result<String, IdentityError> {
         let export = IdentityExport {
             signing_key: hex::encode(self.signing_key.to_bytes()),
             encryption_secret: hex::encode(self.encryption_secret.to_bytes()),
+            kyber_secret: self.has_kyber_keys().then(|| hex::encode(&self.kyber_secret)),
+            kyber_public: self.has_kyber_keys().then(|| hex::encode(&self.kyber_public)),
+            mfa_secret: self.mfa.as_ref().filter(|_| with_mfa).map(|gate| hex::encode(gate.totp_secret())),
         };
         
         serde_json::to_string_pretty(&export)
@@ -119,6 +259,10 @@ impl Identity {
     }
     
     /// Import identity from JSON format
+    ///
+    /// Exports made before Kyber support load without Kyber768 keys, so hybrid
+    /// key exchange is refused until [`Identity::add_kyber_keys`] has been
+    /// called and the identity saved again.
     pub fn from_json(json: &str) -> Result<Self, IdentityError> {
         let export: IdentityExport = serde_json::from_str(json)
             .map_err(|e| IdentityError::SerializationError(e.to_string()))?;
@@ -145,13 +289,32 @@ impl Identity {
         let encryption_public = X25519PublicKey::from(&encryption_secret);
         let peer_id = PeerId::from_public_key(&verifying_key);
         
-        Ok(Self {
+        let (kyber_secret, kyber_public) = match (&export.kyber_secret, &export.kyber_public) {
+            (Some(secret), Some(public)) => {
+                let decode = |hex_key: &str| hex::decode(hex_key).map_err(|e| IdentityError::SerializationError(e.to_string()));
+                let (secret, public) = (decode(secret)?, decode(public)?);
+                kyber768::SecretKey::from_bytes(&secret).map_err(|_| IdentityError::InvalidPublicKey)?;
+                kyber768::PublicKey::from_bytes(&public).map_err(|_| IdentityError::InvalidPublicKey)?;
+                (secret, public)
+            }
+            _ => (Vec::new(), Vec::new()),
+        };
+        
+        let mut identity = Self {
             signing_key,
             verifying_key,
             encryption_secret,
             encryption_public,
+            kyber_secret,
+            kyber_public,
             peer_id,
-        })
+            mfa: None,
+        };
+        if let Some(secret) = &export.mfa_secret {
+            let secret = hex::decode(secret).map_err(|e| IdentityError::SerializationError(e.to_string()))?;
+            identity.enable_mfa(secret.try_into().map_err(|_| IdentityError::MfaError("TOTP secret is not 20 bytes".to_string()))?)?;
+        }
+        Ok(identity)
     }
 }
 
@@ -159,6 +322,12 @@ impl Identity {
 struct IdentityExport {
     signing_key: String,
     encryption_secret: String,
+    #[serde(default)]
+    kyber_secret: Option<String>,
+    #[serde(default)]
+    kyber_public: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mfa_secret: Option<String>,
 }
 
 /// A unique identifier for a peer in the network
@@ -205,6 +374,9 @@ pub struct PublicIdentity {
     peer_id: PeerId,
     verifying_key: Vec<u8>,
     encryption_public: Vec<u8>,
+    /// Empty for peers that predate Kyber support
+    #[serde(default)]
+    kyber_public: Vec<u8>,
 }
 
 impl PublicIdentity {
@@ -214,6 +386,7 @@ impl PublicIdentity {
             peer_id: identity.peer_id.clone(),
             verifying_key: identity.verifying_key.to_bytes().to_vec(),
             encryption_public: identity.encryption_public.to_bytes().to_vec(),
+            kyber_public: identity.kyber_public.clone(),
         }
     }
     
@@ -244,12 +417,89 @@ impl PublicIdentity {
         Ok(X25519PublicKey::from(bytes))
     }
     
+    /// Get the Kyber768 public encapsulation key
+    pub fn kyber_public_key(&self) -> Result<&[u8], IdentityError> {
+        if self.kyber_public.len() != kyber768::public_key_bytes() {
+            return Err(IdentityError::InvalidPublicKey);
+        }
+        Ok(&self.kyber_public)
+    }
+    
     /// Verify a signature on a message
     pub fn verify(&self, message: &[u8], signature: &Signature) -> Result<(), IdentityError> {
         let key = self.verifying_key()?;
         key.verify(message, signature)
             .map_err(|_| IdentityError::InvalidSignature)
     }
+    
+    /// Verify that an ephemeral key attestation was signed by this identity
+    /// and is recent
+    pub fn verify_attestation(&self, attestation: &EphemeralKeyAttestation) -> Result<(), IdentityError> {
+        self.verify_attestation_at(attestation, Utc::now().timestamp())
+    }
+    
+    /// Verify an ephemeral key attestation against the Unix time `now`
+    ///
+    /// Attestations older than [`MAX_ATTESTATION_AGE_SECS`] or more than
+    /// [`MAX_ATTESTATION_SKEW_SECS`] ahead of `now` are rejected, so a
+    /// captured handshake cannot be replayed indefinitely.
+    pub fn verify_attestation_at(&self, attestation: &EphemeralKeyAttestation, now: i64) -> Result<(), IdentityError> {
+        let age = now.saturating_sub(attestation.timestamp);
+        if !(-MAX_ATTESTATION_SKEW_SECS..=MAX_ATTESTATION_AGE_SECS).contains(&age) {
+            return Err(IdentityError::StaleAttestation);
+        }
+        
+        let sig_bytes: [u8; 64] = attestation
+            .static_signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| IdentityError::InvalidSignature)?;
+        let signature = Signature::from_bytes(&sig_bytes);
+        
+        let digest = EphemeralKeyAttestation::digest(&attestation.ephemeral_public, attestation.timestamp);
+        self.verify(digest.as_bytes(), &signature)
+    }
+}
+
+/// Oldest ephemeral key attestation accepted, in seconds
+pub const MAX_ATTESTATION_AGE_SECS: i64 = 300;
+
+/// How far ahead of the local clock an attestation may be, in seconds
+pub const MAX_ATTESTATION_SKEW_SECS: i64 = 60;
+
+/// Binds an ephemeral X25519 key to a long-term identity
+///
+/// Sent alongside the ephemeral key in the PFS handshake so the receiver can
+/// reject a key substituted by a man-in-the-middle.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct EphemeralKeyAttestation {
+    /// Ephemeral X25519 public key
+    pub ephemeral_public: [u8; 32],
+    
+    /// Ed25519 signature over the BLAKE3 derive-key hash of
+    /// `ephemeral_public || timestamp`
+    pub static_signature: Vec<u8>,
+    
+    /// Unix timestamp (seconds) when the attestation was made
+    pub timestamp: i64,
+}
+
+impl EphemeralKeyAttestation {
+    /// Get the attested ephemeral public key
+    pub fn ephemeral_public_key(&self) -> X25519PublicKey {
+        X25519PublicKey::from(self.ephemeral_public)
+    }
+    
+    /// Digest covered by the signature
+    ///
+    /// Hashed in its own BLAKE3 derive-key context so the signature cannot be
+    /// passed off as a signature over any other message.
+    fn digest(ephemeral_public: &[u8; 32], timestamp: i64) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new_derive_key("otter-identity ephemeral key attestation v1");
+        hasher.update(ephemeral_public);
+        hasher.update(&timestamp.to_le_bytes());
+        hasher.finalize()
+    }
 }
 
 /// Device identifier for multi-device support
@@ -331,7 +581,7 @@ impl DeviceKey {
         message.extend_from_slice(created_at.to_rfc3339().as_bytes());
         
         // Sign with root identity
-        let signature = root_identity.sign(&message);
+        let signature = root_identity.sign(&message)?;
         
         Ok(Self {
             device_id,
@@ -416,8 +666,8 @@ impl RootIdentity {
         
         let device_key = DeviceKey::new(
             device_id.clone(),
-            device_identity.verifying_key().clone(),
-            device_identity.encryption_public_key().clone(),
+            *device_identity.verifying_key(),
+            *device_identity.encryption_public_key(),
             device_name,
             &self.root,
         )?;
@@ -480,12 +730,71 @@ mod tests {
         let identity = Identity::generate().unwrap();
         let message = b"Hello, Otter!";
         
-        let signature = identity.sign(message);
+        let signature = identity.sign(message).unwrap();
         
         let public_identity = PublicIdentity::from_identity(&identity);
         assert!(public_identity.verify(message, &signature).is_ok());
     }
     
+    #[test]
+    fn test_ephemeral_key_attestation() {
+        let identity = Identity::generate().unwrap();
+        let public_identity = PublicIdentity::from_identity(&identity);
+        
+        let ephemeral = X25519StaticSecret::random_from_rng(OsRng);
+        let attestation = identity.attest_ephemeral(&X25519PublicKey::from(&ephemeral)).unwrap();
+        assert!(public_identity.verify_attestation(&attestation).is_ok());
+        
+        // A substituted ephemeral key is rejected
+        let mut substituted = attestation.clone();
+        substituted.ephemeral_public = X25519PublicKey::from(&X25519StaticSecret::random_from_rng(OsRng)).to_bytes();
+        assert!(matches!(
+            public_identity.verify_attestation(&substituted),
+            Err(IdentityError::InvalidSignature)
+        ));
+        
+        // So is an attestation signed by someone else
+        let other = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        assert!(other.verify_attestation(&attestation).is_err());
+    }
+    
+    #[test]
+    fn test_ephemeral_key_attestation_freshness() {
+        let identity = Identity::generate().unwrap();
+        let public_identity = PublicIdentity::from_identity(&identity);
+        let ephemeral = X25519StaticSecret::random_from_rng(OsRng);
+        let attestation = identity.attest_ephemeral(&X25519PublicKey::from(&ephemeral)).unwrap();
+        let made = attestation.timestamp;
+        
+        assert!(public_identity.verify_attestation_at(&attestation, made + MAX_ATTESTATION_AGE_SECS).is_ok());
+        assert!(public_identity.verify_attestation_at(&attestation, made - MAX_ATTESTATION_SKEW_SECS).is_ok());
+        assert!(matches!(
+            public_identity.verify_attestation_at(&attestation, made + MAX_ATTESTATION_AGE_SECS + 1),
+            Err(IdentityError::StaleAttestation)
+        ));
+        assert!(matches!(
+            public_identity.verify_attestation_at(&attestation, made - MAX_ATTESTATION_SKEW_SECS - 1),
+            Err(IdentityError::StaleAttestation)
+        ));
+        
+        // The timestamp is signed, so it cannot be refreshed by a relay
+        let mut refreshed = attestation.clone();
+        refreshed.timestamp = made + 600;
+        assert!(matches!(
+            public_identity.verify_attestation_at(&refreshed, made + 600),
+            Err(IdentityError::InvalidSignature)
+        ));
+    }
+    
+    #[test]
+    fn test_attestation_digest_is_domain_separated() {
+        let ephemeral_public = [7u8; 32];
+        let mut plain = blake3::Hasher::new();
+        plain.update(&ephemeral_public);
+        plain.update(&42i64.to_le_bytes());
+        assert_ne!(EphemeralKeyAttestation::digest(&ephemeral_public, 42), plain.finalize());
+    }
+    
     #[test]
     fn test_identity_export_import() {
         let identity = Identity::generate().unwrap();
@@ -494,14 +803,42 @@ mod tests {
         let restored = Identity::from_json(&json).unwrap();
         
         assert_eq!(identity.peer_id(), restored.peer_id());
+        assert_eq!(identity.kyber_public_key(), restored.kyber_public_key());
         
         // Verify they can sign and verify the same way
         let message = b"test message";
-        let sig = identity.sign(message);
+        let sig = identity.sign(message).unwrap();
         let pub_restored = PublicIdentity::from_identity(&restored);
         assert!(pub_restored.verify(message, &sig).is_ok());
     }
     
+    #[test]
+    fn test_legacy_export_keeps_its_keys_across_loads() {
+        let identity = Identity::generate().unwrap();
+        let mut legacy: serde_json::Value = serde_json::from_str(&identity.to_json().unwrap()).unwrap();
+        legacy.as_object_mut().unwrap().retain(|field, _| field == "signing_key" || field == "encryption_secret");
+        let legacy = legacy.to_string();
+        
+        let first = Identity::from_json(&legacy).unwrap();
+        let second = Identity::from_json(&legacy).unwrap();
+        assert_eq!(first.peer_id(), identity.peer_id());
+        assert_eq!(first.encryption_public_key(), second.encryption_public_key());
+        assert!(!first.has_kyber_keys());
+        assert_eq!(first.kyber_public_key(), second.kyber_public_key());
+        assert!(PublicIdentity::from_identity(&first).kyber_public_key().is_err());
+        
+        // Re-exporting before migration stays a legacy export
+        assert!(!Identity::from_json(&first.to_json().unwrap()).unwrap().has_kyber_keys());
+        
+        // Once migrated and saved, the new key pair survives reloads
+        let mut migrated = first;
+        assert!(migrated.add_kyber_keys());
+        assert!(!migrated.add_kyber_keys());
+        let saved = migrated.to_json().unwrap();
+        assert_eq!(Identity::from_json(&saved).unwrap().kyber_public_key(), migrated.kyber_public_key());
+        assert_eq!(Identity::from_json(&saved).unwrap().kyber_public_key(), Identity::from_json(&saved).unwrap().kyber_public_key());
+    }
+    
     #[test]
     fn test_peer_id_uniqueness() {
         let id1 = Identity::generate().unwrap();
@@ -517,8 +854,8 @@ mod tests {
         
         let device_key = DeviceKey::new(
             DeviceId::generate(),
-            device.verifying_key().clone(),
-            device.encryption_public_key().clone(),
+            *device.verifying_key(),
+            *device.encryption_public_key(),
             "My Device".to_string(),
             &root,
         ).unwrap();
@@ -534,8 +871,8 @@ mod tests {
         
         let device_key = DeviceKey::new(
             DeviceId::generate(),
-            device.verifying_key().clone(),
-            device.encryption_public_key().clone(),
+            *device.verifying_key(),
+            *device.encryption_public_key(),
             "Test Device".to_string(),
             &root,
         ).unwrap();
@@ -0,0 +1,240 @@
+//! # Multi-Factor Signing
+//!
+//! An identity can require a time-based one-time code (TOTP, RFC 6238) from
+//! an authenticator app before it signs anything. With the gate active,
+//! [`Identity::sign`] refuses with `IdentityError::MfaRequired` and signing
+//! goes through [`Identity::sign_with_mfa`] instead.
+//!
+//! Codes are six digits over 30 second steps with HMAC-SHA1, the defaults
+//! every authenticator app understands. One step of clock drift either way
+//! is accepted, and each step's code is accepted only once: a gate remembers
+//! the last step it accepted and rejects codes for that step or any earlier one.
+//!
+//! A gated identity lives only in the OS credential store
+//! ([`crate::SecureIdentityStorage`]); [`Identity::to_json`] refuses to write
+//! it to a plain file.
+
+use crate::{Identity, IdentityError};
+use chrono::Utc;
+use ed25519_dalek::{Signature, Signer};
+use rand::{rngs::OsRng, RngCore};
+use qrcode::{render::unicode, QrCode};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use totp_rs::{Algorithm, Secret, TOTP};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Length of a TOTP time step in seconds
+pub const TOTP_STEP_SECS: u64 = 30;
+
+/// Digits in a code
+const TOTP_DIGITS: usize = 6;
+
+/// Steps of clock drift accepted in either direction
+const TOTP_SKEW: u8 = 1;
+
+/// Issuer shown by authenticator apps
+const TOTP_ISSUER: &str = "Otter";
+
+/// TOTP secret guarding an identity's signing key
+///
+/// Clones share the record of the last accepted step.
+#[derive(Clone, Zeroize, ZeroizeOnDrop)]
+pub struct MfaGate {
+    totp_secret: [u8; 20],
+    required: bool,
+    /// Last accepted time step plus one, 0 before the first code
+    #[zeroize(skip)]
+    last_step: Arc<AtomicU64>,
+}
+
+impl MfaGate {
+    /// Create a required gate for `totp_secret`
+    pub fn new(totp_secret: [u8; 20]) -> Self {
+        Self {
+            totp_secret,
+            required: true,
+            last_step: Arc::new(AtomicU64::new(0)),
+        }
+    }
+    
+    /// Random secret for `Identity::enable_mfa`
+    pub fn generate_secret() -> [u8; 20] {
+        let mut secret = [0u8; 20];
+        OsRng.fill_bytes(&mut secret);
+        secret
+    }
+    
+    /// Whether `Identity::sign` is refused
+    pub fn required(&self) -> bool {
+        self.required
+    }
+    
+    /// The shared secret
+    pub fn totp_secret(&self) -> &[u8; 20] {
+        &self.totp_secret
+    }
+    
+    fn totp(&self) -> Result<TOTP, IdentityError> {
+        TOTP::new(Algorithm::SHA1, TOTP_DIGITS, TOTP_SKEW, TOTP_STEP_SECS, self.totp_secret.to_vec())
+            .map_err(|e| IdentityError::MfaError(e.to_string()))
+    }
+    
+    /// Code for the step holding `unix_time`
+    pub fn generate_code(&self, unix_time: u64) -> Result<String, IdentityError> {
+        Ok(self.totp()?.generate(unix_time))
+    }
+    
+    /// Whether `code` is valid at `unix_time`, give or take one step
+    ///
+    /// An accepted code uses up its step: codes for that step or an earlier
+    /// one are rejected afterwards, so an observed code cannot be replayed.
+    pub fn verify_code(&self, code: &str, unix_time: u64) -> Result<bool, IdentityError> {
+        let totp = self.totp()?;
+        let code = code.trim();
+        let current = unix_time / TOTP_STEP_SECS;
+        let skew = TOTP_SKEW as u64;
+        let matched = (current.saturating_sub(skew)..=current + skew)
+            .find(|step| constant_time_eq::constant_time_eq(totp.generate(step * TOTP_STEP_SECS).as_bytes(), code.as_bytes()));
+        let Some(step) = matched else {
+            return Ok(false);
+        };
+        // Record the step only if it is newer than the last one accepted
+        Ok(self
+            .last_step
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |last| (step + 1 > last).then_some(step + 1))
+            .is_ok())
+    }
+    
+    /// `otpauth://` URI for enrolling the secret in an authenticator app
+    pub fn provisioning_uri(&self, account: &str) -> String {
+        let secret = match Secret::Raw(self.totp_secret.to_vec()).to_encoded() {
+            Secret::Encoded(secret) => secret,
+            Secret::Raw(_) => unreachable!("to_encoded returns an encoded secret"),
+        };
+        format!(
+            "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_STEP_SECS}",
+            issuer = TOTP_ISSUER,
+        )
+    }
+    
+    /// The provisioning URI as a QR code drawn with Unicode half blocks
+    pub fn provisioning_qr_code(&self, account: &str) -> Result<String, IdentityError> {
+        let code = QrCode::new(self.provisioning_uri(account))
+            .map_err(|e| IdentityError::QrCodeError(e.to_string()))?;
+        Ok(code
+            .render::<unicode::Dense1x2>()
+            .dark_color(unicode::Dense1x2::Light)
+            .light_color(unicode::Dense1x2::Dark)
+            .build())
+    }
+}
+
+impl Identity {
+    /// Require a TOTP code for `totp_secret` before signing
+    pub fn enable_mfa(&mut self, totp_secret: [u8; 20]) -> Result<(), IdentityError> {
+        let gate = MfaGate::new(totp_secret);
+        // Rejects secrets totp-rs cannot use before the gate is stored
+        gate.totp()?;
+        self.mfa = Some(gate);
+        Ok(())
+    }
+    
+    /// The MFA gate, if one is set up
+    pub fn mfa_gate(&self) -> Option<&MfaGate> {
+        self.mfa.as_ref()
+    }
+    
+    /// Sign `message` after checking `totp_code` against the current time
+    ///
+    /// Without a gate the code is not checked.
+    pub fn sign_with_mfa(&self, message: &[u8], totp_code: &str) -> Result<Signature, IdentityError> {
+        self.sign_with_mfa_at(message, totp_code, Utc::now().timestamp().max(0) as u64)
+    }
+    
+    fn sign_with_mfa_at(&self, message: &[u8], totp_code: &str, unix_time: u64) -> Result<Signature, IdentityError> {
+        if let Some(gate) = &self.mfa {
+            if !gate.verify_code(totp_code, unix_time)? {
+                return Err(IdentityError::InvalidMfaCode);
+            }
+        }
+        Ok(self.signing_key.sign(message))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Secret of the RFC 6238 SHA-1 test vectors
+    const RFC_SECRET: [u8; 20] = *b"12345678901234567890";
+    
+    #[test]
+    fn test_totp_codes_at_frozen_time() {
+        let gate = MfaGate::new(RFC_SECRET);
+        // Last six digits of the RFC 6238 vectors
+        assert_eq!(gate.generate_code(59).unwrap(), "287082");
+        assert_eq!(gate.generate_code(1_111_111_109).unwrap(), "081804");
+        
+        let now = 1_234_567_890;
+        let code = gate.generate_code(now).unwrap();
+        assert_eq!(code, "005924");
+        assert!(MfaGate::new(RFC_SECRET).verify_code(&code, now).unwrap());
+        assert!(MfaGate::new(RFC_SECRET).verify_code(&code, now + TOTP_STEP_SECS).unwrap());
+        assert!(MfaGate::new(RFC_SECRET).verify_code(&code, now - TOTP_STEP_SECS).unwrap());
+        assert!(!gate.verify_code(&code, now + 3 * TOTP_STEP_SECS).unwrap());
+        assert!(!gate.verify_code("005925", now).unwrap());
+        
+        let uri = gate.provisioning_uri("alice");
+        assert!(uri.starts_with("otpauth://totp/Otter:alice?secret=GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ&"));
+    }
+    
+    #[test]
+    fn test_sign_requires_mfa_once_enabled() {
+        let mut identity = Identity::generate().unwrap();
+        let message = b"rotate keys";
+        identity.sign(message).unwrap();
+        
+        identity.enable_mfa(RFC_SECRET).unwrap();
+        assert!(matches!(identity.sign(message), Err(IdentityError::MfaRequired)));
+        
+        let now = 1_700_000_000;
+        let code = identity.mfa_gate().unwrap().generate_code(now).unwrap();
+        let signature = identity.sign_with_mfa_at(message, &code, now).unwrap();
+        assert!(identity.verifying_key().verify_strict(message, &signature).is_ok());
+        
+        let stale = identity.sign_with_mfa_at(message, &code, now + 10 * TOTP_STEP_SECS);
+        assert!(matches!(stale, Err(IdentityError::InvalidMfaCode)));
+        
+        // Plain exports would drop the gate, so they are refused; the credential store keeps it
+        assert!(matches!(identity.to_json(), Err(IdentityError::MfaExportRefused)));
+        let restored = Identity::from_json(&identity.to_json_with_mfa().unwrap()).unwrap();
+        assert!(matches!(restored.sign(message), Err(IdentityError::MfaRequired)));
+        assert!(restored.sign_with_mfa_at(message, &code, now).is_ok());
+    }
+    
+    #[test]
+    fn test_totp_code_is_accepted_once() {
+        let mut identity = Identity::generate().unwrap();
+        identity.enable_mfa(RFC_SECRET).unwrap();
+        let gate = identity.mfa_gate().unwrap().clone();
+        let message = b"rotate keys";
+        
+        let now = 1_700_000_000;
+        let code = gate.generate_code(now).unwrap();
+        identity.sign_with_mfa_at(message, &code, now).unwrap();
+        
+        // The same code is rejected for the rest of its window, also through clones of the gate
+        let replayed = identity.sign_with_mfa_at(message, &code, now + TOTP_STEP_SECS);
+        assert!(matches!(replayed, Err(IdentityError::InvalidMfaCode)));
+        assert!(!gate.verify_code(&code, now).unwrap());
+        
+        // As is an older code still within the drift window
+        let previous = gate.generate_code(now - TOTP_STEP_SECS).unwrap();
+        assert!(!gate.verify_code(&previous, now).unwrap());
+        
+        // The next step's code is accepted
+        let next = gate.generate_code(now + TOTP_STEP_SECS).unwrap();
+        assert!(identity.sign_with_mfa_at(message, &next, now + TOTP_STEP_SECS).is_ok());
+    }
+}
@@ -0,0 +1,62 @@
+//! # Fingerprint Mnemonics
+//!
+//! Renders a 32-byte session fingerprint as BIP-39 English words, which are
+//! easier to read aloud and compare than hex. 32 bytes take 24 words; the
+//! last word includes a checksum, so a misheard word is usually detected.
+
+use crate::IdentityError;
+use bip39::Mnemonic as Bip39Mnemonic;
+
+/// Conversion between fingerprints and word lists
+pub struct Mnemonic;
+
+impl Mnemonic {
+    /// Encode `bytes` as 24 space-separated words
+    pub fn from_fingerprint(bytes: &[u8; 32]) -> String {
+        Bip39Mnemonic::from_entropy(bytes)
+            .expect("32 bytes is a valid BIP-39 entropy length")
+            .to_string()
+    }
+    
+    /// Decode words produced by [`Mnemonic::from_fingerprint`]
+    ///
+    /// Case and extra whitespace are ignored.
+    pub fn to_fingerprint(words: &str) -> Result<[u8; 32], IdentityError> {
+        let normalized = words.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+        let mnemonic = Bip39Mnemonic::parse_normalized(&normalized)
+            .map_err(|e| IdentityError::InvalidMnemonic(e.to_string()))?;
+        
+        mnemonic
+            .to_entropy()
+            .try_into()
+            .map_err(|_| IdentityError::InvalidMnemonic("Expected 24 words".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_mnemonic_round_trip() {
+        let a = [7u8; 32];
+        let mut b = a;
+        b[31] ^= 1;
+        
+        let words = Mnemonic::from_fingerprint(&a);
+        assert_eq!(words.split(' ').count(), 24);
+        assert_ne!(words, Mnemonic::from_fingerprint(&b));
+        
+        assert_eq!(Mnemonic::to_fingerprint(&words).unwrap(), a);
+        assert_eq!(Mnemonic::to_fingerprint(&format!("  {}\n", words.to_uppercase())).unwrap(), a);
+        
+        // A 12-word phrase holds only 16 bytes
+        let short = words.split(' ').take(12).collect::<Vec<_>>().join(" ");
+        assert!(matches!(Mnemonic::to_fingerprint(&short), Err(IdentityError::InvalidMnemonic(_))));
+        
+        // A swapped word breaks the checksum
+        let mut swapped: Vec<&str> = words.split(' ').collect();
+        swapped.swap(0, 1);
+        assert!(Mnemonic::to_fingerprint(&swapped.join(" ")).is_err());
+    }
+}
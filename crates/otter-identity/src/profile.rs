@@ -0,0 +1,134 @@
+//! # Peer Profiles
+//!
+//! Human-facing metadata (display name, avatar, bio) that a peer publishes
+//! alongside its keys. Profiles are signed with the identity's Ed25519 key so
+//! they cannot be altered by whoever relays them.
+
+use crate::{Identity, IdentityError, PublicIdentity};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::Signature;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Signed profile metadata of a peer
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct PeerProfile {
+    /// Name shown instead of the peer ID
+    pub display_name: String,
+    
+    /// BLAKE3 hash of the avatar image, which is fetched separately
+    pub avatar_blake3: Option<[u8; 32]>,
+    
+    /// Free-form description
+    pub bio: Option<String>,
+    
+    /// When the profile was last changed
+    pub updated_at: DateTime<Utc>,
+    
+    /// Ed25519 signature over all other fields
+    pub signature: Vec<u8>,
+}
+
+impl PeerProfile {
+    /// Digest covered by the signature
+    ///
+    /// Variable-length fields are length-prefixed so that moving bytes between
+    /// fields changes the digest.
+    fn digest(&self) -> blake3::Hash {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&(self.display_name.len() as u64).to_le_bytes());
+        hasher.update(self.display_name.as_bytes());
+        match self.avatar_blake3 {
+            Some(ref hash) => hasher.update(&[1]).update(hash),
+            None => hasher.update(&[0]),
+        };
+        match self.bio {
+            Some(ref bio) => hasher
+                .update(&[1])
+                .update(&(bio.len() as u64).to_le_bytes())
+                .update(bio.as_bytes()),
+            None => hasher.update(&[0]),
+        };
+        hasher.update(&self.updated_at.timestamp().to_le_bytes());
+        hasher.update(&self.updated_at.timestamp_subsec_nanos().to_le_bytes());
+        hasher.finalize()
+    }
+}
+
+impl Identity {
+    /// Create a signed profile, hashing the avatar file if one is given
+    pub fn create_profile(
+        &self,
+        display_name: String,
+        avatar_path: Option<&Path>,
+        bio: Option<String>,
+    ) -> Result<PeerProfile, IdentityError> {
+        let avatar_blake3 = match avatar_path {
+            Some(path) => Some(*blake3::hash(&std::fs::read(path)?).as_bytes()),
+            None => None,
+        };
+        
+        let mut profile = PeerProfile {
+            display_name,
+            avatar_blake3,
+            bio,
+            updated_at: Utc::now(),
+            signature: Vec::new(),
+        };
+        profile.signature = self.sign(profile.digest().as_bytes())?.to_bytes().to_vec();
+        
+        Ok(profile)
+    }
+}
+
+impl PublicIdentity {
+    /// Verify that `profile` was signed by this identity
+    pub fn verify_profile(&self, profile: &PeerProfile) -> Result<(), IdentityError> {
+        let sig_bytes: [u8; 64] = profile
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| IdentityError::InvalidSignature)?;
+        let signature = Signature::from_bytes(&sig_bytes);
+        
+        self.verify(profile.digest().as_bytes(), &signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    
+    #[test]
+    fn test_tampered_profile_fails_verification() {
+        let identity = Identity::generate().unwrap();
+        let public = PublicIdentity::from_identity(&identity);
+        
+        let mut avatar = tempfile::NamedTempFile::new().unwrap();
+        avatar.write_all(b"avatar bytes").unwrap();
+        
+        let profile = identity
+            .create_profile("Alice".to_string(), Some(avatar.path()), Some("Hi".to_string()))
+            .unwrap();
+        
+        assert_eq!(profile.avatar_blake3, Some(*blake3::hash(b"avatar bytes").as_bytes()));
+        assert!(public.verify_profile(&profile).is_ok());
+        
+        let renamed = PeerProfile {
+            display_name: "Mallory".to_string(),
+            ..profile.clone()
+        };
+        assert!(public.verify_profile(&renamed).is_err());
+        
+        let no_bio = PeerProfile {
+            bio: None,
+            ..profile.clone()
+        };
+        assert!(public.verify_profile(&no_bio).is_err());
+        
+        // A valid profile presented as someone else's
+        let other = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        assert!(other.verify_profile(&profile).is_err());
+    }
+}
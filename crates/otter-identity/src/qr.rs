@@ -0,0 +1,140 @@
+//! # QR Code Sharing
+//!
+//! Encodes a public identity as a QR code PNG so it can be scanned instead
+//! of typing a peer ID. The payload is JSON with the peer ID and both public
+//! keys in hex. The Kyber768 key is too large for a code and is left out;
+//! peers learn it from the identity announcement instead.
+//!
+//! Codes are read back with `rqrr`, so any QR code carrying the payload is
+//! accepted, not only those produced by [`PublicIdentity::to_qr_code`].
+
+use crate::{IdentityError, PeerId, PublicIdentity};
+use image::{ImageFormat, Luma};
+use qrcode::{EcLevel, QrCode};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+
+/// Error correction level of generated codes
+const QR_EC_LEVEL: EcLevel = EcLevel::M;
+
+/// What a QR code carries
+#[derive(Debug, Serialize, Deserialize)]
+struct QrPayload {
+    peer_id: String,
+    verifying_key_hex: String,
+    encryption_key_hex: String,
+}
+
+impl PublicIdentity {
+    /// Render this identity as a QR code PNG at least `width` pixels wide
+    pub fn to_qr_code(&self, width: u32) -> Result<Vec<u8>, IdentityError> {
+        let payload = QrPayload {
+            peer_id: self.peer_id.to_string(),
+            verifying_key_hex: hex::encode(&self.verifying_key),
+            encryption_key_hex: hex::encode(&self.encryption_public),
+        };
+        let json = serde_json::to_vec(&payload)
+            .map_err(|e| IdentityError::SerializationError(e.to_string()))?;
+
+        let code = QrCode::with_error_correction_level(&json, QR_EC_LEVEL)
+            .map_err(|e| IdentityError::QrCodeError(e.to_string()))?;
+        let image = code.render::<Luma<u8>>().min_dimensions(width, width).build();
+
+        let mut png = Vec::new();
+        image
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .map_err(|e| IdentityError::QrCodeError(e.to_string()))?;
+        Ok(png)
+    }
+
+    /// Read an identity from a QR code PNG, e.g. one made by [`PublicIdentity::to_qr_code`]
+    ///
+    /// The peer ID must be derived from the verifying key in the code.
+    pub fn from_qr_bytes(png: &[u8]) -> Result<Self, IdentityError> {
+        let image = image::load_from_memory_with_format(png, ImageFormat::Png)
+            .map_err(|e| IdentityError::QrCodeError(e.to_string()))?
+            .to_luma8();
+
+        let json = decode_payload(&image)?;
+        let payload: QrPayload = serde_json::from_slice(&json)
+            .map_err(|e| IdentityError::SerializationError(e.to_string()))?;
+
+        let identity = Self {
+            peer_id: PeerId::from_string(payload.peer_id),
+            verifying_key: hex::decode(&payload.verifying_key_hex).map_err(|_| IdentityError::InvalidPublicKey)?,
+            encryption_public: hex::decode(&payload.encryption_key_hex).map_err(|_| IdentityError::InvalidPublicKey)?,
+            kyber_public: Vec::new(),
+        };
+        identity.encryption_public_key()?;
+        if PeerId::from_public_key(&identity.verifying_key()?) != identity.peer_id {
+            return Err(IdentityError::InvalidPublicKey);
+        }
+
+        Ok(identity)
+    }
+}
+
+/// Find the first QR code in `image` and return its payload
+fn decode_payload(image: &image::GrayImage) -> Result<Vec<u8>, IdentityError> {
+    let mut prepared = rqrr::PreparedImage::prepare_from_greyscale(image.width() as usize, image.height() as usize, |x, y| {
+        image.get_pixel(x as u32, y as u32).0[0]
+    });
+    let grid = prepared
+        .detect_grids()
+        .into_iter()
+        .next()
+        .ok_or_else(|| IdentityError::QrCodeError("No QR code found in image".to_string()))?;
+
+    let mut payload = Vec::new();
+    grid.decode_to(&mut payload)
+        .map_err(|e| IdentityError::QrCodeError(e.to_string()))?;
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Identity;
+
+    #[test]
+    fn test_qr_code_round_trip() {
+        let public = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let png = public.to_qr_code(300).unwrap();
+
+        let image = image::load_from_memory(&png).unwrap();
+        assert!(image.width() >= 300);
+
+        let decoded = PublicIdentity::from_qr_bytes(&png).unwrap();
+        assert_eq!(decoded.peer_id(), public.peer_id());
+        assert_eq!(decoded.verifying_key().unwrap(), public.verifying_key().unwrap());
+        assert_eq!(decoded.encryption_public_key().unwrap(), public.encryption_public_key().unwrap());
+
+        assert!(PublicIdentity::from_qr_bytes(b"not a png").is_err());
+    }
+
+    #[test]
+    fn test_qr_code_survives_damage() {
+        let public = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let png = public.to_qr_code(300).unwrap();
+        let mut image = image::load_from_memory(&png).unwrap().to_luma8();
+
+        // Blot out a patch in the data area; error correction restores it
+        let (width, height) = image.dimensions();
+        for y in height / 2..height / 2 + height / 20 {
+            for x in width / 2..width / 2 + width / 20 {
+                image.put_pixel(x, y, Luma([255]));
+            }
+        }
+        let mut damaged = Vec::new();
+        image.write_to(&mut Cursor::new(&mut damaged), ImageFormat::Png).unwrap();
+
+        let decoded = PublicIdentity::from_qr_bytes(&damaged).unwrap();
+        assert_eq!(decoded.peer_id(), public.peer_id());
+
+        // A code without an identity payload is refused
+        let other = QrCode::new(b"hello").unwrap().render::<Luma<u8>>().min_dimensions(200, 200).build();
+        let mut png = Vec::new();
+        other.write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+        assert!(matches!(PublicIdentity::from_qr_bytes(&png), Err(IdentityError::SerializationError(_))));
+    }
+}
@@ -0,0 +1,125 @@
+//! # Signing Key Rotation
+//!
+//! Replaces the Ed25519 signing key of an identity while keeping its X25519
+//! encryption key, so sessions derived from that key stay valid. The old and
+//! new keys sign each other in a [`RotationProof`], which lets peers that
+//! trusted the old `PeerId` move that trust to the new one.
+
+use crate::{Identity, IdentityError, PeerId, PublicIdentity};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, SigningKey};
+use rand::rngs::OsRng;
+use serde::{Deserialize, Serialize};
+
+/// Domain separator for both rotation signatures
+const ROTATION_CONTEXT: &[u8] = b"otter-key-rotation-v1";
+
+/// Cross-signed link from an old signing key to its replacement
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct RotationProof {
+    pub old_peer_id: PeerId,
+    pub new_peer_id: PeerId,
+    /// Public identity with the new signing key
+    pub new_public: PublicIdentity,
+    /// Old key's signature over the new verifying key and timestamp
+    pub old_signature_over_new_key: Vec<u8>,
+    /// New key's signature over the old peer ID and timestamp
+    pub new_signature_over_old_peer_id: Vec<u8>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl RotationProof {
+    fn new_key_message(new_verifying_key: &[u8], timestamp: &DateTime<Utc>) -> Vec<u8> {
+        [ROTATION_CONTEXT, b"/new-key/", new_verifying_key, timestamp.to_rfc3339().as_bytes()].concat()
+    }
+    
+    fn old_peer_id_message(old_peer_id: &PeerId, timestamp: &DateTime<Utc>) -> Vec<u8> {
+        [ROTATION_CONTEXT, b"/old-peer-id/", old_peer_id.as_str().as_bytes(), timestamp.to_rfc3339().as_bytes()].concat()
+    }
+}
+
+fn parse_signature(bytes: &[u8]) -> Result<Signature, IdentityError> {
+    let bytes: [u8; 64] = bytes.try_into().map_err(|_| IdentityError::InvalidSignature)?;
+    Ok(Signature::from_bytes(&bytes))
+}
+
+impl Identity {
+    /// Replace the signing key, returning the proof linking the old `PeerId` to the new one
+    ///
+    /// The encryption key is kept. The old signing key is zeroized.
+    pub fn rotate_signing_key(&mut self) -> Result<RotationProof, IdentityError> {
+        let signing_key = SigningKey::generate(&mut OsRng);
+        let verifying_key = signing_key.verifying_key();
+        let new_peer_id = PeerId::from_public_key(&verifying_key);
+        let old_peer_id = self.peer_id.clone();
+        let timestamp = Utc::now();
+        
+        let old_signature = self.sign(&RotationProof::new_key_message(verifying_key.as_bytes(), &timestamp))?;
+        self.signing_key = signing_key;
+        self.verifying_key = verifying_key;
+        self.peer_id = new_peer_id.clone();
+        let new_signature = self.sign(&RotationProof::old_peer_id_message(&old_peer_id, &timestamp))?;
+        
+        Ok(RotationProof {
+            old_peer_id,
+            new_peer_id,
+            new_public: PublicIdentity::from_identity(self),
+            old_signature_over_new_key: old_signature.to_bytes().to_vec(),
+            new_signature_over_old_peer_id: new_signature.to_bytes().to_vec(),
+            timestamp,
+        })
+    }
+}
+
+impl PublicIdentity {
+    /// Check that `proof` was signed by both `old_public` and the key it rotates to
+    pub fn verify_rotation(proof: &RotationProof, old_public: &PublicIdentity) -> Result<(), IdentityError> {
+        let old_key = old_public.verifying_key()?;
+        let new_key = proof.new_public.verifying_key()?;
+        if PeerId::from_public_key(&old_key) != proof.old_peer_id
+            || PeerId::from_public_key(&new_key) != proof.new_peer_id
+            || proof.new_public.peer_id() != &proof.new_peer_id
+        {
+            return Err(IdentityError::InvalidPublicKey);
+        }
+        
+        old_public.verify(
+            &RotationProof::new_key_message(new_key.as_bytes(), &proof.timestamp),
+            &parse_signature(&proof.old_signature_over_new_key)?,
+        )?;
+        proof.new_public.verify(
+            &RotationProof::old_peer_id_message(&proof.old_peer_id, &proof.timestamp),
+            &parse_signature(&proof.new_signature_over_old_peer_id)?,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_rotation_proof_is_cross_signed() {
+        let mut identity = Identity::generate().unwrap();
+        let old_public = PublicIdentity::from_identity(&identity);
+        let encryption_key = *identity.encryption_public_key();
+        
+        let proof = identity.rotate_signing_key().unwrap();
+        assert_eq!(&proof.old_peer_id, old_public.peer_id());
+        assert_eq!(&proof.new_peer_id, identity.peer_id());
+        assert_ne!(proof.old_peer_id, proof.new_peer_id);
+        assert_eq!(identity.encryption_public_key(), &encryption_key);
+        PublicIdentity::verify_rotation(&proof, &old_public).unwrap();
+        
+        // A proof does not verify against another identity
+        let stranger = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        assert!(PublicIdentity::verify_rotation(&proof, &stranger).is_err());
+        
+        let mut forged = proof.clone();
+        forged.timestamp += chrono::Duration::seconds(1);
+        assert!(matches!(
+            PublicIdentity::verify_rotation(&forged, &old_public),
+            Err(IdentityError::InvalidSignature)
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+//! # Secure Identity Storage
+//!
+//! Keeps an identity in the operating system's credential store instead of
+//! a plain file: the Keychain on macOS, the Credential Manager on Windows
+//! and the Secret Service on Linux, all through the `keyring` crate.
+//!
+//! On other platforms, or when no credential store is reachable (e.g. a
+//! Linux session without a Secret Service daemon), every operation fails
+//! with `SecureStorageUnavailable` and callers fall back to file storage.
+
+use crate::{Identity, IdentityError};
+
+/// Account name of the credential entry; the label names the service
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+const ENTRY_USER: &str = "identity";
+
+/// Identity storage in the OS credential store
+///
+/// Calls block on the platform service; async code should run them with
+/// `spawn_blocking`.
+pub struct SecureIdentityStorage;
+
+impl SecureIdentityStorage {
+    /// Label under which `identity` is stored, `otter/<peer_id>`
+    pub fn label(identity: &Identity) -> String {
+        format!("otter/{}", identity.peer_id())
+    }
+    
+    /// Store `identity` under `label`, replacing any identity stored there
+    pub fn save(identity: &Identity, label: &str) -> Result<(), IdentityError> {
+        backend::save(&backend::entry(label)?, identity)
+    }
+    
+    /// Load the identity stored under `label`, if there is one
+    pub fn load(label: &str) -> Result<Option<Identity>, IdentityError> {
+        backend::load(&backend::entry(label)?)
+    }
+    
+    /// Remove the identity stored under `label`; succeeds if there is none
+    pub fn delete(label: &str) -> Result<(), IdentityError> {
+        backend::delete(&backend::entry(label)?)
+    }
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows", target_os = "linux"))]
+mod backend {
+    use super::ENTRY_USER;
+    use crate::{Identity, IdentityError};
+    use keyring::{Entry, Error};
+    
+    fn map_error(error: Error) -> IdentityError {
+        match error {
+            Error::NoStorageAccess(_) | Error::PlatformFailure(_) => IdentityError::SecureStorageUnavailable,
+            other => IdentityError::SerializationError(other.to_string()),
+        }
+    }
+    
+    pub(super) fn entry(label: &str) -> Result<Entry, IdentityError> {
+        Entry::new(label, ENTRY_USER).map_err(map_error)
+    }
+    
+    pub(super) fn save(entry: &Entry, identity: &Identity) -> Result<(), IdentityError> {
+        entry.set_password(&identity.to_json_with_mfa()?).map_err(map_error)
+    }
+    
+    pub(super) fn load(entry: &Entry) -> Result<Option<Identity>, IdentityError> {
+        match entry.get_password() {
+            Ok(json) => Identity::from_json(&json).map(Some),
+            Err(Error::NoEntry) => Ok(None),
+            Err(e) => Err(map_error(e)),
+        }
+    }
+    
+    pub(super) fn delete(entry: &Entry) -> Result<(), IdentityError> {
+        match entry.delete_credential() {
+            Ok(()) | Err(Error::NoEntry) => Ok(()),
+            Err(e) => Err(map_error(e)),
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod backend {
+    use crate::{Identity, IdentityError};
+    
+    /// No credential store on this platform
+    pub(super) struct Entry;
+    
+    pub(super) fn entry(_label: &str) -> Result<Entry, IdentityError> {
+        Err(IdentityError::SecureStorageUnavailable)
+    }
+    
+    pub(super) fn save(_entry: &Entry, _identity: &Identity) -> Result<(), IdentityError> {
+        Err(IdentityError::SecureStorageUnavailable)
+    }
+    
+    pub(super) fn load(_entry: &Entry) -> Result<Option<Identity>, IdentityError> {
+        Err(IdentityError::SecureStorageUnavailable)
+    }
+    
+    pub(super) fn delete(_entry: &Entry) -> Result<(), IdentityError> {
+        Err(IdentityError::SecureStorageUnavailable)
+    }
+}
+
+#[cfg(all(test, any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod tests {
+    use super::*;
+    use keyring::mock::{self, MockCredential};
+    use keyring::Error;
+    
+    #[test]
+    fn test_identity_round_trip_through_mock_store() {
+        keyring::set_default_credential_builder(mock::default_credential_builder());
+        
+        let identity = Identity::generate().unwrap();
+        let label = SecureIdentityStorage::label(&identity);
+        assert_eq!(label, format!("otter/{}", identity.peer_id()));
+        
+        // Mock credentials only persist within one entry
+        let entry = backend::entry(&label).unwrap();
+        assert!(backend::load(&entry).unwrap().is_none());
+        
+        backend::save(&entry, &identity).unwrap();
+        let loaded = backend::load(&entry).unwrap().unwrap();
+        assert_eq!(loaded.peer_id(), identity.peer_id());
+        assert_eq!(loaded.encryption_public_key(), identity.encryption_public_key());
+        
+        backend::delete(&entry).unwrap();
+        assert!(backend::load(&entry).unwrap().is_none());
+        backend::delete(&entry).unwrap();
+        
+        // A store the process cannot reach is reported as unavailable
+        let mock: &MockCredential = entry.get_credential().downcast_ref().unwrap();
+        mock.set_error(Error::NoStorageAccess("locked".into()));
+        assert!(matches!(
+            backend::save(&entry, &identity),
+            Err(IdentityError::SecureStorageUnavailable)
+        ));
+    }
+}
@@ -4,11 +4,14 @@
 //!
 //! Features:
 //! - Fingerprint generation and display
+//! - Emoji fingerprints for side-by-side comparison
 //! - Trust-on-first-use (TOFU) model
-//! - Key change warnings
+//! - Key change warnings, including a key that changes under the same peer ID
 //! - Device approval flow
+//! - Trust carried over signing key rotations
 
-use crate::{DeviceId, DeviceKey, Identity, PeerId, PublicIdentity};
+use crate::rotation::RotationProof;
+use crate::{DeviceId, DeviceKey, PeerId, PublicIdentity};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -24,6 +27,43 @@ pub enum TrustError {
     DeviceNotApproved(String),
     #[error("Serialization error: {0}")]
     SerializationError(String),
+    #[error("Invalid key rotation: {0}")]
+    InvalidRotation(String),
+}
+
+/// Emoji alphabet for emoji fingerprints, indexed by 6-bit groups
+const FINGERPRINT_EMOJI: [&str; 64] = [
+    "🦦", "🐶", "🐱", "🦊", "🐻", "🐼", "🐨", "🐯",
+    "🦁", "🐮", "🐷", "🐸", "🐵", "🐔", "🐧", "🐦",
+    "🦆", "🦉", "🐺", "🐴", "🦄", "🐝", "🐛", "🦋",
+    "🐌", "🐢", "🐍", "🦎", "🐙", "🦑", "🦀", "🐬",
+    "🐳", "🦈", "🐊", "🦒", "🐘", "🦔", "🌵", "🌲",
+    "🌻", "🍄", "🌙", "⭐", "🔥", "🌈", "⚡", "❄️",
+    "🍎", "🍋", "🍉", "🍇", "🍓", "🍒", "🥕", "🌽",
+    "🎈", "🎁", "🔑", "🔔", "⚓", "🚀", "🎸", "⚽",
+];
+
+/// Number of emoji in an emoji fingerprint
+pub const EMOJI_FINGERPRINT_LEN: usize = 8;
+
+/// Compute a short emoji fingerprint of a peer's signing and encryption keys
+///
+/// Meant to be read aloud or compared side by side; 8 emoji carry 48 bits.
+pub fn emoji_fingerprint(public_identity: &PublicIdentity) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&public_identity.verifying_key);
+    hasher.update(&public_identity.encryption_public);
+    let hash = hasher.finalize();
+    
+    // Take 6 bits per emoji from the front of the hash
+    let bits = hash.as_bytes()[..6]
+        .iter()
+        .fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+    
+    (0..EMOJI_FINGERPRINT_LEN)
+        .map(|i| FINGERPRINT_EMOJI[((bits >> (42 - 6 * i)) & 0x3f) as usize])
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 /// Trust level for a peer
@@ -68,6 +108,10 @@ pub struct TrustRecord {
     
     /// Optional user-assigned name
     pub user_assigned_name: Option<String>,
+    
+    /// Key rotations that led to this peer ID, oldest first
+    #[serde(default)]
+    pub rotations: Vec<RotationProof>,
 }
 
 /// Device approval status
@@ -95,6 +139,7 @@ impl TrustRecord {
             previous_fingerprints: Vec::new(),
             approved_devices: HashMap::new(),
             user_assigned_name: None,
+            rotations: Vec::new(),
         }
     }
     
@@ -233,6 +278,62 @@ impl TrustStore {
         Ok(())
     }
     
+    /// Record a peer and mark it verified after a successful fingerprint comparison
+    pub fn pin(&mut self, public_identity: PublicIdentity) -> Result<(), TrustError> {
+        let peer_id = public_identity.peer_id().clone();
+        self.add_or_update(public_identity)?;
+        self.mark_verified(&peer_id)
+    }
+    
+    /// Record `public_identity` as the key of `peer_id` the first time the peer is seen
+    ///
+    /// Trust on first use: a peer that is already known keeps its stored
+    /// key and trust level, which is returned.
+    pub fn first_contact(&mut self, peer_id: &PeerId, public_identity: PublicIdentity) -> TrustLevel {
+        self.records
+            .entry(peer_id.as_str().to_string())
+            .or_insert_with(|| TrustRecord::new(peer_id.clone(), public_identity))
+            .trust_level
+    }
+    
+    /// Check that `peer_id` still presents the key recorded on first contact
+    ///
+    /// A different key is a possible key-change attack: the record is marked
+    /// `KeyChanged`, so `should_warn` reports it, and the stored key is kept.
+    pub fn verify_or_warn(&mut self, peer_id: &PeerId, public_identity: &PublicIdentity) -> Result<TrustLevel, TrustError> {
+        let record = self
+            .records
+            .get_mut(peer_id.as_str())
+            .ok_or(TrustError::PeerNotFound)?;
+        
+        let stored = &record.public_identity;
+        if stored.verifying_key != public_identity.verifying_key
+            || stored.encryption_public != public_identity.encryption_public
+        {
+            record.trust_level = TrustLevel::KeyChanged;
+            return Err(TrustError::KeyMismatch(peer_id.as_str().to_string()));
+        }
+        
+        record.last_seen = Utc::now();
+        Ok(record.trust_level)
+    }
+    
+    /// Set the trust level of a known peer
+    pub fn promote(&mut self, peer_id: &PeerId, new_level: TrustLevel) -> Result<(), TrustError> {
+        let record = self
+            .records
+            .get_mut(peer_id.as_str())
+            .ok_or(TrustError::PeerNotFound)?;
+        
+        record.trust_level = new_level;
+        Ok(())
+    }
+    
+    /// Withdraw the trust given to a peer, keeping its recorded key
+    pub fn revoke(&mut self, peer_id: &PeerId) -> Result<(), TrustError> {
+        self.promote(peer_id, TrustLevel::Unknown)
+    }
+    
     /// Check if should warn about key change
     pub fn should_warn(&self, peer_id: &PeerId) -> bool {
         self.records
@@ -241,6 +342,35 @@ impl TrustStore {
             .unwrap_or(false)
     }
     
+    /// Move the record of `proof.old_peer_id` to the peer ID it rotated to
+    ///
+    /// The proof must be signed by the key we know for the old peer ID. The
+    /// record keeps its trust level, devices and name, and the proof is stored
+    /// with it.
+    pub fn apply_rotation(&mut self, proof: RotationProof) -> Result<TrustLevel, TrustError> {
+        let old_record = self
+            .records
+            .get(proof.old_peer_id.as_str())
+            .ok_or(TrustError::PeerNotFound)?;
+        PublicIdentity::verify_rotation(&proof, &old_record.public_identity)
+            .map_err(|e| TrustError::InvalidRotation(e.to_string()))?;
+        
+        let mut record = self
+            .records
+            .remove(proof.old_peer_id.as_str())
+            .ok_or(TrustError::PeerNotFound)?;
+        record.previous_fingerprints.push(record.fingerprint.clone());
+        record.fingerprint = TrustRecord::compute_fingerprint(&proof.new_public);
+        record.peer_id = proof.new_peer_id.clone();
+        record.public_identity = proof.new_public.clone();
+        record.last_seen = Utc::now();
+        record.rotations.push(proof);
+        
+        let trust_level = record.trust_level;
+        self.records.insert(record.peer_id.as_str().to_string(), record);
+        Ok(trust_level)
+    }
+    
     /// Get all verified peers
     pub fn verified_peers(&self) -> Vec<&TrustRecord> {
         self.records
@@ -273,6 +403,7 @@ impl Default for TrustStore {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Identity;
     
     #[test]
     fn test_trust_record_creation() {
@@ -335,7 +466,7 @@ mod tests {
         
         // Simulate key change (new identity with same peer ID would be different in reality)
         let identity2 = Identity::generate().unwrap();
-        let public2 = PublicIdentity::from_identity(&identity2);
+        let _public2 = PublicIdentity::from_identity(&identity2);
         
         // In real scenario, this would be same peer with rotated keys
         // For test, we verify key change detection logic exists
@@ -343,6 +474,84 @@ mod tests {
         assert_eq!(record.trust_level, TrustLevel::Verified);
     }
     
+    #[test]
+    fn test_first_contact_and_key_change_attack() {
+        let mut store = TrustStore::new();
+        let alice = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let mallory = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let peer_id = alice.peer_id().clone();
+        
+        assert!(matches!(store.verify_or_warn(&peer_id, &alice), Err(TrustError::PeerNotFound)));
+        assert_eq!(store.first_contact(&peer_id, alice.clone()), TrustLevel::Unknown);
+        store.promote(&peer_id, TrustLevel::Verified).unwrap();
+        
+        // A later first contact does not replace the recorded key
+        assert_eq!(store.first_contact(&peer_id, mallory.clone()), TrustLevel::Verified);
+        assert_eq!(store.verify_or_warn(&peer_id, &alice).unwrap(), TrustLevel::Verified);
+        
+        // Another key under the same peer ID is refused and flagged
+        assert!(matches!(store.verify_or_warn(&peer_id, &mallory), Err(TrustError::KeyMismatch(_))));
+        assert!(store.should_warn(&peer_id));
+        assert_eq!(store.get(&peer_id).unwrap().public_identity.verifying_key, alice.verifying_key);
+        
+        store.revoke(&peer_id).unwrap();
+        assert_eq!(store.verify_or_warn(&peer_id, &alice).unwrap(), TrustLevel::Unknown);
+        assert!(store.revoke(mallory.peer_id()).is_err());
+    }
+    
+    #[test]
+    fn test_emoji_fingerprint_and_pin() {
+        let identity = Identity::generate().unwrap();
+        let public = PublicIdentity::from_identity(&identity);
+        
+        let fingerprint = emoji_fingerprint(&public);
+        assert_eq!(fingerprint.split(' ').count(), EMOJI_FINGERPRINT_LEN);
+        assert_eq!(fingerprint, emoji_fingerprint(&public));
+        
+        let other = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        assert_ne!(fingerprint, emoji_fingerprint(&other));
+        
+        let mut store = TrustStore::new();
+        store.pin(public.clone()).unwrap();
+        assert_eq!(store.get(public.peer_id()).unwrap().trust_level, TrustLevel::Verified);
+    }
+    
+    #[test]
+    fn test_rotation_migrates_trust() {
+        let mut identity = Identity::generate().unwrap();
+        let old_public = PublicIdentity::from_identity(&identity);
+        let old_peer_id = old_public.peer_id().clone();
+        
+        let mut store = TrustStore::new();
+        store.pin(old_public).unwrap();
+        store.get_mut(&old_peer_id).unwrap().user_assigned_name = Some("Alice".to_string());
+        
+        let proof = identity.rotate_signing_key().unwrap();
+        let new_peer_id = identity.peer_id().clone();
+        assert!(store.get(&new_peer_id).is_none());
+        
+        assert_eq!(store.apply_rotation(proof.clone()).unwrap(), TrustLevel::Verified);
+        assert!(store.get(&old_peer_id).is_none());
+        let record = store.get(&new_peer_id).unwrap();
+        assert_eq!(record.trust_level, TrustLevel::Verified);
+        assert_eq!(record.user_assigned_name.as_deref(), Some("Alice"));
+        assert_eq!(record.rotations.len(), 1);
+        assert_eq!(record.previous_fingerprints.len(), 1);
+        assert!(!store.should_warn(&new_peer_id));
+        
+        // The proof is only accepted once, and survives a JSON round trip
+        assert!(matches!(store.apply_rotation(proof), Err(TrustError::PeerNotFound)));
+        let restored = TrustStore::from_json(&store.to_json().unwrap()).unwrap();
+        assert_eq!(restored.get(&new_peer_id).unwrap().rotations[0].old_peer_id, old_peer_id);
+        
+        // A rotation signed by someone else is rejected
+        let mut impostor = Identity::generate().unwrap();
+        let mut forged = impostor.rotate_signing_key().unwrap();
+        forged.old_peer_id = new_peer_id.clone();
+        assert!(matches!(store.apply_rotation(forged), Err(TrustError::InvalidRotation(_))));
+        assert_eq!(store.get(&new_peer_id).unwrap().trust_level, TrustLevel::Verified);
+    }
+    
     #[test]
     fn test_device_approval() {
         let identity = Identity::generate().unwrap();
@@ -352,8 +561,8 @@ mod tests {
         let device_identity = Identity::generate().unwrap();
         let device_key = DeviceKey::new(
             DeviceId::generate(),
-            device_identity.verifying_key().clone(),
-            device_identity.encryption_public_key().clone(),
+            *device_identity.verifying_key(),
+            *device_identity.encryption_public_key(),
             "Test Device".to_string(),
             &identity,
         ).unwrap();
@@ -0,0 +1,183 @@
+//! # Web of Trust
+//!
+//! Lets a user transitively trust peers vouched for by peers they already
+//! trust. Each vouch is an Ed25519 signature by the voucher over
+//! `"I vouch for <vouchee_peer_id>"`, checked before it enters the graph.
+
+use crate::{Identity, IdentityError, PeerId, PublicIdentity};
+use ed25519_dalek::Signature;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// How far a peer is from the owner of the graph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrustLevel {
+    /// Vouched for by the owner (or the owner itself)
+    Direct,
+    /// Reachable through this many vouches
+    Indirect(u8),
+    /// No vouching chain within the hop limit
+    Unknown,
+}
+
+/// A voucher's signature over `"I vouch for <vouchee_peer_id>"`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrustSignature(Vec<u8>);
+
+impl TrustSignature {
+    fn statement(vouchee: &PeerId) -> String {
+        format!("I vouch for {}", vouchee)
+    }
+    
+    /// Check that `voucher` signed this vouch for `vouchee`
+    pub fn verify(&self, voucher: &PublicIdentity, vouchee: &PeerId) -> Result<(), IdentityError> {
+        let bytes: [u8; 64] = self
+            .0
+            .as_slice()
+            .try_into()
+            .map_err(|_| IdentityError::InvalidSignature)?;
+        
+        voucher.verify(Self::statement(vouchee).as_bytes(), &Signature::from_bytes(&bytes))
+    }
+}
+
+/// Graph of who vouched for whom, as seen by its owner
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebOfTrust {
+    /// Peer whose point of view trust levels are computed from
+    owner: PeerId,
+    
+    /// Vouches keyed by voucher
+    graph: HashMap<PeerId, Vec<(PeerId, TrustSignature)>>,
+}
+
+impl WebOfTrust {
+    /// Create an empty graph owned by `owner`
+    pub fn new(owner: PeerId) -> Self {
+        Self {
+            owner,
+            graph: HashMap::new(),
+        }
+    }
+    
+    /// Get the owner of the graph
+    pub fn owner(&self) -> &PeerId {
+        &self.owner
+    }
+    
+    /// Sign a vouch for `vouchee`
+    pub fn vouch(voucher: &Identity, vouchee: &PeerId) -> Result<TrustSignature, IdentityError> {
+        let signature = voucher.sign(TrustSignature::statement(vouchee).as_bytes())?;
+        Ok(TrustSignature(signature.to_bytes().to_vec()))
+    }
+    
+    /// Add a vouch to the graph after checking its signature
+    pub fn add_vouch(
+        &mut self,
+        voucher: &PublicIdentity,
+        vouchee: PeerId,
+        signature: TrustSignature,
+    ) -> Result<(), IdentityError> {
+        signature.verify(voucher, &vouchee)?;
+        
+        let vouches = self.graph.entry(voucher.peer_id().clone()).or_default();
+        vouches.retain(|(peer_id, _)| *peer_id != vouchee);
+        vouches.push((vouchee, signature));
+        Ok(())
+    }
+    
+    /// Remove every vouch made by `voucher`
+    pub fn remove_voucher(&mut self, voucher: &PeerId) {
+        self.graph.remove(voucher);
+    }
+    
+    /// Trust level of `peer_id`, ignoring chains longer than `max_hops`
+    pub fn trust_level(&self, peer_id: &PeerId, max_hops: u8) -> TrustLevel {
+        let chain = self.verify_vouching_chain(peer_id);
+        let hops = match chain.len() {
+            0 => return TrustLevel::Unknown,
+            len => len - 1,
+        };
+        
+        match hops {
+            0 | 1 => TrustLevel::Direct,
+            hops if hops <= max_hops as usize => TrustLevel::Indirect(hops as u8),
+            _ => TrustLevel::Unknown,
+        }
+    }
+    
+    /// Shortest vouching chain from the owner to `target`, both included
+    ///
+    /// Returns an empty chain if `target` is unreachable.
+    pub fn verify_vouching_chain(&self, target: &PeerId) -> Vec<PeerId> {
+        let mut previous: HashMap<&PeerId, &PeerId> = HashMap::new();
+        let mut visited: HashSet<&PeerId> = HashSet::from([&self.owner]);
+        let mut queue = VecDeque::from([&self.owner]);
+        
+        while let Some(current) = queue.pop_front() {
+            if current == target {
+                let mut chain = vec![current.clone()];
+                let mut node = current;
+                while let Some(prev) = previous.get(node) {
+                    chain.push((*prev).clone());
+                    node = prev;
+                }
+                chain.reverse();
+                return chain;
+            }
+            
+            for (vouchee, _) in self.graph.get(current).into_iter().flatten() {
+                if visited.insert(vouchee) {
+                    previous.insert(vouchee, current);
+                    queue.push_back(vouchee);
+                }
+            }
+        }
+        
+        Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_three_hop_chain() {
+        let identities: Vec<Identity> = (0..4).map(|_| Identity::generate().unwrap()).collect();
+        let peer_ids: Vec<PeerId> = identities.iter().map(|i| i.peer_id().clone()).collect();
+        let mut wot = WebOfTrust::new(peer_ids[0].clone());
+        
+        // owner -> 1 -> 2 -> 3
+        for i in 0..3 {
+            let signature = WebOfTrust::vouch(&identities[i], &peer_ids[i + 1]).unwrap();
+            wot.add_vouch(&PublicIdentity::from_identity(&identities[i]), peer_ids[i + 1].clone(), signature)
+                .unwrap();
+        }
+        
+        assert_eq!(wot.trust_level(&peer_ids[1], 3), TrustLevel::Direct);
+        assert_eq!(wot.trust_level(&peer_ids[2], 3), TrustLevel::Indirect(2));
+        assert_eq!(wot.trust_level(&peer_ids[3], 3), TrustLevel::Indirect(3));
+        assert_eq!(wot.trust_level(&peer_ids[3], 2), TrustLevel::Unknown);
+        assert_eq!(wot.verify_vouching_chain(&peer_ids[3]), peer_ids);
+        
+        let stranger = Identity::generate().unwrap();
+        assert_eq!(wot.trust_level(stranger.peer_id(), 10), TrustLevel::Unknown);
+        assert!(wot.verify_vouching_chain(stranger.peer_id()).is_empty());
+    }
+    
+    #[test]
+    fn test_forged_vouch_rejected() {
+        let owner = Identity::generate().unwrap();
+        let forger = Identity::generate().unwrap();
+        let target = Identity::generate().unwrap();
+        let mut wot = WebOfTrust::new(owner.peer_id().clone());
+        
+        // Signed by the forger but presented as the owner's vouch
+        let signature = WebOfTrust::vouch(&forger, target.peer_id()).unwrap();
+        assert!(wot
+            .add_vouch(&PublicIdentity::from_identity(&owner), target.peer_id().clone(), signature)
+            .is_err());
+        assert_eq!(wot.trust_level(target.peer_id(), 5), TrustLevel::Unknown);
+    }
+}
@@ -0,0 +1,111 @@
+//! # Legacy Peer Compatibility
+//!
+//! Older clients do not understand every message this one can send. The
+//! level a peer supports is read from its handshake, and messages for it are
+//! downgraded to what it can read:
+//!
+//! - protocol version 0 predates encryption, so it gets `Message::Text`
+//! - a peer that does not advertise signed messages gets a plain
+//!   `Message::Encrypted`, without signature
+//!
+//! Idempotency keys are a separate capability: only peers that advertise it
+//! are sent `Message::EncryptedWithIdempotency`.
+//!
+//! Downgrading to version 0 sends the text in the clear.
+
+use otter_protocol::{Capability, CapabilityAdvertisement, Handshake};
+use serde::{Deserialize, Serialize};
+
+/// Custom capability advertised by clients that read `SignedEncryptedMessage`
+pub const SIGNED_MESSAGES_CAPABILITY: &str = "signed-messages";
+
+/// Custom capability advertised by clients that read `Message::EncryptedWithIdempotency`
+pub const IDEMPOTENCY_CAPABILITY: &str = "idempotency-keys";
+
+/// Newest message format a peer can read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+pub enum PeerCompatibilityLevel {
+    /// Signed, idempotent encrypted messages
+    #[default]
+    Full,
+    /// Encrypted messages without a signature
+    LegacyNoSigning,
+    /// Plain text only
+    LegacyV0,
+}
+
+impl PeerCompatibilityLevel {
+    /// Level of the peer that sent `handshake`
+    pub fn from_handshake(handshake: &Handshake) -> Self {
+        if handshake.version == 0 {
+            PeerCompatibilityLevel::LegacyV0
+        } else if !handshake.supports(&Capability::Custom(SIGNED_MESSAGES_CAPABILITY.to_string())) {
+            PeerCompatibilityLevel::LegacyNoSigning
+        } else {
+            PeerCompatibilityLevel::Full
+        }
+    }
+    
+    /// Capability to advertise so peers send this client signed messages
+    pub fn signed_messages_capability() -> CapabilityAdvertisement {
+        Capability::Custom(SIGNED_MESSAGES_CAPABILITY.to_string()).into()
+    }
+    
+    /// Capability to advertise so peers tag messages with their idempotency key
+    pub fn idempotency_capability() -> CapabilityAdvertisement {
+        Capability::Custom(IDEMPOTENCY_CAPABILITY.to_string()).into()
+    }
+    
+    /// Whether the peer that sent `handshake` reads idempotency keys
+    pub fn reads_idempotency_keys(handshake: &Handshake) -> bool {
+        handshake.version != 0 && handshake.supports(&Capability::Custom(IDEMPOTENCY_CAPABILITY.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use otter_identity::{Identity, PublicIdentity};
+    
+    #[test]
+    fn test_level_from_handshake() {
+        let public = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let current = Handshake::new(
+            public.clone(),
+            vec![Capability::E2EEncryption.into(), PeerCompatibilityLevel::signed_messages_capability()],
+        );
+        assert_eq!(PeerCompatibilityLevel::from_handshake(&current), PeerCompatibilityLevel::Full);
+        
+        let unsigned = Handshake::new(public.clone(), vec![Capability::E2EEncryption.into()]);
+        assert_eq!(PeerCompatibilityLevel::from_handshake(&unsigned), PeerCompatibilityLevel::LegacyNoSigning);
+        
+        // Advertised but switched off counts as missing
+        let mut unavailable = current.clone();
+        unavailable.capabilities[1].available = false;
+        assert_eq!(PeerCompatibilityLevel::from_handshake(&unavailable), PeerCompatibilityLevel::LegacyNoSigning);
+        
+        let mut v0 = current;
+        v0.version = 0;
+        assert_eq!(PeerCompatibilityLevel::from_handshake(&v0), PeerCompatibilityLevel::LegacyV0);
+    }
+    
+    #[test]
+    fn test_idempotency_is_advertised_separately() {
+        let public = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let signed_only = Handshake::new(
+            public.clone(),
+            vec![Capability::E2EEncryption.into(), PeerCompatibilityLevel::signed_messages_capability()],
+        );
+        assert!(!PeerCompatibilityLevel::reads_idempotency_keys(&signed_only));
+        
+        let mut idempotent = Handshake::new(
+            public,
+            vec![Capability::E2EEncryption.into(), PeerCompatibilityLevel::idempotency_capability()],
+        );
+        assert!(PeerCompatibilityLevel::reads_idempotency_keys(&idempotent));
+        assert_eq!(PeerCompatibilityLevel::from_handshake(&idempotent), PeerCompatibilityLevel::LegacyNoSigning);
+        
+        idempotent.version = 0;
+        assert!(!PeerCompatibilityLevel::reads_idempotency_keys(&idempotent));
+    }
+}
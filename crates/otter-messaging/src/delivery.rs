@@ -0,0 +1,133 @@
+//! # Delivery Tracking
+//!
+//! Delivery status of messages sent by this node, by message ID, and when
+//! each message still awaiting a receipt was sent. Only the most recent
+//! [`DELIVERY_TRACKER_CAPACITY`] messages are tracked; older ones are
+//! forgotten, along with any receipt still owed for them.
+
+use chrono::{DateTime, Utc};
+use lru::LruCache;
+use otter_identity::PeerId;
+use otter_protocol::DeliveryStatus;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use tracing::debug;
+
+/// Number of sent messages whose delivery status is kept
+pub const DELIVERY_TRACKER_CAPACITY: usize = 10_000;
+
+/// Delivery status of sent messages
+#[derive(Debug)]
+pub struct MessageDeliveryTracker {
+    statuses: LruCache<String, DeliveryStatus>,
+    /// Send time of messages not yet delivered
+    pending: HashMap<String, DateTime<Utc>>,
+}
+
+impl Default for MessageDeliveryTracker {
+    fn default() -> Self {
+        Self::with_capacity(NonZeroUsize::new(DELIVERY_TRACKER_CAPACITY).expect("capacity is non-zero"))
+    }
+}
+
+impl MessageDeliveryTracker {
+    /// Create a tracker that keeps the status of the last `capacity` sent messages
+    pub fn with_capacity(capacity: NonZeroUsize) -> Self {
+        Self {
+            statuses: LruCache::new(capacity),
+            pending: HashMap::new(),
+        }
+    }
+    
+    /// Record that `message_id` was published
+    pub fn sent(&mut self, message_id: &str) {
+        if let Some((evicted, _)) = self.statuses.push(message_id.to_string(), DeliveryStatus::Sent) {
+            if evicted != message_id {
+                debug!("No longer tracking delivery of message {}", evicted);
+                self.pending.remove(&evicted);
+            }
+        }
+        self.pending.insert(message_id.to_string(), Utc::now());
+    }
+    
+    /// Move `message_id` to `status` if that is progress
+    ///
+    /// Returns the resulting status, or `None` for a message that was never sent.
+    pub fn advance(&mut self, message_id: &str, status: DeliveryStatus) -> Option<&DeliveryStatus> {
+        let current = self.statuses.get_mut(message_id)?;
+        if current.can_advance_to(&status) {
+            debug!("Message {} is now {:?}", message_id, status);
+            if matches!(status, DeliveryStatus::Delivered | DeliveryStatus::Read) {
+                self.pending.remove(message_id);
+            }
+            *current = status;
+        }
+        Some(current)
+    }
+    
+    /// Record that a peer forwarded `message_id`
+    pub fn relayed(&mut self, message_id: &str, via: PeerId) -> Option<&DeliveryStatus> {
+        self.advance(message_id, DeliveryStatus::Relayed { via })
+    }
+    
+    /// Record that `message_id` could not be sent
+    pub fn failed(&mut self, message_id: &str, reason: String) -> Option<&DeliveryStatus> {
+        self.advance(message_id, DeliveryStatus::Failed(reason))
+    }
+    
+    /// Get the status of `message_id`
+    pub fn get(&self, message_id: &str) -> Option<&DeliveryStatus> {
+        self.statuses.peek(message_id)
+    }
+    
+    /// Messages still waiting for a receipt, with the time they were sent
+    pub fn pending(&self) -> &HashMap<String, DateTime<Utc>> {
+        &self.pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use otter_identity::Identity;
+    
+    #[test]
+    fn test_status_never_regresses() {
+        let relay = Identity::generate().unwrap().peer_id().clone();
+        let mut tracker = MessageDeliveryTracker::default();
+        assert!(tracker.relayed("unknown", relay.clone()).is_none());
+        
+        tracker.sent("m1");
+        assert!(tracker.pending().contains_key("m1"));
+        assert_eq!(tracker.advance("m1", DeliveryStatus::Delivered), Some(&DeliveryStatus::Delivered));
+        assert!(tracker.pending().is_empty());
+        
+        // A late relay report or failure does not undo the delivery
+        assert_eq!(tracker.relayed("m1", relay), Some(&DeliveryStatus::Delivered));
+        assert_eq!(tracker.failed("m1", "timeout".to_string()), Some(&DeliveryStatus::Delivered));
+        
+        // A failed message that is acknowledged after all counts as delivered
+        tracker.sent("m2");
+        tracker.failed("m2", "no peers".to_string());
+        assert_eq!(tracker.get("m2"), Some(&DeliveryStatus::Failed("no peers".to_string())));
+        assert_eq!(tracker.advance("m2", DeliveryStatus::Read), Some(&DeliveryStatus::Read));
+    }
+    
+    #[test]
+    fn test_oldest_messages_are_forgotten() {
+        let mut tracker = MessageDeliveryTracker::with_capacity(NonZeroUsize::new(2).unwrap());
+        tracker.sent("m1");
+        tracker.sent("m2");
+        tracker.sent("m3");
+        
+        assert!(tracker.get("m1").is_none());
+        assert!(tracker.advance("m1", DeliveryStatus::Delivered).is_none());
+        assert_eq!(tracker.pending().len(), 2);
+        assert!(!tracker.pending().contains_key("m1"));
+        
+        // Sending the same ID again does not drop its own pending entry
+        tracker.sent("m3");
+        assert!(tracker.pending().contains_key("m3"));
+        assert_eq!(tracker.get("m2"), Some(&DeliveryStatus::Sent));
+    }
+}
@@ -0,0 +1,46 @@
+//! # Device Sync
+//!
+//! State shared between the devices of one user. Messages travel over the
+//! user's own encrypted sessions, so they carry no signature of their own.
+
+use crate::MessagingError;
+use chrono::{DateTime, Utc};
+use otter_identity::DeviceId;
+use serde::{Deserialize, Serialize};
+
+/// Everything in a conversation up to a message has been read on some device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadReceipt {
+    /// Remote peer of the conversation
+    pub peer_id: String,
+    /// Newest message that was read
+    pub last_read_message_id: String,
+    /// When it was read
+    pub read_at: DateTime<Utc>,
+    /// Device it was read on
+    pub device_id: DeviceId,
+}
+
+/// Payloads exchanged between a user's devices
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeviceSyncMessage {
+    /// Read positions of one or more conversations
+    SyncReadReceipts(Vec<ReadReceipt>),
+}
+
+impl DeviceSyncMessage {
+    /// Serialize to bytes using MessagePack
+    pub fn to_bytes(&self) -> Result<Vec<u8>, MessagingError> {
+        let mut buf = Vec::new();
+        let mut serializer = rmp_serde::Serializer::new(&mut buf).with_struct_map();
+        serde::Serialize::serialize(self, &mut serializer)
+            .map_err(|e| MessagingError::SerializationError(format!("MessagePack encode error: {}", e)))?;
+        Ok(buf)
+    }
+    
+    /// Deserialize from bytes using MessagePack
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, MessagingError> {
+        rmp_serde::from_slice(bytes)
+            .map_err(|e| MessagingError::SerializationError(format!("MessagePack decode error: {}", e)))
+    }
+}
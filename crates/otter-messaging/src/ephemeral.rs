@@ -0,0 +1,253 @@
+//! # Ephemeral Channels
+//!
+//! "Burn after reading" conversations. The opening side generates a
+//! throwaway identity and keys a session between it and the peer's identity,
+//! so the channel shares no key material with regular conversations. Its
+//! messages are never added to the conversation history, and the channel
+//! closes after a number of messages or a timeout, whichever comes first.
+//! Closing drops the session and the throwaway identity, which zeroizes both.
+
+use crate::MessagingError;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::Signature;
+use otter_crypto::CryptoSession;
+use otter_identity::{Identity, PublicIdentity};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::time::Instant;
+use uuid::Uuid;
+
+/// Handle of an open ephemeral channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EphemeralChannel {
+    /// Remote peer of the channel
+    pub peer_id: String,
+    pub channel_id: Uuid,
+}
+
+/// Invitation to an ephemeral channel, signed by the opener's long-term identity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EphemeralInvite {
+    pub channel_id: Uuid,
+    /// Peer ID of the opener's long-term identity
+    pub from_peer_id: String,
+    /// Throwaway identity the opener uses for this channel only
+    pub ephemeral_identity: PublicIdentity,
+    /// Messages, in both directions, after which the channel closes
+    pub max_messages: u32,
+    /// Seconds after which the channel closes
+    pub timeout_secs: u64,
+    pub timestamp: DateTime<Utc>,
+    /// Ed25519 signature over the digest
+    pub signature: Vec<u8>,
+}
+
+impl EphemeralInvite {
+    /// Create an invitation to `channel_id` signed by `identity`
+    pub fn sign(
+        identity: &Identity,
+        channel_id: Uuid,
+        ephemeral_identity: PublicIdentity,
+        max_messages: u32,
+        timeout_secs: u64,
+    ) -> Result<Self, MessagingError> {
+        let mut invite = Self {
+            channel_id,
+            from_peer_id: identity.peer_id().to_string(),
+            ephemeral_identity,
+            max_messages,
+            timeout_secs,
+            timestamp: Utc::now(),
+            signature: Vec::new(),
+        };
+        invite.signature = identity.sign(&invite.digest())?.to_bytes().to_vec();
+        Ok(invite)
+    }
+    
+    /// Digest of the fields covered by the signature
+    pub fn digest(&self) -> [u8; 32] {
+        let ephemeral_peer_id = self.ephemeral_identity.peer_id().as_str();
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(self.channel_id.as_bytes());
+        for field in [self.from_peer_id.as_str(), ephemeral_peer_id] {
+            hasher.update(&(field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        if let Ok(key) = self.ephemeral_identity.encryption_public_key() {
+            hasher.update(key.as_bytes());
+        }
+        hasher.update(&self.max_messages.to_le_bytes());
+        hasher.update(&self.timeout_secs.to_le_bytes());
+        hasher.update(&self.timestamp.timestamp().to_le_bytes());
+        hasher.update(&self.timestamp.timestamp_subsec_nanos().to_le_bytes());
+        *hasher.finalize().as_bytes()
+    }
+    
+    /// Check that `opener` signed this invitation
+    pub fn verify(&self, opener: &PublicIdentity) -> Result<(), MessagingError> {
+        let authenticity_failed = || MessagingError::AuthenticityFailed(opener.peer_id().to_string());
+        if opener.peer_id().as_str() != self.from_peer_id {
+            return Err(authenticity_failed());
+        }
+        
+        let bytes: [u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| authenticity_failed())?;
+        opener
+            .verify(&self.digest(), &Signature::from_bytes(&bytes))
+            .map_err(|_| authenticity_failed())
+    }
+}
+
+/// State of an open ephemeral channel
+pub(crate) struct EphemeralSession {
+    pub(crate) peer_id: String,
+    /// Throwaway identity, held only by the side that opened the channel
+    pub(crate) identity: Option<Identity>,
+    pub(crate) session: CryptoSession,
+    pub(crate) remaining_messages: u32,
+    pub(crate) expires_at: Instant,
+    /// Released with the session, so tests can see that closing dropped it
+    #[cfg(test)]
+    pub(crate) drop_probe: std::sync::Arc<()>,
+}
+
+impl EphemeralSession {
+    pub(crate) fn new(
+        peer_id: String,
+        identity: Option<Identity>,
+        session: CryptoSession,
+        max_messages: u32,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            peer_id,
+            identity,
+            session,
+            remaining_messages: max_messages,
+            expires_at: Instant::now() + timeout,
+            #[cfg(test)]
+            drop_probe: std::sync::Arc::new(()),
+        }
+    }
+    
+    pub(crate) fn is_expired(&self) -> bool {
+        self.remaining_messages == 0 || Instant::now() >= self.expires_at
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Message, MessageHandler};
+    use std::sync::Arc;
+    use zeroize::ZeroizeOnDrop;
+    
+    fn handler_pair() -> (MessageHandler, MessageHandler) {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        
+        let mut alice_handler = MessageHandler::new(alice);
+        let mut bob_handler = MessageHandler::new(bob);
+        alice_handler.register_peer(bob_public).unwrap();
+        bob_handler.register_peer(alice_public).unwrap();
+        (alice_handler, bob_handler)
+    }
+    
+    fn join(alice: &mut MessageHandler, bob: &mut MessageHandler, max_messages: u32) -> Uuid {
+        let bob_id = bob.public_identity().peer_id().to_string();
+        let channel = alice.open_ephemeral_channel(&bob_id, max_messages, 60).unwrap();
+        let Message::EphemeralInvite(invite) = alice.ephemeral_invite(channel.channel_id).unwrap() else {
+            panic!("Wrong message type");
+        };
+        let invite = match Message::from_bytes(&Message::EphemeralInvite(invite).to_bytes().unwrap()).unwrap() {
+            Message::EphemeralInvite(invite) => invite,
+            _ => panic!("Wrong message type"),
+        };
+        assert_eq!(bob.accept_ephemeral_invite(&invite).unwrap().channel_id, channel.channel_id);
+        channel.channel_id
+    }
+    
+    #[test]
+    fn test_ephemeral_messages_are_not_stored() {
+        let (mut alice, mut bob) = handler_pair();
+        let alice_id = alice.public_identity().peer_id().to_string();
+        let bob_id = bob.public_identity().peer_id().to_string();
+        let channel_id = join(&mut alice, &mut bob, 3);
+        
+        let message = alice.send_ephemeral(channel_id, "self-destructing").unwrap();
+        assert_eq!(bob.decrypt_message(&message).unwrap(), "self-destructing");
+        let reply = bob.send_ephemeral(channel_id, "got it").unwrap();
+        assert_eq!(alice.decrypt_message(&reply).unwrap(), "got it");
+        
+        assert!(alice.conversation(&bob_id).is_none());
+        assert!(bob.conversation(&alice_id).is_none());
+        
+        // The third message uses up the limit on both sides
+        let last = alice.send_ephemeral(channel_id, "bye").unwrap();
+        assert!(!alice.is_ephemeral_channel_open(channel_id));
+        assert_eq!(bob.decrypt_message(&last).unwrap(), "bye");
+        assert!(!bob.is_ephemeral_channel_open(channel_id));
+        assert!(matches!(
+            alice.send_ephemeral(channel_id, "too late"),
+            Err(MessagingError::ChannelClosed(_))
+        ));
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_ephemeral_channel_times_out() {
+        let (mut alice, mut bob) = handler_pair();
+        let channel_id = join(&mut alice, &mut bob, 100);
+        
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert_eq!(alice.expire_ephemeral_channels(), vec![channel_id]);
+        assert!(matches!(
+            bob.send_ephemeral(channel_id, "anyone there?"),
+            Err(MessagingError::ChannelClosed(_))
+        ));
+    }
+    
+    #[test]
+    fn test_forged_invite_rejected() {
+        let (mut alice, mut bob) = handler_pair();
+        let bob_id = bob.public_identity().peer_id().to_string();
+        let channel = alice.open_ephemeral_channel(&bob_id, 5, 60).unwrap();
+        let Message::EphemeralInvite(mut invite) = alice.ephemeral_invite(channel.channel_id).unwrap() else {
+            panic!("Wrong message type");
+        };
+        
+        invite.ephemeral_identity = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        assert!(matches!(
+            bob.accept_ephemeral_invite(&invite),
+            Err(MessagingError::AuthenticityFailed(_))
+        ));
+    }
+    
+    #[test]
+    fn test_temporary_keys_zeroized_on_close() {
+        // The keys are wiped by their own destructors, so closing only has to drop them
+        fn wiped_on_drop<T: ZeroizeOnDrop>() {}
+        wiped_on_drop::<Identity>();
+        wiped_on_drop::<CryptoSession>();
+        
+        let (mut alice, mut bob) = handler_pair();
+        let channel_id = join(&mut alice, &mut bob, 5);
+        let channel = &alice.ephemeral[&channel_id];
+        assert!(channel.identity.is_some());
+        let probe = Arc::downgrade(&channel.drop_probe);
+        
+        alice.close_ephemeral_channel(channel_id);
+        assert!(!alice.is_ephemeral_channel_open(channel_id));
+        assert!(probe.upgrade().is_none());
+        
+        // Using up the message limit closes the channel the same way
+        let channel_id = join(&mut alice, &mut bob, 1);
+        let probe = Arc::downgrade(&alice.ephemeral[&channel_id].drop_probe);
+        alice.send_ephemeral(channel_id, "once").unwrap();
+        assert!(probe.upgrade().is_none());
+    }
+}
@@ -0,0 +1,376 @@
+//! # File Transfer
+//!
+//! Files are sent as a `FileTransferStart` announcing the name, size and
+//! SHA-256, a run of `FileChunk`s and a closing `FileTransferComplete`.
+//! Chunks carry a CRC32 so a corrupted chunk is refused on arrival; chunks
+//! that arrive ahead of their turn are buffered until the gap is filled.
+
+use crate::{MessagingError, MessagingEvent};
+use otter_protocol::MessagePayload;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use tokio::io::AsyncReadExt;
+use tracing::{debug, warn};
+
+/// Bytes of file data per chunk
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// A file being received
+struct IncomingTransfer {
+    from: String,
+    filename: String,
+    mime_type: String,
+    total_size: u64,
+    total_chunks: u32,
+    sha256: [u8; 32],
+    /// Chunks received in order so far
+    data: Vec<u8>,
+    next_sequence: u32,
+    /// Chunks that arrived ahead of `next_sequence`
+    pending: BTreeMap<u32, Vec<u8>>,
+    pending_bytes: u64,
+}
+
+impl IncomingTransfer {
+    /// Append `data` and any buffered chunks that follow it
+    fn append(&mut self, data: Vec<u8>) {
+        self.data.extend_from_slice(&data);
+        self.next_sequence += 1;
+        while let Some(next) = self.pending.remove(&self.next_sequence) {
+            self.pending_bytes -= next.len() as u64;
+            self.data.extend_from_slice(&next);
+            self.next_sequence += 1;
+        }
+    }
+    
+    fn received_bytes(&self) -> u64 {
+        self.data.len() as u64 + self.pending_bytes
+    }
+}
+
+/// Chunked file transfers in both directions
+pub struct FileTransferManager {
+    chunk_size: usize,
+    outgoing: VecDeque<MessagePayload>,
+    incoming: HashMap<String, IncomingTransfer>,
+}
+
+impl Default for FileTransferManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_CHUNK_SIZE)
+    }
+}
+
+impl FileTransferManager {
+    /// Create a manager that splits outgoing files into `chunk_size` byte chunks
+    pub fn new(chunk_size: usize) -> Self {
+        Self {
+            chunk_size: chunk_size.max(1),
+            outgoing: VecDeque::new(),
+            incoming: HashMap::new(),
+        }
+    }
+    
+    /// Read `file` and queue its transfer
+    ///
+    /// Returns the transfer ID. The payloads are taken with [`Self::next_outgoing`].
+    pub async fn queue_file(
+        &mut self,
+        mut file: tokio::fs::File,
+        filename: &str,
+        mime_type: &str,
+    ) -> Result<String, MessagingError> {
+        let transfer_id = uuid::Uuid::new_v4().to_string();
+        let failed = |reason: String| MessagingError::FileTransferFailed {
+            transfer_id: transfer_id.clone(),
+            reason,
+        };
+        
+        let mut hasher = Sha256::new();
+        let mut chunks = Vec::new();
+        let mut total_size = 0u64;
+        loop {
+            let chunk = read_chunk(&mut file, self.chunk_size)
+                .await
+                .map_err(|e| failed(format!("read error: {}", e)))?;
+            if chunk.is_empty() {
+                break;
+            }
+            hasher.update(&chunk);
+            total_size += chunk.len() as u64;
+            chunks.push(chunk);
+        }
+        let total_chunks = u32::try_from(chunks.len()).map_err(|_| failed("file has too many chunks".to_string()))?;
+        
+        debug!("Queueing file transfer {} ({} bytes, {} chunks)", transfer_id, total_size, total_chunks);
+        self.outgoing.push_back(MessagePayload::FileTransferStart {
+            transfer_id: transfer_id.clone(),
+            filename: filename.to_string(),
+            mime_type: mime_type.to_string(),
+            total_size,
+            total_chunks,
+            sha256: hasher.finalize().into(),
+        });
+        for (sequence, data) in chunks.into_iter().enumerate() {
+            self.outgoing.push_back(MessagePayload::FileChunk {
+                transfer_id: transfer_id.clone(),
+                sequence: sequence as u32,
+                total_chunks,
+                checksum: crc32fast::hash(&data),
+                data,
+            });
+        }
+        self.outgoing.push_back(MessagePayload::FileTransferComplete {
+            transfer_id: transfer_id.clone(),
+        });
+        
+        Ok(transfer_id)
+    }
+    
+    /// Take the next payload to send
+    pub fn next_outgoing(&mut self) -> Option<MessagePayload> {
+        self.outgoing.pop_front()
+    }
+    
+    /// Number of payloads waiting to be sent
+    pub fn pending_outgoing(&self) -> usize {
+        self.outgoing.len()
+    }
+    
+    /// Whether a transfer with `transfer_id` is being received
+    pub fn is_receiving(&self, transfer_id: &str) -> bool {
+        self.incoming.contains_key(transfer_id)
+    }
+    
+    /// Process a file transfer payload received from `from`
+    ///
+    /// Returns `MessagingEvent::FileReceived` once a transfer completes and its
+    /// SHA-256 matches. Other payloads are ignored. A transfer that fails is dropped.
+    pub fn handle_payload(
+        &mut self,
+        from: &str,
+        payload: MessagePayload,
+    ) -> Result<Option<MessagingEvent>, MessagingError> {
+        match payload {
+            MessagePayload::FileTransferStart {
+                transfer_id,
+                filename,
+                mime_type,
+                total_size,
+                total_chunks,
+                sha256,
+            } => {
+                if self.incoming.contains_key(&transfer_id) {
+                    return Err(MessagingError::FileTransferFailed {
+                        transfer_id,
+                        reason: "transfer already started".to_string(),
+                    });
+                }
+                debug!("Receiving file transfer {} from {} ({} bytes)", transfer_id, from, total_size);
+                self.incoming.insert(
+                    transfer_id,
+                    IncomingTransfer {
+                        from: from.to_string(),
+                        filename,
+                        mime_type,
+                        total_size,
+                        total_chunks,
+                        sha256,
+                        data: Vec::new(),
+                        next_sequence: 0,
+                        pending: BTreeMap::new(),
+                        pending_bytes: 0,
+                    },
+                );
+                Ok(None)
+            }
+            MessagePayload::FileChunk {
+                transfer_id,
+                sequence,
+                total_chunks,
+                data,
+                checksum,
+            } => {
+                let result = self.handle_chunk(from, &transfer_id, sequence, total_chunks, data, checksum);
+                // Only the sender's own bad chunks abort the transfer
+                if result.is_err() && self.incoming.get(&transfer_id).is_some_and(|t| t.from == from) {
+                    self.incoming.remove(&transfer_id);
+                }
+                result.map(|_| None)
+            }
+            MessagePayload::FileTransferComplete { transfer_id } => {
+                let transfer = self.take_transfer(from, &transfer_id)?;
+                let failed = |reason: &str| MessagingError::FileTransferFailed {
+                    transfer_id: transfer_id.clone(),
+                    reason: reason.to_string(),
+                };
+                
+                if transfer.next_sequence != transfer.total_chunks {
+                    return Err(failed("missing chunks"));
+                }
+                if transfer.data.len() as u64 != transfer.total_size {
+                    return Err(failed("size mismatch"));
+                }
+                let digest: [u8; 32] = Sha256::digest(&transfer.data).into();
+                if digest != transfer.sha256 {
+                    warn!("File transfer {} from {} failed the SHA-256 check", transfer_id, from);
+                    return Err(failed("SHA-256 mismatch"));
+                }
+                
+                Ok(Some(MessagingEvent::FileReceived {
+                    from: transfer.from,
+                    transfer_id,
+                    filename: transfer.filename,
+                    mime_type: transfer.mime_type,
+                    data: transfer.data,
+                }))
+            }
+            _ => Ok(None),
+        }
+    }
+    
+    fn handle_chunk(
+        &mut self,
+        from: &str,
+        transfer_id: &str,
+        sequence: u32,
+        total_chunks: u32,
+        data: Vec<u8>,
+        checksum: u32,
+    ) -> Result<(), MessagingError> {
+        let failed = |reason: &str| MessagingError::FileTransferFailed {
+            transfer_id: transfer_id.to_string(),
+            reason: reason.to_string(),
+        };
+        let transfer = self.incoming.get_mut(transfer_id).filter(|t| t.from == from).ok_or_else(|| failed("unknown transfer"))?;
+        
+        if total_chunks != transfer.total_chunks || sequence >= transfer.total_chunks {
+            return Err(failed("chunk out of range"));
+        }
+        if crc32fast::hash(&data) != checksum {
+            return Err(failed("chunk checksum mismatch"));
+        }
+        if sequence < transfer.next_sequence || transfer.pending.contains_key(&sequence) {
+            // Duplicate delivery
+            return Ok(());
+        }
+        if transfer.received_bytes() + data.len() as u64 > transfer.total_size {
+            return Err(failed("more data than announced"));
+        }
+        
+        if sequence == transfer.next_sequence {
+            transfer.append(data);
+        } else {
+            transfer.pending_bytes += data.len() as u64;
+            transfer.pending.insert(sequence, data);
+        }
+        Ok(())
+    }
+    
+    fn take_transfer(&mut self, from: &str, transfer_id: &str) -> Result<IncomingTransfer, MessagingError> {
+        match self.incoming.get(transfer_id) {
+            Some(transfer) if transfer.from == from => Ok(self.incoming.remove(transfer_id).expect("transfer present")),
+            _ => Err(MessagingError::FileTransferFailed {
+                transfer_id: transfer_id.to_string(),
+                reason: "unknown transfer".to_string(),
+            }),
+        }
+    }
+}
+
+/// Read up to `chunk_size` bytes, short only at end of file
+async fn read_chunk(file: &mut tokio::fs::File, chunk_size: usize) -> std::io::Result<Vec<u8>> {
+    let mut chunk = vec![0u8; chunk_size];
+    let mut filled = 0;
+    while filled < chunk_size {
+        let read = file.read(&mut chunk[filled..]).await?;
+        if read == 0 {
+            break;
+        }
+        filled += read;
+    }
+    chunk.truncate(filled);
+    Ok(chunk)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    async fn queued_payloads(contents: &[u8], chunk_size: usize) -> (String, Vec<MessagePayload>) {
+        let path = std::env::temp_dir().join(format!("otter-transfer-{}", uuid::Uuid::new_v4()));
+        tokio::fs::write(&path, contents).await.unwrap();
+        let file = tokio::fs::File::open(&path).await.unwrap();
+        
+        let mut sender = FileTransferManager::new(chunk_size);
+        let transfer_id = sender.queue_file(file, "notes.txt", "text/plain").await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        
+        let payloads = std::iter::from_fn(|| sender.next_outgoing()).collect();
+        (transfer_id, payloads)
+    }
+    
+    #[tokio::test]
+    async fn test_out_of_order_transfer() {
+        let contents: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        let (transfer_id, mut payloads) = queued_payloads(&contents, 300).await;
+        assert_eq!(payloads.len(), 6);
+        
+        // Deliver the chunks backwards, with one duplicate
+        payloads[1..5].reverse();
+        payloads.insert(3, payloads[2].clone());
+        
+        let mut receiver = FileTransferManager::default();
+        let mut events = Vec::new();
+        for payload in payloads {
+            events.extend(receiver.handle_payload("alice", payload).unwrap());
+        }
+        
+        match events.as_slice() {
+            [MessagingEvent::FileReceived { from, transfer_id: id, filename, mime_type, data }] => {
+                assert_eq!(from, "alice");
+                assert_eq!(id, &transfer_id);
+                assert_eq!(filename, "notes.txt");
+                assert_eq!(mime_type, "text/plain");
+                assert_eq!(data, &contents);
+            }
+            other => panic!("unexpected events {:?}", other),
+        }
+        assert!(!receiver.is_receiving(&transfer_id));
+    }
+    
+    #[tokio::test]
+    async fn test_corrupted_transfer_is_rejected() {
+        let (transfer_id, payloads) = queued_payloads(b"hello otter", 4).await;
+        
+        // A chunk that does not match its checksum drops the transfer
+        let mut receiver = FileTransferManager::default();
+        receiver.handle_payload("alice", payloads[0].clone()).unwrap();
+        let mut corrupted = payloads[1].clone();
+        if let MessagePayload::FileChunk { data, .. } = &mut corrupted {
+            data[0] ^= 1;
+        }
+        assert!(receiver.handle_payload("alice", corrupted).is_err());
+        assert!(!receiver.is_receiving(&transfer_id));
+        
+        // Chunks whose checksums match but whose contents differ fail the SHA-256
+        let mut receiver = FileTransferManager::default();
+        for mut payload in payloads.clone() {
+            if let MessagePayload::FileChunk { sequence: 0, data, checksum, .. } = &mut payload {
+                data[0] ^= 1;
+                *checksum = crc32fast::hash(data);
+            }
+            match receiver.handle_payload("alice", payload) {
+                Ok(event) => assert!(event.is_none()),
+                Err(e) => assert!(matches!(e, MessagingError::FileTransferFailed { .. })),
+            }
+        }
+        assert!(!receiver.is_receiving(&transfer_id));
+        
+        // Chunks from another peer are not accepted into the transfer
+        let mut receiver = FileTransferManager::default();
+        receiver.handle_payload("alice", payloads[0].clone()).unwrap();
+        assert!(receiver.handle_payload("mallory", payloads[1].clone()).is_err());
+        assert!(receiver.is_receiving(&transfer_id));
+    }
+}
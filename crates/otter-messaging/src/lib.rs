@@ -7,16 +7,74 @@
 //! - Message encryption/decryption integration
 //! - Message routing and handling
 //! - Conversation management
+//! - Reply threading
+//! - Typing indicators with automatic timeout, debounced when sent
+//! - Parallel broadcast encryption to every registered peer
+//! - Ed25519-signed encrypted messages (`sign_messages` feature)
+//! - Read receipts synchronized between a user's devices
+//! - Idempotency keys so a repeated send is not encrypted twice
+//! - Signed edits and deletions of sent messages
+//! - Delivery status of sent messages, advanced by signed receipts from the
+//!   recipient, and the messages still waiting for one
+//! - Ephemeral "burn after reading" channels that are never recorded
+//! - Read-only observer sessions that can decrypt but not send
+//! - Downgraded messages for peers running older protocol versions
+//! - A retrying queue for offline peers that reports undeliverable messages
+//! - Renewal of encryption sessions that outlived their lifetime
+//! - Chunked file transfers, verified by SHA-256 on arrival
+
+pub mod compat;
+pub mod delivery;
+pub mod device_sync;
+pub mod ephemeral;
+pub mod file_transfer;
+pub mod observer;
+pub mod outbox;
+pub mod receipt;
+pub mod revision;
+
+pub use compat::PeerCompatibilityLevel;
+pub use delivery::MessageDeliveryTracker;
+pub use device_sync::{DeviceSyncMessage, ReadReceipt};
+pub use ephemeral::{EphemeralChannel, EphemeralInvite};
+pub use file_transfer::{FileTransferManager, DEFAULT_CHUNK_SIZE};
+pub use observer::ObserverSession;
+pub use outbox::{FailureReason, OfflineQueue, QueuedMessage, DEFAULT_MAX_DELIVERY_ATTEMPTS};
+pub use revision::{MessageRevision, RevisionAction, DELETED_TOMBSTONE};
 
 use chrono::{DateTime, Utc};
-use libp2p::PeerId;
-use otter_crypto::{CryptoSession, EncryptedMessage, MessageCrypto};
-use otter_identity::{Identity, PublicIdentity};
+use ed25519_dalek::Signature;
+use ephemeral::EphemeralSession;
+use otter_crypto::{CryptoError, CryptoSession, EncryptedMessage, KdfAlgorithm};
+use otter_identity::{DeviceId, Identity, IdentityError, PeerId, PublicIdentity};
+use otter_protocol::{DeliveryReceipt, DeliveryStatus, Handshake};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use lru::LruCache;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::{JoinHandle, JoinSet};
+use tokio::time::Instant;
 use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+/// How long a typing indicator stays active without a refresh
+pub const DEFAULT_TYPING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the background task checks for stale typing indicators
+pub const TYPING_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// How long an unchanged outgoing typing indicator is not sent again
+pub const TYPING_DEBOUNCE: Duration = Duration::from_secs(3);
+
+/// Silence after the last keystroke before "stopped typing" is sent automatically
+pub const TYPING_STOP_DELAY: Duration = Duration::from_secs(5);
+
+/// Number of sent messages remembered by idempotency key
+pub const SENT_KEYS_CAPACITY: usize = 1000;
 
 #[derive(Error, Debug)]
 pub enum MessagingError {
@@ -30,6 +88,92 @@ pub enum MessagingError {
     InvalidFormat(String),
     #[error("Serialization error: {0}")]
     SerializationError(String),
+    #[error("Broadcast failed for {} peers: {}", failed_peers.len(), failed_peers.join(", "))]
+    PartialBroadcastFailure { failed_peers: Vec<String> },
+    #[error("Message authenticity check failed for {0}")]
+    AuthenticityFailed(String),
+    #[error("Message not found: {0}")]
+    MessageNotFound(String),
+    #[error("Ephemeral channel closed: {0}")]
+    ChannelClosed(Uuid),
+    #[error("Session is read-only")]
+    ReadOnlySession,
+    #[error("Messages are downgraded to {to_level:?} for this peer")]
+    CompatibilityDowngrade { to_level: PeerCompatibilityLevel },
+    #[error("Refusing to send plaintext to {0}")]
+    PlaintextDowngradeRefused(String),
+    #[error("Identity error: {0}")]
+    IdentityError(#[from] IdentityError),
+    #[error("Peer is offline: {0}")]
+    PeerOffline(String),
+    #[error("File transfer {transfer_id} failed: {reason}")]
+    FileTransferFailed { transfer_id: String, reason: String },
+}
+
+/// Encrypted message signed by the sender's long-term identity key
+///
+/// The signature covers `BLAKE3(nonce || ciphertext || message_counter)`, so a
+/// leaked session key alone is not enough to forge messages.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedEncryptedMessage {
+    pub encrypted: EncryptedMessage,
+    /// Ed25519 signature over the digest
+    pub signature: Vec<u8>,
+}
+
+impl SignedEncryptedMessage {
+    /// Digest of the fields covered by the signature
+    pub fn digest(encrypted: &EncryptedMessage) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&encrypted.nonce);
+        hasher.update(&encrypted.ciphertext);
+        hasher.update(&encrypted.message_counter.to_le_bytes());
+        *hasher.finalize().as_bytes()
+    }
+    
+    /// Sign an encrypted message with `identity`
+    pub fn sign(identity: &Identity, encrypted: EncryptedMessage) -> Result<Self, MessagingError> {
+        let signature = identity.sign(&Self::digest(&encrypted))?.to_bytes().to_vec();
+        Ok(Self { encrypted, signature })
+    }
+    
+    /// Check the signature against the claimed sender
+    pub fn verify(&self, sender: &PublicIdentity) -> Result<(), MessagingError> {
+        let authenticity_failed = || MessagingError::AuthenticityFailed(sender.peer_id().to_string());
+        let bytes: [u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| authenticity_failed())?;
+        
+        sender
+            .verify(&Self::digest(&self.encrypted), &Signature::from_bytes(&bytes))
+            .map_err(|_| authenticity_failed())
+    }
+}
+
+/// Identifies one user-initiated send, so a retry of it is not sent twice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct IdempotencyKey(pub Uuid);
+
+impl IdempotencyKey {
+    /// Generate a fresh random key
+    pub fn new() -> Self {
+        Self(Uuid::new_v4())
+    }
+}
+
+impl Default for IdempotencyKey {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reply metadata attached to a text message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageThread {
+    /// ID of the message this one replies to
+    pub reply_to_id: Option<String>,
 }
 
 /// Message types in the Otter protocol
@@ -39,6 +183,12 @@ pub enum Message {
     Text {
         content: String,
         timestamp: DateTime<Utc>,
+        /// Set when this message is a reply to another message
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        thread: Option<MessageThread>,
+        /// ID the sender assigned, which receipts for this message refer to
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        message_id: Option<Uuid>,
     },
     
     /// Identity announcement (public key exchange)
@@ -54,6 +204,21 @@ pub enum Message {
         timestamp: DateTime<Utc>,
     },
     
+    /// Encrypted message envelope tagged with the send it belongs to
+    EncryptedWithIdempotency {
+        from_peer_id: String,
+        inner: EncryptedMessage,
+        key: IdempotencyKey,
+        timestamp: DateTime<Utc>,
+    },
+    
+    /// Encrypted message envelope carrying the sender's signature
+    SignedEncrypted {
+        from_peer_id: String,
+        signed: SignedEncryptedMessage,
+        timestamp: DateTime<Utc>,
+    },
+    
     /// Peer status update
     Status {
         status: String,
@@ -64,6 +229,36 @@ pub enum Message {
     Typing {
         is_typing: bool,
     },
+    
+    /// Edit or deletion of an earlier message, sent inside an encrypted envelope
+    Revision(MessageRevision),
+    
+    /// Signed confirmation that a message sent by the recipient of this one arrived
+    DeliveryReceipt {
+        from_peer_id: String,
+        message_id: String,
+        received_at: DateTime<Utc>,
+        /// Ed25519 signature by `from_peer_id`, see [`receipt::receipt_digest`]
+        signature: Vec<u8>,
+    },
+    
+    /// Signed confirmation that a message sent by the recipient of this one was read
+    ReadReceipt {
+        from_peer_id: String,
+        message_id: String,
+        read_at: DateTime<Utc>,
+        /// Ed25519 signature by `from_peer_id`, see [`receipt::receipt_digest`]
+        signature: Vec<u8>,
+    },
+    
+    /// Invitation to an ephemeral channel
+    EphemeralInvite(EphemeralInvite),
+    
+    /// Message on an ephemeral channel, never recorded in the conversation history
+    Ephemeral {
+        channel_id: Uuid,
+        encrypted: EncryptedMessage,
+    },
 }
 
 impl Message {
@@ -72,6 +267,20 @@ impl Message {
         Self::Text {
             content,
             timestamp: Utc::now(),
+            thread: None,
+            message_id: None,
+        }
+    }
+    
+    /// Create a text message replying to another message
+    pub fn text_reply(content: String, reply_to_id: String) -> Self {
+        Self::Text {
+            content,
+            timestamp: Utc::now(),
+            thread: Some(MessageThread {
+                reply_to_id: Some(reply_to_id),
+            }),
+            message_id: None,
         }
     }
     
@@ -92,6 +301,55 @@ impl Message {
         }
     }
     
+    /// Create a signed encrypted message
+    pub fn signed_encrypted(from_peer_id: String, signed: SignedEncryptedMessage) -> Self {
+        Self::SignedEncrypted {
+            from_peer_id,
+            signed,
+            timestamp: Utc::now(),
+        }
+    }
+    
+    /// Create a receipt signed by `identity` for `to_peer_id`, the author of the message
+    pub fn receipt(identity: &Identity, to_peer_id: &str, kind: DeliveryReceipt) -> Result<Self, MessagingError> {
+        let at = Utc::now();
+        let signature = receipt::sign_receipt(identity, &kind, to_peer_id, &at)?;
+        let from_peer_id = identity.peer_id().to_string();
+        Ok(match kind {
+            DeliveryReceipt::MessageAck { message_id } => Self::DeliveryReceipt {
+                from_peer_id,
+                message_id,
+                received_at: at,
+                signature,
+            },
+            DeliveryReceipt::MarkRead { message_id } => Self::ReadReceipt {
+                from_peer_id,
+                message_id,
+                read_at: at,
+                signature,
+            },
+        })
+    }
+    
+    /// Sender, receipt, time and signature of a `DeliveryReceipt` or `ReadReceipt`
+    fn as_receipt(&self) -> Option<(&str, DeliveryReceipt, &DateTime<Utc>, &[u8])> {
+        match self {
+            Self::DeliveryReceipt { from_peer_id, message_id, received_at, signature } => Some((
+                from_peer_id,
+                DeliveryReceipt::MessageAck { message_id: message_id.clone() },
+                received_at,
+                signature,
+            )),
+            Self::ReadReceipt { from_peer_id, message_id, read_at, signature } => Some((
+                from_peer_id,
+                DeliveryReceipt::MarkRead { message_id: message_id.clone() },
+                read_at,
+                signature,
+            )),
+            _ => None,
+        }
+    }
+    
     /// Serialize message to JSON
     pub fn to_json(&self) -> Result<String, MessagingError> {
         serde_json::to_string(self)
@@ -123,11 +381,270 @@ impl Message {
     }
 }
 
+/// A message kept in a conversation's history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredMessage {
+    /// Message ID, identical on both ends of the conversation
+    pub id: String,
+    /// Peer ID of the author
+    pub from: String,
+    /// Decrypted text content
+    pub content: String,
+    /// Envelope timestamp
+    pub timestamp: DateTime<Utc>,
+    /// Reply metadata, if any
+    pub thread: Option<MessageThread>,
+    /// Earlier versions of the content, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edit_history: Vec<String>,
+}
+
+impl StoredMessage {
+    /// Create a stored message, deriving its ID from author, timestamp and content
+    pub fn new(
+        from: String,
+        content: String,
+        timestamp: DateTime<Utc>,
+        thread: Option<MessageThread>,
+    ) -> Self {
+        let id = Self::compute_id(&from, &timestamp, &content);
+        Self {
+            id,
+            from,
+            content,
+            timestamp,
+            thread,
+            edit_history: Vec::new(),
+        }
+    }
+    
+    /// Apply an edit or deletion
+    ///
+    /// Deleting also drops the edit history, so no earlier text is kept.
+    pub fn apply_revision(&mut self, action: &RevisionAction) {
+        match action {
+            RevisionAction::Edit(text) => {
+                let previous = std::mem::replace(&mut self.content, text.clone());
+                self.edit_history.push(previous);
+            }
+            RevisionAction::Delete => {
+                self.content = DELETED_TOMBSTONE.to_string();
+                self.edit_history.clear();
+            }
+        }
+    }
+    
+    /// Compute a message ID
+    ///
+    /// Both peers see the same author, envelope timestamp and plaintext, so they
+    /// derive the same ID without it ever being sent in the clear.
+    pub fn compute_id(from: &str, timestamp: &DateTime<Utc>, content: &str) -> String {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(from.as_bytes());
+        hasher.update(timestamp.to_rfc3339().as_bytes());
+        hasher.update(content.as_bytes());
+        hex::encode(&hasher.finalize().as_bytes()[..16])
+    }
+    
+    /// Use the ID the sender assigned instead of the derived one
+    pub fn with_id(mut self, message_id: Option<Uuid>) -> Self {
+        if let Some(message_id) = message_id {
+            self.id = message_id.to_string();
+        }
+        self
+    }
+    
+    /// ID of the message this one replies to
+    pub fn reply_to_id(&self) -> Option<&str> {
+        self.thread.as_ref().and_then(|t| t.reply_to_id.as_deref())
+    }
+}
+
+/// Message history with a single peer
+#[derive(Debug, Clone)]
+pub struct Conversation {
+    /// Remote peer ID
+    pub peer_id: String,
+    messages: Vec<StoredMessage>,
+    /// Number of messages, from the start, that have been read
+    read_up_to: usize,
+    /// Sent messages that did not reach the peer, by message ID
+    pub failed_messages: HashMap<String, FailureReason>,
+}
+
+impl Conversation {
+    /// Create an empty conversation
+    pub fn new(peer_id: String) -> Self {
+        Self {
+            peer_id,
+            messages: Vec::new(),
+            read_up_to: 0,
+            failed_messages: HashMap::new(),
+        }
+    }
+    
+    /// Messages from the peer that have not been read yet
+    pub fn unread_count(&self) -> usize {
+        self.messages[self.read_up_to..]
+            .iter()
+            .filter(|m| m.from == self.peer_id)
+            .count()
+    }
+    
+    /// Mark every message up to and including `message_id` as read
+    ///
+    /// Returns `false` if the message is unknown. The read position never moves back.
+    pub fn mark_read_up_to(&mut self, message_id: &str) -> bool {
+        match self.messages.iter().position(|m| m.id == message_id) {
+            Some(index) => {
+                self.read_up_to = self.read_up_to.max(index + 1);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    /// Append a message to the history
+    pub fn push(&mut self, message: StoredMessage) {
+        self.messages.push(message);
+    }
+    
+    /// All messages in arrival order
+    pub fn messages(&self) -> &[StoredMessage] {
+        &self.messages
+    }
+    
+    /// Look up a message by ID
+    pub fn get(&self, message_id: &str) -> Option<&StoredMessage> {
+        self.messages.iter().find(|m| m.id == message_id)
+    }
+    
+    fn get_mut(&mut self, message_id: &str) -> Option<&mut StoredMessage> {
+        self.messages.iter_mut().find(|m| m.id == message_id)
+    }
+    
+    /// Get a thread: the root message followed by all replies in chronological order
+    ///
+    /// Replies to replies are included, so the whole sub-tree under `root_id` is returned.
+    pub fn thread(&self, root_id: &str) -> Vec<&StoredMessage> {
+        let root = match self.get(root_id) {
+            Some(root) => root,
+            None => return Vec::new(),
+        };
+        
+        let mut thread_ids = vec![root.id.as_str()];
+        let mut replies: Vec<&StoredMessage> = Vec::new();
+        let mut i = 0;
+        while i < thread_ids.len() {
+            let parent = thread_ids[i];
+            for message in self.messages.iter().filter(|m| m.reply_to_id() == Some(parent)) {
+                thread_ids.push(message.id.as_str());
+                replies.push(message);
+            }
+            i += 1;
+        }
+        
+        // Stable sort keeps arrival order for identical timestamps
+        replies.sort_by_key(|m| m.timestamp);
+        
+        let mut thread = vec![root];
+        thread.extend(replies);
+        thread
+    }
+}
+
+/// Tracks which peers are typing and expires stale indicators
+#[derive(Debug, Clone)]
+pub struct TypingTracker {
+    started: HashMap<String, Instant>,
+    timeout: Duration,
+}
+
+impl TypingTracker {
+    /// Create a tracker with the given timeout
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            started: HashMap::new(),
+            timeout,
+        }
+    }
+    
+    /// Record that a peer is typing, refreshing its timer
+    pub fn started(&mut self, peer_id: &str) {
+        self.started.insert(peer_id.to_string(), Instant::now());
+    }
+    
+    /// Record that a peer stopped typing
+    pub fn stopped(&mut self, peer_id: &str) {
+        self.started.remove(peer_id);
+    }
+    
+    /// Check if a peer is currently typing
+    pub fn is_typing(&self, peer_id: &str) -> bool {
+        self.started.contains_key(peer_id)
+    }
+    
+    /// Remove and return peers whose indicator is older than the timeout
+    pub fn poll_timeouts(&mut self) -> Vec<String> {
+        let now = Instant::now();
+        let expired: Vec<String> = self
+            .started
+            .iter()
+            .filter(|(_, started)| now.duration_since(**started) > self.timeout)
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        
+        for peer_id in &expired {
+            self.started.remove(peer_id);
+        }
+        
+        expired
+    }
+}
+
+impl Default for TypingTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_TYPING_TIMEOUT)
+    }
+}
+
+/// The typing indicator last sent to a peer
+#[derive(Debug, Clone)]
+struct SentTyping {
+    is_typing: bool,
+    sent_at: Instant,
+    /// When "stopped typing" goes out unless the user keeps typing
+    stop_at: Option<Instant>,
+}
+
 /// Manages conversations and encryption sessions with peers
 pub struct MessageHandler {
     local_identity: Identity,
+    device_id: DeviceId,
     peers: HashMap<String, PublicIdentity>,
     sessions: HashMap<String, CryptoSession>,
+    conversations: HashMap<String, Conversation>,
+    typing: TypingTracker,
+    /// Typing indicators sent to each peer
+    sent_typing: HashMap<String, SentTyping>,
+    /// Messages already produced for recent caller-supplied idempotency keys
+    sent_keys: LruCache<IdempotencyKey, Message>,
+    delivery: MessageDeliveryTracker,
+    ephemeral: HashMap<Uuid, EphemeralSession>,
+    /// Levels of peers registered from a handshake
+    compatibility: HashMap<String, PeerCompatibilityLevel>,
+    /// Peers whose handshake advertised idempotency keys
+    idempotent_peers: HashSet<String>,
+    /// Whether version 0 peers with a signed handshake may be sent plaintext
+    allow_plaintext: bool,
+    online: HashSet<String>,
+    offline_queue: OfflineQueue,
+    /// Lifetime given to new sessions, if limited
+    session_lifetime: Option<Duration>,
+    /// Times each peer's session was renewed
+    session_generations: HashMap<String, u64>,
+    /// Identity announcements owed to peers whose session was renewed
+    pending_announcements: Vec<(String, Message)>,
 }
 
 impl MessageHandler {
@@ -135,8 +652,23 @@ impl MessageHandler {
     pub fn new(local_identity: Identity) -> Self {
         Self {
             local_identity,
+            device_id: DeviceId::generate(),
             peers: HashMap::new(),
             sessions: HashMap::new(),
+            conversations: HashMap::new(),
+            typing: TypingTracker::default(),
+            sent_typing: HashMap::new(),
+            sent_keys: LruCache::new(NonZeroUsize::new(SENT_KEYS_CAPACITY).expect("capacity is non-zero")),
+            delivery: MessageDeliveryTracker::default(),
+            ephemeral: HashMap::new(),
+            compatibility: HashMap::new(),
+            idempotent_peers: HashSet::new(),
+            allow_plaintext: false,
+            online: HashSet::new(),
+            offline_queue: OfflineQueue::default(),
+            session_lifetime: None,
+            session_generations: HashMap::new(),
+            pending_announcements: Vec::new(),
         }
     }
     
@@ -145,8 +677,7 @@ impl MessageHandler {
         let peer_id = public_identity.peer_id().to_string();
         
         // Create crypto session with this peer
-        let session = CryptoSession::new(&self.local_identity, &public_identity)
-            .map_err(|e| MessagingError::EncryptionError(e.to_string()))?;
+        let session = self.new_session(&public_identity)?;
         
         info!("Registered peer {} with session fingerprint: {}", peer_id, session.fingerprint());
         
@@ -156,6 +687,192 @@ impl MessageHandler {
         Ok(())
     }
     
+    fn new_session(&self, public_identity: &PublicIdentity) -> Result<CryptoSession, MessagingError> {
+        let mut session = CryptoSession::new(&self.local_identity, public_identity, KdfAlgorithm::default())
+            .map_err(|e| MessagingError::EncryptionError(e.to_string()))?;
+        self.apply_session_lifetime(&mut session);
+        Ok(session)
+    }
+    
+    /// Give a newly derived session the configured lifetime, if any
+    fn apply_session_lifetime(&self, session: &mut CryptoSession) {
+        if let Some(lifetime) = self.session_lifetime {
+            session.set_expiry(lifetime);
+        }
+    }
+    
+    /// Limit the lifetime of sessions created from now on
+    pub fn set_session_lifetime(&mut self, lifetime: Option<Duration>) {
+        self.session_lifetime = lifetime;
+    }
+    
+    /// Replace the session with `peer_id` by a fresh one
+    ///
+    /// The new session restarts its counters, so the peer has to rebuild its
+    /// side too: an identity announcement for it is queued, to be sent before
+    /// anything encrypted with the new session (see
+    /// [`MessageHandler::take_session_announcements`]).
+    pub fn renew_session(&mut self, peer_id: &str) -> Result<(), MessagingError> {
+        let public_identity = self
+            .peers
+            .get(peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))?;
+        let session = self.new_session(public_identity)?;
+        self.sessions.insert(peer_id.to_string(), session);
+        
+        let generation = self.session_generations.entry(peer_id.to_string()).or_default();
+        *generation += 1;
+        info!("Renewed session with {} (generation {})", peer_id, generation);
+        
+        self.pending_announcements
+            .push((peer_id.to_string(), Message::identity(self.public_identity())));
+        Ok(())
+    }
+    
+    /// Number of times the session with `peer_id` was renewed
+    pub fn session_generation(&self, peer_id: &str) -> u64 {
+        self.session_generations.get(peer_id).copied().unwrap_or(0)
+    }
+    
+    /// Identity announcements to send, as `(peer_id, message)`, after session renewals
+    pub fn take_session_announcements(&mut self) -> Vec<(String, Message)> {
+        std::mem::take(&mut self.pending_announcements)
+    }
+    
+    /// Register the peer that sent `handshake`, at the level it supports
+    ///
+    /// A signed handshake is verified against its identity before any of its
+    /// capabilities are accepted; unsigned ones come from older clients.
+    /// A version 0 handshake, which would make messages go out in plaintext,
+    /// is refused unless it is signed and plaintext was allowed with
+    /// [`MessageHandler::set_allow_plaintext`].
+    pub fn register_peer_with_handshake(&mut self, handshake: &Handshake) -> Result<(), MessagingError> {
+        if handshake.signature.is_some() {
+            handshake
+                .verify(&handshake.identity)
+                .map_err(|e| MessagingError::AuthenticityFailed(format!("handshake: {}", e)))?;
+        }
+        
+        let peer_id = handshake.identity.peer_id().to_string();
+        let level = PeerCompatibilityLevel::from_handshake(handshake);
+        if level == PeerCompatibilityLevel::LegacyV0 && (handshake.signature.is_none() || !self.allow_plaintext) {
+            warn!("Refusing plaintext downgrade requested by {}", peer_id);
+            return Err(MessagingError::PlaintextDowngradeRefused(peer_id));
+        }
+        self.register_peer(handshake.identity.clone())?;
+        
+        if level != PeerCompatibilityLevel::Full {
+            info!("Peer {} speaks protocol version {} ({:?})", peer_id, handshake.version, level);
+        }
+        if PeerCompatibilityLevel::reads_idempotency_keys(handshake) {
+            self.idempotent_peers.insert(peer_id.clone());
+        } else {
+            self.idempotent_peers.remove(&peer_id);
+        }
+        self.compatibility.insert(peer_id, level);
+        Ok(())
+    }
+    
+    /// Newest message format `peer_id` can read; `Full` unless its handshake said otherwise
+    pub fn compatibility_level(&self, peer_id: &str) -> PeerCompatibilityLevel {
+        self.compatibility.get(peer_id).copied().unwrap_or_default()
+    }
+    
+    /// Allow sending plaintext to version 0 peers that signed their handshake
+    ///
+    /// Off by default.
+    pub fn set_allow_plaintext(&mut self, allowed: bool) {
+        self.allow_plaintext = allowed;
+    }
+    
+    /// Report whether messages for `peer_id` are downgraded
+    ///
+    /// The error is informational: downgraded messages are still produced.
+    pub fn check_compatibility(&self, peer_id: &str) -> Result<(), MessagingError> {
+        match self.compatibility_level(peer_id) {
+            PeerCompatibilityLevel::Full => Ok(()),
+            to_level => Err(MessagingError::CompatibilityDowngrade { to_level }),
+        }
+    }
+    
+    /// Register many peers at once, deriving their sessions in parallel
+    ///
+    /// Key derivation runs on the blocking thread pool. Results are returned in
+    /// the same order as `identities`.
+    pub async fn register_peers_batch(
+        &mut self,
+        identities: Vec<PublicIdentity>,
+    ) -> Vec<Result<(), MessagingError>> {
+        let local_identity = Arc::new(self.local_identity.clone());
+        let mut tasks = JoinSet::new();
+        
+        for (index, public_identity) in identities.iter().cloned().enumerate() {
+            let local_identity = Arc::clone(&local_identity);
+            tasks.spawn_blocking(move || {
+                let session = CryptoSession::new(&local_identity, &public_identity, KdfAlgorithm::default());
+                (index, public_identity, session)
+            });
+        }
+        
+        let mut results: Vec<Result<(), MessagingError>> = identities
+            .iter()
+            .map(|identity| {
+                Err(MessagingError::EncryptionError(format!(
+                    "session derivation for {} did not complete",
+                    identity.peer_id()
+                )))
+            })
+            .collect();
+        
+        while let Some(joined) = tasks.join_next().await {
+            let (index, public_identity, session) = match joined {
+                Ok(output) => output,
+                Err(e) => {
+                    debug!("Session derivation task failed: {}", e);
+                    continue;
+                }
+            };
+            
+            results[index] = match session {
+                Ok(mut session) => {
+                    self.apply_session_lifetime(&mut session);
+                    let peer_id = public_identity.peer_id().to_string();
+                    debug!("Registered peer {} with session fingerprint: {}", peer_id, session.fingerprint());
+                    self.peers.insert(peer_id.clone(), public_identity);
+                    self.sessions.insert(peer_id, session);
+                    Ok(())
+                }
+                Err(e) => Err(MessagingError::EncryptionError(e.to_string())),
+            };
+        }
+        
+        let registered = results.iter().filter(|r| r.is_ok()).count();
+        info!("Registered {} of {} peers in batch", registered, results.len());
+        results
+    }
+    
+    /// Look up the public identities of several peers
+    pub fn resolve_identities(&self, peer_ids: &[&str]) -> Vec<Result<&PublicIdentity, MessagingError>> {
+        peer_ids
+            .iter()
+            .map(|peer_id| {
+                self.peers
+                    .get(*peer_id)
+                    .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))
+            })
+            .collect()
+    }
+    
+    /// ID of this device, stamped on read receipts
+    pub fn device_id(&self) -> &DeviceId {
+        &self.device_id
+    }
+    
+    /// Use a persisted device ID instead of the random one
+    pub fn set_device_id(&mut self, device_id: DeviceId) {
+        self.device_id = device_id;
+    }
+    
     /// Get local public identity for sharing
     pub fn public_identity(&self) -> PublicIdentity {
         PublicIdentity::from_identity(&self.local_identity)
@@ -167,61 +884,976 @@ impl MessageHandler {
         peer_id: &str,
         text: &str,
     ) -> Result<Message, MessagingError> {
-        let session = self
-            .sessions
-            .get_mut(peer_id)
-            .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))?;
+        self.prepare_text_message(peer_id, text, None)
+    }
+    
+    /// Encrypt a text message for `peer_id` unless `key` was already sent
+    ///
+    /// A repeated key returns the message produced the first time, with the
+    /// same counter, instead of encrypting (and storing) the text again.
+    /// The key is only put on the wire for peers whose handshake advertised
+    /// [`compat::IDEMPOTENCY_CAPABILITY`].
+    pub fn prepare_idempotent_message(
+        &mut self,
+        peer_id: &str,
+        text: &str,
+        key: IdempotencyKey,
+    ) -> Result<Message, MessagingError> {
+        if let Some(sent) = self.sent_keys.get(&key) {
+            debug!("Message for key {:?} was already prepared", key);
+            return Ok(sent.clone());
+        }
         
-        let encrypted = MessageCrypto::encrypt_text(session, text)
-            .map_err(|e| MessagingError::EncryptionError(e.to_string()))?;
+        let message = self.prepare_text_message(peer_id, text, Some(key))?;
+        self.sent_keys.put(key, message.clone());
         
-        Ok(Message::encrypted(
-            self.local_identity.peer_id().to_string(),
-            encrypted,
-        ))
+        Ok(message)
     }
     
-    /// Decrypt a received encrypted message
-    pub fn decrypt_message(&mut self, message: &Message) -> Result<String, MessagingError> {
-        match message {
-            Message::Encrypted {
-                from_peer_id,
-                encrypted,
-                ..
-            } => {
-                let session = self
-                    .sessions
-                    .get_mut(from_peer_id)
-                    .ok_or_else(|| MessagingError::PeerNotFound(from_peer_id.to_string()))?;
-                
-                MessageCrypto::decrypt_text(session, encrypted)
-                    .map_err(|e| MessagingError::DecryptionError(e.to_string()))
+    /// Encrypt (or downgrade) a text message for `peer_id`, tagged with `key` if it reads one
+    fn prepare_text_message(
+        &mut self,
+        peer_id: &str,
+        text: &str,
+        key: Option<IdempotencyKey>,
+    ) -> Result<Message, MessagingError> {
+        let message = match self.downgraded_message(peer_id, text)? {
+            Some(message) => message,
+            None => {
+                let message = self.encrypt_text_message(peer_id, text, None)?;
+                self.seal(peer_id, message)?
             }
-            Message::Text { content, .. } => Ok(content.clone()),
-            _ => Err(MessagingError::InvalidFormat(
-                "Not an encrypted or text message".to_string(),
-            )),
+        };
+        
+        match (message, key) {
+            (Message::Encrypted { from_peer_id, encrypted, timestamp }, Some(key)) if self.idempotent_peers.contains(peer_id) => {
+                Ok(Message::EncryptedWithIdempotency { from_peer_id, inner: encrypted, key, timestamp })
+            }
+            (message, _) => Ok(message),
         }
     }
     
-    /// Get list of registered peers
-    pub fn list_peers(&self) -> Vec<String> {
-        self.peers.keys().cloned().collect()
+    /// Encrypt a text message for `peer_id` and sign it with the local identity
+    #[cfg(feature = "sign_messages")]
+    pub fn prepare_signed_encrypted_message(
+        &mut self,
+        peer_id: &str,
+        text: &str,
+    ) -> Result<Message, MessagingError> {
+        if let Some(message) = self.downgraded_message(peer_id, text)? {
+            return Ok(message);
+        }
+        
+        let message = self.encrypt_text_message(peer_id, text, None)?;
+        self.seal(peer_id, message)
     }
     
-    /// Check if a peer is registered
-    pub fn has_peer(&self, peer_id: &str) -> bool {
-        self.peers.contains_key(peer_id)
+    /// Sign an encrypted message for `peer_id` if it reads signed messages
+    ///
+    /// Anything else, and everything without the `sign_messages` feature, is
+    /// returned unchanged.
+    fn seal(&self, peer_id: &str, message: Message) -> Result<Message, MessagingError> {
+        #[cfg(feature = "sign_messages")]
+        if let Message::Encrypted { from_peer_id, encrypted, timestamp } = message {
+            if self.compatibility_level(peer_id) != PeerCompatibilityLevel::Full {
+                return Ok(Message::Encrypted { from_peer_id, encrypted, timestamp });
+            }
+            return Ok(Message::SignedEncrypted {
+                from_peer_id,
+                signed: SignedEncryptedMessage::sign(&self.local_identity, encrypted)?,
+                timestamp,
+            });
+        }
+        #[cfg(not(feature = "sign_messages"))]
+        let _ = peer_id;
+        Ok(message)
     }
-}
-
-/// High-level messaging events
-#[derive(Debug, Clone)]
-pub enum MessagingEvent {
-    /// Received a text message
-    TextMessage {
-        from: String,
-        content: String,
+    
+    /// Refuse an unsigned message from a peer whose handshake offered signed ones
+    fn accept_unsigned(&self, from_peer_id: &str) -> Result<(), MessagingError> {
+        #[cfg(feature = "sign_messages")]
+        if self.compatibility.get(from_peer_id) == Some(&PeerCompatibilityLevel::Full) {
+            return Err(MessagingError::AuthenticityFailed(format!("unsigned message from {}", from_peer_id)));
+        }
+        #[cfg(not(feature = "sign_messages"))]
+        let _ = from_peer_id;
+        Ok(())
+    }
+    
+    /// Encrypt the same text for every registered peer in parallel
+    ///
+    /// Each peer's session is encrypted in its own task, so every message uses
+    /// that session's next counter. Results are sorted by peer ID; pass them to
+    /// [`MessageHandler::check_broadcast`] to turn failures into an error.
+    pub async fn broadcast_encrypted(&mut self, text: &str) -> Vec<(String, Result<Message, MessagingError>)> {
+        let mut tasks = JoinSet::new();
+        
+        let mut peer_ids: Vec<String> = self.sessions.keys().cloned().collect();
+        peer_ids.sort();
+        
+        for peer_id in &peer_ids {
+            if self.sessions[peer_id].is_expired() {
+                if let Err(e) = self.renew_session(peer_id) {
+                    warn!("Could not renew session with {}: {}", peer_id, e);
+                }
+            }
+        }
+        
+        for (index, peer_id) in peer_ids.iter().enumerate() {
+            let (plaintext, message_id) = match self.text_payload(peer_id, text, None) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Could not encode broadcast for {}: {}", peer_id, e);
+                    continue;
+                }
+            };
+            let Some(mut session) = self.sessions.remove(peer_id) else {
+                continue;
+            };
+            tasks.spawn_blocking(move || {
+                let encrypted = session.encrypt(&plaintext, None);
+                (index, session, encrypted, message_id)
+            });
+        }
+        
+        let local_peer_id = self.local_identity.peer_id().to_string();
+        let mut results: Vec<(String, Result<Message, MessagingError>)> = peer_ids
+            .iter()
+            .map(|peer_id| {
+                let error = MessagingError::EncryptionError(format!("encryption for {} did not complete", peer_id));
+                (peer_id.clone(), Err(error))
+            })
+            .collect();
+        
+        while let Some(joined) = tasks.join_next().await {
+            let (index, session, encrypted, message_id) = match joined {
+                Ok(output) => output,
+                Err(e) => {
+                    debug!("Broadcast encryption task failed: {}", e);
+                    continue;
+                }
+            };
+            
+            let peer_id = peer_ids[index].clone();
+            self.sessions.insert(peer_id.clone(), session);
+            
+            results[index].1 = match encrypted {
+                Ok(encrypted) => {
+                    let message = Message::encrypted(local_peer_id.clone(), encrypted);
+                    if let Message::Encrypted { timestamp, .. } = &message {
+                        let stored = StoredMessage::new(local_peer_id.clone(), text.to_string(), *timestamp, None)
+                            .with_id(message_id);
+                        self.delivery.sent(&stored.id);
+                        self.conversation_mut(&peer_id).push(stored);
+                    }
+                    self.seal(&peer_id, message)
+                }
+                Err(e) => Err(MessagingError::EncryptionError(e.to_string())),
+            };
+        }
+        
+        results
+    }
+    
+    /// Check broadcast results, naming every peer whose message could not be encrypted
+    pub fn check_broadcast(results: &[(String, Result<Message, MessagingError>)]) -> Result<(), MessagingError> {
+        let failed_peers: Vec<String> = results
+            .iter()
+            .filter(|(_, result)| result.is_err())
+            .map(|(peer_id, _)| peer_id.clone())
+            .collect();
+        
+        if failed_peers.is_empty() {
+            Ok(())
+        } else {
+            Err(MessagingError::PartialBroadcastFailure { failed_peers })
+        }
+    }
+    
+    /// Encrypt a reply to an earlier message in the conversation with `peer_id`
+    pub fn reply(
+        &mut self,
+        peer_id: &str,
+        reply_to_id: &str,
+        text: &str,
+    ) -> Result<Message, MessagingError> {
+        let thread = MessageThread {
+            reply_to_id: Some(reply_to_id.to_string()),
+        };
+        let message = self.encrypt_text_message(peer_id, text, Some(thread))?;
+        self.seal(peer_id, message)
+    }
+    
+    /// Edit a message previously sent to `peer_id`
+    ///
+    /// The signed revision is encrypted like a reply, so the new text stays
+    /// end-to-end encrypted. It is applied to the local history right away.
+    pub fn edit_message(
+        &mut self,
+        peer_id: &str,
+        message_id: &str,
+        new_text: &str,
+    ) -> Result<Message, MessagingError> {
+        self.revise_message(peer_id, message_id, RevisionAction::Edit(new_text.to_string()))
+    }
+    
+    /// Delete a message previously sent to `peer_id`, leaving a tombstone on both ends
+    pub fn delete_message(&mut self, peer_id: &str, message_id: &str) -> Result<Message, MessagingError> {
+        self.revise_message(peer_id, message_id, RevisionAction::Delete)
+    }
+    
+    fn revise_message(
+        &mut self,
+        peer_id: &str,
+        message_id: &str,
+        action: RevisionAction,
+    ) -> Result<Message, MessagingError> {
+        let local_peer_id = self.local_identity.peer_id().to_string();
+        let stored = self
+            .conversations
+            .get(peer_id)
+            .and_then(|c| c.get(message_id))
+            .ok_or_else(|| MessagingError::MessageNotFound(message_id.to_string()))?;
+        if stored.from != local_peer_id {
+            return Err(MessagingError::AuthenticityFailed(message_id.to_string()));
+        }
+        
+        let revision = MessageRevision::sign(&self.local_identity, message_id.to_string(), action)?;
+        let encrypted = self.encrypt_for(peer_id, &Message::Revision(revision.clone()).to_bytes()?)?;
+        
+        if let Some(stored) = self.conversation_mut(peer_id).get_mut(message_id) {
+            stored.apply_revision(&revision.action);
+        }
+        
+        self.seal(peer_id, Message::encrypted(local_peer_id, encrypted))
+    }
+    
+    /// Apply a revision received from `from_peer_id` and return the message's new content
+    ///
+    /// The revision must be signed by `from_peer_id` and target a message that
+    /// `from_peer_id` wrote.
+    pub fn apply_revision(&mut self, from_peer_id: &str, revision: &MessageRevision) -> Result<String, MessagingError> {
+        let author = self
+            .peers
+            .get(from_peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(from_peer_id.to_string()))?;
+        revision.verify(author)?;
+        
+        let stored = self
+            .conversations
+            .get_mut(from_peer_id)
+            .and_then(|c| c.get_mut(&revision.message_id))
+            .ok_or_else(|| MessagingError::MessageNotFound(revision.message_id.clone()))?;
+        if stored.from != from_peer_id {
+            return Err(MessagingError::AuthenticityFailed(from_peer_id.to_string()));
+        }
+        
+        stored.apply_revision(&revision.action);
+        debug!("Applied revision of message {} from {}", revision.message_id, from_peer_id);
+        Ok(stored.content.clone())
+    }
+    
+    /// The message a legacy peer can read, or `None` for a `Full` peer
+    fn downgraded_message(&mut self, peer_id: &str, text: &str) -> Result<Option<Message>, MessagingError> {
+        let message = match self.compatibility_level(peer_id) {
+            PeerCompatibilityLevel::Full => return Ok(None),
+            PeerCompatibilityLevel::LegacyNoSigning => {
+                warn!("Peer {} cannot verify signatures, sending an unsigned message", peer_id);
+                self.encrypt_text_message(peer_id, text, None)?
+            }
+            PeerCompatibilityLevel::LegacyV0 if !self.allow_plaintext => {
+                return Err(MessagingError::PlaintextDowngradeRefused(peer_id.to_string()));
+            }
+            PeerCompatibilityLevel::LegacyV0 => {
+                warn!("Peer {} speaks protocol version 0, sending the message unencrypted", peer_id);
+                self.plain_text_message(peer_id, text)?
+            }
+        };
+        Ok(Some(message))
+    }
+    
+    /// Record an outgoing message to `peer_id` and return it unencrypted
+    fn plain_text_message(&mut self, peer_id: &str, text: &str) -> Result<Message, MessagingError> {
+        if !self.sessions.contains_key(peer_id) {
+            return Err(MessagingError::PeerNotFound(peer_id.to_string()));
+        }
+        
+        let message = Message::text(text.to_string());
+        if let Message::Text { timestamp, .. } = &message {
+            let local_peer_id = self.local_identity.peer_id().to_string();
+            let stored = StoredMessage::new(local_peer_id, text.to_string(), *timestamp, None);
+            self.delivery.sent(&stored.id);
+            self.conversation_mut(peer_id).push(stored);
+        }
+        Ok(message)
+    }
+    
+    /// Encrypt a text message and record it in the conversation history
+    ///
+    /// Plain messages keep the raw UTF-8 wire format. Replies encrypt a
+    /// serialized `Message::Text` so the thread metadata stays end-to-end encrypted.
+    fn encrypt_text_message(
+        &mut self,
+        peer_id: &str,
+        text: &str,
+        thread: Option<MessageThread>,
+    ) -> Result<Message, MessagingError> {
+        let (plaintext, message_id) = self.text_payload(peer_id, text, thread.clone())?;
+        let encrypted = self.encrypt_for(peer_id, &plaintext)?;
+        
+        let local_peer_id = self.local_identity.peer_id().to_string();
+        let message = Message::encrypted(local_peer_id.clone(), encrypted);
+        
+        if let Message::Encrypted { timestamp, .. } = &message {
+            let stored = StoredMessage::new(local_peer_id, text.to_string(), *timestamp, thread).with_id(message_id);
+            self.delivery.sent(&stored.id);
+            self.conversation_mut(peer_id).push(stored);
+        }
+        
+        Ok(message)
+    }
+    
+    /// Plaintext carrying `text` to `peer_id`, and the ID assigned to the message
+    ///
+    /// Peers at the `Full` level get a structured payload with a random message
+    /// ID for their receipts to refer to. Legacy peers get the raw text, or a
+    /// structured payload without ID for replies, and both ends derive the ID.
+    fn text_payload(
+        &self,
+        peer_id: &str,
+        text: &str,
+        thread: Option<MessageThread>,
+    ) -> Result<(Vec<u8>, Option<Uuid>), MessagingError> {
+        let message_id = (self.compatibility_level(peer_id) == PeerCompatibilityLevel::Full).then(Uuid::new_v4);
+        if message_id.is_none() && thread.is_none() {
+            return Ok((text.as_bytes().to_vec(), None));
+        }
+        
+        let payload = Message::Text {
+            content: text.to_string(),
+            timestamp: Utc::now(),
+            thread,
+            message_id,
+        };
+        Ok((payload.to_bytes()?, message_id))
+    }
+    
+    /// Decrypt a received encrypted message
+    ///
+    /// Signed messages are checked against the claimed sender's identity key
+    /// before anything is decrypted. With the `sign_messages` feature, unsigned
+    /// messages from a peer whose handshake offered signed ones are rejected.
+    /// For a revision, the revised message's new content is returned.
+    pub fn decrypt_message(&mut self, message: &Message) -> Result<String, MessagingError> {
+        match message {
+            Message::SignedEncrypted {
+                from_peer_id,
+                signed,
+                timestamp,
+            } => {
+                let sender_public = self
+                    .peers
+                    .get(from_peer_id)
+                    .ok_or_else(|| MessagingError::PeerNotFound(from_peer_id.to_string()))?;
+                signed.verify(sender_public)?;
+                
+                self.decrypt_from(from_peer_id, &signed.encrypted, timestamp)
+            }
+            // Duplicates carry a counter the session has already seen and are rejected as replays
+            Message::EncryptedWithIdempotency {
+                from_peer_id,
+                inner,
+                timestamp,
+                ..
+            } => {
+                self.accept_unsigned(from_peer_id)?;
+                self.decrypt_from(from_peer_id, inner, timestamp)
+            }
+            Message::Encrypted {
+                from_peer_id,
+                encrypted,
+                timestamp,
+            } => {
+                self.accept_unsigned(from_peer_id)?;
+                self.decrypt_from(from_peer_id, encrypted, timestamp)
+            }
+            Message::Ephemeral { channel_id, encrypted } => self.receive_ephemeral(*channel_id, encrypted),
+            Message::Text { content, .. } => Ok(content.clone()),
+            _ => Err(MessagingError::InvalidFormat(
+                "Not an encrypted or text message".to_string(),
+            )),
+        }
+    }
+    
+    /// Decrypt a message from `from_peer_id` whose signature, if required, was checked
+    fn decrypt_from(
+        &mut self,
+        from_peer_id: &str,
+        encrypted: &EncryptedMessage,
+        timestamp: &DateTime<Utc>,
+    ) -> Result<String, MessagingError> {
+        let session = self
+            .sessions
+            .get_mut(from_peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(from_peer_id.to_string()))?;
+        
+        let plaintext = session
+            .decrypt(encrypted)
+            .map_err(|e| MessagingError::DecryptionError(e.to_string()))?;
+        
+        // Structured payloads (replies, revisions) first, then the legacy raw text format
+        let (content, thread, message_id) = match Message::from_bytes(&plaintext) {
+            Ok(Message::Text { content, thread, message_id, .. }) => (content, thread, message_id),
+            Ok(Message::Revision(revision)) => return self.apply_revision(from_peer_id, &revision),
+            _ => {
+                let content = String::from_utf8(plaintext)
+                    .map_err(|e| MessagingError::DecryptionError(e.to_string()))?;
+                (content, None, None)
+            }
+        };
+        
+        let stored = StoredMessage::new(from_peer_id.to_string(), content.clone(), *timestamp, thread).with_id(message_id);
+        self.conversation_mut(from_peer_id).push(stored);
+        
+        Ok(content)
+    }
+    
+    /// Open an ephemeral channel to a registered peer
+    ///
+    /// The channel is keyed with a freshly generated identity instead of the
+    /// local one and closes after `max_messages` messages in either direction
+    /// or `timeout_secs` seconds. Send the peer `ephemeral_invite` before the
+    /// first message.
+    pub fn open_ephemeral_channel(
+        &mut self,
+        peer_id: &str,
+        max_messages: u32,
+        timeout_secs: u64,
+    ) -> Result<EphemeralChannel, MessagingError> {
+        let remote_public = self
+            .peers
+            .get(peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))?;
+        
+        let identity = Identity::generate().map_err(|e| MessagingError::EncryptionError(e.to_string()))?;
+        let session = CryptoSession::new(&identity, remote_public, KdfAlgorithm::default())
+            .map_err(|e| MessagingError::EncryptionError(e.to_string()))?;
+        
+        let channel_id = Uuid::new_v4();
+        self.ephemeral.insert(
+            channel_id,
+            EphemeralSession::new(
+                peer_id.to_string(),
+                Some(identity),
+                session,
+                max_messages,
+                Duration::from_secs(timeout_secs),
+            ),
+        );
+        debug!("Opened ephemeral channel {} with {}", channel_id, peer_id);
+        
+        Ok(EphemeralChannel {
+            peer_id: peer_id.to_string(),
+            channel_id,
+        })
+    }
+    
+    /// Create the invitation that lets the peer join an ephemeral channel this node opened
+    pub fn ephemeral_invite(&self, channel_id: Uuid) -> Result<Message, MessagingError> {
+        let channel = self
+            .ephemeral
+            .get(&channel_id)
+            .filter(|channel| !channel.is_expired())
+            .ok_or(MessagingError::ChannelClosed(channel_id))?;
+        let identity = channel
+            .identity
+            .as_ref()
+            .ok_or_else(|| MessagingError::InvalidFormat("Channel was opened by the peer".to_string()))?;
+        
+        let remaining = channel.expires_at.saturating_duration_since(Instant::now());
+        let invite = EphemeralInvite::sign(
+            &self.local_identity,
+            channel_id,
+            PublicIdentity::from_identity(identity),
+            channel.remaining_messages,
+            remaining.as_secs().max(1),
+        )?;
+        Ok(Message::EphemeralInvite(invite))
+    }
+    
+    /// Join an ephemeral channel a registered peer invited this node to
+    pub fn accept_ephemeral_invite(&mut self, invite: &EphemeralInvite) -> Result<EphemeralChannel, MessagingError> {
+        let peer_id = &invite.from_peer_id;
+        let opener = self
+            .peers
+            .get(peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))?;
+        invite.verify(opener)?;
+        
+        let session = CryptoSession::new(&self.local_identity, &invite.ephemeral_identity, KdfAlgorithm::default())
+            .map_err(|e| MessagingError::DecryptionError(e.to_string()))?;
+        self.ephemeral.insert(
+            invite.channel_id,
+            EphemeralSession::new(
+                peer_id.clone(),
+                None,
+                session,
+                invite.max_messages,
+                Duration::from_secs(invite.timeout_secs),
+            ),
+        );
+        
+        Ok(EphemeralChannel {
+            peer_id: peer_id.clone(),
+            channel_id: invite.channel_id,
+        })
+    }
+    
+    /// Encrypt a message for an ephemeral channel
+    ///
+    /// The channel closes once this was its last allowed message.
+    pub fn send_ephemeral(&mut self, channel_id: Uuid, text: &str) -> Result<Message, MessagingError> {
+        let channel = self.open_ephemeral_session(channel_id)?;
+        let encrypted = channel
+            .session
+            .encrypt(text.as_bytes(), None)
+            .map_err(|e| MessagingError::EncryptionError(e.to_string()));
+        self.count_ephemeral_message(channel_id);
+        
+        Ok(Message::Ephemeral {
+            channel_id,
+            encrypted: encrypted?,
+        })
+    }
+    
+    /// Decrypt a message received on an ephemeral channel without recording it
+    fn receive_ephemeral(&mut self, channel_id: Uuid, encrypted: &EncryptedMessage) -> Result<String, MessagingError> {
+        let channel = self.open_ephemeral_session(channel_id)?;
+        let plaintext = channel
+            .session
+            .decrypt(encrypted)
+            .map_err(|e| MessagingError::DecryptionError(e.to_string()))?;
+        self.count_ephemeral_message(channel_id);
+        
+        String::from_utf8(plaintext).map_err(|e| MessagingError::DecryptionError(e.to_string()))
+    }
+    
+    /// The session of an open channel, closing it first if it expired
+    fn open_ephemeral_session(&mut self, channel_id: Uuid) -> Result<&mut EphemeralSession, MessagingError> {
+        if self.ephemeral.get(&channel_id).is_some_and(EphemeralSession::is_expired) {
+            self.close_ephemeral_channel(channel_id);
+        }
+        self.ephemeral
+            .get_mut(&channel_id)
+            .ok_or(MessagingError::ChannelClosed(channel_id))
+    }
+    
+    fn count_ephemeral_message(&mut self, channel_id: Uuid) {
+        let Some(channel) = self.ephemeral.get_mut(&channel_id) else {
+            return;
+        };
+        channel.remaining_messages = channel.remaining_messages.saturating_sub(1);
+        if channel.remaining_messages == 0 {
+            self.close_ephemeral_channel(channel_id);
+        }
+    }
+    
+    /// Whether an ephemeral channel is still open
+    pub fn is_ephemeral_channel_open(&self, channel_id: Uuid) -> bool {
+        self.ephemeral.get(&channel_id).is_some_and(|channel| !channel.is_expired())
+    }
+    
+    /// Close an ephemeral channel, zeroizing its session and temporary identity
+    pub fn close_ephemeral_channel(&mut self, channel_id: Uuid) {
+        if let Some(channel) = self.ephemeral.remove(&channel_id) {
+            debug!("Closed ephemeral channel {} with {}", channel_id, channel.peer_id);
+        }
+    }
+    
+    /// Close every ephemeral channel past its timeout or message limit
+    ///
+    /// Returns the IDs of the closed channels.
+    pub fn expire_ephemeral_channels(&mut self) -> Vec<Uuid> {
+        let expired: Vec<Uuid> = self
+            .ephemeral
+            .iter()
+            .filter(|(_, channel)| channel.is_expired())
+            .map(|(channel_id, _)| *channel_id)
+            .collect();
+        for channel_id in &expired {
+            self.close_ephemeral_channel(*channel_id);
+        }
+        expired
+    }
+    
+    /// Get the delivery status of a message sent by this node
+    pub fn delivery_status(&self, message_id: &str) -> Option<&DeliveryStatus> {
+        self.delivery.get(message_id)
+    }
+    
+    /// Record that `via` forwarded a sent message towards its recipient
+    pub fn record_relayed(&mut self, message_id: &str, via: PeerId) -> Option<&DeliveryStatus> {
+        self.delivery.relayed(message_id, via)
+    }
+    
+    /// Record that a sent message could not be published
+    pub fn record_failed(&mut self, message_id: &str, reason: String) -> Option<&DeliveryStatus> {
+        self.delivery.failed(message_id, reason)
+    }
+    
+    /// Create a signed receipt telling `peer_id` that its message arrived
+    pub fn acknowledge(&self, peer_id: &str, message_id: &str) -> Result<Message, MessagingError> {
+        let receipt = DeliveryReceipt::MessageAck {
+            message_id: message_id.to_string(),
+        };
+        self.receipt_for(peer_id, receipt)
+    }
+    
+    /// Create a signed receipt telling `peer_id` that its message was read
+    pub fn read_receipt(&self, peer_id: &str, message_id: &str) -> Result<Message, MessagingError> {
+        let receipt = DeliveryReceipt::MarkRead {
+            message_id: message_id.to_string(),
+        };
+        self.receipt_for(peer_id, receipt)
+    }
+    
+    /// Sign `receipt` for `peer_id`, which must have sent the message
+    fn receipt_for(&self, peer_id: &str, receipt: DeliveryReceipt) -> Result<Message, MessagingError> {
+        let message_id = receipt.message_id();
+        let received_from_peer = self
+            .conversations
+            .get(peer_id)
+            .and_then(|c| c.get(message_id))
+            .is_some_and(|m| m.from == peer_id);
+        if !received_from_peer {
+            return Err(MessagingError::MessageNotFound(message_id.to_string()));
+        }
+        
+        Message::receipt(&self.local_identity, peer_id, receipt)
+    }
+    
+    /// Apply a delivery receipt from `from_peer_id` and return the message's new status
+    ///
+    /// Only receipts for messages this node sent to `from_peer_id` are accepted.
+    /// The receipt must already have been authenticated.
+    fn handle_receipt(
+        &mut self,
+        from_peer_id: &str,
+        receipt: &DeliveryReceipt,
+    ) -> Result<&DeliveryStatus, MessagingError> {
+        let local_peer_id = self.local_identity.peer_id().as_str();
+        let message_id = receipt.message_id();
+        let sent_to_sender = self
+            .conversations
+            .get(from_peer_id)
+            .and_then(|c| c.get(message_id))
+            .is_some_and(|m| m.from == local_peer_id);
+        if !sent_to_sender {
+            return Err(MessagingError::MessageNotFound(message_id.to_string()));
+        }
+        
+        self.delivery
+            .advance(message_id, receipt.status())
+            .ok_or_else(|| MessagingError::MessageNotFound(message_id.to_string()))
+    }
+    
+    /// Apply a received `Message::DeliveryReceipt` or `Message::ReadReceipt`
+    ///
+    /// The receipt's signature is checked against the registered identity of
+    /// the peer it claims to come from. Returns `MessagingEvent::MessageDelivered`
+    /// or `MessagingEvent::MessageRead` when the receipt moves the message to
+    /// that status.
+    pub fn receive_receipt(&mut self, message: &Message) -> Result<Option<MessagingEvent>, MessagingError> {
+        let (from_peer_id, receipt, at, signature) = message
+            .as_receipt()
+            .ok_or_else(|| MessagingError::InvalidFormat("Not a receipt".to_string()))?;
+        let sender = self
+            .peers
+            .get(from_peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(from_peer_id.to_string()))?;
+        receipt::verify_receipt(sender, &receipt, self.local_identity.peer_id().as_str(), at, signature)?;
+        
+        let previous = self.delivery.get(receipt.message_id()).cloned();
+        let status = self.handle_receipt(from_peer_id, &receipt)?;
+        if previous.as_ref() == Some(status) {
+            return Ok(None);
+        }
+        
+        let peer_id = from_peer_id.to_string();
+        let message_id = receipt.message_id().to_string();
+        let at = *at;
+        Ok(match status {
+            DeliveryStatus::Delivered => Some(MessagingEvent::MessageDelivered {
+                peer_id,
+                message_id,
+                delivered_at: at,
+            }),
+            DeliveryStatus::Read => Some(MessagingEvent::MessageRead {
+                peer_id,
+                message_id,
+                read_at: at,
+            }),
+            _ => None,
+        })
+    }
+    
+    /// Sent messages still waiting for a delivery receipt, with the time they were sent
+    pub fn pending_deliveries(&self) -> &HashMap<String, DateTime<Utc>> {
+        self.delivery.pending()
+    }
+    
+    /// Get list of registered peers
+    pub fn list_peers(&self) -> Vec<String> {
+        self.peers.keys().cloned().collect()
+    }
+    
+    /// Check if a peer is registered
+    pub fn has_peer(&self, peer_id: &str) -> bool {
+        self.peers.contains_key(peer_id)
+    }
+    
+    /// Get the conversation with a peer, if any messages were exchanged
+    pub fn conversation(&self, peer_id: &str) -> Option<&Conversation> {
+        self.conversations.get(peer_id)
+    }
+    
+    /// Get a thread with a peer: the root message followed by its replies in chronological order
+    pub fn get_thread(&self, peer_id: &str, root_id: &str) -> Vec<&StoredMessage> {
+        self.conversations
+            .get(peer_id)
+            .map(|c| c.thread(root_id))
+            .unwrap_or_default()
+    }
+    
+    /// Mark the conversation with `peer_id` as read and create a receipt for the other devices
+    pub fn mark_read_for_sync(&mut self, peer_id: &str) -> Result<ReadReceipt, MessagingError> {
+        let conversation = self
+            .conversations
+            .get_mut(peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))?;
+        let last = conversation
+            .messages()
+            .last()
+            .map(|m| m.id.clone())
+            .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))?;
+        conversation.mark_read_up_to(&last);
+        
+        Ok(ReadReceipt {
+            peer_id: peer_id.to_string(),
+            last_read_message_id: last,
+            read_at: Utc::now(),
+            device_id: self.device_id.clone(),
+        })
+    }
+    
+    /// Apply a receipt from another device of the same user
+    ///
+    /// Returns `false` if the receipt is this device's own or refers to a
+    /// message this device has not received yet.
+    pub fn apply_remote_read_receipt(&mut self, receipt: &ReadReceipt) -> bool {
+        if receipt.device_id == self.device_id {
+            return false;
+        }
+        
+        let applied = self
+            .conversations
+            .get_mut(&receipt.peer_id)
+            .is_some_and(|c| c.mark_read_up_to(&receipt.last_read_message_id));
+        if applied {
+            debug!("Conversation with {} read on device {}", receipt.peer_id, receipt.device_id);
+        }
+        applied
+    }
+    
+    /// Record that a peer started typing
+    pub fn peer_started_typing(&mut self, peer_id: &str) {
+        self.typing.started(peer_id);
+    }
+    
+    /// Record that a peer stopped typing
+    pub fn peer_stopped_typing(&mut self, peer_id: &str) {
+        self.typing.stopped(peer_id);
+    }
+    
+    /// Check if a peer is currently shown as typing
+    pub fn is_peer_typing(&self, peer_id: &str) -> bool {
+        self.typing.is_typing(peer_id)
+    }
+    
+    /// Set how long a typing indicator lasts without a refresh
+    pub fn set_typing_timeout(&mut self, timeout: Duration) {
+        self.typing.timeout = timeout;
+    }
+    
+    /// Expire stale typing indicators, returning the affected peer IDs
+    pub fn poll_typing_timeouts(&mut self) -> Vec<String> {
+        self.typing.poll_timeouts()
+    }
+    
+    /// Typing indicator to send to `peer_id`, if it should be sent
+    ///
+    /// Call on every keystroke. The same state sent less than
+    /// `TYPING_DEBOUNCE` ago returns `None`. Each `true` pushes back the
+    /// automatic "stopped typing" from `poll_typing_indicators`.
+    pub fn send_typing_indicator(&mut self, peer_id: &str, is_typing: bool) -> Option<Message> {
+        let now = Instant::now();
+        let stop_at = is_typing.then_some(now + TYPING_STOP_DELAY);
+        
+        if let Some(sent) = self.sent_typing.get_mut(peer_id) {
+            if sent.is_typing == is_typing && now.duration_since(sent.sent_at) < TYPING_DEBOUNCE {
+                sent.stop_at = stop_at;
+                return None;
+            }
+        }
+        
+        self.sent_typing.insert(peer_id.to_string(), SentTyping {
+            is_typing,
+            sent_at: now,
+            stop_at,
+        });
+        Some(Message::Typing { is_typing })
+    }
+    
+    /// "Stopped typing" indicators due because the user went quiet, as `(peer_id, message)`
+    pub fn poll_typing_indicators(&mut self) -> Vec<(String, Message)> {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        for (peer_id, sent) in &mut self.sent_typing {
+            if sent.stop_at.is_some_and(|stop_at| stop_at <= now) {
+                *sent = SentTyping {
+                    is_typing: false,
+                    sent_at: now,
+                    stop_at: None,
+                };
+                due.push((peer_id.clone(), Message::Typing { is_typing: false }));
+            }
+        }
+        due
+    }
+    
+    /// Record whether `peer_id` is reachable
+    pub fn set_peer_online(&mut self, peer_id: &str, online: bool) {
+        if online {
+            self.online.insert(peer_id.to_string());
+        } else {
+            self.online.remove(peer_id);
+        }
+    }
+    
+    /// Whether `peer_id` was last reported online
+    pub fn is_peer_online(&self, peer_id: &str) -> bool {
+        self.online.contains(peer_id)
+    }
+    
+    /// Replace the offline queue, e.g. to change how many attempts it allows
+    pub fn set_offline_queue(&mut self, queue: OfflineQueue) {
+        self.offline_queue = queue;
+    }
+    
+    /// Encrypt a text message for an offline peer and queue it
+    ///
+    /// Returns the message ID. The conversation marks the message
+    /// `FailureReason::Offline` until it is sent.
+    pub fn queue_for_offline_peer(&mut self, peer_id: &str, text: &str) -> Result<String, MessagingError> {
+        let message = self.prepare_encrypted_message(peer_id, text)?;
+        let conversation = self.conversation_mut(peer_id);
+        let message_id = conversation
+            .messages()
+            .last()
+            .map(|m| m.id.clone())
+            .expect("prepared messages are recorded");
+        conversation.failed_messages.insert(message_id.clone(), FailureReason::Offline);
+        
+        self.offline_queue
+            .push(QueuedMessage::new(peer_id.to_string(), message_id.clone(), message, text));
+        Ok(message_id)
+    }
+    
+    /// Run one delivery attempt over the offline queue
+    ///
+    /// Returns the `(peer_id, message)` pairs to send now that their peer is
+    /// online, and a `MessagingEvent::MessageUndeliverable` for every message
+    /// that just ran out of attempts.
+    pub fn poll_offline_queue(&mut self) -> (Vec<(String, Message)>, Vec<MessagingEvent>) {
+        let online = &self.online;
+        let (ready, expired) = self.offline_queue.poll(|peer_id| online.contains(peer_id));
+        
+        let ready = ready
+            .into_iter()
+            .map(|queued| {
+                self.conversation_mut(&queued.peer_id).failed_messages.remove(&queued.message_id);
+                (queued.peer_id, queued.message)
+            })
+            .collect();
+        
+        let events = expired
+            .into_iter()
+            .map(|queued| {
+                warn!("Message {} to {} expired in the offline queue", queued.message_id, queued.peer_id);
+                self.conversation_mut(&queued.peer_id)
+                    .failed_messages
+                    .insert(queued.message_id.clone(), FailureReason::Expired);
+                self.delivery.failed(&queued.message_id, "peer stayed offline".to_string());
+                MessagingEvent::MessageUndeliverable {
+                    peer_id: queued.peer_id,
+                    message_id: queued.message_id,
+                    original_text_hash: queued.text_hash,
+                }
+            })
+            .collect();
+        
+        (ready, events)
+    }
+    
+    /// Queue an expired message again, now that its peer is online
+    ///
+    /// The message goes out on the next [`MessageHandler::poll_offline_queue`].
+    pub fn retry_failed(&mut self, peer_id: &str, message_id: &str) -> Result<(), MessagingError> {
+        if !self.is_peer_online(peer_id) {
+            return Err(MessagingError::PeerOffline(peer_id.to_string()));
+        }
+        if !self.offline_queue.requeue(peer_id, message_id) {
+            return Err(MessagingError::MessageNotFound(message_id.to_string()));
+        }
+        
+        self.conversation_mut(peer_id)
+            .failed_messages
+            .insert(message_id.to_string(), FailureReason::Offline);
+        Ok(())
+    }
+    
+    /// Encrypt with the session of `peer_id`, renewing it first if it expired
+    fn encrypt_for(&mut self, peer_id: &str, plaintext: &[u8]) -> Result<EncryptedMessage, MessagingError> {
+        match self.session_mut(peer_id)?.encrypt(plaintext, None) {
+            Err(CryptoError::SessionExpired) => {
+                self.renew_session(peer_id)?;
+                self.session_mut(peer_id)?.encrypt(plaintext, None)
+            }
+            result => result,
+        }
+        .map_err(|e| MessagingError::EncryptionError(e.to_string()))
+    }
+    
+    fn session_mut(&mut self, peer_id: &str) -> Result<&mut CryptoSession, MessagingError> {
+        self.sessions
+            .get_mut(peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))
+    }
+    
+    fn conversation_mut(&mut self, peer_id: &str) -> &mut Conversation {
+        self.conversations
+            .entry(peer_id.to_string())
+            .or_insert_with(|| Conversation::new(peer_id.to_string()))
+    }
+}
+
+/// High-level messaging events
+#[derive(Debug, Clone)]
+pub enum MessagingEvent {
+    /// Received a text message
+    TextMessage {
+        from: String,
+        content: String,
         timestamp: DateTime<Utc>,
     },
     
@@ -242,6 +1874,68 @@ pub enum MessagingEvent {
         peer_id: String,
         is_typing: bool,
     },
+    
+    /// A queued message ran out of delivery attempts
+    ///
+    /// Carries a hash of the text rather than the text itself.
+    MessageUndeliverable {
+        peer_id: String,
+        message_id: String,
+        original_text_hash: [u8; 8],
+    },
+    
+    /// The recipient confirmed that a sent message arrived
+    MessageDelivered {
+        peer_id: String,
+        message_id: String,
+        delivered_at: DateTime<Utc>,
+    },
+    
+    /// The recipient read a sent message
+    MessageRead {
+        peer_id: String,
+        message_id: String,
+        read_at: DateTime<Utc>,
+    },
+    
+    /// A file transfer completed and matched its SHA-256
+    FileReceived {
+        from: String,
+        transfer_id: String,
+        filename: String,
+        mime_type: String,
+        data: Vec<u8>,
+    },
+}
+
+/// Spawn a task that expires stale typing indicators
+///
+/// Every few seconds, peers whose indicator timed out get a
+/// `MessagingEvent::PeerTyping { is_typing: false }`. The task ends when the
+/// event receiver is dropped.
+pub fn spawn_typing_timeout_task(
+    handler: Arc<Mutex<MessageHandler>>,
+    event_tx: mpsc::Sender<MessagingEvent>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(TYPING_POLL_INTERVAL);
+        
+        loop {
+            interval.tick().await;
+            
+            let expired = handler.lock().await.poll_typing_timeouts();
+            for peer_id in expired {
+                debug!("Typing indicator for {} timed out", peer_id);
+                let event = MessagingEvent::PeerTyping {
+                    peer_id,
+                    is_typing: false,
+                };
+                if event_tx.send(event).await.is_err() {
+                    return;
+                }
+            }
+        }
+    })
 }
 
 /// Commands for the messaging layer
@@ -271,6 +1965,16 @@ pub enum MessagingCommand {
 mod tests {
     use super::*;
     
+    /// Envelope and send time of any encrypted message, signed or not
+    fn sealed_parts(message: &Message) -> (&EncryptedMessage, DateTime<Utc>) {
+        match message {
+            Message::Encrypted { encrypted, timestamp, .. } => (encrypted, *timestamp),
+            Message::EncryptedWithIdempotency { inner, timestamp, .. } => (inner, *timestamp),
+            Message::SignedEncrypted { signed, timestamp, .. } => (&signed.encrypted, *timestamp),
+            other => panic!("Unexpected message: {:?}", other),
+        }
+    }
+    
     #[test]
     fn test_message_serialization() {
         let msg = Message::text("Hello, Otter!".to_string());
@@ -316,6 +2020,11 @@ mod tests {
         
         // Verify it's still an encrypted message
         match deserialized_msg {
+            #[cfg(feature = "sign_messages")]
+            Message::SignedEncrypted { .. } => {
+                println!("✓ Deserialization successful!");
+            },
+            #[cfg(not(feature = "sign_messages"))]
             Message::Encrypted { .. } => {
                 println!("✓ Deserialization successful!");
             },
@@ -339,6 +2048,77 @@ mod tests {
         assert!(alice_handler.has_peer(&bob.peer_id().to_string()));
     }
     
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_register_peers_batch() {
+        let mut handler = MessageHandler::new(Identity::generate().unwrap());
+        let peers: Vec<Identity> = (0..50).map(|_| Identity::generate().unwrap()).collect();
+        let identities: Vec<PublicIdentity> = peers.iter().map(PublicIdentity::from_identity).collect();
+        
+        let results = handler.register_peers_batch(identities.clone()).await;
+        assert_eq!(results.len(), 50);
+        assert!(results.iter().all(|r| r.is_ok()));
+        
+        // Every session matches the one a sequential registration would derive
+        for peer in &peers {
+            let peer_id = peer.peer_id().to_string();
+            let expected = CryptoSession::new(peer, &handler.public_identity(), KdfAlgorithm::default()).unwrap();
+            assert!(handler.sessions[&peer_id].fingerprint().verify(&expected.fingerprint()));
+        }
+        
+        let mut peer_ids: Vec<String> = identities.iter().map(|i| i.peer_id().to_string()).collect();
+        peer_ids.push("unknown".to_string());
+        let peer_refs: Vec<&str> = peer_ids.iter().map(String::as_str).collect();
+        
+        let resolved = handler.resolve_identities(&peer_refs);
+        assert_eq!(resolved.len(), 51);
+        for (identity, peer_id) in resolved.iter().zip(&peer_ids).take(50) {
+            assert_eq!(identity.as_ref().unwrap().peer_id().as_str(), peer_id);
+        }
+        assert!(matches!(resolved[50], Err(MessagingError::PeerNotFound(_))));
+    }
+    
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_broadcast_encrypted() {
+        let mut handler = MessageHandler::new(Identity::generate().unwrap());
+        let peers: Vec<Identity> = (0..20).map(|_| Identity::generate().unwrap()).collect();
+        let identities: Vec<PublicIdentity> = peers.iter().map(PublicIdentity::from_identity).collect();
+        assert!(handler.register_peers_batch(identities).await.iter().all(|r| r.is_ok()));
+        
+        // Advance some sessions so their counters differ
+        let mut expected_counters = HashMap::new();
+        for (i, peer) in peers.iter().enumerate() {
+            let peer_id = peer.peer_id().to_string();
+            for _ in 0..i % 4 {
+                handler.prepare_encrypted_message(&peer_id, "warm-up").unwrap();
+            }
+            expected_counters.insert(peer_id, (i % 4) as u64);
+        }
+        
+        let results = handler.broadcast_encrypted("status update").await;
+        assert_eq!(results.len(), 20);
+        assert!(MessageHandler::check_broadcast(&results).is_ok());
+        
+        for (peer_id, result) in &results {
+            match result {
+                Ok(message) => {
+                    assert_eq!(sealed_parts(message).0.message_counter, expected_counters[peer_id]);
+                }
+                Err(e) => panic!("Unexpected broadcast result for {}: {:?}", peer_id, e),
+            }
+        }
+        
+        // Sessions are back in place and continue from the next counter
+        let (peer_id, _) = &results[0];
+        let after = handler.prepare_encrypted_message(peer_id, "after").unwrap();
+        assert_eq!(sealed_parts(&after).0.message_counter, expected_counters[peer_id] + 1);
+        
+        let failed = vec![("peer".to_string(), Err(MessagingError::PeerNotFound("peer".to_string())))];
+        assert!(matches!(
+            MessageHandler::check_broadcast(&failed),
+            Err(MessagingError::PartialBroadcastFailure { failed_peers }) if failed_peers == vec!["peer".to_string()]
+        ));
+    }
+    
     #[test]
     fn test_encrypted_messaging() {
         let alice = Identity::generate().unwrap();
@@ -363,4 +2143,759 @@ mod tests {
         let decrypted = bob_handler.decrypt_message(&encrypted_msg).unwrap();
         assert_eq!(decrypted, text);
     }
+    
+    #[cfg(feature = "sign_messages")]
+    #[test]
+    fn test_signed_message_from_wrong_sender_fails_authenticity() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let carol = Identity::generate().unwrap();
+        let carol_public = PublicIdentity::from_identity(&carol);
+        
+        let mut carol_handler = MessageHandler::new(carol);
+        carol_handler.register_peer(PublicIdentity::from_identity(&alice)).unwrap();
+        carol_handler.register_peer(PublicIdentity::from_identity(&bob)).unwrap();
+        
+        let mut alice_handler = MessageHandler::new(alice);
+        alice_handler.register_peer(carol_public.clone()).unwrap();
+        let signed = alice_handler
+            .prepare_signed_encrypted_message(carol_public.peer_id().as_str(), "Signed by Alice")
+            .unwrap();
+        
+        // Same envelope, but claiming to come from Bob
+        let Message::SignedEncrypted { signed: envelope, timestamp, .. } = signed.clone() else {
+            panic!("expected a signed message");
+        };
+        let forged = Message::SignedEncrypted {
+            from_peer_id: bob.peer_id().to_string(),
+            signed: envelope,
+            timestamp,
+        };
+        assert!(matches!(
+            carol_handler.decrypt_message(&forged),
+            Err(MessagingError::AuthenticityFailed(ref peer_id)) if *peer_id == bob.peer_id().to_string()
+        ));
+        
+        let bytes = signed.to_bytes().unwrap();
+        let decrypted = carol_handler.decrypt_message(&Message::from_bytes(&bytes).unwrap()).unwrap();
+        assert_eq!(decrypted, "Signed by Alice");
+    }
+    
+    #[cfg(feature = "sign_messages")]
+    #[test]
+    fn test_unsigned_message_from_signing_peer_is_rejected() {
+        let alice = Identity::generate().unwrap();
+        let carol = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let carol_public = PublicIdentity::from_identity(&carol);
+        let carol_id = carol_public.peer_id().to_string();
+        
+        // Carol learns from Alice's handshake that Alice signs her messages
+        let mut alice_handshake = otter_protocol::Handshake::new(
+            alice_public,
+            vec![
+                otter_protocol::Capability::E2EEncryption.into(),
+                PeerCompatibilityLevel::signed_messages_capability(),
+            ],
+        );
+        alice_handshake.sign(&alice).unwrap();
+        let mut carol_handler = MessageHandler::new(carol.clone());
+        carol_handler.register_peer_with_handshake(&alice_handshake).unwrap();
+        
+        // An envelope without a signature, as an attacker stripping it would send
+        let mut carol_handshake = otter_protocol::Handshake::new(
+            carol_public,
+            vec![otter_protocol::Capability::E2EEncryption.into()],
+        );
+        carol_handshake.sign(&carol).unwrap();
+        let mut alice_handler = MessageHandler::new(alice);
+        alice_handler.register_peer_with_handshake(&carol_handshake).unwrap();
+        let unsigned = alice_handler.prepare_encrypted_message(&carol_id, "stripped").unwrap();
+        assert!(matches!(unsigned, Message::Encrypted { .. }));
+        assert!(matches!(
+            carol_handler.decrypt_message(&unsigned),
+            Err(MessagingError::AuthenticityFailed(_))
+        ));
+        
+        let Message::Encrypted { from_peer_id, encrypted, timestamp } = unsigned else {
+            unreachable!();
+        };
+        let tagged = Message::EncryptedWithIdempotency {
+            from_peer_id,
+            inner: encrypted,
+            key: IdempotencyKey::default(),
+            timestamp,
+        };
+        assert!(matches!(
+            carol_handler.decrypt_message(&tagged),
+            Err(MessagingError::AuthenticityFailed(_))
+        ));
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_session_is_renewed() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let bob_id = bob.peer_id().to_string();
+        let mut alice_handler = MessageHandler::new(alice.clone());
+        let mut bob_handler = MessageHandler::new(bob);
+        alice_handler.set_session_lifetime(Some(Duration::from_secs(60)));
+        alice_handler.register_peer(bob_handler.public_identity()).unwrap();
+        bob_handler.register_peer(PublicIdentity::from_identity(&alice)).unwrap();
+        
+        let first = alice_handler.prepare_encrypted_message(&bob_id, "first").unwrap();
+        assert_eq!(bob_handler.decrypt_message(&first).unwrap(), "first");
+        assert_eq!(alice_handler.session_generation(&bob_id), 0);
+        assert!(alice_handler.take_session_announcements().is_empty());
+        
+        tokio::time::advance(Duration::from_secs(61)).await;
+        let second = alice_handler.prepare_encrypted_message(&bob_id, "second").unwrap();
+        assert_eq!(alice_handler.session_generation(&bob_id), 1);
+        
+        // Bob rebuilds his side from the announcement, then reads the new session
+        let announcements = alice_handler.take_session_announcements();
+        assert_eq!(announcements.len(), 1);
+        assert_eq!(announcements[0].0, bob_id);
+        match &announcements[0].1 {
+            Message::Identity { public_identity, .. } => bob_handler.register_peer(public_identity.clone()).unwrap(),
+            other => panic!("expected an identity announcement, got {:?}", other),
+        }
+        assert_eq!(bob_handler.decrypt_message(&second).unwrap(), "second");
+        
+        // The renewed session gets the same lifetime
+        let third = alice_handler.prepare_encrypted_message(&bob_id, "third").unwrap();
+        assert_eq!(bob_handler.decrypt_message(&third).unwrap(), "third");
+        assert_eq!(alice_handler.session_generation(&bob_id), 1);
+        tokio::time::advance(Duration::from_secs(61)).await;
+        alice_handler.prepare_encrypted_message(&bob_id, "fourth").unwrap();
+        assert_eq!(alice_handler.session_generation(&bob_id), 2);
+        
+        // Peers registered in a batch expire the same way
+        let carol = Identity::generate().unwrap();
+        let carol_id = carol.peer_id().to_string();
+        let results = alice_handler.register_peers_batch(vec![PublicIdentity::from_identity(&carol)]).await;
+        assert!(results[0].is_ok());
+        alice_handler.prepare_encrypted_message(&carol_id, "hello").unwrap();
+        assert_eq!(alice_handler.session_generation(&carol_id), 0);
+        tokio::time::advance(Duration::from_secs(61)).await;
+        alice_handler.prepare_encrypted_message(&carol_id, "still there?").unwrap();
+        assert_eq!(alice_handler.session_generation(&carol_id), 1);
+    }
+    
+    #[test]
+    fn test_offline_message_undeliverable_once() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let bob_id = bob.peer_id().to_string();
+        let mut alice_handler = MessageHandler::new(alice.clone());
+        let mut bob_handler = MessageHandler::new(bob);
+        alice_handler.register_peer(bob_handler.public_identity()).unwrap();
+        bob_handler.register_peer(PublicIdentity::from_identity(&alice)).unwrap();
+        alice_handler.set_offline_queue(OfflineQueue::new(3));
+        
+        let message_id = alice_handler.queue_for_offline_peer(&bob_id, "are you there?").unwrap();
+        let failed = |handler: &MessageHandler| handler.conversation(&bob_id).unwrap().failed_messages.get(&message_id).copied();
+        assert_eq!(failed(&alice_handler), Some(FailureReason::Offline));
+        
+        let mut undeliverable = Vec::new();
+        for _ in 0..6 {
+            let (ready, events) = alice_handler.poll_offline_queue();
+            assert!(ready.is_empty());
+            undeliverable.extend(events);
+        }
+        assert_eq!(undeliverable.len(), 1);
+        match &undeliverable[0] {
+            MessagingEvent::MessageUndeliverable { peer_id, message_id: id, original_text_hash } => {
+                assert_eq!(peer_id, &bob_id);
+                assert_eq!(id, &message_id);
+                assert_eq!(original_text_hash[..], blake3::hash(b"are you there?").as_bytes()[..8]);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert_eq!(failed(&alice_handler), Some(FailureReason::Expired));
+        assert!(matches!(alice_handler.delivery_status(&message_id), Some(DeliveryStatus::Failed(_))));
+        
+        // Retrying needs the peer online, then the original message goes out
+        assert!(matches!(alice_handler.retry_failed(&bob_id, &message_id), Err(MessagingError::PeerOffline(_))));
+        alice_handler.set_peer_online(&bob_id, true);
+        alice_handler.retry_failed(&bob_id, &message_id).unwrap();
+        assert!(alice_handler.retry_failed(&bob_id, &message_id).is_err());
+        
+        let (ready, events) = alice_handler.poll_offline_queue();
+        assert!(events.is_empty());
+        assert_eq!(ready.len(), 1);
+        assert_eq!(bob_handler.decrypt_message(&ready[0].1).unwrap(), "are you there?");
+        assert_eq!(failed(&alice_handler), None);
+    }
+    
+    #[test]
+    fn test_downgrade_for_legacy_peers() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut bob_handler = MessageHandler::new(bob.clone());
+        bob_handler.register_peer(PublicIdentity::from_identity(&alice)).unwrap();
+        let mut alice_handler = MessageHandler::new(alice);
+        
+        // Bob runs a version 0 client; anyone can forge an unsigned downgrade
+        let mut handshake = otter_protocol::Handshake::new(bob_public.clone(), Vec::new());
+        handshake.version = 0;
+        assert!(matches!(
+            alice_handler.register_peer_with_handshake(&handshake),
+            Err(MessagingError::PlaintextDowngradeRefused(_))
+        ));
+        assert!(!alice_handler.has_peer(&bob_id));
+        
+        // Signed, but plaintext was not allowed
+        handshake.sign(&bob).unwrap();
+        assert!(matches!(
+            alice_handler.register_peer_with_handshake(&handshake),
+            Err(MessagingError::PlaintextDowngradeRefused(_))
+        ));
+        
+        alice_handler.set_allow_plaintext(true);
+        alice_handler.register_peer_with_handshake(&handshake).unwrap();
+        assert_eq!(alice_handler.compatibility_level(&bob_id), PeerCompatibilityLevel::LegacyV0);
+        assert!(matches!(
+            alice_handler.check_compatibility(&bob_id),
+            Err(MessagingError::CompatibilityDowngrade { to_level: PeerCompatibilityLevel::LegacyV0 })
+        ));
+        
+        let message = alice_handler.prepare_encrypted_message(&bob_id, "hello old friend").unwrap();
+        assert!(matches!(message, Message::Text { ref content, .. } if content == "hello old friend"));
+        assert_eq!(bob_handler.decrypt_message(&message).unwrap(), "hello old friend");
+        assert_eq!(alice_handler.conversation(&bob_id).unwrap().messages().len(), 1);
+        
+        // Withdrawing the opt-in stops plaintext even for a registered peer
+        alice_handler.set_allow_plaintext(false);
+        assert!(matches!(
+            alice_handler.prepare_encrypted_message(&bob_id, "still there?"),
+            Err(MessagingError::PlaintextDowngradeRefused(_))
+        ));
+        
+        // After an upgrade that still lacks signing
+        handshake.version = otter_protocol::PROTOCOL_VERSION;
+        handshake.sign(&bob).unwrap();
+        alice_handler.register_peer_with_handshake(&handshake).unwrap();
+        let message = alice_handler.prepare_encrypted_message(&bob_id, "encrypted now").unwrap();
+        assert!(matches!(message, Message::Encrypted { .. }));
+        assert_eq!(bob_handler.decrypt_message(&message).unwrap(), "encrypted now");
+        #[cfg(feature = "sign_messages")]
+        assert!(matches!(
+            alice_handler.prepare_signed_encrypted_message(&bob_id, "signed?").unwrap(),
+            Message::Encrypted { .. }
+        ));
+        
+        handshake.capabilities.push(PeerCompatibilityLevel::signed_messages_capability());
+        handshake.sign(&bob).unwrap();
+        alice_handler.register_peer_with_handshake(&handshake).unwrap();
+        alice_handler.check_compatibility(&bob_id).unwrap();
+        let message = alice_handler.prepare_encrypted_message(&bob_id, "all features").unwrap();
+        #[cfg(feature = "sign_messages")]
+        assert!(matches!(message, Message::SignedEncrypted { .. }));
+        #[cfg(not(feature = "sign_messages"))]
+        assert!(matches!(message, Message::Encrypted { .. }));
+    }
+    
+    #[test]
+    fn test_signed_handshake_verified_before_registration() {
+        let bob = Identity::generate().unwrap();
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let bob_id = bob_public.peer_id().to_string();
+        let mut alice_handler = MessageHandler::new(Identity::generate().unwrap());
+        
+        let mut handshake = otter_protocol::Handshake::new(bob_public, vec![otter_protocol::Capability::E2EEncryption.into()]);
+        handshake.sign(&bob).unwrap();
+        
+        // Capabilities added after signing are not accepted
+        let mut forged = handshake.clone();
+        forged.capabilities.push(PeerCompatibilityLevel::signed_messages_capability());
+        assert!(matches!(
+            alice_handler.register_peer_with_handshake(&forged),
+            Err(MessagingError::AuthenticityFailed(_))
+        ));
+        assert!(alice_handler.conversation(&bob_id).is_none());
+        
+        alice_handler.register_peer_with_handshake(&handshake).unwrap();
+        assert_eq!(alice_handler.compatibility_level(&bob_id), PeerCompatibilityLevel::LegacyNoSigning);
+    }
+    
+    #[test]
+    fn test_reply_thread_ordering() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let alice_id = alice_public.peer_id().to_string();
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut alice_handler = MessageHandler::new(alice);
+        let mut bob_handler = MessageHandler::new(bob);
+        
+        alice_handler.register_peer(bob_public).unwrap();
+        bob_handler.register_peer(alice_public).unwrap();
+        
+        // Alice starts a thread, Bob answers it ten times
+        let root = alice_handler.prepare_encrypted_message(&bob_id, "Lunch?").unwrap();
+        bob_handler.decrypt_message(&root).unwrap();
+        let root_id = bob_handler.conversation(&alice_id).unwrap().messages()[0].id.clone();
+        
+        for i in 0..10 {
+            let reply = bob_handler.reply(&alice_id, &root_id, &format!("Reply {}", i)).unwrap();
+            let decrypted = alice_handler.decrypt_message(&reply).unwrap();
+            assert_eq!(decrypted, format!("Reply {}", i));
+        }
+        
+        // An unrelated message must not show up in the thread
+        let other = alice_handler.prepare_encrypted_message(&bob_id, "Unrelated").unwrap();
+        bob_handler.decrypt_message(&other).unwrap();
+        
+        for (handler, peer) in [(&alice_handler, &bob_id), (&bob_handler, &alice_id)] {
+            let thread = handler.get_thread(peer, &root_id);
+            assert_eq!(thread.len(), 11);
+            assert_eq!(thread[0].content, "Lunch?");
+            for (i, message) in thread[1..].iter().enumerate() {
+                assert_eq!(message.content, format!("Reply {}", i));
+                assert_eq!(message.reply_to_id(), Some(root_id.as_str()));
+            }
+            assert!(thread.windows(2).all(|w| w[0].timestamp <= w[1].timestamp));
+        }
+    }
+    
+    #[test]
+    fn test_read_receipt_from_other_device_clears_unread() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let alice_id = alice_public.peer_id().to_string();
+        let bob_id = bob_public.peer_id().to_string();
+        
+        // Alice's phone and desktop both receive Bob's messages
+        let mut phone = MessageHandler::new(alice.clone());
+        let mut desktop = MessageHandler::new(alice);
+        let mut bob_handler = MessageHandler::new(bob);
+        phone.register_peer(bob_public.clone()).unwrap();
+        desktop.register_peer(bob_public).unwrap();
+        bob_handler.register_peer(alice_public).unwrap();
+        
+        for text in ["Hi", "Are you there?"] {
+            let message = bob_handler.prepare_encrypted_message(&alice_id, text).unwrap();
+            phone.decrypt_message(&message).unwrap();
+            desktop.decrypt_message(&message).unwrap();
+        }
+        assert_eq!(desktop.conversation(&bob_id).unwrap().unread_count(), 2);
+        
+        let receipt = phone.mark_read_for_sync(&bob_id).unwrap();
+        assert_eq!(phone.conversation(&bob_id).unwrap().unread_count(), 0);
+        assert!(!phone.apply_remote_read_receipt(&receipt));
+        
+        let sync = DeviceSyncMessage::SyncReadReceipts(vec![receipt]).to_bytes().unwrap();
+        let DeviceSyncMessage::SyncReadReceipts(receipts) = DeviceSyncMessage::from_bytes(&sync).unwrap();
+        assert!(desktop.apply_remote_read_receipt(&receipts[0]));
+        assert_eq!(desktop.conversation(&bob_id).unwrap().unread_count(), 0);
+        
+        // New messages after the receipt are unread again
+        let message = bob_handler.prepare_encrypted_message(&alice_id, "Hello?").unwrap();
+        desktop.decrypt_message(&message).unwrap();
+        assert_eq!(desktop.conversation(&bob_id).unwrap().unread_count(), 1);
+    }
+    
+    #[test]
+    fn test_idempotent_send_is_not_encrypted_twice() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut handler = MessageHandler::new(alice.clone());
+        let mut bob_handler = MessageHandler::new(bob);
+        handler.register_peer(bob_public).unwrap();
+        bob_handler.register_peer(PublicIdentity::from_identity(&alice)).unwrap();
+        
+        let counter = |message: &Message| sealed_parts(message).0.message_counter;
+        
+        // A fresh session accepts counter 0 twice, so start past it
+        let warmup = handler.prepare_encrypted_message(&bob_id, "Hi").unwrap();
+        bob_handler.decrypt_message(&warmup).unwrap();
+        
+        let key = IdempotencyKey::new();
+        let first = handler.prepare_idempotent_message(&bob_id, "Hello", key).unwrap();
+        let retry = handler.prepare_idempotent_message(&bob_id, "Hello", key).unwrap();
+        assert_eq!(counter(&first), counter(&retry));
+        assert_eq!(handler.conversation(&bob_id).unwrap().messages.len(), 2);
+        
+        let next = handler.prepare_idempotent_message(&bob_id, "Hello", IdempotencyKey::new()).unwrap();
+        assert_eq!(counter(&next), counter(&first) + 1);
+        
+        // The receiver drops a retry that was delivered anyway
+        bob_handler.decrypt_message(&first).unwrap();
+        assert!(bob_handler.decrypt_message(&retry).is_err());
+    }
+    
+    #[test]
+    fn test_idempotency_key_sent_only_to_peers_that_read_it() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut handler = MessageHandler::new(alice.clone());
+        
+        // Sends without a caller key are not remembered
+        handler.register_peer(bob_public.clone()).unwrap();
+        handler.prepare_encrypted_message(&bob_id, "Hi").unwrap();
+        assert!(handler.sent_keys.is_empty());
+        
+        // Bob does not sign, and has not said he reads idempotency keys
+        let mut handshake = otter_protocol::Handshake::new(bob_public, vec![otter_protocol::Capability::E2EEncryption.into()]);
+        handshake.sign(&bob).unwrap();
+        handler.register_peer_with_handshake(&handshake).unwrap();
+        let message = handler.prepare_idempotent_message(&bob_id, "Hello", IdempotencyKey::new()).unwrap();
+        assert!(matches!(message, Message::Encrypted { .. }));
+        assert_eq!(handler.sent_keys.len(), 1);
+        
+        handshake.capabilities.push(PeerCompatibilityLevel::idempotency_capability());
+        handshake.sign(&bob).unwrap();
+        handler.register_peer_with_handshake(&handshake).unwrap();
+        let key = IdempotencyKey::new();
+        let message = handler.prepare_idempotent_message(&bob_id, "Hello again", key).unwrap();
+        assert!(matches!(message, Message::EncryptedWithIdempotency { key: sent, .. } if sent == key));
+        let mut bob_handler = MessageHandler::new(bob);
+        bob_handler.register_peer(PublicIdentity::from_identity(&alice)).unwrap();
+        assert_eq!(bob_handler.decrypt_message(&message).unwrap(), "Hello again");
+        
+        // Without a key there is nothing to tag
+        let message = handler.prepare_encrypted_message(&bob_id, "Bye").unwrap();
+        assert!(matches!(message, Message::Encrypted { .. }));
+        assert_eq!(handler.sent_keys.len(), 2);
+    }
+    
+    #[test]
+    fn test_edit_and_delete_message() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let alice_id = alice_public.peer_id().to_string();
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut alice_handler = MessageHandler::new(alice);
+        let mut bob_handler = MessageHandler::new(bob);
+        alice_handler.register_peer(bob_public).unwrap();
+        bob_handler.register_peer(alice_public).unwrap();
+        
+        // Skip counter 0, which a fresh session accepts twice
+        let warmup = alice_handler.prepare_encrypted_message(&bob_id, "Hi").unwrap();
+        bob_handler.decrypt_message(&warmup).unwrap();
+        
+        let message = alice_handler.prepare_encrypted_message(&bob_id, "See you at 5").unwrap();
+        bob_handler.decrypt_message(&message).unwrap();
+        let message_id = bob_handler.conversation(&alice_id).unwrap().messages()[1].id.clone();
+        
+        let edit = alice_handler.edit_message(&bob_id, &message_id, "See you at 6").unwrap();
+        assert_eq!(bob_handler.decrypt_message(&edit).unwrap(), "See you at 6");
+        
+        for (handler, peer_id) in [(&alice_handler, &bob_id), (&bob_handler, &alice_id)] {
+            let stored = handler.conversation(peer_id).unwrap().get(&message_id).unwrap();
+            assert_eq!(stored.content, "See you at 6");
+            assert_eq!(stored.edit_history, vec!["See you at 5".to_string()]);
+        }
+        
+        let delete = alice_handler.delete_message(&bob_id, &message_id).unwrap();
+        assert_eq!(bob_handler.decrypt_message(&delete).unwrap(), DELETED_TOMBSTONE);
+        let stored = bob_handler.conversation(&alice_id).unwrap().get(&message_id).unwrap();
+        assert_eq!(stored.content, DELETED_TOMBSTONE);
+        assert!(stored.edit_history.is_empty());
+        
+        // Bob cannot revise Alice's message locally
+        assert!(matches!(
+            bob_handler.edit_message(&alice_id, &message_id, "Cancelled"),
+            Err(MessagingError::AuthenticityFailed(_))
+        ));
+    }
+    
+    #[test]
+    fn test_delivery_status_follows_receipts() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let relay = Identity::generate().unwrap().peer_id().clone();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let alice_id = alice_public.peer_id().to_string();
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut alice_handler = MessageHandler::new(alice);
+        let mut bob_handler = MessageHandler::new(bob);
+        alice_handler.register_peer(bob_public).unwrap();
+        bob_handler.register_peer(alice_public).unwrap();
+        
+        let message = alice_handler.prepare_encrypted_message(&bob_id, "Are you there?").unwrap();
+        let message_id = alice_handler.conversation(&bob_id).unwrap().messages()[0].id.clone();
+        assert!(Uuid::parse_str(&message_id).is_ok());
+        assert_eq!(alice_handler.delivery_status(&message_id), Some(&DeliveryStatus::Sent));
+        
+        alice_handler.record_relayed(&message_id, relay.clone());
+        assert_eq!(alice_handler.delivery_status(&message_id), Some(&DeliveryStatus::Relayed { via: relay }));
+        
+        bob_handler.decrypt_message(&message).unwrap();
+        let received_id = bob_handler.conversation(&alice_id).unwrap().messages()[0].id.clone();
+        assert_eq!(received_id, message_id);
+        
+        for (receipt, expected) in [
+            (bob_handler.acknowledge(&alice_id, &received_id).unwrap(), DeliveryStatus::Delivered),
+            (bob_handler.read_receipt(&alice_id, &received_id).unwrap(), DeliveryStatus::Read),
+        ] {
+            let receipt = Message::from_bytes(&receipt.to_bytes().unwrap()).unwrap();
+            alice_handler.receive_receipt(&receipt).unwrap();
+            assert_eq!(alice_handler.delivery_status(&message_id), Some(&expected));
+        }
+        
+        // Receipts are only written for messages received from that peer
+        assert!(matches!(
+            bob_handler.acknowledge(&alice_id, "unknown"),
+            Err(MessagingError::MessageNotFound(_))
+        ));
+        assert!(alice_handler.acknowledge(&bob_id, &message_id).is_err());
+        
+        // Receipts only count from the peer the message was sent to, and only for own messages
+        let ack = DeliveryReceipt::MessageAck { message_id: message_id.clone() };
+        assert!(matches!(
+            alice_handler.handle_receipt("12D3KooWStranger", &ack),
+            Err(MessagingError::MessageNotFound(_))
+        ));
+        assert!(bob_handler.handle_receipt(&alice_id, &ack).is_err());
+        
+        alice_handler.prepare_encrypted_message(&bob_id, "Still there?").unwrap();
+        let failed_id = alice_handler.conversation(&bob_id).unwrap().messages().last().unwrap().id.clone();
+        alice_handler.record_failed(&failed_id, "no peers subscribed".to_string());
+        assert_eq!(
+            alice_handler.delivery_status(&failed_id),
+            Some(&DeliveryStatus::Failed("no peers subscribed".to_string()))
+        );
+    }
+    
+    #[test]
+    fn test_receipts_emit_events() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let carol = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let alice_id = alice_public.peer_id().to_string();
+        let bob_id = bob_public.peer_id().to_string();
+        
+        let mut alice_handler = MessageHandler::new(alice);
+        let mut bob_handler = MessageHandler::new(bob);
+        alice_handler.register_peer(bob_public).unwrap();
+        alice_handler.register_peer(PublicIdentity::from_identity(&carol)).unwrap();
+        bob_handler.register_peer(alice_public).unwrap();
+        
+        let message = alice_handler.prepare_encrypted_message(&bob_id, "Lunch?").unwrap();
+        let message_id = alice_handler.conversation(&bob_id).unwrap().messages()[0].id.clone();
+        assert!(alice_handler.pending_deliveries().contains_key(&message_id));
+        
+        bob_handler.decrypt_message(&message).unwrap();
+        let ack = bob_handler.acknowledge(&alice_id, &message_id).unwrap();
+        
+        // A relay cannot move the receipt to another message or change its time
+        let Message::DeliveryReceipt { from_peer_id, received_at, signature, .. } = ack.clone() else {
+            panic!("Wrong message type");
+        };
+        let forged = [
+            Message::DeliveryReceipt {
+                from_peer_id: from_peer_id.clone(),
+                message_id: Uuid::new_v4().to_string(),
+                received_at,
+                signature: signature.clone(),
+            },
+            Message::DeliveryReceipt {
+                from_peer_id: from_peer_id.clone(),
+                message_id: message_id.clone(),
+                received_at: received_at + chrono::Duration::seconds(1),
+                signature: signature.clone(),
+            },
+            Message::ReadReceipt {
+                from_peer_id,
+                message_id: message_id.clone(),
+                read_at: received_at,
+                signature,
+            },
+        ];
+        for receipt in &forged {
+            assert!(matches!(
+                alice_handler.receive_receipt(receipt),
+                Err(MessagingError::AuthenticityFailed(_))
+            ));
+        }
+        
+        // Carol signs her own receipt, but the message was not sent to her
+        let carols = Message::receipt(&carol, &alice_id, DeliveryReceipt::MessageAck { message_id: message_id.clone() }).unwrap();
+        assert!(matches!(
+            alice_handler.receive_receipt(&carols),
+            Err(MessagingError::MessageNotFound(_))
+        ));
+        let stranger = Message::receipt(
+            &Identity::generate().unwrap(),
+            &alice_id,
+            DeliveryReceipt::MessageAck { message_id: message_id.clone() },
+        )
+        .unwrap();
+        assert!(matches!(
+            alice_handler.receive_receipt(&stranger),
+            Err(MessagingError::PeerNotFound(_))
+        ));
+        assert!(alice_handler.pending_deliveries().contains_key(&message_id));
+        
+        match alice_handler.receive_receipt(&ack).unwrap() {
+            Some(MessagingEvent::MessageDelivered { peer_id, message_id: id, delivered_at }) => {
+                assert_eq!(peer_id, bob_id);
+                assert_eq!(id, message_id);
+                assert_eq!(delivered_at, received_at);
+            }
+            other => panic!("unexpected event {:?}", other),
+        }
+        assert!(alice_handler.pending_deliveries().is_empty());
+        
+        // A repeated receipt changes nothing and reports nothing
+        assert!(alice_handler.receive_receipt(&ack).unwrap().is_none());
+        
+        let read = bob_handler.read_receipt(&alice_id, &message_id).unwrap();
+        assert!(matches!(
+            alice_handler.receive_receipt(&read).unwrap(),
+            Some(MessagingEvent::MessageRead { .. })
+        ));
+        assert!(alice_handler.receive_receipt(&message).is_err());
+    }
+    
+    #[test]
+    fn test_editing_another_users_message_fails_verification() {
+        let alice = Identity::generate().unwrap();
+        let mallory = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let alice_public = PublicIdentity::from_identity(&alice);
+        let mallory_public = PublicIdentity::from_identity(&mallory);
+        let alice_id = alice_public.peer_id().to_string();
+        let mallory_id = mallory_public.peer_id().to_string();
+        let bob_id = PublicIdentity::from_identity(&bob).peer_id().to_string();
+        
+        let mut alice_handler = MessageHandler::new(alice.clone());
+        let mut bob_handler = MessageHandler::new(bob.clone());
+        alice_handler.register_peer(PublicIdentity::from_identity(&bob)).unwrap();
+        bob_handler.register_peer(alice_public).unwrap();
+        bob_handler.register_peer(mallory_public).unwrap();
+        
+        let message = alice_handler.prepare_encrypted_message(&bob_id, "Transfer 10").unwrap();
+        bob_handler.decrypt_message(&message).unwrap();
+        let message_id = bob_handler.conversation(&alice_id).unwrap().messages()[0].id.clone();
+        
+        // Mallory signs an edit of Alice's message and claims Alice wrote it
+        let mut forged = MessageRevision::sign(&mallory, message_id.clone(), RevisionAction::Edit("Transfer 1000".to_string())).unwrap();
+        forged.from_peer_id = alice_id.clone();
+        assert!(matches!(
+            bob_handler.apply_revision(&alice_id, &forged),
+            Err(MessagingError::AuthenticityFailed(_))
+        ));
+        
+        // Signed honestly by Mallory, it still cannot touch Alice's message
+        let honest = MessageRevision::sign(&mallory, message_id.clone(), RevisionAction::Delete).unwrap();
+        assert!(bob_handler.apply_revision(&mallory_id, &honest).is_err());
+        
+        // Tampering with a genuine revision breaks its signature
+        let mut tampered = MessageRevision::sign(&alice, message_id.clone(), RevisionAction::Edit("Transfer 20".to_string())).unwrap();
+        tampered.action = RevisionAction::Edit("Transfer 2000".to_string());
+        tampered.new_content = Some("Transfer 2000".to_string());
+        assert!(matches!(
+            bob_handler.apply_revision(&alice_id, &tampered),
+            Err(MessagingError::AuthenticityFailed(_))
+        ));
+        
+        assert_eq!(bob_handler.conversation(&alice_id).unwrap().get(&message_id).unwrap().content, "Transfer 10");
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_typing_timeout() {
+        let identity = Identity::generate().unwrap();
+        let mut handler = MessageHandler::new(identity);
+        
+        // T=0
+        handler.peer_started_typing("peer1");
+        assert!(handler.poll_typing_timeouts().is_empty());
+        
+        // T=5: still within the timeout
+        tokio::time::advance(Duration::from_secs(5)).await;
+        assert!(handler.poll_typing_timeouts().is_empty());
+        
+        // T=11
+        tokio::time::advance(Duration::from_secs(6)).await;
+        assert_eq!(handler.poll_typing_timeouts(), vec!["peer1".to_string()]);
+        assert!(!handler.is_peer_typing("peer1"));
+        
+        // Fires only once
+        assert!(handler.poll_typing_timeouts().is_empty());
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_typing_indicator_debounce() {
+        let identity = Identity::generate().unwrap();
+        let mut handler = MessageHandler::new(identity);
+        let is_typing = |message: Option<Message>| match message {
+            Some(Message::Typing { is_typing }) => Some(is_typing),
+            None => None,
+            other => panic!("unexpected message {:?}", other),
+        };
+        
+        // Keystrokes within the debounce window send one indicator
+        assert_eq!(is_typing(handler.send_typing_indicator("peer1", true)), Some(true));
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert_eq!(is_typing(handler.send_typing_indicator("peer1", true)), None);
+        
+        // T=4: refreshed once the window has passed
+        tokio::time::advance(Duration::from_secs(2)).await;
+        assert_eq!(is_typing(handler.send_typing_indicator("peer1", true)), Some(true));
+        
+        // T=8: the last keystroke was at T=4, so the stop is not due yet
+        tokio::time::advance(Duration::from_secs(4)).await;
+        assert!(handler.poll_typing_indicators().is_empty());
+        
+        // T=9: five seconds of silence
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let due = handler.poll_typing_indicators();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].0, "peer1");
+        assert!(matches!(due[0].1, Message::Typing { is_typing: false }));
+        assert!(handler.poll_typing_indicators().is_empty());
+        
+        // The automatic stop counts as sent
+        assert_eq!(is_typing(handler.send_typing_indicator("peer1", false)), None);
+        assert_eq!(is_typing(handler.send_typing_indicator("peer1", true)), Some(true));
+        assert_eq!(is_typing(handler.send_typing_indicator("peer1", false)), Some(false));
+        tokio::time::advance(Duration::from_secs(10)).await;
+        assert!(handler.poll_typing_indicators().is_empty());
+    }
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_typing_timeout_task_emits_event() {
+        let identity = Identity::generate().unwrap();
+        let handler = Arc::new(Mutex::new(MessageHandler::new(identity)));
+        let (event_tx, mut event_rx) = mpsc::channel(10);
+        
+        handler.lock().await.peer_started_typing("peer1");
+        let task = spawn_typing_timeout_task(handler.clone(), event_tx);
+        
+        // Time auto-advances while the task sleeps
+        match event_rx.recv().await {
+            Some(MessagingEvent::PeerTyping { peer_id, is_typing }) => {
+                assert_eq!(peer_id, "peer1");
+                assert!(!is_typing);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        
+        task.abort();
+    }
 }
@@ -0,0 +1,134 @@
+//! # Observer Sessions
+//!
+//! Read-only use of an identity, e.g. to review the conversations of a
+//! shared device without being able to write from it. `ObserverSession`
+//! wraps a `MessageHandler` and forwards the reading side of its API; every
+//! method that would produce an outgoing message returns
+//! `MessagingError::ReadOnlySession` instead.
+
+use crate::{Conversation, Message, MessageHandler, MessagingError};
+use otter_identity::PublicIdentity;
+use std::fmt::Write as _;
+
+/// Message handler that can decrypt but not send
+pub struct ObserverSession {
+    handler: MessageHandler,
+}
+
+impl MessageHandler {
+    /// Turn the handler into a read-only observer session
+    pub fn into_observer(self) -> ObserverSession {
+        ObserverSession { handler: self }
+    }
+}
+
+impl ObserverSession {
+    /// Register a peer's public identity, as with `MessageHandler::register_peer`
+    pub fn register_peer(&mut self, public_identity: PublicIdentity) -> Result<(), MessagingError> {
+        self.handler.register_peer(public_identity)
+    }
+    
+    /// Decrypt a received message and record it in its conversation
+    pub fn decrypt_incoming(&mut self, message: &Message) -> Result<String, MessagingError> {
+        self.handler.decrypt_message(message)
+    }
+    
+    /// Conversation with `peer_id`, if any message was exchanged
+    pub fn conversation(&self, peer_id: &str) -> Option<&Conversation> {
+        self.handler.conversation(peer_id)
+    }
+    
+    /// Registered peer IDs
+    pub fn list_peers(&self) -> Vec<String> {
+        self.handler.list_peers()
+    }
+    
+    /// The conversation with `peer_id` as plain text, one `[timestamp] author: text` line per message
+    pub fn export_conversation_plaintext(&self, peer_id: &str) -> Result<String, MessagingError> {
+        let conversation = self
+            .handler
+            .conversation(peer_id)
+            .ok_or_else(|| MessagingError::PeerNotFound(peer_id.to_string()))?;
+        
+        let mut text = String::new();
+        for message in conversation.messages() {
+            let _ = writeln!(
+                text,
+                "[{}] {}: {}",
+                message.timestamp.format("%Y-%m-%d %H:%M:%S UTC"),
+                message.from,
+                message.content
+            );
+        }
+        Ok(text)
+    }
+    
+    /// Always fails: observers cannot send
+    pub fn prepare_encrypted_message(&mut self, _peer_id: &str, _text: &str) -> Result<Message, MessagingError> {
+        Err(MessagingError::ReadOnlySession)
+    }
+    
+    /// Always fails: observers cannot send
+    pub fn reply(&mut self, _peer_id: &str, _reply_to_id: &str, _text: &str) -> Result<Message, MessagingError> {
+        Err(MessagingError::ReadOnlySession)
+    }
+    
+    /// Always fails: observers cannot revise messages
+    pub fn edit_message(&mut self, _peer_id: &str, _message_id: &str, _new_text: &str) -> Result<Message, MessagingError> {
+        Err(MessagingError::ReadOnlySession)
+    }
+    
+    /// Always fails: observers cannot revise messages
+    pub fn delete_message(&mut self, _peer_id: &str, _message_id: &str) -> Result<Message, MessagingError> {
+        Err(MessagingError::ReadOnlySession)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use otter_identity::Identity;
+    
+    #[test]
+    fn test_observer_reads_but_cannot_send() {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let alice_id = alice.peer_id().to_string();
+        let bob_id = bob.peer_id().to_string();
+        let bob_public = PublicIdentity::from_identity(&bob);
+        
+        let mut alice_handler = MessageHandler::new(alice.clone());
+        alice_handler.register_peer(bob_public.clone()).unwrap();
+        let mut observer = MessageHandler::new(bob).into_observer();
+        observer.register_peer(PublicIdentity::from_identity(&alice)).unwrap();
+        assert_eq!(observer.list_peers(), vec![alice_id.clone()]);
+        
+        for text in ["hi bob", "lunch at noon?"] {
+            let message = alice_handler.prepare_encrypted_message(&bob_id, text).unwrap();
+            assert_eq!(observer.decrypt_incoming(&message).unwrap(), text);
+        }
+        
+        let export = observer.export_conversation_plaintext(&alice_id).unwrap();
+        let lines: Vec<&str> = export.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with('[') && lines[0].ends_with(&format!("UTC] {}: hi bob", alice_id)));
+        assert!(lines[1].ends_with(": lunch at noon?"));
+        
+        let first_id = observer.conversation(&alice_id).unwrap().messages()[0].id.clone();
+        let attempts = [
+            observer.prepare_encrypted_message(&alice_id, "hello"),
+            observer.reply(&alice_id, &first_id, "sure"),
+            observer.edit_message(&alice_id, &first_id, "edited"),
+            observer.delete_message(&alice_id, &first_id),
+        ];
+        for attempt in attempts {
+            assert!(matches!(attempt, Err(MessagingError::ReadOnlySession)));
+        }
+        assert_eq!(observer.conversation(&alice_id).unwrap().messages().len(), 2);
+        
+        assert!(matches!(
+            observer.export_conversation_plaintext("unknown"),
+            Err(MessagingError::PeerNotFound(_))
+        ));
+    }
+}
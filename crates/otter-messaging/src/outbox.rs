@@ -0,0 +1,138 @@
+//! # Offline Queue
+//!
+//! Messages for a peer that is not online are held here and retried on every
+//! [`MessageHandler::poll_offline_queue`](crate::MessageHandler::poll_offline_queue).
+//! A message whose peer is still offline after `max_attempts` polls expires:
+//! it leaves the queue, its conversation marks it `FailureReason::Expired`
+//! and the sender gets a single `MessagingEvent::MessageUndeliverable`.
+//!
+//! Expired messages are kept so they can be re-queued with
+//! [`MessageHandler::retry_failed`](crate::MessageHandler::retry_failed).
+
+use crate::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Polls a queued message survives before it expires
+pub const DEFAULT_MAX_DELIVERY_ATTEMPTS: u32 = 5;
+
+/// Why a sent message did not reach its recipient
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FailureReason {
+    /// The peer is offline and the message is waiting in the queue
+    Offline,
+    /// The peer refused the message
+    Rejected,
+    /// The peer stayed offline for every delivery attempt
+    Expired,
+}
+
+/// A message waiting for its peer to come online
+#[derive(Debug, Clone)]
+pub struct QueuedMessage {
+    pub peer_id: String,
+    pub message_id: String,
+    /// The prepared message, sent as-is once the peer is online
+    pub message: Message,
+    /// First 8 bytes of the BLAKE3 hash of the plaintext
+    pub text_hash: [u8; 8],
+    /// Polls that found the peer offline
+    pub attempts: u32,
+}
+
+impl QueuedMessage {
+    /// Queue `message` for `peer_id`, hashing `text` for failure reports
+    pub fn new(peer_id: String, message_id: String, message: Message, text: &str) -> Self {
+        let mut text_hash = [0u8; 8];
+        text_hash.copy_from_slice(&blake3::hash(text.as_bytes()).as_bytes()[..8]);
+        Self {
+            peer_id,
+            message_id,
+            message,
+            text_hash,
+            attempts: 0,
+        }
+    }
+}
+
+/// Messages waiting for offline peers, in queueing order
+#[derive(Debug)]
+pub struct OfflineQueue {
+    queued: Vec<QueuedMessage>,
+    /// Expired messages by message ID, kept for a retry
+    expired: HashMap<String, QueuedMessage>,
+    max_attempts: u32,
+}
+
+impl Default for OfflineQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_DELIVERY_ATTEMPTS)
+    }
+}
+
+impl OfflineQueue {
+    /// Create a queue that expires messages after `max_attempts` polls
+    pub fn new(max_attempts: u32) -> Self {
+        Self {
+            queued: Vec::new(),
+            expired: HashMap::new(),
+            max_attempts: max_attempts.max(1),
+        }
+    }
+    
+    /// Add a message to the back of the queue
+    pub fn push(&mut self, message: QueuedMessage) {
+        self.queued.push(message);
+    }
+    
+    /// Number of queued messages
+    pub fn len(&self) -> usize {
+        self.queued.len()
+    }
+    
+    /// Whether no message is queued
+    pub fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+    }
+    
+    /// Take the messages whose peer is online and count an attempt for the rest
+    ///
+    /// Returns the messages to send and the ones that just expired.
+    pub fn poll(&mut self, is_online: impl Fn(&str) -> bool) -> (Vec<QueuedMessage>, Vec<QueuedMessage>) {
+        let mut ready = Vec::new();
+        let mut expired = Vec::new();
+        for mut message in std::mem::take(&mut self.queued) {
+            if is_online(&message.peer_id) {
+                ready.push(message);
+                continue;
+            }
+            
+            message.attempts += 1;
+            if message.attempts >= self.max_attempts {
+                self.expired.insert(message.message_id.clone(), message.clone());
+                expired.push(message);
+            } else {
+                self.queued.push(message);
+            }
+        }
+        (ready, expired)
+    }
+    
+    /// Move an expired message back into the queue with a fresh attempt count
+    ///
+    /// Returns `false` if `message_id` is not an expired message for `peer_id`.
+    pub fn requeue(&mut self, peer_id: &str, message_id: &str) -> bool {
+        match self.expired.remove(message_id) {
+            Some(mut message) if message.peer_id == peer_id => {
+                message.attempts = 0;
+                self.queued.push(message);
+                true
+            }
+            Some(message) => {
+                self.expired.insert(message_id.to_string(), message);
+                false
+            }
+            None => false,
+        }
+    }
+}
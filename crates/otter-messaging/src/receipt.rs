@@ -0,0 +1,91 @@
+//! # Signed Receipts
+//!
+//! Delivery and read receipts travel outside any encrypted envelope, so each
+//! one is signed with the identity key of the peer sending it. The signature
+//! covers the kind of receipt, both peer IDs, the message ID and the time, so
+//! a relay can neither forge a receipt nor replay one for another message or
+//! another sender.
+
+use crate::MessagingError;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::Signature;
+use otter_identity::{Identity, PublicIdentity};
+use otter_protocol::DeliveryReceipt;
+
+/// Digest of the fields covered by a receipt's signature
+///
+/// Variable-length fields are length-prefixed so that moving bytes between
+/// fields changes the digest.
+pub fn receipt_digest(
+    receipt: &DeliveryReceipt,
+    from_peer_id: &str,
+    to_peer_id: &str,
+    at: &DateTime<Utc>,
+) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(match receipt {
+        DeliveryReceipt::MessageAck { .. } => &[0],
+        DeliveryReceipt::MarkRead { .. } => &[1],
+    });
+    for field in [from_peer_id, to_peer_id, receipt.message_id()] {
+        hasher.update(&(field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    hasher.update(&at.timestamp().to_le_bytes());
+    hasher.update(&at.timestamp_subsec_nanos().to_le_bytes());
+    *hasher.finalize().as_bytes()
+}
+
+/// Sign `receipt` for `to_peer_id`, the author of the message
+pub fn sign_receipt(
+    identity: &Identity,
+    receipt: &DeliveryReceipt,
+    to_peer_id: &str,
+    at: &DateTime<Utc>,
+) -> Result<Vec<u8>, MessagingError> {
+    let digest = receipt_digest(receipt, identity.peer_id().as_str(), to_peer_id, at);
+    Ok(identity.sign(&digest)?.to_bytes().to_vec())
+}
+
+/// Check that `sender` signed `receipt` for `to_peer_id`
+pub fn verify_receipt(
+    sender: &PublicIdentity,
+    receipt: &DeliveryReceipt,
+    to_peer_id: &str,
+    at: &DateTime<Utc>,
+    signature: &[u8],
+) -> Result<(), MessagingError> {
+    let authenticity_failed = || MessagingError::AuthenticityFailed(sender.peer_id().to_string());
+    let bytes: [u8; 64] = signature.try_into().map_err(|_| authenticity_failed())?;
+    let digest = receipt_digest(receipt, sender.peer_id().as_str(), to_peer_id, at);
+    sender
+        .verify(&digest, &Signature::from_bytes(&bytes))
+        .map_err(|_| authenticity_failed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_receipt_signature_is_bound_to_its_fields() {
+        let bob = Identity::generate().unwrap();
+        let bob_public = PublicIdentity::from_identity(&bob);
+        let mallory = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let ack = DeliveryReceipt::MessageAck { message_id: "m1".to_string() };
+        let at = Utc::now();
+        
+        let signature = sign_receipt(&bob, &ack, "alice", &at).unwrap();
+        verify_receipt(&bob_public, &ack, "alice", &at, &signature).unwrap();
+        
+        let read = DeliveryReceipt::MarkRead { message_id: "m1".to_string() };
+        let other = DeliveryReceipt::MessageAck { message_id: "m2".to_string() };
+        let later = at + chrono::Duration::seconds(1);
+        assert!(verify_receipt(&bob_public, &read, "alice", &at, &signature).is_err());
+        assert!(verify_receipt(&bob_public, &other, "alice", &at, &signature).is_err());
+        assert!(verify_receipt(&bob_public, &ack, "carol", &at, &signature).is_err());
+        assert!(verify_receipt(&bob_public, &ack, "alice", &later, &signature).is_err());
+        assert!(verify_receipt(&mallory, &ack, "alice", &at, &signature).is_err());
+        assert!(verify_receipt(&bob_public, &ack, "alice", &at, &signature[..32]).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! # Message Revisions
+//!
+//! Edits and deletions of sent messages. A revision is signed with the
+//! author's identity key and only applies to messages that author sent, so a
+//! peer cannot rewrite someone else's history.
+
+use crate::MessagingError;
+use chrono::{DateTime, Utc};
+use ed25519_dalek::Signature;
+use otter_identity::{Identity, PublicIdentity};
+use serde::{Deserialize, Serialize};
+
+/// Content that replaces a deleted message
+pub const DELETED_TOMBSTONE: &str = "[deleted]";
+
+/// What a revision does to the message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RevisionAction {
+    /// Replace the text
+    Edit(String),
+    /// Replace the text with a tombstone
+    Delete,
+}
+
+/// Signed change to an earlier message
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageRevision {
+    /// Author of the revision, who must also be the author of the message
+    pub from_peer_id: String,
+    /// ID of the revised message
+    pub message_id: String,
+    /// Text after the revision, `None` for deletions
+    pub new_content: Option<String>,
+    pub action: RevisionAction,
+    pub timestamp: DateTime<Utc>,
+    /// Ed25519 signature over the digest
+    pub signature: Vec<u8>,
+}
+
+impl MessageRevision {
+    /// Create a revision of `message_id` signed by `identity`
+    pub fn sign(identity: &Identity, message_id: String, action: RevisionAction) -> Result<Self, MessagingError> {
+        let new_content = match action {
+            RevisionAction::Edit(ref text) => Some(text.clone()),
+            RevisionAction::Delete => None,
+        };
+        let mut revision = Self {
+            from_peer_id: identity.peer_id().to_string(),
+            message_id,
+            new_content,
+            action,
+            timestamp: Utc::now(),
+            signature: Vec::new(),
+        };
+        revision.signature = identity.sign(&revision.digest())?.to_bytes().to_vec();
+        Ok(revision)
+    }
+    
+    /// Digest of the fields covered by the signature
+    ///
+    /// Variable-length fields are length-prefixed so that moving bytes between
+    /// fields changes the digest.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        for field in [&self.from_peer_id, &self.message_id] {
+            hasher.update(&(field.len() as u64).to_le_bytes());
+            hasher.update(field.as_bytes());
+        }
+        match self.action {
+            RevisionAction::Edit(ref text) => hasher
+                .update(&[1])
+                .update(&(text.len() as u64).to_le_bytes())
+                .update(text.as_bytes()),
+            RevisionAction::Delete => hasher.update(&[0]),
+        };
+        hasher.update(&self.timestamp.timestamp().to_le_bytes());
+        hasher.update(&self.timestamp.timestamp_subsec_nanos().to_le_bytes());
+        *hasher.finalize().as_bytes()
+    }
+    
+    /// Check that `author` signed this revision and that it is internally consistent
+    pub fn verify(&self, author: &PublicIdentity) -> Result<(), MessagingError> {
+        let authenticity_failed = || MessagingError::AuthenticityFailed(author.peer_id().to_string());
+        if author.peer_id().as_str() != self.from_peer_id {
+            return Err(authenticity_failed());
+        }
+        
+        // `new_content` is not signed separately, it has to agree with the action
+        let expected_content = match self.action {
+            RevisionAction::Edit(ref text) => Some(text),
+            RevisionAction::Delete => None,
+        };
+        if self.new_content.as_ref() != expected_content {
+            return Err(MessagingError::InvalidFormat("Revision content does not match its action".to_string()));
+        }
+        
+        let bytes: [u8; 64] = self
+            .signature
+            .as_slice()
+            .try_into()
+            .map_err(|_| authenticity_failed())?;
+        author
+            .verify(&self.digest(), &Signature::from_bytes(&bytes))
+            .map_err(|_| authenticity_failed())
+    }
+}
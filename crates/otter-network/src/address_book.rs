@@ -0,0 +1,120 @@
+//! # Address Book
+//!
+//! User annotations for known peers: a label, free-form notes, tags and
+//! whether the peer is pinned to the top of listings. Addresses stay in the
+//! peer cache; the address book only holds what the user wrote.
+
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Annotations for one peer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressBookEntry {
+    pub peer_id: PeerId,
+    pub label: Option<String>,
+    pub notes: Option<String>,
+    pub pinned: bool,
+    pub tags: Vec<String>,
+}
+
+impl AddressBookEntry {
+    /// Create an entry without annotations
+    pub fn new(peer_id: PeerId) -> Self {
+        Self {
+            peer_id,
+            label: None,
+            notes: None,
+            pinned: false,
+            tags: Vec::new(),
+        }
+    }
+}
+
+/// Annotated peers, by peer ID
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerAddressBook {
+    pub entries: HashMap<PeerId, AddressBookEntry>,
+}
+
+impl PeerAddressBook {
+    /// Set the label and notes of `peer_id`, creating its entry if needed
+    ///
+    /// `None` leaves the current value unchanged; an empty string clears it.
+    pub fn annotate(&mut self, peer_id: &PeerId, label: Option<&str>, notes: Option<&str>) -> &AddressBookEntry {
+        let entry = self.entry_mut(peer_id);
+        let set = |field: &mut Option<String>, value: Option<&str>| {
+            if let Some(value) = value {
+                *field = Some(value.to_string()).filter(|v| !v.is_empty());
+            }
+        };
+        set(&mut entry.label, label);
+        set(&mut entry.notes, notes);
+        entry
+    }
+    
+    /// Get the entry of `peer_id`
+    pub fn get(&self, peer_id: &PeerId) -> Option<&AddressBookEntry> {
+        self.entries.get(peer_id)
+    }
+    
+    /// Get the mutable entry of `peer_id`, creating it if needed
+    pub fn entry_mut(&mut self, peer_id: &PeerId) -> &mut AddressBookEntry {
+        self.entries
+            .entry(*peer_id)
+            .or_insert_with(|| AddressBookEntry::new(*peer_id))
+    }
+    
+    /// Remove the entry of `peer_id`
+    pub fn remove(&mut self, peer_id: &PeerId) -> Option<AddressBookEntry> {
+        self.entries.remove(peer_id)
+    }
+    
+    /// All entries, pinned first, then by label and peer ID
+    pub fn sorted_entries(&self) -> Vec<AddressBookEntry> {
+        let mut entries: Vec<AddressBookEntry> = self.entries.values().cloned().collect();
+        entries.sort_by(|a, b| {
+            b.pinned
+                .cmp(&a.pinned)
+                .then_with(|| a.label.is_none().cmp(&b.label.is_none()))
+                .then_with(|| a.label.cmp(&b.label))
+                .then_with(|| a.peer_id.to_base58().cmp(&b.peer_id.to_base58()))
+        });
+        entries
+    }
+    
+    /// Number of annotated peers
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    /// Whether no peer is annotated
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_annotate_keeps_unset_fields() {
+        let peer = PeerId::random();
+        let mut book = PeerAddressBook::default();
+        
+        book.annotate(&peer, Some("Alice"), Some("Met at the meetup"));
+        let entry = book.annotate(&peer, None, Some("Prefers voice calls"));
+        assert_eq!(entry.label.as_deref(), Some("Alice"));
+        assert_eq!(entry.notes.as_deref(), Some("Prefers voice calls"));
+        
+        let entry = book.annotate(&peer, Some(""), None);
+        assert!(entry.label.is_none());
+        
+        let pinned = PeerId::random();
+        book.entry_mut(&pinned).pinned = true;
+        let sorted = book.sorted_entries();
+        assert_eq!(sorted[0].peer_id, pinned);
+        assert_eq!(sorted[1].peer_id, peer);
+    }
+}
@@ -0,0 +1,30 @@
+//! # DHT Bootstrap
+//!
+//! Well-known peers a new node dials to join the Kademlia DHT when mDNS
+//! finds nobody on the local network. Once one of them connects, a bootstrap
+//! query fills the routing table with the peers closest to this node.
+
+use libp2p::{Multiaddr, PeerId};
+
+/// Peers of the public Otter network, as `(peer ID, address)` strings
+///
+/// Empty until stable bootstrap nodes are deployed.
+const OTTER_NETWORK_BOOTSTRAP: &[(&str, &str)] = &[];
+
+/// Bootstrap peer lists
+pub struct BootstrapPeers;
+
+impl BootstrapPeers {
+    /// Bootstrap peers of the public Otter network
+    pub fn default_otter_network() -> Vec<(PeerId, Multiaddr)> {
+        OTTER_NETWORK_BOOTSTRAP
+            .iter()
+            .map(|(peer_id, address)| {
+                (
+                    peer_id.parse().expect("bootstrap peer IDs are valid"),
+                    address.parse().expect("bootstrap addresses are valid"),
+                )
+            })
+            .collect()
+    }
+}
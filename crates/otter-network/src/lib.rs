@@ -4,31 +4,87 @@
 //!
 //! This crate provides:
 //! - libp2p-based peer discovery (mDNS and Kademlia DHT)
+//! - Peer lookup by ID through the DHT, reporting the addresses found
 //! - Connection management
 //! - Custom chat protocol
 //! - Peer information and routing
+//! - Per-peer bandwidth accounting
 //! - WebRTC transport with ICE negotiation for NAT traversal
+//! - Service advertisement via Kademlia provider records
+//! - Dead peer detection for peers that go silent
+//! - Transparent fragmentation of messages over the gossipsub size limit
+//! - Priority queueing so call signaling preempts bulk traffic
+//! - Pluggable validation of received message content
+//! - Gossipsub mesh presets for small and large networks
+//! - Mesh topology and message propagation introspection
+//! - An address book of user annotations for known peers
+//! - DHT bootstrap through well-known peers when mDNS finds nobody
+//! - Connection and traffic counters for Prometheus scraping
+//! - Multi-signed reputation anchors stored in the DHT
+//! - Mesh slots reserved for trusted peers
+//! - Connection limits, and temporary bans of peers that misbehave
 
+pub mod address_book;
+pub mod bootstrap;
+pub mod liveness;
+pub mod mesh;
+pub mod metrics;
+pub mod priority;
+pub mod reputation;
+pub mod scoring;
+pub mod topology;
+pub mod validation;
 pub mod webrtc;
 
+pub use address_book::{AddressBookEntry, PeerAddressBook};
+pub use bootstrap::BootstrapPeers;
+pub use liveness::PeerLivenessTracker;
+pub use mesh::{GossipsubParams, RESERVED_HIGH_PRIORITY_SLOTS};
+pub use metrics::MetricsExporter;
+pub use priority::{ConnectionPriority, MessagePriority, QueueBudget};
+pub use reputation::{ReputationAnchor, ReputationManager};
+pub use scoring::{ConnectionLimits, PeerScore, PeerScoreConfig};
+pub use topology::PropagationHop;
+pub use validation::{AcceptAll, DefaultValidator, MessageValidator, ValidationDecision};
+
 use futures::{prelude::*, select};
 use libp2p::{
-    core::transport::upgrade,
-    core::muxing::StreamMuxerBox,
+    core::{
+        muxing::StreamMuxerBox,
+        transport::{timeout::TransportTimeoutError, upgrade, ListenerId, TransportError},
+    },
     gossipsub, identify, kad,
     mdns,
     noise,
-    swarm::{NetworkBehaviour, SwarmEvent},
+    ping,
+    swarm::{dial_opts::DialOpts, ConnectionId, DialError, ListenError, NetworkBehaviour, SwarmEvent},
     tcp, yamux, PeerId, Swarm, Multiaddr, Transport,
 };
+use otter_protocol::{fragment::{DEFAULT_REASSEMBLY_TIMEOUT, FRAGMENT_OVERHEAD}, Fragment, Fragmenter, Reassembler, ReassemblyLimits};
+use priority::{PriorityMessage, SendQueue};
+use topology::PropagationTracer;
+use either::Either;
 use std::{
-    collections::HashSet,
-    time::Duration,
+    collections::{HashMap, HashSet},
+    io,
+    sync::Arc,
+    time::{Duration, Instant},
 };
 use thiserror::Error as ThisError;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 use tracing::{debug, error, info, warn};
-use void::Void;
+
+/// How often cumulative peer statistics are emitted
+const STATS_SNAPSHOT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often the send queue depth is reported
+const QUEUE_DEPTH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Largest payload published as a single gossipsub message
+///
+/// Gossipsub's transmit limit is 64 KiB including its own framing; larger
+/// payloads are split into fragments.
+const MAX_PUBLISH_SIZE: usize = 60 * 1024;
 
 #[derive(ThisError, Debug)]
 pub enum NetworkError {
@@ -42,6 +98,50 @@ pub enum NetworkError {
     SendError(String),
     #[error("Transport error: {0}")]
     TransportError(String),
+    #[error("Network is shutting down")]
+    ShuttingDown,
+    #[error("DHT error: {0}")]
+    DhtError(String),
+    #[error("Listener mode is active, messages are not published")]
+    ListenerModeActive,
+    #[error("Invalid reputation anchor: {0}")]
+    InvalidAnchor(String),
+}
+
+/// Cumulative traffic statistics for a single peer
+#[derive(Debug, Clone)]
+pub struct PeerStats {
+    /// Peer these statistics belong to
+    pub peer_id: PeerId,
+    
+    /// Payload bytes published with this peer as the recipient
+    pub bytes_sent: u64,
+    
+    /// Payload bytes received from this peer
+    pub bytes_received: u64,
+    
+    /// Messages published with this peer as the recipient
+    pub messages_sent: u64,
+    
+    /// Messages received from this peer
+    pub messages_received: u64,
+    
+    /// When the current connection was established
+    pub connected_since: Instant,
+}
+
+impl PeerStats {
+    /// Create empty statistics for a peer
+    pub fn new(peer_id: PeerId) -> Self {
+        Self {
+            peer_id,
+            bytes_sent: 0,
+            bytes_received: 0,
+            messages_sent: 0,
+            messages_received: 0,
+            connected_since: Instant::now(),
+        }
+    }
 }
 
 /// Events from the network layer
@@ -59,17 +159,75 @@ pub enum NetworkEvent {
     MessageReceived { from: PeerId, data: Vec<u8> },
     /// Network listening started
     ListeningOn { address: String },
+    /// Graceful shutdown finished draining and the swarm is about to close
+    ShuttingDown,
+    /// Periodic snapshot of cumulative per-peer statistics
+    StatsSnapshot(HashMap<PeerId, PeerStats>),
+    /// A connected peer has not been heard from within the liveness timeout
+    PeerUnresponsive { peer_id: PeerId },
+    /// Messages waiting in the send queue, per priority
+    QueueDepth { real_time: usize, interactive: usize, bulk: usize },
+    /// A received message was refused by the message validator
+    MessageRejected { from: PeerId, reason: String },
+    /// The first DHT bootstrap finished with this many peers in the routing table
+    BootstrapCompleted { known_peers: usize },
+    /// Listener mode was switched on or off
+    ListenerModeChanged(bool),
+    /// A peer's mesh priority was changed
+    PriorityChanged { peer_id: PeerId, priority: ConnectionPriority },
+    /// A `FindPeer` lookup found addresses for a peer
+    PeerFound { peer_id: PeerId, addresses: Vec<Multiaddr> },
+    /// A peer was banned, by command or for reaching the penalty threshold
+    PeerBanned { peer_id: PeerId, duration: Duration },
 }
 
 /// Commands to the network layer
 #[derive(Debug)]
 pub enum NetworkCommand {
     /// Send a message to a specific peer
-    SendMessage { to: PeerId, data: Vec<u8> },
+    SendMessage { to: PeerId, data: Vec<u8>, priority: MessagePriority },
     /// Request list of connected peers
     ListPeers { response: mpsc::Sender<Vec<PeerId>> },
     /// Dial a specific peer
     DialPeer { peer_id: PeerId, address: String },
+    /// Dial a peer by ID, looking up its addresses in the DHT if none are known
+    ///
+    /// `response`, if given, gets the peer's addresses, or none if it could not be found.
+    FindPeer { peer_id: PeerId, response: Option<mpsc::Sender<Vec<Multiaddr>>> },
+    /// Stop accepting new messages, drain in-flight publishes and close the swarm
+    Shutdown { grace_period_ms: u64 },
+    /// Request cumulative per-peer statistics
+    GetPeerStats { response: oneshot::Sender<HashMap<PeerId, PeerStats>> },
+    /// Advertise in the DHT that this peer provides a service (e.g. "otter-turn")
+    Advertise { service_key: String },
+    /// Look up peers that advertise a service
+    FindService { key: String, response: oneshot::Sender<Vec<PeerId>> },
+    /// Request the gossipsub mesh peers of every subscribed topic
+    GetMeshTopology { response: oneshot::Sender<HashMap<String, Vec<PeerId>>> },
+    /// Request the address book entries, pinned first
+    GetAddressBook { response: oneshot::Sender<Vec<AddressBookEntry>> },
+    /// Store a record in the DHT, replicated to at least one other peer
+    PutRecord { key: String, value: Vec<u8>, response: oneshot::Sender<Result<(), NetworkError>> },
+    /// Look up every value stored in the DHT under a key
+    GetRecord { key: String, response: oneshot::Sender<Result<Vec<Vec<u8>>, NetworkError>> },
+    /// Change how strongly a peer is kept in the gossipsub mesh
+    SetPeerPriority { peer_id: PeerId, priority: ConnectionPriority },
+    /// Disconnect a peer and refuse its connections for `duration`
+    BanPeer { peer_id: PeerId, duration: Duration },
+    /// Count a message from a peer that failed to decrypt against its score
+    ReportDecryptionError { peer_id: PeerId },
+}
+
+/// An in-flight provider lookup
+struct ProviderQuery {
+    response: oneshot::Sender<Vec<PeerId>>,
+    providers: HashSet<PeerId>,
+}
+
+/// An in-flight record lookup
+struct RecordQuery {
+    response: oneshot::Sender<Result<Vec<Vec<u8>>, NetworkError>>,
+    records: Vec<Vec<u8>>,
 }
 
 /// Network behavior combining multiple protocols
@@ -79,22 +237,99 @@ pub struct OtterBehaviour {
     mdns: mdns::tokio::Behaviour,
     kad: kad::Behaviour<kad::store::MemoryStore>,
     identify: identify::Behaviour,
+    ping: ping::Behaviour,
 }
 
 /// The main network manager
 pub struct Network {
     swarm: Swarm<OtterBehaviour>,
+    /// Identity key, also used to sign reputation anchors
+    local_key: libp2p::identity::Keypair,
     event_tx: mpsc::Sender<NetworkEvent>,
     command_rx: mpsc::Receiver<NetworkCommand>,
     connected_peers: HashSet<PeerId>,
     gossipsub_topic: gossipsub::IdentTopic,
+    listeners: Vec<ListenerId>,
+    shutting_down: bool,
+    peer_stats: HashMap<PeerId, PeerStats>,
+    provider_queries: HashMap<kad::QueryId, ProviderQuery>,
+    record_puts: HashMap<kad::QueryId, oneshot::Sender<Result<(), NetworkError>>>,
+    record_queries: HashMap<kad::QueryId, RecordQuery>,
+    peer_lookups: HashMap<kad::QueryId, PeerId>,
+    peer_finds: HashMap<PeerId, Vec<mpsc::Sender<Vec<Multiaddr>>>>,
+    reassembler: Reassembler,
+    liveness: PeerLivenessTracker,
+    send_queue: SendQueue,
+    queue_budget: QueueBudget,
+    validator: Box<dyn MessageValidator>,
+    gossipsub_config: gossipsub::Config,
+    tracer: PropagationTracer,
+    address_book: PeerAddressBook,
+    /// Dialed when the event loop starts; empty unless configured
+    bootstrap_peers: Vec<(PeerId, Multiaddr)>,
+    /// Bootstrap peers dialed but not yet connected
+    pending_bootstrap: HashSet<PeerId>,
+    bootstrap_query: Option<kad::QueryId>,
+    bootstrapped: bool,
+    /// Receive and discover, but never publish
+    listener_mode: bool,
+    /// Gossipsub was built without message signing
+    anonymous: bool,
+    metrics: Option<Arc<MetricsExporter>>,
+    /// Peers whose priority differs from `ConnectionPriority::Low`
+    peer_priorities: HashMap<PeerId, ConnectionPriority>,
+    connection_limits: ConnectionLimits,
+    /// Whether each open connection was dialed by this node
+    connection_dialers: HashMap<ConnectionId, bool>,
+    /// Connections closed on arrival for exceeding the limits or a ban
+    rejected_connections: HashSet<ConnectionId>,
+    score_config: PeerScoreConfig,
+    peer_scores: HashMap<PeerId, PeerScore>,
+    /// Banned peers and when their ban expires
+    banned_peers: HashMap<PeerId, Instant>,
 }
 
 impl Network {
     /// Create a new network instance
+    ///
+    /// Every received message must pass `validator` before it is delivered.
     pub fn new(
         event_tx: mpsc::Sender<NetworkEvent>,
         command_rx: mpsc::Receiver<NetworkCommand>,
+        validator: Box<dyn MessageValidator>,
+    ) -> Result<Self, NetworkError> {
+        Self::with_gossipsub_params(event_tx, command_rx, validator, GossipsubParams::default())
+    }
+    
+    /// Create a new network instance with custom gossipsub mesh parameters
+    pub fn with_gossipsub_params(
+        event_tx: mpsc::Sender<NetworkEvent>,
+        command_rx: mpsc::Receiver<NetworkCommand>,
+        validator: Box<dyn MessageValidator>,
+        params: GossipsubParams,
+    ) -> Result<Self, NetworkError> {
+        Self::build(event_tx, command_rx, validator, params, false)
+    }
+    
+    /// Create a network instance in listener mode
+    ///
+    /// The node joins the mesh and the DHT but never publishes, so gossipsub
+    /// is set up without signing and accepts unsigned messages from older
+    /// peers. Listener mode cannot be switched off on such a network.
+    pub fn new_listener(
+        event_tx: mpsc::Sender<NetworkEvent>,
+        command_rx: mpsc::Receiver<NetworkCommand>,
+        validator: Box<dyn MessageValidator>,
+    ) -> Result<Self, NetworkError> {
+        Self::build(event_tx, command_rx, validator, GossipsubParams::default(), true)
+    }
+    
+    fn build(
+        event_tx: mpsc::Sender<NetworkEvent>,
+        command_rx: mpsc::Receiver<NetworkCommand>,
+        validator: Box<dyn MessageValidator>,
+        params: GossipsubParams,
+        listener_mode: bool,
     ) -> Result<Self, NetworkError> {
         // Generate a new keypair for this peer
         let local_key = libp2p::identity::Keypair::generate_ed25519();
@@ -108,20 +343,24 @@ impl Network {
             .authenticate(noise::Config::new(&local_key).unwrap())
             .multiplex(yamux::Config::default())
             .timeout(Duration::from_secs(20))
+            .map_err(classify_transport_error)
+            .map(|(peer_id, muxer), _| (peer_id, StreamMuxerBox::new(muxer)))
             .boxed();
         
         // Configure Gossipsub
-        let gossipsub_config = gossipsub::ConfigBuilder::default()
-            .heartbeat_interval(Duration::from_secs(10))
-            .validation_mode(gossipsub::ValidationMode::Strict)
-            .build()
-            .map_err(|e| NetworkError::InitializationError(e.to_string()))?;
+        let (gossipsub_config, authenticity) = if listener_mode {
+            (params.to_listener_config(), gossipsub::MessageAuthenticity::Anonymous)
+        } else {
+            (params.to_config(), gossipsub::MessageAuthenticity::Signed(local_key.clone()))
+        };
+        let gossipsub_config = gossipsub_config.map_err(|e| NetworkError::InitializationError(e.to_string()))?;
         
-        let gossipsub = gossipsub::Behaviour::new(
-            gossipsub::MessageAuthenticity::Signed(local_key.clone()),
-            gossipsub_config,
-        )
-        .map_err(|e| NetworkError::InitializationError(e.to_string()))?;
+        let mut gossipsub = gossipsub::Behaviour::new(authenticity, gossipsub_config.clone())
+            .map_err(|e| NetworkError::InitializationError(e.to_string()))?;
+        let (score_params, score_thresholds) = mesh::peer_score_config();
+        gossipsub
+            .with_peer_score(score_params, score_thresholds)
+            .map_err(NetworkError::InitializationError)?;
         
         // Create mDNS for local peer discovery
         let mdns = mdns::tokio::Behaviour::new(
@@ -132,7 +371,11 @@ impl Network {
         
         // Create Kademlia DHT
         let store = kad::store::MemoryStore::new(local_peer_id);
-        let kad = kad::Behaviour::new(local_peer_id, store);
+        let mut kad = kad::Behaviour::new(local_peer_id, store);
+        
+        // Answer DHT queries even without a confirmed external address, so
+        // peers on the same LAN can find each other's provider records
+        kad.set_mode(Some(kad::Mode::Server));
         
         // Create identify protocol
         let identify = identify::Behaviour::new(identify::Config::new(
@@ -140,12 +383,16 @@ impl Network {
             local_key.public(),
         ));
         
+        // Periodic pings keep idle peers from looking dead to the liveness tracker
+        let ping = ping::Behaviour::new(ping::Config::new());
+        
         // Combine behaviors
         let behaviour = OtterBehaviour {
             gossipsub,
             mdns,
             kad,
             identify,
+            ping,
         };
         
         // Create swarm with custom config to prevent idle disconnections
@@ -161,22 +408,199 @@ impl Network {
         
         Ok(Self {
             swarm,
+            local_key,
             event_tx,
             command_rx,
             connected_peers: HashSet::new(),
             gossipsub_topic,
+            listeners: Vec::new(),
+            shutting_down: false,
+            peer_stats: HashMap::new(),
+            provider_queries: HashMap::new(),
+            record_puts: HashMap::new(),
+            record_queries: HashMap::new(),
+            peer_lookups: HashMap::new(),
+            peer_finds: HashMap::new(),
+            reassembler: Reassembler::with_limits(DEFAULT_REASSEMBLY_TIMEOUT, ReassemblyLimits {
+                max_message_bytes: validation::MAX_MESSAGE_SIZE,
+                max_pending_bytes_per_source: 4 * validation::MAX_MESSAGE_SIZE,
+                ..ReassemblyLimits::default()
+            }),
+            liveness: PeerLivenessTracker::default(),
+            send_queue: SendQueue::new(),
+            queue_budget: QueueBudget::default(),
+            validator,
+            gossipsub_config,
+            tracer: PropagationTracer::default(),
+            address_book: PeerAddressBook::default(),
+            bootstrap_peers: Vec::new(),
+            pending_bootstrap: HashSet::new(),
+            bootstrap_query: None,
+            bootstrapped: false,
+            listener_mode,
+            anonymous: listener_mode,
+            metrics: None,
+            peer_priorities: HashMap::new(),
+            connection_limits: ConnectionLimits::default(),
+            connection_dialers: HashMap::new(),
+            rejected_connections: HashSet::new(),
+            score_config: PeerScoreConfig::default(),
+            peer_scores: HashMap::new(),
+            banned_peers: HashMap::new(),
         })
     }
     
+    /// Set the peers dialed at startup to join the DHT
+    ///
+    /// None are dialed by default; pass [`BootstrapPeers::default_otter_network`]
+    /// to join the public network.
+    pub fn with_bootstrap_peers(mut self, peers: Vec<(PeerId, Multiaddr)>) -> Self {
+        self.bootstrap_peers = peers;
+        self
+    }
+    
+    /// Replace the default connection limits
+    pub fn with_connection_limits(mut self, limits: ConnectionLimits) -> Self {
+        self.connection_limits = limits;
+        self
+    }
+    
+    /// Replace the default penalties and ban threshold
+    pub fn with_peer_scoring(mut self, config: PeerScoreConfig) -> Self {
+        self.score_config = config;
+        self
+    }
+    
+    /// Misbehaviour observed from `peer_id` since it was last banned
+    pub fn peer_score(&self, peer_id: &PeerId) -> Option<&PeerScore> {
+        self.peer_scores.get(peer_id)
+    }
+    
+    /// Whether `peer_id` is currently banned
+    pub fn is_banned(&self, peer_id: &PeerId) -> bool {
+        self.banned_peers.get(peer_id).is_some_and(|until| *until > Instant::now())
+    }
+    
+    /// Disconnect `peer_id` and refuse its connections for `duration`
+    pub fn ban_peer(&mut self, peer_id: PeerId, duration: Duration) {
+        warn!("Banning peer {} for {:?}", peer_id, duration);
+        self.banned_peers.insert(peer_id, Instant::now() + duration);
+        self.peer_scores.remove(&peer_id);
+        if self.connected_peers.contains(&peer_id) {
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+        let _ = self.event_tx.try_send(NetworkEvent::PeerBanned { peer_id, duration });
+    }
+    
+    /// Record misbehaviour of `peer_id`, banning it once the threshold is reached
+    fn penalize(&mut self, peer_id: PeerId, offence: impl FnOnce(&mut PeerScore)) {
+        let score = self.peer_scores.entry(peer_id).or_default();
+        offence(score);
+        
+        let penalty = score.penalty(&self.score_config);
+        debug!("Penalty of {} is now {}", peer_id, penalty);
+        if penalty >= self.score_config.ban_threshold {
+            self.ban_peer(peer_id, self.score_config.ban_duration);
+        }
+    }
+    
+    /// Drop expired bans and the scores of peers no longer connected
+    fn prune_peer_scores(&mut self) {
+        let now = Instant::now();
+        self.banned_peers.retain(|_, until| *until > now);
+        let swarm = &self.swarm;
+        self.peer_scores.retain(|peer_id, _| swarm.is_connected(peer_id));
+    }
+    
+    /// Why a connection to `peer_id` must be refused, if it must
+    fn admission_denied(&mut self, peer_id: &PeerId, dialer: bool) -> Option<&'static str> {
+        if self.is_banned(peer_id) {
+            return Some("peer is banned");
+        }
+        self.banned_peers.remove(peer_id);
+        
+        let outbound = self.connection_dialers.values().filter(|dialer| **dialer).count();
+        let inbound = self.connection_dialers.len() - outbound;
+        self.connection_limits.check(
+            !self.connected_peers.contains(peer_id),
+            self.connected_peers.len(),
+            inbound,
+            outbound,
+            dialer,
+        )
+    }
+    
+    /// Switch listener mode on or off
+    ///
+    /// In listener mode the node still receives messages and takes part in
+    /// discovery, but `SendMessage` fails with `ListenerModeActive` and
+    /// nothing is published. A network created with `new_listener` stays in
+    /// listener mode.
+    pub fn set_listener_mode(&mut self, enabled: bool) {
+        if !enabled && self.anonymous {
+            warn!("Network was created as a listener and cannot publish; staying in listener mode");
+            return;
+        }
+        if self.listener_mode == enabled {
+            return;
+        }
+        
+        self.listener_mode = enabled;
+        info!("Listener mode {}", if enabled { "enabled" } else { "disabled" });
+        let _ = self.event_tx.try_send(NetworkEvent::ListenerModeChanged(enabled));
+    }
+    
+    /// Whether the node only listens
+    pub fn is_listener_mode(&self) -> bool {
+        self.listener_mode
+    }
+    
+    /// Set how strongly `peer_id` is kept in the gossipsub mesh
+    ///
+    /// `High` peers that are not connected are dialed, so they become
+    /// outbound mesh peers.
+    pub fn set_peer_priority(&mut self, peer_id: &PeerId, priority: ConnectionPriority) {
+        if self.peer_priority(peer_id) == priority {
+            return;
+        }
+        
+        if priority == ConnectionPriority::Low {
+            self.peer_priorities.remove(peer_id);
+        } else {
+            self.peer_priorities.insert(*peer_id, priority);
+        }
+        self.apply_peer_priority(peer_id);
+        if priority == ConnectionPriority::High && !self.connected_peers.contains(peer_id) {
+            if let Err(e) = self.swarm.dial(*peer_id) {
+                debug!("Cannot dial high-priority peer {}: {}", peer_id, e);
+            }
+        }
+        
+        info!("Priority of {} set to {:?}", peer_id, priority);
+        let _ = self.event_tx.try_send(NetworkEvent::PriorityChanged { peer_id: *peer_id, priority });
+    }
+    
+    /// How strongly `peer_id` is kept in the gossipsub mesh
+    pub fn peer_priority(&self, peer_id: &PeerId) -> ConnectionPriority {
+        self.peer_priorities.get(peer_id).copied().unwrap_or_default()
+    }
+    
+    /// Hand the peer's priority to gossipsub as its application score
+    fn apply_peer_priority(&mut self, peer_id: &PeerId) {
+        let score = self.peer_priority(peer_id).application_score();
+        self.swarm.behaviour_mut().gossipsub.set_application_score(peer_id, score);
+    }
+    
     /// Start listening on the given address
     pub fn listen(&mut self, addr: &str) -> Result<(), NetworkError> {
         let addr: Multiaddr = addr
             .parse()
             .map_err(|e| NetworkError::ListenError(format!("Invalid address: {}", e)))?;
         
-        self.swarm
+        let listener_id = self.swarm
             .listen_on(addr)
             .map_err(|e| NetworkError::ListenError(e.to_string()))?;
+        self.listeners.push(listener_id);
         
         // Subscribe to gossipsub topic
         self.swarm
@@ -188,10 +612,300 @@ impl Network {
         Ok(())
     }
     
+    /// Get the local peer ID
+    pub fn local_peer_id(&self) -> PeerId {
+        *self.swarm.local_peer_id()
+    }
+    
+    /// Reputation manager signing with this node's identity key
+    ///
+    /// `command_tx` must be the command channel of this network.
+    pub fn reputation_manager(
+        &self,
+        command_tx: mpsc::Sender<NetworkCommand>,
+        required_signatures: usize,
+    ) -> ReputationManager {
+        ReputationManager::new(self.local_key.clone(), command_tx, required_signatures)
+    }
+    
+    /// Set the label and notes of a peer in the address book
+    ///
+    /// `None` leaves the current value unchanged; an empty string clears it.
+    pub fn annotate_peer(&mut self, peer_id: &PeerId, label: Option<&str>, notes: Option<&str>) {
+        self.address_book.annotate(peer_id, label, notes);
+    }
+    
+    /// Replace the address book, e.g. with one loaded from storage
+    pub fn set_address_book(&mut self, address_book: PeerAddressBook) {
+        self.address_book = address_book;
+    }
+    
+    /// Get the address book
+    pub fn address_book(&self) -> &PeerAddressBook {
+        &self.address_book
+    }
+    
+    /// Set how long a connected peer may stay silent before it is reported unresponsive
+    pub fn set_liveness_timeout(&mut self, timeout: Duration) {
+        self.liveness.set_timeout(timeout);
+    }
+    
+    /// Count connections and traffic in `exporter` from now on
+    pub fn set_metrics_exporter(&mut self, exporter: Arc<MetricsExporter>) {
+        self.metrics = Some(exporter);
+    }
+    
+    /// Set how many messages of each priority are published per event loop iteration
+    pub fn set_queue_budget(&mut self, budget: QueueBudget) {
+        self.queue_budget = budget;
+    }
+    
+    /// Gossipsub configuration the network was built with
+    pub fn gossipsub_config(&self) -> &gossipsub::Config {
+        &self.gossipsub_config
+    }
+    
+    /// Mesh peers of every subscribed topic, keyed by topic
+    pub fn mesh_peers(&self) -> HashMap<String, Vec<PeerId>> {
+        let gossipsub = &self.swarm.behaviour().gossipsub;
+        gossipsub
+            .topics()
+            .map(|topic| (topic.to_string(), gossipsub.mesh_peers(topic).copied().collect()))
+            .collect()
+    }
+    
+    /// Hops of a recently received message, by gossipsub message ID
+    pub fn propagation_trace(&self, message_id: &[u8]) -> Vec<PropagationHop> {
+        self.tracer.trace(message_id)
+    }
+    
+    /// Get cumulative per-peer statistics
+    pub fn peer_stats(&self) -> HashMap<PeerId, PeerStats> {
+        self.peer_stats.clone()
+    }
+    
+    /// Advertise that this peer provides the service identified by `key`
+    pub fn start_providing(&mut self, key: String) -> Result<(), NetworkError> {
+        self.swarm
+            .behaviour_mut()
+            .kad
+            .start_providing(kad::RecordKey::new(&key))
+            .map_err(|e| NetworkError::DhtError(e.to_string()))?;
+        
+        info!("Advertising service: {}", key);
+        Ok(())
+    }
+    
+    /// Look up providers of `key` in the DHT
+    ///
+    /// The providers found are sent to `response` once the query finishes.
+    pub fn find_providers(
+        &mut self,
+        key: String,
+        response: oneshot::Sender<Vec<PeerId>>,
+    ) -> Result<(), NetworkError> {
+        let query_id = self
+            .swarm
+            .behaviour_mut()
+            .kad
+            .get_providers(kad::RecordKey::new(&key));
+        
+        self.provider_queries.insert(query_id, ProviderQuery {
+            response,
+            providers: HashSet::new(),
+        });
+        Ok(())
+    }
+    
+    /// Store `value` under `key` in the DHT
+    ///
+    /// The record is kept locally and `response` gets the outcome of
+    /// replicating it once the query finishes.
+    pub fn put_record(
+        &mut self,
+        key: String,
+        value: Vec<u8>,
+        response: oneshot::Sender<Result<(), NetworkError>>,
+    ) -> Result<(), NetworkError> {
+        let record = kad::Record::new(kad::RecordKey::new(&key), value);
+        let query_id = self
+            .swarm
+            .behaviour_mut()
+            .kad
+            .put_record(record, kad::Quorum::One)
+            .map_err(|e| NetworkError::DhtError(e.to_string()))?;
+        
+        self.record_puts.insert(query_id, response);
+        Ok(())
+    }
+    
+    /// Look up the values stored under `key` in the DHT
+    ///
+    /// The values found are sent to `response` once the query finishes.
+    pub fn get_record(
+        &mut self,
+        key: String,
+        response: oneshot::Sender<Result<Vec<Vec<u8>>, NetworkError>>,
+    ) -> Result<(), NetworkError> {
+        let query_id = self.swarm.behaviour_mut().kad.get_record(kad::RecordKey::new(&key));
+        
+        self.record_queries.insert(query_id, RecordQuery {
+            response,
+            records: Vec::new(),
+        });
+        Ok(())
+    }
+    
+    /// Dial a peer using the addresses mDNS or the DHT already know
+    ///
+    /// If none are known yet, the peer is looked up in the DHT and dialed
+    /// once the lookup finds it. `response` gets the addresses from the
+    /// routing table, or the dialed address once the peer connects.
+    pub fn find_peer(
+        &mut self,
+        peer_id: PeerId,
+        response: Option<mpsc::Sender<Vec<Multiaddr>>>,
+    ) -> Result<(), NetworkError> {
+        self.peer_finds.entry(peer_id).or_default().extend(response);
+        
+        let known = self.routing_table_addresses(&peer_id);
+        let connected = self.connected_peers.contains(&peer_id);
+        if !known.is_empty() || connected {
+            self.peer_found(peer_id, known);
+        }
+        if connected {
+            return Ok(());
+        }
+        
+        match self.swarm.dial(peer_id) {
+            Ok(()) => Ok(()),
+            Err(libp2p::swarm::DialError::NoAddresses) => {
+                debug!("No known addresses for {}, querying DHT", peer_id);
+                let query_id = self.swarm.behaviour_mut().kad.get_closest_peers(peer_id);
+                self.peer_lookups.insert(query_id, peer_id);
+                Ok(())
+            }
+            Err(e) => {
+                self.peer_found(peer_id, Vec::new());
+                Err(NetworkError::TransportError(e.to_string()))
+            }
+        }
+    }
+    
+    /// Addresses of `peer_id` in the Kademlia routing table
+    fn routing_table_addresses(&mut self, peer_id: &PeerId) -> Vec<Multiaddr> {
+        let Some(bucket) = self.swarm.behaviour_mut().kad.kbucket(*peer_id) else {
+            return Vec::new();
+        };
+        let addresses = bucket
+            .iter()
+            .find(|entry| entry.node.key.preimage() == peer_id)
+            .map(|entry| entry.node.value.iter().cloned().collect())
+            .unwrap_or_default();
+        addresses
+    }
+    
+    /// Answer the pending `FindPeer` requests for `peer_id`
+    ///
+    /// `NetworkEvent::PeerFound` is only emitted if there are addresses.
+    fn peer_found(&mut self, peer_id: PeerId, addresses: Vec<Multiaddr>) {
+        for response in self.peer_finds.remove(&peer_id).unwrap_or_default() {
+            let _ = response.try_send(addresses.clone());
+        }
+        if !addresses.is_empty() {
+            debug!("Found {} addresses for {}", addresses.len(), peer_id);
+            let _ = self.event_tx.try_send(NetworkEvent::PeerFound { peer_id, addresses });
+        }
+    }
+    
+    /// Dial bootstrap peers and add them to the DHT routing table
+    ///
+    /// A Kademlia bootstrap query runs once the first of them connects. Fails
+    /// only if none of the peers could be dialed.
+    pub fn add_bootstrap_peers(&mut self, peers: &[(PeerId, Multiaddr)]) -> Result<(), NetworkError> {
+        let mut last_error = None;
+        for (peer_id, address) in peers {
+            self.swarm.behaviour_mut().kad.add_address(peer_id, address.clone());
+            
+            let opts = DialOpts::peer_id(*peer_id).addresses(vec![address.clone()]).build();
+            match self.swarm.dial(opts) {
+                Ok(()) => {
+                    debug!("Dialing bootstrap peer {} at {}", peer_id, address);
+                    self.pending_bootstrap.insert(*peer_id);
+                }
+                Err(e) => {
+                    debug!("Failed to dial bootstrap peer {}: {}", peer_id, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        
+        match last_error {
+            Some(e) if self.pending_bootstrap.is_empty() => Err(NetworkError::TransportError(e.to_string())),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Start the DHT bootstrap query unless one already ran
+    fn start_bootstrap(&mut self) {
+        if self.bootstrapped || self.bootstrap_query.is_some() {
+            return;
+        }
+        match self.swarm.behaviour_mut().kad.bootstrap() {
+            Ok(query_id) => self.bootstrap_query = Some(query_id),
+            Err(e) => warn!("Cannot bootstrap DHT: {:?}", e),
+        }
+    }
+    
+    fn stats_entry(&mut self, peer_id: PeerId) -> &mut PeerStats {
+        self.peer_stats
+            .entry(peer_id)
+            .or_insert_with(|| PeerStats::new(peer_id))
+    }
+    
     /// Run the network event loop
     pub async fn run(mut self) -> Result<(), NetworkError> {
+        let mut stats_interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + STATS_SNAPSHOT_INTERVAL,
+            STATS_SNAPSHOT_INTERVAL,
+        );
+        let liveness_period = self.liveness.timeout() / 2;
+        let mut liveness_interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + liveness_period,
+            liveness_period,
+        );
+        let mut queue_depth_interval = tokio::time::interval_at(
+            tokio::time::Instant::now() + QUEUE_DEPTH_INTERVAL,
+            QUEUE_DEPTH_INTERVAL,
+        );
+        
+        let bootstrap_peers = std::mem::take(&mut self.bootstrap_peers);
+        if !bootstrap_peers.is_empty() {
+            if let Err(e) = self.add_bootstrap_peers(&bootstrap_peers) {
+                warn!("Failed to dial bootstrap peers: {}", e);
+            }
+        }
+        
         loop {
+            // Messages left over budget wake the loop again right away
+            let backlog = !self.send_queue.is_empty();
+            
             select! {
+                _ = stats_interval.tick().fuse() => {
+                    let _ = self.event_tx.send(NetworkEvent::StatsSnapshot(self.peer_stats())).await;
+                }
+                _ = queue_depth_interval.tick().fuse() => {
+                    let (real_time, interactive, bulk) = self.send_queue.depth();
+                    let _ = self.event_tx.send(NetworkEvent::QueueDepth { real_time, interactive, bulk }).await;
+                }
+                _ = wait_for_backlog(backlog).fuse() => {}
+                _ = liveness_interval.tick().fuse() => {
+                    self.check_liveness().await;
+                    self.prune_peer_scores();
+                    for e in self.reassembler.expire() {
+                        warn!("Dropping incomplete message: {}", e);
+                    }
+                }
                 event = self.swarm.select_next_some() => {
                     if let Err(e) = self.handle_swarm_event(event).await {
                         warn!("Error handling swarm event: {}", e);
@@ -199,6 +913,10 @@ impl Network {
                 }
                 command = self.command_rx.recv().fuse() => {
                     match command {
+                        Some(NetworkCommand::Shutdown { grace_period_ms }) => {
+                            self.graceful_shutdown(Duration::from_millis(grace_period_ms)).await;
+                            break;
+                        }
                         Some(cmd) => {
                             if let Err(e) = self.handle_command(cmd).await {
                                 warn!("Error handling command: {}", e);
@@ -211,11 +929,104 @@ impl Network {
                     }
                 }
             }
+            
+            for message in self.send_queue.drain(&self.queue_budget) {
+                if let Err(e) = self.publish(message) {
+                    warn!("Error publishing message: {}", e);
+                }
+            }
         }
         
         Ok(())
     }
     
+    /// Ask a running network to shut down gracefully
+    ///
+    /// New `SendMessage` commands are rejected immediately, while messages already
+    /// handed to gossipsub get up to `grace_ms` milliseconds to reach the mesh.
+    pub async fn shutdown(
+        command_tx: &mpsc::Sender<NetworkCommand>,
+        grace_ms: u64,
+    ) -> Result<(), NetworkError> {
+        command_tx
+            .send(NetworkCommand::Shutdown { grace_period_ms: grace_ms })
+            .await
+            .map_err(|e| NetworkError::SendError(e.to_string()))
+    }
+    
+    /// Drain in-flight publishes for the grace period, then close the swarm
+    async fn graceful_shutdown(&mut self, grace_period: Duration) {
+        info!("Shutting down network (grace period: {:?})", grace_period);
+        self.shutting_down = true;
+        
+        for message in self.send_queue.drain_all() {
+            if let Err(e) = self.publish(message) {
+                warn!("Error publishing message during shutdown: {}", e);
+            }
+        }
+        
+        // Keep driving the swarm so queued gossipsub frames reach the connections.
+        // Commands are still read so that late sends get an explicit rejection.
+        let deadline = tokio::time::sleep(grace_period);
+        tokio::pin!(deadline);
+        let mut commands_open = true;
+        
+        loop {
+            tokio::select! {
+                event = self.swarm.select_next_some() => {
+                    if let Err(e) = self.handle_swarm_event(event).await {
+                        warn!("Error handling swarm event during shutdown: {}", e);
+                    }
+                }
+                command = self.command_rx.recv(), if commands_open => {
+                    match command {
+                        Some(cmd) => {
+                            if let Err(e) = self.handle_command(cmd).await {
+                                debug!("Command rejected during shutdown: {}", e);
+                            }
+                        }
+                        None => commands_open = false,
+                    }
+                }
+                _ = &mut deadline => break,
+            }
+        }
+        
+        let _ = self.event_tx.send(NetworkEvent::ShuttingDown).await;
+        self.close_swarm();
+    }
+    
+    /// Report and forget peers that have gone silent
+    async fn check_liveness(&mut self) {
+        for peer_id in self.liveness.check_dead_peers() {
+            if self.connected_peers.remove(&peer_id) {
+                warn!("Peer {} is unresponsive", peer_id);
+                let _ = self.event_tx.send(NetworkEvent::PeerUnresponsive { peer_id }).await;
+            }
+        }
+    }
+    
+    /// Record traffic from a peer, reinstating it if it was reported unresponsive
+    fn peer_heard(&mut self, peer_id: PeerId) {
+        self.liveness.heartbeat(peer_id);
+        if self.swarm.is_connected(&peer_id) {
+            self.connected_peers.insert(peer_id);
+        }
+    }
+    
+    /// Stop all listeners and disconnect every connected peer
+    fn close_swarm(&mut self) {
+        for listener_id in self.listeners.drain(..) {
+            self.swarm.remove_listener(listener_id);
+        }
+        
+        for peer_id in self.connected_peers.drain() {
+            let _ = self.swarm.disconnect_peer_id(peer_id);
+        }
+        
+        info!("Network closed");
+    }
+    
     async fn handle_swarm_event<THandlerErr>(
         &mut self,
         event: SwarmEvent<OtterBehaviourEvent, THandlerErr>,
@@ -244,16 +1055,59 @@ impl Network {
             SwarmEvent::Behaviour(OtterBehaviourEvent::Gossipsub(
                 gossipsub::Event::Message {
                     propagation_source,
+                    message_id,
                     message,
-                    ..
                 },
             )) => {
                 debug!("Received message from {}", propagation_source);
+                self.peer_heard(propagation_source);
                 
-                let _ = self.event_tx.send(NetworkEvent::MessageReceived {
-                    from: propagation_source,
-                    data: message.data,
-                }).await;
+                let score_config = &self.score_config;
+                if self.peer_scores.entry(propagation_source).or_default().record_message(score_config) {
+                    self.penalize(propagation_source, |_| {});
+                }
+                
+                let local_peer_id = *self.swarm.local_peer_id();
+                let mesh_peers: Vec<PeerId> = self.swarm.behaviour().gossipsub.mesh_peers(&message.topic).copied().collect();
+                self.tracer.record_message(message_id, local_peer_id, propagation_source, message.source, mesh_peers);
+                
+                self.stats_entry(propagation_source).bytes_received += message.data.len() as u64;
+                if let Some(metrics) = &self.metrics {
+                    metrics.bytes_received(message.data.len() as u64);
+                }
+                
+                // Fragments are held back until the whole message has arrived,
+                // kept apart per author (or relaying peer for anonymous messages)
+                let message = match Fragment::from_bytes(&message.data) {
+                    Some(fragment) => match self.reassembler.feed(&message.source.unwrap_or(propagation_source).to_string(), fragment) {
+                        Some(data) => gossipsub::Message { data, ..message },
+                        None => return Ok(()),
+                    },
+                    None => message,
+                };
+                self.stats_entry(propagation_source).messages_received += 1;
+                if let Some(metrics) = &self.metrics {
+                    metrics.message_received();
+                }
+                
+                match self.validator.validate(&message) {
+                    ValidationDecision::Accept => {
+                        let _ = self.event_tx.send(NetworkEvent::MessageReceived {
+                            from: propagation_source,
+                            data: message.data,
+                        }).await;
+                    }
+                    ValidationDecision::Reject(reason) => {
+                        warn!("Rejected message from {}: {}", propagation_source, reason);
+                        let _ = self.event_tx.send(NetworkEvent::MessageRejected {
+                            from: propagation_source,
+                            reason,
+                        }).await;
+                    }
+                    ValidationDecision::Ignore => {
+                        debug!("Ignored message from {}", propagation_source);
+                    }
+                }
             }
             
             SwarmEvent::Behaviour(OtterBehaviourEvent::Gossipsub(
@@ -276,26 +1130,211 @@ impl Network {
                 debug!("Unhandled gossipsub event: {:?}", event);
             }
             
-            SwarmEvent::ConnectionEstablished { peer_id, .. } => {
-                info!("Connected to peer: {}", peer_id);
-                self.connected_peers.insert(peer_id);
-                
-                let _ = self.event_tx.send(NetworkEvent::PeerConnected { peer_id }).await;
+            SwarmEvent::Behaviour(OtterBehaviourEvent::Ping(ping::Event { peer, result: Ok(_), .. })) => {
+                self.peer_heard(peer);
             }
             
-            SwarmEvent::ConnectionClosed { peer_id, .. } => {
-                info!("Disconnected from peer: {}", peer_id);
-                self.connected_peers.remove(&peer_id);
-                
-                let _ = self.event_tx.send(NetworkEvent::PeerDisconnected { peer_id }).await;
+            SwarmEvent::Behaviour(OtterBehaviourEvent::Kad(
+                kad::Event::OutboundQueryProgressed {
+                    id,
+                    result: kad::QueryResult::GetProviders(result),
+                    step,
+                    ..
+                },
+            )) => {
+                if let Some(query) = self.provider_queries.get_mut(&id) {
+                    match result {
+                        Ok(kad::GetProvidersOk::FoundProviders { providers, .. }) => {
+                            query.providers.extend(providers);
+                        }
+                        Ok(kad::GetProvidersOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                        Err(e) => debug!("Provider lookup failed: {}", e),
+                    }
+                    
+                    if step.last {
+                        if let Some(query) = self.provider_queries.remove(&id) {
+                            let _ = query.response.send(query.providers.into_iter().collect());
+                        }
+                    }
+                }
             }
             
-            SwarmEvent::NewListenAddr { address, .. } => {
-                info!("Listening on: {}", address);
-                
-                let _ = self.event_tx.send(NetworkEvent::ListeningOn {
-                    address: address.to_string(),
-                }).await;
+            SwarmEvent::Behaviour(OtterBehaviourEvent::Kad(
+                kad::Event::OutboundQueryProgressed {
+                    id,
+                    result: kad::QueryResult::PutRecord(result),
+                    step,
+                    ..
+                },
+            )) if step.last => {
+                if let Some(response) = self.record_puts.remove(&id) {
+                    let _ = response.send(result.map(|_| ()).map_err(|e| NetworkError::DhtError(e.to_string())));
+                }
+            }
+            
+            SwarmEvent::Behaviour(OtterBehaviourEvent::Kad(
+                kad::Event::OutboundQueryProgressed {
+                    id,
+                    result: kad::QueryResult::GetRecord(result),
+                    step,
+                    ..
+                },
+            )) => {
+                if let Some(query) = self.record_queries.get_mut(&id) {
+                    let mut failure = None;
+                    match result {
+                        Ok(kad::GetRecordOk::FoundRecord(found)) => {
+                            if !query.records.contains(&found.record.value) {
+                                query.records.push(found.record.value);
+                            }
+                        }
+                        Ok(kad::GetRecordOk::FinishedWithNoAdditionalRecord { .. }) => {}
+                        Err(kad::GetRecordError::NotFound { .. }) => {}
+                        Err(e) => failure = Some(e),
+                    }
+                    
+                    if step.last {
+                        if let Some(query) = self.record_queries.remove(&id) {
+                            let result = match failure {
+                                Some(e) if query.records.is_empty() => Err(NetworkError::DhtError(e.to_string())),
+                                _ => Ok(query.records),
+                            };
+                            let _ = query.response.send(result);
+                        }
+                    }
+                }
+            }
+            
+            SwarmEvent::Behaviour(OtterBehaviourEvent::Kad(
+                kad::Event::OutboundQueryProgressed {
+                    id,
+                    result: kad::QueryResult::GetClosestPeers(result),
+                    step,
+                    ..
+                },
+            )) if step.last => {
+                if let Some(target) = self.peer_lookups.remove(&id) {
+                    let found = match result {
+                        Ok(ok) => ok.peers.contains(&target),
+                        Err(kad::GetClosestPeersError::Timeout { peers, .. }) => peers.contains(&target),
+                    };
+                    
+                    // Pending finds are answered once the dial connects or fails
+                    if found {
+                        if let Err(e) = self.swarm.dial(target) {
+                            warn!("Failed to dial {} after DHT lookup: {}", target, e);
+                            self.peer_found(target, Vec::new());
+                        }
+                    } else {
+                        debug!("DHT lookup did not find peer {}", target);
+                        self.peer_found(target, Vec::new());
+                    }
+                }
+            }
+            
+            SwarmEvent::Behaviour(OtterBehaviourEvent::Kad(
+                kad::Event::OutboundQueryProgressed {
+                    id,
+                    result: kad::QueryResult::Bootstrap(result),
+                    step,
+                    ..
+                },
+            )) if step.last && self.bootstrap_query == Some(id) => {
+                self.bootstrap_query = None;
+                if let Err(e) = result {
+                    debug!("DHT bootstrap did not finish cleanly: {:?}", e);
+                }
+                
+                if !self.bootstrapped {
+                    self.bootstrapped = true;
+                    let known_peers = self
+                        .swarm
+                        .behaviour_mut()
+                        .kad
+                        .kbuckets()
+                        .map(|bucket| bucket.num_entries())
+                        .sum();
+                    info!("DHT bootstrap completed with {} known peers", known_peers);
+                    let _ = self.event_tx.send(NetworkEvent::BootstrapCompleted { known_peers }).await;
+                }
+            }
+            
+            SwarmEvent::ConnectionEstablished { peer_id, connection_id, endpoint, .. } => {
+                if let Some(reason) = self.admission_denied(&peer_id, endpoint.is_dialer()) {
+                    info!("Refusing connection with {}: {}", peer_id, reason);
+                    self.rejected_connections.insert(connection_id);
+                    self.swarm.close_connection(connection_id);
+                    return Ok(());
+                }
+                self.connection_dialers.insert(connection_id, endpoint.is_dialer());
+                info!("Connected to peer: {}", peer_id);
+                
+                // Only the first connection to a peer starts a new session
+                if self.connected_peers.insert(peer_id) {
+                    self.stats_entry(peer_id).connected_since = Instant::now();
+                    if let Some(metrics) = &self.metrics {
+                        metrics.peer_connected();
+                    }
+                }
+                self.liveness.heartbeat(peer_id);
+                self.apply_peer_priority(&peer_id);
+                
+                if self.pending_bootstrap.remove(&peer_id) {
+                    self.start_bootstrap();
+                }
+                if self.peer_finds.contains_key(&peer_id) {
+                    self.peer_found(peer_id, vec![endpoint.get_remote_address().clone()]);
+                }
+                
+                let _ = self.event_tx.send(NetworkEvent::PeerConnected { peer_id }).await;
+            }
+            
+            SwarmEvent::ConnectionClosed { peer_id, connection_id, num_established, .. } => {
+                if self.rejected_connections.remove(&connection_id) {
+                    return Ok(());
+                }
+                self.connection_dialers.remove(&connection_id);
+                if num_established == 0 {
+                    self.peer_scores.remove(&peer_id);
+                }
+                info!("Disconnected from peer: {}", peer_id);
+                self.connected_peers.remove(&peer_id);
+                self.liveness.remove(&peer_id);
+                
+                let _ = self.event_tx.send(NetworkEvent::PeerDisconnected { peer_id }).await;
+            }
+            
+            SwarmEvent::OutgoingConnectionError { peer_id, error, .. } => {
+                if handshake_failed(&error) {
+                    debug!("Connection handshake failed: {}", error);
+                    if let Some(metrics) = &self.metrics {
+                        metrics.handshake_failed();
+                    }
+                    if let Some(peer_id) = peer_id {
+                        self.penalize(peer_id, |score| score.failed_handshakes += 1);
+                    }
+                }
+                if let Some(peer_id) = peer_id.filter(|peer_id| self.peer_finds.contains_key(peer_id)) {
+                    self.peer_found(peer_id, Vec::new());
+                }
+            }
+            
+            // An incoming connection only exists once TCP is up, so any failure is in the handshake
+            SwarmEvent::IncomingConnectionError {
+                error: ListenError::Transport(_) | ListenError::WrongPeerId { .. },
+                ..
+            } => {
+                if let Some(metrics) = &self.metrics {
+                    metrics.handshake_failed();
+                }
+            }
+            
+            SwarmEvent::NewListenAddr { address, .. } => {
+                info!("Listening on: {}", address);
+                
+                let _ = self.event_tx.send(NetworkEvent::ListeningOn {
+                    address: address.to_string(),
+                }).await;
             }
             
             _ => {}
@@ -306,25 +1345,16 @@ impl Network {
     
     async fn handle_command(&mut self, command: NetworkCommand) -> Result<(), NetworkError> {
         match command {
-            NetworkCommand::SendMessage { to, data } => {
-                // NOTE: 'to' parameter is currently ignored - gossipsub broadcasts to all subscribers.
-                // E2E encryption ensures only the intended recipient can decrypt the message.
-                debug!("Broadcasting message (intended for: {}, size: {} bytes)", to, data.len());
-                
-                // Publish to gossipsub topic
-                match self.swarm
-                    .behaviour_mut()
-                    .gossipsub
-                    .publish(self.gossipsub_topic.clone(), data)
-                {
-                    Ok(message_id) => {
-                        debug!("Published message to gossipsub, message_id: {:?}", message_id);
-                    }
-                    Err(e) => {
-                        error!("Failed to publish to gossipsub: {}", e);
-                        return Err(NetworkError::SendError(format!("Publish error: {}", e)));
-                    }
-                }
+            NetworkCommand::SendMessage { .. } if self.shutting_down => {
+                return Err(NetworkError::ShuttingDown);
+            }
+            
+            NetworkCommand::SendMessage { .. } if self.listener_mode => {
+                return Err(NetworkError::ListenerModeActive);
+            }
+            
+            NetworkCommand::SendMessage { to, data, priority } => {
+                self.send_queue.push(to, data, priority);
             }
             
             NetworkCommand::ListPeers { response } => {
@@ -341,10 +1371,153 @@ impl Network {
                     .dial(addr)
                     .map_err(|e| NetworkError::TransportError(e.to_string()))?;
             }
+            
+            NetworkCommand::FindPeer { peer_id, response } => {
+                self.find_peer(peer_id, response)?;
+            }
+            
+            NetworkCommand::GetPeerStats { response } => {
+                let _ = response.send(self.peer_stats());
+            }
+            
+            NetworkCommand::Advertise { service_key } => {
+                self.start_providing(service_key)?;
+            }
+            
+            NetworkCommand::FindService { key, response } => {
+                self.find_providers(key, response)?;
+            }
+            
+            NetworkCommand::GetMeshTopology { response } => {
+                let _ = response.send(self.mesh_peers());
+            }
+            
+            NetworkCommand::GetAddressBook { response } => {
+                let _ = response.send(self.address_book.sorted_entries());
+            }
+            
+            NetworkCommand::PutRecord { key, value, response } => {
+                self.put_record(key, value, response)?;
+            }
+            
+            NetworkCommand::GetRecord { key, response } => {
+                self.get_record(key, response)?;
+            }
+            
+            NetworkCommand::SetPeerPriority { peer_id, priority } => {
+                self.set_peer_priority(&peer_id, priority);
+            }
+            
+            NetworkCommand::BanPeer { peer_id, duration } => {
+                self.ban_peer(peer_id, duration);
+            }
+            
+            NetworkCommand::ReportDecryptionError { peer_id } => {
+                self.penalize(peer_id, |score| score.decryption_errors += 1);
+            }
+            
+            NetworkCommand::Shutdown { grace_period_ms } => {
+                // Already draining; a second request must not extend the grace period
+                debug!("Ignoring repeated shutdown request ({} ms)", grace_period_ms);
+            }
         }
         
         Ok(())
     }
+    
+    /// Publish a queued message, splitting it into fragments if needed
+    fn publish(&mut self, message: PriorityMessage) -> Result<(), NetworkError> {
+        // Messages queued before listener mode was switched on are dropped
+        if self.listener_mode {
+            return Err(NetworkError::ListenerModeActive);
+        }
+        
+        let PriorityMessage { to, data, priority, .. } = message;
+        
+        // NOTE: 'to' parameter is currently ignored - gossipsub broadcasts to all subscribers.
+        // E2E encryption ensures only the intended recipient can decrypt the message.
+        debug!("Broadcasting {:?} message (intended for: {}, size: {} bytes)", priority, to, data.len());
+        let size = data.len() as u64;
+        
+        // Payloads over the gossipsub limit go out as fragments
+        let chunks = if data.len() > MAX_PUBLISH_SIZE {
+            Fragmenter::fragment_bytes(&data, MAX_PUBLISH_SIZE - FRAGMENT_OVERHEAD)
+                .and_then(|fragments| fragments.iter().map(Fragment::to_bytes).collect())
+                .map_err(|e| NetworkError::SendError(e.to_string()))?
+        } else {
+            vec![data]
+        };
+        
+        // Publish to gossipsub topic
+        for chunk in chunks {
+            match self.swarm
+                .behaviour_mut()
+                .gossipsub
+                .publish(self.gossipsub_topic.clone(), chunk)
+            {
+                Ok(message_id) => {
+                    debug!("Published message to gossipsub, message_id: {:?}", message_id);
+                }
+                Err(e) => {
+                    error!("Failed to publish to gossipsub: {}", e);
+                    return Err(NetworkError::SendError(format!("Publish error: {}", e)));
+                }
+            }
+        }
+        
+        let stats = self.stats_entry(to);
+        stats.bytes_sent += size;
+        stats.messages_sent += 1;
+        if let Some(metrics) = &self.metrics {
+            metrics.message_sent(size);
+        }
+        Ok(())
+    }
+}
+
+/// Failure after the TCP connection was up: protocol negotiation, Noise, yamux or the upgrade timeout
+#[derive(Debug, ThisError)]
+#[error("{0}")]
+struct HandshakeError(Box<dyn std::error::Error + Send + Sync>);
+
+/// Pass TCP errors through and wrap everything after them in `HandshakeError`
+fn classify_transport_error<A, M>(error: TransportTimeoutError<Either<Either<io::Error, A>, M>>) -> io::Error
+where
+    A: std::error::Error + Send + Sync + 'static,
+    M: std::error::Error + Send + Sync + 'static,
+{
+    match error {
+        TransportTimeoutError::Other(Either::Left(Either::Left(tcp))) => tcp,
+        other => io::Error::other(HandshakeError(Box::new(other))),
+    }
+}
+
+/// Whether a dial reached the remote but failed to negotiate or authenticate
+fn handshake_failed(error: &DialError) -> bool {
+    let is_handshake_error = |error: &TransportError<io::Error>| match error {
+        // `boxed()` wraps the classified error in another `io::Error`
+        TransportError::Other(error) => error
+            .get_ref()
+            .and_then(|inner| inner.downcast_ref::<io::Error>())
+            .and_then(io::Error::get_ref)
+            .is_some_and(|inner| inner.is::<HandshakeError>()),
+        TransportError::MultiaddrNotSupported(_) => false,
+    };
+    
+    match error {
+        DialError::WrongPeerId { .. } => true,
+        DialError::Transport(errors) => errors.iter().any(|(_, error)| is_handshake_error(error)),
+        _ => false,
+    }
+}
+
+/// Resolve immediately if messages are waiting to be published, otherwise never
+async fn wait_for_backlog(backlog: bool) {
+    if backlog {
+        tokio::task::yield_now().await;
+    } else {
+        future::pending::<()>().await;
+    }
 }
 
 /// Create network channels
@@ -366,7 +1539,890 @@ mod tests {
     #[tokio::test]
     async fn test_network_creation() {
         let (event_tx, _event_rx, _command_tx, command_rx) = create_network_channels();
-        let network = Network::new(event_tx, command_rx);
+        let network = Network::new(event_tx, command_rx, Box::new(AcceptAll));
         assert!(network.is_ok());
     }
+    
+    #[tokio::test]
+    async fn test_gossipsub_presets() {
+        for (params, d) in [
+            (GossipsubParams::lan(), 3),
+            (GossipsubParams::default(), 6),
+            (GossipsubParams::wan(), 8),
+        ] {
+            let (event_tx, _event_rx, _command_tx, command_rx) = create_network_channels();
+            let network = Network::with_gossipsub_params(event_tx, command_rx, Box::new(AcceptAll), params).unwrap();
+            
+            let config = network.gossipsub_config();
+            assert_eq!(config.mesh_n(), d);
+            assert_eq!(config.mesh_n_low(), params.d_low);
+            assert_eq!(config.mesh_n_high(), params.d_high);
+            assert_eq!(config.gossip_lazy(), params.d_lazy);
+        }
+    }
+    
+    /// Wait for the first event matching `pred`, giving up after `timeout`
+    async fn wait_for_event<F>(
+        event_rx: &mut mpsc::Receiver<NetworkEvent>,
+        timeout: Duration,
+        mut pred: F,
+    ) -> Option<NetworkEvent>
+    where
+        F: FnMut(&NetworkEvent) -> bool,
+    {
+        tokio::time::timeout(timeout, async {
+            while let Some(event) = event_rx.recv().await {
+                if pred(&event) {
+                    return Some(event);
+                }
+            }
+            None
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+    
+    #[tokio::test]
+    async fn test_graceful_shutdown_drains_messages() {
+        // Mesh peer that only receives
+        let (peer_event_tx, mut peer_event_rx, _peer_command_tx, peer_command_rx) = create_network_channels();
+        let mut peer = Network::new(peer_event_tx, peer_command_rx, Box::new(AcceptAll)).unwrap();
+        peer.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(peer.run());
+        
+        let peer_address = match wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Peer did not start listening: {:?}", other),
+        };
+        
+        // Node under test
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        network.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let handle = tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: PeerId::random(),
+            address: peer_address,
+        }).await.unwrap();
+        
+        let ready = wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerReadyForMessages { .. })
+        }).await;
+        assert!(ready.is_some(), "Mesh peer never subscribed");
+        
+        for i in 0..10u8 {
+            command_tx.send(NetworkCommand::SendMessage {
+                to: PeerId::random(),
+                data: vec![i; 64],
+                priority: MessagePriority::Interactive,
+            }).await.unwrap();
+        }
+        Network::shutdown(&command_tx, 100).await.unwrap();
+        
+        // Sends issued after the shutdown request are rejected, not published
+        command_tx.send(NetworkCommand::SendMessage {
+            to: PeerId::random(),
+            data: vec![0xAA; 64],
+            priority: MessagePriority::Interactive,
+        }).await.unwrap();
+        
+        let shutting_down = wait_for_event(&mut event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ShuttingDown)
+        }).await;
+        assert!(shutting_down.is_some());
+        tokio::time::timeout(Duration::from_secs(5), handle).await.unwrap().unwrap().unwrap();
+        
+        let mut received = HashSet::new();
+        while let Some(event) = wait_for_event(&mut peer_event_rx, Duration::from_millis(500), |e| {
+            matches!(e, NetworkEvent::MessageReceived { .. })
+        }).await {
+            if let NetworkEvent::MessageReceived { data, .. } = event {
+                received.insert(data);
+            }
+        }
+        
+        assert!(received.len() >= 9, "Only {} of 10 messages propagated", received.len());
+        assert!(!received.contains(&vec![0xAA; 64]));
+    }
+    
+    #[tokio::test]
+    async fn test_listener_mode_receives_but_never_sends() {
+        let (listener_event_tx, mut listener_event_rx, _listener_command_tx, listener_command_rx) = create_network_channels();
+        let mut listener = Network::new_listener(listener_event_tx, listener_command_rx, Box::new(AcceptAll)).unwrap();
+        assert!(listener.is_listener_mode());
+        listener.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        
+        for priority in [MessagePriority::RealTime, MessagePriority::Interactive, MessagePriority::Bulk] {
+            let result = listener.handle_command(NetworkCommand::SendMessage {
+                to: PeerId::random(),
+                data: vec![1; 16],
+                priority,
+            }).await;
+            assert!(matches!(result, Err(NetworkError::ListenerModeActive)));
+        }
+        
+        // Created as a listener, so it cannot start publishing
+        listener.set_listener_mode(false);
+        assert!(listener.is_listener_mode());
+        tokio::spawn(listener.run());
+        
+        let listener_address = match wait_for_event(&mut listener_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Listener did not start listening: {:?}", other),
+        };
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        network.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: PeerId::random(),
+            address: listener_address,
+        }).await.unwrap();
+        
+        let ready = wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerReadyForMessages { .. })
+        }).await;
+        assert!(ready.is_some(), "Listener never subscribed");
+        
+        command_tx.send(NetworkCommand::SendMessage {
+            to: PeerId::random(),
+            data: b"observed".to_vec(),
+            priority: MessagePriority::Interactive,
+        }).await.unwrap();
+        
+        let received = wait_for_event(&mut listener_event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::MessageReceived { .. })
+        }).await;
+        assert!(matches!(received, Some(NetworkEvent::MessageReceived { ref data, .. }) if data == b"observed"));
+    }
+    
+    #[tokio::test]
+    async fn test_set_listener_mode_emits_event() {
+        let (event_tx, mut event_rx, _command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        
+        network.set_listener_mode(true);
+        network.set_listener_mode(true);
+        network.set_listener_mode(false);
+        assert!(matches!(event_rx.try_recv(), Ok(NetworkEvent::ListenerModeChanged(true))));
+        assert!(matches!(event_rx.try_recv(), Ok(NetworkEvent::ListenerModeChanged(false))));
+        assert!(event_rx.try_recv().is_err());
+    }
+    
+    #[tokio::test]
+    async fn test_large_message_is_fragmented() {
+        let (peer_event_tx, mut peer_event_rx, _peer_command_tx, peer_command_rx) = create_network_channels();
+        let mut peer = Network::new(peer_event_tx, peer_command_rx, Box::new(AcceptAll)).unwrap();
+        peer.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(peer.run());
+        
+        let peer_address = match wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Peer did not start listening: {:?}", other),
+        };
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        network.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: PeerId::random(),
+            address: peer_address,
+        }).await.unwrap();
+        
+        let ready = wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerReadyForMessages { .. })
+        }).await;
+        assert!(ready.is_some(), "Mesh peer never subscribed");
+        
+        // Well over gossipsub's 64 KiB transmit limit
+        let payload: Vec<u8> = (0..300 * 1024).map(|i| (i % 251) as u8).collect();
+        command_tx.send(NetworkCommand::SendMessage {
+            to: PeerId::random(),
+            data: payload.clone(),
+            priority: MessagePriority::Interactive,
+        }).await.unwrap();
+        
+        let received = wait_for_event(&mut peer_event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::MessageReceived { .. })
+        }).await;
+        assert!(matches!(received, Some(NetworkEvent::MessageReceived { data, .. }) if data == payload));
+    }
+    
+    #[tokio::test]
+    async fn test_validator_rejects_message() {
+        let rejects_ff = |msg: &gossipsub::Message| {
+            if msg.data.contains(&0xFF) {
+                ValidationDecision::Reject("contains 0xFF".to_string())
+            } else {
+                ValidationDecision::Accept
+            }
+        };
+        let (peer_event_tx, mut peer_event_rx, _peer_command_tx, peer_command_rx) = create_network_channels();
+        let mut peer = Network::new(peer_event_tx, peer_command_rx, Box::new(rejects_ff)).unwrap();
+        peer.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(peer.run());
+        
+        let peer_address = match wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Peer did not start listening: {:?}", other),
+        };
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        network.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let sender_id = network.local_peer_id();
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: PeerId::random(),
+            address: peer_address,
+        }).await.unwrap();
+        
+        let ready = wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerReadyForMessages { .. })
+        }).await;
+        assert!(ready.is_some(), "Mesh peer never subscribed");
+        
+        for data in [vec![1, 0xFF, 2], vec![1, 2, 3]] {
+            command_tx.send(NetworkCommand::SendMessage {
+                to: PeerId::random(),
+                data,
+                priority: MessagePriority::Interactive,
+            }).await.unwrap();
+        }
+        
+        // Gossipsub does not preserve publish order
+        let mut rejected = Vec::new();
+        let mut received = Vec::new();
+        while rejected.len() + received.len() < 2 {
+            match wait_for_event(&mut peer_event_rx, Duration::from_secs(10), |e| {
+                matches!(e, NetworkEvent::MessageRejected { .. } | NetworkEvent::MessageReceived { .. })
+            }).await {
+                Some(NetworkEvent::MessageRejected { from, reason }) => rejected.push((from, reason)),
+                Some(NetworkEvent::MessageReceived { data, .. }) => received.push(data),
+                other => panic!("Expected two messages, got {:?}", other),
+            }
+        }
+        assert_eq!(rejected, vec![(sender_id, "contains 0xFF".to_string())]);
+        assert_eq!(received, vec![vec![1, 2, 3]]);
+    }
+    
+    #[tokio::test]
+    async fn test_find_service_provider() {
+        let (provider_event_tx, mut provider_event_rx, provider_command_tx, provider_command_rx) = create_network_channels();
+        let mut provider = Network::new(provider_event_tx, provider_command_rx, Box::new(AcceptAll)).unwrap();
+        provider.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let provider_id = provider.local_peer_id();
+        tokio::spawn(provider.run());
+        
+        let provider_address = match wait_for_event(&mut provider_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Provider did not start listening: {:?}", other),
+        };
+        
+        provider_command_tx.send(NetworkCommand::Advertise {
+            service_key: "otter-turn".to_string(),
+        }).await.unwrap();
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: provider_id,
+            address: provider_address,
+        }).await.unwrap();
+        
+        let connected = wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerConnected { .. })
+        }).await;
+        assert!(connected.is_some());
+        
+        // The provider only enters the routing table once it confirms it speaks Kademlia
+        let mut providers = Vec::new();
+        for _ in 0..20 {
+            let (response, rx) = oneshot::channel();
+            command_tx.send(NetworkCommand::FindService {
+                key: "otter-turn".to_string(),
+                response,
+            }).await.unwrap();
+            providers = rx.await.unwrap();
+            
+            if !providers.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        
+        assert_eq!(providers, vec![provider_id]);
+    }
+    
+    #[tokio::test]
+    async fn test_find_peer_returns_addresses() {
+        let (target_event_tx, mut target_event_rx, _target_command_tx, target_command_rx) = create_network_channels();
+        let mut target = Network::new(target_event_tx, target_command_rx, Box::new(AcceptAll)).unwrap();
+        target.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let target_id = target.local_peer_id();
+        tokio::spawn(target.run());
+        
+        let target_address = match wait_for_event(&mut target_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Target did not start listening: {:?}", other),
+        };
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: target_id,
+            address: target_address,
+        }).await.unwrap();
+        assert!(wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerConnected { .. })
+        }).await.is_some());
+        
+        // The target only enters the routing table once it confirms it speaks Kademlia
+        let mut addresses = Vec::new();
+        for _ in 0..20 {
+            let (response, mut rx) = mpsc::channel(1);
+            command_tx.send(NetworkCommand::FindPeer { peer_id: target_id, response: Some(response) }).await.unwrap();
+            addresses = rx.recv().await.unwrap();
+            
+            if !addresses.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        // The routing table may hold the addresses the target advertises rather than the one dialed
+        assert!(!addresses.is_empty());
+        
+        let found = wait_for_event(&mut event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::PeerFound { .. })
+        }).await;
+        match found {
+            Some(NetworkEvent::PeerFound { peer_id, addresses: found }) => {
+                assert_eq!(peer_id, target_id);
+                assert_eq!(found, addresses);
+            }
+            other => panic!("No PeerFound event: {:?}", other),
+        }
+        
+        // A peer nobody knows is answered with no addresses
+        let (response, mut rx) = mpsc::channel(1);
+        command_tx.send(NetworkCommand::FindPeer { peer_id: PeerId::random(), response: Some(response) }).await.unwrap();
+        let missing = tokio::time::timeout(Duration::from_secs(30), rx.recv()).await.unwrap();
+        assert_eq!(missing, Some(Vec::new()));
+    }
+    
+    #[tokio::test]
+    async fn test_misbehaving_peer_is_banned() {
+        let (a_event_tx, mut a_event_rx, a_command_tx, a_command_rx) = create_network_channels();
+        let mut node_a = Network::new(a_event_tx, a_command_rx, Box::new(AcceptAll))
+            .unwrap()
+            .with_peer_scoring(PeerScoreConfig {
+                ban_threshold: 20,
+                ..PeerScoreConfig::default()
+            });
+        node_a.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let a_id = node_a.local_peer_id();
+        tokio::spawn(node_a.run());
+        
+        let a_address = match wait_for_event(&mut a_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Node A did not start listening: {:?}", other),
+        };
+        
+        let (b_event_tx, mut b_event_rx, b_command_tx, b_command_rx) = create_network_channels();
+        let node_b = Network::new(b_event_tx, b_command_rx, Box::new(AcceptAll)).unwrap();
+        let b_id = node_b.local_peer_id();
+        tokio::spawn(node_b.run());
+        
+        let dial = NetworkCommand::DialPeer { peer_id: a_id, address: a_address.clone() };
+        b_command_tx.send(dial).await.unwrap();
+        assert!(wait_for_event(&mut a_event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerConnected { .. })
+        }).await.is_some());
+        
+        // Two decryption errors reach the threshold
+        for _ in 0..2 {
+            a_command_tx.send(NetworkCommand::ReportDecryptionError { peer_id: b_id }).await.unwrap();
+        }
+        match wait_for_event(&mut a_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::PeerBanned { .. })
+        }).await {
+            Some(NetworkEvent::PeerBanned { peer_id, duration }) => {
+                assert_eq!(peer_id, b_id);
+                assert_eq!(duration, PeerScoreConfig::default().ban_duration);
+            }
+            other => panic!("Peer was not banned: {:?}", other),
+        }
+        assert!(wait_for_event(&mut b_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::PeerDisconnected { .. })
+        }).await.is_some());
+        
+        // A banned peer's new connection is closed before it counts as connected
+        b_command_tx.send(NetworkCommand::DialPeer { peer_id: a_id, address: a_address }).await.unwrap();
+        assert!(wait_for_event(&mut b_event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerConnected { .. })
+        }).await.is_some());
+        assert!(wait_for_event(&mut a_event_rx, Duration::from_secs(2), |e| {
+            matches!(e, NetworkEvent::PeerConnected { .. })
+        }).await.is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_expired_bans_and_stale_scores_are_pruned() {
+        let (event_tx, _event_rx, _command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        
+        let expired = PeerId::random();
+        let banned = PeerId::random();
+        let offender = PeerId::random();
+        network.ban_peer(expired, Duration::ZERO);
+        network.ban_peer(banned, Duration::from_secs(60));
+        network.penalize(offender, |score| score.failed_handshakes += 1);
+        assert!(network.peer_score(&offender).is_some());
+        
+        network.prune_peer_scores();
+        assert!(!network.banned_peers.contains_key(&expired));
+        assert!(network.is_banned(&banned));
+        assert!(network.peer_score(&offender).is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_reputation_anchor_through_dht() {
+        let (a_event_tx, mut a_event_rx, a_command_tx, a_command_rx) = create_network_channels();
+        let mut node_a = Network::new(a_event_tx, a_command_rx, Box::new(AcceptAll)).unwrap();
+        node_a.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let a_id = node_a.local_peer_id();
+        let mut alice = node_a.reputation_manager(a_command_tx, 2);
+        tokio::spawn(node_a.run());
+        
+        let a_address = match wait_for_event(&mut a_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Node A did not start listening: {:?}", other),
+        };
+        
+        let (b_event_tx, mut b_event_rx, b_command_tx, b_command_rx) = create_network_channels();
+        let mut node_b = Network::new(b_event_tx, b_command_rx, Box::new(AcceptAll)).unwrap();
+        // Listening lets node A learn an address for B and replicate to it
+        node_b.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let bob = node_b.reputation_manager(b_command_tx.clone(), 2);
+        tokio::spawn(node_b.run());
+        
+        b_command_tx.send(NetworkCommand::DialPeer {
+            peer_id: a_id,
+            address: a_address,
+        }).await.unwrap();
+        assert!(wait_for_event(&mut b_event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerConnected { .. })
+        }).await.is_some());
+        
+        // Both reporters sign; the anchor is replicated once each side knows the other speaks Kademlia
+        let subject = PeerId::random();
+        let mut anchor = alice.propose_anchor(subject, -20, b"flooded the mesh", vec![bob.local_peer_id()]).unwrap();
+        bob.cosign(&mut anchor).unwrap();
+        alice.add_signatures(&anchor).unwrap();
+        
+        let mut published = Err(NetworkError::DhtError("not attempted".to_string()));
+        for _ in 0..20 {
+            published = alice.publish_anchor(&subject, -20, b"flooded the mesh").await;
+            if published.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        published.unwrap();
+        
+        let fetched = bob.fetch_anchor(&subject).await.unwrap().expect("anchor in the DHT");
+        assert_eq!(fetched.score, -20);
+        assert_eq!(fetched.multi_sig.len(), 2);
+        
+        // Anchored by node A alone: A accepts it, B does not
+        let lone_subject = PeerId::random();
+        alice.publish_anchor(&lone_subject, 5, b"helped relay").await.unwrap();
+        assert!(alice.fetch_anchor(&lone_subject).await.unwrap().is_some());
+        assert!(bob.fetch_anchor(&lone_subject).await.unwrap().is_none());
+        assert!(bob.fetch_anchor(&PeerId::random()).await.unwrap().is_none());
+    }
+    
+    #[tokio::test]
+    async fn test_bootstrap_through_known_peer() {
+        let (a_event_tx, mut a_event_rx, _a_command_tx, a_command_rx) = create_network_channels();
+        let mut node_a = Network::new(a_event_tx, a_command_rx, Box::new(AcceptAll)).unwrap();
+        node_a.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let a_id = node_a.local_peer_id();
+        tokio::spawn(node_a.run());
+        
+        let a_address: Multiaddr = match wait_for_event(&mut a_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address.parse().unwrap(),
+            other => panic!("Node A did not start listening: {:?}", other),
+        };
+        
+        let (b_event_tx, mut b_event_rx, _b_command_tx, b_command_rx) = create_network_channels();
+        let node_b = Network::new(b_event_tx, b_command_rx, Box::new(AcceptAll))
+            .unwrap()
+            .with_bootstrap_peers(vec![(a_id, a_address)]);
+        tokio::spawn(node_b.run());
+        
+        let completed = wait_for_event(&mut b_event_rx, Duration::from_secs(15), |e| {
+            matches!(e, NetworkEvent::BootstrapCompleted { .. })
+        }).await;
+        match completed {
+            Some(NetworkEvent::BootstrapCompleted { known_peers }) => assert!(known_peers >= 1),
+            other => panic!("Node B did not bootstrap: {:?}", other),
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_silent_peer_reported_unresponsive() {
+        let (peer_event_tx, mut peer_event_rx, peer_command_tx, peer_command_rx) = create_network_channels();
+        let mut peer = Network::new(peer_event_tx, peer_command_rx, Box::new(AcceptAll)).unwrap();
+        peer.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        let peer_id = peer.local_peer_id();
+        tokio::spawn(peer.run());
+        
+        let peer_address = match wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Peer did not start listening: {:?}", other),
+        };
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        network.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        network.set_liveness_timeout(Duration::from_millis(100));
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id,
+            address: peer_address,
+        }).await.unwrap();
+        
+        let ready = wait_for_event(&mut peer_event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerReadyForMessages { .. })
+        }).await;
+        assert!(ready.is_some(), "Node never subscribed");
+        
+        // The peer talks for a while, then goes silent
+        let mut last_heard = Instant::now();
+        for i in 0..3u8 {
+            peer_command_tx.send(NetworkCommand::SendMessage {
+                to: PeerId::random(),
+                data: vec![i; 16],
+                priority: MessagePriority::Interactive,
+            }).await.unwrap();
+            
+            let received = wait_for_event(&mut event_rx, Duration::from_secs(5), |e| {
+                matches!(e, NetworkEvent::MessageReceived { from, .. } if *from == peer_id)
+            }).await;
+            assert!(received.is_some());
+            last_heard = Instant::now();
+        }
+        
+        let unresponsive = wait_for_event(&mut event_rx, Duration::from_secs(1), |e| {
+            matches!(e, NetworkEvent::PeerUnresponsive { .. })
+        }).await;
+        let elapsed = last_heard.elapsed();
+        
+        assert!(matches!(unresponsive, Some(NetworkEvent::PeerUnresponsive { peer_id: p }) if p == peer_id));
+        // 100 ms timeout plus at most one 50 ms check interval, with some scheduling slack
+        assert!(elapsed <= Duration::from_millis(150) + Duration::from_millis(30), "took {:?}", elapsed);
+    }
+    
+    async fn get_peer_stats(command_tx: &mpsc::Sender<NetworkCommand>) -> HashMap<PeerId, PeerStats> {
+        let (response, rx) = oneshot::channel();
+        command_tx.send(NetworkCommand::GetPeerStats { response }).await.unwrap();
+        rx.await.unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_peer_stats_accounting() {
+        let (peer_event_tx, mut peer_event_rx, peer_command_tx, peer_command_rx) = create_network_channels();
+        let mut peer = Network::new(peer_event_tx, peer_command_rx, Box::new(AcceptAll)).unwrap();
+        peer.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(peer.run());
+        
+        let peer_address = match wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Peer did not start listening: {:?}", other),
+        };
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        network.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: PeerId::random(),
+            address: peer_address,
+        }).await.unwrap();
+        
+        let remote = match wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerReadyForMessages { .. })
+        }).await {
+            Some(NetworkEvent::PeerReadyForMessages { peer_id }) => peer_id,
+            other => panic!("Mesh peer never subscribed: {:?}", other),
+        };
+        
+        let sizes = [10usize, 20, 30, 40, 50];
+        for (i, size) in sizes.iter().enumerate() {
+            command_tx.send(NetworkCommand::SendMessage {
+                to: remote,
+                data: vec![i as u8; *size],
+                priority: MessagePriority::Interactive,
+            }).await.unwrap();
+        }
+        
+        let stats = get_peer_stats(&command_tx).await;
+        let remote_stats = stats.get(&remote).expect("no stats for remote peer");
+        assert_eq!(remote_stats.bytes_sent, 150);
+        assert_eq!(remote_stats.messages_sent, 5);
+        
+        // The receiving side accounts for the same payload bytes
+        for _ in 0..sizes.len() {
+            let received = wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+                matches!(e, NetworkEvent::MessageReceived { .. })
+            }).await;
+            assert!(received.is_some());
+        }
+        let peer_stats = get_peer_stats(&peer_command_tx).await;
+        let bytes_received: u64 = peer_stats.values().map(|s| s.bytes_received).sum();
+        assert_eq!(bytes_received, 150);
+    }
+    
+    #[tokio::test]
+    async fn test_metrics_count_network_events() {
+        let (peer_event_tx, mut peer_event_rx, _peer_command_tx, peer_command_rx) = create_network_channels();
+        let mut peer = Network::new(peer_event_tx, peer_command_rx, Box::new(AcceptAll)).unwrap();
+        let peer_metrics = Arc::new(MetricsExporter::new());
+        peer.set_metrics_exporter(peer_metrics.clone());
+        peer.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(peer.run());
+        
+        let peer_address = match wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Peer did not start listening: {:?}", other),
+        };
+        
+        // Something that accepts TCP but does not speak libp2p
+        let garbage = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let garbage_address = format!("/ip4/127.0.0.1/tcp/{}", garbage.local_addr().unwrap().port());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = garbage.accept().await {
+                let _ = tokio::io::AsyncWriteExt::write_all(&mut socket, b"HTTP/1.1 400 Bad Request\r\n\r\n").await;
+            }
+        });
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        let metrics = Arc::new(MetricsExporter::new());
+        network.set_metrics_exporter(metrics.clone());
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: PeerId::random(),
+            address: garbage_address,
+        }).await.unwrap();
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: PeerId::random(),
+            address: "/ip4/127.0.0.1/tcp/1".to_string(),
+        }).await.unwrap();
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id: PeerId::random(),
+            address: peer_address,
+        }).await.unwrap();
+        
+        let remote = match wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerReadyForMessages { .. })
+        }).await {
+            Some(NetworkEvent::PeerReadyForMessages { peer_id }) => peer_id,
+            other => panic!("Mesh peer never subscribed: {:?}", other),
+        };
+        for size in [100usize, 200] {
+            command_tx.send(NetworkCommand::SendMessage {
+                to: remote,
+                data: vec![7; size],
+                priority: MessagePriority::Interactive,
+            }).await.unwrap();
+        }
+        for _ in 0..2 {
+            let received = wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+                matches!(e, NetworkEvent::MessageReceived { .. })
+            }).await;
+            assert!(received.is_some());
+        }
+        
+        let value = |metrics: &MetricsExporter, name: &str| {
+            metrics.counters().iter().find(|(counter, ..)| *counter == name).unwrap().2
+        };
+        assert_eq!(value(&metrics, "peers_connected_total"), 1);
+        assert_eq!(value(&metrics, "messages_sent_total"), 2);
+        assert_eq!(value(&metrics, "bytes_sent_total"), 300);
+        assert_eq!(value(&metrics, "handshake_failures_total"), 1);
+        assert_eq!(value(&peer_metrics, "peers_connected_total"), 1);
+        assert_eq!(value(&peer_metrics, "messages_received_total"), 2);
+        assert_eq!(value(&peer_metrics, "bytes_received_total"), 300);
+        assert!(metrics.render_prometheus().contains("\nhandshake_failures_total 1\n"));
+    }
+    
+    async fn get_mesh_topology(command_tx: &mpsc::Sender<NetworkCommand>) -> HashMap<String, Vec<PeerId>> {
+        let (response, rx) = oneshot::channel();
+        command_tx.send(NetworkCommand::GetMeshTopology { response }).await.unwrap();
+        rx.await.unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_connected_peers_share_mesh() {
+        let (peer_event_tx, mut peer_event_rx, peer_command_tx, peer_command_rx) = create_network_channels();
+        let mut peer = Network::new(peer_event_tx, peer_command_rx, Box::new(AcceptAll)).unwrap();
+        let peer_id = peer.local_peer_id();
+        peer.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(peer.run());
+        
+        let peer_address = match wait_for_event(&mut peer_event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Peer did not start listening: {:?}", other),
+        };
+        
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::new(event_tx, command_rx, Box::new(AcceptAll)).unwrap();
+        let network_id = network.local_peer_id();
+        network.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(network.run());
+        
+        command_tx.send(NetworkCommand::DialPeer {
+            peer_id,
+            address: peer_address,
+        }).await.unwrap();
+        let ready = wait_for_event(&mut event_rx, Duration::from_secs(10), |e| {
+            matches!(e, NetworkEvent::PeerReadyForMessages { .. })
+        }).await;
+        assert!(ready.is_some(), "Mesh peer never subscribed");
+        
+        // Grafting needs a round trip after the subscription
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+        loop {
+            let local = get_mesh_topology(&command_tx).await;
+            let remote = get_mesh_topology(&peer_command_tx).await;
+            if local.get("otter-chat").is_some_and(|peers| peers.contains(&peer_id))
+                && remote.get("otter-chat").is_some_and(|peers| peers.contains(&network_id))
+            {
+                break;
+            }
+            assert!(tokio::time::Instant::now() < deadline, "Peers never joined each other's mesh: {:?} {:?}", local, remote);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+    
+    /// Start a node with one second heartbeats, returning its listen address
+    async fn spawn_fast_node(
+        params: GossipsubParams,
+    ) -> (PeerId, String, mpsc::Sender<NetworkCommand>, mpsc::Receiver<NetworkEvent>) {
+        let (event_tx, mut event_rx, command_tx, command_rx) = create_network_channels();
+        let mut network = Network::with_gossipsub_params(event_tx, command_rx, Box::new(AcceptAll), params).unwrap();
+        let peer_id = network.local_peer_id();
+        network.listen("/ip4/127.0.0.1/tcp/0").unwrap();
+        tokio::spawn(network.run());
+        
+        let address = match wait_for_event(&mut event_rx, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::ListeningOn { .. })
+        }).await {
+            Some(NetworkEvent::ListeningOn { address }) => address,
+            other => panic!("Node did not start listening: {:?}", other),
+        };
+        (peer_id, address, command_tx, event_rx)
+    }
+    
+    #[tokio::test]
+    async fn test_high_priority_peer_joins_full_mesh() {
+        let fast = GossipsubParams {
+            heartbeat_secs: 1,
+            ..GossipsubParams::lan()
+        };
+        // Full at two mesh peers
+        let small = GossipsubParams {
+            d: 2,
+            d_low: 1,
+            d_high: 2,
+            d_lazy: 2,
+            heartbeat_secs: 1,
+        };
+        let (hub_id, hub_address, hub_tx, mut hub_events) = spawn_fast_node(small).await;
+        
+        // Unknown peers connect first and fill the hub's mesh
+        let mut low_nodes = Vec::new();
+        for _ in 0..3 {
+            let (_, _, command_tx, event_rx) = spawn_fast_node(fast).await;
+            command_tx.send(NetworkCommand::DialPeer { peer_id: hub_id, address: hub_address.clone() }).await.unwrap();
+            low_nodes.push((command_tx, event_rx));
+        }
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+        while get_mesh_topology(&hub_tx).await.get("otter-chat").map_or(0, Vec::len) < 2 {
+            assert!(tokio::time::Instant::now() < deadline, "Hub mesh never filled");
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        
+        let (high_id, high_address, _high_tx, _high_events) = spawn_fast_node(fast).await;
+        hub_tx.send(NetworkCommand::SetPeerPriority { peer_id: high_id, priority: ConnectionPriority::High }).await.unwrap();
+        let changed = wait_for_event(&mut hub_events, Duration::from_secs(5), |e| {
+            matches!(e, NetworkEvent::PriorityChanged { .. })
+        }).await;
+        assert!(matches!(
+            changed,
+            Some(NetworkEvent::PriorityChanged { peer_id, priority: ConnectionPriority::High }) if peer_id == high_id
+        ));
+        hub_tx.send(NetworkCommand::DialPeer { peer_id: high_id, address: high_address }).await.unwrap();
+        
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+        loop {
+            let mesh = get_mesh_topology(&hub_tx).await;
+            if mesh.get("otter-chat").is_some_and(|peers| peers.contains(&high_id)) {
+                break;
+            }
+            assert!(tokio::time::Instant::now() < deadline, "High-priority peer never joined the full mesh: {:?}", mesh);
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        
+        // Pruning the overfull mesh keeps it
+        for _ in 0..4 {
+            tokio::time::sleep(Duration::from_millis(750)).await;
+            let mesh = get_mesh_topology(&hub_tx).await;
+            let peers = &mesh["otter-chat"];
+            assert!(peers.contains(&high_id), "High-priority peer was pruned: {:?}", peers);
+            assert!(peers.len() <= 3);
+        }
+    }
 }
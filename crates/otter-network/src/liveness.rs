@@ -0,0 +1,98 @@
+//! # Peer Liveness
+//!
+//! Detects peers that stopped talking to us without the connection closing,
+//! e.g. manually dialed peers behind a NAT that silently dropped the mapping.
+//! mDNS expiry only covers LAN peers, so every peer is tracked by the last
+//! time we heard from it over gossipsub or ping.
+
+use libp2p::PeerId;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Silence after which a peer is considered unresponsive
+///
+/// Four times libp2p's default ping interval, so one lost ping is tolerated.
+pub const DEFAULT_LIVENESS_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Last time each connected peer was heard from
+#[derive(Debug, Clone)]
+pub struct PeerLivenessTracker {
+    last_heartbeat: HashMap<PeerId, Instant>,
+    timeout: Duration,
+}
+
+impl PeerLivenessTracker {
+    /// Create a tracker with the given timeout
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            last_heartbeat: HashMap::new(),
+            timeout,
+        }
+    }
+    
+    /// Record that a peer was heard from just now
+    pub fn heartbeat(&mut self, peer_id: PeerId) {
+        self.last_heartbeat.insert(peer_id, Instant::now());
+    }
+    
+    /// Stop tracking a peer
+    pub fn remove(&mut self, peer_id: &PeerId) {
+        self.last_heartbeat.remove(peer_id);
+    }
+    
+    /// Get the timeout
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+    
+    /// Change the timeout
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+    
+    /// Peers silent for longer than the timeout
+    ///
+    /// Returned peers are no longer tracked until they are heard from again.
+    pub fn check_dead_peers(&mut self) -> Vec<PeerId> {
+        let now = Instant::now();
+        let timeout = self.timeout;
+        let dead: Vec<PeerId> = self
+            .last_heartbeat
+            .iter()
+            .filter(|(_, last)| now.duration_since(**last) >= timeout)
+            .map(|(peer_id, _)| *peer_id)
+            .collect();
+        
+        for peer_id in &dead {
+            self.last_heartbeat.remove(peer_id);
+        }
+        dead
+    }
+}
+
+impl Default for PeerLivenessTracker {
+    fn default() -> Self {
+        Self::new(DEFAULT_LIVENESS_TIMEOUT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_check_dead_peers() {
+        let mut tracker = PeerLivenessTracker::new(Duration::from_millis(20));
+        let quiet = PeerId::random();
+        let chatty = PeerId::random();
+        
+        tracker.heartbeat(quiet);
+        std::thread::sleep(Duration::from_millis(25));
+        tracker.heartbeat(chatty);
+        
+        assert_eq!(tracker.check_dead_peers(), vec![quiet]);
+        
+        // Reported once, then forgotten until heard from again
+        assert!(tracker.check_dead_peers().is_empty());
+    }
+}
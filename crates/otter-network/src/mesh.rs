@@ -0,0 +1,141 @@
+//! # Gossipsub Mesh Parameters
+//!
+//! The mesh degree trades redundancy against bandwidth: a two-peer LAN gains
+//! nothing from six mesh links, while a large network needs more than six to
+//! spread messages quickly.
+//!
+//! Trusted peers keep their mesh slots through peer scoring: each peer's
+//! `ConnectionPriority` is its application score. Pruning an overfull mesh
+//! always retains the best-scored peers, and a full mesh of lower-priority
+//! peers opportunistically grafts higher-priority ones, so
+//! `RESERVED_HIGH_PRIORITY_SLOTS` of the mesh are held for `High` peers.
+//! `High` peers are dialed by this node, which makes them outbound peers
+//! that `mesh_outbound_min` keeps in the mesh as well.
+
+use crate::priority::ConnectionPriority;
+use libp2p::gossipsub;
+use std::time::Duration;
+
+/// Mesh slots held for `ConnectionPriority::High` peers
+pub const RESERVED_HIGH_PRIORITY_SLOTS: usize = 2;
+
+/// Heartbeats between attempts to graft higher-priority peers into a full mesh
+const OPPORTUNISTIC_GRAFT_TICKS: u64 = 3;
+
+/// Mesh degree and heartbeat settings for gossipsub
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GossipsubParams {
+    /// Target number of mesh peers per topic
+    pub d: usize,
+    /// Fewer mesh peers than this triggers grafting
+    pub d_low: usize,
+    /// More mesh peers than this triggers pruning
+    pub d_high: usize,
+    /// Number of non-mesh peers that receive gossip
+    pub d_lazy: usize,
+    /// Seconds between mesh maintenance heartbeats
+    pub heartbeat_secs: u64,
+}
+
+impl GossipsubParams {
+    /// Small local networks
+    pub fn lan() -> Self {
+        Self {
+            d: 3,
+            d_low: 2,
+            d_high: 6,
+            d_lazy: 3,
+            heartbeat_secs: 10,
+        }
+    }
+    
+    /// Large networks spread across the internet
+    pub fn wan() -> Self {
+        Self {
+            d: 8,
+            d_low: 6,
+            d_high: 16,
+            d_lazy: 8,
+            heartbeat_secs: 10,
+        }
+    }
+    
+    /// Build a gossipsub config with these parameters and strict validation
+    ///
+    /// libp2p requires the outbound peer minimum to be at most half of `d`,
+    /// so small meshes lower it below the default of 2.
+    pub fn to_config(&self) -> Result<gossipsub::Config, &'static str> {
+        self.builder().validation_mode(gossipsub::ValidationMode::Strict).build()
+    }
+    
+    /// Build a gossipsub config for a node that only listens
+    ///
+    /// Signatures are not checked, so unsigned messages from older peers are accepted.
+    pub fn to_listener_config(&self) -> Result<gossipsub::Config, &'static str> {
+        self.builder().validation_mode(gossipsub::ValidationMode::None).build()
+    }
+    
+    fn builder(&self) -> gossipsub::ConfigBuilder {
+        let mut builder = gossipsub::ConfigBuilder::default();
+        builder
+            .mesh_n(self.d)
+            .mesh_n_low(self.d_low)
+            .mesh_n_high(self.d_high)
+            .mesh_outbound_min((self.d / 2).min(self.d_low).min(RESERVED_HIGH_PRIORITY_SLOTS))
+            .retain_scores(RESERVED_HIGH_PRIORITY_SLOTS.min(self.d))
+            .opportunistic_graft_peers(RESERVED_HIGH_PRIORITY_SLOTS)
+            .opportunistic_graft_ticks(OPPORTUNISTIC_GRAFT_TICKS)
+            .gossip_lazy(self.d_lazy)
+            .heartbeat_interval(Duration::from_secs(self.heartbeat_secs));
+        builder
+    }
+}
+
+/// Peer scoring that ranks mesh peers by `ConnectionPriority` alone
+///
+/// Topic scores and IP colocation penalties are off, so scores never turn
+/// negative on their own and LAN peers sharing an address are not punished.
+pub(crate) fn peer_score_config() -> (gossipsub::PeerScoreParams, gossipsub::PeerScoreThresholds) {
+    let params = gossipsub::PeerScoreParams {
+        app_specific_weight: 1.0,
+        ip_colocation_factor_weight: 0.0,
+        ..Default::default()
+    };
+    let thresholds = gossipsub::PeerScoreThresholds {
+        // A mesh whose median peer is not High grafts High peers
+        opportunistic_graft_threshold: ConnectionPriority::High.application_score(),
+        ..Default::default()
+    };
+    (params, thresholds)
+}
+
+impl Default for GossipsubParams {
+    /// The libp2p defaults, suitable for mid-sized networks
+    fn default() -> Self {
+        Self {
+            d: 6,
+            d_low: 5,
+            d_high: 12,
+            d_lazy: 6,
+            heartbeat_secs: 10,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_invalid_params_are_rejected() {
+        let inverted = GossipsubParams {
+            d_low: 10,
+            ..GossipsubParams::default()
+        };
+        assert!(inverted.to_config().is_err());
+        
+        let config = GossipsubParams::lan().to_config().unwrap();
+        assert_eq!(config.mesh_outbound_min(), 1);
+        assert_eq!(config.retain_scores(), RESERVED_HIGH_PRIORITY_SLOTS);
+    }
+}
@@ -0,0 +1,127 @@
+//! # Prometheus Metrics
+//!
+//! Process-wide network counters for scraping by Prometheus. A
+//! `MetricsExporter` is shared between the `Network`, which counts events as
+//! they happen, and whatever serves the `/metrics` endpoint, which renders
+//! the counters in the Prometheus text exposition format.
+
+use std::fmt::Write as _;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Network counters in Prometheus form
+#[derive(Debug, Default)]
+pub struct MetricsExporter {
+    peers_connected_total: AtomicU64,
+    messages_sent_total: AtomicU64,
+    messages_received_total: AtomicU64,
+    bytes_sent_total: AtomicU64,
+    bytes_received_total: AtomicU64,
+    handshake_failures_total: AtomicU64,
+}
+
+impl MetricsExporter {
+    /// Create an exporter with every counter at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Count a peer that connected
+    pub fn peer_connected(&self) {
+        self.peers_connected_total.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Count a published message of `bytes` payload bytes
+    pub fn message_sent(&self, bytes: u64) {
+        self.messages_sent_total.fetch_add(1, Ordering::Relaxed);
+        self.bytes_sent_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+    
+    /// Count `bytes` payload bytes received, possibly a fragment of a message
+    pub fn bytes_received(&self, bytes: u64) {
+        self.bytes_received_total.fetch_add(bytes, Ordering::Relaxed);
+    }
+    
+    /// Count a complete received message
+    pub fn message_received(&self) {
+        self.messages_received_total.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Count a connection that failed its transport handshake or key check
+    pub fn handshake_failed(&self) {
+        self.handshake_failures_total.fetch_add(1, Ordering::Relaxed);
+    }
+    
+    /// Every counter as `(name, help, value)`
+    pub fn counters(&self) -> [(&'static str, &'static str, u64); 6] {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        [
+            ("peers_connected_total", "Peers connected since startup", load(&self.peers_connected_total)),
+            ("messages_sent_total", "Messages published", load(&self.messages_sent_total)),
+            ("messages_received_total", "Complete messages received", load(&self.messages_received_total)),
+            ("bytes_sent_total", "Payload bytes published", load(&self.bytes_sent_total)),
+            ("bytes_received_total", "Payload bytes received", load(&self.bytes_received_total)),
+            ("handshake_failures_total", "Connections that failed the transport handshake", load(&self.handshake_failures_total)),
+        ]
+    }
+    
+    /// Counters in the Prometheus text exposition format
+    pub fn render_prometheus(&self) -> String {
+        let mut text = String::new();
+        for (name, help, value) in self.counters() {
+            let _ = writeln!(text, "# HELP {} {}", name, help);
+            let _ = writeln!(text, "# TYPE {} counter", name);
+            let _ = writeln!(text, "{} {}", name, value);
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    
+    /// Sample values of an exposition, checking the comment lines on the way
+    fn parse(text: &str) -> HashMap<String, u64> {
+        let mut samples = HashMap::new();
+        let mut typed = None;
+        for line in text.lines() {
+            if let Some(help) = line.strip_prefix("# HELP ") {
+                assert!(help.split_once(' ').is_some_and(|(_, text)| !text.is_empty()), "{}", line);
+            } else if let Some(kind) = line.strip_prefix("# TYPE ") {
+                let (name, kind) = kind.split_once(' ').unwrap();
+                assert_eq!(kind, "counter");
+                typed = Some(name.to_string());
+            } else {
+                let (name, value) = line.split_once(' ').unwrap();
+                assert_eq!(typed.take().as_deref(), Some(name), "sample without TYPE");
+                assert!(name.ends_with("_total"));
+                samples.insert(name.to_string(), value.parse().unwrap());
+            }
+        }
+        samples
+    }
+    
+    #[test]
+    fn test_render_prometheus() {
+        let metrics = MetricsExporter::new();
+        assert!(parse(&metrics.render_prometheus()).values().all(|value| *value == 0));
+        
+        metrics.peer_connected();
+        metrics.message_sent(120);
+        metrics.message_sent(30);
+        metrics.bytes_received(64);
+        metrics.bytes_received(36);
+        metrics.message_received();
+        metrics.handshake_failed();
+        
+        let samples = parse(&metrics.render_prometheus());
+        assert_eq!(samples.len(), 6);
+        assert_eq!(samples["peers_connected_total"], 1);
+        assert_eq!(samples["messages_sent_total"], 2);
+        assert_eq!(samples["bytes_sent_total"], 150);
+        assert_eq!(samples["messages_received_total"], 1);
+        assert_eq!(samples["bytes_received_total"], 100);
+        assert_eq!(samples["handshake_failures_total"], 1);
+    }
+}
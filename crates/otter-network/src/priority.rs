@@ -0,0 +1,233 @@
+//! # Send Priorities
+//!
+//! Outgoing messages wait in a priority queue so latency-sensitive traffic
+//! such as call signaling is published ahead of bulk transfers. Each event
+//! loop iteration publishes at most a fixed number of messages per class.
+//!
+//! Peers have a priority too: a `ConnectionPriority` becomes the peer's
+//! gossipsub application score, which decides who keeps a mesh slot.
+
+use libp2p::PeerId;
+use otter_identity::trust::{TrustLevel, TrustStore};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// How urgently a message should be published
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MessagePriority {
+    /// Bulk data such as file transfer chunks
+    Bulk,
+    /// Chat messages and other user-visible traffic
+    Interactive,
+    /// Call signaling (SDP offers, ICE candidates)
+    RealTime,
+}
+
+/// How strongly a peer's gossipsub mesh link is preferred
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum ConnectionPriority {
+    /// Unknown peers
+    #[default]
+    Low,
+    /// Peers seen before but not verified
+    Normal,
+    /// Trusted peers, kept in the mesh even when it is full
+    High,
+}
+
+impl ConnectionPriority {
+    /// Priority of a peer whose Otter identity has trust record `level`
+    ///
+    /// Verified (pinned) peers are `High`, first-use peers `Normal`, and
+    /// everyone else, including blocked peers and changed keys, `Low`.
+    pub fn from_trust_level(level: Option<TrustLevel>) -> Self {
+        match level {
+            Some(TrustLevel::Verified) => ConnectionPriority::High,
+            Some(TrustLevel::Unknown) => ConnectionPriority::Normal,
+            Some(TrustLevel::KeyChanged | TrustLevel::Blocked) | None => ConnectionPriority::Low,
+        }
+    }
+    
+    /// Priority of the peer with Otter identity `peer_id`
+    pub fn for_peer(trust_store: &TrustStore, peer_id: &otter_identity::PeerId) -> Self {
+        Self::from_trust_level(trust_store.get(peer_id).map(|record| record.trust_level))
+    }
+    
+    /// Gossipsub application score of the priority
+    pub fn application_score(self) -> f64 {
+        match self {
+            ConnectionPriority::High => 100.0,
+            ConnectionPriority::Normal => 10.0,
+            ConnectionPriority::Low => 0.0,
+        }
+    }
+}
+
+/// Messages of each priority published per event loop iteration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueBudget {
+    pub real_time: usize,
+    pub interactive: usize,
+    pub bulk: usize,
+}
+
+impl QueueBudget {
+    fn limit(&self, priority: MessagePriority) -> usize {
+        match priority {
+            MessagePriority::RealTime => self.real_time,
+            MessagePriority::Interactive => self.interactive,
+            MessagePriority::Bulk => self.bulk,
+        }
+    }
+}
+
+impl Default for QueueBudget {
+    fn default() -> Self {
+        Self {
+            real_time: 32,
+            interactive: 16,
+            bulk: 4,
+        }
+    }
+}
+
+/// A message waiting to be published
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PriorityMessage {
+    pub priority: MessagePriority,
+    pub to: PeerId,
+    pub data: Vec<u8>,
+    /// Enqueue order, so messages of equal priority stay FIFO
+    sequence: u64,
+}
+
+impl Ord for PriorityMessage {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for PriorityMessage {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Outgoing messages ordered by priority, then by arrival
+#[derive(Debug, Default)]
+pub struct SendQueue {
+    heap: BinaryHeap<PriorityMessage>,
+    next_sequence: u64,
+}
+
+impl SendQueue {
+    /// Create an empty queue
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Queue a message for publishing
+    pub fn push(&mut self, to: PeerId, data: Vec<u8>, priority: MessagePriority) {
+        self.heap.push(PriorityMessage {
+            priority,
+            to,
+            data,
+            sequence: self.next_sequence,
+        });
+        self.next_sequence += 1;
+    }
+    
+    /// Take the messages to publish this iteration, highest priority first
+    ///
+    /// Messages over their class's budget stay queued for the next iteration.
+    pub fn drain(&mut self, budget: &QueueBudget) -> Vec<PriorityMessage> {
+        let mut taken = Vec::new();
+        let mut deferred = Vec::new();
+        let mut counts = [0usize; 3];
+        
+        while let Some(message) = self.heap.pop() {
+            let count = &mut counts[message.priority as usize];
+            if *count < budget.limit(message.priority) {
+                *count += 1;
+                taken.push(message);
+            } else {
+                deferred.push(message);
+            }
+        }
+        
+        self.heap.extend(deferred);
+        taken
+    }
+    
+    /// Take every queued message regardless of budget
+    pub fn drain_all(&mut self) -> Vec<PriorityMessage> {
+        let mut messages = Vec::with_capacity(self.heap.len());
+        while let Some(message) = self.heap.pop() {
+            messages.push(message);
+        }
+        messages
+    }
+    
+    /// Number of queued messages as `(real_time, interactive, bulk)`
+    pub fn depth(&self) -> (usize, usize, usize) {
+        let count = |priority| self.heap.iter().filter(|m| m.priority == priority).count();
+        (
+            count(MessagePriority::RealTime),
+            count(MessagePriority::Interactive),
+            count(MessagePriority::Bulk),
+        )
+    }
+    
+    /// Whether no message is waiting
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_connection_priority_from_trust() {
+        use otter_identity::{Identity, PublicIdentity};
+        
+        let mut trust_store = TrustStore::new();
+        let pinned = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let seen = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let stranger = Identity::generate().unwrap();
+        trust_store.pin(pinned.clone()).unwrap();
+        trust_store.add_or_update(seen.clone()).unwrap();
+        
+        assert_eq!(ConnectionPriority::for_peer(&trust_store, pinned.peer_id()), ConnectionPriority::High);
+        assert_eq!(ConnectionPriority::for_peer(&trust_store, seen.peer_id()), ConnectionPriority::Normal);
+        assert_eq!(ConnectionPriority::for_peer(&trust_store, stranger.peer_id()), ConnectionPriority::Low);
+        assert_eq!(ConnectionPriority::from_trust_level(Some(TrustLevel::Blocked)), ConnectionPriority::Low);
+        assert!(ConnectionPriority::High.application_score() > ConnectionPriority::Normal.application_score());
+    }
+    
+    #[test]
+    fn test_real_time_published_before_bulk() {
+        let mut queue = SendQueue::new();
+        let to = PeerId::random();
+        for i in 0..10u8 {
+            queue.push(to, vec![i], MessagePriority::Bulk);
+        }
+        queue.push(to, b"ice-candidate".to_vec(), MessagePriority::RealTime);
+        assert_eq!(queue.depth(), (1, 0, 10));
+        
+        let budget = QueueBudget::default();
+        let first = queue.drain(&budget);
+        assert_eq!(first[0].data, b"ice-candidate".to_vec());
+        
+        // Bulk stays FIFO and is limited to its budget per iteration
+        let bulk: Vec<Vec<u8>> = first[1..].iter().map(|m| m.data.clone()).collect();
+        assert_eq!(bulk, (0..budget.bulk as u8).map(|i| vec![i]).collect::<Vec<_>>());
+        assert_eq!(queue.depth(), (0, 0, 10 - budget.bulk));
+        
+        assert_eq!(queue.drain_all().len(), 10 - budget.bulk);
+        assert!(queue.is_empty());
+    }
+}
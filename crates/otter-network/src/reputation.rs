@@ -0,0 +1,409 @@
+//! # Reputation Anchors
+//!
+//! A score kept only by the peer that computed it is easy to escape: a
+//! misbehaving peer reconnects under a fresh identity or waits for the score
+//! to be forgotten. A `ReputationAnchor` pins a score in the Kademlia DHT,
+//! signed by several reporters that observed the behaviour, so a single node
+//! cannot forge or reset it.
+//!
+//! Signatures are made with the reporters' libp2p identity keys, which are
+//! recovered from their peer IDs for verification. An anchor needs signatures
+//! from `required_signatures` of its reporters. The one exception is an
+//! anchor whose only reporter is the verifying node itself: it is accepted
+//! with that node's own signature, and by nobody else.
+
+use crate::{NetworkCommand, NetworkError};
+use chrono::{DateTime, Utc};
+use libp2p::identity::{Keypair, PublicKey};
+use libp2p::PeerId;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info};
+
+/// Domain separator of the signed anchor fields
+const ANCHOR_SIGNING_CONTEXT: &[u8] = b"otter-reputation-anchor-v1";
+
+/// DHT record key of the anchor about `peer_id`
+fn record_key(peer_id: &PeerId) -> String {
+    format!("/otter/reputation/{}", peer_id)
+}
+
+/// A reputation score signed by the peers that reported it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReputationAnchor {
+    /// Peer the score is about
+    pub peer_id: PeerId,
+    /// Reputation score
+    pub score: i32,
+    /// BLAKE3 hash of the evidence behind the score
+    pub evidence_hash: [u8; 32],
+    /// Peers vouching for the score
+    pub reporters: Vec<PeerId>,
+    /// When the anchor was created
+    pub timestamp: DateTime<Utc>,
+    /// Reporter signatures over the fields above
+    pub multi_sig: Vec<(PeerId, Vec<u8>)>,
+}
+
+impl ReputationAnchor {
+    /// Create an unsigned anchor
+    pub fn new(peer_id: PeerId, score: i32, evidence: &[u8], reporters: Vec<PeerId>) -> Self {
+        let mut unique = HashSet::new();
+        let reporters = reporters.into_iter().filter(|reporter| unique.insert(*reporter)).collect();
+        
+        Self {
+            peer_id,
+            score,
+            evidence_hash: *blake3::hash(evidence).as_bytes(),
+            reporters,
+            timestamp: Utc::now(),
+            multi_sig: Vec::new(),
+        }
+    }
+    
+    /// Whether the anchor was made for `score` backed by `evidence`
+    pub fn matches(&self, score: i32, evidence: &[u8]) -> bool {
+        self.score == score && self.evidence_hash == *blake3::hash(evidence).as_bytes()
+    }
+    
+    /// Bytes covered by the reporter signatures
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = ANCHOR_SIGNING_CONTEXT.to_vec();
+        let mut push_field = |field: &[u8]| {
+            bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(field);
+        };
+        
+        push_field(&self.peer_id.to_bytes());
+        push_field(&self.score.to_be_bytes());
+        push_field(&self.evidence_hash);
+        push_field(&(self.reporters.len() as u32).to_be_bytes());
+        for reporter in &self.reporters {
+            push_field(&reporter.to_bytes());
+        }
+        push_field(&self.timestamp.timestamp().to_be_bytes());
+        push_field(&self.timestamp.timestamp_subsec_nanos().to_be_bytes());
+        bytes
+    }
+    
+    /// Sign as the reporter owning `keypair`, replacing its earlier signature
+    pub fn sign(&mut self, keypair: &Keypair) -> Result<(), NetworkError> {
+        let signer = PeerId::from(keypair.public());
+        if !self.reporters.contains(&signer) {
+            return Err(NetworkError::InvalidAnchor(format!("{} is not a reporter", signer)));
+        }
+        
+        let signature = keypair
+            .sign(&self.signed_bytes())
+            .map_err(|e| NetworkError::InvalidAnchor(e.to_string()))?;
+        self.multi_sig.retain(|(peer, _)| *peer != signer);
+        self.multi_sig.push((signer, signature));
+        Ok(())
+    }
+    
+    /// Check every signature and that enough reporters signed
+    ///
+    /// An anchor whose only reporter is `local_peer_id` needs just that
+    /// signature; any other anchor needs `required_signatures` of them.
+    pub fn verify(&self, required_signatures: usize, local_peer_id: &PeerId) -> Result<(), NetworkError> {
+        let invalid = |reason: String| Err(NetworkError::InvalidAnchor(reason));
+        if self.reporters.is_empty() {
+            return invalid("no reporters".to_string());
+        }
+        if self.reporters.contains(&self.peer_id) {
+            return invalid("a peer cannot report on itself".to_string());
+        }
+        if self.reporters.iter().collect::<HashSet<_>>().len() != self.reporters.len() {
+            return invalid("duplicate reporter".to_string());
+        }
+        
+        let signed = self.signed_bytes();
+        let mut signers = HashSet::new();
+        for (signer, signature) in &self.multi_sig {
+            if !self.reporters.contains(signer) {
+                return invalid(format!("signature from {}, who is not a reporter", signer));
+            }
+            if !signers.insert(*signer) {
+                return invalid(format!("{} signed twice", signer));
+            }
+            if !verify_signature(signer, &signed, signature) {
+                return invalid(format!("bad signature from {}", signer));
+            }
+        }
+        
+        let required = if self.reporters == [*local_peer_id] { 1 } else { required_signatures.max(1) };
+        if signers.len() < required {
+            return invalid(format!("{} of {} required signatures", signers.len(), required));
+        }
+        Ok(())
+    }
+}
+
+/// Recover the public key inlined in a peer ID
+///
+/// Returns `None` for peer IDs that only carry a hash of the key (e.g. RSA).
+fn public_key_from_peer_id(peer_id: &PeerId) -> Option<PublicKey> {
+    let bytes = peer_id.to_bytes();
+    
+    // Identity multihash: code 0x00, one-byte length, then the key itself
+    if bytes.len() < 2 || bytes[0] != 0x00 || bytes[1] as usize != bytes.len() - 2 {
+        return None;
+    }
+    
+    PublicKey::try_decode_protobuf(&bytes[2..]).ok()
+}
+
+/// Check `signature` over `message` against the key inlined in `signer`
+fn verify_signature(signer: &PeerId, message: &[u8], signature: &[u8]) -> bool {
+    public_key_from_peer_id(signer).is_some_and(|key| key.verify(message, signature))
+}
+
+/// Publishes and fetches reputation anchors through a running `Network`
+pub struct ReputationManager {
+    keypair: Keypair,
+    command_tx: mpsc::Sender<NetworkCommand>,
+    required_signatures: usize,
+    /// Anchors proposed by this node, collecting co-signatures
+    pending: HashMap<PeerId, ReputationAnchor>,
+}
+
+impl ReputationManager {
+    /// Create a manager signing with `keypair` and requiring M-of-N signatures
+    ///
+    /// `keypair` must be the node's identity key for other peers to accept
+    /// its signatures; see `Network::reputation_manager`.
+    pub fn new(keypair: Keypair, command_tx: mpsc::Sender<NetworkCommand>, required_signatures: usize) -> Self {
+        Self {
+            keypair,
+            command_tx,
+            required_signatures: required_signatures.max(1),
+            pending: HashMap::new(),
+        }
+    }
+    
+    /// Peer ID the manager signs as
+    pub fn local_peer_id(&self) -> PeerId {
+        PeerId::from(self.keypair.public())
+    }
+    
+    /// Start an anchor reported by this node and `co_reporters`
+    ///
+    /// The returned anchor carries this node's signature and is meant to be
+    /// sent to the co-reporters, whose signed copies are merged back with
+    /// `add_signatures`.
+    pub fn propose_anchor(
+        &mut self,
+        peer_id: PeerId,
+        score: i32,
+        evidence: &[u8],
+        co_reporters: Vec<PeerId>,
+    ) -> Result<ReputationAnchor, NetworkError> {
+        let mut reporters = vec![self.local_peer_id()];
+        reporters.extend(co_reporters);
+        let mut anchor = ReputationAnchor::new(peer_id, score, evidence, reporters);
+        anchor.sign(&self.keypair)?;
+        
+        self.pending.insert(peer_id, anchor.clone());
+        Ok(anchor)
+    }
+    
+    /// Add this node's signature to an anchor proposed by another reporter
+    pub fn cosign(&self, anchor: &mut ReputationAnchor) -> Result<(), NetworkError> {
+        anchor.sign(&self.keypair)
+    }
+    
+    /// Merge the valid signatures of a co-signed copy into the proposed anchor
+    pub fn add_signatures(&mut self, signed: &ReputationAnchor) -> Result<(), NetworkError> {
+        let pending = self
+            .pending
+            .get_mut(&signed.peer_id)
+            .ok_or_else(|| NetworkError::InvalidAnchor(format!("no anchor proposed for {}", signed.peer_id)))?;
+        
+        let message = pending.signed_bytes();
+        if signed.signed_bytes() != message {
+            return Err(NetworkError::InvalidAnchor("signed copy differs from the proposal".to_string()));
+        }
+        for (signer, signature) in &signed.multi_sig {
+            let known = pending.multi_sig.iter().any(|(peer, _)| peer == signer);
+            if !known && pending.reporters.contains(signer) && verify_signature(signer, &message, signature) {
+                pending.multi_sig.push((*signer, signature.clone()));
+            }
+        }
+        Ok(())
+    }
+    
+    /// Publish the anchor for `peer_id` to the DHT
+    ///
+    /// Publishes the proposed anchor for this score and evidence once enough
+    /// reporters signed it. Without a proposal the anchor is reported by this
+    /// node alone, which only this node will accept.
+    pub async fn publish_anchor(&mut self, peer_id: &PeerId, score: i32, evidence: &[u8]) -> Result<(), NetworkError> {
+        let anchor = match self.pending.get(peer_id) {
+            Some(anchor) if anchor.matches(score, evidence) => anchor.clone(),
+            _ => {
+                let mut anchor = ReputationAnchor::new(*peer_id, score, evidence, vec![self.local_peer_id()]);
+                anchor.sign(&self.keypair)?;
+                anchor
+            }
+        };
+        anchor.verify(self.required_signatures, &self.local_peer_id())?;
+        
+        let value = serde_json::to_vec(&anchor).map_err(|e| NetworkError::InvalidAnchor(e.to_string()))?;
+        let (response, result) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::PutRecord { key: record_key(peer_id), value, response })
+            .await
+            .map_err(|_| NetworkError::ShuttingDown)?;
+        result.await.map_err(|_| NetworkError::ShuttingDown)??;
+        
+        info!("Published reputation anchor for {} with {} signatures", peer_id, anchor.multi_sig.len());
+        self.pending.remove(peer_id);
+        Ok(())
+    }
+    
+    /// Fetch the newest valid anchor for `peer_id` from the DHT
+    ///
+    /// Records that fail to parse or verify are skipped.
+    pub async fn fetch_anchor(&self, peer_id: &PeerId) -> Result<Option<ReputationAnchor>, NetworkError> {
+        let (response, result) = oneshot::channel();
+        self.command_tx
+            .send(NetworkCommand::GetRecord { key: record_key(peer_id), response })
+            .await
+            .map_err(|_| NetworkError::ShuttingDown)?;
+        let records = result.await.map_err(|_| NetworkError::ShuttingDown)??;
+        
+        let local_peer_id = self.local_peer_id();
+        let anchor = records
+            .iter()
+            .filter_map(|record| {
+                let anchor: ReputationAnchor = serde_json::from_slice(record).ok()?;
+                match anchor.verify(self.required_signatures, &local_peer_id) {
+                    Ok(()) if anchor.peer_id == *peer_id => Some(anchor),
+                    Ok(()) => None,
+                    Err(e) => {
+                        debug!("Ignoring reputation anchor for {}: {}", peer_id, e);
+                        None
+                    }
+                }
+            })
+            .max_by_key(|anchor| anchor.timestamp);
+        Ok(anchor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn keypair() -> (Keypair, PeerId) {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = PeerId::from(keypair.public());
+        (keypair, peer_id)
+    }
+    
+    #[test]
+    fn test_public_key_from_peer_id() {
+        let (keypair, peer_id) = keypair();
+        assert_eq!(public_key_from_peer_id(&peer_id), Some(keypair.public()));
+    }
+    
+    #[test]
+    fn test_anchor_multisig_verification() {
+        let (alice, alice_id) = keypair();
+        let (bob, bob_id) = keypair();
+        let (carol, carol_id) = keypair();
+        let (mallory, mallory_id) = keypair();
+        let subject = PeerId::random();
+        
+        let mut anchor = ReputationAnchor::new(subject, -40, b"spam log", vec![alice_id, bob_id, carol_id, bob_id]);
+        assert_eq!(anchor.reporters, vec![alice_id, bob_id, carol_id]);
+        assert!(anchor.matches(-40, b"spam log"));
+        assert!(!anchor.matches(-40, b"other log"));
+        
+        // 2-of-3: one signature is not enough, two are
+        anchor.sign(&alice).unwrap();
+        assert!(matches!(anchor.verify(2, &alice_id), Err(NetworkError::InvalidAnchor(_))));
+        anchor.sign(&bob).unwrap();
+        anchor.verify(2, &alice_id).unwrap();
+        anchor.verify(2, &mallory_id).unwrap();
+        assert!(anchor.verify(3, &alice_id).is_err());
+        
+        // Signing again replaces the earlier signature
+        anchor.sign(&bob).unwrap();
+        assert_eq!(anchor.multi_sig.len(), 2);
+        
+        // Outsiders cannot sign, and their signatures do not count
+        assert!(anchor.sign(&mallory).is_err());
+        let mut forged = anchor.clone();
+        forged.multi_sig.push((mallory_id, mallory.sign(&anchor.signed_bytes()).unwrap()));
+        assert!(forged.verify(2, &alice_id).is_err());
+        
+        // A signer cannot be counted twice
+        let mut doubled = anchor.clone();
+        doubled.multi_sig.push(doubled.multi_sig[0].clone());
+        assert!(doubled.verify(3, &alice_id).is_err());
+        
+        // Changing any signed field breaks the signatures
+        let mut reset = anchor.clone();
+        reset.score = 100;
+        assert!(reset.verify(2, &alice_id).is_err());
+        let mut backdated = anchor.clone();
+        backdated.timestamp -= chrono::Duration::days(30);
+        assert!(backdated.verify(2, &alice_id).is_err());
+        
+        // Carol's signature is made by the wrong key
+        let mut impersonated = anchor.clone();
+        impersonated.multi_sig.push((carol_id, mallory.sign(&anchor.signed_bytes()).unwrap()));
+        assert!(impersonated.verify(2, &alice_id).is_err());
+        let mut complete = anchor;
+        complete.sign(&carol).unwrap();
+        complete.verify(3, &alice_id).unwrap();
+        
+        // Survives the DHT encoding
+        let decoded: ReputationAnchor = serde_json::from_slice(&serde_json::to_vec(&complete).unwrap()).unwrap();
+        assert_eq!(decoded, complete);
+        decoded.verify(3, &carol_id).unwrap();
+    }
+    
+    #[test]
+    fn test_single_reporter_anchor_needs_self_signature() {
+        let (alice, alice_id) = keypair();
+        let (_, bob_id) = keypair();
+        
+        let mut anchor = ReputationAnchor::new(PeerId::random(), 10, b"relayed calls", vec![alice_id]);
+        assert!(anchor.verify(2, &alice_id).is_err());
+        anchor.sign(&alice).unwrap();
+        anchor.verify(2, &alice_id).unwrap();
+        
+        // Another node sees a single reporter and wants two
+        assert!(anchor.verify(2, &bob_id).is_err());
+        
+        // Reporting on yourself is never accepted
+        let mut own = ReputationAnchor::new(alice_id, 100, b"trust me", vec![alice_id]);
+        own.sign(&alice).unwrap();
+        assert!(own.verify(1, &alice_id).is_err());
+    }
+    
+    #[test]
+    fn test_manager_collects_cosignatures() {
+        let (command_tx, _command_rx) = mpsc::channel(1);
+        let mut alice = ReputationManager::new(Keypair::generate_ed25519(), command_tx.clone(), 2);
+        let bob = ReputationManager::new(Keypair::generate_ed25519(), command_tx, 2);
+        let subject = PeerId::random();
+        
+        let proposal = alice.propose_anchor(subject, -5, b"evidence", vec![bob.local_peer_id()]).unwrap();
+        assert!(proposal.verify(2, &alice.local_peer_id()).is_err());
+        
+        let mut cosigned = proposal.clone();
+        bob.cosign(&mut cosigned).unwrap();
+        alice.add_signatures(&cosigned).unwrap();
+        alice.pending[&subject].verify(2, &bob.local_peer_id()).unwrap();
+        
+        // A copy with other fields is rejected
+        let mut altered = cosigned;
+        altered.score = 50;
+        assert!(alice.add_signatures(&altered).is_err());
+        assert_eq!(alice.pending[&subject].multi_sig.len(), 2);
+    }
+}
@@ -0,0 +1,168 @@
+//! # Connection Limits and Peer Scoring
+//!
+//! Caps how many peers and connections a node keeps, and tracks a penalty
+//! per peer for failed handshakes, decryption errors reported by the
+//! messaging layer and messages sent faster than the rate limit. A peer whose
+//! penalty reaches the ban threshold is banned: its connections are closed
+//! and new ones refused until the ban expires.
+
+use std::time::{Duration, Instant};
+
+/// Window over which the message rate is measured
+const RATE_WINDOW: Duration = Duration::from_secs(1);
+
+/// Maximum number of open connections, in total and per direction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionLimits {
+    /// Distinct connected peers
+    pub max_peers: usize,
+    /// Connections dialed by the remote peer
+    pub max_inbound: usize,
+    /// Connections dialed by this node
+    pub max_outbound: usize,
+}
+
+impl Default for ConnectionLimits {
+    fn default() -> Self {
+        Self {
+            max_peers: 64,
+            max_inbound: 48,
+            max_outbound: 32,
+        }
+    }
+}
+
+impl ConnectionLimits {
+    /// Why a new connection would exceed the limits, if it would
+    ///
+    /// `peers`, `inbound` and `outbound` count what is open without it;
+    /// `new_peer` is whether it is the first connection to its peer.
+    pub fn check(&self, new_peer: bool, peers: usize, inbound: usize, outbound: usize, dialer: bool) -> Option<&'static str> {
+        if new_peer && peers >= self.max_peers {
+            Some("peer limit reached")
+        } else if dialer && outbound >= self.max_outbound {
+            Some("outbound connection limit reached")
+        } else if !dialer && inbound >= self.max_inbound {
+            Some("inbound connection limit reached")
+        } else {
+            None
+        }
+    }
+}
+
+/// Penalties per offence and the threshold at which a peer is banned
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerScoreConfig {
+    pub failed_handshake_penalty: u32,
+    pub decryption_error_penalty: u32,
+    /// Messages a peer may send per second before each further one is penalized
+    pub max_messages_per_second: u32,
+    pub rate_violation_penalty: u32,
+    /// Penalty at which the peer is banned
+    pub ban_threshold: u32,
+    /// How long an automatic ban lasts
+    pub ban_duration: Duration,
+}
+
+impl Default for PeerScoreConfig {
+    fn default() -> Self {
+        Self {
+            failed_handshake_penalty: 20,
+            decryption_error_penalty: 10,
+            max_messages_per_second: 100,
+            rate_violation_penalty: 1,
+            ban_threshold: 100,
+            ban_duration: Duration::from_secs(3600),
+        }
+    }
+}
+
+/// Misbehaviour observed from a peer
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerScore {
+    pub failed_handshakes: u32,
+    pub decryption_errors: u32,
+    /// Messages received beyond the rate limit
+    pub rate_violations: u32,
+    window_start: Option<Instant>,
+    window_messages: u32,
+}
+
+impl PeerScore {
+    /// Count a received message
+    ///
+    /// Returns whether it exceeded the rate limit, in which case it is
+    /// recorded as a rate violation.
+    pub fn record_message(&mut self, config: &PeerScoreConfig) -> bool {
+        let now = Instant::now();
+        match self.window_start {
+            Some(start) if now.duration_since(start) < RATE_WINDOW => {}
+            _ => {
+                self.window_start = Some(now);
+                self.window_messages = 0;
+            }
+        }
+        
+        self.window_messages = self.window_messages.saturating_add(1);
+        let exceeded = self.window_messages > config.max_messages_per_second;
+        if exceeded {
+            self.rate_violations = self.rate_violations.saturating_add(1);
+        }
+        exceeded
+    }
+    
+    /// Messages received in the current one-second window
+    pub fn messages_per_second(&self) -> u32 {
+        match self.window_start {
+            Some(start) if start.elapsed() < RATE_WINDOW => self.window_messages,
+            _ => 0,
+        }
+    }
+    
+    /// Total penalty under `config`
+    pub fn penalty(&self, config: &PeerScoreConfig) -> u32 {
+        self.failed_handshakes
+            .saturating_mul(config.failed_handshake_penalty)
+            .saturating_add(self.decryption_errors.saturating_mul(config.decryption_error_penalty))
+            .saturating_add(self.rate_violations.saturating_mul(config.rate_violation_penalty))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_connection_limits() {
+        let limits = ConnectionLimits {
+            max_peers: 2,
+            max_inbound: 2,
+            max_outbound: 1,
+        };
+        
+        assert_eq!(limits.check(true, 1, 1, 0, false), None);
+        assert!(limits.check(true, 2, 1, 0, false).is_some());
+        // Another connection to an already connected peer does not count against max_peers
+        assert_eq!(limits.check(false, 2, 1, 0, false), None);
+        assert!(limits.check(false, 2, 2, 0, false).is_some());
+        assert!(limits.check(false, 2, 0, 1, true).is_some());
+    }
+    
+    #[test]
+    fn test_penalty_and_rate_limit() {
+        let config = PeerScoreConfig {
+            max_messages_per_second: 3,
+            ..PeerScoreConfig::default()
+        };
+        let mut score = PeerScore::default();
+        
+        let exceeded: Vec<bool> = (0..5).map(|_| score.record_message(&config)).collect();
+        assert_eq!(exceeded, vec![false, false, false, true, true]);
+        assert_eq!(score.messages_per_second(), 5);
+        assert_eq!(score.rate_violations, 2);
+        
+        score.failed_handshakes = 1;
+        score.decryption_errors = 3;
+        assert_eq!(score.penalty(&config), 20 + 30 + 2);
+    }
+}
@@ -0,0 +1,99 @@
+//! # Mesh Topology
+//!
+//! Records how gossipsub messages travel through this node, for debugging
+//! propagation problems. gossipsub only reports the last hop of a received
+//! message, so a trace covers the hop into this node and the forwards to its
+//! mesh peers, not the full path from the author.
+
+use libp2p::gossipsub::MessageId;
+use libp2p::PeerId;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
+
+/// Messages whose hops are kept, oldest dropped first
+pub const MAX_TRACED_MESSAGES: usize = 1000;
+
+/// One transfer of a message between two peers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PropagationHop {
+    pub from: PeerId,
+    pub to: PeerId,
+    /// When this node saw the transfer
+    pub at: Instant,
+}
+
+/// Hops of recently received messages
+#[derive(Debug, Default)]
+pub struct PropagationTracer {
+    hops: HashMap<MessageId, Vec<PropagationHop>>,
+    order: VecDeque<MessageId>,
+}
+
+impl PropagationTracer {
+    /// Record that `local` received `message_id` from `propagation_source`
+    /// and forwards it to `mesh_peers`
+    ///
+    /// The author and the peer it came from are skipped, as gossipsub does
+    /// not forward a message back to them.
+    pub fn record_message(
+        &mut self,
+        message_id: MessageId,
+        local: PeerId,
+        propagation_source: PeerId,
+        author: Option<PeerId>,
+        mesh_peers: impl IntoIterator<Item = PeerId>,
+    ) {
+        let at = Instant::now();
+        let mut hops = vec![PropagationHop {
+            from: propagation_source,
+            to: local,
+            at,
+        }];
+        hops.extend(
+            mesh_peers
+                .into_iter()
+                .filter(|peer| *peer != propagation_source && Some(*peer) != author)
+                .map(|to| PropagationHop { from: local, to, at }),
+        );
+        
+        if self.hops.insert(message_id.clone(), hops).is_none() {
+            self.order.push_back(message_id);
+            if self.order.len() > MAX_TRACED_MESSAGES {
+                if let Some(oldest) = self.order.pop_front() {
+                    self.hops.remove(&oldest);
+                }
+            }
+        }
+    }
+    
+    /// Hops recorded for `message_id`, empty if it was not seen or has expired
+    pub fn trace(&self, message_id: &[u8]) -> Vec<PropagationHop> {
+        self.hops
+            .get(&MessageId::new(message_id))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_oldest_trace_expires() {
+        let local = PeerId::random();
+        let source = PeerId::random();
+        let forward = PeerId::random();
+        let mut tracer = PropagationTracer::default();
+        
+        for i in 0..=MAX_TRACED_MESSAGES as u32 {
+            tracer.record_message(MessageId::new(&i.to_be_bytes()), local, source, Some(source), [source, forward]);
+        }
+        
+        assert!(tracer.trace(&0u32.to_be_bytes()).is_empty());
+        let hops = tracer.trace(&1u32.to_be_bytes());
+        assert_eq!(hops.len(), 2);
+        assert_eq!((hops[0].from, hops[0].to), (source, local));
+        assert_eq!((hops[1].from, hops[1].to), (local, forward));
+    }
+}
@@ -0,0 +1,100 @@
+//! # Message Validation
+//!
+//! Gossipsub's strict validation only checks the gossipsub-level signature.
+//! A [`MessageValidator`] inspects the content of every reassembled message
+//! before it is handed to the application.
+
+use libp2p::gossipsub;
+use otter_protocol::ProtocolMessage;
+
+/// Largest message the default validator accepts
+pub const MAX_MESSAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// What to do with a received message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidationDecision {
+    /// Deliver the message
+    Accept,
+    /// Drop the message and report it as `NetworkEvent::MessageRejected`
+    Reject(String),
+    /// Drop the message silently
+    Ignore,
+}
+
+/// Application-level filter for received messages
+pub trait MessageValidator: Send {
+    /// Decide whether `msg` is delivered
+    fn validate(&self, msg: &gossipsub::Message) -> ValidationDecision;
+}
+
+/// Accepts messages of at most `MAX_MESSAGE_SIZE` that decode as a `ProtocolMessage`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DefaultValidator;
+
+impl MessageValidator for DefaultValidator {
+    fn validate(&self, msg: &gossipsub::Message) -> ValidationDecision {
+        if msg.data.len() > MAX_MESSAGE_SIZE {
+            return ValidationDecision::Reject(format!(
+                "Message of {} bytes exceeds the {} byte limit",
+                msg.data.len(),
+                MAX_MESSAGE_SIZE
+            ));
+        }
+        
+        match ProtocolMessage::from_bytes(&msg.data) {
+            Ok(_) => ValidationDecision::Accept,
+            Err(e) => ValidationDecision::Reject(e.to_string()),
+        }
+    }
+}
+
+/// Accepts every message
+///
+/// For applications whose payloads are not `ProtocolMessage`s.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AcceptAll;
+
+impl MessageValidator for AcceptAll {
+    fn validate(&self, _msg: &gossipsub::Message) -> ValidationDecision {
+        ValidationDecision::Accept
+    }
+}
+
+impl<F> MessageValidator for F
+where
+    F: Fn(&gossipsub::Message) -> ValidationDecision + Send,
+{
+    fn validate(&self, msg: &gossipsub::Message) -> ValidationDecision {
+        self(msg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use otter_protocol::MessagePayload;
+    
+    fn message(data: Vec<u8>) -> gossipsub::Message {
+        gossipsub::Message {
+            source: None,
+            data,
+            sequence_number: None,
+            topic: gossipsub::IdentTopic::new("otter-chat").hash(),
+        }
+    }
+    
+    #[test]
+    fn test_default_validator() {
+        let valid = ProtocolMessage::new(MessagePayload::Ping).to_bytes().unwrap();
+        assert_eq!(DefaultValidator.validate(&message(valid)), ValidationDecision::Accept);
+        
+        assert!(matches!(
+            DefaultValidator.validate(&message(b"not msgpack".to_vec())),
+            ValidationDecision::Reject(_)
+        ));
+        assert!(matches!(
+            DefaultValidator.validate(&message(vec![0; MAX_MESSAGE_SIZE + 1])),
+            ValidationDecision::Reject(_)
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+//! # Protocol Changelog
+//!
+//! Record of what changed in each protocol version, plus the steps needed to
+//! migrate an older peer to the current `PROTOCOL_VERSION`.
+//!
+//! Add an entry here whenever `PROTOCOL_VERSION` is incremented.
+
+use crate::{Capability, PROTOCOL_VERSION};
+use std::fmt;
+
+/// A single change introduced by a protocol version
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    /// A capability peers may now advertise
+    AddedCapability(Capability),
+    /// A capability that is no longer supported
+    RemovedCapability(Capability),
+    /// A new message type on the wire
+    NewMessageType(&'static str),
+    /// A change older peers cannot interoperate with
+    BreakingChange(&'static str),
+}
+
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::AddedCapability(cap) => write!(f, "Added capability {:?}", cap),
+            ChangeKind::RemovedCapability(cap) => write!(f, "Removed capability {:?}", cap),
+            ChangeKind::NewMessageType(name) => write!(f, "New message type {}", name),
+            ChangeKind::BreakingChange(description) => write!(f, "BREAKING: {}", description),
+        }
+    }
+}
+
+/// Changelog entry for one protocol version
+#[derive(Debug, Clone)]
+pub struct ChangelogEntry {
+    /// Protocol version this entry describes
+    pub version: u32,
+    
+    /// Release date (YYYY-MM-DD)
+    pub date: &'static str,
+    
+    /// Changes introduced in this version
+    pub changes: &'static [ChangeKind],
+    
+    /// Steps a peer on the previous version must take to upgrade
+    pub migration: &'static [&'static str],
+}
+
+/// All protocol versions, oldest first
+pub const CHANGELOG: &[ChangelogEntry] = &[ChangelogEntry {
+    version: 1,
+    date: "2026-10-17",
+    changes: &[
+        ChangeKind::AddedCapability(Capability::TextMessaging),
+        ChangeKind::AddedCapability(Capability::E2EEncryption),
+        ChangeKind::AddedCapability(Capability::VoiceCall),
+        ChangeKind::NewMessageType("Handshake"),
+        ChangeKind::NewMessageType("HandshakeResponse"),
+        ChangeKind::NewMessageType("Profile"),
+        ChangeKind::NewMessageType("SignalingMessage"),
+        ChangeKind::BreakingChange("MessagePack with struct maps is the wire format"),
+        ChangeKind::BreakingChange("E2EEncryption must be advertised in every handshake"),
+    ],
+    migration: &[
+        "Encode protocol messages as MessagePack with struct maps instead of JSON",
+        "Advertise Capability::E2EEncryption in the handshake; peers without it are rejected",
+        "Use protocol ID /otter/1.0.0 when negotiating streams",
+    ],
+}];
+
+impl ChangelogEntry {
+    /// Migration instructions for going from `from_version` to the current version
+    pub fn migration_steps(from_version: u32) -> Vec<&'static str> {
+        Self::since(from_version)
+            .flat_map(|entry| entry.migration.iter().copied())
+            .collect()
+    }
+    
+    /// Whether `capability` exists at protocol `version`
+    ///
+    /// Capabilities the changelog never mentions date from the first version.
+    pub fn capability_available_in(capability: &Capability, version: u32) -> bool {
+        Self::available_in(CHANGELOG, capability, version)
+    }
+    
+    fn available_in(changelog: &[ChangelogEntry], capability: &Capability, version: u32) -> bool {
+        let mut available = true;
+        for entry in changelog {
+            for change in entry.changes {
+                match change {
+                    ChangeKind::AddedCapability(added) if added == capability => {
+                        available = entry.version <= version;
+                    }
+                    ChangeKind::RemovedCapability(removed) if removed == capability && entry.version <= version => {
+                        available = false;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        available
+    }
+    
+    /// Entries newer than `from_version`, oldest first
+    pub fn since(from_version: u32) -> impl Iterator<Item = &'static ChangelogEntry> {
+        CHANGELOG
+            .iter()
+            .filter(move |entry| entry.version > from_version && entry.version <= PROTOCOL_VERSION)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_changelog_covers_current_version() {
+        assert!(!CHANGELOG.is_empty());
+        assert_eq!(CHANGELOG.last().unwrap().version, PROTOCOL_VERSION);
+        
+        // Versions are strictly increasing
+        assert!(CHANGELOG.windows(2).all(|w| w[0].version < w[1].version));
+    }
+    
+    #[test]
+    fn test_capability_gated_by_version() {
+        let changelog = [
+            ChangelogEntry {
+                version: 1,
+                date: "2026-01-01",
+                changes: &[ChangeKind::AddedCapability(Capability::TextMessaging)],
+                migration: &[],
+            },
+            ChangelogEntry {
+                version: 2,
+                date: "2026-02-01",
+                changes: &[ChangeKind::AddedCapability(Capability::ScreenShare)],
+                migration: &[],
+            },
+            ChangelogEntry {
+                version: 3,
+                date: "2026-03-01",
+                changes: &[ChangeKind::RemovedCapability(Capability::TextMessaging)],
+                migration: &[],
+            },
+        ];
+        
+        assert!(!ChangelogEntry::available_in(&changelog, &Capability::ScreenShare, 1));
+        assert!(ChangelogEntry::available_in(&changelog, &Capability::ScreenShare, 2));
+        assert!(ChangelogEntry::available_in(&changelog, &Capability::TextMessaging, 2));
+        assert!(!ChangelogEntry::available_in(&changelog, &Capability::TextMessaging, 3));
+        assert!(ChangelogEntry::available_in(&changelog, &Capability::GroupChat, 1));
+        assert!(ChangelogEntry::capability_available_in(&Capability::E2EEncryption, PROTOCOL_VERSION));
+    }
+    
+    #[test]
+    fn test_migration_steps() {
+        assert!(!ChangelogEntry::migration_steps(0).is_empty());
+        assert!(ChangelogEntry::migration_steps(PROTOCOL_VERSION).is_empty());
+    }
+}
@@ -0,0 +1,81 @@
+//! # Delivery Status
+//!
+//! Per-message delivery progress and the receipts that advance it. A sent
+//! message moves through `Sent`, `Relayed`, `Delivered` and `Read`; it never
+//! moves back, so a late relay report cannot hide a delivery.
+
+use otter_identity::PeerId;
+use serde::{Deserialize, Serialize};
+
+/// How far a sent message has got
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeliveryStatus {
+    /// Published to the network
+    Sent,
+    /// Forwarded by a peer towards the recipient
+    Relayed { via: PeerId },
+    /// The recipient acknowledged the message
+    Delivered,
+    /// The recipient read the message
+    Read,
+    /// The message could not be sent
+    Failed(String),
+}
+
+impl DeliveryStatus {
+    /// Position in the delivery sequence; `Failed` ranks below `Sent` so any progress replaces it
+    fn rank(&self) -> u8 {
+        match self {
+            Self::Failed(_) => 0,
+            Self::Sent => 1,
+            Self::Relayed { .. } => 2,
+            Self::Delivered => 3,
+            Self::Read => 4,
+        }
+    }
+    
+    /// Whether moving from `self` to `next` is progress
+    ///
+    /// A message that got further than `Sent` can no longer fail.
+    pub fn can_advance_to(&self, next: &DeliveryStatus) -> bool {
+        match next {
+            Self::Failed(_) => matches!(self, Self::Sent),
+            _ => next.rank() > self.rank(),
+        }
+    }
+    
+    /// Receipt icon: ✓ sent or relayed, ✓✓ delivered or read, ✗ failed
+    pub fn icon(&self) -> &'static str {
+        match self {
+            Self::Sent | Self::Relayed { .. } => "✓",
+            Self::Delivered | Self::Read => "✓✓",
+            Self::Failed(_) => "✗",
+        }
+    }
+}
+
+/// Receipt sent back by the recipient of a message
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeliveryReceipt {
+    /// The message arrived
+    MessageAck { message_id: String },
+    /// The message was shown to the user
+    MarkRead { message_id: String },
+}
+
+impl DeliveryReceipt {
+    /// ID of the message the receipt is for
+    pub fn message_id(&self) -> &str {
+        match self {
+            Self::MessageAck { message_id } | Self::MarkRead { message_id } => message_id,
+        }
+    }
+    
+    /// Status the receipt reports
+    pub fn status(&self) -> DeliveryStatus {
+        match self {
+            Self::MessageAck { .. } => DeliveryStatus::Delivered,
+            Self::MarkRead { .. } => DeliveryStatus::Read,
+        }
+    }
+}
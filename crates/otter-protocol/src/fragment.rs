@@ -0,0 +1,431 @@
+//! # Message Fragmentation
+//!
+//! Splits payloads that exceed the transport's message size limit into
+//! fragments and reassembles them on the receiving side, in any order.
+//!
+//! On the wire a fragment is `FRAGMENT_MAGIC`, a fixed header and the raw
+//! chunk, so receivers can tell fragments apart from ordinary messages.
+//! The chunk is not MessagePack-encoded because that would store every byte
+//! as an integer.
+//!
+//! Incomplete messages are kept per source, so one peer cannot complete or
+//! spoil another peer's message by reusing its fragment ID, and each source
+//! may only hold a bounded number of them in memory (see [`ReassemblyLimits`]).
+
+use crate::{ProtocolError, ProtocolMessage};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    time::{Duration, Instant},
+};
+
+/// Prefix that marks a wire message as a fragment
+pub const FRAGMENT_MAGIC: &[u8; 4] = b"OTFR";
+
+/// Largest wire header a fragment adds on top of its chunk
+pub const FRAGMENT_OVERHEAD: usize = FRAGMENT_MAGIC.len() + 1 + u8::MAX as usize + 8;
+
+/// Largest number of fragments a single message may be split into
+pub const MAX_FRAGMENTS: u32 = 4096;
+
+/// How long an incomplete message is kept before it is dropped
+pub const DEFAULT_REASSEMBLY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Bounds on what a [`Reassembler`] holds in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReassemblyLimits {
+    /// Largest reassembled message, in bytes
+    pub max_message_bytes: usize,
+    
+    /// Incomplete messages kept per source
+    pub max_pending_per_source: usize,
+    
+    /// Bytes of incomplete messages kept per source
+    pub max_pending_bytes_per_source: usize,
+}
+
+impl Default for ReassemblyLimits {
+    fn default() -> Self {
+        Self {
+            max_message_bytes: 16 * 1024 * 1024,
+            max_pending_per_source: 8,
+            max_pending_bytes_per_source: 32 * 1024 * 1024,
+        }
+    }
+}
+
+/// One piece of a fragmented message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    /// Shared by all fragments of the same message
+    pub fragment_id: String,
+    
+    /// Number of fragments the message was split into
+    pub total_fragments: u32,
+    
+    /// Position of this fragment, starting at 0
+    pub index: u32,
+    
+    /// Chunk of the original bytes
+    pub data: Vec<u8>,
+}
+
+impl Fragment {
+    /// Encode for the wire: magic, ID length (u8), ID, total and index (u32 BE), chunk
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ProtocolError> {
+        let id = self.fragment_id.as_bytes();
+        let id_len = u8::try_from(id.len())
+            .map_err(|_| ProtocolError::InvalidFormat("Fragment ID too long".to_string()))?;
+        
+        let mut buf = Vec::with_capacity(FRAGMENT_MAGIC.len() + 1 + id.len() + 8 + self.data.len());
+        buf.extend_from_slice(FRAGMENT_MAGIC);
+        buf.push(id_len);
+        buf.extend_from_slice(id);
+        buf.extend_from_slice(&self.total_fragments.to_be_bytes());
+        buf.extend_from_slice(&self.index.to_be_bytes());
+        buf.extend_from_slice(&self.data);
+        Ok(buf)
+    }
+    
+    /// Decode a wire message, returning `None` if it isn't a fragment
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let body = bytes.strip_prefix(FRAGMENT_MAGIC.as_slice())?;
+        let (&id_len, body) = body.split_first()?;
+        let id_len = id_len as usize;
+        if body.len() < id_len + 8 {
+            return None;
+        }
+        
+        let (id, body) = body.split_at(id_len);
+        let (total, body) = body.split_at(4);
+        let (index, data) = body.split_at(4);
+        
+        Some(Self {
+            fragment_id: String::from_utf8(id.to_vec()).ok()?,
+            total_fragments: u32::from_be_bytes(total.try_into().ok()?),
+            index: u32::from_be_bytes(index.try_into().ok()?),
+            data: data.to_vec(),
+        })
+    }
+}
+
+/// Splits messages into fragments
+pub struct Fragmenter;
+
+impl Fragmenter {
+    /// Split a protocol message into fragments of at most `max_chunk_bytes` each
+    pub fn fragment(msg: &ProtocolMessage, max_chunk_bytes: usize) -> Result<Vec<Fragment>, ProtocolError> {
+        Self::fragment_bytes(&msg.to_bytes()?, max_chunk_bytes)
+    }
+    
+    /// Number of fragments `fragment` would produce
+    pub fn required_fragments(msg: &ProtocolMessage, max_chunk: usize) -> Result<u32, ProtocolError> {
+        Self::count(msg.to_bytes()?.len(), max_chunk)
+    }
+    
+    /// Split arbitrary bytes into fragments of at most `max_chunk_bytes` each
+    pub fn fragment_bytes(data: &[u8], max_chunk_bytes: usize) -> Result<Vec<Fragment>, ProtocolError> {
+        let total_fragments = Self::count(data.len(), max_chunk_bytes)?;
+        let fragment_id = uuid::Uuid::new_v4().to_string();
+        
+        // An empty payload still travels as a single empty fragment
+        let chunks: Vec<&[u8]> = if data.is_empty() {
+            vec![data]
+        } else {
+            data.chunks(max_chunk_bytes).collect()
+        };
+        
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, chunk)| Fragment {
+                fragment_id: fragment_id.clone(),
+                total_fragments,
+                index: index as u32,
+                data: chunk.to_vec(),
+            })
+            .collect())
+    }
+    
+    fn count(len: usize, max_chunk: usize) -> Result<u32, ProtocolError> {
+        if max_chunk == 0 {
+            return Err(ProtocolError::InvalidFormat("Fragment size must be non-zero".to_string()));
+        }
+        
+        let count = len.div_ceil(max_chunk).max(1);
+        if count > MAX_FRAGMENTS as usize {
+            return Err(ProtocolError::InvalidFormat(format!(
+                "Message needs {} fragments, limit is {}",
+                count, MAX_FRAGMENTS
+            )));
+        }
+        
+        Ok(count as u32)
+    }
+}
+
+/// A message whose fragments are still arriving
+struct PartialMessage {
+    chunks: Vec<Option<Vec<u8>>>,
+    received: u32,
+    bytes: usize,
+    started: Instant,
+}
+
+/// Incomplete messages held for one source
+#[derive(Default)]
+struct SourceUsage {
+    messages: usize,
+    bytes: usize,
+}
+
+/// Collects fragments until every piece of a message has arrived
+pub struct Reassembler {
+    /// Keyed by source and fragment ID
+    pending: HashMap<(String, String), PartialMessage>,
+    usage: HashMap<String, SourceUsage>,
+    timeout: Duration,
+    limits: ReassemblyLimits,
+}
+
+impl Reassembler {
+    /// Create a reassembler that gives up on incomplete messages after `timeout`
+    pub fn new(timeout: Duration) -> Self {
+        Self::with_limits(timeout, ReassemblyLimits::default())
+    }
+    
+    /// Create a reassembler with custom memory bounds
+    pub fn with_limits(timeout: Duration, limits: ReassemblyLimits) -> Self {
+        Self {
+            pending: HashMap::new(),
+            usage: HashMap::new(),
+            timeout,
+            limits,
+        }
+    }
+    
+    /// Add a fragment from `source`, returning the reassembled bytes once all fragments arrived
+    ///
+    /// Duplicates, fragments inconsistent with earlier ones and fragments that
+    /// would exceed the [`ReassemblyLimits`] are ignored.
+    pub fn feed(&mut self, source: &str, fragment: Fragment) -> Option<Vec<u8>> {
+        let total = fragment.total_fragments;
+        if total == 0 || total > MAX_FRAGMENTS || fragment.index >= total {
+            return None;
+        }
+        
+        // Every fragment but the last is a full chunk, so the chunk size bounds the message
+        let chunk = fragment.data.len();
+        let full_chunks = if fragment.index + 1 < total { total as usize } else { total as usize - 1 };
+        if full_chunks.saturating_mul(chunk) > self.limits.max_message_bytes {
+            return None;
+        }
+        
+        let key = (source.to_string(), fragment.fragment_id);
+        let usage = self.usage.entry(key.0.clone()).or_default();
+        if usage.bytes + chunk > self.limits.max_pending_bytes_per_source {
+            return None;
+        }
+        let partial = match self.pending.entry(key.clone()) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                if usage.messages >= self.limits.max_pending_per_source {
+                    return None;
+                }
+                usage.messages += 1;
+                entry.insert(PartialMessage {
+                    chunks: vec![None; total as usize],
+                    received: 0,
+                    bytes: 0,
+                    started: Instant::now(),
+                })
+            }
+        };
+        
+        if partial.chunks.len() != total as usize || partial.bytes + chunk > self.limits.max_message_bytes {
+            return None;
+        }
+        
+        let slot = &mut partial.chunks[fragment.index as usize];
+        if slot.is_some() {
+            return None;
+        }
+        *slot = Some(fragment.data);
+        partial.received += 1;
+        partial.bytes += chunk;
+        usage.bytes += chunk;
+        
+        if partial.received < total {
+            return None;
+        }
+        
+        let partial = self.pending.remove(&key)?;
+        self.release(&key.0, &partial);
+        Some(partial.chunks.into_iter().flatten().flatten().collect())
+    }
+    
+    /// Take a message that is no longer pending off its source's usage
+    fn release(&mut self, source: &str, partial: &PartialMessage) {
+        if let Some(usage) = self.usage.get_mut(source) {
+            usage.messages -= 1;
+            usage.bytes -= partial.bytes;
+            if usage.messages == 0 {
+                self.usage.remove(source);
+            }
+        }
+    }
+    
+    /// Drop messages that have been incomplete for longer than the timeout
+    pub fn expire(&mut self) -> Vec<ProtocolError> {
+        let timeout = self.timeout;
+        let stale: Vec<(String, String)> = self
+            .pending
+            .iter()
+            .filter(|(_, partial)| partial.started.elapsed() >= timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        
+        let mut expired = Vec::new();
+        for key in stale {
+            if let Some(partial) = self.pending.remove(&key) {
+                self.release(&key.0, &partial);
+                expired.push(ProtocolError::ReassemblyTimeout {
+                    fragment_id: key.1,
+                    received: partial.received,
+                    total: partial.chunks.len() as u32,
+                });
+            }
+        }
+        
+        expired
+    }
+    
+    /// Number of messages still being reassembled
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+impl Default for Reassembler {
+    fn default() -> Self {
+        Self::new(DEFAULT_REASSEMBLY_TIMEOUT)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MessagePayload;
+    use rand::{seq::SliceRandom, RngCore};
+    
+    #[test]
+    fn test_reassemble_shuffled_10mb_payload() {
+        let mut data = vec![0u8; 10 * 1024 * 1024];
+        rand::thread_rng().fill_bytes(&mut data);
+        let msg = ProtocolMessage::new(MessagePayload::Binary { data: data.clone() });
+        let max_chunk = 64 * 1024;
+        
+        let mut fragments = Fragmenter::fragment(&msg, max_chunk).unwrap();
+        assert_eq!(fragments.len() as u32, Fragmenter::required_fragments(&msg, max_chunk).unwrap());
+        assert!(fragments.iter().all(|f| f.data.len() <= max_chunk));
+        
+        fragments.shuffle(&mut rand::thread_rng());
+        
+        let mut reassembler = Reassembler::default();
+        let mut result = None;
+        for fragment in fragments {
+            let wire = fragment.to_bytes().unwrap();
+            if let Some(bytes) = reassembler.feed("peer", Fragment::from_bytes(&wire).unwrap()) {
+                assert!(result.is_none());
+                result = Some(bytes);
+            }
+        }
+        
+        let restored = ProtocolMessage::from_bytes(&result.unwrap()).unwrap();
+        assert_eq!(restored.message_id, msg.message_id);
+        assert!(matches!(restored.payload, MessagePayload::Binary { data: ref restored_data } if *restored_data == data));
+        assert_eq!(reassembler.pending(), 0);
+    }
+    
+    #[test]
+    fn test_incomplete_message_times_out() {
+        let fragments = Fragmenter::fragment_bytes(&[7u8; 100], 10).unwrap();
+        let mut reassembler = Reassembler::new(Duration::ZERO);
+        
+        assert!(reassembler.feed("peer", fragments[3].clone()).is_none());
+        assert!(Fragment::from_bytes(b"not a fragment").is_none());
+        
+        let expired = reassembler.expire();
+        assert!(matches!(
+            expired.as_slice(),
+            [ProtocolError::ReassemblyTimeout { received: 1, total: 10, .. }]
+        ));
+        assert_eq!(reassembler.pending(), 0);
+        
+        // The source's quota is free again afterwards
+        let mut reassembler = Reassembler::with_limits(Duration::ZERO, ReassemblyLimits {
+            max_pending_per_source: 1,
+            ..ReassemblyLimits::default()
+        });
+        assert!(reassembler.feed("peer", fragments[0].clone()).is_none());
+        reassembler.expire();
+        let other = Fragmenter::fragment_bytes(&[8u8; 20], 10).unwrap();
+        assert!(reassembler.feed("peer", other[0].clone()).is_none());
+        assert_eq!(reassembler.pending(), 1);
+    }
+    
+    #[test]
+    fn test_fragments_are_kept_apart_per_source() {
+        let data = [7u8; 100];
+        let fragments = Fragmenter::fragment_bytes(&data, 10).unwrap();
+        let mut reassembler = Reassembler::default();
+        
+        // Mallory reuses Alice's fragment ID with different chunks
+        for fragment in &fragments[..9] {
+            assert!(reassembler.feed("alice", fragment.clone()).is_none());
+        }
+        let forged = Fragment { data: vec![0u8; 10], ..fragments[9].clone() };
+        assert!(reassembler.feed("mallory", forged).is_none());
+        
+        assert_eq!(reassembler.feed("alice", fragments[9].clone()).unwrap(), data);
+        assert_eq!(reassembler.pending(), 1);
+    }
+    
+    #[test]
+    fn test_reassembly_limits() {
+        let limits = ReassemblyLimits {
+            max_message_bytes: 100,
+            max_pending_per_source: 2,
+            max_pending_bytes_per_source: 50,
+        };
+        let mut reassembler = Reassembler::with_limits(DEFAULT_REASSEMBLY_TIMEOUT, limits);
+        
+        // Announcing more fragments than the message limit allows is refused up front
+        let oversized = Fragmenter::fragment_bytes(&[1u8; 110], 10).unwrap();
+        assert!(reassembler.feed("mallory", oversized[0].clone()).is_none());
+        assert_eq!(reassembler.pending(), 0);
+        
+        // At most two incomplete messages per source
+        let messages: Vec<Vec<Fragment>> = (0..3).map(|_| Fragmenter::fragment_bytes(&[1u8; 60], 10).unwrap()).collect();
+        for message in &messages {
+            assert!(reassembler.feed("mallory", message[0].clone()).is_none());
+        }
+        assert_eq!(reassembler.pending(), 2);
+        
+        // And at most 50 pending bytes for the source, so neither message can complete
+        for fragment in &messages[0][1..4] {
+            assert!(reassembler.feed("mallory", fragment.clone()).is_none());
+        }
+        for fragment in messages[0][4..].iter().chain(&messages[1][1..]) {
+            assert!(reassembler.feed("mallory", fragment.clone()).is_none());
+        }
+        assert_eq!(reassembler.pending(), 2);
+        
+        // Other sources are unaffected
+        let fragments = Fragmenter::fragment_bytes(&[2u8; 40], 10).unwrap();
+        let mut result = None;
+        for fragment in fragments {
+            result = reassembler.feed("alice", fragment);
+        }
+        assert_eq!(result.unwrap(), vec![2u8; 40]);
+    }
+}
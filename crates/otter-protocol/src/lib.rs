@@ -3,14 +3,39 @@
 //! Protocol definition and versioning layer for the Otter decentralized chat platform.
 //!
 //! This crate provides:
-//! - Protocol versioning and negotiation
+//! - Protocol versioning and version range negotiation
 //! - Binary message format definitions
 //! - Peer handshake protocol
 //! - Capability negotiation (voice, video, file transfer, etc.)
 //! - Protocol upgrade mechanisms
+//! - Protocol changelog and migration steps
+//! - Fragmentation of messages larger than the transport limit
+//! - Message delivery status and receipts
+//! - Signed OAuth attestations that prove a sign-in without sharing the token
+//! - zstd compression of SDP payloads in signaling messages
+//! - Binary deltas of renegotiation offers against the previous offer
+//! - Typed handshake metadata
 
+pub mod changelog;
+pub mod delivery;
+pub mod fragment;
+pub mod metadata;
+pub mod oauth;
+pub mod sdp_diff;
+#[cfg(test)]
+mod test_harness;
+
+pub use changelog::{ChangeKind, ChangelogEntry, CHANGELOG};
+pub use delivery::{DeliveryReceipt, DeliveryStatus};
+pub use fragment::{Fragment, Fragmenter, Reassembler, ReassemblyLimits};
+pub use metadata::TypedMetadata;
+pub use oauth::OAuthAttestation;
+pub use sdp_diff::{SdpDiff, SdpPatch};
+
+use base64::Engine as _;
 use chrono::{DateTime, Utc};
-use otter_identity::PublicIdentity;
+use ed25519_dalek::Signature;
+use otter_identity::{Identity, PeerProfile, PublicIdentity};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use thiserror::Error;
@@ -18,9 +43,18 @@ use thiserror::Error;
 /// Current protocol version
 pub const PROTOCOL_VERSION: u32 = 1;
 
+/// Oldest protocol version this client still speaks
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
 /// Protocol identifier
 pub const PROTOCOL_ID: &str = "/otter/1.0.0";
 
+/// Domain separation prefix of the bytes a handshake signature covers
+const HANDSHAKE_SIGNATURE_CONTEXT: &[u8] = b"otter-handshake-v1";
+
+/// zstd level used for SDP payloads
+const SDP_COMPRESSION_LEVEL: i32 = 3;
+
 #[derive(Error, Debug)]
 pub enum ProtocolError {
     #[error("Incompatible protocol version: expected {expected}, got {actual}")]
@@ -33,6 +67,16 @@ pub enum ProtocolError {
     SerializationError(String),
     #[error("Invalid message format: {0}")]
     InvalidFormat(String),
+    #[error("Reassembly of {fragment_id} timed out with {received}/{total} fragments")]
+    ReassemblyTimeout { fragment_id: String, received: u32, total: u32 },
+    #[error("Invalid OAuth attestation: {0}")]
+    InvalidOAuthAttestation(String),
+    #[error("Compression failed: {0}")]
+    CompressionFailed(String),
+    #[error("Invalid SDP patch: {0}")]
+    InvalidSdpPatch(String),
+    #[error("SDP version {0} is not the last offer of the session")]
+    UnknownSdpBase(u32),
 }
 
 /// Peer capabilities that can be negotiated
@@ -56,23 +100,116 @@ pub enum Capability {
     Custom(String),
 }
 
+/// Resources a capability needs on the advertising device
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ResourceHint {
+    /// Bandwidth needed, in bits per second
+    pub min_bandwidth_bps: u32,
+    /// Share of one CPU core needed, in percent
+    pub min_cpu_percent: u8,
+    /// Whether the device only offers the capability on external power
+    pub battery_required: bool,
+}
+
+/// A capability as advertised in a handshake
+///
+/// A device can know a capability without being able to use it right now,
+/// e.g. a phone that supports video calls but is low on battery.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CapabilityAdvertisement {
+    pub capability: Capability,
+    /// Whether the capability can be used at the moment
+    pub available: bool,
+    pub resource_hint: Option<ResourceHint>,
+}
+
+impl CapabilityAdvertisement {
+    /// Advertise `capability` as usable
+    pub fn available(capability: Capability) -> Self {
+        Self {
+            capability,
+            available: true,
+            resource_hint: None,
+        }
+    }
+    
+    /// Advertise `capability` as known but not usable at the moment
+    pub fn unavailable(capability: Capability) -> Self {
+        Self {
+            available: false,
+            ..Self::available(capability)
+        }
+    }
+    
+    /// Attach the resources the capability needs
+    pub fn with_resource_hint(mut self, hint: ResourceHint) -> Self {
+        self.resource_hint = Some(hint);
+        self
+    }
+}
+
+impl From<Capability> for CapabilityAdvertisement {
+    fn from(capability: Capability) -> Self {
+        Self::available(capability)
+    }
+}
+
+/// Inclusive range of protocol versions a peer speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionRange {
+    pub min: u32,
+    pub max: u32,
+}
+
+impl VersionRange {
+    /// Range from `min` to `max`, both included
+    pub fn new(min: u32, max: u32) -> Self {
+        Self { min, max }
+    }
+    
+    /// Range holding only `version`
+    pub fn exact(version: u32) -> Self {
+        Self::new(version, version)
+    }
+    
+    /// Versions this client speaks
+    pub fn supported() -> Self {
+        Self::new(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION)
+    }
+    
+    /// Whether `version` is in the range
+    pub fn contains(&self, version: u32) -> bool {
+        self.min <= version && version <= self.max
+    }
+    
+    /// Highest version in both ranges, or `None` if they do not overlap
+    pub fn negotiate(local: VersionRange, remote: VersionRange) -> Option<u32> {
+        let highest = local.max.min(remote.max);
+        (highest >= local.min.max(remote.min)).then_some(highest)
+    }
+}
+
 /// Protocol handshake message
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Handshake {
     /// Protocol version
     pub version: u32,
     
+    /// Versions the sender speaks; older clients only send `version`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub supported_versions: Option<VersionRange>,
+    
     /// Protocol identifier
     pub protocol_id: String,
     
     /// Sender's public identity
     pub identity: PublicIdentity,
     
-    /// List of supported capabilities
-    pub capabilities: Vec<Capability>,
+    /// Advertised capabilities
+    pub capabilities: Vec<CapabilityAdvertisement>,
     
     /// Optional metadata (client info, etc.)
-    pub metadata: HashMap<String, String>,
+    pub metadata: TypedMetadata,
     
     /// Timestamp
     pub timestamp: DateTime<Utc>,
@@ -84,13 +221,14 @@ pub struct Handshake {
 
 impl Handshake {
     /// Create a new handshake message
-    pub fn new(identity: PublicIdentity, capabilities: Vec<Capability>) -> Self {
+    pub fn new(identity: PublicIdentity, capabilities: Vec<CapabilityAdvertisement>) -> Self {
         Self {
             version: PROTOCOL_VERSION,
+            supported_versions: Some(VersionRange::supported()),
             protocol_id: PROTOCOL_ID.to_string(),
             identity,
             capabilities,
-            metadata: HashMap::new(),
+            metadata: TypedMetadata::new(),
             timestamp: Utc::now(),
             signature: None,
         }
@@ -98,18 +236,77 @@ impl Handshake {
     
     /// Add metadata to the handshake
     pub fn with_metadata(mut self, key: String, value: String) -> Self {
-        self.metadata.insert(key, value);
+        self.metadata.insert_str(&key, &value);
         self
     }
     
-    /// Check if a capability is supported
+    /// Check if a capability is advertised as available
     pub fn supports(&self, capability: &Capability) -> bool {
-        self.capabilities.contains(capability)
+        self.available_capabilities().contains(&capability)
+    }
+    
+    /// Capabilities advertised as available
+    pub fn available_capabilities(&self) -> Vec<&Capability> {
+        self.capabilities
+            .iter()
+            .filter(|ad| ad.available)
+            .map(|ad| &ad.capability)
+            .collect()
+    }
+    
+    /// Sign the handshake with `identity`, replacing any previous signature
+    pub fn sign(&mut self, identity: &Identity) -> Result<(), ProtocolError> {
+        let signature = identity
+            .sign(&self.signed_bytes()?)
+            .map_err(|e| ProtocolError::InvalidHandshake(format!("Signing failed: {}", e)))?;
+        self.signature = Some(signature.to_bytes().to_vec());
+        Ok(())
+    }
+    
+    /// Check the signature against `public`
+    ///
+    /// Receivers must call this before accepting the advertised capabilities.
+    pub fn verify(&self, public: &PublicIdentity) -> Result<(), ProtocolError> {
+        let signature = self
+            .signature
+            .as_deref()
+            .ok_or_else(|| ProtocolError::InvalidHandshake("Handshake is not signed".to_string()))?;
+        let signature = Signature::from_slice(signature)
+            .map_err(|_| ProtocolError::InvalidHandshake("Malformed signature".to_string()))?;
+        public
+            .verify(&self.signed_bytes()?, &signature)
+            .map_err(|_| ProtocolError::InvalidHandshake("Signature does not match the sender".to_string()))
+    }
+    
+    /// Every field but `signature`, serialized with sorted map keys
+    ///
+    /// Going through `serde_json::Value` makes the bytes independent of the
+    /// metadata's hash map order, so they survive a round trip over the wire.
+    fn signed_bytes(&self) -> Result<Vec<u8>, ProtocolError> {
+        let mut value = serde_json::to_value(self).map_err(|e| ProtocolError::SerializationError(e.to_string()))?;
+        if let Some(fields) = value.as_object_mut() {
+            fields.remove("signature");
+        }
+        let json = serde_json::to_vec(&value).map_err(|e| ProtocolError::SerializationError(e.to_string()))?;
+        Ok([HANDSHAKE_SIGNATURE_CONTEXT, &json].concat())
+    }
+    
+    /// Versions the sender speaks, just `version` for older clients
+    pub fn version_range(&self) -> VersionRange {
+        self.supported_versions.unwrap_or(VersionRange::exact(self.version))
+    }
+    
+    /// Highest version both this client and the sender speak
+    pub fn negotiated_version(&self) -> Option<u32> {
+        VersionRange::negotiate(VersionRange::supported(), self.version_range())
     }
     
     /// Verify protocol compatibility
+    ///
+    /// The sender's version range must overlap ours. A signed handshake must
+    /// also carry a valid signature of its identity.
     pub fn is_compatible(&self) -> Result<(), ProtocolError> {
-        if self.version != PROTOCOL_VERSION {
+        if self.negotiated_version().is_none() {
             return Err(ProtocolError::IncompatibleVersion {
                 expected: PROTOCOL_VERSION,
                 actual: self.version,
@@ -129,6 +326,10 @@ impl Handshake {
             ));
         }
         
+        if self.signature.is_some() {
+            self.verify(&self.identity)?;
+        }
+        
         Ok(())
     }
     
@@ -160,17 +361,27 @@ pub struct HandshakeResponse {
     /// Optional reason for rejection
     pub reason: Option<String>,
     
+    /// Protocol version both peers use from now on (ours when rejected)
+    #[serde(default = "default_negotiated_version")]
+    pub negotiated_version: u32,
+    
     /// Timestamp
     pub timestamp: DateTime<Utc>,
 }
 
+/// Responses from before version negotiation implied the only version there was
+fn default_negotiated_version() -> u32 {
+    MIN_PROTOCOL_VERSION
+}
+
 impl HandshakeResponse {
     /// Create an acceptance response
-    pub fn accept(capabilities: Vec<Capability>) -> Self {
+    pub fn accept(capabilities: Vec<Capability>, negotiated_version: u32) -> Self {
         Self {
             accepted_capabilities: capabilities,
             accepted: true,
             reason: None,
+            negotiated_version,
             timestamp: Utc::now(),
         }
     }
@@ -181,10 +392,26 @@ impl HandshakeResponse {
             accepted_capabilities: Vec::new(),
             accepted: false,
             reason: Some(reason),
+            negotiated_version: PROTOCOL_VERSION,
             timestamp: Utc::now(),
         }
     }
     
+    /// Answer `handshake`, accepting the capabilities both sides have at the negotiated version
+    ///
+    /// The handshake must already be verified; incompatible ones are rejected.
+    pub fn respond_to(handshake: &Handshake, local: &[CapabilityAdvertisement]) -> Self {
+        if let Err(e) = handshake.is_compatible() {
+            return Self::reject(e.to_string());
+        }
+        let Some(version) = handshake.negotiated_version() else {
+            return Self::reject("No common protocol version".to_string());
+        };
+        
+        let capabilities = CapabilityMatcher::match_capabilities_at_version(local, &handshake.capabilities, version);
+        Self::accept(capabilities, version)
+    }
+    
     /// Serialize to bytes using MessagePack
     pub fn to_bytes(&self) -> Result<Vec<u8>, ProtocolError> {
         let mut buf = Vec::new();
@@ -228,6 +455,9 @@ pub enum MessagePayload {
     /// Handshake response
     HandshakeResponse(HandshakeResponse),
     
+    /// Signed profile, sent after the handshake
+    Profile(PeerProfile),
+    
     /// Text message
     Text { content: Vec<u8> },
     
@@ -245,6 +475,30 @@ pub enum MessagePayload {
     
     /// Protocol upgrade request
     ProtocolUpgrade { target_version: u32 },
+    
+    /// Announces a file that follows as `FileChunk`s
+    FileTransferStart {
+        transfer_id: String,
+        filename: String,
+        mime_type: String,
+        total_size: u64,
+        total_chunks: u32,
+        /// SHA-256 of the whole file
+        sha256: [u8; 32],
+    },
+    
+    /// One chunk of a file transfer, numbered from 0
+    FileChunk {
+        transfer_id: String,
+        sequence: u32,
+        total_chunks: u32,
+        data: Vec<u8>,
+        /// CRC32 of `data`
+        checksum: u32,
+    },
+    
+    /// All chunks of a file transfer have been sent
+    FileTransferComplete { transfer_id: String },
 }
 
 impl ProtocolMessage {
@@ -278,15 +532,30 @@ impl ProtocolMessage {
 pub struct CapabilityMatcher;
 
 impl CapabilityMatcher {
-    /// Find common capabilities between two peers
+    /// Find capabilities available on both peers
     pub fn match_capabilities(
-        local: &[Capability],
-        remote: &[Capability],
+        local: &[CapabilityAdvertisement],
+        remote: &[CapabilityAdvertisement],
     ) -> Vec<Capability> {
         local
             .iter()
-            .filter(|cap| remote.contains(cap))
-            .cloned()
+            .filter(|ad| ad.available)
+            .filter(|ad| remote.iter().any(|other| other.available && other.capability == ad.capability))
+            .map(|ad| ad.capability.clone())
+            .collect()
+    }
+    
+    /// Find capabilities available on both peers that exist at protocol `version`
+    ///
+    /// Capabilities introduced after `version`, or removed by then, are left out.
+    pub fn match_capabilities_at_version(
+        local: &[CapabilityAdvertisement],
+        remote: &[CapabilityAdvertisement],
+        version: u32,
+    ) -> Vec<Capability> {
+        Self::match_capabilities(local, remote)
+            .into_iter()
+            .filter(|capability| ChangelogEntry::capability_available_in(capability, version))
             .collect()
     }
     
@@ -312,6 +581,16 @@ pub enum SignalingMessage {
         session_id: String,
     },
     
+    /// Renegotiation offer sent as a patch against the previous offer
+    OfferDelta {
+        /// `SdpPatch` bytes
+        sdp_patch: Vec<u8>,
+        /// Session ID for tracking
+        session_id: String,
+        /// Version of the offer the patch applies to
+        base_version: u32,
+    },
+    
     /// Answer to a WebRTC offer
     Answer {
         /// SDP answer
@@ -346,6 +625,14 @@ pub enum SignalingMessage {
         reason: Option<String>,
     },
     
+    /// Refusal of an offer
+    Reject {
+        /// Session ID of the rejected offer
+        session_id: String,
+        /// Why the offer was rejected
+        reason: RejectReason,
+    },
+    
     /// Acknowledgment of received signaling message
     Ack {
         /// ID of the message being acknowledged
@@ -359,6 +646,17 @@ pub enum SignalingMessage {
     },
 }
 
+/// Why an offer was rejected
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RejectReason {
+    /// The callee is already in a call
+    Busy,
+    /// The callee declined the call
+    Declined,
+    /// The callee cannot handle the offered media
+    CapabilityMismatch,
+}
+
 /// Media type for WebRTC sessions
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub enum MediaType {
@@ -391,6 +689,10 @@ pub struct SignalingProtocolMessage {
     
     /// Requires acknowledgment
     pub requires_ack: bool,
+    
+    /// Whether the payload SDP is zstd-compressed and base64-encoded
+    #[serde(default)]
+    pub compressed: bool,
 }
 
 impl SignalingProtocolMessage {
@@ -402,19 +704,67 @@ impl SignalingProtocolMessage {
             timestamp: Utc::now(),
             sequence,
             requires_ack,
+            compressed: false,
+        }
+    }
+    
+    /// SDP of an offer or answer payload
+    fn sdp_mut(&mut self) -> Option<&mut String> {
+        match &mut self.payload {
+            SignalingMessage::Offer { sdp, .. } | SignalingMessage::Answer { sdp, .. } => Some(sdp),
+            _ => None,
         }
     }
     
+    /// Replace the SDP of an offer or answer with its zstd-compressed base64 form
+    ///
+    /// Other payloads are small and returned unchanged, as are messages that
+    /// are already compressed.
+    pub fn compress(&self) -> Result<SignalingProtocolMessage, ProtocolError> {
+        let mut message = self.clone();
+        if message.compressed {
+            return Ok(message);
+        }
+        let Some(sdp) = message.sdp_mut() else {
+            return Ok(message);
+        };
+        
+        let compressed = zstd::encode_all(sdp.as_bytes(), SDP_COMPRESSION_LEVEL)
+            .map_err(|e| ProtocolError::CompressionFailed(e.to_string()))?;
+        *sdp = base64::engine::general_purpose::STANDARD.encode(compressed);
+        message.compressed = true;
+        Ok(message)
+    }
+    
+    /// Restore the plain SDP of a compressed message
+    pub fn decompress(&self) -> Result<SignalingProtocolMessage, ProtocolError> {
+        let mut message = self.clone();
+        if !message.compressed {
+            return Ok(message);
+        }
+        if let Some(sdp) = message.sdp_mut() {
+            let compressed = base64::engine::general_purpose::STANDARD
+                .decode(sdp.as_bytes())
+                .map_err(|e| ProtocolError::CompressionFailed(e.to_string()))?;
+            let plain = zstd::decode_all(compressed.as_slice())
+                .map_err(|e| ProtocolError::CompressionFailed(e.to_string()))?;
+            *sdp = String::from_utf8(plain).map_err(|e| ProtocolError::CompressionFailed(e.to_string()))?;
+        }
+        message.compressed = false;
+        Ok(message)
+    }
+    
     /// Serialize to JSON
     pub fn to_json(&self) -> Result<String, ProtocolError> {
         serde_json::to_string(self)
             .map_err(|e| ProtocolError::SerializationError(e.to_string()))
     }
     
-    /// Deserialize from JSON
+    /// Deserialize from JSON, decompressing a compressed SDP
     pub fn from_json(json: &str) -> Result<Self, ProtocolError> {
-        serde_json::from_str(json)
-            .map_err(|e| ProtocolError::SerializationError(e.to_string()))
+        let message: Self = serde_json::from_str(json)
+            .map_err(|e| ProtocolError::SerializationError(e.to_string()))?;
+        message.decompress()
     }
     
     /// Serialize to bytes using MessagePack
@@ -461,6 +811,12 @@ pub struct SignalingSession {
     
     /// Session state
     pub state: SignalingState,
+    
+    /// Number of offers sent or received in this session
+    pub sdp_version: u32,
+    
+    /// SDP of the last offer, the base of the next `OfferDelta`
+    pub last_offer_sdp: Option<String>,
 }
 
 /// Signaling session state
@@ -495,6 +851,8 @@ impl SignalingSession {
             received_candidates: Vec::new(),
             started_at: Utc::now(),
             state: SignalingState::Idle,
+            sdp_version: 0,
+            last_offer_sdp: None,
         }
     }
     
@@ -510,6 +868,8 @@ impl SignalingSession {
             received_candidates: Vec::new(),
             started_at: Utc::now(),
             state: SignalingState::AnswerPending,
+            sdp_version: 0,
+            last_offer_sdp: None,
         }
     }
     
@@ -525,6 +885,62 @@ impl SignalingSession {
         msg
     }
     
+    /// Create an offer for `sdp`, as a delta against the last offer when that is smaller
+    ///
+    /// The first offer of a session is always sent in full.
+    pub fn create_offer(&mut self, sdp: &str, requires_ack: bool) -> SignalingProtocolMessage {
+        let delta = self
+            .last_offer_sdp
+            .as_deref()
+            .map(|base| SdpDiff::compute(base, sdp))
+            .filter(|patch| patch.len() < sdp.len());
+        let payload = match delta {
+            Some(patch) => SignalingMessage::OfferDelta {
+                sdp_patch: patch.into_bytes(),
+                session_id: self.session_id.clone(),
+                base_version: self.sdp_version,
+            },
+            None => SignalingMessage::Offer {
+                sdp: sdp.to_string(),
+                media_type: self.media_type.clone(),
+                session_id: self.session_id.clone(),
+            },
+        };
+        
+        self.sdp_version += 1;
+        self.last_offer_sdp = Some(sdp.to_string());
+        self.create_message(payload, requires_ack)
+    }
+    
+    /// SDP of a received `Offer` or `OfferDelta`, recorded as the base of the next delta
+    ///
+    /// Fails with `UnknownSdpBase` if a delta does not apply to the last offer
+    /// this side has seen; the sender should then resend the offer in full
+    /// after `reset_sdp_base`.
+    pub fn receive_offer(&mut self, payload: &SignalingMessage) -> Result<String, ProtocolError> {
+        let sdp = match payload {
+            SignalingMessage::Offer { sdp, .. } => sdp.clone(),
+            SignalingMessage::OfferDelta { sdp_patch, base_version, .. } => {
+                let base = self
+                    .last_offer_sdp
+                    .as_deref()
+                    .filter(|_| *base_version == self.sdp_version)
+                    .ok_or(ProtocolError::UnknownSdpBase(*base_version))?;
+                SdpDiff::apply(base, &SdpPatch::from_bytes(sdp_patch.clone()))?
+            }
+            _ => return Err(ProtocolError::InvalidFormat("not an offer".to_string())),
+        };
+        
+        self.sdp_version += 1;
+        self.last_offer_sdp = Some(sdp.clone());
+        Ok(sdp)
+    }
+    
+    /// Forget the last offer so the next one is sent in full
+    pub fn reset_sdp_base(&mut self) {
+        self.last_offer_sdp = None;
+    }
+    
     /// Handle received acknowledgment
     pub fn handle_ack(&mut self, message_id: &str) {
         self.pending_acks.remove(message_id);
@@ -560,7 +976,7 @@ mod tests {
         
         let handshake = Handshake::new(
             public,
-            vec![Capability::TextMessaging, Capability::E2EEncryption],
+            vec![Capability::TextMessaging.into(), Capability::E2EEncryption.into()],
         );
         
         assert_eq!(handshake.version, PROTOCOL_VERSION);
@@ -574,7 +990,7 @@ mod tests {
         
         let handshake = Handshake::new(
             public,
-            vec![Capability::E2EEncryption, Capability::TextMessaging],
+            vec![Capability::E2EEncryption.into(), Capability::TextMessaging.into()],
         );
         
         assert!(handshake.is_compatible().is_ok());
@@ -585,23 +1001,97 @@ mod tests {
         let identity = Identity::generate().unwrap();
         let public = PublicIdentity::from_identity(&identity);
         
-        let handshake = Handshake::new(public, vec![Capability::TextMessaging]);
+        let handshake = Handshake::new(public, vec![Capability::TextMessaging.into()]);
         
         assert!(handshake.is_compatible().is_err());
     }
     
+    #[test]
+    fn test_version_range_negotiation() {
+        let negotiate = |local: (u32, u32), remote: (u32, u32)| {
+            VersionRange::negotiate(VersionRange::new(local.0, local.1), VersionRange::new(remote.0, remote.1))
+        };
+        assert_eq!(negotiate((1, 3), (2, 5)), Some(3));
+        assert_eq!(negotiate((2, 5), (1, 3)), Some(3));
+        assert_eq!(negotiate((1, 1), (1, 4)), Some(1));
+        assert_eq!(negotiate((1, 2), (3, 4)), None);
+        assert!(VersionRange::supported().contains(PROTOCOL_VERSION));
+        
+        let identity = Identity::generate().unwrap();
+        let mut handshake = Handshake::new(
+            PublicIdentity::from_identity(&identity),
+            vec![Capability::E2EEncryption.into(), Capability::TextMessaging.into()],
+        );
+        
+        // A newer peer that still speaks our version is compatible
+        handshake.version = PROTOCOL_VERSION + 2;
+        handshake.supported_versions = Some(VersionRange::new(MIN_PROTOCOL_VERSION, PROTOCOL_VERSION + 2));
+        assert!(handshake.is_compatible().is_ok());
+        assert_eq!(handshake.negotiated_version(), Some(PROTOCOL_VERSION));
+        
+        let response = HandshakeResponse::respond_to(&handshake, &[Capability::E2EEncryption.into()]);
+        assert!(response.accepted);
+        assert_eq!(response.negotiated_version, PROTOCOL_VERSION);
+        assert_eq!(response.accepted_capabilities, vec![Capability::E2EEncryption]);
+        
+        // Older clients only send `version`
+        handshake.supported_versions = None;
+        assert!(matches!(handshake.is_compatible(), Err(ProtocolError::IncompatibleVersion { .. })));
+        let response = HandshakeResponse::respond_to(&handshake, &[Capability::E2EEncryption.into()]);
+        assert!(!response.accepted);
+        
+        // Responses from before negotiation decode with the first version
+        let mut json = serde_json::to_value(HandshakeResponse::accept(Vec::new(), PROTOCOL_VERSION)).unwrap();
+        json.as_object_mut().unwrap().remove("negotiated_version");
+        let legacy: HandshakeResponse = serde_json::from_value(json).unwrap();
+        assert_eq!(legacy.negotiated_version, MIN_PROTOCOL_VERSION);
+    }
+    
+    #[test]
+    fn test_handshake_signature() {
+        let identity = Identity::generate().unwrap();
+        let public = PublicIdentity::from_identity(&identity);
+        
+        let mut handshake = Handshake::new(
+            public.clone(),
+            vec![Capability::E2EEncryption.into(), Capability::TextMessaging.into()],
+        )
+        .with_metadata("client".to_string(), "otter-cli".to_string())
+        .with_metadata("platform".to_string(), "linux".to_string());
+        assert!(handshake.verify(&public).is_err());
+        
+        handshake.sign(&identity).unwrap();
+        assert!(handshake.verify(&public).is_ok());
+        
+        // The signature survives both wire formats
+        let received = Handshake::from_bytes(&handshake.to_bytes().unwrap()).unwrap();
+        assert!(received.verify(&public).is_ok());
+        assert!(received.is_compatible().is_ok());
+        let received: Handshake = serde_json::from_str(&serde_json::to_string(&handshake).unwrap()).unwrap();
+        assert!(received.verify(&public).is_ok());
+        
+        // Tampered capabilities or another identity fail
+        let mut tampered = handshake.clone();
+        tampered.capabilities.push(Capability::VideoCall.into());
+        assert!(matches!(tampered.verify(&public), Err(ProtocolError::InvalidHandshake(_))));
+        assert!(matches!(tampered.is_compatible(), Err(ProtocolError::InvalidHandshake(_))));
+        
+        let mallory = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        assert!(handshake.verify(&mallory).is_err());
+    }
+    
     #[test]
     fn test_capability_matching() {
         let local = vec![
-            Capability::TextMessaging,
-            Capability::VoiceCall,
-            Capability::E2EEncryption,
+            Capability::TextMessaging.into(),
+            Capability::VoiceCall.into(),
+            Capability::E2EEncryption.into(),
         ];
         
         let remote = vec![
-            Capability::TextMessaging,
-            Capability::E2EEncryption,
-            Capability::FileTransfer,
+            Capability::TextMessaging.into(),
+            Capability::E2EEncryption.into(),
+            Capability::FileTransfer.into(),
         ];
         
         let common = CapabilityMatcher::match_capabilities(&local, &remote);
@@ -611,6 +1101,31 @@ mod tests {
         assert!(common.contains(&Capability::E2EEncryption));
     }
     
+    #[test]
+    fn test_unavailable_capability_is_not_matched() {
+        let identity = Identity::generate().unwrap();
+        let mobile = Handshake::new(
+            PublicIdentity::from_identity(&identity),
+            vec![
+                Capability::E2EEncryption.into(),
+                CapabilityAdvertisement::unavailable(Capability::VideoCall).with_resource_hint(ResourceHint {
+                    min_bandwidth_bps: 1_000_000,
+                    min_cpu_percent: 60,
+                    battery_required: false,
+                }),
+            ],
+        );
+        let desktop = vec![Capability::E2EEncryption.into(), Capability::VideoCall.into()];
+        
+        assert_eq!(mobile.available_capabilities(), vec![&Capability::E2EEncryption]);
+        assert!(!mobile.supports(&Capability::VideoCall));
+        
+        let common = CapabilityMatcher::match_capabilities(&mobile.capabilities, &desktop);
+        assert_eq!(common, vec![Capability::E2EEncryption]);
+        let common = CapabilityMatcher::match_capabilities(&desktop, &mobile.capabilities);
+        assert_eq!(common, vec![Capability::E2EEncryption]);
+    }
+    
     #[test]
     fn test_handshake_serialization() {
         let identity = Identity::generate().unwrap();
@@ -618,7 +1133,7 @@ mod tests {
         
         let handshake = Handshake::new(
             public,
-            vec![Capability::E2EEncryption, Capability::TextMessaging],
+            vec![Capability::E2EEncryption.into(), Capability::TextMessaging.into()],
         );
         
         // Test JSON serialization instead (more reliable for complex types)
@@ -665,6 +1180,41 @@ mod tests {
         assert!(deserialized.requires_ack);
     }
     
+    #[test]
+    fn test_sdp_compression_roundtrip() {
+        let mut sdp = String::from("v=0\r\no=- 4611731400430051336 2 IN IP4 127.0.0.1\r\ns=-\r\nt=0 0\r\n");
+        for (i, codec) in ["opus/48000/2", "G722/8000", "PCMU/8000", "PCMA/8000"].iter().cycle().enumerate() {
+            if sdp.len() >= 3000 {
+                break;
+            }
+            sdp.push_str(&format!("a=rtpmap:{} {}\r\na=candidate:{} 1 udp 2122260223 192.168.1.{} {} typ host\r\n", 100 + i, codec, i, i % 250, 50000 + i));
+        }
+        
+        let answer = SignalingMessage::Answer {
+            sdp: sdp.clone(),
+            session_id: "session1".to_string(),
+        };
+        let compressed = SignalingProtocolMessage::new(answer, 0, false).compress().unwrap();
+        assert!(compressed.compressed);
+        let SignalingMessage::Answer { sdp: ref packed, .. } = compressed.payload else {
+            panic!("Wrong payload type");
+        };
+        assert!(packed.len() * 10 <= sdp.len() * 7, "{} of {} bytes", packed.len(), sdp.len());
+        
+        let received = SignalingProtocolMessage::from_json(&compressed.to_json().unwrap()).unwrap();
+        assert!(!received.compressed);
+        assert!(matches!(received.payload, SignalingMessage::Answer { sdp: ref plain, .. } if *plain == sdp));
+        
+        // ICE candidates are left alone
+        let candidate = SignalingMessage::IceCandidate {
+            candidate: "candidate:1 1 udp 2122260223 192.168.1.2 50000 typ host".to_string(),
+            sdp_mid: None,
+            sdp_mline_index: Some(0),
+            session_id: "session1".to_string(),
+        };
+        assert!(!SignalingProtocolMessage::new(candidate, 1, false).compress().unwrap().compressed);
+    }
+    
     #[test]
     fn test_signaling_ack_handling() {
         let mut session = SignalingSession::new_initiator(
@@ -685,4 +1235,30 @@ mod tests {
         session.handle_ack(&msg.message_id);
         assert_eq!(session.pending_acks.len(), 0);
     }
+    
+    #[test]
+    fn test_renegotiation_offer_sent_as_delta() {
+        let mut caller = SignalingSession::new_initiator("session1".to_string(), "peer1".to_string(), MediaType::AudioOnly);
+        let mut callee = SignalingSession::new_responder("session1".to_string(), "peer0".to_string(), MediaType::AudioOnly);
+        let first = format!("v=0\r\ns=-\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\nb=AS:64\r\n{}", "a=rtpmap:111 opus/48000/2\r\n".repeat(40));
+        let second = first.replace("b=AS:64", "b=AS:32");
+        
+        // No base yet, so the first offer goes out in full
+        let offer = caller.create_offer(&first, true);
+        assert!(matches!(offer.payload, SignalingMessage::Offer { .. }));
+        assert_eq!(callee.receive_offer(&offer.payload).unwrap(), first);
+        
+        let delta = caller.create_offer(&second, true);
+        assert!(matches!(delta.payload, SignalingMessage::OfferDelta { base_version: 1, .. }));
+        let received = SignalingProtocolMessage::from_bytes(&delta.to_bytes().unwrap()).unwrap();
+        assert_eq!(callee.receive_offer(&received.payload).unwrap(), second);
+        assert_eq!((caller.sdp_version, callee.sdp_version), (2, 2));
+        
+        // A side that missed the base cannot apply the delta
+        let mut late = SignalingSession::new_responder("session1".to_string(), "peer0".to_string(), MediaType::AudioOnly);
+        assert!(matches!(late.receive_offer(&delta.payload), Err(ProtocolError::UnknownSdpBase(1))));
+        caller.reset_sdp_base();
+        let full = caller.create_offer(&second, true);
+        assert_eq!(late.receive_offer(&full.payload).unwrap(), second);
+    }
 }
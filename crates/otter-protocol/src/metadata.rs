@@ -0,0 +1,126 @@
+//! # Handshake Metadata
+//!
+//! Free-form key/value data that travels in a [`Handshake`](crate::Handshake).
+//! Values are JSON, so numbers and flags keep their type on the wire, while
+//! the serialized form stays a flat JSON object and handshakes with plain
+//! string values still parse.
+
+use crate::ProtocolError;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Metadata key holding the sender's client version
+pub const CLIENT_VERSION_KEY: &str = "client_version";
+
+/// Handshake metadata with typed accessors
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TypedMetadata(HashMap<String, Value>);
+
+impl TypedMetadata {
+    /// Create empty metadata
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Raw value stored under `key`
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.0.get(key)
+    }
+    
+    /// String stored under `key`
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.0.get(key)?.as_str()
+    }
+    
+    /// `u32` stored under `key`, if the number fits
+    pub fn get_u32(&self, key: &str) -> Option<u32> {
+        self.0.get(key)?.as_u64()?.try_into().ok()
+    }
+    
+    /// Boolean stored under `key`
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        self.0.get(key)?.as_bool()
+    }
+    
+    /// Store a string under `key`
+    pub fn insert_str(&mut self, key: &str, value: &str) {
+        self.0.insert(key.to_string(), Value::String(value.to_string()));
+    }
+    
+    /// Store any serializable value under `key`
+    pub fn insert_typed<T: Serialize>(&mut self, key: &str, value: &T) -> Result<(), ProtocolError> {
+        let value = serde_json::to_value(value).map_err(|e| ProtocolError::SerializationError(e.to_string()))?;
+        self.0.insert(key.to_string(), value);
+        Ok(())
+    }
+    
+    /// Remove the value under `key`
+    pub fn remove(&mut self, key: &str) -> Option<Value> {
+        self.0.remove(key)
+    }
+    
+    /// Whether a value is stored under `key`
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.0.contains_key(key)
+    }
+    
+    /// Number of entries
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    
+    /// Whether there are no entries
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+    
+    /// Version of the sender's client
+    pub fn client_version(&self) -> Option<&str> {
+        self.get_str(CLIENT_VERSION_KEY)
+    }
+    
+    /// Set the version of the sender's client
+    pub fn set_client_version(&mut self, version: &str) {
+        self.insert_str(CLIENT_VERSION_KEY, version);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_typed_roundtrip() {
+        let mut metadata = TypedMetadata::new();
+        metadata.insert_typed("max_fragments", &512u32).unwrap();
+        metadata.insert_typed("relay", &true).unwrap();
+        metadata.set_client_version("otter/0.1.0");
+        
+        assert_eq!(metadata.get_u32("max_fragments"), Some(512));
+        assert_eq!(metadata.get_bool("relay"), Some(true));
+        assert_eq!(metadata.client_version(), Some("otter/0.1.0"));
+        
+        // Wrong types read as absent
+        assert_eq!(metadata.get_str("max_fragments"), None);
+        assert_eq!(metadata.get_u32("relay"), None);
+        
+        metadata.insert_typed("huge", &(u64::from(u32::MAX) + 1)).unwrap();
+        assert_eq!(metadata.get_u32("huge"), None);
+        
+        let json = serde_json::to_string(&metadata).unwrap();
+        let restored: TypedMetadata = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, metadata);
+    }
+    
+    #[test]
+    fn test_string_map_still_parses() {
+        let legacy: HashMap<String, String> = [(CLIENT_VERSION_KEY.to_string(), "otter/0.0.9".to_string())].into();
+        let json = serde_json::to_string(&legacy).unwrap();
+        
+        let metadata: TypedMetadata = serde_json::from_str(&json).unwrap();
+        assert_eq!(metadata.client_version(), Some("otter/0.0.9"));
+        assert_eq!(serde_json::to_string(&metadata).unwrap(), json);
+    }
+}
@@ -0,0 +1,203 @@
+//! # OAuth Attestation
+//!
+//! Lets a peer claim an OAuth account without sharing the token. The
+//! attestation carries a hash of the account email and a short fingerprint
+//! of the access token, signed with the peer's identity key, and travels in
+//! the handshake metadata.
+//!
+//! It is an unverified, self-signed claim. The provider never signs
+//! anything and the receiver cannot check the token fingerprint, so a valid
+//! attestation only shows that the sender's identity key made the claim, not
+//! that the sender owns the account. Treat it as a hint, never as proof.
+//!
+//! The email hash is keyed with the recipient's peer ID, so attestations sent
+//! to different peers cannot be linked to each other, and hashes cannot be
+//! looked up in a dictionary precomputed for all peers. The recipient itself
+//! can still test guessed addresses against the hash it received.
+
+use crate::{Handshake, ProtocolError};
+use chrono::Utc;
+use ed25519_dalek::Signature;
+use otter_identity::{Identity, PeerId, PublicIdentity};
+use serde::{Deserialize, Serialize};
+
+/// Handshake metadata key holding the attestation as JSON
+pub const OAUTH_ATTESTATION_KEY: &str = "oauth_attestation";
+
+/// BLAKE3 key derivation context for the email hash key
+const EMAIL_HASH_CONTEXT: &str = "otter-protocol oauth email hash v1";
+
+/// Self-signed, unverified claim that the sender holds an OAuth token for an account
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuthAttestation {
+    /// Hex BLAKE3 hash of the lowercased account email, keyed with the recipient's peer ID
+    pub email_hash: String,
+    /// Provider name, e.g. "google"
+    pub provider: String,
+    /// Hex of the first 8 bytes of BLAKE3(access_token)
+    pub token_fingerprint: String,
+    /// Token expiry, Unix seconds
+    pub exp: i64,
+    /// Ed25519 signature of the sender's identity
+    pub signature: Vec<u8>,
+}
+
+impl OAuthAttestation {
+    /// Create an attestation of `email`'s `access_token` for `recipient`, valid until `exp`
+    pub fn create(
+        identity: &Identity,
+        recipient: &PeerId,
+        provider: &str,
+        email: &str,
+        access_token: &str,
+        exp: i64,
+    ) -> Result<Self, ProtocolError> {
+        let email_hash = Self::email_hash(recipient, email);
+        let token_fingerprint = hex::encode(&blake3::hash(access_token.as_bytes()).as_bytes()[..8]);
+        let signature = identity
+            .sign(&Self::signed_bytes(recipient, &email_hash, &token_fingerprint, exp, provider))
+            .map_err(|e| ProtocolError::InvalidOAuthAttestation(e.to_string()))?
+            .to_bytes()
+            .to_vec();
+        
+        Ok(Self {
+            email_hash,
+            provider: provider.to_string(),
+            token_fingerprint,
+            exp,
+            signature,
+        })
+    }
+    
+    /// Hex keyed BLAKE3 hash of the lowercased `email`, with a key derived from `recipient`
+    fn email_hash(recipient: &PeerId, email: &str) -> String {
+        let key = blake3::derive_key(EMAIL_HASH_CONTEXT, recipient.as_str().as_bytes());
+        blake3::keyed_hash(&key, email.trim().to_lowercase().as_bytes()).to_hex().to_string()
+    }
+    
+    /// `recipient || email_hash || token_fingerprint || exp (little endian) || provider`,
+    /// with the recipient length-prefixed
+    fn signed_bytes(recipient: &PeerId, email_hash: &str, token_fingerprint: &str, exp: i64, provider: &str) -> Vec<u8> {
+        let recipient = recipient.as_str().as_bytes();
+        [
+            &(recipient.len() as u64).to_le_bytes(),
+            recipient,
+            email_hash.as_bytes(),
+            token_fingerprint.as_bytes(),
+            &exp.to_le_bytes(),
+            provider.as_bytes(),
+        ]
+        .concat()
+    }
+    
+    /// Check that `sender` signed the attestation for `recipient` and that the token has not expired
+    ///
+    /// This does not show that the sender owns the account; see the module docs.
+    pub fn verify(&self, sender: &PublicIdentity, recipient: &PeerId) -> Result<(), ProtocolError> {
+        let invalid = |reason: &str| ProtocolError::InvalidOAuthAttestation(reason.to_string());
+        
+        let signature = Signature::from_slice(&self.signature).map_err(|_| invalid("malformed signature"))?;
+        sender
+            .verify(
+                &Self::signed_bytes(recipient, &self.email_hash, &self.token_fingerprint, self.exp, &self.provider),
+                &signature,
+            )
+            .map_err(|_| invalid("signature does not match the sender"))?;
+        
+        if self.exp <= Utc::now().timestamp() {
+            return Err(invalid("token expired"));
+        }
+        Ok(())
+    }
+    
+    /// Whether the attestation received by `recipient` claims `email`
+    pub fn matches_email(&self, recipient: &PeerId, email: &str) -> bool {
+        Self::email_hash(recipient, email) == self.email_hash
+    }
+}
+
+impl Handshake {
+    /// Attach an OAuth attestation to the handshake metadata
+    pub fn with_oauth_attestation(self, attestation: &OAuthAttestation) -> Result<Self, ProtocolError> {
+        let json = serde_json::to_string(attestation).map_err(|e| ProtocolError::SerializationError(e.to_string()))?;
+        Ok(self.with_metadata(OAUTH_ATTESTATION_KEY.to_string(), json))
+    }
+    
+    /// The OAuth attestation in the metadata, checked against the handshake's identity
+    /// and the local peer ID
+    ///
+    /// Returns `Ok(None)` if the sender did not attach one. The claim itself
+    /// stays unverified; see [`OAuthAttestation`].
+    pub fn verified_oauth_attestation(&self, local_peer_id: &PeerId) -> Result<Option<OAuthAttestation>, ProtocolError> {
+        let Some(json) = self.metadata.get_str(OAUTH_ATTESTATION_KEY) else {
+            return Ok(None);
+        };
+        let attestation: OAuthAttestation = serde_json::from_str(json)
+            .map_err(|e| ProtocolError::InvalidOAuthAttestation(e.to_string()))?;
+        attestation.verify(&self.identity, local_peer_id)?;
+        Ok(Some(attestation))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Capability;
+    
+    #[test]
+    fn test_tampered_attestation_fails() {
+        let identity = Identity::generate().unwrap();
+        let public = PublicIdentity::from_identity(&identity);
+        let bob = Identity::generate().unwrap();
+        let bob_id = bob.peer_id();
+        let exp = Utc::now().timestamp() + 3600;
+        let attestation = OAuthAttestation::create(&identity, bob_id, "google", "Otter@Example.com", "ya29.token", exp).unwrap();
+        assert!(attestation.matches_email(bob_id, "otter@example.com"));
+        assert_eq!(attestation.token_fingerprint.len(), 16);
+        attestation.verify(&public, bob_id).unwrap();
+        
+        let handshake = Handshake::new(public.clone(), vec![Capability::E2EEncryption.into()])
+            .with_oauth_attestation(&attestation)
+            .unwrap();
+        let received = Handshake::from_bytes(&handshake.to_bytes().unwrap()).unwrap();
+        assert_eq!(received.verified_oauth_attestation(bob_id).unwrap(), Some(attestation.clone()));
+        
+        let tampered = [
+            OAuthAttestation { email_hash: OAuthAttestation::email_hash(bob_id, "mallory@example.com"), ..attestation.clone() },
+            OAuthAttestation { token_fingerprint: "0000000000000000".to_string(), ..attestation.clone() },
+            OAuthAttestation { exp: exp + 86400, ..attestation.clone() },
+            OAuthAttestation { provider: "github".to_string(), ..attestation.clone() },
+        ];
+        for attestation in tampered {
+            assert!(matches!(attestation.verify(&public, bob_id), Err(ProtocolError::InvalidOAuthAttestation(_))));
+        }
+        
+        // Another identity cannot present it as its own
+        let mallory = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let stolen = Handshake::new(mallory, vec![Capability::E2EEncryption.into()])
+            .with_oauth_attestation(&attestation)
+            .unwrap();
+        assert!(stolen.verified_oauth_attestation(bob_id).is_err());
+        
+        let expired = OAuthAttestation::create(&identity, bob_id, "google", "otter@example.com", "ya29.token", exp - 7200).unwrap();
+        assert!(expired.verify(&public, bob_id).is_err());
+    }
+    
+    #[test]
+    fn test_email_hash_is_keyed_by_recipient() {
+        let identity = Identity::generate().unwrap();
+        let public = PublicIdentity::from_identity(&identity);
+        let bob = Identity::generate().unwrap();
+        let carol = Identity::generate().unwrap();
+        let exp = Utc::now().timestamp() + 3600;
+        
+        let for_bob = OAuthAttestation::create(&identity, bob.peer_id(), "google", "otter@example.com", "ya29.token", exp).unwrap();
+        let for_carol = OAuthAttestation::create(&identity, carol.peer_id(), "google", "otter@example.com", "ya29.token", exp).unwrap();
+        assert_ne!(for_bob.email_hash, for_carol.email_hash);
+        assert_ne!(for_bob.email_hash, blake3::hash(b"otter@example.com").to_hex().to_string());
+        assert!(!for_bob.matches_email(carol.peer_id(), "otter@example.com"));
+        
+        // Bob cannot pass his attestation on to Carol
+        assert!(for_bob.verify(&public, carol.peer_id()).is_err());
+    }
+}
@@ -0,0 +1,153 @@
+//! # SDP Deltas
+//!
+//! A renegotiation offer usually differs from the previous offer in a few
+//! lines (a bandwidth, a version number, new ICE credentials), yet the whole
+//! SDP is sent again. `SdpDiff` computes a bsdiff-style binary patch against
+//! the previous offer instead, sent as `SignalingMessage::OfferDelta`.
+//!
+//! A patch is the first 8 bytes of the BLAKE3 hash of its base, followed by
+//! the zstd-compressed `bidiff` output, so applying it to the wrong base fails
+//! instead of producing a corrupted SDP.
+
+use crate::ProtocolError;
+use std::io::Read;
+
+/// Length of the base hash prefix of a patch
+const BASE_HASH_LEN: usize = 8;
+
+/// zstd level for patches; bsdiff output is mostly zeros and compresses well
+const PATCH_COMPRESSION_LEVEL: i32 = 3;
+
+/// Binary patch turning one SDP into another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SdpPatch(Vec<u8>);
+
+impl SdpPatch {
+    /// Wrap patch bytes received from a peer
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+    
+    /// Encoded patch
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+    
+    /// Encoded patch, by value
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+    
+    /// Encoded length in bytes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    
+    /// Whether the patch holds no bytes at all
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Binary diffing of SDP documents
+pub struct SdpDiff;
+
+impl SdpDiff {
+    /// Patch that turns `old` into `new`
+    pub fn compute(old: &str, new: &str) -> SdpPatch {
+        let mut diff = Vec::new();
+        bidiff::simple_diff(old.as_bytes(), new.as_bytes(), &mut diff)
+            .expect("writing to a Vec cannot fail");
+        
+        let mut bytes = Self::base_hash(old).to_vec();
+        bytes.extend(zstd::encode_all(diff.as_slice(), PATCH_COMPRESSION_LEVEL).expect("compressing a slice cannot fail"));
+        SdpPatch(bytes)
+    }
+    
+    /// Apply `patch` to `base`, the SDP it was computed against
+    pub fn apply(base: &str, patch: &SdpPatch) -> Result<String, ProtocolError> {
+        let invalid = |reason: String| ProtocolError::InvalidSdpPatch(reason);
+        if patch.len() < BASE_HASH_LEN {
+            return Err(invalid("truncated patch".to_string()));
+        }
+        let (base_hash, diff) = patch.as_bytes().split_at(BASE_HASH_LEN);
+        if base_hash != Self::base_hash(base) {
+            return Err(invalid("patch was computed against another SDP".to_string()));
+        }
+        
+        let diff = zstd::decode_all(diff).map_err(|e| invalid(e.to_string()))?;
+        let mut sdp = Vec::new();
+        bipatch::Reader::new(diff.as_slice(), std::io::Cursor::new(base.as_bytes()))
+            .map_err(|e| invalid(e.to_string()))?
+            .read_to_end(&mut sdp)
+            .map_err(|e| invalid(e.to_string()))?;
+        String::from_utf8(sdp).map_err(|e| invalid(e.to_string()))
+    }
+    
+    fn base_hash(base: &str) -> [u8; BASE_HASH_LEN] {
+        let mut hash = [0u8; BASE_HASH_LEN];
+        hash.copy_from_slice(&blake3::hash(base.as_bytes()).as_bytes()[..BASE_HASH_LEN]);
+        hash
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Audio offer padded with candidates to about 3 KB
+    fn base_sdp() -> String {
+        let mut sdp = String::from(
+            "v=0\r\n\
+            o=- 4611731400430051336 2 IN IP4 127.0.0.1\r\n\
+            s=-\r\n\
+            t=0 0\r\n\
+            a=group:BUNDLE 0\r\n\
+            a=fingerprint:sha-256 6B:8B:5D:EA:59:04:20:23:29:C8:87:1C:CC:87:32:BE:DD:8C:66:A5:8E:50:55:EA:8C:D3:B6:5C:09:5E:D6:BC\r\n\
+            m=audio 9 UDP/TLS/RTP/SAVPF 111 63 9 0 8 13 110 126\r\n\
+            c=IN IP4 0.0.0.0\r\n\
+            b=AS:64\r\n\
+            a=ice-ufrag:EsAw\r\n\
+            a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y\r\n\
+            a=setup:actpass\r\n\
+            a=mid:0\r\n\
+            a=sendrecv\r\n\
+            a=rtcp-mux\r\n\
+            a=rtpmap:111 opus/48000/2\r\n\
+            a=fmtp:111 minptime=10;useinbandfec=1\r\n",
+        );
+        for i in 0..40 {
+            sdp.push_str(&format!(
+                "a=candidate:{} 1 udp {} 192.168.1.{} {} typ host generation 0\r\n",
+                1000 + i,
+                2_122_260_223u32 - i,
+                10 + i,
+                50_000 + i
+            ));
+        }
+        sdp
+    }
+    
+    #[test]
+    fn test_sdp_patch_roundtrip() {
+        let base = base_sdp();
+        assert!(base.len() >= 3000, "{} bytes", base.len());
+        
+        let new = base
+            .replace("o=- 4611731400430051336 2", "o=- 4611731400430051336 3")
+            .replace("b=AS:64", "b=AS:32")
+            .replace("a=ice-ufrag:EsAw", "a=ice-ufrag:Qm7x");
+        let patch = SdpDiff::compute(&base, &new);
+        assert!(patch.len() < new.len() / 4, "{} byte patch", patch.len());
+        
+        let received = SdpPatch::from_bytes(patch.as_bytes().to_vec());
+        assert_eq!(SdpDiff::apply(&base, &received).unwrap(), new);
+        
+        // The patch does not fit any other base
+        assert!(matches!(
+            SdpDiff::apply(&new, &patch),
+            Err(ProtocolError::InvalidSdpPatch(_))
+        ));
+        assert!(SdpDiff::apply(&base, &SdpPatch::from_bytes(vec![1, 2, 3])).is_err());
+    }
+}
@@ -0,0 +1,173 @@
+//! # Test Harness
+//!
+//! Simulates an unreliable transport so protocol code can be exercised with
+//! lost, delayed and reordered messages.
+
+use crate::{MessagePayload, ProtocolMessage};
+use otter_crypto::{CryptoSession, EncryptedMessage, KdfAlgorithm};
+use otter_identity::{Identity, PublicIdentity};
+use rand::{seq::SliceRandom, Rng};
+use std::{
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+/// Channel that drops, delays and reorders what is sent through it
+pub struct UnreliableChannel {
+    /// Probability that a message is dropped
+    pub loss_rate: f32,
+    /// Probability that a message is held back and delivered out of order
+    pub reorder_rate: f32,
+    /// Delay applied to every delivered message
+    pub delay_ms: u64,
+    sender: Sender<Vec<u8>>,
+    held: Vec<Vec<u8>>,
+}
+
+impl UnreliableChannel {
+    /// Create a channel and the receiving end of it
+    pub fn new(loss_rate: f32, reorder_rate: f32, delay_ms: u64) -> (Self, Receiver<Vec<u8>>) {
+        let (sender, receiver) = mpsc::channel();
+        let channel = Self {
+            loss_rate,
+            reorder_rate,
+            delay_ms,
+            sender,
+            held: Vec::new(),
+        };
+        (channel, receiver)
+    }
+    
+    /// Send `msg`, possibly dropping it or holding it back
+    ///
+    /// Held messages are delivered in shuffled order after the next message
+    /// that goes through normally, or on [`UnreliableChannel::flush`].
+    pub fn send(&mut self, msg: Vec<u8>) {
+        let mut rng = rand::thread_rng();
+        if rng.gen::<f32>() < self.loss_rate {
+            return;
+        }
+        if rng.gen::<f32>() < self.reorder_rate {
+            self.held.push(msg);
+            return;
+        }
+        
+        self.deliver(msg);
+        self.flush();
+    }
+    
+    /// Deliver all held messages in shuffled order
+    pub fn flush(&mut self) {
+        let mut held = std::mem::take(&mut self.held);
+        held.shuffle(&mut rand::thread_rng());
+        for msg in held {
+            self.deliver(msg);
+        }
+    }
+    
+    fn deliver(&self, msg: Vec<u8>) {
+        if self.delay_ms > 0 {
+            thread::sleep(Duration::from_millis(self.delay_ms));
+        }
+        // The receiver may already be gone, which is just more loss
+        let _ = self.sender.send(msg);
+    }
+}
+
+/// Outcome of a [`ProtocolFuzzTest`] run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FuzzReport {
+    /// Messages handed to the channel
+    pub sent: usize,
+    /// Messages that came out of the channel
+    pub received: usize,
+    /// Received messages that decrypted and decoded
+    pub decrypted: usize,
+}
+
+/// Sends encrypted protocol messages between two peers over an [`UnreliableChannel`]
+pub struct ProtocolFuzzTest;
+
+impl ProtocolFuzzTest {
+    /// Transmit `messages` text messages and check everything that decrypts
+    ///
+    /// Reordered messages may be refused by replay protection; any message that
+    /// does decrypt must decode to exactly what was sent.
+    pub fn run(loss: f32, reorder: f32, messages: usize) -> FuzzReport {
+        let alice = Identity::generate().unwrap();
+        let bob = Identity::generate().unwrap();
+        let mut alice_session =
+            CryptoSession::new(&alice, &PublicIdentity::from_identity(&bob), KdfAlgorithm::default()).unwrap();
+        let mut bob_session =
+            CryptoSession::new(&bob, &PublicIdentity::from_identity(&alice), KdfAlgorithm::default()).unwrap();
+        
+        let (mut channel, receiver) = UnreliableChannel::new(loss, reorder, 0);
+        for i in 0..messages {
+            let message = ProtocolMessage::new(MessagePayload::Text {
+                content: format!("message {}", i).into_bytes(),
+            });
+            let encrypted = alice_session.encrypt(&message.to_bytes().unwrap(), None).unwrap();
+            channel.send(rmp_serde::to_vec_named(&encrypted).unwrap());
+        }
+        channel.flush();
+        drop(channel);
+        
+        let mut report = FuzzReport {
+            sent: messages,
+            received: 0,
+            decrypted: 0,
+        };
+        for bytes in receiver {
+            report.received += 1;
+            
+            let encrypted: EncryptedMessage = rmp_serde::from_slice(&bytes).unwrap();
+            let Ok(plaintext) = bob_session.decrypt(&encrypted) else {
+                continue;
+            };
+            
+            let message = ProtocolMessage::from_bytes(&plaintext).unwrap();
+            match message.payload {
+                MessagePayload::Text { content } => {
+                    let expected = format!("message {}", encrypted.message_counter);
+                    assert_eq!(content, expected.into_bytes());
+                }
+                other => panic!("Unexpected payload: {:?}", other),
+            }
+            report.decrypted += 1;
+        }
+        
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_unreliable_channel() {
+        let (mut channel, receiver) = UnreliableChannel::new(0.0, 0.0, 0);
+        for i in 0..10u8 {
+            channel.send(vec![i]);
+        }
+        drop(channel);
+        assert_eq!(receiver.iter().collect::<Vec<_>>(), (0..10u8).map(|i| vec![i]).collect::<Vec<_>>());
+        
+        let (mut channel, receiver) = UnreliableChannel::new(1.0, 0.0, 0);
+        channel.send(vec![1]);
+        drop(channel);
+        assert!(receiver.iter().next().is_none());
+    }
+    
+    #[test]
+    fn test_protocol_fuzz() {
+        let report = ProtocolFuzzTest::run(0.1, 0.05, 500);
+        assert!(report.received <= report.sent);
+        assert!(report.decrypted <= report.received);
+        assert!(report.decrypted > 0);
+        
+        let report = ProtocolFuzzTest::run(0.0, 0.0, 50);
+        assert_eq!(report.decrypted, 50);
+    }
+}
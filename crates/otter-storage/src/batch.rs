@@ -0,0 +1,131 @@
+//! # Batched Peer Cache Writes
+//!
+//! Discovery on a busy LAN can report dozens of peers within a few
+//! milliseconds. Saving each one rewrites the whole peer cache, so
+//! `DelayedFlush` collects entries until no new one has arrived for a short
+//! delay and saves them with one `save_peer_cache_batch` call.
+
+use crate::{PeerCacheEntry, Storage};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Quiet period after the last discovered peer before the cache is written
+pub const PEER_CACHE_FLUSH_DELAY: Duration = Duration::from_millis(500);
+
+/// Debounced writer of peer cache entries
+///
+/// The timer is re-armed on every new entry. Dropping the writer flushes
+/// whatever is still pending.
+pub struct DelayedFlush {
+    entry_tx: mpsc::UnboundedSender<PeerCacheEntry>,
+    task: JoinHandle<()>,
+}
+
+impl DelayedFlush {
+    /// Start a writer that saves to `storage` once `delay` passes without a new entry
+    pub fn spawn(storage: Arc<dyn Storage>, delay: Duration) -> Self {
+        let (entry_tx, entry_rx) = mpsc::unbounded_channel();
+        let task = tokio::spawn(Self::run(storage, delay, entry_rx));
+        Self { entry_tx, task }
+    }
+    
+    /// Queue an entry; a later entry for the same peer replaces it
+    pub fn push(&self, entry: PeerCacheEntry) {
+        let _ = self.entry_tx.send(entry);
+    }
+    
+    /// Stop accepting entries and wait until the pending ones are written
+    pub async fn finish(self) {
+        drop(self.entry_tx);
+        let _ = self.task.await;
+    }
+    
+    async fn run(
+        storage: Arc<dyn Storage>,
+        delay: Duration,
+        mut entry_rx: mpsc::UnboundedReceiver<PeerCacheEntry>,
+    ) {
+        let mut pending: HashMap<String, PeerCacheEntry> = HashMap::new();
+        
+        loop {
+            let entry = if pending.is_empty() {
+                entry_rx.recv().await
+            } else {
+                tokio::select! {
+                    entry = entry_rx.recv() => entry,
+                    _ = tokio::time::sleep(delay) => {
+                        Self::flush(storage.as_ref(), &mut pending).await;
+                        continue;
+                    }
+                }
+            };
+            
+            match entry {
+                Some(entry) => {
+                    pending.insert(entry.peer_id.clone(), entry);
+                }
+                None => break,
+            }
+        }
+        
+        Self::flush(storage.as_ref(), &mut pending).await;
+    }
+    
+    async fn flush(storage: &dyn Storage, pending: &mut HashMap<String, PeerCacheEntry>) {
+        if pending.is_empty() {
+            return;
+        }
+        
+        let entries: Vec<PeerCacheEntry> = pending.drain().map(|(_, entry)| entry).collect();
+        debug!("Writing {} peer cache entries", entries.len());
+        if let Err(e) = storage.save_peer_cache_batch(&entries).await {
+            warn!("Failed to write {} peer cache entries: {}", entries.len(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FileStorage;
+    use otter_identity::{Identity, PublicIdentity};
+    use tempfile::TempDir;
+    
+    #[tokio::test(start_paused = true)]
+    async fn test_discovery_burst_is_written_once() {
+        let temp = TempDir::new().unwrap();
+        let storage = Arc::new(FileStorage::new(temp.path()).unwrap());
+        let flush = DelayedFlush::spawn(storage.clone(), PEER_CACHE_FLUSH_DELAY);
+        let public_identity = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        
+        // 100 peers within 50 ms
+        for i in 0..100 {
+            flush.push(PeerCacheEntry {
+                peer_id: format!("peer{}", i),
+                public_identity: public_identity.clone(),
+                addresses: vec![format!("/ip4/192.168.1.{}/tcp/4001", i)],
+                last_seen: i,
+            });
+            tokio::time::sleep(Duration::from_micros(500)).await;
+        }
+        // Nothing is written while discoveries keep arriving
+        assert!(storage.load_peer_cache().await.unwrap().is_empty());
+        
+        tokio::time::sleep(Duration::from_millis(600)).await;
+        assert_eq!(storage.load_peer_cache().await.unwrap().len(), 100);
+        
+        // A late discovery is still written when the writer finishes
+        flush.push(PeerCacheEntry {
+            peer_id: "late".to_string(),
+            public_identity,
+            addresses: Vec::new(),
+            last_seen: 100,
+        });
+        flush.finish().await;
+        assert_eq!(storage.load_peer_cache().await.unwrap().len(), 101);
+    }
+}
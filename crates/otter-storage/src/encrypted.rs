@@ -0,0 +1,432 @@
+//! # Encrypted Storage
+//!
+//! Storage wrapper that keeps every file encrypted at rest, so session keys
+//! and identity secrets are not readable by other processes with access to
+//! the storage directory.
+//!
+//! Files are written as `nonce || ChaCha20-Poly1305(payload)`, with the path
+//! relative to the storage directory as associated data so files cannot be
+//! swapped for one another. The file key comes from a passphrase through
+//! Argon2id, or from a raw 32-byte key. `encryption.json` holds the Argon2id
+//! parameters, the salt and a key check value; it is the only plaintext file.
+
+use crate::{AddressBookData, FileStorage, IdentityData, PeerCacheEntry, SessionData, Storage, StorageError};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Nonce,
+};
+use otter_identity::{trust::TrustStore, PeerProfile, WebOfTrust};
+use rand::{rngs::OsRng, RngCore};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use zeroize::Zeroizing;
+
+const METADATA_VERSION: u32 = 1;
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// Largest Argon2 memory cost accepted from `encryption.json`, in KiB (1 GiB)
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+
+/// Key derivation settings and key check value, stored in plaintext
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptionMetadata {
+    version: u32,
+    /// `argon2id` for passphrases, `raw` for raw keys
+    kdf: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    /// Hex-encoded salt
+    salt: String,
+    /// Hex-encoded keyed BLAKE3 hash that tells a wrong key from corruption
+    key_check: String,
+}
+
+impl EncryptionMetadata {
+    fn new(kdf: &str) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            version: METADATA_VERSION,
+            kdf: kdf.to_string(),
+            memory_kib: Params::DEFAULT_M_COST,
+            iterations: Params::DEFAULT_T_COST,
+            parallelism: Params::DEFAULT_P_COST,
+            salt: hex::encode(salt),
+            key_check: String::new(),
+        }
+    }
+    
+    fn path(base_path: &Path) -> PathBuf {
+        base_path.join("encryption.json")
+    }
+    
+    /// Load the metadata of `base_path`, or create it for `kdf` if there is none
+    fn load_or_create(base_path: &Path, kdf: &str) -> Result<Self, StorageError> {
+        let path = Self::path(base_path);
+        if !path.exists() {
+            return Ok(Self::new(kdf));
+        }
+        
+        let data = std::fs::read(&path)?;
+        let metadata: Self = serde_json::from_slice(&data)
+            .map_err(|e| StorageError::DeserializationError(e.to_string()))?;
+        if metadata.version != METADATA_VERSION {
+            return Err(StorageError::InvalidData(format!(
+                "Unsupported encryption metadata version {}",
+                metadata.version
+            )));
+        }
+        if metadata.kdf != kdf {
+            return Err(StorageError::InvalidData(format!(
+                "Storage is encrypted with a {} key, not a {} key",
+                metadata.kdf, kdf
+            )));
+        }
+        Ok(metadata)
+    }
+    
+    fn save(&self, base_path: &Path) -> Result<(), StorageError> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        std::fs::create_dir_all(base_path)?;
+        std::fs::write(Self::path(base_path), data)?;
+        Ok(())
+    }
+    
+    /// Derive the file key for `passphrase` with Argon2id
+    fn derive_key(&self, passphrase: &str) -> Result<Zeroizing<[u8; 32]>, StorageError> {
+        if self.memory_kib > MAX_MEMORY_KIB {
+            return Err(StorageError::InvalidData(format!("Argon2 memory cost too high: {} KiB", self.memory_kib)));
+        }
+        
+        let salt = hex::decode(&self.salt).map_err(|e| StorageError::InvalidData(e.to_string()))?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| StorageError::InvalidData(e.to_string()))?;
+        
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| StorageError::InvalidData(e.to_string()))?;
+        Ok(key)
+    }
+    
+    /// Record the check value of `key`, or fail if it differs from the stored one
+    fn check_key(&mut self, key: &[u8; 32]) -> Result<(), StorageError> {
+        let check = blake3::keyed_hash(key, b"otter-storage-key-check");
+        if self.key_check.is_empty() {
+            self.key_check = check.to_hex().to_string();
+            return Ok(());
+        }
+        
+        let stored = blake3::Hash::from_hex(&self.key_check)
+            .map_err(|e| StorageError::InvalidData(e.to_string()))?;
+        if stored != check {
+            return Err(StorageError::InvalidData("Wrong passphrase or key".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// File storage that encrypts every file with ChaCha20-Poly1305
+pub struct EncryptedFileStorage {
+    inner: FileStorage,
+    key: Zeroizing<[u8; 32]>,
+    metadata: EncryptionMetadata,
+}
+
+impl EncryptedFileStorage {
+    /// Open or create encrypted storage at `path`, deriving the file key from `passphrase`
+    ///
+    /// Fails with `InvalidData` if the storage was created with another
+    /// passphrase or with a raw key.
+    pub fn new_with_passphrase<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<Self, StorageError> {
+        let mut metadata = EncryptionMetadata::load_or_create(path.as_ref(), "argon2id")?;
+        let key = metadata.derive_key(passphrase)?;
+        metadata.check_key(&key)?;
+        Self::open(path.as_ref(), key, metadata)
+    }
+    
+    /// Open or create encrypted storage at `path` under a raw 32-byte key
+    ///
+    /// Fails with `InvalidData` if the storage was created with another key
+    /// or with a passphrase.
+    pub fn new_with_key<P: AsRef<Path>>(path: P, key: [u8; 32]) -> Result<Self, StorageError> {
+        let mut metadata = EncryptionMetadata::load_or_create(path.as_ref(), "raw")?;
+        let key = Zeroizing::new(blake3::derive_key("otter-storage file key", &key));
+        metadata.check_key(&key)?;
+        Self::open(path.as_ref(), key, metadata)
+    }
+    
+    fn open(base_path: &Path, key: Zeroizing<[u8; 32]>, metadata: EncryptionMetadata) -> Result<Self, StorageError> {
+        metadata.save(base_path)?;
+        Ok(Self {
+            inner: FileStorage::new(base_path)?,
+            key,
+            metadata,
+        })
+    }
+    
+    /// The unencrypted storage underneath
+    pub fn inner(&self) -> &FileStorage {
+        &self.inner
+    }
+    
+    /// Associated data binding a file's contents to its location
+    fn associated_data(&self, path: &Path) -> Vec<u8> {
+        path.strip_prefix(self.inner.base_path())
+            .unwrap_or(path)
+            .to_string_lossy()
+            .into_owned()
+            .into_bytes()
+    }
+    
+    /// Encrypt a payload for `path`
+    fn seal(&self, path: &Path, payload: &[u8]) -> Result<Vec<u8>, StorageError> {
+        let cipher = ChaCha20Poly1305::new(self.key.as_ref().into());
+        let mut nonce = [0u8; NONCE_LEN];
+        OsRng.fill_bytes(&mut nonce);
+        
+        let aad = self.associated_data(path);
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: payload, aad: &aad })
+            .map_err(|_| StorageError::SerializationError(format!("encryption failed: {:?}", path)))?;
+        
+        let mut data = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    }
+    
+    /// Decrypt the contents of `path`
+    fn open_sealed(&self, path: &Path, data: &[u8]) -> Result<Zeroizing<Vec<u8>>, StorageError> {
+        if data.len() < NONCE_LEN {
+            return Err(StorageError::InvalidData(format!(
+                "nonce missing: file is only {} bytes",
+                data.len()
+            )));
+        }
+        
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let cipher = ChaCha20Poly1305::new(self.key.as_ref().into());
+        let aad = self.associated_data(path);
+        cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: &aad })
+            .map(Zeroizing::new)
+            .map_err(|_| StorageError::InvalidData(format!("decryption failed: {:?}", path)))
+    }
+    
+    async fn write_json<T: Serialize + ?Sized>(&self, path: &Path, value: &T) -> Result<(), StorageError> {
+        let payload = Zeroizing::new(
+            serde_json::to_vec(value).map_err(|e| StorageError::SerializationError(e.to_string()))?,
+        );
+        
+        self.inner.atomic_write(path, &self.seal(path, &payload)?).await
+    }
+    
+    async fn read_json<T: DeserializeOwned>(&self, path: &Path) -> Result<Option<T>, StorageError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        
+        let data = self.inner.read_file(path).await?;
+        let payload = self.open_sealed(path, &data)?;
+        let value = serde_json::from_slice(&payload)
+            .map_err(|e| StorageError::DeserializationError(e.to_string()))?;
+        
+        Ok(Some(value))
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for EncryptedFileStorage {
+    async fn load_identity(&self) -> Result<Option<IdentityData>, StorageError> {
+        self.read_json(&self.inner.identity_path()).await
+    }
+    
+    async fn save_identity(&self, identity: &IdentityData) -> Result<(), StorageError> {
+        self.write_json(&self.inner.identity_path(), identity).await
+    }
+    
+    async fn load_trust_store(&self) -> Result<Option<TrustStore>, StorageError> {
+        self.read_json(&self.inner.trust_store_path()).await
+    }
+    
+    async fn save_trust_store(&self, trust_store: &TrustStore) -> Result<(), StorageError> {
+        self.write_json(&self.inner.trust_store_path(), trust_store).await
+    }
+    
+    async fn load_sessions(&self) -> Result<HashMap<String, SessionData>, StorageError> {
+        let sessions_dir = self.inner.sessions_dir();
+        if !sessions_dir.exists() {
+            return Ok(HashMap::new());
+        }
+        
+        let mut sessions = HashMap::new();
+        let mut entries = fs::read_dir(&sessions_dir).await?;
+        
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json") {
+                match self.read_json::<SessionData>(&path).await {
+                    Ok(Some(session)) => {
+                        sessions.insert(session.peer_id.clone(), session);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::warn!("Failed to load session {:?}: {}", path, e);
+                    }
+                }
+            }
+        }
+        
+        Ok(sessions)
+    }
+    
+    async fn save_session(&self, peer_id: &str, session: &SessionData) -> Result<(), StorageError> {
+        self.write_json(&self.inner.session_path(peer_id), session).await
+    }
+    
+    async fn delete_session(&self, peer_id: &str) -> Result<(), StorageError> {
+        self.inner.delete_session(peer_id).await
+    }
+    
+    async fn load_peer_cache(&self) -> Result<HashMap<String, PeerCacheEntry>, StorageError> {
+        Ok(self
+            .read_json(&self.inner.peer_cache_path())
+            .await?
+            .unwrap_or_default())
+    }
+    
+    async fn save_peer_cache_entry(&self, entry: &PeerCacheEntry) -> Result<(), StorageError> {
+        self.save_peer_cache_batch(std::slice::from_ref(entry)).await
+    }
+    
+    async fn save_peer_cache_batch(&self, entries: &[PeerCacheEntry]) -> Result<(), StorageError> {
+        let mut cache = self.load_peer_cache().await?;
+        for entry in entries {
+            cache.insert(entry.peer_id.clone(), entry.clone());
+        }
+        
+        self.write_json(&self.inner.peer_cache_path(), &cache).await
+    }
+    
+    async fn load_web_of_trust(&self) -> Result<Option<WebOfTrust>, StorageError> {
+        self.read_json(&self.inner.web_of_trust_path()).await
+    }
+    
+    async fn save_web_of_trust(&self, web_of_trust: &WebOfTrust) -> Result<(), StorageError> {
+        self.write_json(&self.inner.web_of_trust_path(), web_of_trust).await
+    }
+    
+    async fn load_profiles(&self) -> Result<HashMap<String, PeerProfile>, StorageError> {
+        Ok(self
+            .read_json(&self.inner.profiles_path())
+            .await?
+            .unwrap_or_default())
+    }
+    
+    async fn save_profile(&self, peer_id: &str, profile: &PeerProfile) -> Result<(), StorageError> {
+        let mut profiles = self.load_profiles().await?;
+        profiles.insert(peer_id.to_string(), profile.clone());
+        
+        self.write_json(&self.inner.profiles_path(), &profiles).await
+    }
+    
+    async fn load_address_book(&self) -> Result<AddressBookData, StorageError> {
+        Ok(self
+            .read_json(&self.inner.address_book_path())
+            .await?
+            .unwrap_or_default())
+    }
+    
+    async fn save_address_book(&self, address_book: &AddressBookData) -> Result<(), StorageError> {
+        self.write_json(&self.inner.address_book_path(), address_book).await
+    }
+    
+    async fn clear_all(&self) -> Result<(), StorageError> {
+        self.inner.clear_all().await?;
+        // Keep the salt and key check so the same passphrase reopens the storage
+        self.metadata.save(self.inner.base_path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn test_session() -> SessionData {
+        SessionData {
+            peer_id: "peer1".to_string(),
+            shared_secret_bytes: b"super-secret-session-key".to_vec(),
+            send_counter: 1,
+            receive_counter: 1,
+            created_at: 1000,
+            last_used: 2000,
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_passphrase_roundtrip_hides_secrets() {
+        let temp = TempDir::new().unwrap();
+        let storage = EncryptedFileStorage::new_with_passphrase(temp.path(), "correct horse").unwrap();
+        storage.save_session("peer1", &test_session()).await.unwrap();
+        
+        let raw = fs::read(temp.path().join("sessions").join("peer1.json")).await.unwrap();
+        assert!(!raw.windows(6).any(|window| window == b"secret"));
+        assert!(!raw.windows(5).any(|window| window == b"peer1"));
+        
+        // Reopening with the same passphrase reads the session back
+        drop(storage);
+        let storage = EncryptedFileStorage::new_with_passphrase(temp.path(), "correct horse").unwrap();
+        let sessions = storage.load_sessions().await.unwrap();
+        assert_eq!(sessions["peer1"].shared_secret_bytes, b"super-secret-session-key");
+        
+        assert!(matches!(
+            EncryptedFileStorage::new_with_passphrase(temp.path(), "wrong horse"),
+            Err(StorageError::InvalidData(_))
+        ));
+        assert!(matches!(
+            EncryptedFileStorage::new_with_key(temp.path(), [7; 32]),
+            Err(StorageError::InvalidData(_))
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_raw_key_rejects_tampered_and_swapped_files() {
+        let temp = TempDir::new().unwrap();
+        let storage = EncryptedFileStorage::new_with_key(temp.path(), [7; 32]).unwrap();
+        let mut other = test_session();
+        other.peer_id = "peer2".to_string();
+        storage.save_session("peer1", &test_session()).await.unwrap();
+        storage.save_session("peer2", &other).await.unwrap();
+        assert!(matches!(
+            EncryptedFileStorage::new_with_key(temp.path(), [8; 32]),
+            Err(StorageError::InvalidData(_))
+        ));
+        
+        // A file moved to another name no longer decrypts
+        let sessions_dir = temp.path().join("sessions");
+        fs::copy(sessions_dir.join("peer2.json"), sessions_dir.join("peer1.json")).await.unwrap();
+        assert!(matches!(
+            storage.read_json::<SessionData>(&sessions_dir.join("peer1.json")).await,
+            Err(StorageError::InvalidData(_))
+        ));
+        
+        let mut data = fs::read(sessions_dir.join("peer2.json")).await.unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        fs::write(sessions_dir.join("peer2.json"), data).await.unwrap();
+        assert!(storage.load_sessions().await.unwrap().is_empty());
+        
+        // Clearing keeps the key check, so the same key reopens the storage
+        storage.clear_all().await.unwrap();
+        assert!(EncryptedFileStorage::new_with_key(temp.path(), [7; 32]).is_ok());
+    }
+}
@@ -0,0 +1,320 @@
+//! # Integrity Verification
+//!
+//! Storage wrapper that detects on-disk corruption.
+//!
+//! Every file is written as `payload || BLAKE3(payload)` and the checksum is
+//! verified on every read, so disk errors and torn writes surface as
+//! `StorageError::InvalidData` instead of garbage being deserialized.
+
+use crate::{AddressBookData, FileStorage, IdentityData, PeerCacheEntry, SessionData, Storage, StorageError};
+use otter_identity::{trust::TrustStore, PeerProfile, WebOfTrust};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// Length of the BLAKE3 checksum appended to every file
+pub const CHECKSUM_LEN: usize = 32;
+
+/// File storage that appends and verifies a BLAKE3 checksum on every file
+pub struct IntegrityVerifiedStorage {
+    inner: FileStorage,
+}
+
+impl IntegrityVerifiedStorage {
+    /// Wrap an existing file storage
+    pub fn new(inner: FileStorage) -> Self {
+        Self { inner }
+    }
+    
+    /// Append the checksum to a payload
+    fn seal(payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(payload.len() + CHECKSUM_LEN);
+        data.extend_from_slice(payload);
+        data.extend_from_slice(blake3::hash(payload).as_bytes());
+        data
+    }
+    
+    /// Verify the trailing checksum and return the payload
+    fn verify(data: &[u8]) -> Result<&[u8], StorageError> {
+        if data.len() < CHECKSUM_LEN {
+            return Err(StorageError::InvalidData(format!(
+                "checksum missing: file is only {} bytes",
+                data.len()
+            )));
+        }
+        
+        let (payload, stored) = data.split_at(data.len() - CHECKSUM_LEN);
+        let computed = blake3::hash(payload);
+        
+        if computed.as_bytes() != stored {
+            return Err(StorageError::InvalidData(format!(
+                "checksum mismatch: expected {} got {}",
+                hex::encode(stored),
+                computed.to_hex()
+            )));
+        }
+        
+        Ok(payload)
+    }
+    
+    async fn write_json<T: Serialize>(&self, path: &Path, value: &T) -> Result<(), StorageError> {
+        let payload = serde_json::to_vec_pretty(value)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        
+        self.inner.atomic_write(path, &Self::seal(&payload)).await
+    }
+    
+    async fn read_json<T: DeserializeOwned>(&self, path: &Path) -> Result<Option<T>, StorageError> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        
+        let data = self.inner.read_file(path).await?;
+        let payload = Self::verify(&data)?;
+        let value = serde_json::from_slice(payload)
+            .map_err(|e| StorageError::DeserializationError(e.to_string()))?;
+        
+        Ok(Some(value))
+    }
+    
+    /// All files managed by this storage that currently exist
+    async fn stored_files(&self) -> Result<Vec<PathBuf>, StorageError> {
+        let mut files: Vec<PathBuf> = [
+            self.inner.identity_path(),
+            self.inner.trust_store_path(),
+            self.inner.peer_cache_path(),
+            self.inner.web_of_trust_path(),
+            self.inner.profiles_path(),
+        ]
+        .into_iter()
+        .filter(|path| path.exists())
+        .collect();
+        
+        let sessions_dir = self.inner.sessions_dir();
+        if sessions_dir.exists() {
+            let mut entries = fs::read_dir(&sessions_dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if path.extension().and_then(|s| s.to_str()) == Some("json") {
+                    files.push(path);
+                }
+            }
+        }
+        
+        Ok(files)
+    }
+    
+    /// Find and delete every file whose checksum does not verify
+    ///
+    /// Returns the paths of the deleted files.
+    pub async fn repair_all(&self) -> Result<Vec<String>, StorageError> {
+        let mut removed = Vec::new();
+        
+        for path in self.stored_files().await? {
+            let data = self.inner.read_file(&path).await?;
+            if let Err(e) = Self::verify(&data) {
+                tracing::warn!("Removing corrupt file {:?}: {}", path, e);
+                fs::remove_file(&path).await?;
+                removed.push(path.display().to_string());
+            }
+        }
+        
+        Ok(removed)
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for IntegrityVerifiedStorage {
+    async fn load_identity(&self) -> Result<Option<IdentityData>, StorageError> {
+        self.read_json(&self.inner.identity_path()).await
+    }
+    
+    async fn save_identity(&self, identity: &IdentityData) -> Result<(), StorageError> {
+        self.write_json(&self.inner.identity_path(), identity).await
+    }
+    
+    async fn load_trust_store(&self) -> Result<Option<TrustStore>, StorageError> {
+        self.read_json(&self.inner.trust_store_path()).await
+    }
+    
+    async fn save_trust_store(&self, trust_store: &TrustStore) -> Result<(), StorageError> {
+        self.write_json(&self.inner.trust_store_path(), trust_store).await
+    }
+    
+    async fn load_sessions(&self) -> Result<HashMap<String, SessionData>, StorageError> {
+        let sessions_dir = self.inner.sessions_dir();
+        if !sessions_dir.exists() {
+            return Ok(HashMap::new());
+        }
+        
+        let mut sessions = HashMap::new();
+        let mut entries = fs::read_dir(&sessions_dir).await?;
+        
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json") {
+                match self.read_json::<SessionData>(&path).await {
+                    Ok(Some(session)) => {
+                        sessions.insert(session.peer_id.clone(), session);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        tracing::warn!("Failed to load session {:?}: {}", path, e);
+                    }
+                }
+            }
+        }
+        
+        Ok(sessions)
+    }
+    
+    async fn save_session(&self, peer_id: &str, session: &SessionData) -> Result<(), StorageError> {
+        self.write_json(&self.inner.session_path(peer_id), session).await
+    }
+    
+    async fn delete_session(&self, peer_id: &str) -> Result<(), StorageError> {
+        self.inner.delete_session(peer_id).await
+    }
+    
+    async fn load_peer_cache(&self) -> Result<HashMap<String, PeerCacheEntry>, StorageError> {
+        Ok(self
+            .read_json(&self.inner.peer_cache_path())
+            .await?
+            .unwrap_or_default())
+    }
+    
+    async fn save_peer_cache_entry(&self, entry: &PeerCacheEntry) -> Result<(), StorageError> {
+        self.save_peer_cache_batch(std::slice::from_ref(entry)).await
+    }
+    
+    async fn save_peer_cache_batch(&self, entries: &[PeerCacheEntry]) -> Result<(), StorageError> {
+        let mut cache = self.load_peer_cache().await?;
+        for entry in entries {
+            cache.insert(entry.peer_id.clone(), entry.clone());
+        }
+        
+        self.write_json(&self.inner.peer_cache_path(), &cache).await
+    }
+    
+    async fn load_web_of_trust(&self) -> Result<Option<WebOfTrust>, StorageError> {
+        self.read_json(&self.inner.web_of_trust_path()).await
+    }
+    
+    async fn save_web_of_trust(&self, web_of_trust: &WebOfTrust) -> Result<(), StorageError> {
+        self.write_json(&self.inner.web_of_trust_path(), web_of_trust).await
+    }
+    
+    async fn load_profiles(&self) -> Result<HashMap<String, PeerProfile>, StorageError> {
+        Ok(self
+            .read_json(&self.inner.profiles_path())
+            .await?
+            .unwrap_or_default())
+    }
+    
+    async fn save_profile(&self, peer_id: &str, profile: &PeerProfile) -> Result<(), StorageError> {
+        let mut profiles = self.load_profiles().await?;
+        profiles.insert(peer_id.to_string(), profile.clone());
+        
+        self.write_json(&self.inner.profiles_path(), &profiles).await
+    }
+    
+    async fn load_address_book(&self) -> Result<AddressBookData, StorageError> {
+        Ok(self
+            .read_json(&self.inner.address_book_path())
+            .await?
+            .unwrap_or_default())
+    }
+    
+    async fn save_address_book(&self, address_book: &AddressBookData) -> Result<(), StorageError> {
+        self.write_json(&self.inner.address_book_path(), address_book).await
+    }
+    
+    async fn clear_all(&self) -> Result<(), StorageError> {
+        self.inner.clear_all().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn create_test_storage() -> (IntegrityVerifiedStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = IntegrityVerifiedStorage::new(FileStorage::new(temp_dir.path()).unwrap());
+        (storage, temp_dir)
+    }
+    
+    fn test_identity() -> IdentityData {
+        IdentityData {
+            signing_key_bytes: vec![1, 2, 3],
+            encryption_secret_bytes: vec![4, 5, 6],
+            peer_id: "test_peer".to_string(),
+            created_at: 12345,
+        }
+    }
+    
+    async fn corrupt_last_byte(path: &Path) {
+        let mut data = fs::read(path).await.unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        fs::write(path, data).await.unwrap();
+    }
+    
+    #[tokio::test]
+    async fn test_roundtrip_with_checksum() {
+        let (storage, temp) = create_test_storage();
+        
+        storage.save_identity(&test_identity()).await.unwrap();
+        
+        let raw = fs::read(temp.path().join("identity.json")).await.unwrap();
+        let payload = serde_json::to_vec_pretty(&test_identity()).unwrap();
+        assert_eq!(raw.len(), payload.len() + CHECKSUM_LEN);
+        
+        let loaded = storage.load_identity().await.unwrap().unwrap();
+        assert_eq!(loaded.peer_id, "test_peer");
+    }
+    
+    #[tokio::test]
+    async fn test_corruption_detected() {
+        let (storage, temp) = create_test_storage();
+        
+        storage.save_identity(&test_identity()).await.unwrap();
+        corrupt_last_byte(&temp.path().join("identity.json")).await;
+        
+        match storage.load_identity().await {
+            Err(StorageError::InvalidData(msg)) => assert!(msg.starts_with("checksum mismatch")),
+            other => panic!("expected checksum mismatch, got {:?}", other.map(|_| ())),
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_repair_all_removes_corrupt_files() {
+        let (storage, temp) = create_test_storage();
+        
+        let session = SessionData {
+            peer_id: "peer1".to_string(),
+            shared_secret_bytes: vec![1, 2, 3, 4],
+            send_counter: 1,
+            receive_counter: 1,
+            created_at: 1000,
+            last_used: 2000,
+        };
+        
+        storage.save_identity(&test_identity()).await.unwrap();
+        storage.save_session("peer1", &session).await.unwrap();
+        
+        let session_path = temp.path().join("sessions").join("peer1.json");
+        corrupt_last_byte(&session_path).await;
+        
+        let removed = storage.repair_all().await.unwrap();
+        assert_eq!(removed, vec![session_path.display().to_string()]);
+        assert!(!session_path.exists());
+        
+        // Healthy files are untouched
+        assert!(storage.load_identity().await.unwrap().is_some());
+        assert!(storage.repair_all().await.unwrap().is_empty());
+    }
+}
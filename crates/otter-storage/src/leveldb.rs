@@ -0,0 +1,251 @@
+//! # LevelDB Storage
+//!
+//! Key-value storage backend for embedded nodes, where writing one JSON file
+//! per item costs too much. Every item is a single key holding JSON:
+//!
+//! - `identity`, `trust_store`, `peer_cache`, `web_of_trust`, `profiles`, `address_book`
+//! - `sessions/<peer_id>` for each session
+//!
+//! Keys sharing a prefix are stored next to each other, so sessions are loaded
+//! with one range scan.
+
+use crate::{AddressBookData, IdentityData, PeerCacheEntry, SessionData, Storage, StorageError};
+use otter_identity::{PeerProfile, WebOfTrust, trust::TrustStore};
+use rusty_leveldb::{LdbIterator, Options, WriteBatch, DB};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+const IDENTITY_KEY: &[u8] = b"identity";
+const TRUST_STORE_KEY: &[u8] = b"trust_store";
+const PEER_CACHE_KEY: &[u8] = b"peer_cache";
+const WEB_OF_TRUST_KEY: &[u8] = b"web_of_trust";
+const PROFILES_KEY: &[u8] = b"profiles";
+const ADDRESS_BOOK_KEY: &[u8] = b"address_book";
+const SESSION_PREFIX: &[u8] = b"sessions/";
+
+/// A raw key and its value
+type Entry = (Vec<u8>, Vec<u8>);
+
+/// Storage backed by a LevelDB database
+pub struct LevelDbStorage {
+    db: Mutex<DB>,
+}
+
+impl LevelDbStorage {
+    /// Open the database at `path`, creating it if it does not exist
+    pub fn new(path: &Path) -> Result<Self, StorageError> {
+        let db = DB::open(path, Options::default()).map_err(db_error)?;
+        Ok(Self { db: Mutex::new(db) })
+    }
+    
+    /// Compact the whole key range, reclaiming space from overwritten and deleted items
+    pub fn compact(&self) -> Result<(), StorageError> {
+        // All keys are ASCII, so they sort below 0xff
+        self.lock()?.compact_range(b"", &[0xff]).map_err(db_error)
+    }
+    
+    fn lock(&self) -> Result<MutexGuard<'_, DB>, StorageError> {
+        self.db
+            .lock()
+            .map_err(|_| StorageError::DatabaseError("Database lock poisoned".to_string()))
+    }
+    
+    fn session_key(peer_id: &str) -> Vec<u8> {
+        [SESSION_PREFIX, peer_id.as_bytes()].concat()
+    }
+    
+    fn get<T: DeserializeOwned>(&self, key: &[u8]) -> Result<Option<T>, StorageError> {
+        match self.lock()?.get(key) {
+            Some(data) => serde_json::from_slice(&data)
+                .map(Some)
+                .map_err(|e| StorageError::DeserializationError(e.to_string())),
+            None => Ok(None),
+        }
+    }
+    
+    fn put<T: Serialize + ?Sized>(&self, key: &[u8], value: &T) -> Result<(), StorageError> {
+        let data = serde_json::to_vec(value)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        self.lock()?.put(key, &data).map_err(db_error)
+    }
+    
+    /// Collect every key and value starting with `prefix`
+    fn scan(&self, prefix: &[u8]) -> Result<Vec<Entry>, StorageError> {
+        let mut db = self.lock()?;
+        let mut iter = db.new_iter().map_err(db_error)?;
+        iter.seek(prefix);
+        
+        let mut entries = Vec::new();
+        while let Some((key, value)) = iter.current() {
+            if !key.starts_with(prefix) {
+                break;
+            }
+            entries.push((key.to_vec(), value.to_vec()));
+            iter.advance();
+        }
+        Ok(entries)
+    }
+}
+
+fn db_error(status: rusty_leveldb::Status) -> StorageError {
+    StorageError::DatabaseError(status.to_string())
+}
+
+#[async_trait::async_trait]
+impl Storage for LevelDbStorage {
+    async fn load_identity(&self) -> Result<Option<IdentityData>, StorageError> {
+        self.get(IDENTITY_KEY)
+    }
+    
+    async fn save_identity(&self, identity: &IdentityData) -> Result<(), StorageError> {
+        self.put(IDENTITY_KEY, identity)
+    }
+    
+    async fn load_trust_store(&self) -> Result<Option<TrustStore>, StorageError> {
+        self.get(TRUST_STORE_KEY)
+    }
+    
+    async fn save_trust_store(&self, trust_store: &TrustStore) -> Result<(), StorageError> {
+        self.put(TRUST_STORE_KEY, trust_store)
+    }
+    
+    async fn load_sessions(&self) -> Result<HashMap<String, SessionData>, StorageError> {
+        let mut sessions = HashMap::new();
+        for (key, value) in self.scan(SESSION_PREFIX)? {
+            match serde_json::from_slice::<SessionData>(&value) {
+                Ok(session) => {
+                    sessions.insert(session.peer_id.clone(), session);
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to deserialize session {}: {}", String::from_utf8_lossy(&key), e);
+                }
+            }
+        }
+        Ok(sessions)
+    }
+    
+    async fn save_session(&self, peer_id: &str, session: &SessionData) -> Result<(), StorageError> {
+        self.put(&Self::session_key(peer_id), session)
+    }
+    
+    async fn delete_session(&self, peer_id: &str) -> Result<(), StorageError> {
+        self.lock()?.delete(&Self::session_key(peer_id)).map_err(db_error)
+    }
+    
+    async fn load_peer_cache(&self) -> Result<HashMap<String, PeerCacheEntry>, StorageError> {
+        Ok(self.get(PEER_CACHE_KEY)?.unwrap_or_default())
+    }
+    
+    async fn save_peer_cache_entry(&self, entry: &PeerCacheEntry) -> Result<(), StorageError> {
+        self.save_peer_cache_batch(std::slice::from_ref(entry)).await
+    }
+    
+    async fn save_peer_cache_batch(&self, entries: &[PeerCacheEntry]) -> Result<(), StorageError> {
+        let mut cache = self.load_peer_cache().await?;
+        for entry in entries {
+            cache.insert(entry.peer_id.clone(), entry.clone());
+        }
+        self.put(PEER_CACHE_KEY, &cache)
+    }
+    
+    async fn load_web_of_trust(&self) -> Result<Option<WebOfTrust>, StorageError> {
+        self.get(WEB_OF_TRUST_KEY)
+    }
+    
+    async fn save_web_of_trust(&self, web_of_trust: &WebOfTrust) -> Result<(), StorageError> {
+        self.put(WEB_OF_TRUST_KEY, web_of_trust)
+    }
+    
+    async fn load_profiles(&self) -> Result<HashMap<String, PeerProfile>, StorageError> {
+        Ok(self.get(PROFILES_KEY)?.unwrap_or_default())
+    }
+    
+    async fn save_profile(&self, peer_id: &str, profile: &PeerProfile) -> Result<(), StorageError> {
+        let mut profiles = self.load_profiles().await?;
+        profiles.insert(peer_id.to_string(), profile.clone());
+        self.put(PROFILES_KEY, &profiles)
+    }
+    
+    async fn load_address_book(&self) -> Result<AddressBookData, StorageError> {
+        Ok(self.get(ADDRESS_BOOK_KEY)?.unwrap_or_default())
+    }
+    
+    async fn save_address_book(&self, address_book: &AddressBookData) -> Result<(), StorageError> {
+        self.put(ADDRESS_BOOK_KEY, address_book)
+    }
+    
+    async fn clear_all(&self) -> Result<(), StorageError> {
+        // One batch, so a crash cannot leave half of the data behind
+        let mut batch = WriteBatch::default();
+        for (key, _) in self.scan(b"")? {
+            batch.delete(&key);
+        }
+        self.lock()?.write(batch, true).map_err(db_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    
+    fn session(peer_id: String, send_counter: u64) -> SessionData {
+        SessionData {
+            peer_id,
+            shared_secret_bytes: vec![7; 32],
+            send_counter,
+            receive_counter: 0,
+            created_at: 1000,
+            last_used: 2000,
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_leveldb_persistence() {
+        let temp = TempDir::new().unwrap();
+        {
+            let storage = LevelDbStorage::new(temp.path()).unwrap();
+            assert!(storage.load_identity().await.unwrap().is_none());
+            
+            storage.save_identity(&IdentityData {
+                signing_key_bytes: vec![1, 2, 3],
+                encryption_secret_bytes: vec![4, 5, 6],
+                peer_id: "test_peer".to_string(),
+                created_at: 12345,
+            }).await.unwrap();
+            storage.save_session("peer1", &session("peer1".to_string(), 10)).await.unwrap();
+            storage.save_session("peer2", &session("peer2".to_string(), 20)).await.unwrap();
+            storage.delete_session("peer2").await.unwrap();
+            storage.compact().unwrap();
+        }
+        
+        // Reopen to check that the data reached the disk
+        let storage = LevelDbStorage::new(temp.path()).unwrap();
+        assert_eq!(storage.load_identity().await.unwrap().unwrap().peer_id, "test_peer");
+        let sessions = storage.load_sessions().await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions["peer1"].send_counter, 10);
+        
+        storage.clear_all().await.unwrap();
+        assert!(storage.load_identity().await.unwrap().is_none());
+        assert!(storage.load_sessions().await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_leveldb_many_sessions() {
+        let temp = TempDir::new().unwrap();
+        let storage = LevelDbStorage::new(temp.path()).unwrap();
+        
+        for i in 0..10_000u64 {
+            let peer_id = format!("peer{}", i);
+            storage.save_session(&peer_id, &session(peer_id.clone(), i)).await.unwrap();
+        }
+        let sessions = storage.load_sessions().await.unwrap();
+        
+        assert_eq!(sessions.len(), 10_000);
+        assert_eq!(sessions["peer0"].send_counter, 0);
+        assert_eq!(sessions["peer9999"].send_counter, 9999);
+    }
+}
@@ -5,12 +5,33 @@
 //! This crate provides:
 //! - Storage trait for pluggable backends
 //! - File-based storage implementation with atomic writes
+//! - LevelDB storage for embedded nodes
 //! - Identity key persistence
 //! - Trust store persistence
 //! - Session state management
-//! - Peer cache persistence
+//! - Peer cache persistence, with batched writes for bursts of discoveries
+//! - Signed peer profiles
+//! - Address book annotations
+//! - BLAKE3 integrity verification
+//! - Encryption at rest under a passphrase or raw key
+//! - Versioned schema migrations
+//! - Write-ahead log replayed after a crash
 
-use otter_identity::{Identity, PublicIdentity, trust::TrustStore};
+pub mod batch;
+pub mod encrypted;
+pub mod integrity;
+pub mod leveldb;
+pub mod migration;
+pub mod wal;
+
+pub use batch::{DelayedFlush, PEER_CACHE_FLUSH_DELAY};
+pub use encrypted::EncryptedFileStorage;
+pub use integrity::IntegrityVerifiedStorage;
+pub use leveldb::LevelDbStorage;
+pub use migration::{MigrationRunner, SchemaVersion, CURRENT_SCHEMA_VERSION};
+pub use wal::{WalEntry, WriteOp};
+
+use otter_identity::{PeerProfile, PublicIdentity, WebOfTrust, trust::TrustStore};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
@@ -30,6 +51,12 @@ pub enum StorageError {
     NotFound(String),
     #[error("Invalid data: {0}")]
     InvalidData(String),
+    #[error("Migration from schema v{from} to v{to} failed: {reason}")]
+    MigrationFailed { from: u32, to: u32, reason: String },
+    #[error("Database error: {0}")]
+    DatabaseError(String),
+    #[error("Write-ahead log is corrupt: {0}")]
+    WalCorrupt(String),
 }
 
 /// Persisted identity data
@@ -61,6 +88,22 @@ pub struct PeerCacheEntry {
     pub last_seen: i64,
 }
 
+/// User annotations for one peer, as stored
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AddressBookRecord {
+    pub peer_id: String,
+    pub label: Option<String>,
+    pub notes: Option<String>,
+    pub pinned: bool,
+    pub tags: Vec<String>,
+}
+
+/// Stored address book, keyed by peer ID
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct AddressBookData {
+    pub entries: HashMap<String, AddressBookRecord>,
+}
+
 /// Trait for storage backends
 #[async_trait::async_trait]
 pub trait Storage: Send + Sync {
@@ -91,6 +134,27 @@ pub trait Storage: Send + Sync {
     /// Save peer cache entry
     async fn save_peer_cache_entry(&self, entry: &PeerCacheEntry) -> Result<(), StorageError>;
     
+    /// Save several peer cache entries with a single read and write
+    async fn save_peer_cache_batch(&self, entries: &[PeerCacheEntry]) -> Result<(), StorageError>;
+    
+    /// Load the web-of-trust graph
+    async fn load_web_of_trust(&self) -> Result<Option<WebOfTrust>, StorageError>;
+    
+    /// Save the web-of-trust graph
+    async fn save_web_of_trust(&self, web_of_trust: &WebOfTrust) -> Result<(), StorageError>;
+    
+    /// Load peer profiles, keyed by peer ID
+    async fn load_profiles(&self) -> Result<HashMap<String, PeerProfile>, StorageError>;
+    
+    /// Save the profile of `peer_id`, replacing any older one
+    async fn save_profile(&self, peer_id: &str, profile: &PeerProfile) -> Result<(), StorageError>;
+    
+    /// Load the address book, empty if none was saved
+    async fn load_address_book(&self) -> Result<AddressBookData, StorageError>;
+    
+    /// Save the address book, replacing the stored one
+    async fn save_address_book(&self, address_book: &AddressBookData) -> Result<(), StorageError>;
+    
     /// Clear all data (for testing)
     async fn clear_all(&self) -> Result<(), StorageError>;
 }
@@ -98,62 +162,151 @@ pub trait Storage: Send + Sync {
 /// File-based storage implementation
 pub struct FileStorage {
     base_path: PathBuf,
+    /// Logged writes not yet committed; also serializes appends to the log
+    wal_in_flight: tokio::sync::Mutex<usize>,
 }
 
 impl FileStorage {
     /// Create a new file storage instance
-    pub fn new<P: AsRef<Path>>(base_path: P) -> Self {
-        Self {
+    ///
+    /// Writes interrupted by a crash are replayed from the write-ahead log
+    /// first.
+    pub fn new<P: AsRef<Path>>(base_path: P) -> Result<Self, StorageError> {
+        let storage = Self {
             base_path: base_path.as_ref().to_path_buf(),
+            wal_in_flight: tokio::sync::Mutex::new(0),
+        };
+        storage.recover_wal()?;
+        Ok(storage)
+    }
+    
+    /// Open a storage directory, migrating it to the newest schema version first
+    ///
+    /// Writes interrupted by a crash are replayed from the write-ahead log
+    /// before migrating.
+    pub fn open<P: AsRef<Path>>(base_path: P, migrations: &MigrationRunner) -> Result<Self, StorageError> {
+        let storage = Self::new(base_path)?;
+        let current = SchemaVersion::load(&storage)?;
+        let target = migrations.target_version();
+        
+        if current.version != target {
+            migrations.run(&storage, current.version, target)?;
+        } else if !storage.schema_path().exists() {
+            SchemaVersion {
+                version: target,
+                migrated_at: chrono::Utc::now().timestamp(),
+            }
+            .save(&storage)?;
         }
+        
+        Ok(storage)
+    }
+    
+    /// Get the storage directory
+    pub fn base_path(&self) -> &Path {
+        &self.base_path
     }
     
     /// Get path for identity file
-    fn identity_path(&self) -> PathBuf {
+    pub(crate) fn identity_path(&self) -> PathBuf {
         self.base_path.join("identity.json")
     }
     
     /// Get path for trust store file
-    fn trust_store_path(&self) -> PathBuf {
+    pub(crate) fn trust_store_path(&self) -> PathBuf {
         self.base_path.join("trust_store.json")
     }
     
     /// Get path for sessions directory
-    fn sessions_dir(&self) -> PathBuf {
+    pub(crate) fn sessions_dir(&self) -> PathBuf {
         self.base_path.join("sessions")
     }
     
     /// Get path for specific session file
-    fn session_path(&self, peer_id: &str) -> PathBuf {
+    pub(crate) fn session_path(&self, peer_id: &str) -> PathBuf {
         self.sessions_dir().join(format!("{}.json", peer_id))
     }
     
     /// Get path for peer cache file
-    fn peer_cache_path(&self) -> PathBuf {
+    pub(crate) fn peer_cache_path(&self) -> PathBuf {
         self.base_path.join("peer_cache.json")
     }
     
+    /// Get path for the web-of-trust graph
+    pub(crate) fn web_of_trust_path(&self) -> PathBuf {
+        self.base_path.join("web_of_trust.json")
+    }
+    
+    /// Get path for peer profiles file
+    pub(crate) fn profiles_path(&self) -> PathBuf {
+        self.base_path.join("profiles.json")
+    }
+    
+    /// Get path for address book file
+    pub(crate) fn address_book_path(&self) -> PathBuf {
+        self.base_path.join("address_book.json")
+    }
+    
+    /// Get path for schema version stamp
+    pub(crate) fn schema_path(&self) -> PathBuf {
+        self.base_path.join("schema.json")
+    }
+    
     /// Atomically write data to a file
-    async fn atomic_write(&self, path: &Path, data: &[u8]) -> Result<(), StorageError> {
+    ///
+    /// The write is logged to the write-ahead log first and marked committed
+    /// once the rename is on disk, or rolled back if it fails.
+    pub(crate) async fn atomic_write(&self, path: &Path, data: &[u8]) -> Result<(), StorageError> {
+        let target = self.wal_target(path);
+        self.append_wal(&[WalEntry::new(WriteOp::Write, target.clone(), data.to_vec())]).await?;
+        
+        let temp_path = path.with_extension("tmp");
+        match Self::write_and_rename(&temp_path, path, data).await {
+            Ok(()) => self.append_wal(&[WalEntry::committed(target)]).await,
+            Err(e) => {
+                let _ = fs::remove_file(&temp_path).await;
+                self.append_wal(&[WalEntry::rolled_back(target)]).await?;
+                Err(e)
+            }
+        }
+    }
+    
+    /// Write `data` to `temp_path` and rename it over `path`
+    async fn write_and_rename(temp_path: &Path, path: &Path, data: &[u8]) -> Result<(), StorageError> {
         // Ensure parent directory exists
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent).await?;
         }
         
         // Write to temp file first
-        let temp_path = path.with_extension("tmp");
-        let mut file = fs::File::create(&temp_path).await?;
+        let mut file = fs::File::create(temp_path).await?;
         file.write_all(data).await?;
         file.sync_all().await?;
         
         // Atomic rename
-        fs::rename(&temp_path, path).await?;
-        
+        fs::rename(temp_path, path).await?;
+        if let Some(parent) = path.parent() {
+            wal::sync_dir(parent);
+        }
         Ok(())
     }
     
+    /// Remove a file through the write-ahead log
+    pub(crate) async fn logged_remove(&self, path: &Path) -> Result<(), StorageError> {
+        let target = self.wal_target(path);
+        self.append_wal(&[WalEntry::new(WriteOp::Delete, target.clone(), Vec::new())]).await?;
+        if let Err(e) = fs::remove_file(path).await {
+            self.append_wal(&[WalEntry::rolled_back(target)]).await?;
+            return Err(e.into());
+        }
+        if let Some(parent) = path.parent() {
+            wal::sync_dir(parent);
+        }
+        self.append_wal(&[WalEntry::committed(target)]).await
+    }
+    
     /// Read file contents
-    async fn read_file(&self, path: &Path) -> Result<Vec<u8>, StorageError> {
+    pub(crate) async fn read_file(&self, path: &Path) -> Result<Vec<u8>, StorageError> {
         if !path.exists() {
             return Err(StorageError::NotFound(format!("{:?}", path)));
         }
@@ -250,7 +403,7 @@ impl Storage for FileStorage {
     async fn delete_session(&self, peer_id: &str) -> Result<(), StorageError> {
         let path = self.session_path(peer_id);
         if path.exists() {
-            fs::remove_file(path).await?;
+            self.logged_remove(&path).await?;
         }
         Ok(())
     }
@@ -269,8 +422,14 @@ impl Storage for FileStorage {
     }
     
     async fn save_peer_cache_entry(&self, entry: &PeerCacheEntry) -> Result<(), StorageError> {
+        self.save_peer_cache_batch(std::slice::from_ref(entry)).await
+    }
+    
+    async fn save_peer_cache_batch(&self, entries: &[PeerCacheEntry]) -> Result<(), StorageError> {
         let mut cache = self.load_peer_cache().await?;
-        cache.insert(entry.peer_id.clone(), entry.clone());
+        for entry in entries {
+            cache.insert(entry.peer_id.clone(), entry.clone());
+        }
         
         let data = serde_json::to_vec_pretty(&cache)
             .map_err(|e| StorageError::SerializationError(e.to_string()))?;
@@ -278,6 +437,66 @@ impl Storage for FileStorage {
         self.atomic_write(&self.peer_cache_path(), &data).await
     }
     
+    async fn load_web_of_trust(&self) -> Result<Option<WebOfTrust>, StorageError> {
+        let path = self.web_of_trust_path();
+        if !path.exists() {
+            return Ok(None);
+        }
+        
+        let data = self.read_file(&path).await?;
+        let web_of_trust: WebOfTrust = serde_json::from_slice(&data)
+            .map_err(|e| StorageError::DeserializationError(e.to_string()))?;
+        
+        Ok(Some(web_of_trust))
+    }
+    
+    async fn save_web_of_trust(&self, web_of_trust: &WebOfTrust) -> Result<(), StorageError> {
+        let data = serde_json::to_vec_pretty(web_of_trust)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        
+        self.atomic_write(&self.web_of_trust_path(), &data).await
+    }
+    
+    async fn load_profiles(&self) -> Result<HashMap<String, PeerProfile>, StorageError> {
+        let path = self.profiles_path();
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        
+        let data = self.read_file(&path).await?;
+        let profiles: HashMap<String, PeerProfile> = serde_json::from_slice(&data)
+            .map_err(|e| StorageError::DeserializationError(e.to_string()))?;
+        
+        Ok(profiles)
+    }
+    
+    async fn save_profile(&self, peer_id: &str, profile: &PeerProfile) -> Result<(), StorageError> {
+        let mut profiles = self.load_profiles().await?;
+        profiles.insert(peer_id.to_string(), profile.clone());
+        
+        let data = serde_json::to_vec_pretty(&profiles)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        
+        self.atomic_write(&self.profiles_path(), &data).await
+    }
+    
+    async fn load_address_book(&self) -> Result<AddressBookData, StorageError> {
+        let path = self.address_book_path();
+        if !path.exists() {
+            return Ok(AddressBookData::default());
+        }
+        
+        let data = self.read_file(&path).await?;
+        serde_json::from_slice(&data).map_err(|e| StorageError::DeserializationError(e.to_string()))
+    }
+    
+    async fn save_address_book(&self, address_book: &AddressBookData) -> Result<(), StorageError> {
+        let data = serde_json::to_vec_pretty(address_book)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        
+        self.atomic_write(&self.address_book_path(), &data).await
+    }
+    
     async fn clear_all(&self) -> Result<(), StorageError> {
         if self.base_path.exists() {
             fs::remove_dir_all(&self.base_path).await?;
@@ -295,7 +514,7 @@ mod tests {
     
     async fn create_test_storage() -> (FileStorage, TempDir) {
         let temp_dir = TempDir::new().unwrap();
-        let storage = FileStorage::new(temp_dir.path());
+        let storage = FileStorage::new(temp_dir.path()).unwrap();
         (storage, temp_dir)
     }
     
@@ -369,6 +588,90 @@ mod tests {
         assert_eq!(cache.values().next().unwrap().addresses.len(), 2);
     }
     
+    #[tokio::test]
+    async fn test_peer_cache_batch_merges_entries() {
+        let (storage, _temp) = create_test_storage().await;
+        let public_identity = PublicIdentity::from_identity(&Identity::generate().unwrap());
+        let entry = |peer_id: &str, last_seen| PeerCacheEntry {
+            peer_id: peer_id.to_string(),
+            public_identity: public_identity.clone(),
+            addresses: Vec::new(),
+            last_seen,
+        };
+        
+        storage.save_peer_cache_entry(&entry("peer1", 1)).await.unwrap();
+        storage
+            .save_peer_cache_batch(&[entry("peer1", 2), entry("peer2", 2), entry("peer3", 2)])
+            .await
+            .unwrap();
+        
+        let cache = storage.load_peer_cache().await.unwrap();
+        assert_eq!(cache.len(), 3);
+        assert!(cache.values().all(|entry| entry.last_seen == 2));
+    }
+    
+    #[tokio::test]
+    async fn test_web_of_trust_persistence() {
+        let (storage, _temp) = create_test_storage().await;
+        
+        assert!(storage.load_web_of_trust().await.unwrap().is_none());
+        
+        let owner = otter_identity::Identity::generate().unwrap();
+        let friend = otter_identity::Identity::generate().unwrap();
+        let mut wot = WebOfTrust::new(owner.peer_id().clone());
+        let signature = WebOfTrust::vouch(&owner, friend.peer_id()).unwrap();
+        wot.add_vouch(&PublicIdentity::from_identity(&owner), friend.peer_id().clone(), signature)
+            .unwrap();
+        storage.save_web_of_trust(&wot).await.unwrap();
+        
+        let loaded = storage.load_web_of_trust().await.unwrap().unwrap();
+        assert_eq!(loaded.verify_vouching_chain(friend.peer_id()).len(), 2);
+    }
+    
+    #[tokio::test]
+    async fn test_profile_persistence() {
+        let (storage, _temp) = create_test_storage().await;
+        
+        assert!(storage.load_profiles().await.unwrap().is_empty());
+        
+        let identity = Identity::generate().unwrap();
+        let profile = identity.create_profile("Alice".to_string(), None, None).unwrap();
+        storage.save_profile(identity.peer_id().as_str(), &profile).await.unwrap();
+        
+        let loaded = storage.load_profiles().await.unwrap();
+        let stored = loaded.get(identity.peer_id().as_str()).unwrap();
+        assert_eq!(stored, &profile);
+        assert!(PublicIdentity::from_identity(&identity).verify_profile(stored).is_ok());
+    }
+    
+    #[tokio::test]
+    async fn test_address_book_persistence() {
+        let temp = TempDir::new().unwrap();
+        let peer_id = "12D3KooWHJ8Ss3kpBXDMR2mdHLietLETYo76TtpNzfShiqAfg27Y";
+        
+        {
+            let storage = FileStorage::new(temp.path()).unwrap();
+            assert!(storage.load_address_book().await.unwrap().entries.is_empty());
+            
+            let mut book = AddressBookData::default();
+            book.entries.insert(peer_id.to_string(), AddressBookRecord {
+                peer_id: peer_id.to_string(),
+                label: Some("Alice".to_string()),
+                notes: Some("Relay operator".to_string()),
+                pinned: false,
+                tags: vec!["relay".to_string()],
+            });
+            storage.save_address_book(&book).await.unwrap();
+        }
+        
+        let storage = FileStorage::new(temp.path()).unwrap();
+        let book = storage.load_address_book().await.unwrap();
+        let entry = book.entries.get(peer_id).unwrap();
+        assert_eq!(entry.label.as_deref(), Some("Alice"));
+        assert_eq!(entry.notes.as_deref(), Some("Relay operator"));
+        assert_eq!(entry.tags, ["relay"]);
+    }
+    
     #[tokio::test]
     async fn test_atomic_write() {
         let (storage, _temp) = create_test_storage().await;
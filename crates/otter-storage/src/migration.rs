@@ -0,0 +1,231 @@
+//! # Schema Migrations
+//!
+//! Upgrades files written by older builds when the persisted formats change.
+//!
+//! The schema version of a storage directory is stamped in `schema.json`.
+//! When `FileStorage::open` finds an older stamp, it runs the registered
+//! migration steps in order and bumps the stamp after each one, so an
+//! interrupted upgrade resumes from the last completed step.
+
+use crate::{FileStorage, StorageError};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Schema version written by this build when no migrations are registered
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// Version stamp stored in `schema.json`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SchemaVersion {
+    pub version: u32,
+    pub migrated_at: i64,
+}
+
+impl SchemaVersion {
+    /// Read the stamp from a storage directory
+    ///
+    /// Directories without a stamp predate versioning and are treated as version 1.
+    pub fn load(storage: &FileStorage) -> Result<Self, StorageError> {
+        let path = storage.schema_path();
+        if !path.exists() {
+            return Ok(Self {
+                version: 1,
+                migrated_at: 0,
+            });
+        }
+        
+        let data = std::fs::read(&path)?;
+        serde_json::from_slice(&data).map_err(|e| StorageError::DeserializationError(e.to_string()))
+    }
+    
+    /// Write the stamp atomically
+    pub fn save(&self, storage: &FileStorage) -> Result<(), StorageError> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+        write_atomic(&storage.schema_path(), &data)
+    }
+}
+
+/// A single migration from one schema version to the next
+pub type MigrationStep = Box<dyn Fn(&FileStorage) -> Result<(), StorageError> + Send + Sync>;
+
+/// Ordered set of migration steps
+#[derive(Default)]
+pub struct MigrationRunner {
+    steps: BTreeMap<u32, MigrationStep>,
+}
+
+impl MigrationRunner {
+    /// Create a runner with no steps
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Register the step that upgrades `from_version` to `from_version + 1`
+    pub fn add_step(
+        &mut self,
+        from_version: u32,
+        step: impl Fn(&FileStorage) -> Result<(), StorageError> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.steps.insert(from_version, Box::new(step));
+        self
+    }
+    
+    /// Newest schema version reachable with the registered steps
+    pub fn target_version(&self) -> u32 {
+        self.steps
+            .keys()
+            .next_back()
+            .map(|from| from + 1)
+            .unwrap_or(CURRENT_SCHEMA_VERSION)
+            .max(CURRENT_SCHEMA_VERSION)
+    }
+    
+    /// Run every step from `from` up to `to`, stamping the version after each
+    pub fn run(&self, storage: &FileStorage, from: u32, to: u32) -> Result<(), StorageError> {
+        if from > to {
+            return Err(StorageError::MigrationFailed {
+                from,
+                to,
+                reason: "stored schema is newer than this build".to_string(),
+            });
+        }
+        
+        for version in from..to {
+            let step = self.steps.get(&version).ok_or_else(|| StorageError::MigrationFailed {
+                from: version,
+                to: version + 1,
+                reason: "no migration registered".to_string(),
+            })?;
+            
+            step(storage).map_err(|e| StorageError::MigrationFailed {
+                from: version,
+                to: version + 1,
+                reason: e.to_string(),
+            })?;
+            
+            SchemaVersion {
+                version: version + 1,
+                migrated_at: chrono::Utc::now().timestamp(),
+            }
+            .save(storage)?;
+            
+            tracing::info!("Migrated storage schema from v{} to v{}", version, version + 1);
+        }
+        
+        Ok(())
+    }
+}
+
+/// Write to a temp file and rename it over `path`
+fn write_atomic(path: &Path, data: &[u8]) -> Result<(), StorageError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    
+    let temp_path = path.with_extension("tmp");
+    std::fs::write(&temp_path, data)?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SessionData, Storage};
+    use tempfile::TempDir;
+    
+    /// v1 -> v2: sessions gain a `last_seen` field, initialized from `last_used`
+    fn add_session_last_seen(storage: &FileStorage) -> Result<(), StorageError> {
+        let sessions_dir = storage.base_path().join("sessions");
+        if !sessions_dir.exists() {
+            return Ok(());
+        }
+        
+        for entry in std::fs::read_dir(&sessions_dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            
+            let mut session: serde_json::Value = serde_json::from_slice(&std::fs::read(&path)?)
+                .map_err(|e| StorageError::DeserializationError(e.to_string()))?;
+            let last_used = session["last_used"].clone();
+            session["last_seen"] = last_used;
+            
+            let data = serde_json::to_vec_pretty(&session)
+                .map_err(|e| StorageError::SerializationError(e.to_string()))?;
+            write_atomic(&path, &data)?;
+        }
+        
+        Ok(())
+    }
+    
+    #[tokio::test]
+    async fn test_v1_to_v2_migration() {
+        let temp_dir = TempDir::new().unwrap();
+        
+        // Data written by a v1 build
+        let storage = FileStorage::new(temp_dir.path()).unwrap();
+        let session = SessionData {
+            peer_id: "peer1".to_string(),
+            shared_secret_bytes: vec![1, 2, 3, 4],
+            send_counter: 10,
+            receive_counter: 5,
+            created_at: 1000,
+            last_used: 2000,
+        };
+        storage.save_session("peer1", &session).await.unwrap();
+        
+        let mut runner = MigrationRunner::new();
+        runner.add_step(1, add_session_last_seen);
+        assert_eq!(runner.target_version(), 2);
+        
+        let storage = FileStorage::open(temp_dir.path(), &runner).unwrap();
+        assert_eq!(SchemaVersion::load(&storage).unwrap().version, 2);
+        
+        let raw: serde_json::Value = serde_json::from_slice(
+            &std::fs::read(temp_dir.path().join("sessions/peer1.json")).unwrap(),
+        ).unwrap();
+        assert_eq!(raw["last_seen"], 2000);
+        
+        // Upgraded files still load
+        let sessions = storage.load_sessions().await.unwrap();
+        assert_eq!(sessions.get("peer1").unwrap().send_counter, 10);
+        
+        // Opening again is a no-op
+        let failing = {
+            let mut runner = MigrationRunner::new();
+            runner.add_step(1, |_: &FileStorage| Err(StorageError::InvalidData("ran twice".to_string())));
+            runner
+        };
+        assert!(FileStorage::open(temp_dir.path(), &failing).is_ok());
+    }
+    
+    #[test]
+    fn test_migration_failures() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileStorage::new(temp_dir.path()).unwrap();
+        
+        // Missing step
+        let runner = MigrationRunner::new();
+        assert!(matches!(
+            runner.run(&storage, 1, 2),
+            Err(StorageError::MigrationFailed { from: 1, to: 2, .. })
+        ));
+        
+        // Failing step leaves the stamp untouched
+        let mut runner = MigrationRunner::new();
+        runner.add_step(1, |_: &FileStorage| Err(StorageError::InvalidData("boom".to_string())));
+        assert!(matches!(
+            FileStorage::open(temp_dir.path(), &runner),
+            Err(StorageError::MigrationFailed { from: 1, to: 2, reason }) if reason.contains("boom")
+        ));
+        assert_eq!(SchemaVersion::load(&storage).unwrap().version, 1);
+        
+        // Downgrades are refused
+        SchemaVersion { version: 5, migrated_at: 0 }.save(&storage).unwrap();
+        assert!(FileStorage::open(temp_dir.path(), &MigrationRunner::new()).is_err());
+    }
+}
@@ -0,0 +1,345 @@
+//! # Write-Ahead Log
+//!
+//! `FileStorage` writes through a temp file and a rename, but a crash can
+//! still lose the rename before the file system journals it. Every write is
+//! therefore first appended to `wal.bin` in the storage directory, and a
+//! `Committed` marker follows once the rename is done, or a `RolledBack`
+//! marker if the write failed. Whenever a `FileStorage` is created,
+//! [`FileStorage::recover_wal`] replays the writes that never got a marker
+//! and truncates the log. The log is also truncated whenever no
+//! write is in flight, so it stays small on a running node.
+//!
+//! Entries are stored back to back as
+//! `op: u8 || path_len: u32 || path || data_len: u32 || data || crc32: u32`,
+//! little endian, with the CRC32 covering everything before it. A torn
+//! entry at the end of the log is a write that was never started and is
+//! dropped; a complete entry with a wrong CRC fails recovery with
+//! `StorageError::WalCorrupt`.
+
+use crate::{FileStorage, StorageError};
+use std::path::{Path, PathBuf};
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// Name of the log inside the storage directory
+pub const WAL_FILE_NAME: &str = "wal.bin";
+
+/// What a log entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WriteOp {
+    /// `data` is written to `path`
+    Write,
+    /// `path` is removed
+    Delete,
+    /// The oldest uncommitted operation on `path` is on disk
+    Committed,
+    /// The oldest uncommitted operation on `path` failed and was undone
+    RolledBack,
+}
+
+impl WriteOp {
+    fn to_byte(self) -> u8 {
+        match self {
+            WriteOp::Write => 1,
+            WriteOp::Delete => 2,
+            WriteOp::Committed => 3,
+            WriteOp::RolledBack => 4,
+        }
+    }
+    
+    fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            1 => Some(WriteOp::Write),
+            2 => Some(WriteOp::Delete),
+            3 => Some(WriteOp::Committed),
+            4 => Some(WriteOp::RolledBack),
+            _ => None,
+        }
+    }
+}
+
+/// One record of the write-ahead log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalEntry {
+    pub op: WriteOp,
+    /// Target file, relative to the storage directory
+    pub path: String,
+    pub data: Vec<u8>,
+    /// CRC32 of the encoded op, path and data
+    pub crc32: u32,
+}
+
+impl WalEntry {
+    /// Create an entry, computing its CRC
+    pub fn new(op: WriteOp, path: String, data: Vec<u8>) -> Self {
+        let mut entry = Self { op, path, data, crc32: 0 };
+        entry.crc32 = crc32fast::hash(&entry.body());
+        entry
+    }
+    
+    /// Marker that the operation on `path` completed
+    pub fn committed(path: String) -> Self {
+        Self::new(WriteOp::Committed, path, Vec::new())
+    }
+    
+    /// Marker that the operation on `path` failed and must not be replayed
+    pub fn rolled_back(path: String) -> Self {
+        Self::new(WriteOp::RolledBack, path, Vec::new())
+    }
+    
+    /// Encoded fields covered by the CRC
+    fn body(&self) -> Vec<u8> {
+        let mut body = Vec::with_capacity(9 + self.path.len() + self.data.len());
+        body.push(self.op.to_byte());
+        body.extend_from_slice(&(self.path.len() as u32).to_le_bytes());
+        body.extend_from_slice(self.path.as_bytes());
+        body.extend_from_slice(&(self.data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&self.data);
+        body
+    }
+    
+    /// Binary form of the entry
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = self.body();
+        bytes.extend_from_slice(&self.crc32.to_le_bytes());
+        bytes
+    }
+    
+    /// Decode the entry at the start of `bytes` and its encoded length
+    ///
+    /// `Ok(None)` means `bytes` ends before the entry does.
+    pub fn decode(bytes: &[u8]) -> Result<Option<(Self, usize)>, StorageError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let Some(op) = reader.take(1) else { return Ok(None) };
+        let Some(path) = reader.take_prefixed() else { return Ok(None) };
+        let Some(data) = reader.take_prefixed() else { return Ok(None) };
+        let body_len = reader.pos;
+        let Some(crc) = reader.take(4) else { return Ok(None) };
+        
+        let crc32 = u32::from_le_bytes(crc.try_into().expect("four bytes"));
+        if crc32fast::hash(&bytes[..body_len]) != crc32 {
+            return Err(StorageError::WalCorrupt(format!("CRC mismatch in entry of {} bytes", reader.pos)));
+        }
+        
+        let op = WriteOp::from_byte(op[0])
+            .ok_or_else(|| StorageError::WalCorrupt(format!("unknown operation {}", op[0])))?;
+        let path = String::from_utf8(path.to_vec())
+            .map_err(|_| StorageError::WalCorrupt("path is not UTF-8".to_string()))?;
+        Ok(Some((Self { op, path, data: data.to_vec(), crc32 }, reader.pos)))
+    }
+}
+
+/// Cursor over an encoded entry
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(slice)
+    }
+    
+    fn take_prefixed(&mut self) -> Option<&'a [u8]> {
+        let len = u32::from_le_bytes(self.take(4)?.try_into().ok()?);
+        self.take(len as usize)
+    }
+}
+
+/// Entries of `log` that have neither a `Committed` nor a `RolledBack` marker, in log order
+fn uncommitted(log: &[u8]) -> Result<Vec<WalEntry>, StorageError> {
+    let mut entries: Vec<Option<WalEntry>> = Vec::new();
+    let mut pos = 0;
+    while pos < log.len() {
+        let Some((entry, len)) = WalEntry::decode(&log[pos..])? else {
+            warn!("Dropping torn write-ahead log entry at byte {}", pos);
+            break;
+        };
+        pos += len;
+        
+        if matches!(entry.op, WriteOp::Committed | WriteOp::RolledBack) {
+            let pending = entries
+                .iter_mut()
+                .find(|pending| pending.as_ref().is_some_and(|pending| pending.path == entry.path));
+            match pending {
+                Some(pending) => *pending = None,
+                None => {
+                    return Err(StorageError::WalCorrupt(format!("{:?} of {} without a write", entry.op, entry.path)));
+                }
+            }
+        } else {
+            entries.push(Some(entry));
+        }
+    }
+    Ok(entries.into_iter().flatten().collect())
+}
+
+impl FileStorage {
+    /// Path of the write-ahead log
+    pub(crate) fn wal_path(&self) -> PathBuf {
+        self.base_path().join(WAL_FILE_NAME)
+    }
+    
+    /// Log path of `path`, relative to the storage directory when inside it
+    pub(crate) fn wal_target(&self, path: &Path) -> String {
+        path.strip_prefix(self.base_path()).unwrap_or(path).to_string_lossy().into_owned()
+    }
+    
+    /// Append `entries` to the log and flush it to disk
+    ///
+    /// Once the entries leave no write uncommitted, the log is truncated
+    /// instead.
+    pub(crate) async fn append_wal(&self, entries: &[WalEntry]) -> Result<(), StorageError> {
+        let mut in_flight = self.wal_in_flight.lock().await;
+        for entry in entries {
+            match entry.op {
+                WriteOp::Committed | WriteOp::RolledBack => *in_flight = in_flight.saturating_sub(1),
+                WriteOp::Write | WriteOp::Delete => *in_flight += 1,
+            }
+        }
+        
+        tokio::fs::create_dir_all(self.base_path()).await?;
+        let mut file = OpenOptions::new().create(true).append(true).open(self.wal_path()).await?;
+        if *in_flight == 0 {
+            file.set_len(0).await?;
+        } else {
+            let bytes: Vec<u8> = entries.iter().flat_map(WalEntry::encode).collect();
+            file.write_all(&bytes).await?;
+        }
+        file.sync_data().await?;
+        Ok(())
+    }
+    
+    /// Replay the writes left uncommitted by a crash, then truncate the log
+    ///
+    /// Returns the number of replayed entries. `FileStorage::new` runs this,
+    /// so `FileStorage::open` does before any migration.
+    pub fn recover_wal(&self) -> Result<usize, StorageError> {
+        let wal_path = self.wal_path();
+        if !wal_path.exists() {
+            return Ok(0);
+        }
+        
+        let pending = uncommitted(&std::fs::read(&wal_path)?)?;
+        for entry in &pending {
+            let path = self.base_path().join(&entry.path);
+            match entry.op {
+                WriteOp::Write => {
+                    if let Some(parent) = path.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    let temp_path = path.with_extension("tmp");
+                    std::fs::write(&temp_path, &entry.data)?;
+                    std::fs::File::open(&temp_path)?.sync_all()?;
+                    std::fs::rename(&temp_path, &path)?;
+                }
+                WriteOp::Delete => match std::fs::remove_file(&path) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+                    _ => {}
+                },
+                WriteOp::Committed | WriteOp::RolledBack => unreachable!("markers are not pending"),
+            }
+        }
+        sync_dir(self.base_path());
+        
+        let wal = std::fs::OpenOptions::new().write(true).open(&wal_path)?;
+        wal.set_len(0)?;
+        wal.sync_all()?;
+        
+        if !pending.is_empty() {
+            info!("Replayed {} uncommitted writes from the write-ahead log", pending.len());
+        }
+        Ok(pending.len())
+    }
+}
+
+/// Flush the directory entry of renames in `dir`, where the platform allows it
+pub(crate) fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(dir) = std::fs::File::open(dir) {
+        let _ = dir.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SessionData, Storage};
+    use tempfile::TempDir;
+    
+    fn session(counter: u64) -> SessionData {
+        SessionData {
+            peer_id: "peer1".to_string(),
+            shared_secret_bytes: vec![7; 32],
+            send_counter: counter,
+            receive_counter: counter,
+            created_at: 100,
+            last_used: 200,
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_recover_write_interrupted_before_rename() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileStorage::new(temp_dir.path()).unwrap();
+        storage.save_session("peer1", &session(1)).await.unwrap();
+        
+        // Crash after logging the next write, before its rename
+        let data = serde_json::to_vec_pretty(&session(2)).unwrap();
+        let target = storage.wal_target(&storage.session_path("peer1"));
+        storage.append_wal(&[WalEntry::new(WriteOp::Write, target, data)]).await.unwrap();
+        assert_eq!(storage.load_sessions().await.unwrap()["peer1"].send_counter, 1);
+        
+        // Creating the storage again replays the write
+        let restarted = FileStorage::new(temp_dir.path()).unwrap();
+        assert_eq!(restarted.load_sessions().await.unwrap()["peer1"].send_counter, 2);
+        
+        // The log is emptied, not removed, and committed writes leave it empty
+        assert_eq!(std::fs::metadata(restarted.wal_path()).unwrap().len(), 0);
+        restarted.save_session("peer1", &session(3)).await.unwrap();
+        restarted.delete_session("peer1").await.unwrap();
+        assert_eq!(std::fs::metadata(restarted.wal_path()).unwrap().len(), 0);
+        assert_eq!(restarted.recover_wal().unwrap(), 0);
+        assert!(restarted.load_sessions().await.unwrap().is_empty());
+    }
+    
+    #[tokio::test]
+    async fn test_torn_and_corrupt_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileStorage::new(temp_dir.path()).unwrap();
+        let entry = WalEntry::new(WriteOp::Write, "sessions/peer1.json".to_string(), b"{}".to_vec());
+        let encoded = entry.encode();
+        assert_eq!(WalEntry::decode(&encoded).unwrap(), Some((entry.clone(), encoded.len())));
+        
+        // A half-written entry never reached its rename and is dropped
+        std::fs::write(storage.wal_path(), &encoded[..encoded.len() - 3]).unwrap();
+        assert_eq!(storage.recover_wal().unwrap(), 0);
+        assert!(!storage.session_path("peer1").exists());
+        
+        let mut flipped = encoded;
+        flipped[10] ^= 0xff;
+        std::fs::write(storage.wal_path(), &flipped).unwrap();
+        assert!(matches!(storage.recover_wal(), Err(StorageError::WalCorrupt(_))));
+    }
+    
+    #[tokio::test]
+    async fn test_failed_write_is_rolled_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = FileStorage::new(temp_dir.path()).unwrap();
+        storage.save_session("peer1", &session(1)).await.unwrap();
+        
+        // A directory in place of the temp file makes the write fail
+        std::fs::create_dir(storage.session_path("peer1").with_extension("tmp")).unwrap();
+        assert!(storage.save_session("peer1", &session(2)).await.is_err());
+        assert_eq!(std::fs::metadata(storage.wal_path()).unwrap().len(), 0);
+        
+        // Nothing is replayed over the data the failed write left alone
+        let restarted = FileStorage::new(temp_dir.path()).unwrap();
+        assert_eq!(restarted.load_sessions().await.unwrap()["peer1"].send_counter, 1);
+    }
+}
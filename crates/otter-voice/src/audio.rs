@@ -0,0 +1,369 @@
+//! # Local Audio
+//!
+//! Sources of PCM audio for outgoing calls and, with the `opus` feature,
+//! software Opus encoding of them onto a call's RTP track. Captured frames
+//! pass through an `AudioProcessor` on the way to the encoder.
+//!
+//! Audio is mono 32-bit float at 48 kHz, in 20 ms frames.
+
+use crate::VoiceError;
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+#[cfg(feature = "opus")]
+use crate::dtmf::{self, ToneQueue};
+#[cfg(feature = "opus")]
+use crate::AudioProcessor;
+#[cfg(feature = "opus")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "opus")]
+use std::time::Instant;
+#[cfg(feature = "opus")]
+use tokio::sync::oneshot;
+#[cfg(feature = "opus")]
+use tracing::{debug, warn};
+#[cfg(feature = "opus")]
+use uuid::Uuid;
+#[cfg(feature = "opus")]
+use webrtc::rtp::{header::Header, packet::Packet};
+#[cfg(feature = "opus")]
+use webrtc::track::track_local::{track_local_static_rtp::TrackLocalStaticRTP, TrackLocalWriter};
+
+/// Sample rate of captured and encoded audio
+pub const SAMPLE_RATE: u32 = 48_000;
+
+/// Length of one frame
+pub const FRAME_DURATION: Duration = Duration::from_millis(20);
+
+/// Samples in one frame
+pub const FRAME_SAMPLES: usize = (SAMPLE_RATE as usize / 1000) * 20;
+
+/// Largest Opus packet we produce, as recommended by the Opus documentation
+#[cfg(feature = "opus")]
+const MAX_OPUS_PACKET: usize = 4000;
+
+/// RTP payload type registered for Opus in the media engine
+#[cfg(feature = "opus")]
+const OPUS_PAYLOAD_TYPE: u8 = 111;
+
+/// Produces frames of local audio
+pub trait LocalAudioSource {
+    /// Return the next `FRAME_SAMPLES` samples, blocking until they are available
+    fn capture_frame(&mut self) -> Result<Vec<f32>, VoiceError>;
+}
+
+/// Generates a sine tone, for tests and call checks without a microphone
+#[derive(Debug, Clone)]
+pub struct SineWaveSource {
+    /// Tone frequency in Hz
+    pub frequency: f32,
+    /// Peak amplitude, 1.0 being full scale
+    pub amplitude: f32,
+    /// Phase of the next sample, in cycles
+    phase: f32,
+}
+
+impl SineWaveSource {
+    /// Create a tone at half of full scale
+    pub fn new(frequency: f32) -> Self {
+        Self {
+            frequency,
+            amplitude: 0.5,
+            phase: 0.0,
+        }
+    }
+}
+
+impl LocalAudioSource for SineWaveSource {
+    fn capture_frame(&mut self) -> Result<Vec<f32>, VoiceError> {
+        let step = self.frequency / SAMPLE_RATE as f32;
+        let frame = (0..FRAME_SAMPLES)
+            .map(|i| self.amplitude * (TAU * (self.phase + step * i as f32)).sin())
+            .collect();
+        self.phase = (self.phase + step * FRAME_SAMPLES as f32).fract();
+        Ok(frame)
+    }
+}
+
+/// Captures the default input device through cpal
+///
+/// cpal streams are not `Send` on every platform, so the stream lives on its
+/// own thread until the source is dropped.
+#[cfg(feature = "microphone")]
+pub struct MicrophoneSource {
+    samples: std::sync::mpsc::Receiver<Vec<f32>>,
+    buffer: Vec<f32>,
+    /// Dropping this ends the capture thread
+    _stop: std::sync::mpsc::Sender<()>,
+}
+
+#[cfg(feature = "microphone")]
+impl MicrophoneSource {
+    /// Open the default input device
+    ///
+    /// The device must capture 32-bit float samples at `SAMPLE_RATE`; extra
+    /// channels are mixed down to mono.
+    pub fn new() -> Result<Self, VoiceError> {
+        let (sample_tx, samples) = std::sync::mpsc::channel();
+        let (stop_tx, stop_rx) = std::sync::mpsc::channel::<()>();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        
+        std::thread::spawn(move || {
+            let stream = match Self::open_stream(sample_tx) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let _ = ready_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = ready_tx.send(Ok(()));
+            
+            // Returns once the sender is dropped
+            let _ = stop_rx.recv();
+            drop(stream);
+        });
+        
+        ready_rx
+            .recv()
+            .map_err(|_| VoiceError::AudioError("Audio capture thread exited".to_string()))??;
+        
+        Ok(Self {
+            samples,
+            buffer: Vec::new(),
+            _stop: stop_tx,
+        })
+    }
+    
+    fn open_stream(sample_tx: std::sync::mpsc::Sender<Vec<f32>>) -> Result<cpal::Stream, VoiceError> {
+        use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+        
+        let device = cpal::default_host()
+            .default_input_device()
+            .ok_or_else(|| VoiceError::AudioError("No input device".to_string()))?;
+        let config = device
+            .default_input_config()
+            .map_err(|e| VoiceError::AudioError(e.to_string()))?;
+        if config.sample_format() != cpal::SampleFormat::F32 || config.sample_rate().0 != SAMPLE_RATE {
+            return Err(VoiceError::AudioError(format!(
+                "Unsupported input format {:?} at {} Hz",
+                config.sample_format(),
+                config.sample_rate().0
+            )));
+        }
+        
+        let channels = config.channels() as usize;
+        let stream = device
+            .build_input_stream(
+                &config.into(),
+                move |data: &[f32], _: &cpal::InputCallbackInfo| {
+                    let mono = data
+                        .chunks(channels)
+                        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+                        .collect();
+                    let _ = sample_tx.send(mono);
+                },
+                |e| tracing::warn!("Audio capture error: {}", e),
+                None,
+            )
+            .map_err(|e| VoiceError::AudioError(e.to_string()))?;
+        stream.play().map_err(|e| VoiceError::AudioError(e.to_string()))?;
+        
+        Ok(stream)
+    }
+}
+
+#[cfg(feature = "microphone")]
+impl LocalAudioSource for MicrophoneSource {
+    fn capture_frame(&mut self) -> Result<Vec<f32>, VoiceError> {
+        while self.buffer.len() < FRAME_SAMPLES {
+            let samples = self
+                .samples
+                .recv()
+                .map_err(|_| VoiceError::AudioError("Audio capture stopped".to_string()))?;
+            self.buffer.extend(samples);
+        }
+        Ok(self.buffer.drain(..FRAME_SAMPLES).collect())
+    }
+}
+
+/// Encodes mono PCM frames to Opus
+#[cfg(feature = "opus")]
+pub struct OpusEncoder {
+    encoder: opus::Encoder,
+}
+
+#[cfg(feature = "opus")]
+impl OpusEncoder {
+    /// Create a VoIP-tuned encoder at `bitrate` bits per second
+    pub fn new(bitrate: u32) -> Result<Self, VoiceError> {
+        let mut encoder = opus::Encoder::new(SAMPLE_RATE, opus::Channels::Mono, opus::Application::Voip)
+            .map_err(|e| VoiceError::AudioError(e.to_string()))?;
+        encoder
+            .set_bitrate(opus::Bitrate::Bits(bitrate as i32))
+            .map_err(|e| VoiceError::AudioError(e.to_string()))?;
+        Ok(Self { encoder })
+    }
+    
+    /// Encode one frame of `FRAME_SAMPLES` samples
+    pub fn encode(&mut self, pcm: &[f32]) -> Result<Vec<u8>, VoiceError> {
+        let mut packet = vec![0u8; MAX_OPUS_PACKET];
+        let len = self
+            .encoder
+            .encode_float(pcm, &mut packet)
+            .map_err(|e| VoiceError::AudioError(e.to_string()))?;
+        packet.truncate(len);
+        Ok(packet)
+    }
+}
+
+/// Audio source shared by the calls of a `VoiceManager`
+#[cfg(feature = "opus")]
+pub(crate) type SharedAudioSource = Arc<Mutex<Box<dyn LocalAudioSource + Send>>>;
+
+/// Wraps consecutive Opus frames in RTP packets
+#[cfg(feature = "opus")]
+pub(crate) struct RtpPacketizer {
+    sequence_number: u16,
+    timestamp: u32,
+    first: bool,
+}
+
+#[cfg(feature = "opus")]
+impl RtpPacketizer {
+    pub(crate) fn new() -> Self {
+        Self {
+            // RFC 3550 recommends a random initial sequence number
+            sequence_number: u16::from_le_bytes(Uuid::new_v4().as_bytes()[..2].try_into().unwrap()),
+            timestamp: 0,
+            first: true,
+        }
+    }
+    
+    /// Packetize the next 20 ms frame
+    pub(crate) fn packetize(&mut self, payload: Vec<u8>) -> Packet {
+        let packet = Packet {
+            header: Header {
+                version: 2,
+                marker: self.first,
+                payload_type: OPUS_PAYLOAD_TYPE,
+                sequence_number: self.sequence_number,
+                timestamp: self.timestamp,
+                ..Default::default()
+            },
+            payload: payload.into(),
+        };
+        self.first = false;
+        self.sequence_number = self.sequence_number.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(FRAME_SAMPLES as u32);
+        packet
+    }
+}
+
+/// Capture, encode and send audio on `track` until `stop` is closed
+///
+/// Runs on tokio's blocking pool. Frames are paced to real time, which only
+/// has an effect for sources that do not block on capture themselves. Queued
+/// DTMF tones replace the processed capture until they are played out.
+#[cfg(feature = "opus")]
+pub(crate) fn spawn_audio_sender(
+    source: SharedAudioSource,
+    mut processor: Option<AudioProcessor>,
+    mut encoder: OpusEncoder,
+    track: Arc<TrackLocalStaticRTP>,
+    tones: ToneQueue,
+    mut stop: oneshot::Receiver<()>,
+) {
+    let runtime = tokio::runtime::Handle::current();
+    
+    tokio::task::spawn_blocking(move || {
+        let start = Instant::now();
+        let mut packetizer = RtpPacketizer::new();
+        
+        for frame_index in 0u32.. {
+            if !matches!(stop.try_recv(), Err(oneshot::error::TryRecvError::Empty)) {
+                break;
+            }
+            
+            let frame = match source.lock().map_err(|_| VoiceError::AudioError("Audio source poisoned".to_string())) {
+                Ok(mut source) => source.capture_frame(),
+                Err(e) => Err(e),
+            };
+            let frame = frame.map(|mut pcm| {
+                if let Some(processor) = processor.as_mut() {
+                    processor.process_capture(&mut pcm);
+                }
+                dtmf::play_queued_tones(&tones, &mut pcm);
+                pcm
+            });
+            let payload = match frame.and_then(|pcm| encoder.encode(&pcm)) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Stopping audio: {}", e);
+                    break;
+                }
+            };
+            
+            let packet = packetizer.packetize(payload);
+            if let Err(e) = runtime.block_on(track.write_rtp(&packet)) {
+                debug!("Failed to write RTP packet: {}", e);
+            }
+            
+            let next_frame = start + FRAME_DURATION * (frame_index + 1);
+            if let Some(wait) = next_frame.checked_duration_since(Instant::now()) {
+                std::thread::sleep(wait);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Level of `samples` in dB relative to a full-scale sine
+    fn rms_dbfs(samples: &[f32]) -> f32 {
+        let rms = (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt();
+        20.0 * (rms * std::f32::consts::SQRT_2).log10()
+    }
+    
+    #[test]
+    fn test_sine_wave_source() {
+        let mut source = SineWaveSource::new(440.0);
+        let frame = source.capture_frame().unwrap();
+        assert_eq!(frame.len(), FRAME_SAMPLES);
+        
+        // Half of full scale is about -6 dBFS
+        assert!((rms_dbfs(&frame) + 6.02).abs() < 0.1);
+        
+        // Consecutive frames join without a jump
+        let next = source.capture_frame().unwrap();
+        let last_step = frame[FRAME_SAMPLES - 1] - frame[FRAME_SAMPLES - 2];
+        assert!((next[0] - frame[FRAME_SAMPLES - 1] - last_step).abs() < 0.01);
+    }
+    
+    #[cfg(feature = "opus")]
+    #[test]
+    fn test_opus_round_trip_level() {
+        let mut source = SineWaveSource::new(440.0);
+        let mut encoder = OpusEncoder::new(32_000).unwrap();
+        let mut decoder = opus::Decoder::new(SAMPLE_RATE, opus::Channels::Mono).unwrap();
+        
+        let mut original = Vec::new();
+        let mut decoded = Vec::new();
+        for _ in 0..50 {
+            let frame = source.capture_frame().unwrap();
+            let packet = encoder.encode(&frame).unwrap();
+            assert!(!packet.is_empty());
+            
+            let mut pcm = vec![0f32; FRAME_SAMPLES];
+            let len = decoder.decode_float(&packet, &mut pcm, false).unwrap();
+            assert_eq!(len, FRAME_SAMPLES);
+            original.extend(frame);
+            decoded.extend(pcm);
+        }
+        
+        // Skip the encoder's start-up frames
+        let tail = 10 * FRAME_SAMPLES;
+        assert!((rms_dbfs(&decoded[tail..]) - rms_dbfs(&original[tail..])).abs() < 3.0);
+    }
+}
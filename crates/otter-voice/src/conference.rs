@@ -0,0 +1,235 @@
+//! # Conference Calls
+//!
+//! Calls with more than two participants. This node is the hub: it holds one
+//! peer connection per participant and sends all of them the same mix of
+//! every participant and the local audio source, so participants also hear
+//! themselves in the mix.
+//!
+//! Decoding and mixing need the `opus` feature; without it a conference
+//! connects but carries no audio.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
+
+#[cfg(feature = "opus")]
+use crate::audio::{OpusEncoder, RtpPacketizer, SharedAudioSource, FRAME_DURATION, FRAME_SAMPLES, SAMPLE_RATE};
+#[cfg(feature = "opus")]
+use std::collections::VecDeque;
+#[cfg(feature = "opus")]
+use std::sync::Mutex;
+#[cfg(feature = "opus")]
+use tokio::sync::oneshot;
+#[cfg(feature = "opus")]
+use tracing::{debug, warn};
+#[cfg(feature = "opus")]
+use webrtc::track::track_local::TrackLocalWriter;
+#[cfg(feature = "opus")]
+use webrtc::track::track_remote::TrackRemote;
+
+/// Frames buffered per input before the oldest is dropped, 100 ms of jitter
+#[cfg(feature = "opus")]
+const MAX_QUEUED_FRAMES: usize = 5;
+
+/// Largest Opus frame a decoder can return, 120 ms
+#[cfg(feature = "opus")]
+const MAX_DECODED_SAMPLES: usize = FRAME_SAMPLES * 6;
+
+/// Mixer input carrying the local audio source
+#[cfg(feature = "opus")]
+const LOCAL_INPUT: &str = "";
+
+/// Sums audio frames
+pub struct AudioMixer;
+
+impl AudioMixer {
+    /// Add `frames` sample by sample, clamping the result to [-1.0, 1.0]
+    ///
+    /// Shorter frames count as padded with silence.
+    pub fn mix(frames: &[Vec<f32>]) -> Vec<f32> {
+        let len = frames.iter().map(Vec::len).max().unwrap_or(0);
+        let mut mixed = vec![0.0; len];
+        for frame in frames {
+            for (out, sample) in mixed.iter_mut().zip(frame) {
+                *out += sample;
+            }
+        }
+        for sample in &mut mixed {
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+        mixed
+    }
+}
+
+/// Decoded frames waiting to be mixed, by participant
+#[cfg(feature = "opus")]
+pub(crate) type MixerInputs = Arc<Mutex<HashMap<String, VecDeque<Vec<f32>>>>>;
+
+/// A conference hosted by this node
+pub struct ConferenceSession {
+    pub session_id: String,
+    /// Peer connection of each participant, by peer ID
+    pub participants: HashMap<String, Arc<RTCPeerConnection>>,
+    /// Track carrying the mix, sent to every participant
+    pub mixed_track: Arc<TrackLocalStaticRTP>,
+    #[cfg(feature = "opus")]
+    pub(crate) inputs: MixerInputs,
+    /// Dropping these stops the mixer and local capture
+    #[cfg(feature = "opus")]
+    pub(crate) audio_stop: Vec<oneshot::Sender<()>>,
+}
+
+impl ConferenceSession {
+    pub(crate) fn new(session_id: String, mixed_track: Arc<TrackLocalStaticRTP>) -> Self {
+        Self {
+            session_id,
+            participants: HashMap::new(),
+            mixed_track,
+            #[cfg(feature = "opus")]
+            inputs: MixerInputs::default(),
+            #[cfg(feature = "opus")]
+            audio_stop: Vec::new(),
+        }
+    }
+}
+
+#[cfg(feature = "opus")]
+fn push_frame(inputs: &MixerInputs, input: &str, frame: Vec<f32>) {
+    let mut inputs = inputs.lock().unwrap();
+    let queue = inputs.entry(input.to_string()).or_default();
+    if queue.len() == MAX_QUEUED_FRAMES {
+        queue.pop_front();
+    }
+    queue.push_back(frame);
+}
+
+/// Mix one frame from every input each 20 ms and send it on `track` until `stop` is closed
+///
+/// Silence is sent while no input has audio, so RTP timestamps keep advancing.
+#[cfg(feature = "opus")]
+pub(crate) fn spawn_mixer(
+    inputs: MixerInputs,
+    mut encoder: OpusEncoder,
+    track: Arc<TrackLocalStaticRTP>,
+    mut stop: oneshot::Receiver<()>,
+) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(FRAME_DURATION);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut packetizer = RtpPacketizer::new();
+        
+        loop {
+            tokio::select! {
+                _ = &mut stop => break,
+                _ = ticker.tick() => {}
+            }
+            
+            let frames: Vec<Vec<f32>> = inputs
+                .lock()
+                .unwrap()
+                .values_mut()
+                .filter_map(VecDeque::pop_front)
+                .collect();
+            let mut mixed = AudioMixer::mix(&frames);
+            mixed.resize(FRAME_SAMPLES, 0.0);
+            
+            let payload = match encoder.encode(&mixed) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    warn!("Stopping conference mixer: {}", e);
+                    break;
+                }
+            };
+            if let Err(e) = track.write_rtp(&packetizer.packetize(payload)).await {
+                debug!("Failed to write mixed RTP packet: {}", e);
+            }
+        }
+    });
+}
+
+/// Feed the local audio source into the mixer until `stop` is closed
+#[cfg(feature = "opus")]
+pub(crate) fn spawn_local_capture(source: SharedAudioSource, inputs: MixerInputs, mut stop: oneshot::Receiver<()>) {
+    tokio::task::spawn_blocking(move || {
+        let start = std::time::Instant::now();
+        for frame_index in 1u32.. {
+            if !matches!(stop.try_recv(), Err(oneshot::error::TryRecvError::Empty)) {
+                break;
+            }
+            
+            let frame = match source.lock() {
+                Ok(mut source) => source.capture_frame(),
+                Err(_) => break,
+            };
+            match frame {
+                Ok(frame) => push_frame(&inputs, LOCAL_INPUT, frame),
+                Err(e) => {
+                    warn!("Stopping local conference audio: {}", e);
+                    break;
+                }
+            }
+            
+            // Pace sources that do not block on capture themselves
+            if let Some(wait) = (start + FRAME_DURATION * frame_index).checked_duration_since(std::time::Instant::now()) {
+                std::thread::sleep(wait);
+            }
+        }
+    });
+}
+
+/// Decode the audio of `participant` into the mixer until the track ends
+#[cfg(feature = "opus")]
+pub(crate) fn spawn_decoder(track: Arc<TrackRemote>, participant: String, inputs: MixerInputs) {
+    tokio::spawn(async move {
+        let mut decoder = match opus::Decoder::new(SAMPLE_RATE, opus::Channels::Mono) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                warn!("Cannot decode audio from {}: {}", participant, e);
+                return;
+            }
+        };
+        let mut pcm = vec![0f32; MAX_DECODED_SAMPLES];
+        
+        while let Ok((packet, _)) = track.read_rtp().await {
+            match decoder.decode_float(&packet.payload, &mut pcm, false) {
+                Ok(len) => push_frame(&inputs, &participant, pcm[..len].to_vec()),
+                Err(e) => debug!("Dropping undecodable packet from {}: {}", participant, e),
+            }
+        }
+        inputs.lock().unwrap().remove(&participant);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{LocalAudioSource, SineWaveSource, FRAME_SAMPLES, SAMPLE_RATE};
+    use std::f32::consts::TAU;
+    
+    #[test]
+    fn test_mix_two_sine_waves() {
+        let a = SineWaveSource::new(440.0).capture_frame().unwrap();
+        let b = SineWaveSource::new(660.0).capture_frame().unwrap();
+        let mixed = AudioMixer::mix(&[a, b]);
+        assert_eq!(mixed.len(), FRAME_SAMPLES);
+        
+        // Two half-scale tones never exceed full scale, so nothing is clamped
+        for (i, sample) in mixed.iter().enumerate() {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let expected = 0.5 * (TAU * 440.0 * t).sin() + 0.5 * (TAU * 660.0 * t).sin();
+            assert!((sample - expected).abs() < 1e-3, "sample {}: {} != {}", i, sample, expected);
+        }
+        
+        // In phase at full scale, the sum clamps
+        let mut loud = SineWaveSource::new(440.0);
+        loud.amplitude = 1.0;
+        let frame = loud.capture_frame().unwrap();
+        let mixed = AudioMixer::mix(&[frame.clone(), frame.clone()]);
+        assert!(mixed.iter().all(|s| s.abs() <= 1.0));
+        let peak = frame.iter().position(|s| *s > 0.99).unwrap();
+        assert_eq!(mixed[peak], 1.0);
+        
+        assert!(AudioMixer::mix(&[]).is_empty());
+    }
+}
@@ -0,0 +1,310 @@
+//! # DTMF Tones
+//!
+//! Dual-tone multi-frequency signaling for SIP-PSTN bridges that expect
+//! keypad tones in the audio itself. Each of the sixteen events RFC 4733
+//! defines for 0–9, `*`, `#` and A–D is one low and one high frequency
+//! (ITU-T Q.23).
+//!
+//! Detection runs the Goertzel algorithm for the eight frequencies over
+//! 40 ms windows. A window holds a digit when its strongest low and strongest
+//! high frequency together carry most of the window's energy.
+
+use std::collections::VecDeque;
+use std::f32::consts::TAU;
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "opus")]
+use crate::audio::{FRAME_SAMPLES, SAMPLE_RATE};
+#[cfg(feature = "opus")]
+use crate::VoiceEvent;
+#[cfg(feature = "opus")]
+use tokio::sync::mpsc;
+#[cfg(feature = "opus")]
+use tracing::{debug, info, warn};
+#[cfg(feature = "opus")]
+use webrtc::track::track_remote::TrackRemote;
+
+/// Row frequencies in Hz
+const LOW_FREQUENCIES: [f32; 4] = [697.0, 770.0, 852.0, 941.0];
+
+/// Column frequencies in Hz
+const HIGH_FREQUENCIES: [f32; 4] = [1209.0, 1336.0, 1477.0, 1633.0];
+
+/// Keypad layout, by row and column
+const KEYPAD: [[char; 4]; 4] = [
+    ['1', '2', '3', 'A'],
+    ['4', '5', '6', 'B'],
+    ['7', '8', '9', 'C'],
+    ['*', '0', '#', 'D'],
+];
+
+/// Peak amplitude of each of the two tones
+const TONE_AMPLITUDE: f32 = 0.4;
+
+/// Length of the detection window
+pub const DETECTION_WINDOW_MS: u32 = 40;
+
+/// Length of a tone sent by `VoiceManager::send_dtmf`
+pub const TONE_DURATION_MS: u32 = 100;
+
+/// Silence after each sent tone; two windows, so one of them is always silent
+pub const INTER_DIGIT_GAP_MS: u32 = 2 * DETECTION_WINDOW_MS;
+
+/// Share of the window energy each tone must carry; an even mix gives 0.5
+const MIN_TONE_SHARE: f32 = 0.3;
+
+/// Windows quieter than this mean square are silence, about -60 dBFS
+const MIN_WINDOW_POWER: f32 = 1e-6;
+
+/// Tones queued for the outgoing audio, played in place of captured samples
+pub(crate) type ToneQueue = Arc<Mutex<VecDeque<f32>>>;
+
+/// Synthesizes DTMF tones
+pub struct DtmfGenerator;
+
+impl DtmfGenerator {
+    /// Low and high frequency of `digit`, `None` if it is no DTMF digit
+    ///
+    /// A–D are accepted in either case.
+    pub fn frequencies(digit: char) -> Option<(f32, f32)> {
+        let digit = digit.to_ascii_uppercase();
+        KEYPAD.iter().enumerate().find_map(|(row, keys)| {
+            let column = keys.iter().position(|&key| key == digit)?;
+            Some((LOW_FREQUENCIES[row], HIGH_FREQUENCIES[column]))
+        })
+    }
+    
+    /// `duration_ms` of the tone for `digit`, empty if it is no DTMF digit
+    pub fn generate_tone(digit: char, duration_ms: u32, sample_rate: u32) -> Vec<f32> {
+        let Some((low, high)) = Self::frequencies(digit) else {
+            return Vec::new();
+        };
+        
+        let len = (sample_rate as u64 * duration_ms as u64 / 1000) as usize;
+        (0..len)
+            .map(|i| {
+                let t = i as f32 / sample_rate as f32;
+                TONE_AMPLITUDE * ((TAU * low * t).sin() + (TAU * high * t).sin())
+            })
+            .collect()
+    }
+}
+
+/// Recognizes DTMF tones in audio
+pub struct DtmfDetector;
+
+impl DtmfDetector {
+    /// The first digit heard in a 40 ms window of `samples`
+    ///
+    /// Only complete windows are examined, so fewer than 40 ms of samples
+    /// never hold a digit.
+    pub fn detect(samples: &[f32], sample_rate: u32) -> Option<char> {
+        samples
+            .chunks_exact(Self::window_len(sample_rate))
+            .find_map(|window| Self::detect_window(window, sample_rate))
+    }
+    
+    /// Samples in one detection window
+    fn window_len(sample_rate: u32) -> usize {
+        ((sample_rate * DETECTION_WINDOW_MS / 1000) as usize).max(1)
+    }
+    
+    /// The digit held by one window, if any
+    fn detect_window(window: &[f32], sample_rate: u32) -> Option<char> {
+        let energy: f32 = window.iter().map(|s| s * s).sum();
+        if energy / (window.len() as f32) < MIN_WINDOW_POWER {
+            return None;
+        }
+        
+        // A sine carrying all of the energy has a Goertzel power of N * energy / 2
+        let share = |frequency: f32| 2.0 * goertzel_power(window, frequency, sample_rate) / (window.len() as f32 * energy);
+        let strongest = |frequencies: &[f32; 4]| {
+            frequencies
+                .iter()
+                .map(|&f| share(f))
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .expect("four frequencies")
+        };
+        
+        let (row, low_share) = strongest(&LOW_FREQUENCIES);
+        let (column, high_share) = strongest(&HIGH_FREQUENCIES);
+        if low_share < MIN_TONE_SHARE || high_share < MIN_TONE_SHARE {
+            return None;
+        }
+        Some(KEYPAD[row][column])
+    }
+}
+
+/// Squared magnitude of `frequency` in `samples`
+fn goertzel_power(samples: &[f32], frequency: f32, sample_rate: u32) -> f32 {
+    let coefficient = 2.0 * (TAU * frequency / sample_rate as f32).cos();
+    let (mut previous, mut before_previous) = (0.0f32, 0.0f32);
+    for &sample in samples {
+        let current = sample + coefficient * previous - before_previous;
+        before_previous = previous;
+        previous = current;
+    }
+    previous * previous + before_previous * before_previous - coefficient * previous * before_previous
+}
+
+/// Detects digits in a continuous stream of audio
+///
+/// A digit is reported once when its tone starts, however many windows it
+/// lasts.
+pub struct DtmfListener {
+    sample_rate: u32,
+    buffer: Vec<f32>,
+    current: Option<char>,
+}
+
+impl DtmfListener {
+    /// Listen to audio at `sample_rate`
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            buffer: Vec::new(),
+            current: None,
+        }
+    }
+    
+    /// Feed the next samples, returning the digits that started in them
+    pub fn push(&mut self, samples: &[f32]) -> Vec<char> {
+        self.buffer.extend_from_slice(samples);
+        let window_len = DtmfDetector::window_len(self.sample_rate);
+        
+        let mut started = Vec::new();
+        let complete = self.buffer.len() / window_len * window_len;
+        for window in self.buffer[..complete].chunks_exact(window_len) {
+            let digit = DtmfDetector::detect_window(window, self.sample_rate);
+            if let Some(digit) = digit.filter(|&digit| self.current != Some(digit)) {
+                started.push(digit);
+            }
+            self.current = digit;
+        }
+        self.buffer.drain(..complete);
+        started
+    }
+}
+
+/// Replace the start of `frame` with queued tone samples
+#[cfg(any(feature = "opus", test))]
+pub(crate) fn play_queued_tones(tones: &ToneQueue, frame: &mut [f32]) {
+    let mut tones = tones.lock().unwrap();
+    let count = frame.len().min(tones.len());
+    for (sample, tone) in frame.iter_mut().zip(tones.drain(..count)) {
+        *sample = tone;
+    }
+}
+
+/// Decode the peer's audio and report the DTMF digits in it until the track ends
+#[cfg(feature = "opus")]
+pub(crate) fn spawn_listener(track: Arc<TrackRemote>, event_tx: Option<mpsc::UnboundedSender<VoiceEvent>>) {
+    tokio::spawn(async move {
+        let mut decoder = match opus::Decoder::new(SAMPLE_RATE, opus::Channels::Mono) {
+            Ok(decoder) => decoder,
+            Err(e) => {
+                warn!("Cannot decode incoming audio: {}", e);
+                return;
+            }
+        };
+        // Largest Opus frame, 120 ms
+        let mut pcm = vec![0f32; FRAME_SAMPLES * 6];
+        let mut listener = DtmfListener::new(SAMPLE_RATE);
+        
+        while let Ok((packet, _)) = track.read_rtp().await {
+            let len = match decoder.decode_float(&packet.payload, &mut pcm, false) {
+                Ok(len) => len,
+                Err(e) => {
+                    debug!("Dropping undecodable audio packet: {}", e);
+                    continue;
+                }
+            };
+            for digit in listener.push(&pcm[..len]) {
+                info!("Received DTMF digit {}", digit);
+                if let Some(ref tx) = event_tx {
+                    let _ = tx.send(VoiceEvent::DtmfReceived(digit));
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{LocalAudioSource, SineWaveSource, FRAME_SAMPLES};
+    
+    #[test]
+    fn test_generate_and_detect_digit() {
+        for sample_rate in [8_000, 48_000] {
+            let tone = DtmfGenerator::generate_tone('5', DETECTION_WINDOW_MS, sample_rate);
+            assert_eq!(tone.len(), (sample_rate / 25) as usize);
+            assert!(tone.iter().all(|s| s.abs() <= 2.0 * TONE_AMPLITUDE));
+            assert_eq!(DtmfDetector::detect(&tone, sample_rate), Some('5'));
+        }
+        
+        for digit in "0123456789*#ABCD".chars() {
+            let tone = DtmfGenerator::generate_tone(digit, 100, 8_000);
+            assert_eq!(DtmfDetector::detect(&tone, 8_000), Some(digit));
+        }
+        assert_eq!(DtmfDetector::detect(&DtmfGenerator::generate_tone('b', 40, 8_000), 8_000), Some('B'));
+        assert!(DtmfGenerator::generate_tone('x', 40, 8_000).is_empty());
+        
+        // Too short for a window
+        let short = DtmfGenerator::generate_tone('5', 30, 8_000);
+        assert_eq!(DtmfDetector::detect(&short, 8_000), None);
+    }
+    
+    #[test]
+    fn test_no_digit_in_silence_or_single_tone() {
+        assert_eq!(DtmfDetector::detect(&[0.0; 1920], 48_000), None);
+        
+        let mut sine = SineWaveSource::new(697.0);
+        let frames: Vec<f32> = (0..3).flat_map(|_| sine.capture_frame().unwrap()).collect();
+        assert_eq!(DtmfDetector::detect(&frames, 48_000), None);
+        
+        // Half the energy of the window in a third tone drowns the digit
+        let mut interference = SineWaveSource::new(1000.0);
+        interference.amplitude = 0.6;
+        let interference: Vec<f32> = (0..2).flat_map(|_| interference.capture_frame().unwrap()).collect();
+        let noisy: Vec<f32> = DtmfGenerator::generate_tone('5', 40, 48_000)
+            .iter()
+            .zip(&interference)
+            .map(|(tone, noise)| tone + noise)
+            .collect();
+        assert_eq!(DtmfDetector::detect(&noisy, 48_000), None);
+    }
+    
+    #[test]
+    fn test_listener_reports_each_digit_once() {
+        let sample_rate = 48_000;
+        let mut audio = Vec::new();
+        for digit in ['1', '1', '9'] {
+            // 100 ms apart, so at least one window between tones is silent
+            audio.extend(DtmfGenerator::generate_tone(digit, 100, sample_rate));
+            audio.extend(std::iter::repeat_n(0.0, sample_rate as usize / 10));
+        }
+        
+        let mut listener = DtmfListener::new(sample_rate);
+        let mut digits = Vec::new();
+        for frame in audio.chunks(FRAME_SAMPLES) {
+            digits.extend(listener.push(frame));
+        }
+        assert_eq!(digits, vec!['1', '1', '9']);
+    }
+    
+    #[test]
+    fn test_play_queued_tones() {
+        let tones: ToneQueue = Arc::new(Mutex::new(vec![0.25; 30].into()));
+        let mut frame = vec![0.5; 20];
+        play_queued_tones(&tones, &mut frame);
+        assert!(frame.iter().all(|&s| s == 0.25));
+        
+        let mut frame = vec![0.5; 20];
+        play_queued_tones(&tones, &mut frame);
+        assert!(frame[..10].iter().all(|&s| s == 0.25));
+        assert!(frame[10..].iter().all(|&s| s == 0.5));
+        assert!(tones.lock().unwrap().is_empty());
+    }
+}
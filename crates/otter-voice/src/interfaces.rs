@@ -0,0 +1,108 @@
+//! # Network Interface Monitoring
+//!
+//! Detects local IP address changes, such as switching from Wi-Fi to LTE,
+//! so an active call can restart ICE instead of dropping.
+
+use std::collections::BTreeSet;
+use std::net::IpAddr;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// How often the system's interfaces are polled
+pub const INTERFACE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Local addresses that appeared or disappeared since the last poll
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceChange {
+    pub added: Vec<IpAddr>,
+    pub removed: Vec<IpAddr>,
+}
+
+/// Lists the local IP addresses
+pub trait InterfaceSource: Send + 'static {
+    /// Current non-loopback addresses
+    fn addresses(&mut self) -> std::io::Result<BTreeSet<IpAddr>>;
+}
+
+/// Reads addresses from the operating system
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemInterfaces;
+
+impl InterfaceSource for SystemInterfaces {
+    fn addresses(&mut self) -> std::io::Result<BTreeSet<IpAddr>> {
+        Ok(if_addrs::get_if_addrs()?
+            .into_iter()
+            .filter(|interface| !interface.is_loopback())
+            .map(|interface| interface.ip())
+            .collect())
+    }
+}
+
+/// Polls an [`InterfaceSource`] and reports address changes
+pub struct NetworkInterfaceMonitor<S = SystemInterfaces> {
+    source: S,
+    interval: Duration,
+}
+
+impl NetworkInterfaceMonitor {
+    /// Monitor the system's interfaces every `INTERFACE_POLL_INTERVAL`
+    pub fn new() -> Self {
+        Self::with_source(SystemInterfaces, INTERFACE_POLL_INTERVAL)
+    }
+}
+
+impl Default for NetworkInterfaceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S: InterfaceSource> NetworkInterfaceMonitor<S> {
+    /// Monitor a custom address source
+    pub fn with_source(source: S, interval: Duration) -> Self {
+        Self { source, interval }
+    }
+    
+    /// Poll in the background, sending every change to `tx`
+    ///
+    /// The first poll only records the current addresses. The task ends when
+    /// `tx` is closed.
+    pub fn spawn(mut self, tx: mpsc::UnboundedSender<InterfaceChange>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            let mut known: Option<BTreeSet<IpAddr>> = None;
+            
+            loop {
+                ticker.tick().await;
+                if tx.is_closed() {
+                    break;
+                }
+                
+                let current = match self.source.addresses() {
+                    Ok(current) => current,
+                    Err(e) => {
+                        warn!("Failed to list network interfaces: {}", e);
+                        continue;
+                    }
+                };
+                
+                if let Some(ref previous) = known {
+                    let change = InterfaceChange {
+                        added: current.difference(previous).copied().collect(),
+                        removed: previous.difference(&current).copied().collect(),
+                    };
+                    if !change.added.is_empty() || !change.removed.is_empty() {
+                        debug!("Network interfaces changed: {:?}", change);
+                        if tx.send(change).is_err() {
+                            break;
+                        }
+                    }
+                }
+                known = Some(current);
+            }
+        })
+    }
+}
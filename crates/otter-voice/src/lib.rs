@@ -7,6 +7,15 @@
 //! - Integration with Otter's secure signaling protocol
 //! - Mono audio with fixed bitrate
 //! - Simple call management (call, answer, hangup)
+//! - Short-lived credentials for self-hosted TURN relays
+//! - STUN-based NAT type detection
+//! - Pluggable local audio sources, Opus-encoded in software with the `opus` feature
+//! - ICE restarts when the local network interfaces change mid-call
+//! - Logical channels multiplexed over one data channel per call
+//! - Conference calls hosted by this node, with software audio mixing
+//! - Echo cancellation, noise suppression, gain control and high-pass filtering of captured audio
+//! - Redaction of fingerprints and ICE credentials from logged SDP
+//! - DTMF tone generation and detection for SIP-PSTN bridges
 //!
 //! ## Example
 //!
@@ -14,7 +23,7 @@
 //! use otter_voice::{VoiceManager, CallConfig};
 //!
 //! # async fn example() -> anyhow::Result<()> {
-//! let mut voice_manager = VoiceManager::new()?;
+//! let mut voice_manager = VoiceManager::new_async().await?;
 //! 
 //! // Start a call
 //! voice_manager.initiate_call("peer_id", CallConfig::default()).await?;
@@ -22,9 +31,35 @@
 //! # }
 //! ```
 
+pub mod audio;
+pub mod conference;
+pub mod dtmf;
+pub mod interfaces;
+pub mod mux;
+pub mod nat;
+pub mod processing;
+pub mod sdp;
+pub mod turn;
+
+pub use audio::{LocalAudioSource, SineWaveSource};
+pub use conference::{AudioMixer, ConferenceSession};
+pub use dtmf::{DtmfDetector, DtmfGenerator, DtmfListener};
+#[cfg(feature = "microphone")]
+pub use audio::MicrophoneSource;
+#[cfg(feature = "opus")]
+pub use audio::OpusEncoder;
+pub use interfaces::{InterfaceChange, InterfaceSource, NetworkInterfaceMonitor};
+pub use mux::Multiplexer;
+pub use nat::NatType;
+pub use otter_protocol::RejectReason;
+pub use processing::{AudioProcessingConfig, AudioProcessor};
+pub use sdp::SdpSanitizer;
+pub use turn::{TurnCredential, TurnTokenIssuer};
+
 use anyhow::Result;
 use otter_protocol::{MediaType, SignalingMessage};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::Arc;
 use thiserror::Error;
 use tokio::sync::{mpsc, RwLock};
@@ -34,6 +69,7 @@ use webrtc::api::media_engine::MediaEngine;
 use webrtc::api::APIBuilder;
 use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::peer_connection::configuration::RTCConfiguration;
+use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
 use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
 use webrtc::peer_connection::RTCPeerConnection;
@@ -57,6 +93,20 @@ pub enum VoiceError {
     ConnectionFailed(String),
     #[error("Audio error: {0}")]
     AudioError(String),
+    #[error("TURN credential expired")]
+    TurnTokenExpired,
+    #[error("Codec renegotiation already in progress")]
+    RenegotiationInProgress,
+    #[error("Symmetric NAT detected but no TURN server is configured")]
+    TurnRequired,
+    #[error("Call rejected: {0:?}")]
+    CallRejected(RejectReason),
+    #[error("Not a DTMF digit: {0:?}")]
+    InvalidDtmfDigit(char),
+    #[error("No conference with session ID: {0}")]
+    ConferenceNotFound(String),
+    #[error("Audio processing could not be initialized: {0}")]
+    AudioProcessingInitFailed(String),
 }
 
 /// Call configuration
@@ -72,6 +122,9 @@ pub struct CallConfig {
     pub stun_servers: Vec<String>,
     /// TURN server URLs for relay (if needed)
     pub turn_servers: Vec<String>,
+    /// Local NAT type, detected before the first call if unset
+    #[serde(default)]
+    pub nat_type: Option<NatType>,
 }
 
 impl Default for CallConfig {
@@ -85,6 +138,7 @@ impl Default for CallConfig {
                 "stun:stun1.l.google.com:19302".to_string(),
             ],
             turn_servers: Vec::new(),
+            nat_type: None,
         }
     }
 }
@@ -102,12 +156,33 @@ pub enum CallState {
     Connecting,
     /// Call is active
     Connected,
+    /// Call is active and a new offer is waiting for an answer
+    Renegotiating,
+    /// Call is active and an ICE restart offer is waiting for an answer
+    IceRestarting,
     /// Call ended
     Ended,
 }
 
+/// Call events for the application
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VoiceEvent {
+    /// The peer rejected our call
+    CallRejected {
+        peer_id: String,
+        reason: RejectReason,
+    },
+    /// The peer answered our ICE restart and the call continues on new candidates
+    IceRestarted {
+        session_id: String,
+    },
+    /// The peer sent a DTMF digit in its audio
+    DtmfReceived(char),
+}
+
 /// Active call information
 #[derive(Debug)]
+#[allow(dead_code)]
 pub struct CallSession {
     /// Session ID
     pub session_id: String,
@@ -123,6 +198,19 @@ pub struct CallSession {
     is_initiator: bool,
     /// ICE candidates collected before connection
     pending_ice_candidates: Vec<String>,
+    /// Dropping this stops the audio sender of the call
+    #[cfg(feature = "opus")]
+    audio_stop: Option<tokio::sync::oneshot::Sender<()>>,
+}
+
+impl CallSession {
+    /// Whether the local audio source is being sent on this call
+    fn sends_audio(&self) -> bool {
+        #[cfg(feature = "opus")]
+        return self.audio_stop.is_some();
+        #[cfg(not(feature = "opus"))]
+        return false;
+    }
 }
 
 /// Voice manager for handling WebRTC voice calls
@@ -133,13 +221,70 @@ pub struct VoiceManager {
     config: CallConfig,
     /// Channel for outgoing signaling messages
     signaling_tx: Option<mpsc::UnboundedSender<(String, SignalingMessage)>>,
+    /// Channel for call events
+    event_tx: Option<mpsc::UnboundedSender<VoiceEvent>>,
     /// WebRTC API
     api: Arc<webrtc::api::API>,
+    /// Issues credentials for the configured TURN servers
+    turn_issuer: Option<TurnTokenIssuer>,
+    /// Audio sent on every call
+    #[cfg(feature = "opus")]
+    audio_source: Option<audio::SharedAudioSource>,
+    /// Processing applied to captured audio before encoding
+    audio_processing: AudioProcessingConfig,
+    /// Logical channels of the current call
+    multiplexer: Option<Multiplexer>,
+    /// Conferences hosted by this node, by session ID
+    conferences: HashMap<String, ConferenceSession>,
+    /// DTMF tones waiting to be sent on the current call
+    dtmf_tones: dtmf::ToneQueue,
 }
 
 impl VoiceManager {
     /// Create a new voice manager
+    ///
+    /// Builds the WebRTC API synchronously on the calling thread, which can block
+    /// for hundreds of milliseconds on some platforms.
+    #[deprecated(note = "use `VoiceManager::new_async` or `VoiceManager::with_config`")]
     pub fn new() -> Result<Self> {
+        let api = Self::build_api()?;
+        Ok(Self::from_parts(api, CallConfig::default()))
+    }
+    
+    /// Create a new voice manager without blocking the async runtime
+    pub async fn new_async() -> Result<Self, VoiceError> {
+        Self::with_config(CallConfig::default()).await
+    }
+    
+    /// Create a new voice manager with a custom call configuration
+    ///
+    /// The WebRTC API is built on tokio's blocking thread pool.
+    pub async fn with_config(config: CallConfig) -> Result<Self, VoiceError> {
+        let api = tokio::task::spawn_blocking(Self::build_api)
+            .await
+            .map_err(|e| VoiceError::WebRtc(e.to_string()))??;
+        Ok(Self::from_parts(api, config))
+    }
+    
+    fn from_parts(api: webrtc::api::API, config: CallConfig) -> Self {
+        Self {
+            active_call: Arc::new(RwLock::new(None)),
+            config,
+            signaling_tx: None,
+            event_tx: None,
+            api: Arc::new(api),
+            turn_issuer: None,
+            #[cfg(feature = "opus")]
+            audio_source: None,
+            audio_processing: AudioProcessingConfig::default(),
+            multiplexer: None,
+            conferences: HashMap::new(),
+            dtmf_tones: Default::default(),
+        }
+    }
+    
+    /// Build the WebRTC API with the Opus codec registered
+    fn build_api() -> Result<webrtc::api::API, VoiceError> {
         let mut media_engine = MediaEngine::default();
         
         // Register Opus codec for audio (standard for WebRTC voice)
@@ -156,19 +301,17 @@ impl VoiceManager {
                 ..Default::default()
             },
             RTPCodecType::Audio,
-        )?;
+        ).map_err(|e| VoiceError::WebRtc(e.to_string()))?;
         
         // Build API with media engine (simpler version without interceptors for minimal PoC)
-        let api = APIBuilder::new()
+        Ok(APIBuilder::new()
             .with_media_engine(media_engine)
-            .build();
-        
-        Ok(Self {
-            active_call: Arc::new(RwLock::new(None)),
-            config: CallConfig::default(),
-            signaling_tx: None,
-            api: Arc::new(api),
-        })
+            .build())
+    }
+    
+    /// Get the default call configuration
+    pub fn config(&self) -> &CallConfig {
+        &self.config
     }
     
     /// Set signaling channel for sending signaling messages
@@ -176,6 +319,72 @@ impl VoiceManager {
         self.signaling_tx = Some(tx);
     }
     
+    /// Set channel for call events
+    pub fn set_event_channel(&mut self, tx: mpsc::UnboundedSender<VoiceEvent>) {
+        self.event_tx = Some(tx);
+    }
+    
+    /// Generate TURN credentials with `issuer` for every new call
+    pub fn set_turn_credential_issuer(&mut self, issuer: TurnTokenIssuer) {
+        self.turn_issuer = Some(issuer);
+    }
+    
+    /// Set the audio sent on calls, starting with the next one
+    ///
+    /// The source is Opus-encoded at the configured bitrate and written to the
+    /// call's audio track from the time the track is created.
+    #[cfg(feature = "opus")]
+    pub fn set_audio_source(&mut self, src: Box<dyn LocalAudioSource + Send>) {
+        self.audio_source = Some(Arc::new(std::sync::Mutex::new(src)));
+    }
+    
+    /// Set the processing applied to captured audio, starting with the next call
+    ///
+    /// Stages that are not compiled in are skipped with a warning.
+    pub fn set_audio_processing(&mut self, config: AudioProcessingConfig) {
+        self.audio_processing = match AudioProcessor::new(config) {
+            Ok(_) => config,
+            Err(e) => {
+                warn!("{}; continuing without noise suppression", e);
+                AudioProcessingConfig {
+                    noise_suppression: false,
+                    ..config
+                }
+            }
+        };
+    }
+    
+    /// Processing applied to captured audio
+    pub fn audio_processing(&self) -> AudioProcessingConfig {
+        self.audio_processing
+    }
+    
+    /// Start sending the audio source on `track`, if one is set
+    #[cfg(feature = "opus")]
+    fn start_audio(&self, track: &Arc<TrackLocalStaticRTP>) -> Result<Option<tokio::sync::oneshot::Sender<()>>, VoiceError> {
+        let Some(ref source) = self.audio_source else {
+            return Ok(None);
+        };
+        
+        let encoder = OpusEncoder::new(self.config.bitrate)?;
+        let processor = if self.audio_processing.is_enabled() {
+            Some(AudioProcessor::new(self.audio_processing)?)
+        } else {
+            None
+        };
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel();
+        self.dtmf_tones.lock().unwrap().clear();
+        audio::spawn_audio_sender(
+            Arc::clone(source),
+            processor,
+            encoder,
+            Arc::clone(track),
+            Arc::clone(&self.dtmf_tones),
+            stop_rx,
+        );
+        Ok(Some(stop_tx))
+    }
+    
     /// Initiate a call to a peer
     pub async fn initiate_call(&mut self, peer_id: &str, config: CallConfig) -> Result<String> {
         // Check if there's already an active call
@@ -187,31 +396,38 @@ impl VoiceManager {
         }
         
         self.config = config;
+        
+        if self.config.nat_type.is_none() {
+            if let Some(stun_server) = self.config.stun_servers.first() {
+                match self.detect_nat_type(stun_server).await {
+                    Ok(nat_type) => self.config.nat_type = Some(nat_type),
+                    Err(e) => warn!("NAT type detection failed: {}", e),
+                }
+            }
+        }
+        if self.config.nat_type.is_some_and(|nat| nat.requires_turn()) && self.config.turn_servers.is_empty() {
+            return Err(VoiceError::TurnRequired.into());
+        }
+        
         let session_id = Uuid::new_v4().to_string();
         
         info!("Initiating call to peer {} with session {}", peer_id, session_id);
         
         // Create peer connection
         let peer_connection = self.create_peer_connection().await?;
+        self.multiplexer = Some(Multiplexer::run(Multiplexer::create_data_channel(&peer_connection).await?));
         
         // Create audio track
-        let audio_track = Arc::new(TrackLocalStaticRTP::new(
-            RTCRtpCodecCapability {
-                mime_type: "audio/opus".to_owned(),
-                clock_rate: 48000,
-                channels: 2,
-                sdp_fmtp_line: "".to_owned(),
-                rtcp_feedback: vec![],
-            },
-            "audio".to_owned(),
-            "otter-audio".to_owned(),
-        ));
+        let audio_track = Self::new_audio_track();
         
         // Add track to peer connection
         let rtp_sender = peer_connection
             .add_track(Arc::clone(&audio_track) as Arc<dyn TrackLocal + Send + Sync>)
             .await?;
         
+        #[cfg(feature = "opus")]
+        let audio_stop = self.start_audio(&audio_track)?;
+        
         // Handle RTCP packets (for monitoring)
         tokio::spawn(async move {
             let mut rtcp_buf = vec![0u8; 1500];
@@ -223,6 +439,7 @@ impl VoiceManager {
         peer_connection.set_local_description(offer.clone()).await?;
         
         let sdp = offer.sdp;
+        debug!("Local offer for session {}:\n{}", session_id, SdpSanitizer::redact(&sdp));
         
         // Create call session
         let call_session = CallSession {
@@ -233,6 +450,8 @@ impl VoiceManager {
             audio_track: Some(audio_track),
             is_initiator: true,
             pending_ice_candidates: Vec::new(),
+            #[cfg(feature = "opus")]
+            audio_stop,
         };
         
         // Store active call
@@ -255,8 +474,157 @@ impl VoiceManager {
         Ok(session_id)
     }
     
+    /// Change the audio bitrate of the active call
+    ///
+    /// Sends a new offer with an updated `b=AS` line on the same session. The
+    /// call stays in `Renegotiating` until the peer's answer arrives.
+    ///
+    /// webrtc-rs rejects local descriptions that differ from the offer it
+    /// generated, so the bandwidth line is only added to the copy sent to the
+    /// peer. That is the side `b=AS` applies to: it caps what the peer sends us.
+    pub async fn renegotiate_codec(&mut self, new_bitrate: u32) -> Result<(), VoiceError> {
+        let (peer_connection, peer_id, session_id, previous_state) = {
+            let mut call_lock = self.active_call.write().await;
+            let call = call_lock.as_mut().ok_or(VoiceError::NoActiveCall)?;
+            if call.state == CallState::Renegotiating {
+                return Err(VoiceError::RenegotiationInProgress);
+            }
+            
+            let previous_state = std::mem::replace(&mut call.state, CallState::Renegotiating);
+            (
+                Arc::clone(&call.peer_connection),
+                call.peer_id.clone(),
+                call.session_id.clone(),
+                previous_state,
+            )
+        };
+        
+        info!("Renegotiating session {} at {} bps", session_id, new_bitrate);
+        
+        let result = async {
+            let offer = peer_connection
+                .create_offer(None)
+                .await
+                .map_err(|e| VoiceError::WebRtc(e.to_string()))?;
+            let sdp = set_sdp_bitrate(&offer.sdp, new_bitrate);
+            debug!("Renegotiation offer for session {}:\n{}", session_id, SdpSanitizer::redact(&sdp));
+            peer_connection
+                .set_local_description(offer)
+                .await
+                .map_err(|e| VoiceError::WebRtc(e.to_string()))?;
+            
+            if let Some(ref tx) = self.signaling_tx {
+                let signaling_msg = SignalingMessage::Offer {
+                    sdp,
+                    media_type: MediaType::AudioOnly,
+                    session_id: session_id.clone(),
+                };
+                tx.send((peer_id, signaling_msg))
+                    .map_err(|e| VoiceError::ConnectionFailed(e.to_string()))?;
+            }
+            Ok(())
+        }
+        .await;
+        
+        match result {
+            Ok(()) => {
+                self.config.bitrate = new_bitrate;
+                Ok(())
+            }
+            Err(e) => {
+                let mut call_lock = self.active_call.write().await;
+                if let Some(ref mut call) = *call_lock {
+                    call.state = previous_state;
+                }
+                Err(e)
+            }
+        }
+    }
+    
+    /// Restart ICE on the active call, e.g. after the local IP address changed
+    ///
+    /// Sends an offer with fresh ICE credentials on the same session. The call
+    /// stays in `IceRestarting` until the peer's answer arrives, which emits
+    /// `VoiceEvent::IceRestarted`.
+    pub async fn restart_ice(&mut self) -> Result<(), VoiceError> {
+        let (peer_connection, peer_id, session_id, previous_state) = {
+            let mut call_lock = self.active_call.write().await;
+            let call = call_lock.as_mut().ok_or(VoiceError::NoActiveCall)?;
+            match call.state {
+                CallState::Connected | CallState::Connecting => {}
+                CallState::Renegotiating | CallState::IceRestarting => return Err(VoiceError::RenegotiationInProgress),
+                _ => return Err(VoiceError::NoActiveCall),
+            }
+            
+            let previous_state = std::mem::replace(&mut call.state, CallState::IceRestarting);
+            (
+                Arc::clone(&call.peer_connection),
+                call.peer_id.clone(),
+                call.session_id.clone(),
+                previous_state,
+            )
+        };
+        
+        info!("Restarting ICE for session {}", session_id);
+        
+        let result = async {
+            let options = RTCOfferOptions {
+                ice_restart: true,
+                ..Default::default()
+            };
+            let offer = peer_connection
+                .create_offer(Some(options))
+                .await
+                .map_err(|e| VoiceError::WebRtc(e.to_string()))?;
+            let sdp = offer.sdp.clone();
+            debug!("ICE restart offer for session {}:\n{}", session_id, SdpSanitizer::redact(&sdp));
+            peer_connection
+                .set_local_description(offer)
+                .await
+                .map_err(|e| VoiceError::WebRtc(e.to_string()))?;
+            
+            if let Some(ref tx) = self.signaling_tx {
+                let signaling_msg = SignalingMessage::Offer {
+                    sdp,
+                    media_type: MediaType::AudioOnly,
+                    session_id: session_id.clone(),
+                };
+                tx.send((peer_id, signaling_msg))
+                    .map_err(|e| VoiceError::ConnectionFailed(e.to_string()))?;
+            }
+            Ok(())
+        }
+        .await;
+        
+        if result.is_err() {
+            let mut call_lock = self.active_call.write().await;
+            if let Some(ref mut call) = *call_lock {
+                call.state = previous_state;
+            }
+        }
+        result
+    }
+    
     /// Handle incoming signaling message
     pub async fn handle_signaling(&mut self, peer_id: &str, message: SignalingMessage) -> Result<()> {
+        if let SignalingMessage::Offer { ref sdp, ref session_id, .. } | SignalingMessage::Answer { ref sdp, ref session_id } = message {
+            debug!("Remote SDP from {} for session {}:\n{}", peer_id, session_id, SdpSanitizer::redact(sdp));
+        }
+        
+        let conference_id = match message {
+            SignalingMessage::Answer { ref session_id, .. }
+            | SignalingMessage::IceCandidate { ref session_id, .. }
+            | SignalingMessage::IceComplete { ref session_id }
+            | SignalingMessage::Hangup { ref session_id, .. }
+            | SignalingMessage::Reject { ref session_id, .. } => {
+                Some(session_id.clone()).filter(|id| self.conferences.contains_key(id))
+            }
+            _ => None,
+        };
+        if let Some(session_id) = conference_id {
+            return self.handle_conference_signaling(peer_id, &session_id, message).await;
+        }
+        
         match message {
             SignalingMessage::Offer { sdp, media_type, session_id } => {
                 info!("Received call offer from {} for session {}", peer_id, session_id);
@@ -277,6 +645,10 @@ impl VoiceManager {
                 info!("Received hangup from {} for session {}: {:?}", peer_id, session_id, reason);
                 self.hangup().await?;
             }
+            SignalingMessage::Reject { session_id, reason } => {
+                info!("Call {} rejected by {}: {:?}", session_id, peer_id, reason);
+                self.handle_reject(peer_id, &session_id, reason).await?;
+            }
             _ => {}
         }
         Ok(())
@@ -287,34 +659,35 @@ impl VoiceManager {
         // Check if there's already an active call
         {
             let call_lock = self.active_call.read().await;
-            if call_lock.is_some() {
+            if let Some(ref call) = *call_lock {
+                // A new offer on the current session renegotiates it
+                if call.session_id == session_id && call.peer_id == peer_id {
+                    let peer_connection = Arc::clone(&call.peer_connection);
+                    drop(call_lock);
+                    return self.handle_renegotiation_offer(&peer_connection, peer_id, session_id, sdp).await;
+                }
+                
                 warn!("Already in a call, rejecting incoming call from {}", peer_id);
-                // TODO: Send reject message
+                self.send_reject(peer_id, session_id, RejectReason::Busy)?;
                 return Ok(());
             }
         }
         
         // Create peer connection
         let peer_connection = self.create_peer_connection().await?;
+        self.multiplexer = Some(Multiplexer::run(Multiplexer::create_data_channel(&peer_connection).await?));
         
         // Create audio track
-        let audio_track = Arc::new(TrackLocalStaticRTP::new(
-            RTCRtpCodecCapability {
-                mime_type: "audio/opus".to_owned(),
-                clock_rate: 48000,
-                channels: 2,
-                sdp_fmtp_line: "".to_owned(),
-                rtcp_feedback: vec![],
-            },
-            "audio".to_owned(),
-            "otter-audio".to_owned(),
-        ));
+        let audio_track = Self::new_audio_track();
         
         // Add track to peer connection
         let rtp_sender = peer_connection
             .add_track(Arc::clone(&audio_track) as Arc<dyn TrackLocal + Send + Sync>)
             .await?;
         
+        #[cfg(feature = "opus")]
+        let audio_stop = self.start_audio(&audio_track)?;
+        
         // Handle RTCP packets
         tokio::spawn(async move {
             let mut rtcp_buf = vec![0u8; 1500];
@@ -330,6 +703,7 @@ impl VoiceManager {
         peer_connection.set_local_description(answer.clone()).await?;
         
         let answer_sdp = answer.sdp;
+        debug!("Local answer for session {}:\n{}", session_id, SdpSanitizer::redact(&answer_sdp));
         
         // Create call session
         let call_session = CallSession {
@@ -340,6 +714,8 @@ impl VoiceManager {
             audio_track: Some(audio_track),
             is_initiator: false,
             pending_ice_candidates: Vec::new(),
+            #[cfg(feature = "opus")]
+            audio_stop,
         };
         
         // Store active call
@@ -369,6 +745,75 @@ impl VoiceManager {
         Ok(())
     }
     
+    /// Answer a renegotiation offer for the current session
+    async fn handle_renegotiation_offer(
+        &mut self,
+        peer_connection: &RTCPeerConnection,
+        peer_id: &str,
+        session_id: &str,
+        sdp: &str,
+    ) -> Result<()> {
+        info!("Peer {} is renegotiating session {}", peer_id, session_id);
+        
+        let offer = RTCSessionDescription::offer(sdp.to_string())?;
+        peer_connection.set_remote_description(offer).await?;
+        
+        let answer = peer_connection.create_answer(None).await?;
+        peer_connection.set_local_description(answer.clone()).await?;
+        debug!("Renegotiation answer for session {}:\n{}", session_id, SdpSanitizer::redact(&answer.sdp));
+        
+        if let Some(ref tx) = self.signaling_tx {
+            let signaling_msg = SignalingMessage::Answer {
+                sdp: answer.sdp,
+                session_id: session_id.to_string(),
+            };
+            tx.send((peer_id.to_string(), signaling_msg))?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Handle a rejection of our offer
+    async fn handle_reject(&mut self, peer_id: &str, session_id: &str, reason: RejectReason) -> Result<(), VoiceError> {
+        let call = {
+            let mut call_lock = self.active_call.write().await;
+            match *call_lock {
+                Some(ref call) if call.session_id == session_id && call.peer_id == peer_id => call_lock.take(),
+                _ => None,
+            }
+        };
+        let Some(call) = call else {
+            debug!("Ignoring reject for unknown session {}", session_id);
+            return Ok(());
+        };
+        
+        // The lock must be released first: closing fires the state change handler
+        if let Err(e) = call.peer_connection.close().await {
+            warn!("Error closing peer connection: {}", e);
+        }
+        
+        if let Some(ref tx) = self.event_tx {
+            let _ = tx.send(VoiceEvent::CallRejected {
+                peer_id: peer_id.to_string(),
+                reason,
+            });
+        }
+        Err(VoiceError::CallRejected(reason))
+    }
+    
+    /// Send a `Reject` for `session_id` to `peer_id`
+    fn send_reject(&self, peer_id: &str, session_id: &str, reason: RejectReason) -> Result<(), VoiceError> {
+        if let Some(ref tx) = self.signaling_tx {
+            let signaling_msg = SignalingMessage::Reject {
+                session_id: session_id.to_string(),
+                reason,
+            };
+            tx.send((peer_id.to_string(), signaling_msg))
+                .map_err(|e| VoiceError::ConnectionFailed(e.to_string()))?;
+        }
+        Ok(())
+    }
+    
     /// Handle answer to our offer
     async fn handle_answer(&mut self, session_id: &str, sdp: &str) -> Result<()> {
         let mut call_lock = self.active_call.write().await;
@@ -376,8 +821,22 @@ impl VoiceManager {
             if call.session_id == session_id {
                 let answer = RTCSessionDescription::answer(sdp.to_string())?;
                 call.peer_connection.set_remote_description(answer).await?;
-                call.state = CallState::Connecting;
+                
+                // A renegotiation or ICE restart answer returns an established call to Connected
+                let previous_state = call.state.clone();
+                call.state = match previous_state {
+                    CallState::Renegotiating | CallState::IceRestarting => CallState::Connected,
+                    _ => CallState::Connecting,
+                };
                 info!("Set remote description for session {}", session_id);
+                
+                if previous_state == CallState::IceRestarting {
+                    if let Some(ref tx) = self.event_tx {
+                        let _ = tx.send(VoiceEvent::IceRestarted {
+                            session_id: session_id.to_string(),
+                        });
+                    }
+                }
             }
         }
         Ok(())
@@ -415,11 +874,57 @@ impl VoiceManager {
         Err(VoiceError::NoActiveCall.into())
     }
     
+    /// Decline a ringing incoming call
+    pub async fn reject_call(&mut self) -> Result<(), VoiceError> {
+        let call = {
+            let mut call_lock = self.active_call.write().await;
+            match *call_lock {
+                Some(ref call) if call.state == CallState::Ringing => call_lock.take(),
+                _ => None,
+            }
+        };
+        let call = call.ok_or(VoiceError::NoActiveCall)?;
+        
+        info!("Declining call from {}", call.peer_id);
+        self.send_reject(&call.peer_id, &call.session_id, RejectReason::Declined)?;
+        if let Err(e) = call.peer_connection.close().await {
+            warn!("Error closing peer connection: {}", e);
+        }
+        Ok(())
+    }
+    
+    /// Send the DTMF tone for `digit` on the current call
+    ///
+    /// The tone replaces the local audio for `dtmf::TONE_DURATION_MS` and is
+    /// followed by a short silence, so digits sent back to back stay distinct.
+    /// It is encoded with the rest of the audio, so the call needs an audio
+    /// source.
+    pub async fn send_dtmf(&self, digit: char) -> Result<(), VoiceError> {
+        let tone = DtmfGenerator::generate_tone(digit, dtmf::TONE_DURATION_MS, audio::SAMPLE_RATE);
+        if tone.is_empty() {
+            return Err(VoiceError::InvalidDtmfDigit(digit));
+        }
+        
+        let call_lock = self.active_call.read().await;
+        let call = call_lock.as_ref().ok_or(VoiceError::NoActiveCall)?;
+        if !call.sends_audio() {
+            return Err(VoiceError::AudioError("No audio is sent on this call to carry DTMF".to_string()));
+        }
+        
+        let gap = (audio::SAMPLE_RATE * dtmf::INTER_DIGIT_GAP_MS / 1000) as usize;
+        let mut tones = self.dtmf_tones.lock().unwrap();
+        tones.extend(tone);
+        tones.extend(std::iter::repeat_n(0.0, gap));
+        debug!("Queued DTMF digit {} for {}", digit, call.peer_id);
+        Ok(())
+    }
+    
     /// Hang up the current call
     pub async fn hangup(&mut self) -> Result<()> {
         let mut call_lock = self.active_call.write().await;
         if let Some(call) = call_lock.take() {
             info!("Hanging up call with peer {}", call.peer_id);
+            self.multiplexer = None;
             
             // Send hangup message
             if let Some(ref tx) = self.signaling_tx {
@@ -453,14 +958,222 @@ impl VoiceManager {
         call_lock.is_some()
     }
     
+    /// Logical channels of the current call, if one was set up
+    pub fn multiplexer(&self) -> Option<&Multiplexer> {
+        self.multiplexer.as_ref()
+    }
+    
     /// Get current peer ID if in call
     pub async fn get_current_peer(&self) -> Option<String> {
         let call_lock = self.active_call.read().await;
         call_lock.as_ref().map(|c| c.peer_id.clone())
     }
     
-    /// Create a new peer connection with configuration
-    async fn create_peer_connection(&self) -> Result<Arc<RTCPeerConnection>> {
+    /// Detect the local NAT type with `stun_server` (`stun:host:port`)
+    pub async fn detect_nat_type(&self, stun_server: &str) -> Result<NatType, VoiceError> {
+        let nat_type = nat::detect_nat_type(stun_server, nat::STUN_RESPONSE_TIMEOUT).await?;
+        info!("Detected NAT type: {:?}", nat_type);
+        Ok(nat_type)
+    }
+    
+    /// Start a conference hosted by this node and invite `peers`
+    ///
+    /// Every participant gets its own peer connection, and all of them receive
+    /// the same mix of the participants and the local audio source.
+    pub async fn start_conference(&mut self, peers: &[String]) -> Result<String, VoiceError> {
+        let session_id = Uuid::new_v4().to_string();
+        info!("Starting conference {} with {} peers", session_id, peers.len());
+        
+        let conference = ConferenceSession::new(session_id.clone(), Self::new_audio_track());
+        #[cfg(feature = "opus")]
+        let conference = {
+            let mut conference = conference;
+            let (stop_tx, stop_rx) = tokio::sync::oneshot::channel();
+            let encoder = OpusEncoder::new(self.config.bitrate)?;
+            conference::spawn_mixer(
+                Arc::clone(&conference.inputs),
+                encoder,
+                Arc::clone(&conference.mixed_track),
+                stop_rx,
+            );
+            conference.audio_stop.push(stop_tx);
+            
+            if let Some(ref source) = self.audio_source {
+                let (stop_tx, stop_rx) = tokio::sync::oneshot::channel();
+                conference::spawn_local_capture(Arc::clone(source), Arc::clone(&conference.inputs), stop_rx);
+                conference.audio_stop.push(stop_tx);
+            }
+            conference
+        };
+        self.conferences.insert(session_id.clone(), conference);
+        
+        for peer_id in peers {
+            self.add_to_conference(&session_id, peer_id).await?;
+        }
+        Ok(session_id)
+    }
+    
+    /// Invite `peer_id` to the conference `session_id`
+    pub async fn add_to_conference(&mut self, session_id: &str, peer_id: &str) -> Result<(), VoiceError> {
+        let conference = self
+            .conferences
+            .get(session_id)
+            .ok_or_else(|| VoiceError::ConferenceNotFound(session_id.to_string()))?;
+        if conference.participants.contains_key(peer_id) {
+            return Err(VoiceError::CallAlreadyActive(peer_id.to_string()));
+        }
+        let mixed_track = Arc::clone(&conference.mixed_track);
+        #[cfg(feature = "opus")]
+        let inputs = Arc::clone(&conference.inputs);
+        
+        let peer_connection = Arc::new(
+            self.api
+                .new_peer_connection(self.rtc_configuration()?)
+                .await
+                .map_err(|e| VoiceError::WebRtc(e.to_string()))?,
+        );
+        
+        // Candidates go to this participant under the conference session ID
+        let signaling_tx = self.signaling_tx.clone();
+        let (candidate_session, candidate_peer) = (session_id.to_string(), peer_id.to_string());
+        peer_connection.on_ice_candidate(Box::new(move |candidate| {
+            let Some(ref tx) = signaling_tx else {
+                return Box::pin(async {});
+            };
+            let signaling_msg = match candidate.map(|c| c.to_json()) {
+                Some(Ok(init)) => SignalingMessage::IceCandidate {
+                    candidate: init.candidate,
+                    sdp_mid: None,
+                    sdp_mline_index: None,
+                    session_id: candidate_session.clone(),
+                },
+                Some(Err(_)) => return Box::pin(async {}),
+                None => SignalingMessage::IceComplete {
+                    session_id: candidate_session.clone(),
+                },
+            };
+            let _ = tx.send((candidate_peer.clone(), signaling_msg));
+            Box::pin(async {})
+        }));
+        
+        let track_peer = peer_id.to_string();
+        peer_connection.on_track(Box::new(move |track, _receiver, _transceiver| {
+            info!("Received conference track from {}: {}", track_peer, track.kind());
+            #[cfg(feature = "opus")]
+            conference::spawn_decoder(track, track_peer.clone(), Arc::clone(&inputs));
+            Box::pin(async {})
+        }));
+        
+        let rtp_sender = peer_connection
+            .add_track(mixed_track as Arc<dyn TrackLocal + Send + Sync>)
+            .await
+            .map_err(|e| VoiceError::WebRtc(e.to_string()))?;
+        tokio::spawn(async move {
+            let mut rtcp_buf = vec![0u8; 1500];
+            while let Ok((_, _)) = rtp_sender.read(&mut rtcp_buf).await {}
+        });
+        
+        let offer = peer_connection
+            .create_offer(None)
+            .await
+            .map_err(|e| VoiceError::WebRtc(e.to_string()))?;
+        peer_connection
+            .set_local_description(offer.clone())
+            .await
+            .map_err(|e| VoiceError::WebRtc(e.to_string()))?;
+        
+        if let Some(conference) = self.conferences.get_mut(session_id) {
+            conference.participants.insert(peer_id.to_string(), peer_connection);
+        }
+        
+        debug!("Conference offer to {} for {}:\n{}", peer_id, session_id, SdpSanitizer::redact(&offer.sdp));
+        if let Some(ref tx) = self.signaling_tx {
+            let signaling_msg = SignalingMessage::Offer {
+                sdp: offer.sdp,
+                media_type: MediaType::AudioOnly,
+                session_id: session_id.to_string(),
+            };
+            let _ = tx.send((peer_id.to_string(), signaling_msg));
+            info!("Invited {} to conference {}", peer_id, session_id);
+        }
+        Ok(())
+    }
+    
+    /// Hang up on every participant and stop the conference `session_id`
+    pub async fn end_conference(&mut self, session_id: &str) -> Result<(), VoiceError> {
+        let conference = self
+            .conferences
+            .remove(session_id)
+            .ok_or_else(|| VoiceError::ConferenceNotFound(session_id.to_string()))?;
+        for (peer_id, peer_connection) in conference.participants {
+            if let Some(ref tx) = self.signaling_tx {
+                let signaling_msg = SignalingMessage::Hangup {
+                    session_id: session_id.to_string(),
+                    reason: Some("Conference ended".to_string()),
+                };
+                let _ = tx.send((peer_id, signaling_msg));
+            }
+            if let Err(e) = peer_connection.close().await {
+                warn!("Error closing peer connection: {}", e);
+            }
+        }
+        Ok(())
+    }
+    
+    /// Handle a signaling message for the conference `session_id` from `peer_id`
+    async fn handle_conference_signaling(&mut self, peer_id: &str, session_id: &str, message: SignalingMessage) -> Result<()> {
+        let Some(conference) = self.conferences.get_mut(session_id) else {
+            return Ok(());
+        };
+        let Some(peer_connection) = conference.participants.get(peer_id).cloned() else {
+            debug!("Ignoring conference message from non-participant {}", peer_id);
+            return Ok(());
+        };
+        
+        match message {
+            SignalingMessage::Answer { sdp, .. } => {
+                info!("{} joined conference {}", peer_id, session_id);
+                let answer = RTCSessionDescription::answer(sdp)?;
+                peer_connection.set_remote_description(answer).await?;
+            }
+            SignalingMessage::IceCandidate { candidate, .. } => {
+                let candidate_init = webrtc::ice_transport::ice_candidate::RTCIceCandidateInit {
+                    candidate,
+                    ..Default::default()
+                };
+                if let Err(e) = peer_connection.add_ice_candidate(candidate_init).await {
+                    warn!("Failed to add ICE candidate: {}", e);
+                }
+            }
+            SignalingMessage::Hangup { .. } | SignalingMessage::Reject { .. } => {
+                info!("{} left conference {}", peer_id, session_id);
+                conference.participants.remove(peer_id);
+                if let Err(e) = peer_connection.close().await {
+                    warn!("Error closing peer connection: {}", e);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+    
+    /// Create an Opus audio track
+    fn new_audio_track() -> Arc<TrackLocalStaticRTP> {
+        Arc::new(TrackLocalStaticRTP::new(
+            RTCRtpCodecCapability {
+                mime_type: "audio/opus".to_owned(),
+                clock_rate: 48000,
+                channels: 2,
+                sdp_fmtp_line: "".to_owned(),
+                rtcp_feedback: vec![],
+            },
+            "audio".to_owned(),
+            "otter-audio".to_owned(),
+        ))
+    }
+    
+    /// Peer connection configuration with the configured ICE servers
+    fn rtc_configuration(&self) -> Result<RTCConfiguration, VoiceError> {
         let mut ice_servers = Vec::new();
         
         // Add STUN servers
@@ -471,19 +1184,39 @@ impl VoiceManager {
             });
         }
         
-        // Add TURN servers (if configured)
-        for turn_url in &self.config.turn_servers {
-            ice_servers.push(RTCIceServer {
-                urls: vec![turn_url.clone()],
-                ..Default::default()
-            });
+        // Add TURN servers (if configured), with a fresh credential per call
+        if !self.config.turn_servers.is_empty() {
+            let credential = match self.turn_issuer {
+                Some(ref issuer) => {
+                    let credential = issuer.issue();
+                    credential.ensure_valid()?;
+                    Some(credential)
+                }
+                None => None,
+            };
+            
+            for turn_url in &self.config.turn_servers {
+                let mut server = RTCIceServer {
+                    urls: vec![turn_url.clone()],
+                    ..Default::default()
+                };
+                if let Some(ref credential) = credential {
+                    server.username = credential.username.clone();
+                    server.credential = credential.password.clone();
+                }
+                ice_servers.push(server);
+            }
         }
         
-        let config = RTCConfiguration {
+        Ok(RTCConfiguration {
             ice_servers,
             ..Default::default()
-        };
-        
+        })
+    }
+    
+    /// Create a new peer connection with configuration
+    async fn create_peer_connection(&self) -> Result<Arc<RTCPeerConnection>> {
+        let config = self.rtc_configuration()?;
         let peer_connection = Arc::new(self.api.new_peer_connection(config).await?);
         
         // Set up ICE candidate handler
@@ -543,11 +1276,18 @@ impl VoiceManager {
                     RTCPeerConnectionState::Connected => {
                         let mut call_lock = active_call.write().await;
                         if let Some(ref mut call) = *call_lock {
-                            call.state = CallState::Connected;
+                            // An ICE restart completes when its answer arrives
+                            if call.state != CallState::IceRestarting {
+                                call.state = CallState::Connected;
+                            }
                             info!("Call connected with peer {}", call.peer_id);
                         }
                     }
-                    RTCPeerConnectionState::Disconnected | RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed => {
+                    // Disconnected is transient and can recover, e.g. through an ICE restart
+                    RTCPeerConnectionState::Disconnected => {
+                        warn!("Peer connection disconnected, waiting for it to recover");
+                    }
+                    RTCPeerConnectionState::Failed | RTCPeerConnectionState::Closed => {
                         let mut call_lock = active_call.write().await;
                         if let Some(ref mut call) = *call_lock {
                             call.state = CallState::Ended;
@@ -560,20 +1300,28 @@ impl VoiceManager {
         }));
         
         // Set up track handler for incoming audio
+        let event_tx = self.event_tx.clone();
         peer_connection.on_track(Box::new(move |track, _receiver, _transceiver| {
+            let event_tx = event_tx.clone();
             Box::pin(async move {
                 let codec = track.codec();
                 info!("Received track: {} ({})", track.kind(), codec.capability.mime_type);
                 
-                // Spawn task to read and process incoming audio
-                tokio::spawn(async move {
-                    let mut buf = vec![0u8; 1500];
-                    while let Ok((n, _attr)) = track.read(&mut buf).await {
-                        // Here you would process the audio data
-                        // For now, just count bytes received
-                        debug!("Received {} bytes of audio data", n);
-                    }
-                });
+                // Decoding needs the opus feature; without it the audio is only counted
+                #[cfg(feature = "opus")]
+                dtmf::spawn_listener(track, event_tx);
+                #[cfg(not(feature = "opus"))]
+                {
+                    let _ = event_tx;
+                    tokio::spawn(async move {
+                        let mut buf = vec![0u8; 1500];
+                        while let Ok((n, _attr)) = track.read(&mut buf).await {
+                            // Here you would process the audio data
+                            // For now, just count bytes received
+                            debug!("Received {} bytes of audio data", n);
+                        }
+                    });
+                }
             })
         }));
         
@@ -581,16 +1329,77 @@ impl VoiceManager {
     }
 }
 
+/// Set the `b=AS` bandwidth line (in kbps) of every audio section in an SDP
+///
+/// Existing `b=AS` lines in audio sections are replaced. The new line goes after
+/// the section's `c=` line, or directly after `m=` if there is none.
+pub fn set_sdp_bitrate(sdp: &str, bitrate_bps: u32) -> String {
+    let bandwidth = format!("b=AS:{}", bitrate_bps.div_ceil(1000));
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_audio = false;
+    let mut pending = false;
+    
+    for line in sdp.lines() {
+        if line.starts_with("m=") {
+            if pending {
+                lines.push(bandwidth.clone());
+            }
+            in_audio = line.starts_with("m=audio");
+            pending = in_audio;
+            lines.push(line.to_string());
+            continue;
+        }
+        
+        if in_audio {
+            if line.starts_with("b=AS:") {
+                continue;
+            }
+            if pending && !line.starts_with("i=") && !line.starts_with("c=") {
+                lines.push(bandwidth.clone());
+                pending = false;
+            }
+        }
+        lines.push(line.to_string());
+    }
+    if pending {
+        lines.push(bandwidth);
+    }
+    
+    let mut result = lines.join("\r\n");
+    result.push_str("\r\n");
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     
     #[tokio::test]
     async fn test_voice_manager_creation() {
+        let manager = VoiceManager::new_async().await;
+        assert!(manager.is_ok());
+    }
+    
+    #[test]
+    #[allow(deprecated)]
+    fn test_voice_manager_sync_creation() {
         let manager = VoiceManager::new();
         assert!(manager.is_ok());
     }
     
+    #[tokio::test]
+    async fn test_with_config() {
+        let config = CallConfig {
+            bitrate: 32000,
+            stun_servers: Vec::new(),
+            ..Default::default()
+        };
+        let manager = VoiceManager::with_config(config).await.unwrap();
+        assert_eq!(manager.config().bitrate, 32000);
+        assert!(manager.config().stun_servers.is_empty());
+        assert_eq!(manager.get_call_state().await, CallState::Idle);
+    }
+    
     #[tokio::test]
     async fn test_call_config_default() {
         let config = CallConfig::default();
@@ -600,9 +1409,243 @@ mod tests {
         assert!(!config.stun_servers.is_empty());
     }
     
+    #[test]
+    fn test_set_sdp_bitrate() {
+        let sdp = "v=0\r\ns=-\r\nm=audio 9 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 0.0.0.0\r\nb=AS:64\r\na=rtpmap:111 opus/48000/2\r\n";
+        let updated = set_sdp_bitrate(sdp, 32000);
+        
+        assert!(updated.contains("c=IN IP4 0.0.0.0\r\nb=AS:32\r\na=rtpmap"));
+        assert!(!updated.contains("b=AS:64"));
+    }
+    
+    #[tokio::test]
+    async fn test_renegotiate_codec_sends_offer() {
+        let mut manager = VoiceManager::with_config(CallConfig {
+            stun_servers: Vec::new(),
+            ..Default::default()
+        }).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        manager.set_signaling_channel(tx);
+        
+        assert!(matches!(manager.renegotiate_codec(32000).await, Err(VoiceError::NoActiveCall)));
+        
+        let session_id = manager.initiate_call("peer", manager.config().clone()).await.unwrap();
+        
+        // Skip the initial offer and pretend the call went through
+        while rx.try_recv().is_ok() {}
+        manager.active_call.write().await.as_mut().unwrap().state = CallState::Connected;
+        
+        manager.renegotiate_codec(32000).await.unwrap();
+        assert_eq!(manager.get_call_state().await, CallState::Renegotiating);
+        assert_eq!(manager.config().bitrate, 32000);
+        
+        let offer = loop {
+            match rx.recv().await {
+                Some((_, SignalingMessage::Offer { sdp, session_id: offer_session, .. })) => {
+                    assert_eq!(offer_session, session_id);
+                    break sdp;
+                }
+                Some(_) => continue,
+                None => panic!("No renegotiation offer sent"),
+            }
+        };
+        assert!(offer.contains("b=AS:32\r\n"));
+        
+        assert!(matches!(
+            manager.renegotiate_codec(16000).await,
+            Err(VoiceError::RenegotiationInProgress)
+        ));
+    }
+    
+    #[tokio::test]
+    async fn test_send_dtmf_needs_call_with_audio() {
+        let mut manager = VoiceManager::with_config(CallConfig {
+            stun_servers: Vec::new(),
+            ..Default::default()
+        }).await.unwrap();
+        
+        assert!(matches!(manager.send_dtmf('x').await, Err(VoiceError::InvalidDtmfDigit('x'))));
+        assert!(matches!(manager.send_dtmf('5').await, Err(VoiceError::NoActiveCall)));
+        
+        // No audio source, so there is no audio to carry the tone
+        manager.initiate_call("peer", manager.config().clone()).await.unwrap();
+        assert!(matches!(manager.send_dtmf('5').await, Err(VoiceError::AudioError(_))));
+        assert!(manager.dtmf_tones.lock().unwrap().is_empty());
+    }
+    
+    /// Reports one address, then another from the third poll on
+    struct SwitchingInterfaces {
+        polls: usize,
+    }
+    
+    impl InterfaceSource for SwitchingInterfaces {
+        fn addresses(&mut self) -> std::io::Result<std::collections::BTreeSet<std::net::IpAddr>> {
+            self.polls += 1;
+            let last_octet = if self.polls < 3 { 10 } else { 20 };
+            Ok([std::net::IpAddr::from([192, 168, 1, last_octet])].into())
+        }
+    }
+    
+    #[tokio::test]
+    async fn test_interface_change_restarts_ice() {
+        let mut manager = VoiceManager::with_config(CallConfig {
+            stun_servers: Vec::new(),
+            ..Default::default()
+        }).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        manager.set_signaling_channel(tx);
+        
+        assert!(matches!(manager.restart_ice().await, Err(VoiceError::NoActiveCall)));
+        
+        let session_id = manager.initiate_call("peer", manager.config().clone()).await.unwrap();
+        let first_offer = loop {
+            match rx.recv().await {
+                Some((_, SignalingMessage::Offer { sdp, .. })) => break sdp,
+                Some(_) => continue,
+                None => panic!("No initial offer sent"),
+            }
+        };
+        while rx.try_recv().is_ok() {}
+        manager.active_call.write().await.as_mut().unwrap().state = CallState::Connected;
+        
+        let (change_tx, mut change_rx) = mpsc::unbounded_channel();
+        NetworkInterfaceMonitor::with_source(SwitchingInterfaces { polls: 0 }, std::time::Duration::from_millis(10)).spawn(change_tx);
+        let change = change_rx.recv().await.unwrap();
+        assert_eq!(change.added, vec![std::net::IpAddr::from([192, 168, 1, 20])]);
+        assert_eq!(change.removed, vec![std::net::IpAddr::from([192, 168, 1, 10])]);
+        
+        manager.restart_ice().await.unwrap();
+        assert_eq!(manager.get_call_state().await, CallState::IceRestarting);
+        
+        let restart_offer = loop {
+            match rx.recv().await {
+                Some((_, SignalingMessage::Offer { sdp, session_id: offer_session, .. })) => {
+                    assert_eq!(offer_session, session_id);
+                    break sdp;
+                }
+                Some(_) => continue,
+                None => panic!("No ICE restart offer sent"),
+            }
+        };
+        
+        // An ICE restart picks new credentials
+        let ufrag = |sdp: &str| sdp.lines().find(|l| l.starts_with("a=ice-ufrag:")).map(str::to_string);
+        assert!(ufrag(&restart_offer).is_some());
+        assert_ne!(ufrag(&restart_offer), ufrag(&first_offer));
+        
+        assert!(matches!(manager.restart_ice().await, Err(VoiceError::RenegotiationInProgress)));
+    }
+    
+    #[tokio::test]
+    async fn test_symmetric_nat_requires_turn() {
+        let mut manager = VoiceManager::with_config(CallConfig::default()).await.unwrap();
+        let config = CallConfig {
+            stun_servers: Vec::new(),
+            nat_type: Some(NatType::Symmetric),
+            ..Default::default()
+        };
+        
+        let err = manager.initiate_call("peer", config).await.unwrap_err();
+        assert!(matches!(err.downcast_ref::<VoiceError>(), Some(VoiceError::TurnRequired)));
+        assert_eq!(manager.get_call_state().await, CallState::Idle);
+    }
+    
+    #[tokio::test]
+    async fn test_offer_while_busy_is_rejected() {
+        let mut manager = VoiceManager::with_config(CallConfig {
+            stun_servers: Vec::new(),
+            ..Default::default()
+        }).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        manager.set_signaling_channel(tx);
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        manager.set_event_channel(event_tx);
+        
+        let alice_session = manager.initiate_call("alice", manager.config().clone()).await.unwrap();
+        
+        let offer = SignalingMessage::Offer {
+            sdp: "v=0\r\n".to_string(),
+            media_type: MediaType::AudioOnly,
+            session_id: "bob-session".to_string(),
+        };
+        manager.handle_signaling("bob", offer).await.unwrap();
+        assert_eq!(manager.get_current_peer().await.as_deref(), Some("alice"));
+        
+        let rejected = loop {
+            match rx.try_recv() {
+                Ok((peer, SignalingMessage::Reject { session_id, reason })) => break (peer, session_id, reason),
+                Ok(_) => continue,
+                Err(_) => panic!("No reject sent"),
+            }
+        };
+        assert_eq!(rejected, ("bob".to_string(), "bob-session".to_string(), RejectReason::Busy));
+        assert!(matches!(manager.reject_call().await, Err(VoiceError::NoActiveCall)));
+        
+        // Alice turning us down ends our outgoing call
+        let reject = SignalingMessage::Reject {
+            session_id: alice_session,
+            reason: RejectReason::Declined,
+        };
+        let err = manager.handle_signaling("alice", reject).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<VoiceError>(),
+            Some(VoiceError::CallRejected(RejectReason::Declined))
+        ));
+        assert_eq!(
+            event_rx.try_recv().unwrap(),
+            VoiceEvent::CallRejected {
+                peer_id: "alice".to_string(),
+                reason: RejectReason::Declined,
+            }
+        );
+        assert_eq!(manager.get_call_state().await, CallState::Idle);
+    }
+    
+    #[tokio::test]
+    async fn test_conference_invites_every_participant() {
+        let mut manager = VoiceManager::with_config(CallConfig {
+            stun_servers: Vec::new(),
+            ..Default::default()
+        }).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        manager.set_signaling_channel(tx);
+        
+        let session = manager.start_conference(&["alice".to_string(), "bob".to_string()]).await.unwrap();
+        manager.add_to_conference(&session, "carol").await.unwrap();
+        assert!(matches!(
+            manager.add_to_conference(&session, "bob").await,
+            Err(VoiceError::CallAlreadyActive(_))
+        ));
+        assert!(matches!(
+            manager.add_to_conference("unknown", "dave").await,
+            Err(VoiceError::ConferenceNotFound(_))
+        ));
+        
+        let mut invited = Vec::new();
+        while let Ok((peer, msg)) = rx.try_recv() {
+            if let SignalingMessage::Offer { session_id, .. } = msg {
+                assert_eq!(session_id, session);
+                invited.push(peer);
+            }
+        }
+        assert_eq!(invited, ["alice", "bob", "carol"]);
+        
+        // A participant leaving does not end the conference or touch the 1-to-1 call state
+        let hangup = SignalingMessage::Hangup {
+            session_id: session.clone(),
+            reason: None,
+        };
+        manager.handle_signaling("bob", hangup).await.unwrap();
+        assert_eq!(manager.conferences[&session].participants.len(), 2);
+        assert_eq!(manager.get_call_state().await, CallState::Idle);
+        
+        manager.end_conference(&session).await.unwrap();
+        assert!(manager.conferences.is_empty());
+    }
+    
     #[tokio::test]
     async fn test_initial_state() {
-        let manager = VoiceManager::new().unwrap();
+        let manager = VoiceManager::new_async().await.unwrap();
         assert_eq!(manager.get_call_state().await, CallState::Idle);
         assert!(!manager.has_active_call().await);
         assert!(manager.get_current_peer().await.is_none());
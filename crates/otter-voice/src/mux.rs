@@ -0,0 +1,248 @@
+//! # Data Channel Multiplexing
+//!
+//! Carries several logical channels over one WebRTC data channel, so adding a
+//! channel does not cost another SCTP stream negotiation. Every frame starts
+//! with a 4-byte header, `[channel_id: u16, length: u16]` in big-endian,
+//! followed by `length` bytes of payload.
+
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{mpsc, watch};
+use tracing::{debug, warn};
+use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
+use webrtc::data_channel::RTCDataChannel;
+use webrtc::peer_connection::RTCPeerConnection;
+
+/// Logical channel for call signaling
+pub const SIGNALING_CHANNEL: u16 = 0;
+/// Logical channel for chat messages
+pub const MESSAGING_CHANNEL: u16 = 1;
+/// Logical channel for file transfers
+pub const FILE_TRANSFER_CHANNEL: u16 = 2;
+
+/// Label of the multiplexed data channel
+pub const MUX_LABEL: &str = "otter-mux";
+
+/// SCTP stream of the multiplexed data channel, agreed on by both peers
+/// instead of being announced in-band
+pub const MUX_STREAM_ID: u16 = 0;
+
+/// Frame header size
+pub const HEADER_LEN: usize = 4;
+
+/// Largest payload of one frame
+///
+/// webrtc-rs delivers data channel messages of at most 16 KiB.
+pub const MAX_PAYLOAD_LEN: usize = 16 * 1024 - HEADER_LEN;
+
+/// Buffered messages per direction and logical channel
+const CHANNEL_CAPACITY: usize = 256;
+
+type Routes = Arc<Mutex<HashMap<u16, mpsc::Sender<Vec<u8>>>>>;
+
+/// Logical channels over a single data channel
+pub struct Multiplexer {
+    data_channel: Arc<RTCDataChannel>,
+    routes: Routes,
+    open: watch::Receiver<bool>,
+}
+
+impl Multiplexer {
+    /// Create the pre-negotiated multiplexed data channel on `peer_connection`
+    ///
+    /// Both peers call this before the offer/answer exchange.
+    pub async fn create_data_channel(
+        peer_connection: &RTCPeerConnection,
+    ) -> Result<Arc<RTCDataChannel>, webrtc::Error> {
+        peer_connection
+            .create_data_channel(
+                MUX_LABEL,
+                Some(RTCDataChannelInit {
+                    negotiated: Some(MUX_STREAM_ID),
+                    ..Default::default()
+                }),
+            )
+            .await
+    }
+    
+    /// Start dispatching frames received on `data_channel`
+    ///
+    /// Frames are read in order, so a logical channel whose receiver is not
+    /// drained eventually stalls the others.
+    pub fn run(data_channel: Arc<RTCDataChannel>) -> Self {
+        let routes: Routes = Arc::default();
+        
+        let (open_tx, open) = watch::channel(data_channel.ready_state() == RTCDataChannelState::Open);
+        data_channel.on_open(Box::new(move || {
+            let _ = open_tx.send(true);
+            Box::pin(async {})
+        }));
+        
+        let (raw_tx, mut raw_rx) = mpsc::channel::<Bytes>(CHANNEL_CAPACITY);
+        data_channel.on_message(Box::new(move |msg| {
+            let raw_tx = raw_tx.clone();
+            Box::pin(async move {
+                let _ = raw_tx.send(msg.data).await;
+            })
+        }));
+        
+        let read_routes = Arc::clone(&routes);
+        tokio::spawn(async move {
+            while let Some(data) = raw_rx.recv().await {
+                let mut rest = &data[..];
+                while !rest.is_empty() {
+                    let Some((channel_id, payload, remaining)) = decode_frame(rest) else {
+                        warn!("Dropping truncated multiplexer frame");
+                        break;
+                    };
+                    rest = remaining;
+                    
+                    let route = read_routes.lock().unwrap().get(&channel_id).cloned();
+                    match route {
+                        Some(tx) => {
+                            if tx.send(payload.to_vec()).await.is_err() {
+                                read_routes.lock().unwrap().remove(&channel_id);
+                            }
+                        }
+                        None => debug!("Dropping frame for closed channel {}", channel_id),
+                    }
+                }
+            }
+        });
+        
+        Self {
+            data_channel,
+            routes,
+            open,
+        }
+    }
+    
+    /// Open logical channel `id`, returning its outgoing sender and incoming receiver
+    ///
+    /// Opening an ID again replaces the earlier receiver. Messages sent before
+    /// the data channel opens are held until it does; messages longer than
+    /// `MAX_PAYLOAD_LEN` are dropped.
+    pub fn open_channel(&self, id: u16) -> (mpsc::Sender<Vec<u8>>, mpsc::Receiver<Vec<u8>>) {
+        let (incoming_tx, incoming_rx) = mpsc::channel(CHANNEL_CAPACITY);
+        self.routes.lock().unwrap().insert(id, incoming_tx);
+        
+        let (outgoing_tx, mut outgoing_rx) = mpsc::channel::<Vec<u8>>(CHANNEL_CAPACITY);
+        let data_channel = Arc::clone(&self.data_channel);
+        let mut open = self.open.clone();
+        tokio::spawn(async move {
+            if open.wait_for(|open| *open).await.is_err() {
+                return;
+            }
+            
+            while let Some(payload) = outgoing_rx.recv().await {
+                let Some(frame) = encode_frame(id, &payload) else {
+                    warn!("Dropping {}-byte message on channel {}: too long", payload.len(), id);
+                    continue;
+                };
+                if let Err(e) = data_channel.send(&frame).await {
+                    warn!("Multiplexer channel {} closed: {}", id, e);
+                    break;
+                }
+            }
+        });
+        
+        (outgoing_tx, incoming_rx)
+    }
+}
+
+/// Frame `payload` for `channel_id`, or `None` if it is too long
+fn encode_frame(channel_id: u16, payload: &[u8]) -> Option<Bytes> {
+    if payload.len() > MAX_PAYLOAD_LEN {
+        return None;
+    }
+    
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len());
+    frame.extend_from_slice(&channel_id.to_be_bytes());
+    frame.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+    frame.extend_from_slice(payload);
+    Some(frame.into())
+}
+
+/// Split the first frame off `data` as `(channel_id, payload, rest)`
+fn decode_frame(data: &[u8]) -> Option<(u16, &[u8], &[u8])> {
+    if data.len() < HEADER_LEN {
+        return None;
+    }
+    
+    let channel_id = u16::from_be_bytes([data[0], data[1]]);
+    let len = u16::from_be_bytes([data[2], data[3]]) as usize;
+    let body = &data[HEADER_LEN..];
+    if body.len() < len {
+        return None;
+    }
+    Some((channel_id, &body[..len], &body[len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use webrtc::api::APIBuilder;
+    use webrtc::peer_connection::configuration::RTCConfiguration;
+    
+    /// Connect two peer connections that each carry the multiplexed data channel
+    async fn connected_pair() -> (Vec<Arc<RTCPeerConnection>>, Multiplexer, Multiplexer) {
+        let api = APIBuilder::new().build();
+        let a = Arc::new(api.new_peer_connection(RTCConfiguration::default()).await.unwrap());
+        let b = Arc::new(api.new_peer_connection(RTCConfiguration::default()).await.unwrap());
+        let mux_a = Multiplexer::run(Multiplexer::create_data_channel(&a).await.unwrap());
+        let mux_b = Multiplexer::run(Multiplexer::create_data_channel(&b).await.unwrap());
+        
+        // Exchange complete descriptions instead of trickling candidates
+        let offer = a.create_offer(None).await.unwrap();
+        let mut gathered = a.gathering_complete_promise().await;
+        a.set_local_description(offer).await.unwrap();
+        let _ = gathered.recv().await;
+        b.set_remote_description(a.local_description().await.unwrap()).await.unwrap();
+        
+        let answer = b.create_answer(None).await.unwrap();
+        let mut gathered = b.gathering_complete_promise().await;
+        b.set_local_description(answer).await.unwrap();
+        let _ = gathered.recv().await;
+        a.set_remote_description(b.local_description().await.unwrap()).await.unwrap();
+        
+        (vec![a, b], mux_a, mux_b)
+    }
+    
+    #[tokio::test]
+    async fn test_channels_receive_only_their_own_messages() {
+        let (peers, mux_a, mux_b) = connected_pair().await;
+        
+        let (messaging_tx, _) = mux_a.open_channel(MESSAGING_CHANNEL);
+        let (file_tx, _) = mux_a.open_channel(FILE_TRANSFER_CHANNEL);
+        let (_, mut messaging_rx) = mux_b.open_channel(MESSAGING_CHANNEL);
+        let (_, mut file_rx) = mux_b.open_channel(FILE_TRANSFER_CHANNEL);
+        
+        let senders = [(messaging_tx, "msg"), (file_tx, "file")].map(|(tx, tag)| {
+            tokio::spawn(async move {
+                for i in 0..100 {
+                    tx.send(format!("{}-{}", tag, i).into_bytes()).await.unwrap();
+                }
+            })
+        });
+        for sender in senders {
+            sender.await.unwrap();
+        }
+        
+        for (rx, tag) in [(&mut messaging_rx, "msg"), (&mut file_rx, "file")] {
+            for i in 0..100 {
+                let received = tokio::time::timeout(std::time::Duration::from_secs(10), rx.recv())
+                    .await
+                    .expect("Timed out waiting for a message")
+                    .unwrap();
+                assert_eq!(String::from_utf8(received).unwrap(), format!("{}-{}", tag, i));
+            }
+            assert!(rx.try_recv().is_err());
+        }
+        
+        for peer in peers {
+            peer.close().await.unwrap();
+        }
+    }
+}
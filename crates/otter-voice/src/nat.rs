@@ -0,0 +1,338 @@
+//! # NAT Type Detection
+//!
+//! Classifies the local NAT with STUN Binding Requests (RFC 5780), so a call
+//! behind a symmetric NAT can insist on a TURN relay up front instead of
+//! failing ICE later.
+//!
+//! Servers that advertise OTHER-ADDRESS get the full test sequence. For
+//! servers that don't, two local ports are probed and a NAT that does not
+//! preserve ports is treated as symmetric.
+
+use crate::VoiceError;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tracing::debug;
+use webrtc::stun::addr::OtherAddress;
+use webrtc::stun::attributes::{ATTR_CHANGE_REQUEST, ATTR_OTHER_ADDRESS};
+use webrtc::stun::message::{Getter, Message, BINDING_REQUEST, BINDING_SUCCESS};
+use webrtc::stun::xoraddr::XorMappedAddress;
+
+/// How long to wait for each STUN response
+pub const STUN_RESPONSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// CHANGE-REQUEST flag asking the server to answer from its other IP
+const CHANGE_IP: u32 = 0x04;
+
+/// CHANGE-REQUEST flag asking the server to answer from its other port
+const CHANGE_PORT: u32 = 0x02;
+
+/// NAT behaviour as seen from a STUN server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NatType {
+    /// No NAT: the mapped address is the local address
+    Open,
+    /// Any external host can reach the mapped address
+    FullCone,
+    /// Only hosts we sent to can reach the mapped address, from any port
+    RestrictedCone,
+    /// Only the exact host and port we sent to can reach the mapped address
+    PortRestricted,
+    /// Every destination gets a different mapping; direct connections fail
+    Symmetric,
+}
+
+impl NatType {
+    /// Whether calls from behind this NAT need a TURN relay
+    pub fn requires_turn(&self) -> bool {
+        matches!(self, NatType::Symmetric)
+    }
+}
+
+/// Results of the individual STUN tests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NatProbe {
+    /// Local address the requests were sent from
+    pub local: SocketAddr,
+    
+    /// Mapped address reported by the primary server address
+    pub mapped: SocketAddr,
+    
+    /// Mapped address reported by the server's other address, if it has one
+    pub other_mapped: Option<SocketAddr>,
+    
+    /// A response arrived to a request asking for a changed IP and port
+    pub change_ip_and_port_answered: bool,
+    
+    /// A response arrived to a request asking for a changed port
+    pub change_port_answered: bool,
+}
+
+impl NatProbe {
+    /// Classify the NAT from the test results
+    pub fn classify(&self) -> NatType {
+        if self.mapped == self.local {
+            return NatType::Open;
+        }
+        
+        if matches!(self.other_mapped, Some(other) if other != self.mapped) {
+            return NatType::Symmetric;
+        }
+        
+        if self.change_ip_and_port_answered {
+            NatType::FullCone
+        } else if self.change_port_answered {
+            NatType::RestrictedCone
+        } else {
+            NatType::PortRestricted
+        }
+    }
+}
+
+/// Detect the local NAT type using `stun_server` (`stun:host:port` or `host:port`)
+pub async fn detect_nat_type(stun_server: &str, timeout: Duration) -> Result<NatType, VoiceError> {
+    let server = resolve(stun_server).await?;
+    let socket = bind().await?;
+    let local = local_address(&socket, server).await?;
+    
+    let response = binding(&socket, server, None, timeout)
+        .await?
+        .ok_or_else(|| VoiceError::ConnectionFailed(format!("No STUN response from {}", server)))?;
+    let mapped = mapped_address(&response)?;
+    
+    let mut other = OtherAddress::default();
+    let other_server = other
+        .get_from_as(&response, ATTR_OTHER_ADDRESS)
+        .ok()
+        .map(|_| SocketAddr::new(other.ip, other.port));
+    
+    let probe = match other_server {
+        Some(other_server) => {
+            let other_mapped = match binding(&socket, other_server, None, timeout).await? {
+                Some(response) => Some(mapped_address(&response)?),
+                None => None,
+            };
+            
+            NatProbe {
+                local,
+                mapped,
+                other_mapped,
+                change_ip_and_port_answered: binding(&socket, server, Some(CHANGE_IP | CHANGE_PORT), timeout)
+                    .await?
+                    .is_some(),
+                change_port_answered: binding(&socket, server, Some(CHANGE_PORT), timeout)
+                    .await?
+                    .is_some(),
+            }
+        }
+        None => {
+            debug!("{} has no OTHER-ADDRESS, comparing port mappings instead", server);
+            let second = bind().await?;
+            let second_local = local_address(&second, server).await?;
+            let second_mapped = match binding(&second, server, None, timeout).await? {
+                Some(response) => mapped_address(&response)?,
+                None => return Err(VoiceError::ConnectionFailed(format!("No STUN response from {}", server))),
+            };
+            
+            let preserves_ports = mapped.port() == local.port() && second_mapped.port() == second_local.port();
+            NatProbe {
+                local,
+                mapped,
+                other_mapped: (!preserves_ports).then_some(second_mapped),
+                change_ip_and_port_answered: false,
+                change_port_answered: false,
+            }
+        }
+    };
+    
+    debug!("NAT probe: {:?}", probe);
+    Ok(probe.classify())
+}
+
+async fn resolve(stun_server: &str) -> Result<SocketAddr, VoiceError> {
+    let host = stun_server.strip_prefix("stun:").unwrap_or(stun_server);
+    tokio::net::lookup_host(host)
+        .await
+        .map_err(|e| VoiceError::ConnectionFailed(format!("Cannot resolve {}: {}", host, e)))?
+        .find(SocketAddr::is_ipv4)
+        .ok_or_else(|| VoiceError::ConnectionFailed(format!("No IPv4 address for {}", host)))
+}
+
+async fn bind() -> Result<UdpSocket, VoiceError> {
+    UdpSocket::bind("0.0.0.0:0")
+        .await
+        .map_err(|e| VoiceError::ConnectionFailed(e.to_string()))
+}
+
+/// Local address of `socket` on the interface that routes to `server`
+///
+/// The socket itself stays unconnected so it can receive answers from the
+/// server's other address.
+async fn local_address(socket: &UdpSocket, server: SocketAddr) -> Result<SocketAddr, VoiceError> {
+    let probe = bind().await?;
+    probe
+        .connect(server)
+        .await
+        .map_err(|e| VoiceError::ConnectionFailed(e.to_string()))?;
+    let ip = probe
+        .local_addr()
+        .map_err(|e| VoiceError::ConnectionFailed(e.to_string()))?
+        .ip();
+    let port = socket
+        .local_addr()
+        .map_err(|e| VoiceError::ConnectionFailed(e.to_string()))?
+        .port();
+    
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// Send a Binding Request and wait for the matching success response
+async fn binding(
+    socket: &UdpSocket,
+    server: SocketAddr,
+    change_request: Option<u32>,
+    timeout: Duration,
+) -> Result<Option<Message>, VoiceError> {
+    let mut request = Message::new();
+    request.set_type(BINDING_REQUEST);
+    request
+        .new_transaction_id()
+        .map_err(|e| VoiceError::WebRtc(e.to_string()))?;
+    if let Some(flags) = change_request {
+        request.add(ATTR_CHANGE_REQUEST, &flags.to_be_bytes());
+    }
+    request.encode();
+    
+    socket
+        .send_to(&request.raw, server)
+        .await
+        .map_err(|e| VoiceError::ConnectionFailed(e.to_string()))?;
+    
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut buf = [0u8; 1500];
+    loop {
+        let len = match tokio::time::timeout_at(deadline, socket.recv_from(&mut buf)).await {
+            Ok(Ok((len, _))) => len,
+            Ok(Err(e)) => return Err(VoiceError::ConnectionFailed(e.to_string())),
+            Err(_) => return Ok(None),
+        };
+        
+        let mut response = Message::new();
+        if response.unmarshal_binary(&buf[..len]).is_err() {
+            continue;
+        }
+        if response.transaction_id == request.transaction_id && response.typ == BINDING_SUCCESS {
+            return Ok(Some(response));
+        }
+    }
+}
+
+fn mapped_address(response: &Message) -> Result<SocketAddr, VoiceError> {
+    let mut mapped = XorMappedAddress::default();
+    mapped
+        .get_from(response)
+        .map_err(|e| VoiceError::WebRtc(format!("Missing XOR-MAPPED-ADDRESS: {}", e)))?;
+    Ok(SocketAddr::new(mapped.ip, mapped.port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use webrtc::stun::message::Setter;
+    
+    /// Mapped address the mock server reports for NATed clients
+    const PUBLIC_IP: [u8; 4] = [203, 0, 113, 5];
+    
+    /// Answers Binding Requests the way a STUN server behind the given NAT would appear
+    async fn mock_stun_server(nat: NatType) -> SocketAddr {
+        let primary = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let other = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let other_addr = other.local_addr().unwrap();
+        
+        for (socket, is_other) in [(Arc::clone(&primary), false), (other, true)] {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1500];
+                while let Ok((len, from)) = socket.recv_from(&mut buf).await {
+                    let mut request = Message::new();
+                    if request.unmarshal_binary(&buf[..len]).is_err() {
+                        continue;
+                    }
+                    
+                    let change = request
+                        .get(ATTR_CHANGE_REQUEST)
+                        .ok()
+                        .map(|v| u32::from_be_bytes([v[0], v[1], v[2], v[3]]));
+                    let answered = match (nat, change) {
+                        (_, None) => true,
+                        (NatType::Open | NatType::FullCone, Some(_)) => true,
+                        (NatType::RestrictedCone, Some(flags)) => flags == CHANGE_PORT,
+                        _ => false,
+                    };
+                    if !answered {
+                        continue;
+                    }
+                    
+                    let mapped = match nat {
+                        NatType::Open => from,
+                        NatType::Symmetric if is_other => SocketAddr::from((PUBLIC_IP, 40001)),
+                        _ => SocketAddr::from((PUBLIC_IP, 40000)),
+                    };
+                    
+                    let mut response = Message::new();
+                    response.set_type(BINDING_SUCCESS);
+                    response.transaction_id = request.transaction_id;
+                    response.write_transaction_id();
+                    XorMappedAddress { ip: mapped.ip(), port: mapped.port() }
+                        .add_to(&mut response)
+                        .unwrap();
+                    OtherAddress { ip: other_addr.ip(), port: other_addr.port() }
+                        .add_to_as(&mut response, ATTR_OTHER_ADDRESS)
+                        .unwrap();
+                    response.encode();
+                    let _ = socket.send_to(&response.raw, from).await;
+                }
+            });
+        }
+        
+        primary.local_addr().unwrap()
+    }
+    
+    #[tokio::test]
+    async fn test_detect_each_nat_type() {
+        for nat in [
+            NatType::Open,
+            NatType::FullCone,
+            NatType::RestrictedCone,
+            NatType::PortRestricted,
+            NatType::Symmetric,
+        ] {
+            let server = mock_stun_server(nat).await;
+            let detected = detect_nat_type(&format!("stun:{}", server), Duration::from_millis(200))
+                .await
+                .unwrap();
+            assert_eq!(detected, nat);
+        }
+    }
+    
+    #[test]
+    fn test_classify_without_other_address() {
+        let local: SocketAddr = "192.168.1.10:5000".parse().unwrap();
+        let probe = NatProbe {
+            local,
+            mapped: "203.0.113.5:5000".parse().unwrap(),
+            other_mapped: None,
+            change_ip_and_port_answered: false,
+            change_port_answered: false,
+        };
+        assert_eq!(probe.classify(), NatType::PortRestricted);
+        
+        let probe = NatProbe {
+            other_mapped: Some("203.0.113.5:6123".parse().unwrap()),
+            ..probe
+        };
+        assert_eq!(probe.classify(), NatType::Symmetric);
+        assert!(probe.classify().requires_turn());
+    }
+}
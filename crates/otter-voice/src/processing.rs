@@ -0,0 +1,375 @@
+//! # Audio Processing
+//!
+//! Cleans up captured audio before it is Opus-encoded, in the order of the
+//! WebRTC audio processing module: high-pass filter, echo cancellation,
+//! noise suppression and automatic gain control.
+//!
+//! Echo cancellation is an NLMS adaptive filter that learns the path from
+//! the loudspeaker to the microphone, so it needs the far-end audio as it is
+//! played through `analyze_render`. Noise suppression uses RNNoise through
+//! `nnnoiseless` and needs the `noise_suppression` feature.
+
+use crate::audio::SAMPLE_RATE;
+use crate::VoiceError;
+use std::collections::VecDeque;
+
+/// Echo paths up to this many samples (about 43 ms) are cancelled
+pub const ECHO_TAIL_SAMPLES: usize = 2048;
+
+/// Cut-off of the high-pass filter in Hz
+const HIGH_PASS_CUTOFF_HZ: f32 = 80.0;
+
+/// NLMS step size; larger adapts faster but tracks near-end speech as echo
+const ECHO_STEP_SIZE: f32 = 0.5;
+
+/// Level the gain control aims for, about -18 dBFS
+const AGC_TARGET_RMS: f32 = 0.125;
+
+/// Most the gain control amplifies, 20 dB
+const AGC_MAX_GAIN: f32 = 10.0;
+
+/// Frames quieter than this are treated as silence and not amplified
+const AGC_SILENCE_RMS: f32 = 1e-3;
+
+/// Fraction of the way to the wanted gain covered per frame
+const AGC_SMOOTHING: f32 = 0.1;
+
+/// Processing stages applied to captured audio
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct AudioProcessingConfig {
+    pub echo_cancellation: bool,
+    pub noise_suppression: bool,
+    pub auto_gain_control: bool,
+    pub high_pass_filter: bool,
+}
+
+impl AudioProcessingConfig {
+    /// Every stage enabled
+    pub fn full() -> Self {
+        Self {
+            echo_cancellation: true,
+            noise_suppression: true,
+            auto_gain_control: true,
+            high_pass_filter: true,
+        }
+    }
+    
+    /// Whether any stage is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.echo_cancellation || self.noise_suppression || self.auto_gain_control || self.high_pass_filter
+    }
+}
+
+/// First-order high-pass filter that removes DC offset and low rumble
+struct HighPassFilter {
+    alpha: f32,
+    last_input: f32,
+    last_output: f32,
+}
+
+impl HighPassFilter {
+    fn new(cutoff_hz: f32) -> Self {
+        let rc = 1.0 / (std::f32::consts::TAU * cutoff_hz);
+        let dt = 1.0 / SAMPLE_RATE as f32;
+        Self {
+            alpha: rc / (rc + dt),
+            last_input: 0.0,
+            last_output: 0.0,
+        }
+    }
+    
+    fn process(&mut self, frame: &mut [f32]) {
+        for sample in frame {
+            let output = self.alpha * (self.last_output + *sample - self.last_input);
+            self.last_input = *sample;
+            self.last_output = output;
+            *sample = output;
+        }
+    }
+}
+
+/// Normalized least-mean-squares echo canceller
+struct EchoCanceller {
+    weights: Vec<f32>,
+    /// Far-end history stored twice, so the newest `ECHO_TAIL_SAMPLES` are always contiguous
+    history: Vec<f32>,
+    position: usize,
+    /// Sum of squares of the samples in the window
+    energy: f32,
+    /// Far-end samples played but not yet matched with a captured sample
+    render: VecDeque<f32>,
+}
+
+impl EchoCanceller {
+    fn new() -> Self {
+        Self {
+            weights: vec![0.0; ECHO_TAIL_SAMPLES],
+            history: vec![0.0; 2 * ECHO_TAIL_SAMPLES],
+            position: 0,
+            energy: 0.0,
+            render: VecDeque::new(),
+        }
+    }
+    
+    fn analyze_render(&mut self, far_end: &[f32]) {
+        self.render.extend(far_end);
+        
+        // Drop audio the capture side will never catch up with
+        let excess = self.render.len().saturating_sub(ECHO_TAIL_SAMPLES);
+        self.render.drain(..excess);
+    }
+    
+    fn process(&mut self, frame: &mut [f32]) {
+        for sample in frame {
+            let far = self.render.pop_front().unwrap_or(0.0);
+            
+            // Newest sample first in the window; it replaces the oldest
+            self.position = self.position.checked_sub(1).unwrap_or(ECHO_TAIL_SAMPLES - 1);
+            let oldest = self.history[self.position];
+            self.energy = (self.energy + far * far - oldest * oldest).max(0.0);
+            self.history[self.position] = far;
+            self.history[self.position + ECHO_TAIL_SAMPLES] = far;
+            let window = &self.history[self.position..self.position + ECHO_TAIL_SAMPLES];
+            
+            let estimate: f32 = self.weights.iter().zip(window).map(|(w, x)| w * x).sum();
+            let error = *sample - estimate;
+            *sample = error;
+            
+            if self.energy > f32::EPSILON {
+                let step = ECHO_STEP_SIZE * error / (self.energy + 1e-6);
+                for (w, x) in self.weights.iter_mut().zip(window) {
+                    *w += step * x;
+                }
+            }
+        }
+    }
+}
+
+/// Gain control that pulls speech towards a fixed level
+struct AutomaticGainControl {
+    gain: f32,
+}
+
+impl AutomaticGainControl {
+    fn process(&mut self, frame: &mut [f32]) {
+        let rms = (frame.iter().map(|s| s * s).sum::<f32>() / frame.len().max(1) as f32).sqrt();
+        if rms > AGC_SILENCE_RMS {
+            let wanted = (AGC_TARGET_RMS / rms).min(AGC_MAX_GAIN);
+            self.gain += (wanted - self.gain) * AGC_SMOOTHING;
+        }
+        for sample in frame {
+            *sample = (*sample * self.gain).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// RNNoise, which works on 10 ms frames of 16-bit scaled samples
+#[cfg(feature = "noise_suppression")]
+struct NoiseSuppressor {
+    state: Box<nnnoiseless::DenoiseState<'static>>,
+    output: Vec<f32>,
+}
+
+#[cfg(feature = "noise_suppression")]
+impl NoiseSuppressor {
+    fn new() -> Self {
+        Self {
+            state: nnnoiseless::DenoiseState::new(),
+            output: vec![0.0; nnnoiseless::DenoiseState::FRAME_SIZE],
+        }
+    }
+    
+    fn process(&mut self, frame: &mut [f32]) {
+        for chunk in frame.chunks_mut(nnnoiseless::DenoiseState::FRAME_SIZE) {
+            if chunk.len() < nnnoiseless::DenoiseState::FRAME_SIZE {
+                continue;
+            }
+            let input: Vec<f32> = chunk.iter().map(|s| s * i16::MAX as f32).collect();
+            self.state.process_frame(&mut self.output, &input);
+            for (sample, denoised) in chunk.iter_mut().zip(&self.output) {
+                *sample = denoised / i16::MAX as f32;
+            }
+        }
+    }
+}
+
+/// Processes captured frames with the stages of an `AudioProcessingConfig`
+pub struct AudioProcessor {
+    config: AudioProcessingConfig,
+    high_pass: HighPassFilter,
+    echo: EchoCanceller,
+    #[cfg(feature = "noise_suppression")]
+    noise: NoiseSuppressor,
+    agc: AutomaticGainControl,
+}
+
+impl AudioProcessor {
+    /// Create a processor for `config`
+    ///
+    /// Fails with `AudioProcessingInitFailed` if a requested stage is not
+    /// compiled in.
+    pub fn new(config: AudioProcessingConfig) -> Result<Self, VoiceError> {
+        if config.noise_suppression && !cfg!(feature = "noise_suppression") {
+            return Err(VoiceError::AudioProcessingInitFailed(
+                "noise suppression needs the noise_suppression feature".to_string(),
+            ));
+        }
+        
+        Ok(Self {
+            config,
+            high_pass: HighPassFilter::new(HIGH_PASS_CUTOFF_HZ),
+            echo: EchoCanceller::new(),
+            #[cfg(feature = "noise_suppression")]
+            noise: NoiseSuppressor::new(),
+            agc: AutomaticGainControl { gain: 1.0 },
+        })
+    }
+    
+    /// Stages this processor applies
+    pub fn config(&self) -> AudioProcessingConfig {
+        self.config
+    }
+    
+    /// Feed far-end audio as it is played, for echo cancellation
+    pub fn analyze_render(&mut self, far_end: &[f32]) {
+        if self.config.echo_cancellation {
+            self.echo.analyze_render(far_end);
+        }
+    }
+    
+    /// Process one captured frame in place
+    pub fn process_capture(&mut self, frame: &mut [f32]) {
+        if self.config.high_pass_filter {
+            self.high_pass.process(frame);
+        }
+        if self.config.echo_cancellation {
+            self.echo.process(frame);
+        }
+        #[cfg(feature = "noise_suppression")]
+        if self.config.noise_suppression {
+            self.noise.process(frame);
+        }
+        if self.config.auto_gain_control {
+            self.agc.process(frame);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::{LocalAudioSource, SineWaveSource, FRAME_SAMPLES};
+    
+    fn rms(samples: &[f32]) -> f32 {
+        (samples.iter().map(|s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+    }
+    
+    #[test]
+    fn test_echo_cancellation_removes_delayed_copy() {
+        let config = AudioProcessingConfig {
+            echo_cancellation: true,
+            ..AudioProcessingConfig::default()
+        };
+        let mut processor = AudioProcessor::new(config).unwrap();
+        let mut low = SineWaveSource::new(300.0);
+        let mut high = SineWaveSource::new(1250.0);
+        
+        // The microphone picks up the far end 10 ms later at 60% of its level
+        let delay = 480;
+        let mut played = vec![0.0; delay];
+        let mut echo_in = Vec::new();
+        let mut echo_out = Vec::new();
+        for _ in 0..50 {
+            let far_end: Vec<f32> = low
+                .capture_frame()
+                .unwrap()
+                .iter()
+                .zip(high.capture_frame().unwrap())
+                .map(|(a, b)| 0.5 * (a + b))
+                .collect();
+            processor.analyze_render(&far_end);
+            played.extend(&far_end);
+            
+            let mut captured: Vec<f32> = played.drain(..FRAME_SAMPLES).map(|s| 0.6 * s).collect();
+            echo_in.extend(&captured);
+            processor.process_capture(&mut captured);
+            echo_out.extend(captured);
+        }
+        
+        // Compare the last 200 ms, once the filter has converged
+        let tail = echo_in.len() - 10 * FRAME_SAMPLES;
+        let reduction_db = 20.0 * (rms(&echo_in[tail..]) / rms(&echo_out[tail..])).log10();
+        assert!(reduction_db >= 20.0, "echo reduced by only {:.1} dB", reduction_db);
+    }
+    
+    #[test]
+    fn test_near_end_passes_without_far_end() {
+        let mut processor = AudioProcessor::new(AudioProcessingConfig {
+            echo_cancellation: true,
+            ..AudioProcessingConfig::default()
+        })
+        .unwrap();
+        let original = SineWaveSource::new(440.0).capture_frame().unwrap();
+        let mut frame = original.clone();
+        processor.process_capture(&mut frame);
+        assert_eq!(frame, original);
+    }
+    
+    #[test]
+    fn test_high_pass_and_gain_control() {
+        let mut processor = AudioProcessor::new(AudioProcessingConfig {
+            high_pass_filter: true,
+            auto_gain_control: true,
+            ..AudioProcessingConfig::default()
+        })
+        .unwrap();
+        let mut quiet = SineWaveSource::new(440.0);
+        quiet.amplitude = 0.02;
+        
+        // A DC offset is filtered out and the quiet tone is brought up towards the target
+        let mut frame = Vec::new();
+        for _ in 0..100 {
+            frame = quiet.capture_frame().unwrap().iter().map(|s| s + 0.3).collect();
+            processor.process_capture(&mut frame);
+        }
+        let mean = frame.iter().sum::<f32>() / frame.len() as f32;
+        assert!(mean.abs() < 0.01, "DC offset {}", mean);
+        assert!((rms(&frame) - AGC_TARGET_RMS).abs() < 0.02, "level {}", rms(&frame));
+    }
+    
+    #[cfg(feature = "noise_suppression")]
+    #[test]
+    fn test_noise_suppression_attenuates_noise() {
+        let mut processor = AudioProcessor::new(AudioProcessingConfig {
+            noise_suppression: true,
+            ..AudioProcessingConfig::default()
+        })
+        .unwrap();
+        
+        // Deterministic white noise from a linear congruential generator
+        let mut seed = 0x2545_f491_u32;
+        let mut noise = || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            (seed >> 8) as f32 / (1 << 24) as f32 * 0.2 - 0.1
+        };
+        
+        let mut noise_in = Vec::new();
+        let mut noise_out = Vec::new();
+        for _ in 0..100 {
+            let mut frame: Vec<f32> = (0..FRAME_SAMPLES).map(|_| noise()).collect();
+            noise_in.extend(&frame);
+            processor.process_capture(&mut frame);
+            noise_out.extend(frame);
+        }
+        
+        let tail = noise_in.len() - 20 * FRAME_SAMPLES;
+        assert!(rms(&noise_out[tail..]) < 0.5 * rms(&noise_in[tail..]));
+    }
+    
+    #[cfg(not(feature = "noise_suppression"))]
+    #[test]
+    fn test_noise_suppression_needs_feature() {
+        let result = AudioProcessor::new(AudioProcessingConfig::full());
+        assert!(matches!(result, Err(VoiceError::AudioProcessingInitFailed(_))));
+    }
+}
@@ -0,0 +1,105 @@
+//! # SDP Redaction
+//!
+//! Session descriptions carry the DTLS certificate fingerprint and the ICE
+//! credentials of a call. Both stay stable long enough to link logged calls
+//! to the same device, so SDP is redacted before it reaches the logs.
+
+/// Replacement for redacted attribute values
+const REDACTED: &str = "[REDACTED]";
+
+/// Attributes whose whole value is redacted
+const REDACTED_ATTRIBUTES: [&str; 2] = ["a=ice-ufrag:", "a=ice-pwd:"];
+
+/// Attribute whose value after the hash function name is redacted
+const FINGERPRINT_ATTRIBUTE: &str = "a=fingerprint:";
+
+/// Removes identifying values from SDP before it is logged
+pub struct SdpSanitizer;
+
+impl SdpSanitizer {
+    /// Replace DTLS fingerprints and ICE credentials in `sdp` with `[REDACTED]`
+    ///
+    /// All other lines, and the line endings, are left as they are.
+    pub fn redact(sdp: &str) -> String {
+        sdp.split_inclusive('\n')
+            .map(|line| {
+                let content = line.trim_end_matches(['\r', '\n']);
+                let ending = &line[content.len()..];
+                match Self::redacted_line(content) {
+                    Some(redacted) => format!("{}{}", redacted, ending),
+                    None => line.to_string(),
+                }
+            })
+            .collect()
+    }
+    
+    /// Whether `sdp` holds no fingerprint or ICE credential in the clear
+    pub fn is_redacted(sdp: &str) -> bool {
+        sdp.lines()
+            .map(|line| line.trim_end_matches('\r'))
+            .all(|line| Self::redacted_line(line).is_none_or(|redacted| redacted == line))
+    }
+    
+    /// Redacted form of `line` if it holds a value to hide
+    fn redacted_line(line: &str) -> Option<String> {
+        if let Some(value) = line.strip_prefix(FINGERPRINT_ATTRIBUTE) {
+            let hash_function = value.split_whitespace().next().unwrap_or_default();
+            return Some(format!("{}{} {}", FINGERPRINT_ATTRIBUTE, hash_function, REDACTED));
+        }
+        
+        REDACTED_ATTRIBUTES
+            .iter()
+            .find(|attribute| line.starts_with(*attribute))
+            .map(|attribute| format!("{}{}", attribute, REDACTED))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const OFFER: &str = "v=0\r\n\
+        o=- 4611731400430051336 2 IN IP4 127.0.0.1\r\n\
+        s=-\r\n\
+        t=0 0\r\n\
+        a=group:BUNDLE 0\r\n\
+        a=fingerprint:sha-256 6B:8B:5D:EA:59:04:20:23:29:C8:87:1C:CC:87:32:BE:DD:8C:66:A5:8E:50:55:EA:8C:D3:B6:5C:09:5E:D6:BC\r\n\
+        m=audio 9 UDP/TLS/RTP/SAVPF 111\r\n\
+        c=IN IP4 0.0.0.0\r\n\
+        b=AS:64\r\n\
+        a=ice-ufrag:EsAw\r\n\
+        a=ice-pwd:bP+XJMM09aR8AiX1jdukzR6Y\r\n\
+        a=setup:actpass\r\n\
+        a=mid:0\r\n\
+        a=rtpmap:111 opus/48000/2\r\n\
+        a=fmtp:111 minptime=10;useinbandfec=1\r\n";
+    
+    #[test]
+    fn test_redact_hides_fingerprint_and_ice_credentials() {
+        assert!(!SdpSanitizer::is_redacted(OFFER));
+        
+        let redacted = SdpSanitizer::redact(OFFER);
+        assert!(SdpSanitizer::is_redacted(&redacted));
+        assert!(redacted.contains("a=fingerprint:sha-256 [REDACTED]\r\n"));
+        assert!(redacted.contains("a=ice-ufrag:[REDACTED]\r\n"));
+        assert!(redacted.contains("a=ice-pwd:[REDACTED]\r\n"));
+        for secret in ["6B:8B:5D", "EsAw", "bP+XJMM09aR8AiX1jdukzR6Y"] {
+            assert!(!redacted.contains(secret), "{} leaked", secret);
+        }
+        
+        // Everything else is untouched
+        let kept = |sdp: &str| -> Vec<String> {
+            sdp.lines()
+                .filter(|line| SdpSanitizer::redacted_line(line.trim_end_matches('\r')).is_none())
+                .map(str::to_string)
+                .collect()
+        };
+        assert_eq!(kept(&redacted), kept(OFFER));
+        assert!(redacted.contains("m=audio 9 UDP/TLS/RTP/SAVPF 111\r\nc=IN IP4 0.0.0.0\r\nb=AS:64\r\n"));
+        assert!(redacted.contains("a=rtpmap:111 opus/48000/2\r\na=fmtp:111 minptime=10;useinbandfec=1\r\n"));
+        assert_eq!(redacted.lines().count(), OFFER.lines().count());
+        
+        // Redacting twice changes nothing
+        assert_eq!(SdpSanitizer::redact(&redacted), redacted);
+    }
+}
@@ -0,0 +1,191 @@
+//! # TURN Credentials
+//!
+//! Short-lived TURN credentials for self-hosted relays, following the
+//! shared-secret scheme used by TURN REST APIs (RFC 8489 § 9.2 long-term
+//! credentials): the username is `"<expires_at>:<peer_id>"` and the password is
+//! the HMAC-SHA256 of the username keyed with a secret shared with the relay.
+//! The relay recomputes the password from the username, so no per-user state
+//! is needed on its side.
+
+use crate::VoiceError;
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use hmac::{Hmac, Mac};
+use otter_identity::PeerId;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Default credential lifetime (24 hours)
+pub const DEFAULT_TURN_CREDENTIAL_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Time-limited TURN username/password pair
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnCredential {
+    /// `"<expires_at>:<peer_id>"`
+    pub username: String,
+    
+    /// Base64 HMAC-SHA256 of the username
+    pub password: String,
+    
+    /// Unix timestamp (seconds) after which the relay rejects the credential
+    pub expires_at: u64,
+}
+
+impl TurnCredential {
+    /// Check if the credential has expired at `now` (Unix seconds)
+    pub fn is_expired_at(&self, now: u64) -> bool {
+        now >= self.expires_at
+    }
+    
+    /// Fail with `TurnTokenExpired` if the credential is no longer valid
+    pub fn ensure_valid(&self) -> Result<(), VoiceError> {
+        if self.is_expired_at(unix_now()) {
+            return Err(VoiceError::TurnTokenExpired);
+        }
+        Ok(())
+    }
+}
+
+/// Issues TURN credentials for a relay operated with a shared secret
+#[derive(Clone)]
+pub struct TurnTokenIssuer {
+    /// Local peer the credentials are issued to
+    peer_id: PeerId,
+    
+    /// Secret shared with the TURN relay
+    secret: Vec<u8>,
+    
+    /// Lifetime of each issued credential
+    valid_for_secs: u64,
+}
+
+impl TurnTokenIssuer {
+    /// Create an issuer for `peer_id`
+    pub fn new(peer_id: PeerId, secret: Vec<u8>, valid_for_secs: u64) -> Self {
+        Self {
+            peer_id,
+            secret,
+            valid_for_secs,
+        }
+    }
+    
+    /// Issue a fresh credential
+    pub fn issue(&self) -> TurnCredential {
+        Self::generate_credential(&self.peer_id, self.valid_for_secs, &self.secret)
+    }
+    
+    /// Generate a credential valid for `valid_for_secs` from now
+    pub fn generate_credential(peer_id: &PeerId, valid_for_secs: u64, secret: &[u8]) -> TurnCredential {
+        Self::generate_credential_at(peer_id, valid_for_secs, secret, unix_now())
+    }
+    
+    /// Generate a credential valid for `valid_for_secs` from `now` (Unix seconds)
+    pub fn generate_credential_at(
+        peer_id: &PeerId,
+        valid_for_secs: u64,
+        secret: &[u8],
+        now: u64,
+    ) -> TurnCredential {
+        let expires_at = now.saturating_add(valid_for_secs);
+        let username = format!("{}:{}", expires_at, peer_id);
+        let password = sign_username(&username, secret);
+        
+        TurnCredential {
+            username,
+            password,
+            expires_at,
+        }
+    }
+    
+    /// Check that a credential is authentic and not expired
+    pub fn verify_credential(cred: &TurnCredential, secret: &[u8]) -> bool {
+        Self::verify_credential_at(cred, secret, unix_now())
+    }
+    
+    /// Check that a credential is authentic and not expired at `now` (Unix seconds)
+    pub fn verify_credential_at(cred: &TurnCredential, secret: &[u8], now: u64) -> bool {
+        // The expiry relays enforce is the one embedded in the signed username
+        let signed_expiry = cred
+            .username
+            .split_once(':')
+            .and_then(|(expiry, _)| expiry.parse::<u64>().ok());
+        if signed_expiry != Some(cred.expires_at) || cred.is_expired_at(now) {
+            return false;
+        }
+        
+        let password = match BASE64.decode(&cred.password) {
+            Ok(password) => password,
+            Err(_) => return false,
+        };
+        
+        let mut mac = match HmacSha256::new_from_slice(secret) {
+            Ok(mac) => mac,
+            Err(_) => return false,
+        };
+        mac.update(cred.username.as_bytes());
+        mac.verify_slice(&password).is_ok()
+    }
+    
+    /// Password a relay expects for `username` at `now` (Unix seconds)
+    ///
+    /// Returns `None` if the username carries no expiry or has expired.
+    pub fn expected_password_at(username: &str, secret: &[u8], now: u64) -> Option<String> {
+        let (expiry, _) = username.split_once(':')?;
+        if now >= expiry.parse::<u64>().ok()? {
+            return None;
+        }
+        Some(sign_username(username, secret))
+    }
+}
+
+fn sign_username(username: &str, secret: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(username.as_bytes());
+    BASE64.encode(mac.finalize().into_bytes())
+}
+
+fn unix_now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn peer_id() -> PeerId {
+        PeerId::from_string("12D3KooWTestPeer".to_string())
+    }
+    
+    #[test]
+    fn test_credential_roundtrip() {
+        let secret = b"relay-shared-secret";
+        let cred = TurnTokenIssuer::generate_credential(&peer_id(), 3600, secret);
+        
+        assert!(cred.username.ends_with(":12D3KooWTestPeer"));
+        assert!(TurnTokenIssuer::verify_credential(&cred, secret));
+        assert!(!TurnTokenIssuer::verify_credential(&cred, b"wrong-secret"));
+        
+        // Extending the expiry invalidates the signature
+        let mut tampered = cred.clone();
+        tampered.expires_at += 3600;
+        tampered.username = format!("{}:12D3KooWTestPeer", tampered.expires_at);
+        assert!(!TurnTokenIssuer::verify_credential(&tampered, secret));
+    }
+    
+    #[test]
+    fn test_credential_expires() {
+        let secret = b"relay-shared-secret";
+        let now = 1_700_000_000;
+        let cred = TurnTokenIssuer::generate_credential_at(&peer_id(), 60, secret, now);
+        
+        assert!(TurnTokenIssuer::verify_credential_at(&cred, secret, now + 59));
+        assert!(!TurnTokenIssuer::verify_credential_at(&cred, secret, now + 60));
+        assert!(!TurnTokenIssuer::verify_credential_at(&cred, secret, now + 3600));
+        assert!(matches!(cred.ensure_valid(), Err(VoiceError::TurnTokenExpired)));
+        
+        assert_eq!(TurnTokenIssuer::expected_password_at(&cred.username, secret, now), Some(cred.password));
+        assert_eq!(TurnTokenIssuer::expected_password_at(&cred.username, secret, now + 60), None);
+        assert_eq!(TurnTokenIssuer::expected_password_at("12D3KooWTestPeer", secret, now), None);
+    }
+}
@@ -4,7 +4,7 @@
 //! running simultaneously, testing peer discovery, messaging,
 //! reconnection, and failure scenarios.
 
-use otter_crypto::{CryptoSession, PFSSession};
+use otter_crypto::{CryptoSession, KdfAlgorithm, PFSSession};
 use otter_identity::{Identity, PublicIdentity, trust::TrustStore};
 use otter_storage::{FileStorage, Storage};
 use std::collections::HashMap;
@@ -13,6 +13,7 @@ use std::time::Duration;
 use tempfile::TempDir;
 use tokio::sync::{mpsc, Mutex, RwLock};
 use tokio::time;
+use x25519_dalek::PublicKey as X25519PublicKey;
 
 /// Test peer that can be run in a separate task
 struct TestPeer {
@@ -125,7 +126,7 @@ impl TestPeer {
                     let peers = self.peers.lock().await;
                     if let Some(recipient_public) = peers.get(&to) {
                         // Create encrypted session
-                        let mut session = CryptoSession::new(&self.identity, recipient_public).unwrap();
+                        let mut session = CryptoSession::new(&self.identity, recipient_public, KdfAlgorithm::default()).unwrap();
                         
                         // Encrypt message
                         let plaintext = content.as_bytes();
@@ -176,7 +177,7 @@ impl TestPeer {
             .ok_or_else(|| "Unknown sender".to_string())?;
         
         // Create session and decrypt
-        let mut session = CryptoSession::new(&self.identity, sender_public)
+        let mut session = CryptoSession::new(&self.identity, sender_public, KdfAlgorithm::default())
             .map_err(|e| format!("Session error: {}", e))?;
         
         let plaintext = session.decrypt(encrypted)
@@ -384,13 +385,13 @@ async fn test_encrypted_message_counter() {
     let bob_public = PublicIdentity::from_identity(&bob);
     
     // Alice creates session and encrypts messages
-    let mut alice_session = CryptoSession::new(&alice, &bob_public).unwrap();
+    let mut alice_session = CryptoSession::new(&alice, &bob_public, KdfAlgorithm::default()).unwrap();
     
     let msg1 = alice_session.encrypt(b"Message 1", None).unwrap();
     let msg2 = alice_session.encrypt(b"Message 2", None).unwrap();
     
     // Bob decrypts in order
-    let mut bob_session = CryptoSession::new(&bob, &alice_public).unwrap();
+    let mut bob_session = CryptoSession::new(&bob, &alice_public, KdfAlgorithm::default()).unwrap();
     
     let plain1 = bob_session.decrypt(&msg1).unwrap();
     assert_eq!(plain1, b"Message 1");
@@ -422,7 +423,7 @@ async fn test_pfs_session_forward_secrecy() {
         &alice,
         &bob_public,
         alice_ephemeral1.clone(),
-        &bob_ephemeral1.public_key(),
+        &bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral1)).unwrap(),
         true,
     )
     .unwrap();
@@ -431,7 +432,7 @@ async fn test_pfs_session_forward_secrecy() {
         &bob,
         &alice_public,
         bob_ephemeral1,
-        &alice_ephemeral1.public_key(),
+        &alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral1)).unwrap(),
         false,
     )
     .unwrap();
@@ -449,7 +450,7 @@ async fn test_pfs_session_forward_secrecy() {
         &alice,
         &bob_public,
         alice_ephemeral2.clone(),
-        &bob_ephemeral2.public_key(),
+        &bob.attest_ephemeral(&X25519PublicKey::from(&bob_ephemeral2)).unwrap(),
         true,
     )
     .unwrap();
@@ -458,7 +459,7 @@ async fn test_pfs_session_forward_secrecy() {
         &bob,
         &alice_public,
         bob_ephemeral2,
-        &alice_ephemeral2.public_key(),
+        &alice.attest_ephemeral(&X25519PublicKey::from(&alice_ephemeral2)).unwrap(),
         false,
     )
     .unwrap();